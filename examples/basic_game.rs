@@ -0,0 +1,233 @@
+//! A minimal game loop: a player hierarchy spawned from a bundle, ordered
+//! update systems, a fixed-timestep physics phase and a shutdown phase that
+//! despawns everything. Run with `cargo run --example basic_game`.
+
+use ecs::prelude::*;
+
+pub const FIXED_STEP: f32 = 1.0 / 60.0;
+
+pub struct Startup;
+
+impl SchedulePhase for Startup {
+    const PHASE: &'static str = "startup";
+}
+
+pub struct Update;
+
+impl SchedulePhase for Update {
+    const PHASE: &'static str = "update";
+}
+
+pub struct FixedUpdate;
+
+impl SchedulePhase for FixedUpdate {
+    const PHASE: &'static str = "fixed_update";
+}
+
+pub struct PostUpdate;
+
+impl SchedulePhase for PostUpdate {
+    const PHASE: &'static str = "post_update";
+}
+
+pub struct Shutdown;
+
+impl SchedulePhase for Shutdown {
+    const PHASE: &'static str = "shutdown";
+}
+
+pub struct Main;
+
+impl ScheduleLabel for Main {
+    const LABEL: &'static str = "main";
+}
+
+#[derive(Debug)]
+pub struct Player {
+    pub health: u32,
+}
+
+impl Component for Player {}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Position {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Component for Position {}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Velocity {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Component for Velocity {}
+
+pub struct Weapon;
+
+impl Component for Weapon {}
+
+pub struct Shield;
+
+impl Component for Shield {}
+
+/// Added to the children of every player by an observer.
+pub struct Equipped;
+
+impl Component for Equipped {}
+
+#[derive(Default)]
+pub struct Time {
+    pub delta: f32,
+    pub elapsed: f32,
+    accumulator: f32,
+}
+
+impl Resource for Time {}
+
+#[derive(Default)]
+pub struct Input {
+    pub move_x: f32,
+}
+
+impl Resource for Input {}
+
+#[derive(Default)]
+pub struct GameLog {
+    pub lines: Vec<String>,
+    pub equipped: usize,
+    pub deleted: usize,
+    pub fixed_steps: usize,
+}
+
+impl Resource for GameLog {}
+
+fn spawn_player(actions: &mut Actions) {
+    let player = CreateEntity::new()
+        .with_bundle((
+            Player { health: 100 },
+            Position::default(),
+            Velocity::default(),
+        ))
+        .with_child(CreateEntity::new().with(Weapon))
+        .with_child(CreateEntity::new().with(Shield));
+
+    actions.add(player);
+}
+
+fn equip_children(
+    players: &[Entity],
+    entities: &Entities,
+    actions: &mut Actions,
+    log: &mut GameLog,
+) {
+    for player in players {
+        for child in entities.children(*player, false) {
+            actions.add(AddComponent::new(child, Equipped));
+            log.equipped += 1;
+        }
+    }
+}
+
+fn read_input(time: &Time, input: &mut Input) {
+    input.move_x = if time.elapsed < 0.5 { 1.0 } else { -1.0 };
+}
+
+fn steer(input: &Input, players: Query<&mut Velocity, With<Player>>) {
+    for mut velocity in players {
+        velocity.x = input.move_x * 2.0;
+    }
+}
+
+fn physics(mut bodies: Query<(&mut Position, &Velocity)>) {
+    bodies.par_for_each_mut(256, |(mut position, velocity)| {
+        position.x += velocity.x * FIXED_STEP;
+        position.y += velocity.y * FIXED_STEP;
+    });
+}
+
+fn count_step(log: &mut GameLog) {
+    log.fixed_steps += 1;
+}
+
+fn report(players: Query<(&Player, &Position)>, log: &mut GameLog) {
+    for (player, position) in players {
+        log.lines.push(format!(
+            "player hp {} at ({:.2}, {:.2})",
+            player.health, position.x, position.y
+        ));
+    }
+}
+
+fn despawn_players(players: Query<Entity, With<Player>>, actions: &mut Actions) {
+    for player in players {
+        actions.add(DeleteEntity::new(player));
+    }
+}
+
+fn count_deleted(entities: &[Entity], log: &mut GameLog) {
+    log.deleted += entities.len();
+}
+
+pub fn setup() -> World {
+    let mut world = World::new();
+    world.add_resource(Time::default());
+    world.add_resource(Input::default());
+    world.add_resource(GameLog::default());
+
+    world.add_system(Startup, Main, spawn_player);
+    world.add_system(Update, Main, steer.before(read_input));
+    world.add_system(FixedUpdate, Main, physics.after(count_step));
+    world.add_system(PostUpdate, Main, report);
+    world.add_system(Shutdown, Main, despawn_players);
+
+    world.add_observers(Observers::<AddComponent<Player>>::new().add_system(equip_children));
+    world.add_observers(Observers::<DeleteEntity>::new().add_system(count_deleted));
+
+    world.init();
+    world.run::<Startup>();
+    world
+}
+
+/// Runs one frame, stepping physics as many times as `delta` covers.
+pub fn frame(world: &mut World, delta: f32) {
+    let mut time = world.resource_mut::<Time>();
+    time.delta = delta;
+    time.elapsed += delta;
+    time.accumulator += delta;
+    drop(time);
+
+    world.run::<Update>();
+
+    while world.resource::<Time>().accumulator >= FIXED_STEP {
+        world.run::<FixedUpdate>();
+        world.resource_mut::<Time>().accumulator -= FIXED_STEP;
+    }
+
+    world.run::<PostUpdate>();
+}
+
+pub fn shutdown(world: &mut World) {
+    world.run::<Shutdown>();
+}
+
+fn main() {
+    let mut world = setup();
+
+    for _ in 0..4 {
+        frame(&mut world, 0.25);
+    }
+
+    shutdown(&mut world);
+
+    let log = world.resource::<GameLog>();
+    for line in &log.lines {
+        println!("{}", line);
+    }
+    println!(
+        "equipped {} children, ran {} fixed steps, deleted {} entities",
+        log.equipped, log.fixed_steps, log.deleted
+    );
+}
@@ -1,19 +1,32 @@
 use crate::{
-    core::{ComponentId, Entity},
+    core::{ComponentId, ComponentMask, Entity},
     storage::{
+        bits::BitSet,
         sparse::{SparseMap, SparseSet},
         table::TableId,
     },
 };
 use std::{
-    collections::HashSet,
     hash::{Hash, Hasher},
+    sync::atomic::{AtomicU64, Ordering},
 };
 
+/// Source of archetype generations, shared by every `Archetypes` so a
+/// replaced set never repeats a generation.
+static GENERATION: AtomicU64 = AtomicU64::new(1);
+
+fn next_generation() -> u64 {
+    GENERATION.fetch_add(1, Ordering::Relaxed)
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct ArchetypeId(u64);
 
 impl ArchetypeId {
+    /// Stands in for "no archetype", e.g. the source of a newly created
+    /// entity. Not produced by `new` for any component set in practice.
+    pub const NONE: ArchetypeId = ArchetypeId(u64::MAX);
+
     pub fn new(components: &[ComponentId]) -> Self {
         let mut components = components.iter().copied().collect::<Vec<_>>();
         components.sort();
@@ -48,10 +61,12 @@ impl Into<TableId> for &ArchetypeId {
     }
 }
 
+#[derive(Clone)]
 pub struct Archetype {
     id: ArchetypeId,
     entities: SparseMap<usize, Entity>,
     components: Box<[ComponentId]>,
+    mask: ComponentMask,
 }
 
 impl Archetype {
@@ -59,13 +74,16 @@ impl Archetype {
         Self {
             id,
             entities: SparseMap::new(),
+            mask: ComponentMask::from_ids(&components),
             components: components.into_boxed_slice(),
         }
     }
 
     pub fn added(&self, component: ComponentId) -> Vec<ComponentId> {
         let mut components = self.components.to_vec();
-        components.push(component);
+        if !components.contains(&component) {
+            components.push(component);
+        }
         components
     }
 
@@ -86,12 +104,32 @@ impl Archetype {
     pub fn components(&self) -> &[ComponentId] {
         &self.components
     }
+
+    pub fn mask(&self) -> &ComponentMask {
+        &self.mask
+    }
+
+    pub fn allocated_bytes(&self) -> usize {
+        self.entities.allocated_bytes() + self.components.len() * std::mem::size_of::<ComponentId>()
+    }
 }
 
+#[derive(Clone)]
 pub struct Archetypes {
     archetypes: SparseMap<ArchetypeId, Archetype>,
-    entities: SparseSet<ArchetypeId>,
-    components: SparseMap<ComponentId, HashSet<ArchetypeId>>,
+    entities: SparseSet<(Entity, ArchetypeId)>,
+    /// Archetypes containing each component, in creation order so scans
+    /// visit archetypes in the same order on every run.
+    components: SparseMap<ComponentId, Vec<ArchetypeId>>,
+    /// Components that were part of an archetype at some point, even if
+    /// it was removed since.
+    seen: BitSet,
+    /// Component mask of each entity's archetype, indexed by entity id, so
+    /// `has` is one bit test.
+    masks: Vec<Option<(Entity, ComponentMask)>>,
+    generation: u64,
+    moves: usize,
+    batch_moves: usize,
 }
 
 impl Archetypes {
@@ -100,11 +138,37 @@ impl Archetypes {
             archetypes: SparseMap::new(),
             entities: SparseSet::new(),
             components: SparseMap::new(),
+            seen: BitSet::new(),
+            masks: Vec::new(),
+            generation: next_generation(),
+            moves: 0,
+            batch_moves: 0,
         }
     }
 
+    /// The entity's archetype. Returns `None` for an entity whose id slot is
+    /// held by another generation.
     pub fn archetype_id(&self, entity: Entity) -> Option<&ArchetypeId> {
-        self.entities.get(entity.id())
+        self.entities
+            .get(entity.id())
+            .filter(|(stored, _)| *stored == entity)
+            .map(|(_, id)| id)
+    }
+
+    /// Components of the entity's archetype. `None` for dead entities.
+    pub fn mask(&self, entity: Entity) -> Option<&ComponentMask> {
+        match self.masks.get(entity.id()) {
+            Some(Some((stored, mask))) if *stored == entity => Some(mask),
+            _ => None,
+        }
+    }
+
+    fn set_mask(&mut self, entity: Entity, mask: ComponentMask) {
+        if entity.id() >= self.masks.len() {
+            self.masks.resize(entity.id() + 1, None);
+        }
+
+        self.masks[entity.id()] = Some((entity, mask));
     }
 
     pub fn archetype(&self, archetype_id: &ArchetypeId) -> Option<&Archetype> {
@@ -112,30 +176,14 @@ impl Archetypes {
     }
 
     pub fn entity_archetype(&self, entity: Entity) -> Option<&Archetype> {
-        self.entities
-            .get(entity.id())
+        self.archetype_id(entity)
             .and_then(|id| self.archetypes.get(id))
     }
 
     pub fn entities(&self, components: &[ComponentId], without: &[ComponentId]) -> Vec<&Entity> {
-        let mut entities = vec![];
-
-        for component_id in components {
-            if let Some(archetypes) = self.components.get(component_id) {
-                for achetype in archetypes {
-                    if let Some(archetype) = self.archetypes.get(achetype) {
-                        let has = components
-                            .iter()
-                            .all(|c| archetype.components().contains(c));
-                        if has && without.iter().all(|c| !archetype.components().contains(c)) {
-                            entities.extend(archetype.entities());
-                        }
-                    }
-                }
-            }
-        }
-
-        entities
+        self.matching(components, without)
+            .flat_map(|archetype| archetype.entities())
+            .collect()
     }
 
     pub fn archetypes(
@@ -143,36 +191,59 @@ impl Archetypes {
         components: &[ComponentId],
         without: &[ComponentId],
     ) -> Vec<&ArchetypeId> {
-        let mut results = vec![];
-
-        for component_id in components {
-            if let Some(archetypes) = self.components.get(component_id) {
-                for achetype in archetypes {
-                    if let Some(archetype) = self.archetypes.get(achetype) {
-                        let has = components
-                            .iter()
-                            .all(|c| archetype.components().contains(c));
-                        if has && without.iter().all(|c| !archetype.components().contains(c)) {
-                            results.push(archetype.id());
-                        }
-                    }
-                }
-            }
-        }
+        self.matching(components, without)
+            .map(|archetype| archetype.id())
+            .collect()
+    }
 
-        results
+    /// Archetypes that have every component in `components` and none in
+    /// `without`, each once. Only the archetypes of the component in the
+    /// fewest of them are checked. An empty `components` list matches every
+    /// archetype, including the empty one.
+    pub fn matching<'a: 'b, 'b>(
+        &'a self,
+        components: &'b [ComponentId],
+        without: &'b [ComponentId],
+    ) -> Box<dyn Iterator<Item = &'a Archetype> + 'b> {
+        let filter = move |archetype: &&Archetype| {
+            components
+                .iter()
+                .all(|c| archetype.components().contains(c))
+                && without.iter().all(|c| !archetype.components().contains(c))
+        };
+
+        let smallest = components
+            .iter()
+            .map(|c| self.components.get(c).map_or(&[][..], Vec::as_slice))
+            .min_by_key(|archetypes| archetypes.len());
+
+        match smallest {
+            Some(archetypes) => Box::new(
+                archetypes
+                    .iter()
+                    .filter_map(|id| self.archetypes.get(id))
+                    .filter(filter),
+            ),
+            None => Box::new(self.archetypes.values().iter().filter(filter)),
+        }
     }
 
+    /// Matching archetypes of `entities`, each once, in the order the
+    /// entities are listed.
     pub fn entity_archetypes(
         &self,
         components: &[ComponentId],
         without: &[ComponentId],
         entities: &[Entity],
     ) -> Vec<&ArchetypeId> {
-        let mut results = vec![];
+        let mut results: Vec<&ArchetypeId> = vec![];
 
         for entity in entities {
             if let Some(archetype) = self.entity_archetype(*entity) {
+                if results.contains(&archetype.id()) {
+                    continue;
+                }
+
                 let has = components
                     .iter()
                     .all(|c| archetype.components().contains(c));
@@ -187,12 +258,14 @@ impl Archetypes {
 
     pub fn add_entity(&mut self, entity: Entity) -> ArchetypeId {
         let id = ArchetypeId::new(&[]);
-        self.entities.insert(entity.id(), id);
+        self.entities.insert(entity.id(), (entity, id));
+        self.set_mask(entity, ComponentMask::new());
 
         if let Some(archetype) = self.archetypes.get_mut(&id) {
             archetype.entities.insert(entity.id(), entity);
         } else {
             self.archetypes.insert(id, Archetype::new(id, Vec::new()));
+            self.generation = next_generation();
             self.archetypes
                 .get_mut(&id)
                 .unwrap()
@@ -203,34 +276,33 @@ impl Archetypes {
         id
     }
 
-    pub fn add_component(&mut self, entity: Entity, component: ComponentId) -> Option<ArchetypeId> {
-        if let Some(id) = self.entities.get(entity.id()).cloned() {
-            let components = {
-                let archetype = self.archetypes.get_mut(&id).unwrap();
-                archetype.entities.remove(&entity.id());
-                archetype.added(component)
-            };
-
-            let new_id = ArchetypeId::new(&components);
+    /// Adds every entity in `entities` to the empty archetype with a single
+    /// archetype lookup.
+    pub fn add_entities(&mut self, entities: &[Entity]) -> ArchetypeId {
+        let id = ArchetypeId::new(&[]);
+        if !self.archetypes.contains(&id) {
+            self.archetypes.insert(id, Archetype::new(id, Vec::new()));
+            self.generation = next_generation();
+        }
 
-            for component in components.iter() {
-                self.add_component_archetype(*component, new_id);
-            }
+        self.entities.reserve(entities.len());
+        let archetype = self.archetypes.get_mut(&id).unwrap();
+        archetype.entities.reserve(entities.len());
+        for entity in entities {
+            self.entities.insert(entity.id(), (*entity, id));
+            archetype.entities.insert(entity.id(), *entity);
+        }
 
-            if let Some(archetype) = self.archetypes.get_mut(&new_id) {
-                archetype.entities.insert(entity.id(), entity);
-            } else {
-                let mut archetype = Archetype::new(new_id, components);
-                archetype.entities.insert(entity.id(), entity);
-                self.archetypes.insert(new_id, archetype);
-            }
+        for entity in entities {
+            self.set_mask(*entity, ComponentMask::new());
+        }
 
-            self.entities.insert(entity.id(), new_id);
+        id
+    }
 
-            Some(new_id)
-        } else {
-            None
-        }
+    pub fn add_component(&mut self, entity: Entity, component: ComponentId) -> Option<ArchetypeId> {
+        let components = self.entity_archetype(entity)?.added(component);
+        self.move_entity(entity, components)
     }
 
     pub fn remove_component(
@@ -238,60 +310,252 @@ impl Archetypes {
         entity: Entity,
         component: ComponentId,
     ) -> Option<ArchetypeId> {
-        if let Some(id) = self.entities.get(entity.id()).cloned() {
-            let components = {
-                let archetype = self.archetypes.get_mut(&id).unwrap();
-                archetype.entities.remove(&entity.id());
-                archetype.removed(component)
-            };
-            let new_id = ArchetypeId::new(&components);
+        let components = self.entity_archetype(entity)?.removed(component);
+        self.move_entity(entity, components)
+    }
 
+    /// Moves an entity straight into the archetype made of `components`,
+    /// creating it if needed, without visiting any intermediate archetype.
+    pub fn move_entity(
+        &mut self,
+        entity: Entity,
+        components: Vec<ComponentId>,
+    ) -> Option<ArchetypeId> {
+        let id = self.archetype_id(entity).copied()?;
+        let new_id = ArchetypeId::new(&components);
+
+        if new_id == id {
+            return Some(id);
+        }
+
+        self.archetypes
+            .get_mut(&id)
+            .unwrap()
+            .entities
+            .remove(&entity.id());
+
+        if let Some(archetype) = self.archetypes.get_mut(&new_id) {
+            archetype.entities.insert(entity.id(), entity);
+        } else {
             for component in components.iter() {
                 self.add_component_archetype(*component, new_id);
             }
 
-            if let Some(archetype) = self.archetypes.get_mut(&new_id) {
-                archetype.entities.insert(entity.id(), entity);
-            } else {
-                let mut archetype = Archetype::new(new_id, components);
-                archetype.entities.insert(entity.id(), entity);
-                self.archetypes.insert(new_id, archetype);
+            let mut archetype = Archetype::new(new_id, components);
+            archetype.entities.insert(entity.id(), entity);
+            self.archetypes.insert(new_id, archetype);
+            self.generation = next_generation();
+        }
+
+        let mask = self.archetypes.get(&new_id).unwrap().mask.clone();
+        self.set_mask(entity, mask);
+        self.entities.insert(entity.id(), (entity, new_id));
+        self.moves += 1;
+
+        Some(new_id)
+    }
+
+    /// Moves every entity of `from` into the archetype made of `components`
+    /// in one step. Returns the destination and the entities that moved.
+    pub fn move_archetype(
+        &mut self,
+        from: ArchetypeId,
+        components: Vec<ComponentId>,
+    ) -> Option<(ArchetypeId, Vec<Entity>)> {
+        let new_id = ArchetypeId::new(&components);
+        let archetype = self.archetypes.get_mut(&from)?;
+        if new_id == from {
+            return Some((from, archetype.entities().to_vec()));
+        }
+
+        let entities = archetype.entities().to_vec();
+        archetype.entities.clear();
+
+        let archetype = match self.archetypes.get_mut(&new_id) {
+            Some(archetype) => archetype,
+            None => {
+                for component in components.iter() {
+                    self.add_component_archetype(*component, new_id);
+                }
+
+                self.archetypes
+                    .insert(new_id, Archetype::new(new_id, components));
+                self.archetypes.get_mut(&new_id).unwrap()
+            }
+        };
+
+        for entity in &entities {
+            archetype.entities.insert(entity.id(), *entity);
+            self.entities.insert(entity.id(), (*entity, new_id));
+        }
+
+        let mask = archetype.mask.clone();
+        for entity in &entities {
+            self.set_mask(*entity, mask.clone());
+        }
+
+        self.moves += entities.len();
+        self.batch_moves += 1;
+
+        Some((new_id, entities))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Archetype> {
+        self.archetypes.values().iter()
+    }
+
+    /// Drops repeated component ids from an archetype and from the
+    /// component lookup. Returns false if there were none.
+    pub(crate) fn dedup_components(&mut self, id: &ArchetypeId) -> bool {
+        let Some(archetype) = self.archetypes.get_mut(id) else {
+            return false;
+        };
+
+        let mut components = Vec::with_capacity(archetype.components.len());
+        for component in archetype.components.iter() {
+            if !components.contains(component) {
+                components.push(*component);
             }
+        }
 
-            self.entities.insert(entity.id(), new_id);
+        if components.len() == archetype.components.len() {
+            return false;
+        }
 
-            Some(new_id)
-        } else {
-            None
+        archetype.components = components.into_boxed_slice();
+        self.generation = next_generation();
+        for component in archetype.components.iter() {
+            if let Some(archetypes) = self.components.get_mut(component) {
+                let mut seen = false;
+                archetypes.retain(|other| other != id || !std::mem::replace(&mut seen, true));
+            }
         }
+
+        true
     }
 
-    pub fn delete_entity(&mut self, entity: Entity) -> Option<ArchetypeId> {
-        if let Some(id) = self.entities.remove(entity.id()) {
-            let archetype = self.archetypes.get_mut(&id).unwrap();
-            archetype.entities.remove(&entity.id());
-            Some(id)
-        } else {
-            None
+    /// Lists `component` a second time in an archetype, for
+    /// `testing::corrupt`.
+    pub(crate) fn repeat_component(&mut self, id: &ArchetypeId, component: ComponentId) {
+        if let Some(archetype) = self.archetypes.get_mut(id) {
+            let mut components = archetype.components.to_vec();
+            components.push(component);
+            archetype.components = components.into_boxed_slice();
+            self.add_component_archetype(component, *id);
         }
     }
 
-    pub fn has(&self, entity: Entity, component: ComponentId) -> bool {
-        if let Some(id) = self.entities.get(entity.id()) {
-            let archetype = self.archetypes.get(id).unwrap();
-            archetype.components().contains(&component)
-        } else {
-            false
+    /// Empties the mask of `entity`, for `testing::corrupt`.
+    pub(crate) fn clear_mask(&mut self, entity: Entity) {
+        if let Some(Some((_, mask))) = self.masks.get_mut(entity.id()) {
+            *mask = ComponentMask::new();
         }
     }
 
+    /// Copies the mask of `entity`'s archetype into its own. Returns false
+    /// if the entity has no archetype.
+    pub(crate) fn resync_mask(&mut self, entity: Entity) -> bool {
+        let Some(mask) = self.entity_archetype(entity).map(|a| a.mask.clone()) else {
+            return false;
+        };
+
+        self.masks[entity.id()] = Some((entity, mask));
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.archetypes.len()
+    }
+
+    /// Changes whenever an archetype is created, removed or has its
+    /// components changed. Unique across `Archetypes`, so state cached
+    /// against one generation is never mistaken as current.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Removes an archetype that holds no entities, keeping the order of the
+    /// others. Returns `None` if it doesn't exist or still has entities.
+    pub fn remove(&mut self, id: &ArchetypeId) -> Option<Archetype> {
+        if !self.archetypes.get(id)?.entities.is_empty() {
+            return None;
+        }
+
+        let archetype = self.archetypes.shift_remove(id)?;
+        self.generation = next_generation();
+        for component in archetype.components() {
+            if let Some(archetypes) = self.components.get_mut(component) {
+                archetypes.retain(|other| other != id);
+            }
+        }
+
+        Some(archetype)
+    }
+
+    /// Heap memory of the archetype maps, including each archetype's entity
+    /// list.
+    pub fn allocated_bytes(&self) -> usize {
+        let sets = self
+            .components
+            .values()
+            .iter()
+            .map(|set| set.capacity() * std::mem::size_of::<ArchetypeId>())
+            .sum::<usize>();
+        let archetypes = self
+            .archetypes
+            .values()
+            .iter()
+            .map(|archetype| archetype.allocated_bytes())
+            .sum::<usize>();
+
+        self.archetypes.allocated_bytes()
+            + self.masks.capacity() * std::mem::size_of::<Option<(Entity, ComponentMask)>>()
+            + self.entities.allocated_bytes()
+            + self.components.allocated_bytes()
+            + sets
+            + archetypes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.archetypes.is_empty()
+    }
+
+    /// Total number of archetype transitions performed so far.
+    pub fn moves(&self) -> usize {
+        self.moves
+    }
+
+    /// Number of whole-archetype moves done by `move_archetype`.
+    pub fn batch_moves(&self) -> usize {
+        self.batch_moves
+    }
+
+    pub fn delete_entity(&mut self, entity: Entity) -> Option<ArchetypeId> {
+        let id = self.archetype_id(entity).copied()?;
+        self.entities.remove(entity.id());
+        self.masks[entity.id()] = None;
+
+        let archetype = self.archetypes.get_mut(&id).unwrap();
+        archetype.entities.remove(&entity.id());
+        Some(id)
+    }
+
+    pub fn has(&self, entity: Entity, component: ComponentId) -> bool {
+        self.mask(entity)
+            .is_some_and(|mask| mask.contains(component))
+    }
+
+    /// True if `component` has been part of an archetype.
+    pub fn seen(&self, component: ComponentId) -> bool {
+        self.seen.get(component.id())
+    }
+
     fn add_component_archetype(&mut self, component: ComponentId, id: ArchetypeId) {
+        self.seen.set(component.id());
         if let Some(archetypes) = self.components.get_mut(&component) {
-            archetypes.insert(id);
+            archetypes.push(id);
         } else {
-            let mut archetypes = HashSet::new();
-            archetypes.insert(id);
-            self.components.insert(component, archetypes);
+            self.components.insert(component, vec![id]);
         }
     }
 }
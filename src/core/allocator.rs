@@ -1,3 +1,5 @@
+use std::collections::VecDeque;
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct GenId {
     id: usize,
@@ -18,38 +20,98 @@ impl GenId {
     }
 }
 
+/// How freed ids are handed out again.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AllocMode {
+    /// The most recently freed id is reused first.
+    #[default]
+    Standard,
+    /// Freed ids are reused in the order they were freed, and each reuse
+    /// bumps the generation by exactly one. Fresh ids are always the next
+    /// unused index, so the same sequence of calls yields the same ids.
+    Deterministic,
+}
+
+#[derive(Clone)]
 pub struct IdAllocator {
     next_id: usize,
-    free: Vec<usize>,
+    free: VecDeque<usize>,
     generations: Vec<u32>,
+    alive: Vec<bool>,
+    mode: AllocMode,
 }
 
 impl IdAllocator {
     pub fn new() -> Self {
         Self {
             next_id: 0,
-            free: Vec::new(),
+            free: VecDeque::new(),
             generations: Vec::new(),
+            alive: Vec::new(),
+            mode: AllocMode::Standard,
         }
     }
 
+    pub fn mode(&self) -> AllocMode {
+        self.mode
+    }
+
+    /// Changes the recycling order. Ids already on the free list keep their
+    /// relative order.
+    pub fn set_mode(&mut self, mode: AllocMode) {
+        self.mode = mode;
+    }
+
     pub fn allocate(&mut self) -> GenId {
-        let id = if let Some(id) = self.free.pop() {
+        let free = match self.mode {
+            AllocMode::Standard => self.free.pop_back(),
+            AllocMode::Deterministic => self.free.pop_front(),
+        };
+
+        let id = if let Some(id) = free {
             id
         } else {
             let id = self.next_id;
             self.next_id += 1;
             self.generations.push(0);
+            self.alive.push(false);
             id
         };
 
+        self.alive[id] = true;
         GenId::new(id, self.generations[id])
     }
 
+    /// Marks exactly `id` as alive. Fails if the slot is alive or `id` is
+    /// older than the slot's current generation.
+    pub fn allocate_at(&mut self, id: GenId) -> bool {
+        let index = id.id();
+        while self.next_id <= index {
+            self.free.push_back(self.next_id);
+            self.generations.push(0);
+            self.alive.push(false);
+            self.next_id += 1;
+        }
+
+        if self.alive[index] || id.generation() < self.generations[index] {
+            return false;
+        }
+
+        self.free.retain(|free| *free != index);
+        self.generations[index] = id.generation();
+        self.alive[index] = true;
+        true
+    }
+
     pub fn free(&mut self, id: GenId) {
+        if !self.is_alive(id) {
+            return;
+        }
+
         let index = id.id();
         self.generations[index] += 1;
-        self.free.push(index);
+        self.alive[index] = false;
+        self.free.push_back(index);
     }
 
     pub fn free_list(&mut self, ids: impl Iterator<Item = GenId>) {
@@ -59,30 +121,17 @@ impl IdAllocator {
     }
 
     pub fn reserve(&mut self, amount: usize) {
-        let new_capacity = self.next_id + amount;
-
-        if self.generations.capacity() < new_capacity {
-            self.generations
-                .reserve(new_capacity - self.generations.capacity());
-        }
-
-        if self.free.capacity() < new_capacity {
-            self.free.reserve(new_capacity - self.free.capacity());
-        }
-
-        for index in new_capacity..self.free.len() {
-            self.free.push(index);
-        }
-
-        self.next_id = new_capacity;
+        self.generations.reserve(amount);
+        self.alive.reserve(amount);
     }
 
     pub fn iter(&self) -> impl Iterator<Item = GenId> + '_ {
         self.generations
             .iter()
+            .zip(self.alive.iter())
             .enumerate()
-            .filter_map(|(id, generation)| {
-                if *generation != 0 {
+            .filter_map(|(id, (generation, alive))| {
+                if *alive {
                     Some(GenId::new(id, *generation))
                 } else {
                     None
@@ -95,20 +144,60 @@ impl IdAllocator {
     }
 
     pub fn is_alive(&self, id: GenId) -> bool {
-        id.id() < self.next_id && self.generations[id.id()] == id.generation()
+        id.id() < self.next_id
+            && self.alive[id.id()]
+            && self.generations[id.id()] == id.generation()
+    }
+
+    /// True if any generation of `index` is alive.
+    pub fn is_id_alive(&self, index: usize) -> bool {
+        index < self.next_id && self.alive[index]
     }
 
     pub fn is_empty(&self) -> bool {
-        self.next_id == 0
+        self.len() == 0
     }
 
     pub fn len(&self) -> usize {
         self.next_id - self.free.len()
     }
 
+    /// FNV-1a hash of the mode, free list order, generations and liveness.
+    /// Stable across runs and platforms, so two replays can be compared.
+    pub fn state_hash(&self) -> u64 {
+        const PRIME: u64 = 0x100000001b3;
+        let mut hash: u64 = 0xcbf29ce484222325;
+        let mut write = |value: u64| {
+            for byte in value.to_le_bytes() {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(PRIME);
+            }
+        };
+
+        write(self.mode as u64);
+        write(self.next_id as u64);
+        write(self.free.len() as u64);
+        for id in &self.free {
+            write(*id as u64);
+        }
+        for (generation, alive) in self.generations.iter().zip(&self.alive) {
+            write(*generation as u64);
+            write(*alive as u64);
+        }
+
+        hash
+    }
+
+    pub fn allocated_bytes(&self) -> usize {
+        self.free.capacity() * std::mem::size_of::<usize>()
+            + self.generations.capacity() * std::mem::size_of::<u32>()
+            + self.alive.capacity() * std::mem::size_of::<bool>()
+    }
+
     pub fn clear(&mut self) {
         self.next_id = 0;
         self.free.clear();
         self.generations.clear();
+        self.alive.clear();
     }
 }
@@ -1,8 +1,19 @@
-use std::{alloc::Layout, any::TypeId, collections::HashMap, fmt::Debug};
+use std::{
+    alloc::Layout,
+    any::TypeId,
+    collections::HashMap,
+    fmt::Debug,
+    marker::PhantomData,
+    sync::atomic::{AtomicU32, Ordering},
+};
 
-use crate::storage::blob::Blob;
+use crate::storage::{blob::Blob, sparse::SparseKey};
 
-pub trait Component: 'static {}
+pub trait Component: Send + Sync + 'static {
+    /// Name the component is saved under, kept when the type is renamed or
+    /// moved. Without one, snapshots and dumps use the type name.
+    const STABLE_ID: Option<&'static str> = None;
+}
 
 #[derive(Debug, Copy, Clone, Hash, Ord, PartialOrd, Eq, PartialEq)]
 pub struct ComponentId(usize);
@@ -29,6 +40,55 @@ impl From<ComponentId> for usize {
     }
 }
 
+impl SparseKey for ComponentId {
+    fn sparse_index(&self) -> usize {
+        self.0
+    }
+}
+
+/// A `ComponentId` resolved once for `C`, so hot paths skip the `TypeId`
+/// lookup. Only valid for the registry it came from.
+pub struct ComponentHandle<C: Component> {
+    id: ComponentId,
+    registry: u32,
+    _marker: PhantomData<fn() -> C>,
+}
+
+impl<C: Component> ComponentHandle<C> {
+    pub fn id(&self) -> ComponentId {
+        self.id
+    }
+
+    /// Id of the `Components` registry the handle was created from.
+    pub fn registry(&self) -> u32 {
+        self.registry
+    }
+}
+
+impl<C: Component> Clone for ComponentHandle<C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<C: Component> Copy for ComponentHandle<C> {}
+
+impl<C: Component> Debug for ComponentHandle<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ComponentHandle")
+            .field("component", &std::any::type_name::<C>())
+            .field("id", &self.id)
+            .field("registry", &self.registry)
+            .finish()
+    }
+}
+
+impl<C: Component> From<ComponentHandle<C>> for ComponentId {
+    fn from(handle: ComponentHandle<C>) -> Self {
+        handle.id
+    }
+}
+
 impl From<&ComponentId> for usize {
     fn from(id: &ComponentId) -> Self {
         id.0
@@ -55,10 +115,98 @@ impl std::fmt::Display for ComponentId {
     }
 }
 
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StorageKind {
+    #[default]
+    Table,
+    Sparse,
+}
+
+/// Clones the value at `index` of a blob holding the component.
+pub type CloneFn = fn(&Blob, usize) -> Option<Blob>;
+
+/// Registration options applied to a component's meta in one step.
+/// Unset options leave the current value alone.
+#[derive(Debug, Default, Clone)]
+pub struct ComponentDescriptor {
+    storage: Option<StorageKind>,
+    clone: Option<CloneFn>,
+    requires: Vec<ComponentId>,
+    drop_immediately: bool,
+}
+
+impl ComponentDescriptor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn storage(mut self, storage: StorageKind) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
+    pub fn cloneable<T: Component + Clone>(mut self) -> Self {
+        self.clone = Some(|blob, index| {
+            let value = blob.get::<T>(index)?.clone();
+            let mut blob = Blob::new::<T>();
+            blob.push(value);
+            Some(blob)
+        });
+        self
+    }
+
+    pub fn requires(mut self, component: ComponentId) -> Self {
+        if !self.requires.contains(&component) {
+            self.requires.push(component);
+        }
+        self
+    }
+
+    /// Values are dropped as soon as their entities are cleared instead of
+    /// over the following frames.
+    pub fn drop_immediately(mut self) -> Self {
+        self.drop_immediately = true;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ComponentError {
+    ConflictingStorage {
+        component: &'static str,
+        current: StorageKind,
+        requested: StorageKind,
+    },
+}
+
+impl std::fmt::Display for ComponentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ComponentError::ConflictingStorage {
+                component,
+                current,
+                requested,
+            } => write!(
+                f,
+                "{} is registered with {:?} storage, cannot switch to {:?}",
+                component, current, requested
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ComponentError {}
+
 pub struct ComponentMeta {
     name: &'static str,
+    stable_id: Option<&'static str>,
     layout: Layout,
-    type_id: TypeId,
+    type_id: Option<TypeId>,
+    drop: Option<fn(*mut u8)>,
+    storage: Option<StorageKind>,
+    clone: Option<CloneFn>,
+    requires: Vec<ComponentId>,
+    drop_immediately: bool,
     extensions: HashMap<TypeId, Blob>,
 }
 
@@ -66,24 +214,122 @@ impl ComponentMeta {
     pub fn new<T: Component>() -> Self {
         Self {
             name: std::any::type_name::<T>(),
+            stable_id: T::STABLE_ID,
             layout: Layout::new::<T>(),
-            type_id: TypeId::of::<T>(),
+            type_id: Some(TypeId::of::<T>()),
+            drop: *Blob::with_capacity::<T>(0).drop_fn(),
+            storage: None,
+            clone: None,
+            requires: Vec::new(),
+            drop_immediately: false,
+            extensions: HashMap::new(),
+        }
+    }
+
+    /// Meta for a component without a Rust type, known only by its layout.
+    pub fn dynamic(name: &'static str, layout: Layout, drop: Option<fn(*mut u8)>) -> Self {
+        Self {
+            name,
+            stable_id: None,
+            layout,
+            type_id: None,
+            drop,
+            storage: None,
+            clone: None,
+            requires: Vec::new(),
+            drop_immediately: false,
             extensions: HashMap::new(),
         }
     }
 
+    pub fn storage(&self) -> StorageKind {
+        self.storage.unwrap_or_default()
+    }
+
+    pub fn is_cloneable(&self) -> bool {
+        self.clone.is_some()
+    }
+
+    pub fn clone_fn(&self) -> Option<CloneFn> {
+        self.clone
+    }
+
+    /// Components this component expects to be present alongside it.
+    pub fn requires(&self) -> &[ComponentId] {
+        &self.requires
+    }
+
+    pub fn drops_immediately(&self) -> bool {
+        self.drop_immediately
+    }
+
+    fn check(&self, descriptor: &ComponentDescriptor) -> Result<(), ComponentError> {
+        match (self.storage, descriptor.storage) {
+            (Some(current), Some(requested)) if current != requested => {
+                Err(ComponentError::ConflictingStorage {
+                    component: self.name,
+                    current,
+                    requested,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn apply(&mut self, descriptor: ComponentDescriptor) {
+        if descriptor.storage.is_some() {
+            self.storage = descriptor.storage;
+        }
+
+        if descriptor.clone.is_some() {
+            self.clone = descriptor.clone;
+        }
+
+        for component in descriptor.requires {
+            if !self.requires.contains(&component) {
+                self.requires.push(component);
+            }
+        }
+
+        self.drop_immediately |= descriptor.drop_immediately;
+    }
+
     pub fn name(&self) -> &'static str {
         self.name
     }
 
+    /// `Component::STABLE_ID` of the component.
+    pub fn stable_id(&self) -> Option<&'static str> {
+        self.stable_id
+    }
+
+    /// The stable id, or the name for components without one.
+    pub fn key(&self) -> &'static str {
+        self.stable_id.unwrap_or(self.name)
+    }
+
     pub fn layout(&self) -> Layout {
         self.layout
     }
 
-    pub fn type_id(&self) -> TypeId {
+    /// `None` for components registered with `register_dynamic`.
+    pub fn type_id(&self) -> Option<TypeId> {
         self.type_id
     }
 
+    pub fn is_dynamic(&self) -> bool {
+        self.type_id.is_none()
+    }
+
+    pub fn drop_fn(&self) -> Option<fn(*mut u8)> {
+        self.drop
+    }
+
+    /// An empty blob for values of the component.
+    pub fn blob(&self) -> Blob {
+        Blob::from_layout(self.layout, self.drop, self.name)
+    }
+
     pub fn extension<T: 'static>(&self) -> Option<&T> {
         self.extensions
             .get(&TypeId::of::<T>())
@@ -94,24 +340,77 @@ impl ComponentMeta {
 pub struct Components {
     components: Vec<ComponentMeta>,
     id_map: HashMap<TypeId, usize>,
+    dynamic: HashMap<&'static str, usize>,
+    registry: u32,
 }
 
 impl Components {
     pub fn new() -> Self {
+        static NEXT_REGISTRY: AtomicU32 = AtomicU32::new(0);
+
         Self {
             components: Vec::new(),
             id_map: HashMap::new(),
+            dynamic: HashMap::new(),
+            registry: NEXT_REGISTRY.fetch_add(1, Ordering::Relaxed),
         }
     }
 
+    /// Unique per registry, so handles can be checked against the world
+    /// they are used with.
+    pub fn registry(&self) -> u32 {
+        self.registry
+    }
+
+    /// Handle for an already registered `T`.
+    pub fn handle<T: Component>(&self) -> Option<ComponentHandle<T>> {
+        Some(ComponentHandle {
+            id: self.get_id::<T>()?,
+            registry: self.registry,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Registers `T`, or returns its id if it is already registered.
     pub fn register<T: Component>(&mut self) -> ComponentId {
         let type_id = TypeId::of::<T>();
+        if let Some(id) = self.id_map.get(&type_id) {
+            return ComponentId::new(*id);
+        }
+
         let id = self.components.len();
         self.components.push(ComponentMeta::new::<T>());
         self.id_map.insert(type_id, id);
         ComponentId::new(id)
     }
 
+    /// Registers a component known only by `name` and `layout`, for types
+    /// defined at runtime by scripts or plugins. `drop` runs on each value
+    /// the world drops. Returns the existing id if `name` is already
+    /// registered this way.
+    pub fn register_dynamic(
+        &mut self,
+        name: &str,
+        layout: Layout,
+        drop: Option<fn(*mut u8)>,
+    ) -> ComponentId {
+        if let Some(id) = self.dynamic.get(name) {
+            return ComponentId::new(*id);
+        }
+
+        let name: &'static str = Box::leak(name.to_owned().into_boxed_str());
+        let id = self.components.len();
+        self.components
+            .push(ComponentMeta::dynamic(name, layout, drop));
+        self.dynamic.insert(name, id);
+        ComponentId::new(id)
+    }
+
+    /// Id of a component registered with `register_dynamic`.
+    pub fn dynamic_id(&self, name: &str) -> Option<ComponentId> {
+        self.dynamic.get(name).map(|id| ComponentId::new(*id))
+    }
+
     pub fn get(&self, id: ComponentId) -> &ComponentMeta {
         &self.components[usize::from(id)]
     }
@@ -128,6 +427,12 @@ impl Components {
         self.id_map.contains_key(&TypeId::of::<T>())
     }
 
+    pub fn get_id<T: Component>(&self) -> Option<ComponentId> {
+        self.id_map
+            .get(&TypeId::of::<T>())
+            .map(|id| ComponentId::new(*id))
+    }
+
     pub fn id<T: Component>(&self) -> ComponentId {
         self.id_map
             .get(&TypeId::of::<T>())
@@ -139,6 +444,19 @@ impl Components {
         &self.components[usize::from(id)]
     }
 
+    /// Merges `descriptor` into the meta of `id`. Nothing is changed if it
+    /// contradicts the current registration.
+    pub fn describe(
+        &mut self,
+        id: ComponentId,
+        descriptor: ComponentDescriptor,
+    ) -> Result<(), ComponentError> {
+        let meta = &mut self.components[usize::from(id)];
+        meta.check(&descriptor)?;
+        meta.apply(descriptor);
+        Ok(())
+    }
+
     pub fn extend_meta<T: 'static>(&mut self, id: ComponentId, extension: T) {
         let meta = self.components.get_mut(*id).unwrap();
         let mut blob = Blob::new::<T>();
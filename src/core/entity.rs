@@ -1,5 +1,5 @@
-use super::{GenId, IdAllocator};
-use crate::storage::sparse::SparseMap;
+use super::{AllocMode, GenId, IdAllocator};
+use crate::storage::sparse::{SparseMap, SparseSet};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Entity {
@@ -27,9 +27,11 @@ impl Into<GenId> for Entity {
     }
 }
 
+#[derive(Clone)]
 pub struct Entities {
     allocator: IdAllocator,
     nodes: SparseMap<Entity, EntityNode>,
+    roots: SparseSet<Entity>,
 }
 
 impl Entities {
@@ -37,6 +39,7 @@ impl Entities {
         Self {
             allocator: IdAllocator::new(),
             nodes: SparseMap::new(),
+            roots: SparseSet::new(),
         }
     }
 
@@ -46,16 +49,68 @@ impl Entities {
         let entity = Entity::new(id.id(), id.generation());
 
         self.nodes.insert(entity, node);
+        self.roots.insert(entity.id(), entity);
 
         entity
     }
 
+    /// Creates `count` root entities, reserving room for all of them first.
+    pub fn create_batch(&mut self, count: usize) -> Vec<Entity> {
+        self.reserve(count);
+        self.nodes.reserve(count);
+        self.roots.reserve(count);
+
+        let mut entities = Vec::with_capacity(count);
+        for _ in 0..count {
+            let id = self.allocator.allocate();
+            let entity = Entity::new(id.id(), id.generation());
+            self.nodes.insert(entity, EntityNode::new(None));
+            self.roots.insert(entity.id(), entity);
+            entities.push(entity);
+        }
+
+        entities
+    }
+
+    /// Creates `entity` with its exact id and generation. Returns false if
+    /// the id is alive or the generation is older than the id's current one.
+    pub fn create_at(&mut self, entity: Entity) -> bool {
+        if !self.allocator.allocate_at(entity.into()) {
+            return false;
+        }
+
+        self.nodes.insert(entity, EntityNode::new(None));
+        self.roots.insert(entity.id(), entity);
+        true
+    }
+
+    /// Deletes `entity`, detaching it from its parent first. Children are
+    /// deleted when `recursive` is set, otherwise they become roots. A dead
+    /// or stale handle deletes nothing, so it can't reach a newer entity
+    /// reusing the id; freeing bumps the id's generation for that reason.
     pub fn delete(&mut self, entity: Entity, recursive: bool) -> Vec<Entity> {
+        if !self.contains(entity) {
+            return Vec::new();
+        }
+
+        if let Some(parent) = self.parent(entity).and_then(|p| self.nodes.get_mut(&p)) {
+            parent.children.retain(|e| *e != entity);
+        }
+
+        self.delete_node(entity, recursive)
+    }
+
+    fn delete_node(&mut self, entity: Entity, recursive: bool) -> Vec<Entity> {
         let mut deleted = Vec::new();
         if let Some(node) = self.nodes.remove(&entity) {
-            if recursive {
-                for child in node.children {
-                    deleted.extend(self.delete(child, true));
+            self.roots.remove(entity.id());
+            for child in node.children {
+                if recursive {
+                    deleted.extend(self.delete_node(child, true));
+                } else if let Some(node) = self.nodes.get_mut(&child) {
+                    node.parent = None;
+                    self.roots.insert(child.id(), child);
+                    self.update_depths(child, 0);
                 }
             }
             self.allocator
@@ -65,10 +120,76 @@ impl Entities {
         deleted
     }
 
+    /// Deletes `entity` and applies `policy` to its children. Returns the
+    /// deleted entities; reparented children are left alive.
+    pub fn delete_with_policy(&mut self, entity: Entity, policy: OrphanPolicy) -> Vec<Entity> {
+        if !self.contains(entity) {
+            return Vec::new();
+        }
+
+        let new_parent = match policy {
+            OrphanPolicy::Despawn => return self.delete(entity, true),
+            OrphanPolicy::ReparentToGrandparent => self.parent(entity),
+            OrphanPolicy::ReparentToRoot => None,
+        };
+
+        let children = match self.nodes.get_mut(&entity) {
+            Some(node) => std::mem::take(&mut node.children),
+            None => return Vec::new(),
+        };
+
+        let depth = new_parent
+            .and_then(|parent| self.depth(parent))
+            .map_or(0, |depth| depth.saturating_add(1));
+
+        for child in children {
+            if let Some(node) = self.nodes.get_mut(&child) {
+                node.parent = new_parent;
+            }
+
+            if let Some(parent) = new_parent.and_then(|parent| self.nodes.get_mut(&parent)) {
+                parent.children.push(child);
+            } else {
+                self.roots.insert(child.id(), child);
+            }
+
+            self.update_depths(child, depth);
+        }
+
+        self.delete(entity, false)
+    }
+
     pub fn reserve(&mut self, amount: usize) {
         self.allocator.reserve(amount);
     }
 
+    pub fn alloc_mode(&self) -> AllocMode {
+        self.allocator.mode()
+    }
+
+    pub fn set_alloc_mode(&mut self, mode: AllocMode) {
+        self.allocator.set_mode(mode);
+    }
+
+    /// Heap memory held by the allocator, the hierarchy nodes and the root
+    /// set. Child lists are included.
+    pub fn allocated_bytes(&self) -> usize {
+        self.allocator.allocated_bytes()
+            + self.nodes.allocated_bytes()
+            + self
+                .nodes
+                .values()
+                .iter()
+                .map(|node| node.children.capacity() * std::mem::size_of::<Entity>())
+                .sum::<usize>()
+            + self.roots.allocated_bytes()
+    }
+
+    /// See `IdAllocator::state_hash`.
+    pub fn state_hash(&self) -> u64 {
+        self.allocator.state_hash()
+    }
+
     pub fn len(&self) -> usize {
         self.allocator.len()
     }
@@ -82,6 +203,11 @@ impl Entities {
             .is_alive(GenId::new(entity.id(), entity.generation()))
     }
 
+    /// True if any generation of the id is alive.
+    pub fn contains_id(&self, id: usize) -> bool {
+        self.allocator.is_id_alive(id)
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
         self.allocator
             .iter()
@@ -89,9 +215,22 @@ impl Entities {
     }
 }
 
+/// What happens to the children of a deleted entity.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OrphanPolicy {
+    /// Delete the whole subtree.
+    #[default]
+    Despawn,
+    /// Attach the children to the deleted entity's parent, or the root if it has none.
+    ReparentToGrandparent,
+    ReparentToRoot,
+}
+
+#[derive(Clone)]
 pub struct EntityNode {
     parent: Option<Entity>,
     children: Vec<Entity>,
+    depth: u16,
 }
 
 impl EntityNode {
@@ -99,6 +238,7 @@ impl EntityNode {
         Self {
             parent,
             children: Vec::new(),
+            depth: 0,
         }
     }
 
@@ -106,6 +246,10 @@ impl EntityNode {
         self.parent
     }
 
+    pub fn depth(&self) -> u16 {
+        self.depth
+    }
+
     pub fn children(&self) -> &[Entity] {
         &self.children
     }
@@ -129,56 +273,44 @@ impl EntityNode {
 
 impl Entities {
     pub fn add_entity(&mut self, entity: Entity) {
-        self.nodes.insert(
-            entity,
-            EntityNode {
-                parent: None,
-                children: Vec::new(),
-            },
-        );
+        self.nodes.insert(entity, EntityNode::new(None));
+        self.roots.insert(entity.id(), entity);
     }
 
     pub fn set_parent(&mut self, entity: Entity, parent: Option<Entity>) {
-        if let Some(old_parent) = self
-            .nodes
-            .get_mut(&entity)
-            .and_then(|e| {
-                let old = e.parent;
-                e.parent = parent;
-                old
-            })
-            .and_then(|old_parent| self.nodes.get_mut(&old_parent))
-        {
-            old_parent.children.retain(|e| *e != entity);
-        }
-        if let Some(parent) = parent {
-            if let Some(parent_node) = self.nodes.get_mut(&parent) {
-                parent_node.children.push(entity);
+        match parent {
+            Some(parent) => self.add_child(parent, entity),
+            None => {
+                if let Some(parent) = self.parent(entity) {
+                    self.remove_child(parent, entity);
+                }
             }
         }
     }
 
+    /// Makes `child` a child of `entity`. Ignored if either entity is dead or
+    /// `entity` is `child` or one of its descendants.
     pub fn add_child(&mut self, entity: Entity, child: Entity) {
-        if !self.contains(entity) || !self.contains(child) {
+        if !self.contains(entity) || !self.contains(child) || self.is_ancestor(child, entity) {
             return;
         }
 
-        {
-            let parent = self.nodes.get_mut(&entity).unwrap();
-            parent.children.push(child);
-        }
-
         let old_parent = self.nodes.get_mut(&child).and_then(|e| {
             let old = e.parent;
             e.parent = Some(entity);
             old
         });
 
-        if let Some(old_parent) = old_parent {
-            if let Some(old_parent) = self.nodes.get_mut(&old_parent) {
-                old_parent.children.retain(|e| *e != child);
-            }
+        if let Some(old_parent) = old_parent.and_then(|p| self.nodes.get_mut(&p)) {
+            old_parent.children.retain(|e| *e != child);
         }
+
+        let parent = self.nodes.get_mut(&entity).unwrap();
+        parent.children.push(child);
+        let depth = parent.depth.saturating_add(1);
+
+        self.roots.remove(child.id());
+        self.update_depths(child, depth);
     }
 
     pub fn remove_child(&mut self, entity: Entity, child: Entity) {
@@ -190,15 +322,104 @@ impl Entities {
             parent.children.retain(|e| *e != child);
         }
 
-        if let Some(child) = self.nodes.get_mut(&child) {
-            child.parent = None;
+        if let Some(node) = self.nodes.get_mut(&child) {
+            if node.parent == Some(entity) {
+                node.parent = None;
+                self.roots.insert(child.id(), child);
+                self.update_depths(child, 0);
+            }
+        }
+    }
+
+    /// Adds `child` to the child list of `entity` without any checks or
+    /// updating `child`, for `testing::corrupt`.
+    pub(crate) fn list_child(&mut self, entity: Entity, child: Entity) {
+        if let Some(parent) = self.nodes.get_mut(&entity) {
+            parent.children.push(child);
         }
     }
 
+    /// Drops `child` from the child list of `entity` without touching
+    /// `child` itself, which may be dead. Returns false if it wasn't listed.
+    pub fn prune_child(&mut self, entity: Entity, child: Entity) -> bool {
+        let Some(parent) = self.nodes.get_mut(&entity) else {
+            return false;
+        };
+
+        let len = parent.children.len();
+        parent.children.retain(|e| *e != child);
+        parent.children.len() != len
+    }
+
     pub fn parent(&self, entity: Entity) -> Option<Entity> {
         self.nodes.get(&entity).and_then(|e| e.parent)
     }
 
+    /// Children of `entity` in the order they were added. Empty for
+    /// childless or dead entities.
+    pub fn children_of(&self, entity: Entity) -> &[Entity] {
+        self.nodes
+            .get(&entity)
+            .map(|node| node.children.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Number of ancestors of `entity`; roots have depth 0.
+    pub fn depth(&self, entity: Entity) -> Option<u16> {
+        self.nodes.get(&entity).map(|node| node.depth)
+    }
+
+    /// Live entities without a parent.
+    pub fn roots(&self) -> &[Entity] {
+        self.roots.values()
+    }
+
+    pub fn is_root(&self, entity: Entity) -> bool {
+        self.roots.get(entity.id()) == Some(&entity)
+    }
+
+    /// Number of entities in the subtree rooted at `entity`, itself included.
+    /// Walks the subtree on each call.
+    pub fn subtree_size(&self, entity: Entity) -> Option<usize> {
+        let node = self.nodes.get(&entity)?;
+        let mut size = 1;
+        let mut stack = node.children.clone();
+        while let Some(next) = stack.pop() {
+            if let Some(node) = self.nodes.get(&next) {
+                size += 1;
+                stack.extend(node.children.iter().copied());
+            }
+        }
+
+        Some(size)
+    }
+
+    /// True if `ancestor` is `entity` or one of its ancestors.
+    pub fn is_ancestor(&self, ancestor: Entity, entity: Entity) -> bool {
+        let mut current = Some(entity);
+        while let Some(next) = current {
+            if next == ancestor {
+                return true;
+            }
+            current = self.parent(next);
+        }
+
+        false
+    }
+
+    /// Sets the depth of `entity` and everything below it. Depths saturate at
+    /// `u16::MAX`.
+    fn update_depths(&mut self, entity: Entity, depth: u16) {
+        let mut stack = vec![(entity, depth)];
+        while let Some((entity, depth)) = stack.pop() {
+            if let Some(node) = self.nodes.get_mut(&entity) {
+                node.depth = depth;
+                let depth = depth.saturating_add(1);
+                stack.extend(node.children.iter().map(|child| (*child, depth)));
+            }
+        }
+    }
+
     pub fn children(&self, entity: Entity, recursive: bool) -> Vec<Entity> {
         let mut children = Vec::new();
         if let Some(node) = self.nodes.get(&entity) {
@@ -0,0 +1,137 @@
+use super::{ComponentId, Components};
+use crate::system::observer::builtin::Bundle;
+
+/// Words kept inline. Masks holding higher component ids move to the heap.
+const INLINE_WORDS: usize = 2;
+const WORD_BITS: usize = u64::BITS as usize;
+
+/// A set of components as a bitmask, so membership is a bit test instead
+/// of a scan of an archetype's component list. Holds ids below 128 inline.
+/// Build masks once, e.g. with `from_types`, and test them with
+/// `World::has_all` or `World::has_any`.
+#[derive(Debug, Clone)]
+pub enum ComponentMask {
+    Inline([u64; INLINE_WORDS]),
+    Boxed(Box<[u64]>),
+}
+
+impl ComponentMask {
+    pub fn new() -> Self {
+        Self::Inline([0; INLINE_WORDS])
+    }
+
+    pub fn from_ids(ids: &[ComponentId]) -> Self {
+        let mut mask = Self::new();
+        for id in ids {
+            mask.set(*id);
+        }
+        mask
+    }
+
+    /// Mask of every component of `B`. Panics if one isn't registered.
+    pub fn from_types<B: Bundle>(components: &Components) -> Self {
+        Self::from_ids(&B::component_ids(components))
+    }
+
+    pub fn set(&mut self, id: ComponentId) {
+        let (word, bit) = Self::locate(id);
+        if word >= self.words().len() {
+            let mut words = vec![0; word + 1];
+            words[..self.words().len()].copy_from_slice(self.words());
+            *self = Self::Boxed(words.into_boxed_slice());
+        }
+
+        self.words_mut()[word] |= bit;
+    }
+
+    pub fn unset(&mut self, id: ComponentId) {
+        let (word, bit) = Self::locate(id);
+        if let Some(word) = self.words_mut().get_mut(word) {
+            *word &= !bit;
+        }
+    }
+
+    pub fn contains(&self, id: ComponentId) -> bool {
+        let (word, bit) = Self::locate(id);
+        self.words().get(word).is_some_and(|word| word & bit != 0)
+    }
+
+    /// True if every component of `other` is in this mask.
+    pub fn contains_all(&self, other: &ComponentMask) -> bool {
+        other
+            .words()
+            .iter()
+            .enumerate()
+            .all(|(index, word)| self.word(index) & word == *word)
+    }
+
+    /// True if any component of `other` is in this mask.
+    pub fn intersects(&self, other: &ComponentMask) -> bool {
+        self.words()
+            .iter()
+            .zip(other.words())
+            .any(|(a, b)| a & b != 0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.words()
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words().iter().all(|word| *word == 0)
+    }
+
+    pub fn is_inline(&self) -> bool {
+        matches!(self, Self::Inline(_))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = ComponentId> + '_ {
+        self.words().iter().enumerate().flat_map(|(index, word)| {
+            (0..WORD_BITS)
+                .filter(move |bit| word & (1 << bit) != 0)
+                .map(move |bit| ComponentId::new(index * WORD_BITS + bit))
+        })
+    }
+
+    fn locate(id: ComponentId) -> (usize, u64) {
+        (id.id() / WORD_BITS, 1 << (id.id() % WORD_BITS))
+    }
+
+    fn word(&self, index: usize) -> u64 {
+        self.words().get(index).copied().unwrap_or(0)
+    }
+
+    fn words(&self) -> &[u64] {
+        match self {
+            Self::Inline(words) => words,
+            Self::Boxed(words) => words,
+        }
+    }
+
+    fn words_mut(&mut self) -> &mut [u64] {
+        match self {
+            Self::Inline(words) => words,
+            Self::Boxed(words) => words,
+        }
+    }
+}
+
+impl Default for ComponentMask {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Masks are equal when they hold the same components, however they are
+/// stored.
+impl PartialEq for ComponentMask {
+    fn eq(&self, other: &Self) -> bool {
+        let len = self.words().len().max(other.words().len());
+        (0..len).all(|index| self.word(index) == other.word(index))
+    }
+}
+
+impl Eq for ComponentMask {}
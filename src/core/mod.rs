@@ -1,7 +1,9 @@
 pub mod allocator;
 pub mod component;
 pub mod entity;
+pub mod mask;
 
 pub use allocator::*;
 pub use component::*;
 pub use entity::*;
+pub use mask::*;
@@ -0,0 +1,9 @@
+pub mod archetype;
+pub mod core;
+pub mod prelude;
+pub mod schedule;
+pub mod storage;
+pub mod system;
+pub mod tasks;
+pub mod testing;
+pub mod world;
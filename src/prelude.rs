@@ -0,0 +1,43 @@
+pub use crate::{
+    core::{Component, ComponentMask, Entities, Entity, OrphanPolicy},
+    schedule::{ScheduleLabel, SchedulePhase},
+    system::{
+        commands::Commands,
+        local::Local,
+        observer::{
+            builtin::{
+                AddChildren, AddComponent, Bundle, Change, ClearEntities, ComponentChanged,
+                ComponentRemoved, CreateEntity, DeleteEntity, EditEntity, HierarchyChange,
+                RemovalCause, RemoveBundle, RemoveChildren, RemoveComponent, RemoveResource,
+                SetComponent, SetParent, SpawnBatch,
+            },
+            Action, ActionAccess, Actions, DeliveryOrder, Observers,
+        },
+        work::{budgeted_system, WorkQueue},
+        IntoSystem, ReadOnlySystemArg, SystemArg,
+    },
+    world::{
+        borrow::{ComponentRef, ComponentRefMut},
+        dump::DebugDumpRegistry,
+        event::{EventReader, EventWriter, Events},
+        external::{Backpressure, CommandSender},
+        extract::WorldExtractor,
+        index::Indexed,
+        matcher::{MatchBegan, MatchEnded, MatcherId},
+        name::{Name, NamePolicy},
+        prefab::{PrefabBuilder, SpawnPrefab},
+        provenance::{Provenance, ProvenanceSource},
+        query::{Changed, Children, NonEmpty, Not, Parent, Query, QueryResultCache, With},
+        recording::{ActionRecording, EntityMapper},
+        reflect::{FieldValue, Reflect},
+        registered::{RefreshPoint, Registered, RegisteredQueryId},
+        removed::Removed,
+        resource::{Resource, ResourceRef, ResourceRefMut},
+        scope::{EntityScope, ScopeMember},
+        shared::{InputWriter, SharedInput, SharedResource},
+        snapshot::{Snapshot, SnapshotLoader, SnapshotRegistry},
+        spawn::EntityWorldMut,
+        transition::ArchetypeTransition,
+        World,
+    },
+};
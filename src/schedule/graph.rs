@@ -1,4 +1,6 @@
+use super::ScheduleBuildError;
 use crate::{
+    storage::sparse::SparseKey,
     system::System,
     world::{meta::AccessType, World},
 };
@@ -20,6 +22,12 @@ impl NodeId {
     }
 }
 
+impl SparseKey for NodeId {
+    fn sparse_index(&self) -> usize {
+        self.0
+    }
+}
+
 impl std::ops::Deref for NodeId {
     type Target = usize;
 
@@ -51,6 +59,10 @@ impl Node {
         self.system.run(world);
     }
 
+    pub fn system(&self) -> &System {
+        &self.system
+    }
+
     pub fn reads(&self) -> &[AccessType] {
         self.system.reads()
     }
@@ -59,6 +71,10 @@ impl Node {
         self.system.writes()
     }
 
+    pub fn registrars(&self) -> Vec<fn(&mut World)> {
+        self.system.registrars()
+    }
+
     pub fn dependencies(&self) -> &[NodeId] {
         &self.dependencies
     }
@@ -95,9 +111,7 @@ impl SystemGraph {
         let node_id = self.add_node(node);
 
         for after_id in after_ids {
-            if self.nodes[*after_id].reads().contains(&AccessType::World) {
-                self.nodes[*after_id].add_dependency(node_id);
-            }
+            self.nodes[*after_id].add_dependency(node_id);
         }
 
         let before_ids = before_systems
@@ -106,10 +120,8 @@ impl SystemGraph {
             .map(|system| self.add_system(system))
             .collect::<Vec<_>>();
 
-        if self.nodes[*node_id].reads().contains(&AccessType::World) {
-            for before_id in before_ids {
-                self.nodes[*node_id].add_dependency(before_id);
-            }
+        for before_id in before_ids {
+            self.nodes[*node_id].add_dependency(before_id);
         }
 
         node_id
@@ -125,6 +137,12 @@ impl SystemGraph {
     pub fn append(&mut self, other: &mut Self) {
         let offset = self.nodes.len();
 
+        for node in &mut other.nodes {
+            for dependency in &mut node.dependencies {
+                dependency.0 += offset;
+            }
+        }
+
         self.nodes.append(&mut other.nodes);
 
         for parents in &mut other.hierarchy {
@@ -148,40 +166,46 @@ impl SystemGraph {
             .collect()
     }
 
-    pub fn build(&mut self) {
+    /// Orders the nodes into rows that run one after another. A node's
+    /// dependencies always land in an earlier row; systems reading the whole
+    /// world get a row of their own. Systems marked to run first come before
+    /// all the others. Fails, keeping the previous order, if the ordering
+    /// constraints form a cycle.
+    pub fn build(&mut self) -> Result<(), ScheduleBuildError> {
+        let firsts = (0..self.nodes.len())
+            .filter(|i| self.nodes[*i].system.runs_first())
+            .map(NodeId::new)
+            .collect::<Vec<_>>();
+
         let mut dependency_graph = HashMap::<NodeId, HashSet<NodeId>>::new();
         for (i, node) in self.nodes.iter().enumerate() {
-            dependency_graph.insert(NodeId::new(i), HashSet::new());
+            let mut dependencies = node.dependencies().iter().copied().collect::<HashSet<_>>();
+            if !node.system.runs_first() {
+                dependencies.extend(firsts.iter().copied());
+            }
+            dependency_graph.insert(NodeId::new(i), dependencies);
+        }
+
+        for (i, node) in self.nodes.iter().enumerate() {
             for (j, other_node) in self.nodes.iter().enumerate() {
-                if i == j
-                    || dependency_graph
-                        .get(&NodeId::new(j))
-                        .and_then(|set| set.get(&NodeId::new(i)))
-                        .is_some()
-                {
+                if i == j || dependency_graph[&NodeId::new(j)].contains(&NodeId::new(i)) {
                     continue;
                 }
 
                 let writes = node.writes();
                 let reads = other_node.reads();
+                let other_writes = other_node.writes();
 
-                if writes
-                    .iter()
-                    .any(|write| (*write) != AccessType::None && reads.contains(write))
-                {
+                if writes.iter().any(|write| {
+                    (*write) != AccessType::None
+                        && (reads.contains(write) || other_writes.contains(write))
+                }) {
                     dependency_graph
                         .entry(NodeId::new(i))
                         .or_insert_with(HashSet::new)
                         .insert(NodeId::new(j));
                 }
             }
-
-            for dependency in node.dependencies() {
-                dependency_graph
-                    .entry(NodeId::new(i))
-                    .or_insert_with(HashSet::new)
-                    .insert(*dependency);
-            }
         }
 
         let mut hierarchy = Vec::new();
@@ -197,6 +221,11 @@ impl SystemGraph {
                 })
                 .collect::<Vec<NodeId>>();
 
+            if group.is_empty() {
+                let systems = self.cycle(dependency_graph);
+                return Err(ScheduleBuildError::Cycle { systems });
+            }
+
             group.sort();
 
             for node_id in &group {
@@ -213,23 +242,52 @@ impl SystemGraph {
                 })
                 .collect::<Vec<_>>();
 
-            group.retain(|node_id| !world_nodes.contains(&node_id));
+            group.retain(|node_id| !world_nodes.contains(node_id));
 
-            hierarchy.insert(0, group);
-
-            for world_id in world_nodes {
-                hierarchy.push(vec![world_id])
+            let mut rows = world_nodes
+                .into_iter()
+                .map(|node_id| vec![node_id])
+                .collect::<Vec<_>>();
+            if !group.is_empty() {
+                rows.insert(0, group);
             }
+
+            hierarchy.splice(0..0, rows);
         }
 
-        hierarchy.sort_by(|a, b| {
-            let a_first = a.first().unwrap();
-            let b_first = b.first().unwrap();
+        self.hierarchy = hierarchy;
+        Ok(())
+    }
+
+    /// Names of the systems left in `graph` once every node depending on no
+    /// remaining node is peeled off, leaving the cycles.
+    fn cycle(&self, mut graph: HashMap<NodeId, HashSet<NodeId>>) -> Vec<&'static str> {
+        loop {
+            let free = graph
+                .iter()
+                .filter(|(_, dependencies)| {
+                    dependencies
+                        .iter()
+                        .all(|dependency| !graph.contains_key(dependency))
+                })
+                .map(|(node_id, _)| *node_id)
+                .collect::<Vec<_>>();
+
+            if free.is_empty() {
+                break;
+            }
 
-            a_first.cmp(b_first)
-        });
+            for node_id in free {
+                graph.remove(&node_id);
+            }
+        }
 
-        self.hierarchy = hierarchy;
+        let mut node_ids = graph.into_keys().collect::<Vec<_>>();
+        node_ids.sort();
+        node_ids
+            .into_iter()
+            .map(|node_id| self.nodes[*node_id].system.name())
+            .collect()
     }
 
     pub fn nodes(&self) -> &[Node] {
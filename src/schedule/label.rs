@@ -0,0 +1,81 @@
+use super::ScheduleLabel;
+use std::{
+    any::TypeId,
+    collections::HashSet,
+    sync::{Mutex, OnceLock},
+};
+
+/// Identifies a schedule within a phase. Type labels compare by `TypeId`;
+/// named labels compare by their string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LabelKey {
+    Type(TypeId),
+    Name(&'static str),
+}
+
+/// Anything a schedule can be keyed by: a `ScheduleLabel` type, a
+/// `&'static str` or a `String` built at runtime.
+pub trait DynLabel {
+    fn key(&self) -> LabelKey;
+
+    fn name(&self) -> &'static str;
+}
+
+impl<L: ScheduleLabel> DynLabel for L {
+    fn key(&self) -> LabelKey {
+        LabelKey::Type(TypeId::of::<L>())
+    }
+
+    fn name(&self) -> &'static str {
+        L::LABEL
+    }
+}
+
+impl DynLabel for &'static str {
+    fn key(&self) -> LabelKey {
+        LabelKey::Name(self)
+    }
+
+    fn name(&self) -> &'static str {
+        self
+    }
+}
+
+/// Interned once per distinct string, so a `String` label costs one leaked
+/// allocation the first time it is seen.
+impl DynLabel for String {
+    fn key(&self) -> LabelKey {
+        LabelKey::Name(intern(self))
+    }
+
+    fn name(&self) -> &'static str {
+        intern(self)
+    }
+}
+
+impl DynLabel for LabelKey {
+    fn key(&self) -> LabelKey {
+        *self
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            LabelKey::Type(_) => "<type label>",
+            LabelKey::Name(name) => name,
+        }
+    }
+}
+
+fn intern(name: &str) -> &'static str {
+    static NAMES: OnceLock<Mutex<HashSet<&'static str>>> = OnceLock::new();
+
+    let mut names = NAMES.get_or_init(Default::default).lock().unwrap();
+    match names.get(name) {
+        Some(name) => name,
+        None => {
+            let name: &'static str = Box::leak(name.to_owned().into_boxed_str());
+            names.insert(name);
+            name
+        }
+    }
+}
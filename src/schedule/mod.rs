@@ -1,16 +1,21 @@
 use crate::{
     storage::sparse::SparseMap,
-    system::IntoSystem,
+    system::{IntoSystem, System},
     world::{meta::AccessType, resource::Resource, World},
 };
-use std::any::{Any, TypeId};
+use std::{
+    any::{Any, TypeId},
+    fmt::Display,
+};
 
 use self::{
     graph::SystemGraph,
-    runner::{ParallelRunner, ScheduleRunner},
+    label::{DynLabel, LabelKey},
+    runner::{ParallelRunner, RunMode, ScheduleRunner, SequentialRunner},
 };
 
 pub mod graph;
+pub mod label;
 pub mod runner;
 
 pub trait ScheduleLabel: 'static {
@@ -21,27 +26,120 @@ pub trait SchedulePhase: 'static {
     const PHASE: &'static str;
 }
 
+/// Run once by `World::shutdown` if any schedule was added to it.
+pub struct Shutdown;
+
+impl SchedulePhase for Shutdown {
+    const PHASE: &'static str = "shutdown";
+}
+
+/// What to do when a function is added to a schedule that already runs it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateSystemPolicy {
+    #[default]
+    Allow,
+    Warn,
+    Reject,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleError {
+    DuplicateSystem {
+        phase: &'static str,
+        label: &'static str,
+        system: &'static str,
+    },
+}
+
+impl Display for ScheduleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScheduleError::DuplicateSystem {
+                phase,
+                label,
+                system,
+            } => write!(f, "{} is already in {}/{}", system, phase, label),
+        }
+    }
+}
+
+impl std::error::Error for ScheduleError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleBuildError {
+    /// Ordering constraints, explicit or from conflicting access, that can't
+    /// all hold. Names the systems on the cycle, in the order they were added.
+    Cycle { systems: Vec<&'static str> },
+}
+
+impl Display for ScheduleBuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScheduleBuildError::Cycle { systems } => write!(
+                f,
+                "system ordering contains a cycle between {}",
+                systems.join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ScheduleBuildError {}
+
 pub struct Schedule {
     graph: SystemGraph,
+    mode: RunMode,
     runner: Box<dyn ScheduleRunner>,
 }
 
 impl Schedule {
     pub fn new() -> Self {
+        Self::with_run_mode(RunMode::Parallel)
+    }
+
+    pub fn with_run_mode(mode: RunMode) -> Self {
         Self {
             graph: SystemGraph::new(),
-            runner: Box::new(ParallelRunner),
+            mode,
+            runner: Self::runner(mode),
         }
     }
 
+    fn runner(mode: RunMode) -> Box<dyn ScheduleRunner> {
+        match mode {
+            RunMode::Sequential => Box::new(SequentialRunner),
+            RunMode::Parallel => Box::new(ParallelRunner),
+        }
+    }
+
+    pub fn run_mode(&self) -> RunMode {
+        self.mode
+    }
+
+    /// `Sequential` runs every system on the calling thread, in the same
+    /// order on every run.
+    pub fn set_run_mode(&mut self, mode: RunMode) {
+        self.mode = mode;
+        self.runner = Self::runner(mode);
+    }
+
     pub fn add_system<M>(&mut self, system: impl IntoSystem<M>) {
         self.graph.add_system(system.into_system());
     }
 
+    /// Moves the systems of `schedule` into this one, which keeps its run
+    /// mode.
     pub fn append(&mut self, mut schedule: Schedule) {
         self.graph.append(&mut schedule.graph);
     }
 
+    pub fn contains_system(&self, type_id: TypeId) -> bool {
+        self.graph
+            .nodes()
+            .iter()
+            .any(|node| node.system().type_id() == Some(type_id))
+    }
+
     pub fn reads(&self) -> Vec<AccessType> {
         self.graph.reads()
     }
@@ -54,56 +152,81 @@ impl Schedule {
         self.runner.run(&self.graph, world);
     }
 
-    pub fn build(&mut self) {
-        self.graph.build();
+    pub fn registrars(&self) -> Vec<fn(&mut World)> {
+        self.graph
+            .nodes()
+            .iter()
+            .flat_map(|node| node.registrars())
+            .collect()
+    }
+
+    pub fn build(&mut self) -> Result<(), ScheduleBuildError> {
+        self.graph.build()
     }
 }
 
 pub struct Schedules {
-    schedules: SparseMap<TypeId, SparseMap<TypeId, Schedule>>,
+    /// Looked up by phase only. The order phases run in is kept by the
+    /// world, see `World::add_phase`.
+    schedules: SparseMap<TypeId, SparseMap<LabelKey, Schedule>>,
+    mode: RunMode,
 }
 
 impl Schedules {
     pub fn new() -> Self {
         Self {
             schedules: SparseMap::new(),
+            mode: RunMode::Parallel,
         }
     }
 
+    /// Run mode of the schedules created from now on.
+    pub fn default_run_mode(&self) -> RunMode {
+        self.mode
+    }
+
+    pub fn set_default_run_mode(&mut self, mode: RunMode) {
+        self.mode = mode;
+    }
+
     pub fn add_system<M>(
         &mut self,
         phase: impl SchedulePhase,
-        label: impl ScheduleLabel,
+        label: impl DynLabel,
         system: impl IntoSystem<M>,
     ) {
+        self.schedule_mut(phase, label).add_system(system);
+    }
+
+    /// Sets the run mode of the schedule of `label` in `phase`, creating an
+    /// empty schedule if there is none yet.
+    pub fn set_run_mode(&mut self, phase: impl SchedulePhase, label: impl DynLabel, mode: RunMode) {
+        self.schedule_mut(phase, label).set_run_mode(mode);
+    }
+
+    fn schedule_mut(&mut self, phase: impl SchedulePhase, label: impl DynLabel) -> &mut Schedule {
         let phase_id = phase.type_id();
-        let label_id = label.type_id();
+        if !self.schedules.contains(&phase_id) {
+            self.schedules.insert(phase_id, SparseMap::new());
+        }
 
-        if let Some(phase) = self.schedules.get_mut(&phase_id) {
-            if let Some(schedule) = phase.get_mut(&label_id) {
-                schedule.add_system(system);
-            } else {
-                let mut schedule = Schedule::new();
-                schedule.add_system(system);
-                phase.insert(label_id, schedule);
-            }
-        } else {
-            let mut phase = SparseMap::new();
-            let mut schedule = Schedule::new();
-            schedule.add_system(system);
-            phase.insert(label_id, schedule);
-            self.schedules.insert(phase_id, phase);
+        let label_id = label.key();
+        let phase = self.schedules.get_mut(&phase_id).unwrap();
+        if !phase.contains(&label_id) {
+            phase.insert(label_id, Schedule::with_run_mode(self.mode));
         }
+
+        phase.get_mut(&label_id).unwrap()
     }
 
     pub fn add_schedule(
         &mut self,
         phase: impl SchedulePhase,
-        label: impl ScheduleLabel,
+        label: impl DynLabel,
         schedule: Schedule,
     ) {
         let phase_id = phase.type_id();
-        let label_id = label.type_id();
+        let label_id = label.key();
 
         if let Some(phase) = self.schedules.get_mut(&phase_id) {
             if let Some(found) = phase.get_mut(&label_id) {
@@ -123,17 +246,88 @@ impl Schedules {
 
         if let Some(phase) = self.schedules.get(&phase_id) {
             for schedule in phase.values() {
+                if world.frame_token().is_cancelled() {
+                    break;
+                }
+
                 schedule.run(world);
             }
         }
     }
 
-    pub(crate) fn build(&mut self) {
+    /// Every system, in phase, schedule and system registration order.
+    pub fn systems(&self) -> impl Iterator<Item = &System> {
+        self.schedules
+            .values()
+            .iter()
+            .flat_map(|phase| phase.values().iter())
+            .flat_map(|schedule| schedule.graph.nodes().iter())
+            .map(|node| node.system())
+    }
+
+    /// Component registrars of every system, in phase, schedule and system
+    /// registration order.
+    pub fn registrars(&self) -> Vec<fn(&mut World)> {
+        self.schedules
+            .values()
+            .iter()
+            .flat_map(|phase| phase.values().iter())
+            .flat_map(|schedule| schedule.registrars())
+            .collect()
+    }
+
+    pub(crate) fn build(&mut self) -> Result<(), ScheduleBuildError> {
         for phase in self.schedules.values_mut() {
             for schedule in phase.values_mut() {
-                schedule.build();
+                schedule.build()?;
             }
         }
+
+        Ok(())
+    }
+
+    /// Removes the schedule of `label` from `phase`, keeping the run order
+    /// of the phase's other labels. The returned schedule can be added back
+    /// with `add_schedule`.
+    pub fn remove_label(
+        &mut self,
+        phase: impl SchedulePhase,
+        label: impl DynLabel,
+    ) -> Option<Schedule> {
+        let phase_id = phase.type_id();
+        let schedules = self.schedules.get_mut(&phase_id)?;
+        let schedule = schedules.shift_remove(&label.key())?;
+
+        if schedules.is_empty() {
+            self.schedules.shift_remove(&phase_id);
+        }
+
+        Some(schedule)
+    }
+
+    pub fn contains_phase<P: SchedulePhase>(&self) -> bool {
+        self.schedules.contains(&TypeId::of::<P>())
+    }
+
+    pub fn remove_phase<P: SchedulePhase>(&mut self) -> Option<SparseMap<LabelKey, Schedule>> {
+        self.schedules.shift_remove(&TypeId::of::<P>())
+    }
+
+    /// True if the schedule of `label` in `P` already runs a system built
+    /// from the function `type_id` refers to.
+    pub fn contains_system<P: SchedulePhase>(&self, label: LabelKey, type_id: TypeId) -> bool {
+        self.schedules
+            .get(&TypeId::of::<P>())
+            .and_then(|schedules| schedules.get(&label))
+            .map(|schedule| schedule.contains_system(type_id))
+            .unwrap_or(false)
+    }
+
+    pub fn contains(&self, phase: impl SchedulePhase, label: impl DynLabel) -> bool {
+        self.schedules
+            .get(&phase.type_id())
+            .map(|schedules| schedules.contains(&label.key()))
+            .unwrap_or(false)
     }
 
     pub fn clear(&mut self) {
@@ -148,8 +342,8 @@ impl GlobalSchedules {
         Self(Schedules::new())
     }
 
-    pub fn build(&mut self) {
-        self.0.build();
+    pub fn build(&mut self) -> Result<(), ScheduleBuildError> {
+        self.0.build()
     }
 }
 
@@ -182,8 +376,8 @@ impl SceneSchedules {
         Self(Schedules::new())
     }
 
-    pub fn build(&mut self) {
-        self.0.build();
+    pub fn build(&mut self) -> Result<(), ScheduleBuildError> {
+        self.0.build()
     }
 }
 
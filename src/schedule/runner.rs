@@ -1,12 +1,6 @@
 use super::graph;
-use crate::{
-    tasks::{barrier::JobBarrier, ScopedTaskPool},
-    world::World,
-};
-use std::{
-    num::NonZeroUsize,
-    sync::{Arc, Mutex},
-};
+use crate::{system::commands, tasks::ScopedTaskPool, world::World};
+use std::num::NonZeroUsize;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RunMode {
@@ -23,11 +17,17 @@ pub struct SequentialRunner;
 impl ScheduleRunner for SequentialRunner {
     fn run(&self, graph: &graph::SystemGraph, world: &World) {
         for row in graph.hierarchy() {
+            if world.frame_token().is_cancelled() {
+                break;
+            }
+
             for id in row {
                 let node = &graph.nodes()[**id];
 
-                node.run(world);
+                commands::with_slot(id.id(), || node.run(world));
             }
+
+            world.merge_commands();
         }
     }
 }
@@ -40,27 +40,20 @@ impl ScheduleRunner for ParallelRunner {
             .unwrap_or(NonZeroUsize::new(1).unwrap())
             .into();
         for row in graph.hierarchy() {
-            let num_threads = row.len().min(available_threads);
-
-            ScopedTaskPool::new(num_threads, |sender| {
-                let (barrier, lock) = JobBarrier::new(row.len());
-                let barrier = Arc::new(Mutex::new(barrier));
-
-                for node in row {
-                    let barrier = barrier.clone();
-                    let node = &graph.nodes()[node.id()];
+            if world.frame_token().is_cancelled() {
+                break;
+            }
 
-                    sender.send(move || {
-                        node.run(world);
+            let num_threads = row.len().min(available_threads);
 
-                        barrier.lock().unwrap().notify();
-                    });
+            ScopedTaskPool::scope(num_threads, |pool| {
+                for id in row {
+                    let node = &graph.nodes()[id.id()];
+                    pool.execute(move || commands::with_slot(id.id(), || node.run(world)));
                 }
-
-                sender.join();
-
-                lock.wait(barrier.lock().unwrap());
             });
+
+            world.merge_commands();
         }
     }
 }
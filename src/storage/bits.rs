@@ -1,3 +1,4 @@
+#[derive(Clone)]
 pub struct BitSet {
     bits: Vec<u8>,
 }
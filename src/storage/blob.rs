@@ -6,7 +6,7 @@ pub struct Blob {
     len: usize,
     layout: Layout,
     aligned_layout: Layout,
-    data: Vec<u8>,
+    data: NonNull<u8>,
     drop: Option<fn(*mut u8)>,
     debug_name: &'static str,
 }
@@ -15,7 +15,7 @@ impl Blob {
     pub fn new<T>() -> Self {
         let base_layout = Layout::new::<T>();
         let aligned_layout = Self::align_layout(&base_layout);
-        let data = Vec::with_capacity(aligned_layout.size());
+        let data = Self::alloc_array(&aligned_layout, 1);
         let debug_name = std::any::type_name::<T>();
 
         let drop = if std::mem::needs_drop::<T>() {
@@ -38,7 +38,7 @@ impl Blob {
     pub fn with_capacity<T>(capacity: usize) -> Self {
         let base_layout = Layout::new::<T>();
         let aligned_layout = Self::align_layout(&base_layout);
-        let data = Vec::with_capacity(aligned_layout.size() * capacity);
+        let data = Self::alloc_array(&aligned_layout, capacity);
         let debug_name = std::any::type_name::<T>();
 
         let drop = if std::mem::needs_drop::<T>() {
@@ -58,13 +58,28 @@ impl Blob {
         }
     }
 
+    /// An empty blob for values only known by their layout, e.g. components
+    /// registered at runtime. `drop` runs on each value the blob drops.
+    pub fn from_layout(layout: Layout, drop: Option<fn(*mut u8)>, name: &'static str) -> Self {
+        let aligned_layout = Self::align_layout(&layout);
+        Self {
+            capacity: 0,
+            len: 0,
+            layout,
+            aligned_layout,
+            data: Self::dangling(&aligned_layout),
+            drop,
+            debug_name: name,
+        }
+    }
+
     pub fn copy(&self, capacity: usize) -> Self {
         Blob {
             capacity,
             len: 0,
             layout: self.layout,
             aligned_layout: self.aligned_layout,
-            data: Vec::with_capacity(self.aligned_layout.size() * capacity),
+            data: Self::alloc_array(&self.aligned_layout, capacity),
             drop: self.drop.clone(),
             debug_name: self.debug_name,
         }
@@ -76,7 +91,7 @@ impl Blob {
             len: self.len,
             layout: self.layout,
             aligned_layout: self.aligned_layout,
-            data: std::mem::take(&mut self.data),
+            data: std::mem::replace(&mut self.data, Self::dangling(&self.aligned_layout)),
             drop: self.drop.clone(),
             debug_name: self.debug_name,
         };
@@ -107,6 +122,11 @@ impl Blob {
         self.len == 0
     }
 
+    /// Name of the element type the blob was created for.
+    pub fn type_name(&self) -> &'static str {
+        self.debug_name
+    }
+
     pub fn drop_fn(&self) -> &Option<fn(*mut u8)> {
         &self.drop
     }
@@ -128,9 +148,10 @@ impl Blob {
     }
 
     pub fn to_vec<T: 'static>(&mut self) -> Vec<T> {
+        self.debug_assert_type::<T>();
         let mut vec: Vec<T> = Vec::with_capacity(self.len);
 
-        let src = self.data.as_mut_ptr();
+        let src = self.data.as_ptr();
         let dst = vec.as_mut_ptr() as *mut u8;
 
         unsafe {
@@ -140,11 +161,10 @@ impl Blob {
 
                 std::ptr::copy_nonoverlapping(src, dst, self.aligned_layout.size());
             }
-            self.data.set_len(0);
+            vec.set_len(self.len);
         }
 
         self.len = 0;
-        self.capacity = 0;
 
         vec
     }
@@ -154,7 +174,9 @@ impl Blob {
         self.dealloc();
     }
 
+    /// Moves `value` into the blob, which drops it along with the rest.
     pub fn push<T>(&mut self, value: T) {
+        self.debug_assert_type::<T>();
         if self.len >= self.capacity {
             self.grow();
         }
@@ -168,6 +190,50 @@ impl Blob {
         self.len += 1;
     }
 
+    /// Moves the value at `value` into the blob by copying its bytes.
+    ///
+    /// # Safety
+    /// `value` must point to a valid value of the blob's layout, which the
+    /// caller gives up: it must not be used or dropped afterwards.
+    pub unsafe fn push_raw(&mut self, value: *const u8) {
+        if self.len >= self.capacity {
+            self.grow();
+        }
+
+        std::ptr::copy_nonoverlapping(value, self.offset(self.len), self.layout.size());
+        self.len += 1;
+    }
+
+    /// Reallocates to hold exactly `len` values. Blobs of zero-sized types
+    /// are left untouched.
+    pub fn shrink_to_fit(&mut self) {
+        if self.aligned_layout.size() == 0 || self.capacity <= self.len {
+            return;
+        }
+
+        if self.len == 0 {
+            self.dealloc();
+            return;
+        }
+
+        let old_layout = Self::array_layout(&self.aligned_layout, self.capacity);
+        let new_layout = Self::array_layout(&self.aligned_layout, self.len);
+        let data =
+            unsafe { std::alloc::realloc(self.data.as_ptr(), old_layout, new_layout.size()) };
+
+        self.data =
+            NonNull::new(data).unwrap_or_else(|| std::alloc::handle_alloc_error(new_layout));
+        self.capacity = self.len;
+    }
+
+    pub fn allocated_bytes(&self) -> usize {
+        self.aligned_layout.size() * self.capacity
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.grow_exact(self.len + additional);
+    }
+
     pub fn extend<T>(&mut self, values: Vec<T>) {
         for value in values {
             self.push(value);
@@ -175,6 +241,7 @@ impl Blob {
     }
 
     pub fn pop<T>(&mut self) -> Option<T> {
+        self.debug_assert_type::<T>();
         if self.len > 0 {
             self.len -= 1;
             unsafe {
@@ -195,7 +262,7 @@ impl Blob {
 
         unsafe {
             let dst = self.offset(self.len) as *mut u8;
-            let src = other.data.as_mut_ptr();
+            let src = other.data.as_ptr();
             std::ptr::copy_nonoverlapping(src, dst, other.aligned_layout.size() * other.len);
         }
 
@@ -203,43 +270,133 @@ impl Blob {
         other.dealloc();
     }
 
+    /// Moves the value at `index` out into a blob of its own, which drops it
+    /// unless it is moved on, and fills the slot with the last value.
     pub fn swap_remove(&mut self, index: usize) -> Blob {
         if index >= self.len {
             panic!("Index out of bounds");
         }
 
-        if self.len == 1 {
-            return self.take();
+        let mut blob = self.copy(1);
+        unsafe {
+            let size = self.aligned_layout.size();
+            std::ptr::copy_nonoverlapping(self.offset(index), blob.data.as_ptr(), size);
+            blob.len = 1;
+            self.fill_from_last(index);
+        }
+
+        blob
+    }
+
+    /// Typed `swap_remove`: hands the value back instead of a blob.
+    pub fn swap_remove_as<T>(&mut self, index: usize) -> T {
+        self.debug_assert_type::<T>();
+        if index >= self.len {
+            panic!("Index out of bounds");
+        }
+
+        unsafe {
+            let value = std::ptr::read(self.offset(index) as *const T);
+            self.fill_from_last(index);
+            value
+        }
+    }
+
+    /// Moves the last value into the slot of `index` and shrinks the blob
+    /// by one. The value at `index` must already have been moved out; when
+    /// `index` is the last slot nothing is copied.
+    unsafe fn fill_from_last(&mut self, index: usize) {
+        let last = self.len - 1;
+        if index != last {
+            let size = self.aligned_layout.size();
+            std::ptr::copy_nonoverlapping(self.offset(last), self.offset(index), size);
+        }
+
+        self.len = last;
+    }
+
+    /// Moves out the value at `index` and fills its slot with the last value
+    /// of `other`.
+    pub fn swap_remove_from(&mut self, index: usize, other: &mut Blob) -> Blob {
+        if index >= self.len || other.is_empty() {
+            panic!("Index out of bounds");
         }
 
         unsafe {
             let mut blob = self.copy(1);
+            let size = self.aligned_layout.size();
 
-            let src = self.offset(index);
-            let dst = blob.data.as_mut_ptr();
-            std::ptr::copy_nonoverlapping(src, dst, self.aligned_layout.size());
+            std::ptr::copy_nonoverlapping(self.offset(index), blob.data.as_ptr(), size);
+            blob.len = 1;
 
-            self.len -= 1;
+            other.len -= 1;
+            std::ptr::copy_nonoverlapping(other.offset(other.len), self.offset(index), size);
 
             blob
         }
     }
 
+    /// Drops the value at `index` and moves the last value of `other` into its slot.
+    pub fn replace_from(&mut self, index: usize, other: &mut Blob) {
+        if index >= self.len || other.is_empty() {
+            panic!("Index out of bounds");
+        }
+
+        other.len -= 1;
+
+        unsafe {
+            let dst = self.offset(index);
+            if let Some(drop) = &self.drop {
+                drop(dst);
+            }
+
+            std::ptr::copy_nonoverlapping(other.offset(other.len), dst, self.aligned_layout.size());
+        }
+    }
+
+    /// Moves `value` into the slot of `index` and hands back the old value,
+    /// so it is dropped by the caller. Out of bounds, `value` is dropped and
+    /// `None` returned.
     pub fn replace<T>(&mut self, index: usize, value: T) -> Option<T> {
+        self.debug_assert_type::<T>();
         if index < self.len {
             unsafe {
-                let src = self.offset(index) as *mut T;
-                let mut old = std::ptr::read(src);
-                Some(std::mem::replace(&mut old, value))
+                let dst = self.offset(index) as *mut T;
+                Some(std::ptr::replace(dst, value))
             }
         } else {
             None
         }
     }
 
+    /// Moves the first `count` values into a new blob, shifting the rest down.
+    pub fn split_front(&mut self, count: usize) -> Blob {
+        let count = count.min(self.len);
+        let mut front = self.copy(count);
+        let size = self.aligned_layout.size();
+
+        unsafe {
+            std::ptr::copy_nonoverlapping(self.data.as_ptr(), front.data.as_ptr(), size * count);
+            std::ptr::copy(
+                self.offset(count),
+                self.data.as_ptr(),
+                size * (self.len - count),
+            );
+        }
+
+        front.len = count;
+        self.len -= count;
+
+        front
+    }
+
+    pub fn as_slice<T>(&self) -> &[T] {
+        self.debug_assert_type::<T>();
+        unsafe { std::slice::from_raw_parts(self.data.as_ptr() as *const T, self.len) }
+    }
+
     pub fn ptr<'a>(&'a self) -> Ptr<'a> {
-        let data = NonNull::new(self.data.as_ptr() as *mut u8).unwrap();
-        Ptr::new(data, self.aligned_layout, self.len)
+        Ptr::new(self.data, self.aligned_layout, self.len)
     }
 
     pub fn get<T>(&self, index: usize) -> Option<&T> {
@@ -273,8 +430,18 @@ impl Blob {
         unsafe { Layout::from_size_align_unchecked(size + padding, align) }
     }
 
+    pub(crate) fn debug_assert_type<T>(&self) {
+        debug_assert_eq!(
+            self.debug_name,
+            std::any::type_name::<T>(),
+            "blob of {} read as {}",
+            self.debug_name,
+            std::any::type_name::<T>()
+        );
+    }
+
     fn grow(&mut self) {
-        let new_capacity = self.capacity * 2;
+        let new_capacity = (self.capacity * 2).max(1);
         self.grow_exact(new_capacity);
     }
 
@@ -283,56 +450,72 @@ impl Blob {
             return;
         }
 
-        let new_layout = Layout::from_size_align(
-            self.aligned_layout.size() * new_capacity,
-            self.aligned_layout.align(),
-        )
-        .unwrap();
-        let new_data = unsafe { std::alloc::alloc(new_layout) };
+        let old_layout = Self::array_layout(&self.aligned_layout, self.capacity);
+        let new_layout = Self::array_layout(&self.aligned_layout, new_capacity);
 
-        unsafe {
-            std::ptr::copy_nonoverlapping(
-                self.data.as_ptr(),
-                new_data,
-                self.aligned_layout.size() * self.len,
-            );
-            self.data.clear();
-            self.data = Vec::from_raw_parts(
-                new_data,
-                self.aligned_layout.size() * self.len,
-                new_layout.size(),
-            );
+        if new_layout.size() > 0 {
+            let data = unsafe {
+                if old_layout.size() == 0 {
+                    std::alloc::alloc(new_layout)
+                } else {
+                    std::alloc::realloc(self.data.as_ptr(), old_layout, new_layout.size())
+                }
+            };
+
+            self.data =
+                NonNull::new(data).unwrap_or_else(|| std::alloc::handle_alloc_error(new_layout));
         }
 
         self.capacity = new_capacity;
     }
 
+    fn array_layout(layout: &Layout, capacity: usize) -> Layout {
+        let size = layout
+            .size()
+            .checked_mul(capacity)
+            .expect("Blob capacity overflow");
+
+        Layout::from_size_align(size, layout.align()).expect("Blob capacity overflow")
+    }
+
+    fn alloc_array(layout: &Layout, capacity: usize) -> NonNull<u8> {
+        let layout = Self::array_layout(layout, capacity);
+        if layout.size() == 0 {
+            return Self::dangling(&layout);
+        }
+
+        let data = unsafe { std::alloc::alloc(layout) };
+        NonNull::new(data).unwrap_or_else(|| std::alloc::handle_alloc_error(layout))
+    }
+
+    fn dangling(layout: &Layout) -> NonNull<u8> {
+        NonNull::new(layout.align() as *mut u8).unwrap()
+    }
+
     fn offset(&self, index: usize) -> *mut u8 {
-        unsafe { self.data.as_ptr().add(index * self.aligned_layout.size()) as *mut u8 }
+        unsafe { self.data.as_ptr().add(index * self.aligned_layout.size()) }
     }
 
     fn dealloc(&mut self) {
-        if self.capacity > 0 {
-            self.data.clear();
-            self.data.shrink_to_fit();
-
-            self.capacity = 0;
-            self.len = 0;
+        let layout = Self::array_layout(&self.aligned_layout, self.capacity);
+        if layout.size() > 0 {
+            unsafe { std::alloc::dealloc(self.data.as_ptr(), layout) };
         }
+
+        self.data = Self::dangling(&self.aligned_layout);
+        self.capacity = 0;
+        self.len = 0;
     }
 
     fn drop_all(&mut self) {
         for i in 0..self.len {
             let ptr = self.offset(i);
             if let Some(drop) = &self.drop {
-                drop(ptr as *mut u8);
+                drop(ptr);
             }
         }
 
         self.len = 0;
-        unsafe {
-            self.data.set_len(0);
-        }
     }
 }
 
@@ -345,13 +528,14 @@ fn drop<T>(data: *mut u8) {
 
 impl Drop for Blob {
     fn drop(&mut self) {
-        if self.capacity > 0 {
-            self.drop_all();
-            self.dealloc();
-        }
+        self.drop_all();
+        self.dealloc();
     }
 }
 
+unsafe impl Send for Blob {}
+unsafe impl Sync for Blob {}
+
 pub struct BlobIterator<'a, T> {
     blob: &'a Blob,
     current: usize,
@@ -1,5 +1,23 @@
 use std::collections::HashMap;
 
+/// Largest index the array-backed containers accept in debug builds. Dense
+/// ids stay far below it; anything above is almost certainly a hash used as
+/// an index, which would allocate a slot for every smaller value.
+pub const SPARSE_INDEX_LIMIT: usize = 1 << 24;
+
+/// A key with a small, dense index, usable with `SparseArray` and
+/// `SparseSet`. Hashed keys like `ArchetypeId` or `TypeId` belong in a
+/// `SparseMap`, which indexes through a hash map instead.
+pub trait SparseKey {
+    fn sparse_index(&self) -> usize;
+}
+
+impl SparseKey for usize {
+    fn sparse_index(&self) -> usize {
+        *self
+    }
+}
+
 #[derive(Debug, Clone, Hash, Ord, PartialOrd, Eq, PartialEq)]
 pub struct SparseArray<V> {
     values: Vec<Option<V>>,
@@ -16,27 +34,33 @@ impl<V> SparseArray<V> {
         }
     }
 
-    pub fn insert(&mut self, index: usize, value: V) {
+    pub fn insert(&mut self, key: impl SparseKey, value: V) {
+        let index = key.sparse_index();
+        debug_assert!(
+            index <= SPARSE_INDEX_LIMIT,
+            "sparse index {} is above {}; hashed keys belong in a SparseMap",
+            index,
+            SPARSE_INDEX_LIMIT
+        );
         if index >= self.values.len() {
             self.values.resize_with(index + 1, || None);
         }
         self.values[index] = Some(value);
     }
 
-    pub fn get(&self, index: usize) -> Option<&V> {
-        self.values.get(index).map(|value| value.as_ref().unwrap())
+    pub fn get(&self, key: impl SparseKey) -> Option<&V> {
+        let index = key.sparse_index();
+        self.values.get(index).and_then(|value| value.as_ref())
     }
 
-    pub fn get_mut(&mut self, index: usize) -> Option<&mut V> {
-        self.values
-            .get_mut(index)
-            .map(|value| value.as_mut().unwrap())
+    pub fn get_mut(&mut self, key: impl SparseKey) -> Option<&mut V> {
+        let index = key.sparse_index();
+        self.values.get_mut(index).and_then(|value| value.as_mut())
     }
 
-    pub fn remove(&mut self, index: usize) -> Option<V> {
-        self.values
-            .get_mut(index)
-            .map(|value| value.take().unwrap())
+    pub fn remove(&mut self, key: impl SparseKey) -> Option<V> {
+        let index = key.sparse_index();
+        self.values.get_mut(index).and_then(|value| value.take())
     }
 
     pub fn iter(&self) -> impl Iterator<Item = &V> {
@@ -47,13 +71,19 @@ impl<V> SparseArray<V> {
         self.values.iter_mut().filter_map(|value| value.as_mut())
     }
 
-    pub fn contains(&self, index: usize) -> bool {
+    pub fn contains(&self, key: impl SparseKey) -> bool {
+        let index = key.sparse_index();
         self.values
             .get(index)
             .map(|value| value.is_some())
             .unwrap_or(false)
     }
 
+    /// Slots allocated, one per index up to the largest one inserted.
+    pub fn capacity(&self) -> usize {
+        self.values.capacity()
+    }
+
     pub fn len(&self) -> usize {
         self.values.len()
     }
@@ -66,6 +96,21 @@ impl<V> SparseArray<V> {
         self.values.clear();
     }
 
+    /// Drops trailing empty slots and releases unused capacity.
+    pub fn shrink_to_fit(&mut self) {
+        let len = self
+            .values
+            .iter()
+            .rposition(|value| value.is_some())
+            .map_or(0, |last| last + 1);
+        self.values.truncate(len);
+        self.values.shrink_to_fit();
+    }
+
+    pub fn allocated_bytes(&self) -> usize {
+        self.values.capacity() * std::mem::size_of::<Option<V>>()
+    }
+
     pub fn into_immutable(self) -> ImmutableSparseArray<V> {
         ImmutableSparseArray {
             values: self.values.into_boxed_slice(),
@@ -73,6 +118,7 @@ impl<V> SparseArray<V> {
     }
 }
 
+#[derive(Clone)]
 pub struct SparseSet<V> {
     values: Vec<V>,
     indices: Vec<usize>,
@@ -96,7 +142,8 @@ impl<V> SparseSet<V> {
         }
     }
 
-    pub fn insert(&mut self, index: usize, value: V) -> Option<V> {
+    pub fn insert(&mut self, key: impl SparseKey, value: V) -> Option<V> {
+        let index = key.sparse_index();
         if let Some(mapped_index) = self.array.get(index) {
             let old = std::mem::replace(&mut self.values[*mapped_index], value);
 
@@ -111,23 +158,28 @@ impl<V> SparseSet<V> {
         }
     }
 
-    pub fn get(&self, index: usize) -> Option<&V> {
+    pub fn get(&self, key: impl SparseKey) -> Option<&V> {
+        let index = key.sparse_index();
         self.array
             .get(index)
             .map(|mapped_index| &self.values[*mapped_index])
     }
 
-    pub fn get_mut(&mut self, index: usize) -> Option<&mut V> {
+    pub fn get_mut(&mut self, key: impl SparseKey) -> Option<&mut V> {
+        let index = key.sparse_index();
         self.array
             .get(index)
             .map(|mapped_index| &mut self.values[*mapped_index])
     }
 
-    pub fn remove(&mut self, index: usize) -> Option<V> {
+    pub fn remove(&mut self, key: impl SparseKey) -> Option<V> {
+        let index = key.sparse_index();
         if let Some(mapped_index) = self.array.remove(index) {
             let value = self.values.swap_remove(mapped_index);
-            let index = self.indices.swap_remove(mapped_index);
-            self.array.insert(index, mapped_index);
+            self.indices.swap_remove(mapped_index);
+            if let Some(moved) = self.indices.get(mapped_index) {
+                self.array.insert(*moved, mapped_index);
+            }
             Some(value)
         } else {
             None
@@ -150,7 +202,8 @@ impl<V> SparseSet<V> {
         self.indices.iter().cloned()
     }
 
-    pub fn contains(&self, index: usize) -> bool {
+    pub fn contains(&self, key: impl SparseKey) -> bool {
+        let index = key.sparse_index();
         self.array.contains(index)
     }
 
@@ -162,12 +215,39 @@ impl<V> SparseSet<V> {
         self.values.is_empty()
     }
 
+    pub fn reserve(&mut self, additional: usize) {
+        self.values.reserve(additional);
+        self.indices.reserve(additional);
+    }
+
     pub fn clear(&mut self) {
         self.values.clear();
         self.indices.clear();
         self.array = SparseArray::new();
     }
 
+    pub fn shrink_to_fit(&mut self) {
+        self.values.shrink_to_fit();
+        self.indices.shrink_to_fit();
+        self.array.shrink_to_fit();
+    }
+
+    /// Values the dense storage holds without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.values.capacity()
+    }
+
+    /// Index slots allocated, one per key up to the largest index seen.
+    pub fn sparse_capacity(&self) -> usize {
+        self.array.capacity()
+    }
+
+    pub fn allocated_bytes(&self) -> usize {
+        self.values.capacity() * std::mem::size_of::<V>()
+            + self.indices.capacity() * std::mem::size_of::<usize>()
+            + self.array.allocated_bytes()
+    }
+
     pub fn into_immutable(self) -> ImmutableSparseSet<V> {
         ImmutableSparseSet {
             values: self.values.into_boxed_slice(),
@@ -177,6 +257,10 @@ impl<V> SparseSet<V> {
     }
 }
 
+/// Insertion-ordered map indexed through a hash map, so any hashable key
+/// works, including hashes like `ArchetypeId`. Memory follows the number of
+/// entries, not the key values.
+#[derive(Clone)]
 pub struct SparseMap<K, V>
 where
     K: Eq + std::hash::Hash + Clone,
@@ -249,6 +333,18 @@ where
         }
     }
 
+    /// Removes the entry while keeping the order of the remaining entries.
+    pub fn shift_remove(&mut self, key: &K) -> Option<V> {
+        let index = self.map.remove(key)?;
+        let value = self.values.remove(index);
+        self.keys.remove(index);
+        for (index, key) in self.keys.iter().enumerate().skip(index) {
+            self.map.insert(key.clone(), index);
+        }
+
+        Some(value)
+    }
+
     pub fn drain(&mut self) -> impl Iterator<Item = (K, V)> + '_ {
         self.keys
             .drain(..)
@@ -288,6 +384,12 @@ where
         self.values.is_empty()
     }
 
+    pub fn reserve(&mut self, additional: usize) {
+        self.keys.reserve(additional);
+        self.values.reserve(additional);
+        self.map.reserve(additional);
+    }
+
     pub fn clear(&mut self) {
         self.keys.clear();
         self.values.clear();
@@ -306,6 +408,19 @@ where
         other.clear();
     }
 
+    /// Entries the map holds without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.values.capacity().min(self.map.capacity())
+    }
+
+    /// Shallow estimate: the key and value vectors plus the index map,
+    /// ignoring anything the values own.
+    pub fn allocated_bytes(&self) -> usize {
+        self.values.capacity() * std::mem::size_of::<V>()
+            + self.keys.capacity() * std::mem::size_of::<K>()
+            + self.map.capacity() * (std::mem::size_of::<K>() + std::mem::size_of::<usize>())
+    }
+
     pub fn sort(&mut self, sorter: fn(&V, &V) -> std::cmp::Ordering) {
         self.keys.sort_by(|a, b| {
             let value_a = &self.values[*self.map.get(a).unwrap()];
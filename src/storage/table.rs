@@ -3,78 +3,578 @@ use super::{
     ptr::Ptr,
     sparse::{ImmutableSparseSet, SparseMap, SparseSet},
 };
-use crate::core::GenId;
-use std::hash::{Hash, Hasher};
+use crate::core::{CloneFn, GenId};
+use std::{
+    alloc::Layout,
+    cell::UnsafeCell,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    ops::Range,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+/// Source of structure ticks, shared by every table so a table replaced by
+/// a new one never repeats a tick.
+static STRUCTURE: AtomicU64 = AtomicU64::new(1);
+
+fn next_structure() -> u64 {
+    STRUCTURE.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Default size of one column chunk in bytes.
+pub const COLUMN_CHUNK_BYTES: usize = 16 * 1024;
+
+/// Values per chunk for elements of `element_size` bytes in chunks of
+/// `chunk_bytes`.
+pub fn chunk_len(element_size: usize, chunk_bytes: usize) -> usize {
+    (chunk_bytes / element_size.max(1)).max(1)
+}
+
+/// Values of a column split over blobs of at most `chunk_len` values. The
+/// chunk holding the last value starts small and doubles as it fills, so
+/// small tables don't pay for a whole chunk. Once a chunk is full it never
+/// grows again, and its values keep their address until they are removed
+/// or moved by `swap_remove`. Every chunk before the one holding the last
+/// value is full. Columns spanning more than one chunk keep at most one
+/// empty chunk past it.
+struct Chunks {
+    template: Blob,
+    chunk_len: usize,
+    blobs: Vec<Blob>,
+    len: usize,
+}
+
+impl Chunks {
+    fn new(template: Blob, chunk_len: usize) -> Self {
+        Self {
+            template,
+            chunk_len,
+            blobs: Vec::new(),
+            len: 0,
+        }
+    }
+
+    fn locate(&self, index: usize) -> (usize, usize) {
+        (index / self.chunk_len, index % self.chunk_len)
+    }
+
+    /// Chunk that the next pushed value goes into, with room for at least
+    /// one more value.
+    fn tail(&mut self) -> &mut Blob {
+        let chunk = self.len / self.chunk_len;
+        if chunk == self.blobs.len() {
+            self.blobs.push(self.template.copy(0));
+        }
+
+        let blob = &mut self.blobs[chunk];
+        if blob.len() == blob.capacity() {
+            let capacity = (blob.capacity() * 2).clamp(1, self.chunk_len);
+            blob.reserve(capacity - blob.len());
+        }
+
+        blob
+    }
+
+    fn push<T>(&mut self, value: T) {
+        self.tail().push(value);
+        self.len += 1;
+    }
+
+    fn push_blob(&mut self, mut blob: Blob) {
+        while !blob.is_empty() {
+            let chunk_len = self.chunk_len;
+            let tail = self.tail();
+            let room = chunk_len - tail.len();
+            let count = blob.len().min(room);
+
+            if count == blob.len() {
+                tail.append(&mut blob);
+            } else {
+                tail.append(&mut blob.split_front(count));
+            }
+
+            self.len += count;
+        }
+    }
+
+    fn swap_remove(&mut self, index: usize) -> Blob {
+        if index >= self.len {
+            panic!("Index out of bounds");
+        }
+
+        let (chunk, offset) = self.locate(index);
+        let (last_chunk, _) = self.locate(self.len - 1);
+        let blob = if chunk == last_chunk {
+            self.blobs[chunk].swap_remove(offset)
+        } else {
+            let (head, tail) = self.blobs.split_at_mut(last_chunk);
+            head[chunk].swap_remove_from(offset, &mut tail[0])
+        };
+
+        self.len -= 1;
+        let used = self.len.div_ceil(self.chunk_len);
+        self.blobs.truncate(if used > 1 { used + 1 } else { 1 });
 
+        blob
+    }
+
+    fn replace(&mut self, index: usize, mut blob: Blob) {
+        let (chunk, offset) = self.locate(index);
+        self.blobs[chunk].replace_from(offset, &mut blob);
+    }
+
+    fn blob(&self, index: usize) -> Option<(&Blob, usize)> {
+        if index < self.len {
+            let (chunk, offset) = self.locate(index);
+            Some((&self.blobs[chunk], offset))
+        } else {
+            None
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        self.blobs.iter().map(Blob::capacity).sum()
+    }
+
+    /// Sizes the chunks for `additional` more values. Chunks that will be
+    /// filled are allocated whole; the last one only as large as needed.
+    fn reserve(&mut self, additional: usize) {
+        let needed = self.len + additional;
+        for chunk in 0..needed.div_ceil(self.chunk_len) {
+            let capacity = (needed - chunk * self.chunk_len).min(self.chunk_len);
+            match self.blobs.get_mut(chunk) {
+                Some(blob) if blob.capacity() < capacity => blob.reserve(capacity - blob.len()),
+                Some(_) => {}
+                None => self.blobs.push(self.template.copy(capacity)),
+            }
+        }
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.blobs.truncate(self.len.div_ceil(self.chunk_len));
+        if let Some(blob) = self.blobs.last_mut() {
+            blob.shrink_to_fit();
+        }
+    }
+
+    fn clear(&mut self) {
+        self.blobs.clear();
+        self.len = 0;
+    }
+
+    /// The same values moved into chunks of `chunk_len`.
+    fn rechunk(&mut self, chunk_len: usize) -> Self {
+        let mut chunks = Chunks::new(self.template.copy(0), chunk_len);
+        chunks.reserve(self.len);
+        for blob in self.blobs.drain(..) {
+            chunks.push_blob(blob);
+        }
+
+        self.len = 0;
+        chunks
+    }
+}
+
+/// Type-erased storage for one component of a table, split into fixed-size
+/// chunks (see `Chunks`). The chunks live behind an `Arc` so a forked world
+/// can share them; the first write after a fork copies the column with the
+/// clone fn handed to `share`. Each value has a change tick, stamped by
+/// `get_mut` and `set_changed_tick` and carried along when rows move.
 pub struct Column {
-    data: Blob,
+    data: UnsafeCell<Arc<Chunks>>,
+    ticks: Vec<AtomicU64>,
+    stamps: AtomicU64,
+    shared: AtomicBool,
+    clone: Mutex<Option<CloneFn>>,
 }
 
+// SAFETY: the chunks are only swapped by `unshare`, which holds the `clone`
+// lock, and by `&mut self` methods. Callers already keep readers and writers
+// of the same column apart (declared access), as they did before columns
+// could be shared.
+unsafe impl Send for Column {}
+unsafe impl Sync for Column {}
+
 impl Column {
     pub fn new<T>() -> Self {
-        Self {
-            data: Blob::new::<T>(),
-        }
+        Self::with_capacity::<T>(0)
     }
 
+    /// An empty column for the same type with room for `capacity` values,
+    /// in chunks of the default size.
     pub fn copy(&self, capacity: usize) -> Self {
+        let template = self.chunks().template.copy(0);
+        let chunk_len = chunk_len(template.aligned_layout().size(), COLUMN_CHUNK_BYTES);
+        Self::from_chunks(Chunks::new(template, chunk_len), capacity)
+    }
+
+    pub fn with_capacity<T>(capacity: usize) -> Self {
+        let template = Blob::with_capacity::<T>(0);
+        let chunk_len = chunk_len(template.aligned_layout().size(), COLUMN_CHUNK_BYTES);
+        Self::from_chunks(Chunks::new(template, chunk_len), capacity)
+    }
+
+    /// An empty column for values only known by their layout, in chunks of
+    /// the default size.
+    pub fn from_layout(layout: Layout, drop: Option<fn(*mut u8)>, name: &'static str) -> Self {
+        let template = Blob::from_layout(layout, drop, name);
+        let chunk_len = chunk_len(template.aligned_layout().size(), COLUMN_CHUNK_BYTES);
+        Self::from_chunks(Chunks::new(template, chunk_len), 0)
+    }
+
+    /// A column holding the values of `blob` in a single chunk of their
+    /// size, for rows moving between tables. Tables rechunk their columns
+    /// when inserted into `Tables`.
+    pub fn from_blob(blob: Blob) -> Self {
+        let mut chunks = Chunks::new(blob.copy(0), blob.len().max(1));
+        chunks.push_blob(blob);
+        Self::from_chunks(chunks, 0)
+    }
+
+    fn from_chunks(mut chunks: Chunks, capacity: usize) -> Self {
+        chunks.reserve(capacity);
+
         Self {
-            data: self.data.copy(capacity),
+            ticks: (0..chunks.len).map(|_| AtomicU64::new(0)).collect(),
+            stamps: AtomicU64::new(0),
+            data: UnsafeCell::new(Arc::new(chunks)),
+            shared: AtomicBool::new(false),
+            clone: Mutex::new(None),
         }
     }
 
-    pub fn with_capacity<T>(capacity: usize) -> Self {
+    /// A column holding the same values without copying them. Both columns
+    /// copy their values with `clone` the first time they are written.
+    pub fn share(&self, clone: CloneFn) -> Self {
+        *self.clone.lock().unwrap() = Some(clone);
+        self.shared.store(true, Ordering::Release);
+
         Self {
-            data: Blob::with_capacity::<T>(capacity),
+            data: UnsafeCell::new(self.arc().clone()),
+            ticks: self
+                .ticks
+                .iter()
+                .map(|tick| AtomicU64::new(tick.load(Ordering::Relaxed)))
+                .collect(),
+            stamps: AtomicU64::new(self.stamps()),
+            shared: AtomicBool::new(true),
+            clone: Mutex::new(Some(clone)),
         }
     }
 
-    pub fn from_blob(blob: Blob) -> Self {
-        Self { data: blob }
+    /// True while the values may still be referenced by another column.
+    pub fn is_shared(&self) -> bool {
+        self.shared.load(Ordering::Acquire) && Arc::strong_count(self.arc()) > 1
+    }
+
+    /// True if both columns read the same values.
+    pub fn same_data(&self, other: &Column) -> bool {
+        Arc::ptr_eq(self.arc(), other.arc())
+    }
+
+    fn arc(&self) -> &Arc<Chunks> {
+        // SAFETY: see the `Sync` impl.
+        unsafe { &*self.data.get() }
+    }
+
+    fn chunks(&self) -> &Chunks {
+        self.arc()
+    }
+
+    fn chunks_mut(&mut self) -> &mut Chunks {
+        self.unshare();
+        Arc::get_mut(self.data.get_mut()).expect("column was unshared")
+    }
+
+    fn unshare(&self) {
+        if !self.shared.load(Ordering::Acquire) {
+            return;
+        }
+
+        let clone = self.clone.lock().unwrap();
+        if !self.shared.load(Ordering::Acquire) {
+            return;
+        }
+
+        let chunks = self.arc();
+        if Arc::strong_count(chunks) > 1 {
+            let clone = clone.expect("shared column without a clone fn");
+            let mut copy = Chunks::new(chunks.template.copy(0), chunks.chunk_len);
+            copy.reserve(chunks.len);
+            for blob in &chunks.blobs {
+                for index in 0..blob.len() {
+                    copy.push_blob(clone(blob, index).expect("shared column value"));
+                }
+            }
+
+            // SAFETY: see the `Sync` impl.
+            unsafe { *self.data.get() = Arc::new(copy) };
+        }
+
+        self.shared.store(false, Ordering::Release);
+    }
+
+    /// Moves the values into chunks of `chunk_bytes`. Does nothing if the
+    /// chunk length stays the same.
+    pub fn rechunk(&mut self, chunk_bytes: usize) {
+        let chunk_len = chunk_len(self.element_size(), chunk_bytes);
+        if chunk_len != self.chunk_len() {
+            let chunks = self.chunks_mut().rechunk(chunk_len);
+            *self.chunks_mut() = chunks;
+        }
     }
 
     pub fn push<T>(&mut self, value: T) {
-        self.data.push(value);
+        self.chunks_mut().push(value);
+        self.ticks.push(AtomicU64::new(0));
     }
 
-    fn push_blob(&mut self, mut blob: Blob) {
-        self.data.append(&mut blob);
+    fn push_blob(&mut self, blob: Blob) {
+        let count = blob.len();
+        self.chunks_mut().push_blob(blob);
+        self.ticks.extend((0..count).map(|_| AtomicU64::new(0)));
     }
 
-    pub fn swap_remove(&mut self, index: usize) -> Blob {
-        self.data.swap_remove(index)
+    /// Moves the last value into the slot of `index`, which may be in
+    /// another chunk. Returns the removed value and the index the moved
+    /// value came from, `None` if `index` was the last one.
+    pub fn swap_remove(&mut self, index: usize) -> (Blob, Option<usize>) {
+        let last = self.len().saturating_sub(1);
+        let blob = self.chunks_mut().swap_remove(index);
+        self.ticks.swap_remove(index);
+        (blob, (index != last).then_some(last))
     }
 
-    pub fn offset(&self, index: usize) -> Option<Ptr> {
-        if index < self.data.len() {
-            Some(self.data.ptr().add(index))
-        } else {
-            None
+    /// `swap_remove` for callers that don't want the value: it is dropped
+    /// here. Returns the index the moved value came from.
+    pub fn swap_remove_drop(&mut self, index: usize) -> Option<usize> {
+        let (removed, moved) = self.swap_remove(index);
+        drop(removed);
+        moved
+    }
+
+    pub fn replace(&mut self, index: usize, blob: Blob, tick: u64) {
+        self.chunks_mut().replace(index, blob);
+        self.set_changed_tick(index, tick);
+    }
+
+    /// Tick of the last `get_mut`, `replace` or insertion of the value at
+    /// `index`.
+    pub fn changed_tick(&self, index: usize) -> Option<u64> {
+        self.ticks
+            .get(index)
+            .map(|tick| tick.load(Ordering::Acquire))
+    }
+
+    pub fn set_changed_tick(&self, index: usize, tick: u64) {
+        if let Some(changed) = self.ticks.get(index) {
+            changed.store(tick, Ordering::Release);
+            self.stamps.fetch_add(1, Ordering::AcqRel);
         }
     }
 
+    /// Number of times a value of the column was stamped with a change
+    /// tick, so readers can tell whether anything changed without looking
+    /// at every tick.
+    pub fn stamps(&self) -> u64 {
+        self.stamps.load(Ordering::Acquire)
+    }
+
+    /// Pointer for reading. Writing through it skips copy-on-write; use
+    /// `get_mut` instead.
+    pub fn offset(&self, index: usize) -> Option<Ptr> {
+        let (blob, offset) = self.chunks().blob(index)?;
+        Some(blob.ptr().add(offset))
+    }
+
     pub fn get<T>(&self, index: usize) -> Option<&T> {
-        self.data.get(index)
+        let (blob, offset) = self.chunks().blob(index)?;
+        blob.get(offset)
+    }
+
+    /// Stamps the value at `index` as changed at `tick`.
+    pub fn get_mut<T>(&self, index: usize, tick: u64) -> Option<&mut T> {
+        self.unshare();
+        let (blob, offset) = self.chunks().blob(index)?;
+        let value = blob.get_mut(offset)?;
+        self.set_changed_tick(index, tick);
+        Some(value)
+    }
+
+    /// Pointer to the values of one chunk, for reading like `offset`.
+    pub fn chunk(&self, index: usize) -> Option<Ptr<'_>> {
+        self.chunks().blobs.get(index).map(|blob| blob.ptr())
     }
 
-    pub fn get_mut<T>(&self, index: usize) -> Option<&mut T> {
-        self.data.get_mut(index)
+    /// Number of allocated chunks, including a spare empty one.
+    pub fn chunk_count(&self) -> usize {
+        self.chunks().blobs.len()
     }
 
-    pub fn ptr(&self) -> Ptr {
-        self.data.ptr()
+    /// Values each chunk has room for.
+    pub fn chunk_len(&self) -> usize {
+        self.chunks().chunk_len
+    }
+
+    /// Values held by each allocated chunk.
+    pub fn chunk_lens(&self) -> impl Iterator<Item = usize> + '_ {
+        self.chunks().blobs.iter().map(|blob| blob.len())
     }
 
     pub fn len(&self) -> usize {
-        self.data.len()
+        self.chunks().len
     }
 
     pub fn is_empty(&self) -> bool {
-        self.data.is_empty()
+        self.len() == 0
     }
 
     pub fn clear(&mut self) {
-        self.data.clear();
+        self.chunks_mut().clear();
+        self.ticks.clear();
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.chunks().capacity()
+    }
+
+    /// Allocates chunks until `additional` more values fit.
+    pub fn reserve(&mut self, additional: usize) {
+        self.chunks_mut().reserve(additional);
+    }
+
+    /// Frees the chunks past the last value.
+    pub fn shrink_to_fit(&mut self) {
+        self.chunks_mut().shrink_to_fit();
+    }
+
+    pub fn allocated_bytes(&self) -> usize {
+        self.chunks()
+            .blobs
+            .iter()
+            .map(|blob| blob.allocated_bytes())
+            .sum()
+    }
+
+    /// Bytes per element, padded to the element's alignment.
+    pub fn element_size(&self) -> usize {
+        self.chunks().template.aligned_layout().size()
+    }
+
+    /// Checks in debug builds that the column holds `T`, like blob reads.
+    fn debug_assert_type<T>(&self) {
+        self.chunks().template.debug_assert_type::<T>();
+    }
+
+    /// Splits the values into one view per range, in the order given, so
+    /// each can be handed to a different thread. Panics if a range is out of
+    /// bounds, overlaps another one or `T` doesn't fit the column.
+    pub fn partition_mut<T>(&mut self, ranges: &[Range<usize>]) -> Vec<ColumnSliceMut<'_, T>> {
+        self.debug_assert_type::<T>();
+        assert_eq!(
+            std::alloc::Layout::new::<T>().pad_to_align().size(),
+            self.element_size(),
+            "{} doesn't fit the column",
+            std::any::type_name::<T>()
+        );
+        assert_disjoint(ranges, self.len());
+
+        self.unshare();
+        let column = &*self;
+        ranges
+            .iter()
+            .map(|range| ColumnSliceMut::new(column, range.clone()))
+            .collect()
+    }
+}
+
+/// Panics unless every range is in `0..len` and no two overlap.
+fn assert_disjoint(ranges: &[Range<usize>], len: usize) {
+    let mut sorted = ranges.to_vec();
+    sorted.sort_by_key(|range| range.start);
+
+    for range in &sorted {
+        assert!(
+            range.start <= range.end && range.end <= len,
+            "range {:?} is out of bounds for {} rows",
+            range,
+            len
+        );
+    }
+
+    for pair in sorted.windows(2) {
+        assert!(
+            pair[0].end <= pair[1].start,
+            "ranges {:?} and {:?} overlap",
+            pair[0],
+            pair[1]
+        );
+    }
+}
+
+/// Mutable view of a range of rows of one column, made by
+/// `Column::partition_mut` or `RowsMut::take`. No other view covers the same
+/// rows, so views of one column can be used from different threads at once.
+pub struct ColumnSliceMut<'a, T> {
+    column: &'a Column,
+    range: Range<usize>,
+    _marker: PhantomData<&'a mut T>,
+}
+
+impl<'a, T> ColumnSliceMut<'a, T> {
+    /// The column must be unshared and `range` disjoint from every other
+    /// view of it.
+    fn new(column: &'a Column, range: Range<usize>) -> Self {
+        Self {
+            column,
+            range,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Rows of the column the view covers.
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.range.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.range.is_empty()
+    }
+
+    /// Value at `index` within the view.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        match index < self.len() {
+            true => self.column.get(self.range.start + index),
+            false => None,
+        }
+    }
+
+    /// Value at `index` within the view, stamped as changed at `tick`.
+    pub fn get_mut(&mut self, index: usize, tick: u64) -> Option<&mut T> {
+        if index >= self.len() {
+            return None;
+        }
+
+        let row = self.range.start + index;
+        let (blob, offset) = self.column.chunks().blob(row)?;
+        self.column.set_changed_tick(row, tick);
+        blob.get_mut(offset)
+    }
+
+    /// Every value of the view, each stamped as changed at `tick`.
+    pub fn for_each_mut(&mut self, tick: u64, mut f: impl FnMut(&mut T)) {
+        for index in 0..self.len() {
+            if let Some(value) = self.get_mut(index, tick) {
+                f(value);
+            }
+        }
     }
 }
 
@@ -132,6 +632,7 @@ impl<I: Into<GenId> + Clone> TableBuilder<I> {
             columns: self.columns.into_immutable(),
             rows: Vec::with_capacity(self.capacity),
             sparse: SparseSet::with_capacity(self.capacity),
+            structure: next_structure(),
         }
     }
 }
@@ -141,6 +642,7 @@ pub struct Table<I: Into<GenId> + Clone> {
     columns: ImmutableSparseSet<Column>,
     rows: Vec<I>,
     sparse: SparseSet<Row>,
+    structure: u64,
 }
 
 impl<I: Into<GenId> + Clone> Table<I> {
@@ -161,6 +663,7 @@ impl<I: Into<GenId> + Clone> Table<I> {
             columns: columns.into_immutable(),
             rows: Vec::with_capacity(capacity),
             sparse: SparseSet::with_capacity(capacity),
+            structure: next_structure(),
         }
     }
 
@@ -168,9 +671,32 @@ impl<I: Into<GenId> + Clone> Table<I> {
         self.id
     }
 
+    /// A table with the same rows whose columns share their values with
+    /// this one. `clone` gives the clone fn of a column index; a column that
+    /// holds values but has none is returned as the error.
+    pub fn share(&self, clone: impl Fn(usize) -> Option<CloneFn>) -> Result<Self, usize> {
+        let mut columns = SparseSet::with_capacity(self.columns.len());
+        for (index, column) in self.columns.indices().zip(self.columns.iter()) {
+            let column = match clone(index) {
+                _ if column.is_empty() => column.copy(0),
+                Some(clone) => column.share(clone),
+                None => return Err(index),
+            };
+            columns.insert(index, column);
+        }
+
+        Ok(Self {
+            id: self.id,
+            columns: columns.into_immutable(),
+            rows: self.rows.clone(),
+            sparse: self.sparse.clone(),
+            structure: next_structure(),
+        })
+    }
+
     pub fn cell(&self, row: I, column: usize) -> Option<TableCell> {
         let gen_id: GenId = row.into();
-        if let Some(row) = self.sparse.get(gen_id.id()) {
+        if let Some(row) = self.row_of(gen_id) {
             self.columns
                 .get(column)
                 .and_then(|column| column.offset(**row))
@@ -182,7 +708,7 @@ impl<I: Into<GenId> + Clone> Table<I> {
 
     pub fn get<T>(&self, row: I, column: usize) -> Option<&T> {
         let gen_id: GenId = row.into();
-        if let Some(row) = self.sparse.get(gen_id.id()) {
+        if let Some(row) = self.row_of(gen_id) {
             self.columns
                 .get(column)
                 .and_then(|column| column.get(**row))
@@ -191,21 +717,44 @@ impl<I: Into<GenId> + Clone> Table<I> {
         }
     }
 
-    pub fn get_mut<T>(&self, row: I, column: usize) -> Option<&mut T> {
+    /// Stamps the cell as changed at `tick`.
+    pub fn get_mut<T>(&self, row: I, column: usize, tick: u64) -> Option<&mut T> {
         let gen_id: GenId = row.into();
-        if let Some(row) = self.sparse.get(gen_id.id()) {
+        if let Some(row) = self.row_of(gen_id) {
             self.columns
                 .get(column)
-                .and_then(|column| column.get_mut(**row))
+                .and_then(|column| column.get_mut(**row, tick))
         } else {
             None
         }
     }
 
+    pub fn replace(&mut self, row: I, column: usize, blob: Blob, tick: u64) -> bool {
+        let gen_id: GenId = row.into();
+        if let Some(row) = self.row_of(gen_id).copied() {
+            if let Some(column) = self.columns.get_mut(column) {
+                column.replace(*row, blob, tick);
+                return true;
+            }
+        }
+
+        false
+    }
+
+    pub fn changed_tick(&self, row: I, column: usize) -> Option<u64> {
+        let gen_id: GenId = row.into();
+        let row = self.row_of(gen_id)?;
+        self.columns.get(column)?.changed_tick(**row)
+    }
+
     pub fn columns(&self) -> impl Iterator<Item = &Column> {
         self.columns.iter()
     }
 
+    pub fn column_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.columns.indices()
+    }
+
     pub fn column(&self, index: usize) -> Option<&Column> {
         self.columns.get(index)
     }
@@ -224,7 +773,7 @@ impl<I: Into<GenId> + Clone> Table<I> {
 
     pub fn select_row(&self, row: I, columns: &[usize]) -> Option<SelectedRow<I>> {
         let gen_id: GenId = row.clone().into();
-        if let Some(_row) = self.sparse.get(gen_id.id()) {
+        if let Some(_row) = self.row_of(gen_id) {
             let mut cells = SparseSet::with_capacity(columns.len());
 
             for &column in columns {
@@ -243,56 +792,303 @@ impl<I: Into<GenId> + Clone> Table<I> {
         }
     }
 
-    pub fn remove_row(&mut self, row: I) -> Option<TableRow<I>> {
+    /// Takes `row` out of the table, moving the last row into its slot.
+    /// The row index is updated from the returned move.
+    pub fn remove_row(&mut self, row: I) -> Option<RemovedRow<I>> {
         let gen_id: GenId = row.clone().into();
-        if let Some(_row) = self.sparse.remove(gen_id.id()) {
-            let mut columns = SparseSet::with_capacity(self.columns.len());
-
-            for index in &self.columns.indices().collect::<Vec<_>>() {
-                let column = self.column_mut(*index).unwrap();
-                let blob = column.swap_remove(*_row);
-                let mut column = column.copy(1);
-                column.push_blob(blob);
-                columns.insert(*index, column);
+        let slot = *self.row_of(gen_id)?;
+        Some(self.remove_slot(slot))
+    }
+
+    /// Removes the first row listed under `row`, found by scanning the rows
+    /// rather than through the row index, for rows the index lost track of.
+    pub(crate) fn remove_stray_row(&mut self, row: I) -> Option<RemovedRow<I>> {
+        let gen_id: GenId = row.into();
+        let slot = self
+            .rows
+            .iter()
+            .position(|stored| stored.clone().into() == gen_id)?;
+        Some(self.remove_slot(Row::new(slot)))
+    }
+
+    fn remove_slot(&mut self, slot: Row) -> RemovedRow<I> {
+        let row = self.rows[*slot].clone();
+        let gen_id: GenId = row.clone().into();
+        let mut columns = SparseSet::with_capacity(self.columns.len());
+
+        for index in &self.columns.indices().collect::<Vec<_>>() {
+            let column = self.column_mut(*index).unwrap();
+            let tick = column.changed_tick(*slot).unwrap_or_default();
+            let (blob, _) = column.swap_remove(*slot);
+            let removed = Column::from_blob(blob);
+            removed.set_changed_tick(0, tick);
+            columns.insert(*index, removed);
+        }
+
+        let last = Row::new(self.rows.len() - 1);
+        self.rows.swap_remove(*slot);
+        self.structure = next_structure();
+
+        let moved = (slot != last).then(|| self.rows[*slot].clone());
+        let removed = RemovedRow {
+            removed: TableRow::new(row, columns),
+            moved,
+            moved_from: last,
+            moved_to: slot,
+        };
+
+        if self.sparse.get(gen_id.id()) == Some(&slot) {
+            self.sparse.remove(gen_id.id());
+        }
+        if let Some(moved) = &removed.moved {
+            let moved: GenId = moved.clone().into();
+            if self.sparse.get(moved.id()) == Some(&last) {
+                self.sparse.insert(moved.id(), removed.moved_to);
             }
+        }
 
-            self.rows.swap_remove(*_row);
+        removed
+    }
 
-            Some(TableRow::new(row, columns))
-        } else {
-            None
+    /// Empties the table, handing back its columns with the values they
+    /// held. Empty columns of the same types are left in their place.
+    pub fn take_columns(&mut self) -> Vec<(usize, Column)> {
+        let mut taken = Vec::with_capacity(self.columns.len());
+        for index in self.columns.indices().collect::<Vec<_>>() {
+            let column = self.column_mut(index).unwrap();
+            let empty = column.copy(0);
+            taken.push((index, std::mem::replace(column, empty)));
         }
+
+        self.rows.clear();
+        self.sparse.clear();
+        self.structure = next_structure();
+        taken
     }
 
+    /// Pushes the values of `row` as the row of `id`. Columns the table
+    /// doesn't have are dropped with the row.
+    ///
+    /// Panics, before changing anything, if `row` lacks a column of the
+    /// table.
     pub fn add_row(&mut self, id: I, mut row: TableRow<I>) -> Row {
+        if let Some(index) = self
+            .columns
+            .indices()
+            .find(|index| row.column(*index).is_none())
+        {
+            panic!(
+                "Missing column {} in row added to table {}",
+                index,
+                self.id.id()
+            );
+        }
+
         let gen_id: GenId = id.clone().into();
         let new_row = Row::new(self.rows.len());
         self.sparse.insert(gen_id.id(), new_row);
         self.rows.push(id.clone());
+        self.structure = next_structure();
 
         for index in &self.columns.indices().collect::<Vec<_>>() {
             let mut column = row.remove(*index).expect("Missing column");
-            self.column_mut(*index)
-                .unwrap()
-                .push_blob(column.swap_remove(0));
+            let tick = column.changed_tick(0).unwrap_or_default();
+            let target = self.column_mut(*index).unwrap();
+            target.push_blob(column.swap_remove(0).0);
+            target.set_changed_tick(*new_row, tick);
         }
 
         new_row
     }
 
+    /// Row of `id`, if the row stored under its index has the same generation.
+    fn row_of(&self, id: GenId) -> Option<&Row> {
+        self.sparse.get(id.id()).filter(|row| {
+            self.rows
+                .get(***row)
+                .map(|stored| stored.clone().into() == id)
+                .unwrap_or(false)
+        })
+    }
+
     pub fn capacity(&self) -> usize {
         self.rows.capacity()
     }
 
+    /// Changes whenever a row is added or removed. Unique across tables, so
+    /// a table replaced by another never reports the same tick.
+    pub fn structure_tick(&self) -> u64 {
+        self.structure
+    }
+
+    /// Moves every column into chunks of `chunk_bytes`, see
+    /// `Column::rechunk`.
+    pub fn rechunk(&mut self, chunk_bytes: usize) {
+        for column in self.columns.iter_mut() {
+            column.rechunk(chunk_bytes);
+        }
+    }
+
+    /// Moves every row, in order, into a new table with room for exactly
+    /// them. Change ticks move with the values.
+    pub fn rebuild(&mut self) -> Self {
+        let len = self.rows.len();
+        let mut columns = SparseSet::with_capacity(self.columns.len());
+        for index in self.column_indices() {
+            columns.insert(index, self.column(index).unwrap().copy(len));
+        }
+
+        let mut table = Self {
+            id: self.id,
+            columns: columns.into_immutable(),
+            rows: Vec::with_capacity(len),
+            sparse: SparseSet::with_capacity(len),
+            structure: next_structure(),
+        };
+
+        let mut rows = Vec::with_capacity(len);
+        while let Some(id) = self.rows.last().cloned() {
+            let row = self.remove_row(id.clone()).expect("listed row").removed;
+            rows.push((id, row));
+        }
+
+        for (id, row) in rows.into_iter().rev() {
+            table.add_row(id, row);
+        }
+
+        table
+    }
+
+    /// Makes room for `additional` more rows in every column and the row
+    /// list.
+    pub fn reserve(&mut self, additional: usize) {
+        for column in self.columns.iter_mut() {
+            column.reserve(additional);
+        }
+
+        self.rows.reserve(additional);
+    }
+
+    /// Releases the spare capacity of every column, the row list and the
+    /// row index.
+    pub fn shrink_to_fit(&mut self) {
+        for column in self.columns.iter_mut() {
+            column.shrink_to_fit();
+        }
+
+        self.rows.shrink_to_fit();
+        self.sparse.shrink_to_fit();
+    }
+
+    /// Estimate of the heap memory held by the table.
+    pub fn allocated_bytes(&self) -> usize {
+        self.columns
+            .iter()
+            .map(|column| column.allocated_bytes())
+            .sum::<usize>()
+            + self.index_bytes()
+    }
+
+    /// Heap memory of the row list and the row index.
+    pub fn index_bytes(&self) -> usize {
+        self.rows.capacity() * std::mem::size_of::<I>() + self.sparse.allocated_bytes()
+    }
+
     pub fn rows(&self) -> &[I] {
         &self.rows
     }
 
+    /// Splits the rows into views of `chunk_size` rows (the last may be
+    /// shorter), each covering `columns` and the matching ids. Views can be
+    /// used from different threads at once. Columns the table doesn't have
+    /// are left out. Panics if a column is listed twice.
+    pub fn partition_rows_mut(
+        &mut self,
+        columns: &[usize],
+        chunk_size: usize,
+    ) -> Vec<RowsMut<'_, I>> {
+        self.partition_rows(columns, chunk_size)
+    }
+
+    /// `partition_rows_mut` for callers that keep other accesses to
+    /// `columns` apart themselves, like parallel queries through their
+    /// declared access.
+    pub(crate) fn partition_rows(
+        &self,
+        columns: &[usize],
+        chunk_size: usize,
+    ) -> Vec<RowsMut<'_, I>> {
+        let mut sorted = columns.to_vec();
+        sorted.sort_unstable();
+        sorted.dedup();
+        assert_eq!(sorted.len(), columns.len(), "column listed twice");
+
+        let table = self;
+        let columns = columns
+            .iter()
+            .filter_map(|index| Some((*index, table.columns.get(*index)?)))
+            .collect::<Vec<_>>();
+        for (_, column) in &columns {
+            column.unshare();
+        }
+
+        let chunk_size = chunk_size.max(1);
+        (0..table.len())
+            .step_by(chunk_size)
+            .map(|start| {
+                let range = start..(start + chunk_size).min(table.len());
+                RowsMut {
+                    rows: &table.rows[range.clone()],
+                    columns: columns.clone(),
+                    range,
+                }
+            })
+            .collect()
+    }
+
     pub fn len(&self) -> usize {
         self.rows.len()
     }
 }
 
+/// A range of rows of a table from `Table::partition_rows_mut`. Each column
+/// can be taken out once as a `ColumnSliceMut`.
+pub struct RowsMut<'a, I: Into<GenId> + Clone> {
+    rows: &'a [I],
+    columns: Vec<(usize, &'a Column)>,
+    range: Range<usize>,
+}
+
+impl<'a, I: Into<GenId> + Clone> RowsMut<'a, I> {
+    /// Ids of the rows, in row order.
+    pub fn rows(&self) -> &'a [I] {
+        self.rows
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+
+    /// The view of `column`, or `None` if it wasn't requested or was
+    /// already taken. Panics if `T` doesn't fit the column.
+    pub fn take<T>(&mut self, column: usize) -> Option<ColumnSliceMut<'a, T>> {
+        let position = self
+            .columns
+            .iter()
+            .position(|(index, _)| *index == column)?;
+        let (_, column) = self.columns.swap_remove(position);
+        column.debug_assert_type::<T>();
+        assert_eq!(
+            std::alloc::Layout::new::<T>().pad_to_align().size(),
+            column.element_size(),
+            "{} doesn't fit the column",
+            std::any::type_name::<T>()
+        );
+
+        Some(ColumnSliceMut::new(column, self.range.clone()))
+    }
+}
+
 pub struct TableCell<'a>(Ptr<'a>);
 
 impl<'a> TableCell<'a> {
@@ -304,6 +1100,10 @@ impl<'a> TableCell<'a> {
         self.0.get(0)
     }
 
+    pub fn into_ptr(self) -> Ptr<'a> {
+        self.0
+    }
+
     pub fn get_mut<T>(&self) -> &mut T {
         self.0.get_mut(0)
     }
@@ -332,6 +1132,15 @@ impl<'a, I: Into<GenId> + Clone> SelectedRow<'a, I> {
     }
 }
 
+/// A row taken out by `Table::remove_row`. Unless it was the last row, the
+/// last row was moved from `moved_from` into its slot, `moved_to`.
+pub struct RemovedRow<I: Into<GenId> + Clone> {
+    pub removed: TableRow<I>,
+    pub moved: Option<I>,
+    pub moved_from: Row,
+    pub moved_to: Row,
+}
+
 pub struct TableRow<I: Into<GenId> + Clone> {
     id: I,
     columns: SparseSet<Column>,
@@ -376,6 +1185,9 @@ pub struct TableId(u64);
 
 impl TableId {
     pub fn new(columns: &[usize]) -> Self {
+        let mut columns = columns.to_vec();
+        columns.sort();
+
         let mut hasher = std::collections::hash_map::DefaultHasher::new();
         columns.hash(&mut hasher);
         Self(hasher.finish())
@@ -402,27 +1214,74 @@ impl std::ops::Deref for TableId {
 
 pub struct Tables<I: Into<GenId> + Clone> {
     tables: SparseMap<TableId, Table<I>>,
+    chunk_bytes: usize,
 }
 
 impl<I: Into<GenId> + Clone> Tables<I> {
     pub fn new() -> Self {
         Self {
             tables: SparseMap::new(),
+            chunk_bytes: COLUMN_CHUNK_BYTES,
         }
     }
 
-    pub fn insert(&mut self, table: Table<I>) {
+    /// Inserts `table` with its columns rechunked to `chunk_bytes`.
+    pub fn insert(&mut self, mut table: Table<I>) {
+        table.rechunk(self.chunk_bytes);
         self.tables.insert(table.id(), table);
     }
 
+    pub fn chunk_bytes(&self) -> usize {
+        self.chunk_bytes
+    }
+
+    /// Sets the chunk size of new tables and rechunks the existing ones.
+    pub fn set_chunk_bytes(&mut self, chunk_bytes: usize) {
+        self.chunk_bytes = chunk_bytes;
+        for table in self.iter_mut() {
+            table.rechunk(chunk_bytes);
+        }
+    }
+
     pub fn get(&self, id: TableId) -> Option<&Table<I>> {
         self.tables.get(&id)
     }
 
+    pub fn remove(&mut self, id: TableId) -> Option<Table<I>> {
+        self.tables.shift_remove(&id)
+    }
+
     pub fn get_mut(&mut self, id: TableId) -> Option<&mut Table<I>> {
         self.tables.get_mut(&id)
     }
 
+    pub fn iter(&self) -> impl Iterator<Item = &Table<I>> {
+        self.tables.values().iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Table<I>> {
+        self.tables.values_mut().iter_mut()
+    }
+
+    /// Shares every table, see `Table::share`. The error is the table and
+    /// column that can't be shared.
+    pub fn share(
+        &self,
+        clone: impl Fn(usize) -> Option<CloneFn>,
+    ) -> Result<Self, (TableId, usize)> {
+        let mut tables = SparseMap::with_capacity(self.tables.len());
+        for table in self.iter() {
+            let mut shared = table.share(&clone).map_err(|column| (table.id(), column))?;
+            shared.rechunk(self.chunk_bytes);
+            tables.insert(table.id(), shared);
+        }
+
+        Ok(Self {
+            tables,
+            chunk_bytes: self.chunk_bytes,
+        })
+    }
+
     pub fn array(&self, ids: &[TableId]) -> Box<[&Table<I>]> {
         let mut array = Vec::with_capacity(ids.len());
 
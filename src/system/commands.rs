@@ -0,0 +1,103 @@
+use super::{
+    observer::{Action, Actions},
+    SystemArg,
+};
+use crate::world::{meta::AccessMeta, World};
+use std::{cell::Cell, sync::Mutex};
+
+thread_local! {
+    static SLOT: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Runs `f` with `slot` as the merge position of the `Commands` it creates.
+/// Runners pass the node's index so buffers from one row merge in node
+/// order no matter which system finished first.
+pub(crate) fn with_slot(slot: usize, f: impl FnOnce()) {
+    let previous = SLOT.replace(slot);
+    f();
+    SLOT.set(previous);
+}
+
+/// Buffers handed back by dropped `Commands`, waiting to be merged into the
+/// world's `Actions`.
+#[derive(Default)]
+pub struct CommandBuffers {
+    buffers: Mutex<Vec<(usize, Actions)>>,
+}
+
+impl CommandBuffers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn push(&self, slot: usize, actions: Actions) {
+        self.buffers.lock().unwrap().push((slot, actions));
+    }
+
+    /// Appends every waiting buffer to `actions`, ordered by slot and then
+    /// by the order they were handed back. Returns the number of buffers.
+    pub fn drain(&self, actions: &mut Actions) -> usize {
+        let mut buffers = std::mem::take(&mut *self.buffers.lock().unwrap());
+        buffers.sort_by_key(|(slot, _)| *slot);
+
+        let count = buffers.len();
+        for (_, buffer) in buffers {
+            actions.append(buffer);
+        }
+
+        count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffers.lock().unwrap().is_empty()
+    }
+}
+
+/// Queues actions from a system without borrowing the `Actions` resource,
+/// so systems that only queue actions don't conflict with each other. The
+/// buffer is merged into `Actions` once the system's row has finished.
+pub struct Commands<'a> {
+    buffers: &'a CommandBuffers,
+    slot: usize,
+    actions: Actions,
+}
+
+impl Commands<'_> {
+    pub fn add<A: Action>(&mut self, action: A) {
+        self.actions.add(action);
+    }
+
+    /// Number of actions queued by this system so far.
+    pub fn len(&self) -> usize {
+        self.actions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+}
+
+impl Drop for Commands<'_> {
+    fn drop(&mut self) {
+        if !self.actions.is_empty() {
+            let actions = std::mem::take(&mut self.actions);
+            self.buffers.push(self.slot, actions);
+        }
+    }
+}
+
+impl SystemArg for Commands<'_> {
+    type Item<'a> = Commands<'a>;
+
+    fn get<'a>(world: &'a World) -> Self::Item<'a> {
+        Commands {
+            buffers: world.command_buffers(),
+            slot: SLOT.get(),
+            actions: Actions::new(),
+        }
+    }
+
+    fn metas() -> Vec<AccessMeta> {
+        vec![]
+    }
+}
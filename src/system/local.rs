@@ -0,0 +1,109 @@
+use super::SystemArg;
+use crate::world::{
+    meta::{Access, AccessMeta, AccessType},
+    World,
+};
+use std::{
+    any::Any,
+    cell::Cell,
+    ops::{Deref, DerefMut},
+    ptr,
+    sync::{Mutex, MutexGuard},
+};
+
+type Slots = Vec<Box<dyn Any + Send>>;
+
+thread_local! {
+    /// Slots of the system running on this thread, and the next slot to hand
+    /// out. Null outside of systems.
+    static CURRENT: Cell<(*mut Slots, usize)> = const { Cell::new((ptr::null_mut(), 0)) };
+}
+
+/// State owned by one system and kept across its runs. Created with
+/// `T::default()` on the system's first run. Two `Local<T>` arguments of
+/// the same system are separate values.
+pub struct Local<'a, T: Default + Send + 'static>(&'a mut T);
+
+impl<'a, T: Default + Send + 'static> Local<'a, T> {
+    pub(crate) fn into_inner(self) -> &'a mut T {
+        self.0
+    }
+}
+
+impl<T: Default + Send + 'static> Deref for Local<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.0
+    }
+}
+
+impl<T: Default + Send + 'static> DerefMut for Local<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.0
+    }
+}
+
+impl<T: Default + Send + 'static> SystemArg for Local<'_, T> {
+    type Item<'a> = Local<'a, T>;
+
+    /// Takes the next slot of the running system. Arguments are fetched in
+    /// the same order every run, so each `Local` gets the same slot back.
+    fn get<'a>(_: &'a World) -> Self::Item<'a> {
+        let (slots, index) = CURRENT.get();
+        if slots.is_null() {
+            panic!(
+                "Local<{}> used outside of a system",
+                std::any::type_name::<T>()
+            );
+        }
+        CURRENT.set((slots, index + 1));
+
+        // Safety: `slots` is locked by the `SystemLocals::enter` guard for the
+        // whole run, and each index is handed out once per run.
+        let slots = unsafe { &mut *slots };
+        if slots.len() == index {
+            slots.push(Box::new(T::default()));
+        }
+        let value = slots[index]
+            .downcast_mut::<T>()
+            .expect("Local slot type mismatch.");
+
+        Local(unsafe { &mut *(value as *mut T) })
+    }
+
+    fn metas() -> Vec<AccessMeta> {
+        vec![AccessMeta::new(AccessType::None, Access::Read)]
+    }
+}
+
+/// The `Local` values of one system.
+#[derive(Default)]
+pub(crate) struct SystemLocals {
+    slots: Mutex<Slots>,
+}
+
+impl SystemLocals {
+    /// Makes these the locals handed to `Local` arguments until the guard
+    /// drops.
+    pub(crate) fn enter(&self) -> LocalsGuard<'_> {
+        let mut slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+        let previous = CURRENT.replace((&mut *slots as *mut Slots, 0));
+
+        LocalsGuard {
+            _slots: slots,
+            previous,
+        }
+    }
+}
+
+pub(crate) struct LocalsGuard<'a> {
+    _slots: MutexGuard<'a, Slots>,
+    previous: (*mut Slots, usize),
+}
+
+impl Drop for LocalsGuard<'_> {
+    fn drop(&mut self) {
+        CURRENT.set(self.previous);
+    }
+}
@@ -1,20 +1,102 @@
 use crate::{
     core::Entities,
+    system::local::SystemLocals,
     world::{
+        error::ResourceError,
         meta::{Access, AccessMeta, AccessType},
         resource::Resource,
         World,
     },
 };
+use std::{
+    any::{type_name, TypeId},
+    cell::Cell,
+    ptr,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+};
 
+pub mod commands;
+pub mod local;
 pub mod observer;
+pub mod work;
+
+thread_local! {
+    static LAST_RUN: Cell<u64> = const { Cell::new(0) };
+    /// Query counters of the system running on this thread. Null outside of
+    /// systems.
+    static QUERY_STATS: Cell<*const QueryStats> = const { Cell::new(ptr::null()) };
+}
+
+/// Change tick of the previous run of the system running on this thread,
+/// or 0 outside of systems. `Changed<C>` queries compare against it.
+pub fn last_run_tick() -> u64 {
+    LAST_RUN.get()
+}
+
+/// Counts a query built by the system running on this thread, if any, for
+/// `World::audit`.
+pub(crate) fn record_query(frame: u64, matched: bool) {
+    let stats = QUERY_STATS.get();
+    if stats.is_null() {
+        return;
+    }
+
+    // SAFETY: set by `System::run` for the duration of the system's body.
+    let stats = unsafe { &*stats };
+    stats.queried.store(true, Ordering::Relaxed);
+    if matched {
+        stats.last_match.store(frame + 1, Ordering::Relaxed);
+    }
+}
+
+/// Puts back the previous `QUERY_STATS` when the system's body ends, even
+/// by a panic, so it never points at a dropped system.
+struct StatsScope(*const QueryStats);
+
+impl Drop for StatsScope {
+    fn drop(&mut self) {
+        QUERY_STATS.set(self.0);
+    }
+}
+
+/// Whether a system's queries match anything.
+#[derive(Default)]
+pub struct QueryStats {
+    queried: AtomicBool,
+    /// Frame of the last query that matched a table, plus one. 0 if none
+    /// ever did.
+    last_match: AtomicU64,
+}
+
+impl QueryStats {
+    /// True once the system built a query.
+    pub fn queried(&self) -> bool {
+        self.queried.load(Ordering::Relaxed)
+    }
+
+    /// Frame in which one of the system's queries last matched a table.
+    pub fn last_match(&self) -> Option<u64> {
+        self.last_match.load(Ordering::Relaxed).checked_sub(1)
+    }
+}
 
 pub struct System {
     function: Box<dyn for<'a> Fn(&'a World) + Send + Sync>,
+    type_id: Option<TypeId>,
+    name: &'static str,
     reads: Vec<AccessType>,
     writes: Vec<AccessType>,
+    registrars: Vec<fn(&mut World)>,
     before: Vec<System>,
     after: Vec<System>,
+    last_run: AtomicU64,
+    /// Whether `run` holds the resource borrows of the system's arguments.
+    /// Off for system sets, whose systems borrow their own.
+    holds_borrows: bool,
+    locals: SystemLocals,
+    /// Runs before every other system of its schedule.
+    first: bool,
+    queries: QueryStats,
 }
 
 impl System {
@@ -24,13 +106,40 @@ impl System {
     {
         Self {
             function: Box::new(function),
+            type_id: None,
+            name: std::any::type_name::<F>(),
             reads,
             writes,
+            registrars: vec![],
             before: vec![],
             after: vec![],
+            last_run: AtomicU64::new(0),
+            holds_borrows: true,
+            locals: SystemLocals::default(),
+            first: false,
+            queries: QueryStats::default(),
         }
     }
 
+    /// Tags the system with the function it was built from, so adding the
+    /// same function twice to a schedule can be detected.
+    fn with_fn<F: 'static>(mut self) -> Self {
+        self.type_id = Some(TypeId::of::<F>());
+        self.name = std::any::type_name::<F>();
+        self
+    }
+
+    /// Type of the function the system was built from. Closures created by
+    /// the same expression share a type, so they are reported as the same
+    /// system even when their captures differ. `None` for system sets.
+    pub fn type_id(&self) -> Option<TypeId> {
+        self.type_id
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
     pub fn reads(&self) -> &[AccessType] {
         &self.reads
     }
@@ -39,6 +148,17 @@ impl System {
         &self.writes
     }
 
+    /// Functions registering the components this system's arguments refer to,
+    /// run when the world is finalized.
+    pub fn registrars(&self) -> Vec<fn(&mut World)> {
+        let mut registrars = self.registrars.clone();
+        for system in self.before.iter().chain(self.after.iter()) {
+            registrars.extend(system.registrars());
+        }
+
+        registrars
+    }
+
     pub(crate) fn befores_mut(&mut self) -> &mut Vec<System> {
         &mut self.before
     }
@@ -47,8 +167,34 @@ impl System {
         &mut self.after
     }
 
+    /// Runs the system under a fresh change tick, remembering it as the
+    /// last run for its next `Changed<C>` queries.
     pub fn run(&self, world: &World) {
+        let tick = world.increment_change_tick();
+        let last_run = self.last_run.swap(tick, Ordering::AcqRel);
+        let previous = LAST_RUN.replace(last_run);
+        let _borrows = self.holds_borrows.then(|| world.resources().enter());
+        let _locals = self.locals.enter();
+        let _stats = StatsScope(QUERY_STATS.replace(&self.queries));
         (self.function)(world);
+        LAST_RUN.set(previous);
+    }
+
+    pub fn query_stats(&self) -> &QueryStats {
+        &self.queries
+    }
+
+    pub fn runs_first(&self) -> bool {
+        self.first
+    }
+
+    pub(crate) fn set_first(&mut self, first: bool) {
+        self.first = first;
+    }
+
+    /// Change tick of the system's last run, 0 if it never ran.
+    pub fn last_run(&self) -> u64 {
+        self.last_run.load(Ordering::Acquire)
     }
 }
 
@@ -99,6 +245,13 @@ impl SystemSet {
             .flat_map(|system| system.writes().to_vec())
             .collect()
     }
+
+    pub fn registrars(&self) -> Vec<fn(&mut World)> {
+        self.systems
+            .iter()
+            .flat_map(|system| system.registrars())
+            .collect()
+    }
 }
 
 impl IntoSystem<()> for SystemSet {
@@ -111,7 +264,8 @@ impl IntoSystem<()> for SystemSet {
             writes.extend(system.writes().to_vec());
         }
 
-        let system = System::new(
+        let registrars = self.registrars();
+        let mut system = System::new(
             move |world| {
                 for system in &self.systems {
                     system.run(world);
@@ -121,6 +275,10 @@ impl IntoSystem<()> for SystemSet {
             writes,
         );
 
+        system.name = "SystemSet";
+        system.registrars = registrars;
+        system.holds_borrows = false;
+
         system
     }
 
@@ -133,6 +291,7 @@ impl IntoSystem<()> for SystemSet {
             writes.extend(system.writes().to_vec());
         }
 
+        let registrars = self.registrars();
         let mut system = System::new(
             move |world| {
                 for system in &self.systems {
@@ -143,6 +302,9 @@ impl IntoSystem<()> for SystemSet {
             writes,
         );
 
+        system.name = "SystemSet";
+        system.registrars = registrars;
+        system.holds_borrows = false;
         system.before.push(other.into_system());
 
         system
@@ -157,6 +319,7 @@ impl IntoSystem<()> for SystemSet {
             writes.extend(system.writes().to_vec());
         }
 
+        let registrars = self.registrars();
         let mut system = System::new(
             move |world| {
                 for system in &self.systems {
@@ -167,19 +330,42 @@ impl IntoSystem<()> for SystemSet {
             writes,
         );
 
+        system.name = "SystemSet";
+        system.registrars = registrars;
+        system.holds_borrows = false;
         system.after.push(other.into_system());
 
         system
     }
 }
 
+/// A value a system can take as a parameter.
+///
+/// Crates building on this one implement it for their own wrappers. `get`
+/// should only go through the `World` accessors meant for it:
+/// [`World::resource`], [`World::resource_mut`], [`World::component`],
+/// [`World::component_mut`], [`World::entities`], [`World::archetypes`],
+/// [`World::components`], [`World::tables`], [`World::frame`] and
+/// [`World::trace`]. The `_mut` accessors hand out `&mut` through `&World`,
+/// so every access made in `get` must be declared by `metas`; the schedule
+/// only keeps systems apart based on what they declare. State that is not a
+/// resource or component can be guarded with [`AccessType::Custom`].
 pub trait SystemArg {
     type Item<'a>;
 
     fn get<'a>(world: &'a World) -> Self::Item<'a>;
     fn metas() -> Vec<AccessMeta>;
+
+    /// Registers any component the argument refers to.
+    fn register(_: &mut World) {}
 }
 
+/// Marker for arguments that never hand out `&mut` access to world data.
+///
+/// # Safety
+/// Implementors must only declare and perform read access in `get`.
+pub unsafe trait ReadOnlySystemArg: SystemArg {}
+
 impl SystemArg for &World {
     type Item<'a> = &'a World;
 
@@ -197,7 +383,9 @@ pub type ArgItem<'a, A> = <A as SystemArg>::Item<'a>;
 
 pub trait IntoSystem<M> {
     fn into_system(self) -> System;
+    /// Adds `system` to the same schedule, running before this one.
     fn before<Marker>(self, system: impl IntoSystem<Marker>) -> System;
+    /// Adds `system` to the same schedule, running after this one.
     fn after<Marker>(self, system: impl IntoSystem<Marker>) -> System;
 }
 
@@ -209,7 +397,8 @@ impl<R: Resource> SystemArg for &R {
     type Item<'a> = &'a R;
 
     fn get<'a>(world: &'a World) -> Self::Item<'a> {
-        world.resource::<R>()
+        let resource = world.hold_resource::<R>();
+        resource.unwrap_or_else(|| panic!("{}", ResourceError::Missing(type_name::<R>())))
     }
 
     fn metas() -> Vec<AccessMeta> {
@@ -218,11 +407,46 @@ impl<R: Resource> SystemArg for &R {
     }
 }
 
+unsafe impl ReadOnlySystemArg for &World {}
+
+unsafe impl<R: Resource> ReadOnlySystemArg for &R {}
+
 impl<R: Resource> SystemArg for &mut R {
     type Item<'a> = &'a mut R;
 
     fn get<'a>(world: &'a World) -> Self::Item<'a> {
-        world.resource_mut::<R>()
+        let resource = world.hold_resource_mut::<R>();
+        resource.unwrap_or_else(|| panic!("{}", ResourceError::Missing(type_name::<R>())))
+    }
+
+    fn metas() -> Vec<AccessMeta> {
+        let ty = AccessType::resource::<R>();
+        vec![AccessMeta::new(ty, Access::Write)]
+    }
+}
+
+/// `None` while the resource hasn't been added. The access is declared
+/// either way, so scheduling doesn't depend on whether it exists.
+impl<R: Resource> SystemArg for Option<&R> {
+    type Item<'a> = Option<&'a R>;
+
+    fn get<'a>(world: &'a World) -> Self::Item<'a> {
+        world.hold_resource::<R>()
+    }
+
+    fn metas() -> Vec<AccessMeta> {
+        let ty = AccessType::resource::<R>();
+        vec![AccessMeta::new(ty, Access::Read)]
+    }
+}
+
+unsafe impl<R: Resource> ReadOnlySystemArg for Option<&R> {}
+
+impl<R: Resource> SystemArg for Option<&mut R> {
+    type Item<'a> = Option<&'a mut R>;
+
+    fn get<'a>(world: &'a World) -> Self::Item<'a> {
+        world.hold_resource_mut::<R>()
     }
 
     fn metas() -> Vec<AccessMeta> {
@@ -243,6 +467,8 @@ impl SystemArg for &Entities {
     }
 }
 
+unsafe impl ReadOnlySystemArg for &Entities {}
+
 impl<F: Fn() + Send + Sync + 'static> IntoSystem<F> for F {
     fn into_system(self) -> System {
         let system = System::new(
@@ -251,7 +477,8 @@ impl<F: Fn() + Send + Sync + 'static> IntoSystem<F> for F {
             },
             vec![],
             vec![],
-        );
+        )
+        .with_fn::<F>();
 
         system
     }
@@ -263,7 +490,8 @@ impl<F: Fn() + Send + Sync + 'static> IntoSystem<F> for F {
             },
             vec![],
             vec![],
-        );
+        )
+        .with_fn::<F>();
 
         system.before.push(other.into_system());
 
@@ -277,7 +505,8 @@ impl<F: Fn() + Send + Sync + 'static> IntoSystem<F> for F {
             },
             vec![],
             vec![],
-        );
+        )
+        .with_fn::<F>();
 
         system.after.push(other.into_system());
 
@@ -300,9 +529,13 @@ macro_rules! impl_into_system {
 
                 AccessMeta::pick(&mut reads, &mut writes, &metas);
 
-                let system = System::new(move |world| {
+                let mut system = System::new(move |world| {
                     (self)($($arg::get(world)),*);
-                }, reads, writes);
+                }, reads, writes).with_fn::<F>();
+
+                system.registrars.push(|world| {
+                    $($arg::register(world);)*
+                });
 
                 system
             }
@@ -318,8 +551,11 @@ macro_rules! impl_into_system {
 
                 let mut system = System::new(move |world| {
                     (self)($($arg::get(world)),*);
-                }, reads, writes);
+                }, reads, writes).with_fn::<F>();
 
+                system.registrars.push(|world| {
+                    $($arg::register(world);)*
+                });
                 system.before.push(other.into_system());
 
                 system
@@ -336,8 +572,11 @@ macro_rules! impl_into_system {
 
                 let mut system = System::new(move |world| {
                     (self)($($arg::get(world)),*);
-                }, reads, writes);
+                }, reads, writes).with_fn::<F>();
 
+                system.registrars.push(|world| {
+                    $($arg::register(world);)*
+                });
                 system.after.push(other.into_system());
 
                 system
@@ -356,7 +595,13 @@ macro_rules! impl_into_system {
                 $(metas.extend($arg::metas());)*
                 metas
             }
+
+            fn register(world: &mut World) {
+                $($arg::register(world);)*
+            }
         }
+
+        unsafe impl<$($arg: ReadOnlySystemArg),*> ReadOnlySystemArg for ($($arg,)*) {}
     };
 }
 
@@ -1,13 +1,81 @@
 use crate::{
+    core::Entity,
+    schedule::runner::RunMode,
     storage::{blob::Blob, sparse::SparseMap},
-    world::{resource::Resource, World},
+    tasks::ScopedTaskPool,
+    world::{
+        meta::AccessType,
+        priority::SkippedAction,
+        provenance::ProvenanceSource,
+        recording::{EntityMapper, RecordedEntry},
+        resource::Resource,
+        trace::TraceLevel,
+        World,
+    },
 };
 use std::any::TypeId;
 
+/// What an action type touches while executing.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum ActionAccess {
+    /// Needs the whole world; runs alone.
+    #[default]
+    ExclusiveWorld,
+    /// Only touches the listed resources and components, through the `&self`
+    /// accessors (`World::resource_mut`, `World::component_mut`). Declared
+    /// actions may not create, delete or move entities, queue actions or
+    /// register anything; debug builds panic if one does.
+    Declared {
+        reads: Vec<AccessType>,
+        writes: Vec<AccessType>,
+    },
+}
+
+impl ActionAccess {
+    pub fn is_exclusive(&self) -> bool {
+        match self {
+            ActionAccess::ExclusiveWorld => true,
+            ActionAccess::Declared { reads, writes } => reads
+                .iter()
+                .chain(writes.iter())
+                .any(|ty| *ty == AccessType::World),
+        }
+    }
+
+    pub fn conflicts(&self, other: &ActionAccess) -> bool {
+        match (self, other) {
+            (
+                ActionAccess::Declared { reads, writes },
+                ActionAccess::Declared {
+                    reads: other_reads,
+                    writes: other_writes,
+                },
+            ) if !self.is_exclusive() && !other.is_exclusive() => {
+                writes
+                    .iter()
+                    .any(|ty| other_reads.contains(ty) || other_writes.contains(ty))
+                    || other_writes.iter().any(|ty| reads.contains(ty))
+            }
+            _ => true,
+        }
+    }
+}
+
+type ExecuteFn = Box<dyn Fn(&mut World, &mut Blob, &mut ActionOutputs) + Send + Sync>;
+
+/// Runs a blob of declared actions against a shared world, see
+/// `Action::execute_shared`.
+type ExecuteSharedFn = fn(&World, &mut Blob, &mut ActionOutputs, &mut ActionJournal);
+
 pub struct ActionData {
     actions: Blob,
     priority: u32,
-    execute: Box<dyn Fn(&mut World, &mut Blob, &mut ActionOutputs) + Send + Sync>,
+    access: ActionAccess,
+    register: fn(&mut World),
+    name: &'static str,
+    execute: ExecuteFn,
+    execute_shared: ExecuteSharedFn,
+    capture: fn(&mut World, &Blob, &mut ActionJournal),
 }
 
 impl ActionData {
@@ -15,11 +83,57 @@ impl ActionData {
         Self {
             actions: Blob::new::<A>(),
             priority: A::PRIORITY,
+            access: A::access(),
+            register: A::register,
+            name: std::any::type_name::<A>(),
             execute: Box::new(|world, blob, outputs| {
                 for action in blob.iter_mut::<A>() {
-                    outputs.add::<A>(action.execute(world));
+                    if action.skip(world) {
+                        world.trace(TraceLevel::Info, std::any::type_name::<A>(), "skipped");
+                        world.priority_diagnostics().record_skip(action);
+                        continue;
+                    }
+
+                    world.priority_diagnostics().begin::<A>();
+                    let name = std::any::type_name::<A>();
+                    let begun = world.begin_provenance(|| ProvenanceSource::Action(name));
+                    let recorded = world.record_begin(&*action);
+                    let output = action.execute(world);
+                    if let Some(index) = recorded {
+                        world.record_end(index);
+                    }
+                    world.end_provenance(begun);
+                    world.priority_diagnostics().end();
+                    outputs.add::<A>(output);
                 }
             }),
+            execute_shared: |world, blob, outputs, journal| {
+                // No provenance or creations to attribute: declared actions
+                // can't create entities.
+                for (index, action) in blob.iter_mut::<A>().enumerate() {
+                    if action.skip(world) {
+                        let name = std::any::type_name::<A>();
+                        journal
+                            .traces
+                            .push((TraceLevel::Info, name, "skipped".into()));
+                        journal
+                            .skips
+                            .extend(world.priority_diagnostics().skipped(action));
+                        if let Some(recorded) = journal.recorded.get_mut(index) {
+                            *recorded = None;
+                        }
+                        continue;
+                    }
+
+                    outputs.add::<A>(action.execute_shared(world));
+                }
+            },
+            capture: |world, blob, journal| {
+                journal.recorded = blob
+                    .iter::<A>()
+                    .map(|action| world.capture(action))
+                    .collect();
+            },
         }
     }
 
@@ -27,8 +141,27 @@ impl ActionData {
         self.priority
     }
 
+    pub fn access(&self) -> &ActionAccess {
+        &self.access
+    }
+
     pub fn execute(&self, world: &mut World, blob: &mut Blob, outputs: &mut ActionOutputs) {
+        if !blob.is_empty() {
+            (self.register)(world);
+        }
+
+        #[cfg(debug_assertions)]
+        let structure = (!self.access.is_exclusive()).then(|| Structure::of(world));
         (self.execute)(world, blob, outputs);
+        #[cfg(debug_assertions)]
+        if let Some(structure) = structure {
+            structure.assert_unchanged(world, &[self.name]);
+        }
+    }
+
+    /// Type name of the action.
+    pub fn name(&self) -> &'static str {
+        self.name
     }
 
     pub fn actions(&self) -> &Blob {
@@ -48,15 +181,105 @@ impl ActionData {
     }
 }
 
+/// `Output` values are stored type-erased between the action running and its
+/// observers reading them, tagged with the output's `TypeId`.
 pub trait Action: 'static {
-    type Output;
+    type Output: 'static;
     const PRIORITY: u32 = 0;
 
     fn execute(&mut self, world: &mut World) -> Self::Output;
 
+    /// Runs an action whose `access` is `Declared` while other declared
+    /// action types run on other threads, so it only gets shared access to
+    /// the world. Declared actions must implement it, and `execute` by
+    /// calling it, which is used when the type runs alone.
+    fn execute_shared(&mut self, _: &World) -> Self::Output {
+        panic!(
+            "{} declares its access but doesn't implement execute_shared",
+            std::any::type_name::<Self>()
+        )
+    }
+
     fn skip(&self, _: &World) -> bool {
         false
     }
+
+    /// Registers any component the action refers to.
+    fn register(_: &mut World) {}
+
+    /// Entities the action operates on. Only read by the priority
+    /// diagnostics, to explain why the action was skipped.
+    fn referenced_entities(&self) -> Vec<Entity> {
+        Vec::new()
+    }
+
+    /// Points the action's entities at their replacements when a recording
+    /// is replayed onto another world.
+    fn map_entities(&mut self, _: &EntityMapper) {}
+
+    /// Declaring access lets the flush run this action type concurrently
+    /// with other declared types it doesn't conflict with.
+    fn access() -> ActionAccess {
+        ActionAccess::ExclusiveWorld
+    }
+}
+
+/// What a declared action type reported while running on another thread.
+/// Applied to the world in queue order once its group is done, so traces,
+/// priority diagnostics and recordings read as if the types ran one after
+/// another.
+#[derive(Default)]
+struct ActionJournal {
+    traces: Vec<(TraceLevel, &'static str, String)>,
+    skips: Vec<SkippedAction>,
+    /// One entry per queued action while recording, emptied for the ones
+    /// that were skipped.
+    recorded: Vec<Option<RecordedEntry>>,
+}
+
+impl ActionJournal {
+    fn apply(self, world: &World) {
+        for (level, source, message) in self.traces {
+            world.trace(level, source, message);
+        }
+
+        world.priority_diagnostics().record_skipped(self.skips);
+        world.record_captured(self.recorded.into_iter().flatten());
+    }
+}
+
+/// Debug-only fingerprint of the world's entities and archetypes, and of
+/// the queued actions, to catch declared actions changing either.
+#[cfg(debug_assertions)]
+#[derive(PartialEq, Eq)]
+struct Structure {
+    entities: usize,
+    generation: u64,
+    rows: u64,
+    queued: usize,
+}
+
+#[cfg(debug_assertions)]
+impl Structure {
+    fn of(world: &World) -> Self {
+        Self {
+            entities: world.entities().len(),
+            generation: world.archetypes().generation(),
+            rows: world
+                .tables()
+                .iter()
+                .fold(0, |rows, table| rows.wrapping_add(table.structure_tick())),
+            queued: world.resource::<Actions>().len(),
+        }
+    }
+
+    fn assert_unchanged(&self, world: &World, names: &[&'static str]) {
+        assert!(
+            *self == Self::of(world),
+            "declared actions {:?} created, deleted or moved entities or queued actions",
+            names
+        );
+    }
 }
 
 #[derive(Default)]
@@ -82,6 +305,8 @@ impl Actions {
         }
     }
 
+    /// Types new to `self` are queued after the existing ones, in the order
+    /// `actions` first saw them.
     pub fn append(&mut self, mut actions: Actions) {
         for (type_id, mut data) in actions.actions.drain() {
             if let Some(other) = self.actions.get_mut(&type_id) {
@@ -92,29 +317,210 @@ impl Actions {
         }
     }
 
+    /// Stable: types with the same priority keep the order they were first
+    /// queued in.
     fn sort(&mut self) {
         self.actions.sort(|a, b| a.priority().cmp(&b.priority()));
     }
 
+    /// Runs every queued action in priority order. Consecutive action types
+    /// with mutually disjoint declared access run concurrently, one task per
+    /// type, unless the world's default run mode is `Sequential`; exclusive
+    /// types run alone.
     pub fn execute(&mut self, world: &mut World) -> ActionOutputs {
         self.sort();
         let mut outputs = ActionOutputs::new();
+        let mut group = Vec::<(&ActionData, Blob)>::new();
 
         for data in self.actions.values_mut() {
             let mut actions = data.clear();
-            data.execute(world, &mut actions, &mut outputs);
+            if actions.is_empty() {
+                continue;
+            }
+
+            let data = &*data;
+            let exclusive = data.access.is_exclusive();
+            if exclusive || group.iter().any(|(o, _)| o.access.conflicts(&data.access)) {
+                Self::execute_group(world, &mut group, &mut outputs);
+            }
+
+            if exclusive {
+                data.execute(world, &mut actions, &mut outputs);
+            } else {
+                group.push((data, actions));
+            }
         }
 
+        Self::execute_group(world, &mut group, &mut outputs);
         outputs
     }
 
+    fn execute_group(
+        world: &mut World,
+        group: &mut Vec<(&ActionData, Blob)>,
+        outputs: &mut ActionOutputs,
+    ) {
+        if group.len() < 2 || world.default_run_mode() == RunMode::Sequential {
+            for (data, mut actions) in group.drain(..) {
+                data.execute(world, &mut actions, outputs);
+            }
+            return;
+        }
+
+        let mut journals = group
+            .iter()
+            .map(|(data, actions)| {
+                (data.register)(world);
+                let mut journal = ActionJournal::default();
+                (data.capture)(world, actions, &mut journal);
+                journal
+            })
+            .collect::<Vec<_>>();
+        let mut results = group
+            .iter()
+            .map(|_| ActionOutputs::new())
+            .collect::<Vec<_>>();
+
+        let world = &*world;
+        #[cfg(debug_assertions)]
+        let structure = Structure::of(world);
+        let jobs = group
+            .iter_mut()
+            .zip(results.iter_mut().zip(journals.iter_mut()))
+            .map(|((data, actions), (outputs, journal))| {
+                let execute = data.execute_shared;
+                move || execute(world, actions, outputs, journal)
+            })
+            .collect::<Vec<_>>();
+        ScopedTaskPool::scope(jobs.len(), |pool| {
+            for job in jobs {
+                pool.execute(job);
+            }
+        });
+
+        #[cfg(debug_assertions)]
+        {
+            let names = group.iter().map(|(data, _)| data.name).collect::<Vec<_>>();
+            structure.assert_unchanged(world, &names);
+        }
+
+        group.clear();
+        for (results, journal) in results.into_iter().zip(journals) {
+            journal.apply(world);
+            outputs.merge(results);
+        }
+    }
+
     pub fn is_empty(&self) -> bool {
         self.actions.values().iter().all(|data| data.is_empty())
     }
+
+    /// Number of queued actions across all types.
+    pub fn len(&self) -> usize {
+        self.actions
+            .values()
+            .iter()
+            .map(|data| data.actions.len())
+            .sum()
+    }
+
+    pub fn allocated_bytes(&self) -> usize {
+        self.actions
+            .values()
+            .iter()
+            .map(|data| data.actions.allocated_bytes())
+            .sum()
+    }
+}
+
+/// Outputs of a single action type, tagged with the action and output type
+/// so they are never read back as anything else.
+pub struct ActionOutput {
+    outputs: Blob,
+    action: TypeId,
+    output: TypeId,
+    action_name: &'static str,
+}
+
+impl ActionOutput {
+    pub fn new<A: Action>() -> Self {
+        Self::from_blob::<A>(Blob::new::<A::Output>())
+    }
+
+    /// Wraps outputs of `A` built elsewhere, e.g. replayed from a recording.
+    /// `outputs` must hold `A::Output` values; in debug builds that is
+    /// checked wherever the outputs are added to, merged or delivered.
+    pub fn from_blob<A: Action>(outputs: Blob) -> Self {
+        Self {
+            outputs,
+            action: TypeId::of::<A>(),
+            output: TypeId::of::<A::Output>(),
+            action_name: std::any::type_name::<A>(),
+        }
+    }
+
+    /// True if the outputs are values of `A::Output`.
+    pub fn is<A: Action>(&self) -> bool {
+        self.output == TypeId::of::<A::Output>() && self.holds::<A::Output>()
+    }
+
+    /// True if the blob actually holds `T` values, whatever the tag says.
+    fn holds<T>(&self) -> bool {
+        self.outputs.type_name() == std::any::type_name::<T>()
+            && *self.outputs.layout() == std::alloc::Layout::new::<T>()
+    }
+
+    pub fn action_type(&self) -> TypeId {
+        self.action
+    }
+
+    pub fn output_type(&self) -> TypeId {
+        self.output
+    }
+
+    pub fn action_name(&self) -> &'static str {
+        self.action_name
+    }
+
+    /// Name of the type the outputs were written as.
+    pub fn output_name(&self) -> &'static str {
+        self.outputs.type_name()
+    }
+
+    pub fn outputs(&self) -> &Blob {
+        &self.outputs
+    }
+
+    pub fn into_outputs(self) -> Blob {
+        self.outputs
+    }
+
+    pub fn len(&self) -> usize {
+        self.outputs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.outputs.is_empty()
+    }
+
+    fn append(&mut self, other: &mut ActionOutput) {
+        debug_assert!(
+            self.action == other.action
+                && self.output_name() == other.output_name()
+                && self.outputs.layout() == other.outputs.layout(),
+            "merging {} outputs of {} into {} outputs of {}",
+            other.output_name(),
+            other.action_name,
+            self.output_name(),
+            self.action_name
+        );
+
+        self.outputs.append(&mut other.outputs);
+    }
 }
 
 pub struct ActionOutputs {
-    outputs: SparseMap<TypeId, Blob>,
+    outputs: SparseMap<TypeId, ActionOutput>,
 }
 
 impl ActionOutputs {
@@ -131,22 +537,61 @@ impl ActionOutputs {
     }
 
     pub fn add<A: Action>(&mut self, output: A::Output) {
-        if let Some(outputs) = self.outputs.get_mut(&TypeId::of::<A>()) {
-            outputs.push(output);
-        } else {
-            let mut outputs = Blob::new::<A::Output>();
-            outputs.push(output);
-            self.outputs.insert(TypeId::of::<A>(), outputs);
+        let entry = self.entry::<A>();
+        debug_assert!(
+            entry.holds::<A::Output>(),
+            "{} outputs written as {} into outputs stored as {}",
+            std::any::type_name::<A>(),
+            std::any::type_name::<A::Output>(),
+            entry.output_name()
+        );
+
+        entry.outputs.push(output);
+    }
+
+    /// Appends a blob of already built `A::Output` values.
+    pub fn extend<A: Action>(&mut self, mut outputs: Blob) {
+        assert!(
+            outputs.type_name() == std::any::type_name::<A::Output>()
+                && *outputs.layout() == std::alloc::Layout::new::<A::Output>(),
+            "{} outputs must be {}, got {}",
+            std::any::type_name::<A>(),
+            std::any::type_name::<A::Output>(),
+            outputs.type_name()
+        );
+
+        self.entry::<A>().outputs.append(&mut outputs);
+    }
+
+    fn entry<A: Action>(&mut self) -> &mut ActionOutput {
+        let type_id = TypeId::of::<A>();
+        if !self.outputs.contains(&type_id) {
+            self.outputs.insert(type_id, ActionOutput::new::<A>());
         }
+
+        self.outputs.get_mut(&type_id).unwrap()
     }
 
+    /// Outputs of types new to `self` go after the existing ones, in the
+    /// order `outputs` first saw them.
     pub fn merge(&mut self, mut outputs: Self) {
-        for (type_id, mut blob) in outputs.outputs.drain() {
-            if let Some(outputs) = self.outputs.get_mut(&type_id) {
-                outputs.append(&mut blob);
-            } else {
-                self.outputs.insert(type_id, blob);
-            }
+        for (type_id, output) in outputs.outputs.drain() {
+            debug_assert!(
+                output.action == type_id,
+                "{} outputs filed under another action",
+                output.action_name
+            );
+            self.insert(output);
+        }
+    }
+
+    /// Adds outputs taken from another `ActionOutputs` or built with
+    /// `ActionOutput::from_blob`, after any already held for the action.
+    pub fn insert(&mut self, mut output: ActionOutput) {
+        if let Some(outputs) = self.outputs.get_mut(&output.action) {
+            outputs.append(&mut output);
+        } else {
+            self.outputs.insert(output.action, output);
         }
     }
 
@@ -154,7 +599,27 @@ impl ActionOutputs {
         self.outputs.keys()
     }
 
-    pub fn remove(&mut self, type_id: &TypeId) -> Option<Blob> {
+    pub fn allocated_bytes(&self) -> usize {
+        self.outputs
+            .values()
+            .iter()
+            .map(|output| output.outputs.allocated_bytes())
+            .sum()
+    }
+
+    pub fn get<A: Action>(&self) -> Option<&[A::Output]> {
+        let output = self.outputs.get(&TypeId::of::<A>())?;
+        debug_assert!(
+            output.holds::<A::Output>(),
+            "{} outputs of {} read as {}",
+            output.output_name(),
+            output.action_name,
+            std::any::type_name::<A::Output>()
+        );
+        Some(output.outputs.as_slice::<A::Output>())
+    }
+
+    pub fn remove(&mut self, type_id: &TypeId) -> Option<ActionOutput> {
         self.outputs.remove(type_id)
     }
 
@@ -1,35 +1,138 @@
-use super::{action::Actions, Action, ActionOutputs};
+use super::{Action, ActionOutputs};
 use crate::{
-    core::{Component, Entity},
-    world::World,
+    core::{Component, ComponentId, Components, Entity, OrphanPolicy},
+    storage::blob::Blob,
+    world::{lifecycle::EntityEdit, recording::EntityMapper, resource::Resource, World},
 };
-use std::fmt::Debug;
+use std::{fmt::Debug, marker::PhantomData};
 
+/// A group of components added together, e.g. `(Player, Position, Velocity)`.
+/// The entity moves straight to the archetype with all of them.
+pub trait Bundle: 'static {
+    fn add_to(self, entity: CreateEntity) -> CreateEntity;
+
+    fn register(world: &mut World);
+
+    /// Ids of the bundle's components, in order. Panics if one of them isn't
+    /// registered.
+    fn component_ids(components: &Components) -> Vec<ComponentId>;
+
+    /// Moves each component into a blob, paired with the id of its column.
+    fn into_values(self, components: &Components) -> Vec<(ComponentId, Blob)>;
+}
+
+macro_rules! impl_bundle {
+    ($($name:ident),+) => {
+        impl<$($name: Component),+> Bundle for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn add_to(self, entity: CreateEntity) -> CreateEntity {
+                let ($($name,)+) = self;
+                entity$(.with($name))+
+            }
+
+            fn register(world: &mut World) {
+                $(world.register::<$name>();)+
+            }
+
+            fn component_ids(components: &Components) -> Vec<ComponentId> {
+                vec![$(components.id::<$name>()),+]
+            }
+
+            #[allow(non_snake_case)]
+            fn into_values(self, components: &Components) -> Vec<(ComponentId, Blob)> {
+                let ($($name,)+) = self;
+                vec![$({
+                    let mut value = Blob::new::<$name>();
+                    value.push($name);
+                    (components.id::<$name>(), value)
+                }),+]
+            }
+        }
+    };
+}
+
+impl_bundle!(A);
+impl_bundle!(A, B);
+impl_bundle!(A, B, C);
+impl_bundle!(A, B, C, D);
+impl_bundle!(A, B, C, D, E);
+impl_bundle!(A, B, C, D, E, F);
+impl_bundle!(A, B, C, D, E, F, G);
+impl_bundle!(A, B, C, D, E, F, G, H);
+
+/// A component value held by `CreateEntity` until the entity is created.
+struct ComponentValue {
+    register: fn(&mut World) -> ComponentId,
+    value: Blob,
+}
+
+/// Creates an entity with all of its components in a single archetype move.
+/// Observers see a `CreateEntity` output and an `AddComponent` output per
+/// component.
 pub struct CreateEntity {
-    add_components: Vec<Box<dyn FnMut(Entity, &mut World)>>,
+    components: Vec<ComponentValue>,
+    children: Vec<CreateEntity>,
 }
 
 impl CreateEntity {
     pub fn new() -> Self {
         Self {
-            add_components: Vec::new(),
+            components: Vec::new(),
+            children: Vec::new(),
         }
     }
 
+    pub fn with_bundle<B: Bundle>(self, bundle: B) -> Self {
+        bundle.add_to(self)
+    }
+
+    /// Spawns `child` along with this entity and parents it, so observers of
+    /// either see the hierarchy in place. Observers see a `CreateEntity`
+    /// output per child and an `AddChildren` output.
+    pub fn with_child(mut self, child: CreateEntity) -> Self {
+        self.children.push(child);
+        self
+    }
+
     pub fn with<C: Component>(mut self, component: C) -> Self {
-        let mut component = Box::new(Some(component));
-        let add_component = move |entity: Entity, world: &mut World| {
-            if let Some(component) = component.take() {
-                world
-                    .resource_mut::<Actions>()
-                    .add(AddComponent::new(entity, component));
-            }
-        };
+        let mut value = Blob::new::<C>();
+        value.push(component);
 
-        self.add_components.push(Box::new(add_component));
+        self.components.push(ComponentValue {
+            register: |world| {
+                world.register::<C>();
+                world.component_id::<C>()
+            },
+            value,
+        });
 
         self
     }
+
+    /// Clones the action for a recording, if every component value is of a
+    /// component registered as cloneable.
+    pub(crate) fn record(&self, world: &mut World) -> Option<Self> {
+        let mut components = Vec::with_capacity(self.components.len());
+        for component in &self.components {
+            let id = (component.register)(world);
+            let clone = world.components().meta(id).clone_fn()?;
+            components.push(ComponentValue {
+                register: component.register,
+                value: clone(&component.value, 0)?,
+            });
+        }
+
+        let children = self
+            .children
+            .iter()
+            .map(|child| child.record(world))
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(Self {
+            components,
+            children,
+        })
+    }
 }
 
 impl Debug for CreateEntity {
@@ -44,15 +147,54 @@ impl Action for CreateEntity {
 
     fn execute(&mut self, world: &mut crate::world::World) -> Self::Output {
         let entity = world.create();
+        let values = std::mem::take(&mut self.components)
+            .into_iter()
+            .map(|component| ((component.register)(world), component.value))
+            .collect();
+        world.insert_values(entity, values);
 
-        for add_component in self.add_components.iter_mut() {
-            add_component(entity, world);
+        if !self.children.is_empty() {
+            let mut children = Vec::with_capacity(self.children.len());
+            for child in self.children.iter_mut() {
+                let child = child.execute(world);
+                world
+                    .resource_mut::<ActionOutputs>()
+                    .add::<CreateEntity>(child);
+                children.push(child);
+            }
+
+            let children = AddChildren::new(entity, children).execute(world);
+            world
+                .resource_mut::<ActionOutputs>()
+                .add::<AddChildren>(children);
         }
 
         entity
     }
 }
 
+/// Creates `count` empty entities with `World::create_batch`. Observers get
+/// the whole batch as one output.
+#[derive(Debug, Clone)]
+pub struct SpawnBatch {
+    count: usize,
+}
+
+impl SpawnBatch {
+    pub fn new(count: usize) -> Self {
+        Self { count }
+    }
+}
+
+impl Action for SpawnBatch {
+    type Output = Vec<Entity>;
+    const PRIORITY: u32 = CreateEntity::PRIORITY;
+
+    fn execute(&mut self, world: &mut World) -> Self::Output {
+        world.create_batch(self.count)
+    }
+}
+
 pub struct AddComponent<C: Component> {
     entity: Entity,
     component: Option<C>,
@@ -65,11 +207,20 @@ impl<C: Component> AddComponent<C> {
             component: Some(component),
         }
     }
+
+    /// `None` once the component has been added.
+    pub(crate) fn try_clone(&self) -> Option<Self>
+    where
+        C: Clone,
+    {
+        let component = self.component.clone()?;
+        Some(Self::new(self.entity, component))
+    }
 }
 
 impl<C: Component> Action for AddComponent<C> {
     type Output = Entity;
-    const PRIORITY: u32 = CreateEntity::PRIORITY - 1;
+    const PRIORITY: u32 = CreateEntity::PRIORITY - 10;
 
     fn execute(&mut self, world: &mut crate::world::World) -> Self::Output {
         if let Some(component) = self.component.take() {
@@ -78,6 +229,18 @@ impl<C: Component> Action for AddComponent<C> {
 
         self.entity
     }
+
+    fn register(world: &mut World) {
+        world.register::<C>();
+    }
+
+    fn referenced_entities(&self) -> Vec<Entity> {
+        vec![self.entity]
+    }
+
+    fn map_entities(&mut self, mapper: &EntityMapper) {
+        self.entity = mapper.map(self.entity);
+    }
 }
 
 impl<C: Component> Debug for AddComponent<C> {
@@ -88,6 +251,169 @@ impl<C: Component> Debug for AddComponent<C> {
     }
 }
 
+enum SetValue<C: Component> {
+    Value(C),
+    Update(Box<dyn FnOnce(&mut C) + Send>),
+}
+
+/// Overwrites a component the entity already has, in place. Unlike
+/// `AddComponent` the entity never changes archetype; entities without the
+/// component are skipped.
+pub struct SetComponent<C: Component> {
+    entity: Entity,
+    value: Option<SetValue<C>>,
+}
+
+impl<C: Component> SetComponent<C> {
+    pub fn new(entity: Entity, value: C) -> Self {
+        Self {
+            entity,
+            value: Some(SetValue::Value(value)),
+        }
+    }
+
+    /// Updates the current value with `update` instead of replacing it.
+    pub fn with(entity: Entity, update: impl FnOnce(&mut C) + Send + 'static) -> Self {
+        Self {
+            entity,
+            value: Some(SetValue::Update(Box::new(update))),
+        }
+    }
+
+    /// Only values can be cloned, not updates.
+    pub(crate) fn try_clone(&self) -> Option<Self>
+    where
+        C: Clone,
+    {
+        match &self.value {
+            Some(SetValue::Value(value)) => Some(Self::new(self.entity, value.clone())),
+            _ => None,
+        }
+    }
+}
+
+impl<C: Component> Debug for SetComponent<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SetComponent")
+            .field("entity", &self.entity)
+            .finish()
+    }
+}
+
+impl<C: Component> Action for SetComponent<C> {
+    type Output = Entity;
+    const PRIORITY: u32 = AddComponent::<C>::PRIORITY - 5;
+
+    fn execute(&mut self, world: &mut World) -> Self::Output {
+        let Some(value) = self.value.take() else {
+            return self.entity;
+        };
+
+        let capture = world
+            .component_meta::<C>()
+            .and_then(|meta| meta.extension::<ChangeCapture<C>>())
+            .map(|capture| capture.0);
+
+        let old = {
+            let Some(mut current) = world.component_mut::<C>(self.entity) else {
+                return self.entity;
+            };
+
+            let old = capture.map(|clone| clone(&current));
+            match value {
+                SetValue::Value(value) => *current = value,
+                SetValue::Update(update) => update(&mut current),
+            }
+            old
+        };
+
+        world
+            .resource_mut::<ActionOutputs>()
+            .add::<ComponentChanged<C>>(Change::new(self.entity, old));
+
+        let id = world.component_id::<C>();
+        world.reindex(self.entity, &[id]);
+
+        self.entity
+    }
+
+    fn skip(&self, world: &World) -> bool {
+        !world.has::<C>(self.entity)
+    }
+
+    fn register(world: &mut World) {
+        world.register::<C>();
+    }
+
+    fn referenced_entities(&self) -> Vec<Entity> {
+        vec![self.entity]
+    }
+
+    fn map_entities(&mut self, mapper: &EntityMapper) {
+        self.entity = mapper.map(self.entity);
+    }
+}
+
+/// Component meta extension added by `World::capture_changes`.
+pub struct ChangeCapture<C: Component>(pub fn(&C) -> C);
+
+/// A value of `C` overwritten by `SetComponent`. `old` is only kept for
+/// components opted in with `World::capture_changes`.
+#[derive(Debug)]
+pub struct Change<C: Component> {
+    entity: Entity,
+    old: Option<C>,
+}
+
+impl<C: Component> Change<C> {
+    pub fn new(entity: Entity, old: Option<C>) -> Self {
+        Self { entity, old }
+    }
+
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    pub fn old(&self) -> Option<&C> {
+        self.old.as_ref()
+    }
+}
+
+/// Observer channel for `SetComponent<C>` overwrites, carrying the old value
+/// when captured.
+pub struct ComponentChanged<C: Component> {
+    change: Option<Change<C>>,
+}
+
+impl<C: Component> ComponentChanged<C> {
+    pub fn new(change: Change<C>) -> Self {
+        Self {
+            change: Some(change),
+        }
+    }
+}
+
+impl<C: Component> Debug for ComponentChanged<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ComponentChanged")
+            .field("entity", &self.change.as_ref().map(|change| change.entity))
+            .finish()
+    }
+}
+
+impl<C: Component> Action for ComponentChanged<C> {
+    type Output = Change<C>;
+    const PRIORITY: u32 = SetComponent::<C>::PRIORITY;
+
+    fn execute(&mut self, _: &mut World) -> Self::Output {
+        self.change.take().expect("ComponentChanged executed twice")
+    }
+
+    fn register(world: &mut World) {
+        world.register::<C>();
+    }
+}
+
 pub struct RemoveComponent<C: Component> {
     entity: Entity,
     _marker: std::marker::PhantomData<C>,
@@ -102,6 +428,12 @@ impl<C: Component> RemoveComponent<C> {
     }
 }
 
+impl<C: Component> Clone for RemoveComponent<C> {
+    fn clone(&self) -> Self {
+        Self::new(self.entity)
+    }
+}
+
 impl<C: Component> Debug for RemoveComponent<C> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("RemoveComponent")
@@ -112,10 +444,13 @@ impl<C: Component> Debug for RemoveComponent<C> {
 
 impl<C: Component> Action for RemoveComponent<C> {
     type Output = Entity;
-    const PRIORITY: u32 = AddComponent::<C>::PRIORITY - 1;
+    const PRIORITY: u32 = AddComponent::<C>::PRIORITY - 10;
 
     fn execute(&mut self, world: &mut crate::world::World) -> Self::Output {
         world.remove_component::<C>(self.entity);
+        world
+            .resource_mut::<ActionOutputs>()
+            .add::<ComponentRemoved<C>>(Removal::new(self.entity, RemovalCause::Removed));
 
         self.entity
     }
@@ -123,16 +458,171 @@ impl<C: Component> Action for RemoveComponent<C> {
     fn skip(&self, world: &World) -> bool {
         !world.has::<C>(self.entity)
     }
+
+    fn register(world: &mut World) {
+        world.register::<C>();
+    }
+
+    fn referenced_entities(&self) -> Vec<Entity> {
+        vec![self.entity]
+    }
+
+    fn map_entities(&mut self, mapper: &EntityMapper) {
+        self.entity = mapper.map(self.entity);
+    }
 }
 
-#[derive(Debug)]
+/// Removes every component of `B` the entity has with a single archetype
+/// move. Observers see `RemoveComponent` and `ComponentRemoved` outputs per
+/// component removed.
+pub struct RemoveBundle<B: Bundle> {
+    entity: Entity,
+    _marker: std::marker::PhantomData<fn() -> B>,
+}
+
+impl<B: Bundle> RemoveBundle<B> {
+    pub fn new(entity: Entity) -> Self {
+        Self {
+            entity,
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<B: Bundle> Clone for RemoveBundle<B> {
+    fn clone(&self) -> Self {
+        Self::new(self.entity)
+    }
+}
+
+impl<B: Bundle> Debug for RemoveBundle<B> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RemoveBundle")
+            .field("entity", &self.entity)
+            .finish()
+    }
+}
+
+impl<B: Bundle> Action for RemoveBundle<B> {
+    type Output = EntityEdit;
+    const PRIORITY: u32 = CreateEntity::PRIORITY - 20;
+
+    fn execute(&mut self, world: &mut World) -> Self::Output {
+        let components = B::component_ids(world.components());
+        world
+            .remove_values(self.entity, &components)
+            .unwrap_or_else(|| EntityEdit::new(self.entity))
+    }
+
+    fn skip(&self, world: &World) -> bool {
+        !world.entities().contains(self.entity)
+    }
+
+    fn register(world: &mut World) {
+        B::register(world);
+    }
+
+    fn referenced_entities(&self) -> Vec<Entity> {
+        vec![self.entity]
+    }
+
+    fn map_entities(&mut self, mapper: &EntityMapper) {
+        self.entity = mapper.map(self.entity);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RemovalCause {
+    /// The component was removed and the entity is still alive.
+    Removed,
+    EntityDeleted,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Removal {
+    entity: Entity,
+    cause: RemovalCause,
+}
+
+impl Removal {
+    pub fn new(entity: Entity, cause: RemovalCause) -> Self {
+        Self { entity, cause }
+    }
+
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    pub fn cause(&self) -> RemovalCause {
+        self.cause
+    }
+}
+
+/// Observer channel for removals of `C` along with why they happened.
+/// `RemoveComponent<C>` observers still see every removal, without a cause.
+///
+/// Observers run in ascending action priority within a flush, so when an
+/// entity is deleted its `DeleteEntity` observers run first, followed by the
+/// `RemoveComponent<C>` and `ComponentRemoved<C>` observers of each of its
+/// components. The two removal channels share a priority and run in the
+/// order their observers were added.
+pub struct ComponentRemoved<C: Component> {
+    removal: Removal,
+    _marker: std::marker::PhantomData<C>,
+}
+
+impl<C: Component> ComponentRemoved<C> {
+    pub fn new(removal: Removal) -> Self {
+        Self {
+            removal,
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<C: Component> Debug for ComponentRemoved<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ComponentRemoved")
+            .field("removal", &self.removal)
+            .finish()
+    }
+}
+
+impl<C: Component> Action for ComponentRemoved<C> {
+    type Output = Removal;
+    const PRIORITY: u32 = RemoveComponent::<C>::PRIORITY;
+
+    fn execute(&mut self, _: &mut World) -> Self::Output {
+        self.removal
+    }
+
+    fn register(world: &mut World) {
+        world.register::<C>();
+    }
+}
+
+/// Deletes an entity and, by default, its whole subtree. Every entity
+/// actually deleted gets its own output, all delivered to `DeleteEntity`
+/// observers in one batch with the deleted root first, and the removal
+/// observers of each one's components run too. Use `orphans` to reparent
+/// the children instead of deleting them.
+#[derive(Debug, Clone)]
 pub struct DeleteEntity {
     entity: Entity,
+    orphans: OrphanPolicy,
 }
 
 impl DeleteEntity {
     pub fn new(entity: Entity) -> Self {
-        Self { entity }
+        Self {
+            entity,
+            orphans: OrphanPolicy::default(),
+        }
+    }
+
+    pub fn orphans(mut self, policy: OrphanPolicy) -> Self {
+        self.orphans = policy;
+        self
     }
 }
 
@@ -141,28 +631,293 @@ impl Action for DeleteEntity {
     const PRIORITY: u32 = CreateEntity::PRIORITY - 100;
 
     fn execute(&mut self, world: &mut crate::world::World) -> Self::Output {
-        world.delete(self.entity);
+        let deleted = world.delete_with_policy(self.entity, self.orphans);
+
+        let mut outputs = world.resource_mut::<ActionOutputs>();
+        for entity in deleted.into_iter().filter(|e| *e != self.entity) {
+            outputs.add::<DeleteEntity>(entity);
+        }
 
         self.entity
     }
+
+    fn skip(&self, world: &World) -> bool {
+        !world.entities().contains(self.entity)
+    }
+
+    fn referenced_entities(&self) -> Vec<Entity> {
+        vec![self.entity]
+    }
+
+    fn map_entities(&mut self, mapper: &EntityMapper) {
+        self.entity = mapper.map(self.entity);
+    }
+}
+
+/// Deletes every entity with `World::clear_entities`, e.g. when switching
+/// scenes. Runs after the other actions of its flush. Outputs the number of
+/// entities deleted.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClearEntities;
+
+impl Action for ClearEntities {
+    type Output = usize;
+    const PRIORITY: u32 = DeleteEntity::PRIORITY - 10;
+
+    fn execute(&mut self, world: &mut World) -> Self::Output {
+        world.clear_entities()
+    }
+}
+
+/// Removes the resource `R` at flush time. Outputs the removed value;
+/// skipped if the world doesn't have `R`.
+pub struct RemoveResource<R: Resource>(PhantomData<R>);
+
+impl<R: Resource> RemoveResource<R> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<R: Resource> Default for RemoveResource<R> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R: Resource> Action for RemoveResource<R> {
+    type Output = R;
+
+    fn execute(&mut self, world: &mut World) -> Self::Output {
+        world
+            .remove_resource::<R>()
+            .expect("resource was checked by skip")
+    }
+
+    fn skip(&self, world: &World) -> bool {
+        !world.contains_resource::<R>()
+    }
+}
+
+enum ComponentEdit {
+    Insert {
+        id: fn(&World) -> ComponentId,
+        value: Blob,
+        notify_existing: bool,
+        added: fn(&mut ActionOutputs, Entity),
+    },
+    Remove {
+        id: fn(&World) -> ComponentId,
+        removed: fn(&mut ActionOutputs, Entity),
+    },
+}
+
+/// Adds, removes and overwrites several components of an entity with a single
+/// archetype move. AddComponent/RemoveComponent outputs are only emitted once
+/// the whole edit has been applied.
+pub struct EditEntity {
+    entity: Entity,
+    edits: Vec<ComponentEdit>,
+}
+
+impl EditEntity {
+    pub fn new(entity: Entity) -> Self {
+        Self {
+            entity,
+            edits: Vec::new(),
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn add<C: Component>(self, component: C) -> Self {
+        self.insert(component, true)
+    }
+
+    /// Overwrites the component if present, otherwise behaves like `add`.
+    pub fn set<C: Component>(self, component: C) -> Self {
+        self.insert(component, false)
+    }
+
+    pub fn remove<C: Component>(mut self) -> Self {
+        self.edits.push(ComponentEdit::Remove {
+            id: |world| world.component_id::<C>(),
+            removed: |outputs, entity| {
+                outputs.add::<RemoveComponent<C>>(entity);
+                outputs.add::<ComponentRemoved<C>>(Removal::new(entity, RemovalCause::Removed));
+            },
+        });
+
+        self
+    }
+
+    fn insert<C: Component>(mut self, component: C, notify_existing: bool) -> Self {
+        let mut value = Blob::new::<C>();
+        value.push(component);
+
+        self.edits.push(ComponentEdit::Insert {
+            id: |world| world.component_id::<C>(),
+            value,
+            notify_existing,
+            added: |outputs, entity| outputs.add::<AddComponent<C>>(entity),
+        });
+
+        self
+    }
+
+    /// Clones the action for a recording, if every inserted value is of a
+    /// component registered as cloneable.
+    pub(crate) fn record(&self, world: &mut World) -> Option<Self> {
+        let mut edits = Vec::with_capacity(self.edits.len());
+        for edit in &self.edits {
+            edits.push(match edit {
+                ComponentEdit::Insert {
+                    id,
+                    value,
+                    notify_existing,
+                    added,
+                } => {
+                    let clone = world.components().meta(id(world)).clone_fn()?;
+                    ComponentEdit::Insert {
+                        id: *id,
+                        value: clone(value, 0)?,
+                        notify_existing: *notify_existing,
+                        added: *added,
+                    }
+                }
+                ComponentEdit::Remove { id, removed } => ComponentEdit::Remove {
+                    id: *id,
+                    removed: *removed,
+                },
+            });
+        }
+
+        Some(Self {
+            entity: self.entity,
+            edits,
+        })
+    }
+}
+
+impl Debug for EditEntity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EditEntity")
+            .field("entity", &self.entity)
+            .finish()
+    }
+}
+
+impl Action for EditEntity {
+    type Output = EntityEdit;
+    const PRIORITY: u32 = CreateEntity::PRIORITY - 10;
+
+    fn execute(&mut self, world: &mut World) -> Self::Output {
+        let mut inserts = Vec::<(ComponentId, Blob)>::new();
+        let mut removes = Vec::<ComponentId>::new();
+        let mut notify_added = Vec::<(ComponentId, bool, fn(&mut ActionOutputs, Entity))>::new();
+        let mut notify_removed = Vec::<(ComponentId, fn(&mut ActionOutputs, Entity))>::new();
+
+        for edit in self.edits.drain(..) {
+            match edit {
+                ComponentEdit::Insert {
+                    id,
+                    value,
+                    notify_existing,
+                    added,
+                } => {
+                    let id = id(world);
+                    inserts.retain(|(c, _)| *c != id);
+                    removes.retain(|c| *c != id);
+                    notify_added.retain(|(c, _, _)| *c != id);
+                    notify_removed.retain(|(c, _)| *c != id);
+                    inserts.push((id, value));
+                    notify_added.push((id, notify_existing, added));
+                }
+                ComponentEdit::Remove { id, removed } => {
+                    let id = id(world);
+                    inserts.retain(|(c, _)| *c != id);
+                    notify_added.retain(|(c, _, _)| *c != id);
+                    if !removes.contains(&id) {
+                        removes.push(id);
+                        notify_removed.push((id, removed));
+                    }
+                }
+            }
+        }
+
+        let edit = match world.edit_entity(self.entity, inserts, &removes) {
+            Some(edit) => edit,
+            None => return EntityEdit::new(self.entity),
+        };
+
+        let mut outputs = world.resource_mut::<ActionOutputs>();
+        for (id, notify_existing, added) in notify_added {
+            if edit.added().contains(&id) || (notify_existing && edit.replaced().contains(&id)) {
+                added(&mut outputs, self.entity);
+            }
+        }
+
+        for (id, removed) in notify_removed {
+            if edit.removed().contains(&id) {
+                removed(&mut outputs, self.entity);
+            }
+        }
+
+        edit
+    }
+
+    fn skip(&self, world: &World) -> bool {
+        !world.entities().contains(self.entity)
+    }
+
+    fn referenced_entities(&self) -> Vec<Entity> {
+        vec![self.entity]
+    }
+
+    fn map_entities(&mut self, mapper: &EntityMapper) {
+        self.entity = mapper.map(self.entity);
+    }
 }
 
+/// Makes `entity` a child of `parent`, or a root when `parent` is `None`.
+/// Skipped if either entity is no longer alive, so a handle kept past a
+/// delete can't reach whatever reuses its id.
+#[derive(Clone)]
 pub struct SetParent {
     entity: Entity,
     parent: Option<Entity>,
+    parent_name: Option<String>,
 }
 
 impl SetParent {
     pub fn new(entity: Entity, parent: Option<Entity>) -> Self {
-        Self { entity, parent }
+        Self {
+            entity,
+            parent,
+            parent_name: None,
+        }
+    }
+
+    /// Parents `entity` to the entity named `parent`, looked up when the
+    /// action runs. Skipped, with a warning traced, if no single entity has
+    /// that name.
+    pub fn by_name(entity: Entity, parent: impl Into<String>) -> Self {
+        Self {
+            entity,
+            parent: None,
+            parent_name: Some(parent.into()),
+        }
     }
 }
 
 impl Action for SetParent {
     type Output = Entity;
-    const PRIORITY: u32 = CreateEntity::PRIORITY - 3;
+    const PRIORITY: u32 = CreateEntity::PRIORITY - 30;
 
     fn execute(&mut self, world: &mut crate::world::World) -> Self::Output {
+        if let Some(name) = &self.parent_name {
+            self.parent = world.find_named(name);
+        }
+
         world.set_parent(self.entity, self.parent);
 
         world
@@ -171,8 +926,32 @@ impl Action for SetParent {
 
         self.entity
     }
+
+    fn skip(&self, world: &World) -> bool {
+        if !world.entities().contains(self.entity) {
+            return true;
+        }
+
+        match (&self.parent_name, self.parent) {
+            (Some(name), _) => world.resolve_named("SetParent", name).is_none(),
+            (None, Some(parent)) => !world.entities().contains(parent),
+            (None, None) => false,
+        }
+    }
+
+    fn referenced_entities(&self) -> Vec<Entity> {
+        [self.entity].into_iter().chain(self.parent).collect()
+    }
+
+    fn map_entities(&mut self, mapper: &EntityMapper) {
+        self.entity = mapper.map(self.entity);
+        self.parent = self.parent.map(|parent| mapper.map(parent));
+    }
 }
 
+/// Adds `children` to `entity`. Skipped if `entity` is no longer alive;
+/// dead children are left out.
+#[derive(Clone)]
 pub struct AddChildren {
     entity: Entity,
     children: Vec<Entity>,
@@ -186,7 +965,7 @@ impl AddChildren {
 
 impl Action for AddChildren {
     type Output = Vec<Entity>;
-    const PRIORITY: u32 = CreateEntity::PRIORITY - 3;
+    const PRIORITY: u32 = CreateEntity::PRIORITY - 30;
 
     fn execute(&mut self, world: &mut crate::world::World) -> Self::Output {
         for child in self.children.iter() {
@@ -199,8 +978,27 @@ impl Action for AddChildren {
 
         self.children.clone()
     }
+
+    fn skip(&self, world: &World) -> bool {
+        !world.entities().contains(self.entity)
+    }
+
+    fn referenced_entities(&self) -> Vec<Entity> {
+        [self.entity]
+            .into_iter()
+            .chain(self.children.iter().copied())
+            .collect()
+    }
+
+    fn map_entities(&mut self, mapper: &EntityMapper) {
+        self.entity = mapper.map(self.entity);
+        for child in self.children.iter_mut() {
+            *child = mapper.map(*child);
+        }
+    }
 }
 
+#[derive(Clone)]
 pub struct RemoveChildren {
     entity: Entity,
     children: Vec<Entity>,
@@ -214,7 +1012,7 @@ impl RemoveChildren {
 
 impl Action for RemoveChildren {
     type Output = Entity;
-    const PRIORITY: u32 = CreateEntity::PRIORITY - 3;
+    const PRIORITY: u32 = CreateEntity::PRIORITY - 30;
 
     fn execute(&mut self, world: &mut crate::world::World) -> Self::Output {
         for child in self.children.iter() {
@@ -227,6 +1025,20 @@ impl Action for RemoveChildren {
 
         self.entity
     }
+
+    fn referenced_entities(&self) -> Vec<Entity> {
+        [self.entity]
+            .into_iter()
+            .chain(self.children.iter().copied())
+            .collect()
+    }
+
+    fn map_entities(&mut self, mapper: &EntityMapper) {
+        self.entity = mapper.map(self.entity);
+        for child in self.children.iter_mut() {
+            *child = mapper.map(*child);
+        }
+    }
 }
 
 pub struct HierarchyChange {
@@ -241,9 +1053,13 @@ impl HierarchyChange {
 
 impl Action for HierarchyChange {
     type Output = Entity;
-    const PRIORITY: u32 = CreateEntity::PRIORITY - 4;
+    const PRIORITY: u32 = CreateEntity::PRIORITY - 40;
 
     fn execute(&mut self, _: &mut crate::world::World) -> Self::Output {
         self.entity
     }
+
+    fn referenced_entities(&self) -> Vec<Entity> {
+        vec![self.entity]
+    }
 }
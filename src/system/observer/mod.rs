@@ -1,5 +1,9 @@
-use super::{ArgItem, SystemArg};
+use super::{local::SystemLocals, ArgItem, System, SystemArg};
 use crate::{
+    schedule::{
+        label::{DynLabel, LabelKey},
+        SchedulePhase,
+    },
     storage::{blob::Blob, sparse::SparseMap},
     world::{
         meta::{AccessMeta, AccessType},
@@ -7,17 +11,29 @@ use crate::{
         World,
     },
 };
-use std::any::TypeId;
+use std::{
+    any::TypeId,
+    time::{Duration, Instant},
+};
 
 pub mod action;
 pub mod builtin;
+pub mod order;
 
 pub use action::*;
+pub use order::{ObserverOrderBuilder, ObserverOrderError};
+
+use order::ObserverOrder;
 
 pub struct Observer<A: Action> {
     function: Box<dyn Fn(&[A::Output], &World)>,
     reads: Vec<AccessType>,
     writes: Vec<AccessType>,
+    registrars: Vec<fn(&mut World)>,
+    locals: SystemLocals,
+    priority: i32,
+    before: Vec<Observer<A>>,
+    after: Vec<Observer<A>>,
 }
 
 impl<A: Action> Observer<A> {
@@ -30,9 +46,47 @@ impl<A: Action> Observer<A> {
             function: Box::new(function),
             reads,
             writes,
+            registrars: vec![A::register],
+            locals: SystemLocals::default(),
+            priority: 0,
+            before: vec![],
+            after: vec![],
         }
     }
 
+    /// Observers of the same action run by ascending priority, ties in the
+    /// order they were added. Separate from `Action::PRIORITY`, which orders
+    /// the actions themselves.
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// The observer with the ones chained to it by `before` and `after`, in
+    /// the order they run. They all take this observer's priority, so the
+    /// chain stays together.
+    fn into_chain(mut self) -> Vec<Observer<A>> {
+        let before = std::mem::take(&mut self.before);
+        let after = std::mem::take(&mut self.after);
+        let priority = self.priority;
+
+        let mut chain = before
+            .into_iter()
+            .flat_map(Observer::into_chain)
+            .collect::<Vec<_>>();
+        chain.push(self);
+        chain.extend(after.into_iter().flat_map(Observer::into_chain));
+        for observer in &mut chain {
+            observer.priority = priority;
+        }
+
+        chain
+    }
+
     pub fn reads(&self) -> &[AccessType] {
         &self.reads
     }
@@ -41,18 +95,158 @@ impl<A: Action> Observer<A> {
         &self.writes
     }
 
+    pub fn registrars(&self) -> &[fn(&mut World)] {
+        &self.registrars
+    }
+
     pub fn run(&self, outputs: &[A::Output], world: &World) {
+        let _borrows = world.resources().enter();
+        let _locals = self.locals.enter();
         (self.function)(outputs, world);
     }
+
+    /// Hands the observer only the outputs `keep` accepts, and skips it
+    /// when none are left.
+    pub fn filter(self, keep: impl Fn(&A::Output) -> bool + 'static) -> Self
+    where
+        A::Output: Clone,
+    {
+        let function = self.function;
+        Self {
+            function: Box::new(move |outputs: &[A::Output], world: &World| {
+                let kept = outputs
+                    .iter()
+                    .filter(|output| keep(output))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if !kept.is_empty() {
+                    function(&kept, world);
+                }
+            }),
+            reads: self.reads,
+            writes: self.writes,
+            registrars: self.registrars,
+            locals: self.locals,
+            priority: self.priority,
+            before: self.before,
+            after: self.after,
+        }
+    }
+}
+
+impl<A: Action> IntoObserver<A, ()> for Observer<A> {
+    fn into_observer(self) -> Observer<A> {
+        self
+    }
+}
+
+/// Where, within the label's schedule, the outputs held for phase-scheduled
+/// observers are delivered.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryOrder {
+    /// Before every other system of the label.
+    #[default]
+    First,
+    /// Wherever the schedule puts it, as a system that reads the world.
+    Unordered,
+}
+
+type AddDelivery = Box<dyn FnOnce(&mut World, System)>;
+
+/// Runs a blob of observers on a blob of action outputs.
+type ObserverExecutor = Box<dyn Fn(&Blob, &Blob, &World) + Send + Sync>;
+
+/// Observers delivered by a system in a phase and label, instead of during
+/// flush.
+pub struct ScheduledDelivery {
+    key: DeliveryKey,
+    order: DeliveryOrder,
+    clone: fn(&Blob) -> Blob,
+    add: AddDelivery,
+}
+
+impl ScheduledDelivery {
+    pub fn key(&self) -> DeliveryKey {
+        self.key
+    }
+
+    pub fn order(&self) -> DeliveryOrder {
+        self.order
+    }
+
+    /// Adds the system delivering the outputs to its phase and label.
+    pub fn add_to(self, world: &mut World, system: System) {
+        (self.add)(world, system)
+    }
+}
+
+/// Phase and label a scheduled delivery runs in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DeliveryKey {
+    pub phase: TypeId,
+    pub label: LabelKey,
 }
 
 pub struct Observers<A: Action> {
     systems: Vec<Observer<A>>,
+    deferred: bool,
+    scheduled: Option<ScheduledDelivery>,
 }
 
 impl<A: Action> Observers<A> {
     pub fn new() -> Self {
-        Self { systems: vec![] }
+        Self {
+            systems: vec![],
+            deferred: false,
+            scheduled: None,
+        }
+    }
+
+    /// Keeps these observers out of `World::flush`. Their outputs are held
+    /// until `World::run_deferred_observers` is called.
+    pub fn deferred(mut self) -> Self {
+        self.deferred = true;
+        self.scheduled = None;
+        self
+    }
+
+    pub fn is_deferred(&self) -> bool {
+        self.deferred
+    }
+
+    /// Keeps these observers out of `World::flush`. Their outputs are held,
+    /// across flushes, until a system added to `phase` and `label` delivers
+    /// them, first in the label unless `delivery_order` says otherwise.
+    pub fn deliver_in<P: SchedulePhase, L: DynLabel>(mut self, phase: P, label: L) -> Self
+    where
+        A::Output: Clone,
+    {
+        let key = DeliveryKey {
+            phase: TypeId::of::<P>(),
+            label: label.key(),
+        };
+        let label = label.key();
+
+        self.deferred = false;
+        self.scheduled = Some(ScheduledDelivery {
+            key,
+            order: DeliveryOrder::First,
+            clone: clone_outputs::<A>,
+            add: Box::new(move |world, system| world.add_system(phase, label, system)),
+        });
+        self
+    }
+
+    /// Order of the delivery set with `deliver_in`.
+    pub fn delivery_order(mut self, order: DeliveryOrder) -> Self {
+        if let Some(scheduled) = &mut self.scheduled {
+            scheduled.order = order;
+        }
+        self
+    }
+
+    pub fn scheduled(&self) -> Option<&ScheduledDelivery> {
+        self.scheduled.as_ref()
     }
 
     pub fn add_system<M>(mut self, system: impl IntoObserver<A, M>) -> Self {
@@ -61,6 +255,19 @@ impl<A: Action> Observers<A> {
         self
     }
 
+    /// Adds `system` to run among the other observers of `A` by ascending
+    /// `priority`, wherever they were added from. `add_system` uses 0.
+    pub fn add_system_with_priority<M>(
+        mut self,
+        priority: i32,
+        system: impl IntoObserver<A, M>,
+    ) -> Self {
+        self.systems
+            .push(system.into_observer().with_priority(priority));
+
+        self
+    }
+
     pub fn take(&mut self) -> Vec<Observer<A>> {
         std::mem::take(&mut self.systems)
     }
@@ -68,6 +275,26 @@ impl<A: Action> Observers<A> {
 
 pub trait IntoObserver<A: Action, M> {
     fn into_observer(self) -> Observer<A>;
+
+    /// Adds `observer` to the same action, running right before this one.
+    fn before<Marker>(self, observer: impl IntoObserver<A, Marker>) -> Observer<A>
+    where
+        Self: Sized,
+    {
+        let mut this = self.into_observer();
+        this.before.push(observer.into_observer());
+        this
+    }
+
+    /// Adds `observer` to the same action, running right after this one.
+    fn after<Marker>(self, observer: impl IntoObserver<A, Marker>) -> Observer<A>
+    where
+        Self: Sized,
+    {
+        let mut this = self.into_observer();
+        this.after.push(observer.into_observer());
+        this
+    }
 }
 
 impl<A: Action, F> IntoObserver<A, F> for F
@@ -85,101 +312,411 @@ where
     }
 }
 
+fn sort_observers<A: Action>(systems: &mut Blob) {
+    let mut observers = systems.to_vec::<Box<Observer<A>>>();
+    observers.sort_by_key(|observer| observer.priority);
+    systems.extend(observers);
+}
+
+/// Outputs handed to deferred observers per call while draining.
+pub const DEFERRED_CHUNK: usize = 64;
+
+fn clone_outputs<A: Action>(outputs: &Blob) -> Blob
+where
+    A::Output: Clone,
+{
+    let outputs = outputs.as_slice::<A::Output>();
+    let mut cloned = Blob::with_capacity::<A::Output>(outputs.len());
+    for output in outputs {
+        cloned.push(output.clone());
+    }
+
+    cloned
+}
+
+/// When held observers get their outputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Holding {
+    /// On `World::run_deferred_observers`.
+    Deferred,
+    Scheduled(DeliveryKey),
+}
+
+/// Observers kept out of flush, with the outputs held for them.
+struct HeldObservers {
+    holding: Holding,
+    systems: Blob,
+    pending: Blob,
+    /// Copies outputs for this group when another group takes them too.
+    /// `None` for deferred observers, which take the originals.
+    clone: Option<fn(&Blob) -> Blob>,
+}
+
 pub struct ObserverSystems {
-    executor: Box<dyn Fn(Blob, &Blob, &World) + Send + Sync>,
+    executor: ObserverExecutor,
+    /// Stable-sorts a blob of observers by their priority.
+    sort: fn(&mut Blob),
+    output: TypeId,
     systems: Blob,
+    held: Vec<HeldObservers>,
     priority: u32,
+    registrars: Vec<fn(&mut World)>,
+    name: &'static str,
+    /// Set once the action produced outputs for these observers.
+    delivered: bool,
 }
 
 impl ObserverSystems {
     pub fn new<A: Action>() -> Self {
         Self {
-            executor: Box::new(move |mut outputs, systems, world| {
-                let outputs = outputs.to_vec();
+            executor: Box::new(move |outputs, systems, world| {
+                debug_assert!(
+                    outputs.type_name() == std::any::type_name::<A::Output>(),
+                    "observers of {} given {} outputs",
+                    std::any::type_name::<A>(),
+                    outputs.type_name()
+                );
+                let outputs = outputs.as_slice::<A::Output>();
 
                 for system in systems.iter_mut::<Box<Observer<A>>>() {
-                    system.run(&outputs, world);
+                    system.run(outputs, world);
                 }
             }),
+            sort: sort_observers::<A>,
+            output: TypeId::of::<A::Output>(),
             systems: Blob::new::<Box<Observer<A>>>(),
+            held: Vec::new(),
             priority: A::PRIORITY,
+            registrars: Vec::new(),
+            name: std::any::type_name::<A>(),
+            delivered: false,
         }
     }
 
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// True if any observer was added, immediate or held.
+    pub fn has_observers(&self) -> bool {
+        !self.systems.is_empty() || self.held.iter().any(|held| !held.systems.is_empty())
+    }
+
+    /// True once the action produced outputs for these observers.
+    pub fn delivered(&self) -> bool {
+        self.delivered
+    }
+
     pub fn priority(&self) -> u32 {
         self.priority
     }
 
+    pub fn registrars(&self) -> &[fn(&mut World)] {
+        &self.registrars
+    }
+
     pub fn add_observer<A: Action>(&mut self, observer: Observer<A>) {
-        self.systems.push(Box::new(observer));
+        self.add_observers(vec![observer]);
     }
 
     pub fn add_observers<A: Action>(&mut self, observers: Vec<Observer<A>>) {
-        for observer in observers {
-            self.add_observer(observer);
+        for observer in observers.into_iter().flat_map(Observer::into_chain) {
+            self.registrars.extend_from_slice(observer.registrars());
+            self.systems.push(Box::new(observer));
+        }
+
+        (self.sort)(&mut self.systems);
+    }
+
+    pub fn add_deferred<A: Action>(&mut self, observers: Vec<Observer<A>>) {
+        self.add_held(Holding::Deferred, None, observers);
+    }
+
+    /// Adds observers delivered by a scheduled system. Returns true if
+    /// `key` had no observers of this channel yet, so the system delivering
+    /// them still has to be added.
+    pub fn add_scheduled<A: Action>(
+        &mut self,
+        key: DeliveryKey,
+        clone: fn(&Blob) -> Blob,
+        observers: Vec<Observer<A>>,
+    ) -> bool {
+        let holding = Holding::Scheduled(key);
+        let new = !self.held.iter().any(|held| held.holding == holding);
+        self.add_held(holding, Some(clone), observers);
+        new
+    }
+
+    fn add_held<A: Action>(
+        &mut self,
+        holding: Holding,
+        clone: Option<fn(&Blob) -> Blob>,
+        observers: Vec<Observer<A>>,
+    ) {
+        let index = match self.held.iter().position(|held| held.holding == holding) {
+            Some(index) => index,
+            None => {
+                self.held.push(HeldObservers {
+                    holding,
+                    systems: Blob::new::<Box<Observer<A>>>(),
+                    pending: Blob::new::<A::Output>(),
+                    clone,
+                });
+                self.held.len() - 1
+            }
+        };
+
+        for observer in observers.into_iter().flat_map(Observer::into_chain) {
+            self.registrars.extend_from_slice(observer.registrars());
+            self.held[index].systems.push(Box::new(observer));
         }
+
+        (self.sort)(&mut self.held[index].systems);
     }
 
-    pub fn execute(&mut self, outputs: Blob, world: &World) {
-        (self.executor)(outputs, &self.systems, world);
+    /// Runs the immediate observers and holds the outputs for the others,
+    /// if there are any.
+    pub fn execute(&mut self, outputs: ActionOutput, world: &World) {
+        assert!(
+            outputs.output_type() == self.output,
+            "observers of {} given {} outputs of {}",
+            self.name,
+            outputs.output_name(),
+            outputs.action_name()
+        );
+
+        let mut outputs = outputs.into_outputs();
+        self.delivered = true;
+        (self.executor)(&outputs, &self.systems, world);
+
+        for held in &mut self.held {
+            if let Some(clone) = held.clone {
+                held.pending.append(&mut clone(&outputs));
+            }
+        }
+
+        if let Some(held) = self.held_mut(Holding::Deferred) {
+            held.pending.append(&mut outputs);
+        }
+    }
+
+    fn held_mut(&mut self, holding: Holding) -> Option<&mut HeldObservers> {
+        self.held.iter_mut().find(|held| held.holding == holding)
+    }
+
+    /// Outputs waiting for the deferred observers.
+    pub fn pending(&self) -> usize {
+        self.held
+            .iter()
+            .find(|held| held.holding == Holding::Deferred)
+            .map_or(0, |held| held.pending.len())
+    }
+
+    /// Outputs waiting for the observers delivered at `key`.
+    pub fn pending_scheduled(&self, key: DeliveryKey) -> usize {
+        self.held
+            .iter()
+            .find(|held| held.holding == Holding::Scheduled(key))
+            .map_or(0, |held| held.pending.len())
+    }
+
+    /// Hands the next chunk of pending outputs to the deferred observers.
+    /// Returns false once nothing is pending.
+    pub fn execute_deferred(&mut self, world: &World) -> bool {
+        let executor = &self.executor;
+        let Some(held) = self
+            .held
+            .iter_mut()
+            .find(|h| h.holding == Holding::Deferred)
+        else {
+            return false;
+        };
+
+        if held.pending.is_empty() {
+            return false;
+        }
+
+        let outputs = held.pending.split_front(DEFERRED_CHUNK);
+        executor(&outputs, &held.systems, world);
+
+        true
+    }
+
+    /// Hands everything held for `key` to its observers at once.
+    pub fn execute_scheduled(&mut self, key: DeliveryKey, world: &World) {
+        let executor = &self.executor;
+        let holding = Holding::Scheduled(key);
+        let Some(held) = self.held.iter_mut().find(|h| h.holding == holding) else {
+            return;
+        };
+
+        if !held.pending.is_empty() {
+            let outputs = held.pending.split_front(held.pending.len());
+            executor(&outputs, &held.systems, world);
+        }
     }
 }
 
 #[derive(Default)]
 pub struct Observables {
     observers: SparseMap<TypeId, ObserverSystems>,
+    constraints: ObserverOrder,
+    order: Vec<TypeId>,
 }
 
 impl Observables {
     pub fn new() -> Self {
         Self {
             observers: SparseMap::new(),
+            constraints: ObserverOrder::new(),
+            order: Vec::new(),
         }
     }
 
+    /// Runs the observers of `A` before those of `B`. Errors if `B` is
+    /// already ordered before `A`, directly or not.
+    pub fn order_channels<A: Action, B: Action>(&mut self) -> Result<(), ObserverOrderError> {
+        self.constraints.add::<A, B>()?;
+        self.sort();
+        Ok(())
+    }
+
+    /// Channels in the order their observers run.
+    pub fn order(&self) -> &[TypeId] {
+        &self.order
+    }
+
+    /// Observers of every channel, in the order they run.
+    pub fn channels(&self) -> impl Iterator<Item = &ObserverSystems> {
+        self.order
+            .iter()
+            .filter_map(|type_id| self.observers.get(type_id))
+    }
+
     pub fn add_observer<A: Action>(&mut self, observer: Observer<A>) {
-        let type_id = TypeId::of::<A>();
+        self.systems_mut::<A>().add_observer(observer);
+        self.sort();
+    }
 
-        if let Some(systems) = self.observers.get_mut(&type_id) {
-            systems.add_observer(observer);
-        } else {
-            let mut systems = ObserverSystems::new::<A>();
-            systems.add_observer(observer);
-            self.observers.insert(type_id, systems);
-        }
+    /// Returns the scheduled delivery of `observers` if its system still has
+    /// to be added.
+    pub fn add_observers<A: Action>(
+        &mut self,
+        mut observers: Observers<A>,
+    ) -> Option<ScheduledDelivery> {
+        let deferred = observers.is_deferred();
+        let scheduled = observers.scheduled.take();
+        let systems = self.systems_mut::<A>();
+
+        let scheduled = match scheduled {
+            Some(scheduled) => systems
+                .add_scheduled(scheduled.key, scheduled.clone, observers.take())
+                .then_some(scheduled),
+            None if deferred => {
+                systems.add_deferred(observers.take());
+                None
+            }
+            None => {
+                systems.add_observers(observers.take());
+                None
+            }
+        };
 
         self.sort();
+        scheduled
     }
 
-    pub fn add_observers<A: Action>(&mut self, mut observers: Observers<A>) {
+    fn systems_mut<A: Action>(&mut self) -> &mut ObserverSystems {
         let type_id = TypeId::of::<A>();
-
-        if let Some(systems) = self.observers.get_mut(&type_id) {
-            systems.add_observers(observers.take());
-        } else {
-            let mut systems = ObserverSystems::new::<A>();
-            systems.add_observers(observers.take());
-            self.observers.insert(type_id, systems);
+        if !self.observers.contains(&type_id) {
+            self.observers.insert(type_id, ObserverSystems::new::<A>());
         }
 
-        self.sort();
+        self.observers.get_mut(&type_id).unwrap()
+    }
+
+    pub fn registrars(&self) -> Vec<fn(&mut World)> {
+        self.observers
+            .values()
+            .iter()
+            .flat_map(|systems| systems.registrars().iter().copied())
+            .collect()
     }
 
     pub fn swap(&mut self, mut observables: Observables) {
         std::mem::swap(&mut self.observers, &mut observables.observers);
+        std::mem::swap(&mut self.constraints, &mut observables.constraints);
+        std::mem::swap(&mut self.order, &mut observables.order);
     }
 
+    /// Orders channels by action priority, ties in registration order, then
+    /// applies the configured channel orderings on top.
     pub fn sort(&mut self) {
         self.observers.sort(|a, b| a.priority().cmp(&b.priority()));
+        let channels = self.observers.keys().copied().collect::<Vec<_>>();
+        self.order = self.constraints.apply(&channels);
     }
 
     pub fn execute(&mut self, mut outputs: ActionOutputs, world: &World) {
-        for (type_id, observers) in self.observers.iter_mut() {
+        for type_id in &self.order {
             if let Some(outputs) = outputs.remove(type_id) {
+                let observers = self.observers.get_mut(type_id).unwrap();
                 observers.execute(outputs, world);
             }
         }
     }
+
+    /// Delivers the outputs of `A` held for `key`.
+    pub fn execute_scheduled<A: Action>(&mut self, key: DeliveryKey, world: &World) {
+        if let Some(systems) = self.observers.get_mut(&TypeId::of::<A>()) {
+            systems.execute_scheduled(key, world);
+        }
+    }
+
+    /// Outputs of `A` held for observers delivered at `key`.
+    pub fn pending_scheduled<A: Action>(&self, key: DeliveryKey) -> usize {
+        self.observers
+            .get(&TypeId::of::<A>())
+            .map_or(0, |systems| systems.pending_scheduled(key))
+    }
+
+    /// Outputs of `A` waiting for deferred observers.
+    pub fn pending<A: Action>(&self) -> usize {
+        self.observers
+            .get(&TypeId::of::<A>())
+            .map(|systems| systems.pending())
+            .unwrap_or(0)
+    }
+
+    pub fn pending_total(&self) -> usize {
+        self.observers
+            .values()
+            .iter()
+            .map(|systems| systems.pending())
+            .sum()
+    }
+
+    /// Delivers pending outputs to deferred observers in priority order,
+    /// a chunk at a time, until everything is delivered or `budget` has
+    /// passed since `start`. At least one chunk is delivered per call.
+    /// Returns true when nothing is left pending.
+    pub fn execute_deferred(
+        &mut self,
+        world: &World,
+        start: Instant,
+        budget: Option<Duration>,
+    ) -> bool {
+        for type_id in &self.order {
+            let observers = self.observers.get_mut(type_id).unwrap();
+            while observers.execute_deferred(world) {
+                if budget.map(|b| start.elapsed() >= b).unwrap_or(false) {
+                    return self.pending_total() == 0;
+                }
+            }
+        }
+
+        true
+    }
 }
 
 impl Resource for Observables {}
@@ -199,10 +736,14 @@ macro_rules! impl_into_observer {
 
                 AccessMeta::pick(&mut reads, &mut writes, &metas);
 
-                let system = Observer::<Act>::new(move |outputs: &[Act::Output], world: &World| {
+                let mut system = Observer::<Act>::new(move |outputs: &[Act::Output], world: &World| {
                     (self)(outputs, $($arg::get(world)),*);
                 }, reads, writes);
 
+                system.registrars.push(|world| {
+                    $($arg::register(world);)*
+                });
+
                 system
             }
         }
@@ -0,0 +1,147 @@
+use super::{Action, Observables};
+use std::{any::TypeId, collections::HashMap, fmt::Display, marker::PhantomData};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ObserverOrderError {
+    /// Ordering `before` ahead of `after` would close a cycle.
+    Cycle {
+        before: &'static str,
+        after: &'static str,
+    },
+}
+
+impl Display for ObserverOrderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ObserverOrderError::Cycle { before, after } => write!(
+                f,
+                "running {} observers before {} observers creates a cycle",
+                before, after
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ObserverOrderError {}
+
+/// Explicit orderings between observer channels, layered over action
+/// priority.
+#[derive(Default)]
+pub struct ObserverOrder {
+    edges: Vec<(TypeId, TypeId)>,
+    names: HashMap<TypeId, &'static str>,
+}
+
+impl ObserverOrder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.edges.is_empty()
+    }
+
+    pub fn add<A: Action, B: Action>(&mut self) -> Result<(), ObserverOrderError> {
+        let before = TypeId::of::<A>();
+        let after = TypeId::of::<B>();
+        if before == after || self.reaches(after, before) {
+            return Err(ObserverOrderError::Cycle {
+                before: std::any::type_name::<A>(),
+                after: std::any::type_name::<B>(),
+            });
+        }
+
+        self.names.insert(before, std::any::type_name::<A>());
+        self.names.insert(after, std::any::type_name::<B>());
+        if !self.edges.contains(&(before, after)) {
+            self.edges.push((before, after));
+        }
+
+        Ok(())
+    }
+
+    fn reaches(&self, from: TypeId, to: TypeId) -> bool {
+        let mut stack = vec![from];
+        let mut seen = vec![];
+        while let Some(next) = stack.pop() {
+            if next == to {
+                return true;
+            }
+
+            if !seen.contains(&next) {
+                seen.push(next);
+                stack.extend(
+                    self.edges
+                        .iter()
+                        .filter(|(before, _)| *before == next)
+                        .map(|(_, after)| *after),
+                );
+            }
+        }
+
+        false
+    }
+
+    /// Reorders `channels`, given in priority order, so every configured
+    /// channel runs after the channels ordered before it. Everything else
+    /// keeps its relative position.
+    pub fn apply(&self, channels: &[TypeId]) -> Vec<TypeId> {
+        if self.edges.is_empty() {
+            return channels.to_vec();
+        }
+
+        let mut remaining = channels.to_vec();
+        let mut order = Vec::with_capacity(channels.len());
+        while !remaining.is_empty() {
+            let index = remaining
+                .iter()
+                .position(|channel| {
+                    !self
+                        .edges
+                        .iter()
+                        .any(|(before, after)| after == channel && remaining.contains(before))
+                })
+                .expect("observer order is acyclic");
+            order.push(remaining.remove(index));
+        }
+
+        order
+    }
+}
+
+/// Returned by `World::configure_observer_order`.
+pub struct ObserverOrderBuilder<'a> {
+    observables: &'a mut Observables,
+}
+
+impl<'a> ObserverOrderBuilder<'a> {
+    pub fn new(observables: &'a mut Observables) -> Self {
+        Self { observables }
+    }
+
+    pub fn channel<A: Action>(self) -> ChannelOrder<'a, A> {
+        ChannelOrder {
+            observables: self.observables,
+            _marker: PhantomData,
+        }
+    }
+}
+
+pub struct ChannelOrder<'a, A: Action> {
+    observables: &'a mut Observables,
+    _marker: PhantomData<A>,
+}
+
+impl<'a, A: Action> ChannelOrder<'a, A> {
+    /// Runs the observers of `A` before those of `B` whenever both have
+    /// outputs in the same flush wave.
+    pub fn before<B: Action>(self) -> Result<ObserverOrderBuilder<'a>, ObserverOrderError> {
+        self.observables.order_channels::<A, B>()?;
+        Ok(ObserverOrderBuilder::new(self.observables))
+    }
+
+    pub fn after<B: Action>(self) -> Result<ObserverOrderBuilder<'a>, ObserverOrderError> {
+        self.observables.order_channels::<B, A>()?;
+        Ok(ObserverOrderBuilder::new(self.observables))
+    }
+}
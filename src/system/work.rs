@@ -0,0 +1,161 @@
+use super::{ArgItem, System, SystemArg};
+use crate::world::{
+    meta::{Access, AccessMeta, AccessType},
+    resource::Resource,
+    World,
+};
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Items waiting for the system made by `budgeted_system`, which works
+/// through them oldest first as far as its budget allows each run. Added by
+/// the system if it doesn't exist yet.
+pub struct WorkQueue<T: Send + Sync + 'static> {
+    /// Items and the frame they were queued in.
+    items: VecDeque<(T, u64)>,
+    frame: u64,
+    processed: usize,
+    requeued: usize,
+}
+
+impl<T: Send + Sync + 'static> WorkQueue<T> {
+    pub fn new() -> Self {
+        Self {
+            items: VecDeque::new(),
+            frame: 0,
+            processed: 0,
+            requeued: 0,
+        }
+    }
+
+    pub fn push(&mut self, item: T) {
+        self.items.push_back((item, self.frame));
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Frames the oldest pending item had been waiting at the last run, 0
+    /// when nothing is pending. Keeps growing while the budget can't keep
+    /// up with the queue.
+    pub fn oldest_age(&self) -> u64 {
+        self.items
+            .front()
+            .map_or(0, |(_, queued)| self.frame.saturating_sub(*queued))
+    }
+
+    pub fn stats(&self) -> WorkQueueStats {
+        WorkQueueStats {
+            pending: self.items.len(),
+            oldest_age: self.oldest_age(),
+            processed: self.processed,
+            requeued: self.requeued,
+        }
+    }
+
+    /// Hands items to `work` until the queue is empty or `budget` has
+    /// passed, always at least one. Items `work` gives back are queued
+    /// again after the rest, for the next run.
+    fn drain(&mut self, frame: u64, budget: Duration, mut work: impl FnMut(T) -> Option<T>) {
+        self.frame = frame;
+        self.processed = 0;
+
+        let start = Instant::now();
+        let mut requeued = Vec::new();
+        while let Some((item, _)) = self.items.pop_front() {
+            requeued.extend(work(item));
+            self.processed += 1;
+
+            if start.elapsed() >= budget {
+                break;
+            }
+        }
+
+        self.requeued = requeued.len();
+        for item in requeued {
+            self.push(item);
+        }
+    }
+}
+
+impl<T: Send + Sync + 'static> Default for WorkQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Send + Sync + 'static> Resource for WorkQueue<T> {}
+
+/// Progress of a `WorkQueue` as of its last run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WorkQueueStats {
+    pub pending: usize,
+    /// See `WorkQueue::oldest_age`.
+    pub oldest_age: u64,
+    /// Items handed to the worker in the last run.
+    pub processed: usize,
+    /// Items the worker gave back to be continued next run.
+    pub requeued: usize,
+}
+
+/// A system working through the `WorkQueue<T>` for up to `budget` each
+/// run, for work that doesn't fit in one frame. `worker` gets each item
+/// along with its arguments, fetched once per run and declared like those
+/// of any system; returning `Some` queues the item again to continue on a
+/// later run:
+///
+/// ```ignore
+/// fn step(mut path: PathRequest, (grid, paths): &mut (&Grid, &mut Paths)) -> Option<PathRequest> {
+///     path.expand(grid, 64);
+///     path.done().then(|| paths.finish(path))
+/// }
+///
+/// world.add_system(Update, "ai", budgeted_system(Duration::from_millis(2), step));
+/// ```
+pub fn budgeted_system<T, A, F>(budget: Duration, worker: F) -> System
+where
+    T: Send + Sync + 'static,
+    A: SystemArg,
+    for<'a> F: Fn(T, &mut A) -> Option<T>
+        + Fn(T, &mut ArgItem<'a, A>) -> Option<T>
+        + Send
+        + Sync
+        + 'static,
+{
+    let mut metas = A::metas();
+    metas.push(AccessMeta::new(
+        AccessType::resource::<WorkQueue<T>>(),
+        Access::Write,
+    ));
+
+    let mut reads = vec![];
+    let mut writes = vec![];
+    AccessMeta::pick(&mut reads, &mut writes, &metas);
+
+    let mut system = System::new(
+        move |world| {
+            let queue = <&mut WorkQueue<T> as SystemArg>::get(world);
+            let mut args = A::get(world);
+            queue.drain(world.frame(), budget, |item| worker(item, &mut args));
+        },
+        reads,
+        writes,
+    )
+    .with_fn::<F>();
+
+    system.registrars.push(|world: &mut World| {
+        A::register(world);
+        if world.try_resource::<WorkQueue<T>>().is_none() {
+            world.add_resource(WorkQueue::<T>::new());
+        }
+    });
+
+    system
+}
@@ -1,4 +1,4 @@
-use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::sync::{Arc, Condvar, MutexGuard};
 
 pub struct JobBarrier {
     count: usize,
@@ -31,23 +31,19 @@ impl JobBarrier {
 
 pub struct BarrierLock {
     condvar: Arc<Condvar>,
-    guard: Arc<Mutex<()>>,
 }
 
 impl BarrierLock {
     fn new(condvar: Arc<Condvar>) -> Self {
-        let guard = Arc::new(Mutex::new(()));
-        Self { condvar, guard }
+        Self { condvar }
     }
 
+    /// Blocks until every job has notified. Waits on the barrier's own mutex
+    /// so a notify between the check and the wait can't be missed.
     pub fn wait(&self, barrier: MutexGuard<JobBarrier>) {
-        let count = barrier.count;
-        let total = barrier.total;
-
-        if count < total {
-            std::mem::drop(barrier);
-            let guard = self.guard.lock().unwrap();
-            let _ = self.condvar.wait(guard);
-        }
+        let _barrier = self
+            .condvar
+            .wait_while(barrier, |barrier| barrier.count < barrier.total)
+            .unwrap();
     }
 }
@@ -1,6 +1,13 @@
 use std::{
-    sync::mpsc::Sender,
-    thread::{sleep, JoinHandle},
+    any::Any,
+    collections::VecDeque,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{Receiver, Sender},
+        Arc, Condvar, Mutex, PoisonError,
+    },
+    thread::JoinHandle,
 };
 
 pub mod barrier;
@@ -25,82 +32,230 @@ impl std::fmt::Display for Worker {
     }
 }
 
+/// Shared flag asking long-running work to stop. Work is only stopped
+/// between units (jobs, batches, schedule rows), never preempted. Clones
+/// observe the same flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Clears the flag so the token can be used again.
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
 pub struct TaskPool {
     workers: Vec<Worker>,
-    sender: Sender<Job>,
+    shared: Arc<Shared>,
+    next: AtomicUsize,
 }
 
-type Job = Option<Box<dyn FnOnce() + Send + 'static>>;
+type Job = Box<dyn FnOnce() + Send + 'static>;
 
-impl TaskPool {
-    pub fn new(size: usize) -> Self {
-        let (sender, receiver) = std::sync::mpsc::channel();
-        let receiver = std::sync::Arc::new(std::sync::Mutex::new(receiver));
-
-        let mut workers = Vec::with_capacity(size);
+/// Empty polls a worker makes, yielding in between, before it parks.
+const SPINS_BEFORE_SLEEP: usize = 16;
 
-        for id in 0..size {
-            let receiver = receiver.clone();
-            let thread = std::thread::spawn(move || loop {
-                let job: Job = receiver.lock().unwrap().recv().unwrap();
+/// Per-worker job queues. Workers pop from their own queue and steal from the
+/// back of the others when it runs dry.
+struct Shared {
+    queues: Vec<Mutex<VecDeque<Job>>>,
+    executed: Vec<AtomicUsize>,
+    queued: AtomicUsize,
+    sleeping: AtomicUsize,
+    idle: Mutex<()>,
+    signal: Condvar,
+    shutdown: AtomicBool,
+}
 
-                match job {
-                    Some(job) => job(),
-                    None => break,
-                }
-            });
+impl Shared {
+    fn push(&self, queue: usize, job: Job) {
+        self.queues[queue].lock().unwrap().push_back(job);
+        self.queued.fetch_add(1, Ordering::SeqCst);
+    }
 
-            workers.push(Worker::new(id, thread));
+    fn pop(&self, worker: usize, seed: &mut u64) -> Option<Job> {
+        if let Some(job) = self.queues[worker].lock().unwrap().pop_front() {
+            return Some(job);
+        } else if self.queued.load(Ordering::SeqCst) == 0 {
+            return None;
         }
 
-        Self { workers, sender }
+        *seed ^= *seed << 13;
+        *seed ^= *seed >> 7;
+        *seed ^= *seed << 17;
+
+        let count = self.queues.len();
+        let start = (*seed as usize) % count;
+        (0..count)
+            .map(|offset| (start + offset) % count)
+            .filter(|victim| *victim != worker)
+            .find_map(|victim| self.queues[victim].try_lock().ok()?.pop_back())
     }
 
-    pub fn execute(&self, f: impl FnOnce() + Send + 'static) {
-        self.sender.send(Some(Box::new(f))).unwrap();
+    fn wake(&self, all: bool) {
+        if self.sleeping.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+
+        let _idle = self.idle.lock().unwrap();
+        if all {
+            self.signal.notify_all();
+        } else {
+            self.signal.notify_one();
+        }
     }
 
-    pub fn join(&mut self) {
-        for worker in &mut self.workers {
-            self.sender.send(None).unwrap();
-            if let Some(thread) = worker.thread.take() {
-                thread.join().unwrap();
+    fn run(&self, worker: usize) {
+        let mut seed = (worker as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1;
+        let mut misses = 0;
+
+        loop {
+            if let Some(job) = self.pop(worker, &mut seed) {
+                self.queued.fetch_sub(1, Ordering::SeqCst);
+                job();
+                self.executed[worker].fetch_add(1, Ordering::Relaxed);
+                misses = 0;
+                continue;
+            } else if misses < SPINS_BEFORE_SLEEP {
+                misses += 1;
+                std::thread::yield_now();
+                continue;
+            }
+
+            misses = 0;
+
+            let idle = self.idle.lock().unwrap();
+            self.sleeping.fetch_add(1, Ordering::SeqCst);
+
+            if self.queued.load(Ordering::SeqCst) > 0 {
+                self.sleeping.fetch_sub(1, Ordering::SeqCst);
+                continue;
+            } else if self.shutdown.load(Ordering::SeqCst) {
+                self.sleeping.fetch_sub(1, Ordering::SeqCst);
+                break;
             }
+
+            let _idle = self.signal.wait(idle).unwrap();
+            self.sleeping.fetch_sub(1, Ordering::SeqCst);
         }
     }
 }
 
-impl Drop for TaskPool {
-    fn drop(&mut self) {
-        self.join();
-    }
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskPoolMetrics {
+    /// Jobs run by each worker, including stolen ones.
+    pub executed: Vec<usize>,
 }
 
-pub struct ScopedSender<'a> {
-    sender: Sender<ScopedJob<'a>>,
-    thread_count: usize,
+impl TaskPoolMetrics {
+    pub fn total(&self) -> usize {
+        self.executed.iter().sum()
+    }
 }
 
-impl<'a> ScopedSender<'a> {
-    pub fn new(sender: Sender<ScopedJob<'a>>, thread_count: usize) -> Self {
+impl TaskPool {
+    pub fn new(size: usize) -> Self {
+        let size = size.max(1);
+        let shared = Arc::new(Shared {
+            queues: (0..size).map(|_| Mutex::new(VecDeque::new())).collect(),
+            executed: (0..size).map(|_| AtomicUsize::new(0)).collect(),
+            queued: AtomicUsize::new(0),
+            sleeping: AtomicUsize::new(0),
+            idle: Mutex::new(()),
+            signal: Condvar::new(),
+            shutdown: AtomicBool::new(false),
+        });
+
+        let mut workers = Vec::with_capacity(size);
+
+        for id in 0..size {
+            let shared = shared.clone();
+            let thread = std::thread::spawn(move || shared.run(id));
+
+            workers.push(Worker::new(id, thread));
+        }
+
         Self {
-            sender,
-            thread_count,
+            workers,
+            shared,
+            next: AtomicUsize::new(0),
         }
     }
 
-    pub fn send(&self, f: impl FnOnce() + Send + Sync + 'a) {
-        let _ = self.sender.send(Some(Box::new(f)));
+    /// Queues a job on the next worker in round-robin order.
+    pub fn execute(&self, f: impl FnOnce() + Send + 'static) {
+        let queue = self.next.fetch_add(1, Ordering::Relaxed) % self.shared.queues.len();
+        self.shared.push(queue, Box::new(f));
+        self.shared.wake(false);
     }
 
-    pub fn join(&self) {
-        for _ in 0..self.thread_count {
-            let _ = self.sender.send(None);
+    /// Like `execute`, but the job is dropped without running if `token` is
+    /// cancelled before a worker picks it up.
+    pub fn execute_with_token(&self, token: &CancellationToken, f: impl FnOnce() + Send + 'static) {
+        let token = token.clone();
+        self.execute(move || {
+            if !token.is_cancelled() {
+                f();
+            }
+        });
+    }
+
+    /// Spreads the jobs evenly over the workers before waking them.
+    pub fn execute_batch<F>(&self, jobs: impl IntoIterator<Item = F>)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let start = self.next.load(Ordering::Relaxed);
+        let count = self.shared.queues.len();
+        let mut pushed = 0;
+
+        for (index, job) in jobs.into_iter().enumerate() {
+            self.shared.push((start + index) % count, Box::new(job));
+            pushed += 1;
+        }
+
+        self.next.fetch_add(pushed, Ordering::Relaxed);
+        self.shared.wake(true);
+    }
+
+    pub fn metrics(&self) -> TaskPoolMetrics {
+        TaskPoolMetrics {
+            executed: self
+                .shared
+                .executed
+                .iter()
+                .map(|count| count.load(Ordering::Relaxed))
+                .collect(),
+        }
+    }
+
+    /// Runs every queued job, then stops the workers.
+    pub fn join(&mut self) {
+        self.shared.shutdown.store(true, Ordering::SeqCst);
+        self.shared.wake(true);
+
+        for worker in &mut self.workers {
+            if let Some(thread) = worker.thread.take() {
+                thread.join().unwrap();
+            }
         }
     }
 }
 
-impl<'a> Drop for ScopedSender<'a> {
+impl Drop for TaskPool {
     fn drop(&mut self) {
         self.join();
     }
@@ -108,54 +263,76 @@ impl<'a> Drop for ScopedSender<'a> {
 
 type ScopedJob<'a> = Option<Box<dyn FnOnce() + Send + 'a>>;
 
+/// Workers that live for a single call to `ScopedTaskPool::scope`, so jobs
+/// can borrow from the caller.
 pub struct ScopedTaskPool<'a> {
     sender: Sender<ScopedJob<'a>>,
-    _marker: std::marker::PhantomData<&'a ()>,
+    workers: usize,
 }
 
 impl<'a> ScopedTaskPool<'a> {
-    pub fn new(size: usize, executor: impl Fn(ScopedSender<'a>)) -> Self {
+    /// Starts `size` workers and runs `f` with the pool. Returns once `f`
+    /// has returned, every job queued has run and the workers have stopped.
+    /// A job that panics doesn't take its worker down; the first panic is
+    /// resumed here after the workers stop.
+    pub fn scope<R>(size: usize, f: impl FnOnce(&ScopedTaskPool<'a>) -> R) -> R {
+        let size = size.max(1);
         let (sender, receiver) = std::sync::mpsc::channel();
-        let receiver = std::sync::Arc::new(std::sync::Mutex::new(receiver));
+        let receiver = Mutex::new(receiver);
+        let panic = Mutex::new(None);
 
-        std::thread::scope(|scope| {
+        let result = std::thread::scope(|scope| {
             for _ in 0..size {
-                let receiver = receiver.clone();
-                scope.spawn(move || loop {
-                    let receiver = match receiver.lock() {
-                        Ok(receiver) => receiver,
-                        Err(_) => break,
-                    };
-
-                    let job: ScopedJob = match receiver.recv() {
-                        Ok(job) => job,
-                        Err(_) => break,
-                    };
-
-                    match job {
-                        Some(job) => {
-                            job();
-                            sleep(std::time::Duration::from_nanos(1));
-                        }
-                        None => break,
-                    }
-                });
+                scope.spawn(|| Self::work(&receiver, &panic));
             }
 
-            executor(ScopedSender::new(sender.clone(), size));
+            // Dropping the pool closes the channel, so the workers stop even
+            // if `f` panics.
+            let pool = ScopedTaskPool {
+                sender,
+                workers: size,
+            };
+            let result = f(&pool);
+            pool.join();
+            result
         });
 
-        Self {
-            sender,
-            _marker: std::marker::PhantomData,
+        match panic.into_inner().unwrap_or_else(PoisonError::into_inner) {
+            Some(payload) => std::panic::resume_unwind(payload),
+            None => result,
         }
     }
 
     pub fn execute(&self, f: impl FnOnce() + Send + 'a) {
-        self.sender.send(Some(Box::new(f))).unwrap();
+        let _ = self.sender.send(Some(Box::new(f)));
     }
 
-    pub fn join(&mut self) {
-        self.sender.send(None).unwrap();
+    /// Stops the workers once the jobs queued so far have run. Jobs queued
+    /// afterwards never run.
+    pub fn join(&self) {
+        for _ in 0..self.workers {
+            let _ = self.sender.send(None);
+        }
+    }
+
+    fn work(receiver: &Mutex<Receiver<ScopedJob<'a>>>, panic: &Mutex<Option<Box<dyn Any + Send>>>) {
+        loop {
+            let job = receiver
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .recv();
+
+            match job {
+                Ok(Some(job)) => {
+                    if let Err(payload) = catch_unwind(AssertUnwindSafe(job)) {
+                        panic
+                            .lock()
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .get_or_insert(payload);
+                    }
+                }
+                Ok(None) | Err(_) => break,
+            }
+        }
     }
 }
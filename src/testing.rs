@@ -0,0 +1,174 @@
+use crate::{
+    archetype::ArchetypeId,
+    core::{ComponentId, Entity},
+    world::World,
+};
+use std::{fs, path::Path};
+
+/// Compares `text` with the golden file at `path`. A missing golden file is
+/// written from `text` and the check passes; a mismatch panics with a
+/// unified diff from the golden file to `text`.
+#[track_caller]
+pub fn assert_snapshot(text: &str, path: impl AsRef<Path>) {
+    let path = path.as_ref();
+    let golden = match fs::read_to_string(path) {
+        Ok(golden) => golden,
+        Err(_) => {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir).expect("Failed to create snapshot directory.");
+            }
+            fs::write(path, text).expect("Failed to write snapshot.");
+            return;
+        }
+    };
+
+    if golden != text {
+        let diff = unified_diff(&golden, text, 3);
+        panic!("snapshot {} doesn't match:\n{}", path.display(), diff);
+    }
+}
+
+/// Dumps `world` with `World::dump_text` and checks it against a golden
+/// file, relative to the calling crate's manifest directory.
+///
+/// ```ignore
+/// assert_world_snapshot!(world, &registry, "tests/golden/frame_10.txt");
+/// assert_world_snapshot!(world, "tests/golden/frame_10.txt");
+/// ```
+#[macro_export]
+macro_rules! assert_world_snapshot {
+    ($world:expr, $path:expr) => {
+        $crate::assert_world_snapshot!(
+            $world,
+            &$crate::world::dump::DebugDumpRegistry::new(),
+            $path
+        )
+    };
+    ($world:expr, $registry:expr, $path:expr) => {
+        $crate::testing::assert_snapshot(
+            &$world.dump_text($registry),
+            ::std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join($path),
+        )
+    };
+}
+
+/// Line diff from `old` to `new` in unified format, with `context`
+/// unchanged lines around each change.
+pub fn unified_diff(old: &str, new: &str, context: usize) -> String {
+    let old = old.lines().collect::<Vec<_>>();
+    let new = new.lines().collect::<Vec<_>>();
+    let edits = diff_lines(&old, &new);
+
+    let mut diff = String::from("--- golden\n+++ actual\n");
+    let changed = edits
+        .iter()
+        .enumerate()
+        .filter(|(_, edit)| !matches!(edit, Edit::Same(..)))
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+
+    let mut index = 0;
+    while index < changed.len() {
+        let start = changed[index].saturating_sub(context);
+        let mut end = changed[index] + context + 1;
+        while index + 1 < changed.len() && changed[index + 1] <= end + context {
+            index += 1;
+            end = changed[index] + context + 1;
+        }
+        let end = end.min(edits.len());
+        index += 1;
+
+        let (old_start, new_start) =
+            edits[..start]
+                .iter()
+                .fold((0, 0), |(o, n), edit| match edit {
+                    Edit::Same(..) => (o + 1, n + 1),
+                    Edit::Removed(_) => (o + 1, n),
+                    Edit::Added(_) => (o, n + 1),
+                });
+        let hunk = &edits[start..end];
+        let old_len = hunk.iter().filter(|e| !matches!(e, Edit::Added(_))).count();
+        let new_len = hunk
+            .iter()
+            .filter(|e| !matches!(e, Edit::Removed(_)))
+            .count();
+
+        diff.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            old_start + 1,
+            old_len,
+            new_start + 1,
+            new_len
+        ));
+        for edit in hunk {
+            let line = match edit {
+                Edit::Same(line) => format!(" {}\n", line),
+                Edit::Removed(line) => format!("-{}\n", line),
+                Edit::Added(line) => format!("+{}\n", line),
+            };
+            diff.push_str(&line);
+        }
+    }
+
+    diff
+}
+
+enum Edit<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Shortest edit script through the longest common subsequence of lines.
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Edit<'a>> {
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = match old[i] == new[j] {
+                true => common[i + 1][j + 1] + 1,
+                false => common[i + 1][j].max(common[i][j + 1]),
+            };
+        }
+    }
+
+    let mut edits = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            edits.push(Edit::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            edits.push(Edit::Removed(old[i]));
+            i += 1;
+        } else {
+            edits.push(Edit::Added(new[j]));
+            j += 1;
+        }
+    }
+
+    edits
+}
+
+/// Damage `World::repair` knows how to fix, for testing recovery paths.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Corruption {
+    /// Removes the entity's table row, dropping its values.
+    MissingRow(Entity),
+    /// Forgets the entity everywhere but in its table.
+    OrphanRow(Entity),
+    /// Lists `component` a second time in the archetype.
+    RepeatComponent {
+        archetype: ArchetypeId,
+        component: ComponentId,
+    },
+    /// Lists `child` under `parent` without checking it is alive.
+    DeadChild { parent: Entity, child: Entity },
+    /// Empties the entity's component mask.
+    ClearMask(Entity),
+}
+
+/// Applies `corruption` to `world`, bypassing every invariant it keeps.
+pub fn corrupt(world: &mut World, corruption: Corruption) {
+    world.corrupt(corruption);
+}
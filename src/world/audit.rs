@@ -0,0 +1,104 @@
+use super::World;
+use crate::{
+    core::ComponentId,
+    schedule::{GlobalSchedules, SceneSchedules},
+    system::observer::Observables,
+};
+use std::fmt::Display;
+
+/// Frames a system may go without its queries matching anything before
+/// `World::audit` reports it.
+pub const DEFAULT_AUDIT_WINDOW: u64 = 120;
+
+/// Registrations that don't do anything, found by `World::audit`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuditReport {
+    /// Components registered but never part of an archetype.
+    pub unused_components: Vec<&'static str>,
+    /// Systems whose queries matched no table within the audit window.
+    pub unmatched_systems: Vec<&'static str>,
+    /// Actions with observers that never produced an output.
+    pub silent_observers: Vec<&'static str>,
+}
+
+impl AuditReport {
+    pub fn is_empty(&self) -> bool {
+        self.unused_components.is_empty()
+            && self.unmatched_systems.is_empty()
+            && self.silent_observers.is_empty()
+    }
+}
+
+impl Display for AuditReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return write!(f, "nothing unused");
+        }
+
+        let sections = [
+            ("unused components", &self.unused_components),
+            ("systems matching nothing", &self.unmatched_systems),
+            ("observers never notified", &self.silent_observers),
+        ];
+
+        for (title, names) in sections {
+            if names.is_empty() {
+                continue;
+            }
+
+            writeln!(f, "{}:", title)?;
+            for name in names {
+                writeln!(f, "  {}", name)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl World {
+    /// Lists components, systems and observers that have done nothing so
+    /// far. Systems that never build a query aren't judged.
+    pub fn audit(&self) -> AuditReport {
+        let unused_components = (0..self.components.len())
+            .map(ComponentId::from)
+            .filter(|id| !self.archetypes.seen(*id))
+            .map(|id| self.components.meta(id).name())
+            .collect();
+
+        let mut unmatched_systems = Vec::new();
+        let global = self.resources.get::<GlobalSchedules>();
+        let scene = self.resources.get::<SceneSchedules>();
+        for system in global.systems().chain(scene.systems()) {
+            let stats = system.query_stats();
+            let stale = match stats.last_match() {
+                Some(frame) => self.frame.saturating_sub(frame) > self.audit_window,
+                None => true,
+            };
+
+            if stats.queried() && stale && !unmatched_systems.contains(&system.name()) {
+                unmatched_systems.push(system.name());
+            }
+        }
+
+        let silent_observers = self
+            .resources
+            .get::<Observables>()
+            .channels()
+            .filter(|channel| channel.has_observers() && !channel.delivered())
+            .map(|channel| channel.name())
+            .collect();
+
+        AuditReport {
+            unused_components,
+            unmatched_systems,
+            silent_observers,
+        }
+    }
+
+    /// Frames a system may go without its queries matching anything before
+    /// `audit` reports it.
+    pub fn set_audit_window(&mut self, frames: u64) {
+        self.audit_window = frames;
+    }
+}
@@ -0,0 +1,230 @@
+use crate::core::{Component, ComponentId, Entity};
+#[cfg(feature = "strict-borrows")]
+use std::{
+    collections::HashMap,
+    panic::Location,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+use std::{
+    fmt::Debug,
+    ops::{Deref, DerefMut},
+};
+
+#[cfg(feature = "strict-borrows")]
+type Caller = &'static Location<'static>;
+
+#[cfg(feature = "strict-borrows")]
+enum BorrowState {
+    Shared(Vec<Caller>),
+    Exclusive(Caller),
+}
+
+/// Live `ComponentRef` and `ComponentRefMut` borrows by entity and
+/// component. With the `strict-borrows` feature every component access
+/// registers one and aliasing panics, naming both call sites. Without it
+/// nothing is tracked.
+#[derive(Default)]
+pub struct ComponentBorrows {
+    #[cfg(feature = "strict-borrows")]
+    borrows: Mutex<HashMap<(Entity, ComponentId), BorrowState>>,
+}
+
+impl ComponentBorrows {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Panics if `component` of `entity` is borrowed in a way that conflicts
+    /// with an access from the caller, for raw accesses that hold no guard.
+    #[allow(unused_variables)]
+    #[cfg_attr(feature = "strict-borrows", track_caller)]
+    pub(crate) fn check(&self, entity: Entity, component: ComponentId, name: &str, write: bool) {
+        #[cfg(feature = "strict-borrows")]
+        {
+            let borrows = self.lock();
+            if let Some(state) = borrows.get(&(entity, component)) {
+                Self::conflict(state, entity, name, write, Location::caller());
+            }
+        }
+    }
+
+    /// A conflict panics with the lock held, so the map is still usable
+    /// after a caught borrow panic.
+    #[cfg(feature = "strict-borrows")]
+    fn lock(&self) -> MutexGuard<'_, HashMap<(Entity, ComponentId), BorrowState>> {
+        self.borrows.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    #[cfg(feature = "strict-borrows")]
+    fn conflict(state: &BorrowState, entity: Entity, name: &str, write: bool, caller: Caller) {
+        let held = match state {
+            BorrowState::Exclusive(at) => Some(("mutably", *at)),
+            BorrowState::Shared(at) if write => at.first().map(|at| ("immutably", *at)),
+            BorrowState::Shared(_) => None,
+        };
+
+        if let Some((how, at)) = held {
+            let access = if write { "mutably" } else { "immutably" };
+            panic!(
+                "{} of {:?} accessed {} at {} while borrowed {} at {}",
+                name, entity, access, caller, how, at
+            );
+        }
+    }
+
+    /// Registers a borrow of `component` of `entity` held until the guard is
+    /// dropped, panicking if it conflicts with one already held. Tracks
+    /// nothing without the `strict-borrows` feature.
+    #[allow(unused_variables)]
+    #[cfg_attr(feature = "strict-borrows", track_caller)]
+    pub(crate) fn acquire(
+        &self,
+        entity: Entity,
+        component: ComponentId,
+        name: &str,
+        write: bool,
+    ) -> BorrowGuard<'_> {
+        #[cfg(feature = "strict-borrows")]
+        {
+            let at = Location::caller();
+            let mut borrows = self.lock();
+            match borrows.get_mut(&(entity, component)) {
+                Some(state) => {
+                    Self::conflict(state, entity, name, write, at);
+                    if let BorrowState::Shared(callers) = state {
+                        callers.push(at);
+                    }
+                }
+                None => {
+                    let state = match write {
+                        true => BorrowState::Exclusive(at),
+                        false => BorrowState::Shared(vec![at]),
+                    };
+                    borrows.insert((entity, component), state);
+                }
+            }
+
+            BorrowGuard {
+                borrows: self,
+                entity,
+                component,
+                at,
+            }
+        }
+
+        #[cfg(not(feature = "strict-borrows"))]
+        BorrowGuard {
+            _borrows: std::marker::PhantomData,
+        }
+    }
+
+    #[cfg(feature = "strict-borrows")]
+    fn release(&self, entity: Entity, component: ComponentId, at: Caller) {
+        let mut borrows = self.lock();
+        let key = (entity, component);
+        let empty = match borrows.get_mut(&key) {
+            Some(BorrowState::Shared(callers)) => {
+                if let Some(index) = callers.iter().position(|caller| *caller == at) {
+                    callers.swap_remove(index);
+                }
+                callers.is_empty()
+            }
+            Some(BorrowState::Exclusive(_)) => true,
+            None => false,
+        };
+
+        if empty {
+            borrows.remove(&key);
+        }
+    }
+}
+
+/// Holds a borrow registered by `ComponentBorrows::acquire`. Empty without
+/// the `strict-borrows` feature.
+pub(crate) struct BorrowGuard<'a> {
+    #[cfg(feature = "strict-borrows")]
+    borrows: &'a ComponentBorrows,
+    #[cfg(feature = "strict-borrows")]
+    entity: Entity,
+    #[cfg(feature = "strict-borrows")]
+    component: ComponentId,
+    #[cfg(feature = "strict-borrows")]
+    at: Caller,
+    #[cfg(not(feature = "strict-borrows"))]
+    _borrows: std::marker::PhantomData<&'a ComponentBorrows>,
+}
+
+#[cfg(feature = "strict-borrows")]
+impl Drop for BorrowGuard<'_> {
+    fn drop(&mut self) {
+        self.borrows.release(self.entity, self.component, self.at);
+    }
+}
+
+/// A shared borrow of a component, returned by `World::component` and
+/// `&C` query items. Tracked with the `strict-borrows` feature, a plain
+/// reference without it.
+pub struct ComponentRef<'a, C: Component> {
+    value: &'a C,
+    _guard: BorrowGuard<'a>,
+}
+
+impl<'a, C: Component> ComponentRef<'a, C> {
+    pub(crate) fn new(value: &'a C, guard: BorrowGuard<'a>) -> Self {
+        Self {
+            value,
+            _guard: guard,
+        }
+    }
+}
+
+impl<C: Component> Deref for ComponentRef<'_, C> {
+    type Target = C;
+
+    fn deref(&self) -> &Self::Target {
+        self.value
+    }
+}
+
+impl<C: Component + Debug> Debug for ComponentRef<'_, C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+/// An exclusive borrow of a component, returned by `World::component_mut`
+/// and `&mut C` query items. Tracked with the `strict-borrows` feature, a
+/// plain reference without it.
+pub struct ComponentRefMut<'a, C: Component> {
+    value: &'a mut C,
+    _guard: BorrowGuard<'a>,
+}
+
+impl<'a, C: Component> ComponentRefMut<'a, C> {
+    pub(crate) fn new(value: &'a mut C, guard: BorrowGuard<'a>) -> Self {
+        Self {
+            value,
+            _guard: guard,
+        }
+    }
+}
+
+impl<C: Component> Deref for ComponentRefMut<'_, C> {
+    type Target = C;
+
+    fn deref(&self) -> &Self::Target {
+        self.value
+    }
+}
+
+impl<C: Component> DerefMut for ComponentRefMut<'_, C> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.value
+    }
+}
+
+impl<C: Component + Debug> Debug for ComponentRefMut<'_, C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.value.fmt(f)
+    }
+}
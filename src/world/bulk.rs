@@ -0,0 +1,128 @@
+use super::{lifecycle::Lifecycle, tracking::TrackingEvent, World};
+use crate::{
+    core::{Component, ComponentId, Entity},
+    storage::blob::Blob,
+    system::observer::{action::ActionOutputs, builtin::AddComponent},
+};
+
+impl World {
+    /// Overwrites `C` with a clone of `value` on every entity that has `C`,
+    /// every component in `filter` and none in `without`. Values are written
+    /// straight into the table columns, so no actions or observers run.
+    /// Returns the number of rows written.
+    pub fn fill_component<C: Component + Clone>(
+        &mut self,
+        value: C,
+        filter: &[ComponentId],
+        without: &[ComponentId],
+    ) -> usize {
+        let Some(component_id) = self.components.get_id::<C>() else {
+            return 0;
+        };
+
+        self.borrows.assert_unborrowed();
+        let mut components = filter.to_vec();
+        components.push(component_id);
+        let archetypes = self
+            .archetypes
+            .archetypes(&components, without)
+            .into_iter()
+            .copied()
+            .collect::<Vec<_>>();
+
+        let tick = self.change_tick();
+        let mut written = 0;
+        for archetype in archetypes {
+            let Some(table) = self.tables.get_mut(archetype.into()) else {
+                continue;
+            };
+
+            let rows = table.len();
+            let Some(column) = table.column_mut(component_id.into()) else {
+                continue;
+            };
+
+            for row in 0..rows {
+                if let Some(slot) = column.get_mut::<C>(row, tick) {
+                    *slot = value.clone();
+                    written += 1;
+                }
+            }
+        }
+
+        written
+    }
+
+    /// Adds a clone of `value` to every entity that has every component in
+    /// `filter`, none in `without` and no `C` yet. Each matching archetype
+    /// moves into its destination table in one batch. `AddComponent<C>`
+    /// observers receive all the entities on the next flush. Returns the
+    /// entities that received the component.
+    pub fn add_component_to_matching<C: Component + Clone>(
+        &mut self,
+        value: C,
+        filter: &[ComponentId],
+        without: &[ComponentId],
+    ) -> Vec<Entity> {
+        self.register::<C>();
+        let component_id = self.components.id::<C>();
+        self.borrows.assert_unborrowed();
+
+        let mut without = without.to_vec();
+        without.push(component_id);
+        let archetypes = self
+            .archetypes
+            .matching(filter, &without)
+            .filter(|archetype| !archetype.entities().is_empty())
+            .map(|archetype| *archetype.id())
+            .collect::<Vec<_>>();
+
+        let mut added = Vec::new();
+        for from in archetypes {
+            let entities = Lifecycle::add_component_batch(
+                from,
+                component_id,
+                &value,
+                self.change_tick(),
+                &mut self.archetypes,
+                &mut self.tables,
+            );
+
+            let to = entities
+                .first()
+                .and_then(|entity| self.archetypes.archetype_id(*entity).copied());
+            if let Some(to) = to {
+                let component = self.components.meta(component_id).name();
+                for entity in &entities {
+                    self.transitions.record(*entity, from, to);
+                    if self.tracked.is_tracked(*entity) {
+                        let event = TrackingEvent::AddComponent {
+                            component,
+                            from,
+                            to,
+                        };
+                        self.tracked.record(*entity, self.frame, event);
+                    }
+                }
+            }
+
+            for entity in &entities {
+                self.reindex(*entity, &[component_id]);
+            }
+
+            added.extend(entities);
+        }
+
+        if !added.is_empty() {
+            let mut outputs = Blob::with_capacity::<Entity>(added.len());
+            for entity in &added {
+                outputs.push(*entity);
+            }
+
+            self.resource_mut::<ActionOutputs>()
+                .extend::<AddComponent<C>>(outputs);
+        }
+
+        added
+    }
+}
@@ -0,0 +1,64 @@
+use super::{trace::TraceLevel, World};
+
+/// Tables holding this many rows or fewer are never shrunk.
+pub const COMPACT_MIN_LEN: usize = 32;
+
+/// Sizes reported by `World::compact`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CompactReport {
+    pub tables_shrunk: usize,
+    pub bytes_before: usize,
+    pub bytes_after: usize,
+}
+
+impl CompactReport {
+    pub fn bytes_freed(&self) -> usize {
+        self.bytes_before.saturating_sub(self.bytes_after)
+    }
+}
+
+impl World {
+    /// Releases spare table capacity left behind by mass deletions. A table
+    /// is shrunk only when its capacity is more than twice its length and it
+    /// holds more than `COMPACT_MIN_LEN` rows, so tables that shrink and grow
+    /// every frame are left alone.
+    pub fn compact(&mut self) -> CompactReport {
+        self.borrows.assert_unborrowed();
+        let mut report = CompactReport::default();
+
+        for table in self.tables.iter_mut() {
+            let bytes = table.allocated_bytes();
+            report.bytes_before += bytes;
+
+            let capacity = table
+                .columns()
+                .filter(|column| column.allocated_bytes() > 0)
+                .map(|column| column.capacity())
+                .chain(std::iter::once(table.capacity()))
+                .max()
+                .unwrap_or(0);
+
+            if capacity > table.len() * 2 && table.len() > COMPACT_MIN_LEN {
+                table.shrink_to_fit();
+            }
+
+            // Columns only free whole chunks, so a shrunk table may not get
+            // any smaller.
+            let after = table.allocated_bytes();
+            if after < bytes {
+                report.tables_shrunk += 1;
+            }
+            report.bytes_after += after;
+        }
+
+        if report.tables_shrunk > 0 {
+            let message = format!(
+                "shrunk {} tables from {} to {} bytes",
+                report.tables_shrunk, report.bytes_before, report.bytes_after
+            );
+            self.trace(TraceLevel::Info, "compact", message);
+        }
+
+        report
+    }
+}
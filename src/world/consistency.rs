@@ -0,0 +1,251 @@
+use super::World;
+use crate::{
+    archetype::ArchetypeId,
+    core::{ComponentId, Entity},
+    storage::table::TableId,
+};
+use std::fmt::Display;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConsistencyError {
+    /// A live entity has no archetype.
+    MissingArchetype { entity: Entity },
+    /// A live entity is not listed in the archetype it maps to.
+    NotInArchetype {
+        entity: Entity,
+        archetype: ArchetypeId,
+    },
+    /// A live entity's component mask differs from its archetype's.
+    MaskMismatch {
+        entity: Entity,
+        archetype: ArchetypeId,
+    },
+    /// A live entity has no row in its archetype's table.
+    MissingRow {
+        entity: Entity,
+        archetype: ArchetypeId,
+    },
+    /// An archetype lists a component more than once.
+    DuplicateComponent {
+        archetype: ArchetypeId,
+        component: ComponentId,
+    },
+    /// A table row belongs to a dead entity or to an entity mapped elsewhere.
+    OrphanRow { entity: Entity, table: TableId },
+    /// A child list contains a dead or re-generated entity.
+    DeadChild { parent: Entity, child: Entity },
+    /// A child list contains an entity whose parent link points elsewhere.
+    ParentMismatch {
+        parent: Entity,
+        child: Entity,
+        recorded: Option<Entity>,
+    },
+    /// An entity's parent link points to a dead entity.
+    DeadParent { entity: Entity, parent: Entity },
+    /// An entity's parent does not list it as a child.
+    NotInChildren { entity: Entity, parent: Entity },
+    /// A cached hierarchy depth differs from the parent chain.
+    WrongDepth {
+        entity: Entity,
+        cached: u16,
+        expected: u16,
+    },
+    /// The cached root set disagrees with an entity's parent link.
+    RootMismatch { entity: Entity, cached: bool },
+}
+
+impl Display for ConsistencyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConsistencyError::MissingArchetype { entity } => {
+                write!(f, "{:?} has no archetype", entity)
+            }
+            ConsistencyError::NotInArchetype { entity, archetype } => write!(
+                f,
+                "{:?} is not listed in archetype {}",
+                entity,
+                archetype.id()
+            ),
+            ConsistencyError::MissingRow { entity, archetype } => write!(
+                f,
+                "{:?} has no row in the table of archetype {}",
+                entity,
+                archetype.id()
+            ),
+            ConsistencyError::MaskMismatch { entity, archetype } => write!(
+                f,
+                "{:?} has a component mask differing from archetype {}",
+                entity,
+                archetype.id()
+            ),
+            ConsistencyError::DuplicateComponent {
+                archetype,
+                component,
+            } => write!(
+                f,
+                "archetype {} lists component {} more than once",
+                archetype.id(),
+                component.id()
+            ),
+            ConsistencyError::OrphanRow { entity, table } => {
+                write!(f, "table {} has an orphan row for {:?}", table.id(), entity)
+            }
+            ConsistencyError::DeadChild { parent, child } => {
+                write!(f, "{:?} lists dead child {:?}", parent, child)
+            }
+            ConsistencyError::ParentMismatch {
+                parent,
+                child,
+                recorded,
+            } => write!(
+                f,
+                "{:?} lists child {:?} whose parent is {:?}",
+                parent, child, recorded
+            ),
+            ConsistencyError::DeadParent { entity, parent } => {
+                write!(f, "{:?} has dead parent {:?}", entity, parent)
+            }
+            ConsistencyError::NotInChildren { entity, parent } => {
+                write!(
+                    f,
+                    "{:?} is missing from the children of {:?}",
+                    entity, parent
+                )
+            }
+            ConsistencyError::WrongDepth {
+                entity,
+                cached,
+                expected,
+            } => write!(
+                f,
+                "{:?} has cached depth {} but is {} deep",
+                entity, cached, expected
+            ),
+            ConsistencyError::RootMismatch { entity, cached } => {
+                let state = if *cached { "cached" } else { "missing" };
+                write!(f, "{:?} is {} as a root", entity, state)
+            }
+        }
+    }
+}
+
+impl World {
+    /// Cross-checks entities, archetypes, tables and the hierarchy.
+    /// Walks the whole world, so it is meant for debugging and tests.
+    pub fn check_consistency(&self) -> Result<(), Vec<ConsistencyError>> {
+        let mut errors = Vec::new();
+        let entities = self.entities();
+        let archetypes = self.archetypes();
+
+        for entity in entities.iter() {
+            match archetypes.entity_archetype(entity) {
+                Some(archetype) => {
+                    let id = *archetype.id();
+                    if !archetype.entities().contains(&entity) {
+                        errors.push(ConsistencyError::NotInArchetype {
+                            entity,
+                            archetype: id,
+                        });
+                    }
+
+                    if archetypes.mask(entity) != Some(archetype.mask()) {
+                        errors.push(ConsistencyError::MaskMismatch {
+                            entity,
+                            archetype: id,
+                        });
+                    }
+
+                    let has_row = self
+                        .tables()
+                        .get(id.into())
+                        .map(|table| table.rows().contains(&entity))
+                        .unwrap_or(false);
+                    if !has_row {
+                        errors.push(ConsistencyError::MissingRow {
+                            entity,
+                            archetype: id,
+                        });
+                    }
+                }
+                None => errors.push(ConsistencyError::MissingArchetype { entity }),
+            }
+
+            for child in entities.children(entity, false) {
+                if !entities.contains(child) {
+                    errors.push(ConsistencyError::DeadChild {
+                        parent: entity,
+                        child,
+                    });
+                } else if entities.parent(child) != Some(entity) {
+                    errors.push(ConsistencyError::ParentMismatch {
+                        parent: entity,
+                        child,
+                        recorded: entities.parent(child),
+                    });
+                }
+            }
+
+            if let Some(parent) = entities.parent(entity) {
+                if !entities.contains(parent) {
+                    errors.push(ConsistencyError::DeadParent { entity, parent });
+                } else if !entities.children(parent, false).contains(&entity) {
+                    errors.push(ConsistencyError::NotInChildren { entity, parent });
+                }
+            }
+
+            let cached = entities.is_root(entity);
+            if cached != entities.parent(entity).is_none() {
+                errors.push(ConsistencyError::RootMismatch { entity, cached });
+            }
+
+            let mut expected = 0u16;
+            let mut ancestor = entities.parent(entity);
+            while let Some(next) = ancestor.filter(|_| expected < u16::MAX) {
+                expected += 1;
+                ancestor = entities.parent(next);
+            }
+            let cached = entities.depth(entity).unwrap_or_default();
+            if cached != expected {
+                errors.push(ConsistencyError::WrongDepth {
+                    entity,
+                    cached,
+                    expected,
+                });
+            }
+        }
+
+        for archetype in archetypes.iter() {
+            let components = archetype.components();
+            for (index, component) in components.iter().enumerate() {
+                if components[..index].contains(component) {
+                    errors.push(ConsistencyError::DuplicateComponent {
+                        archetype: *archetype.id(),
+                        component: *component,
+                    });
+                }
+            }
+        }
+
+        for table in self.tables().iter() {
+            for entity in table.rows() {
+                let mapped = archetypes
+                    .archetype_id(*entity)
+                    .map(|id| TableId::from(id.id()) == table.id())
+                    .unwrap_or(false);
+
+                if !entities.contains(*entity) || !mapped {
+                    errors.push(ConsistencyError::OrphanRow {
+                        entity: *entity,
+                        table: table.id(),
+                    });
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use super::{resource::Resource, trace::TraceLevel, World};
+use crate::archetype::ArchetypeId;
+use std::time::{Duration, Instant};
+
+/// Archetypes holding fewer entities than this are moved into a freshly
+/// built table by `World::defragment`.
+pub const DEFRAGMENT_MIN_LEN: usize = 8;
+
+/// What a `World::defragment` pass did, summed over every call of the pass.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DefragmentReport {
+    pub archetypes_removed: usize,
+    pub tables_rebuilt: usize,
+    pub tables_shrunk: usize,
+    pub bytes_before: usize,
+    pub bytes_after: usize,
+    /// False while archetypes of the pass are left for a later call.
+    pub complete: bool,
+}
+
+impl DefragmentReport {
+    pub fn bytes_freed(&self) -> usize {
+        self.bytes_before.saturating_sub(self.bytes_after)
+    }
+}
+
+/// Archetypes the running `World::defragment` pass has yet to visit, and
+/// the report of the calls so far.
+#[derive(Default)]
+pub struct DefragmentProgress {
+    queue: Vec<ArchetypeId>,
+    report: DefragmentReport,
+}
+
+impl DefragmentProgress {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_running(&self) -> bool {
+        !self.queue.is_empty()
+    }
+}
+
+impl Resource for DefragmentProgress {}
+
+impl World {
+    /// Visits archetypes in creation order until `budget` runs out, picking
+    /// up where the previous call stopped. Archetypes left without entities
+    /// are removed with their tables, archetypes below `DEFRAGMENT_MIN_LEN`
+    /// entities are moved into a freshly built table and the rest have their
+    /// spare capacity released. Entities keep their ids, values and change
+    /// ticks, so no observer events are emitted. At least one archetype is
+    /// visited per call.
+    pub fn defragment(&mut self, budget: Duration) -> DefragmentReport {
+        self.borrows.assert_unborrowed();
+        let start = Instant::now();
+
+        let mut progress = std::mem::take(&mut *self.resource_mut::<DefragmentProgress>());
+        if progress.queue.is_empty() {
+            progress.queue = self.archetypes.iter().map(|a| *a.id()).collect();
+            progress.queue.reverse();
+            progress.report = DefragmentReport::default();
+        }
+
+        while let Some(id) = progress.queue.pop() {
+            self.defragment_archetype(id, &mut progress.report);
+            if start.elapsed() >= budget {
+                break;
+            }
+        }
+
+        progress.report.complete = progress.queue.is_empty();
+        let report = progress.report;
+        *self.resource_mut::<DefragmentProgress>() = progress;
+
+        if report.complete {
+            let message = format!(
+                "removed {} archetypes, rebuilt {} and shrunk {} tables, from {} to {} bytes",
+                report.archetypes_removed,
+                report.tables_rebuilt,
+                report.tables_shrunk,
+                report.bytes_before,
+                report.bytes_after
+            );
+            self.trace(TraceLevel::Info, "defragment", message);
+        }
+
+        report
+    }
+
+    fn defragment_archetype(&mut self, id: ArchetypeId, report: &mut DefragmentReport) {
+        let Some(len) = self.archetypes.archetype(&id).map(|a| a.entities().len()) else {
+            return;
+        };
+        let Some(table) = self.tables.get_mut(id.into()) else {
+            return;
+        };
+
+        let bytes = table.allocated_bytes();
+        report.bytes_before += bytes;
+
+        // The empty archetype is where every entity starts, and archetypes
+        // named by waiting transitions must outlive them.
+        if len == 0 && id != ArchetypeId::new(&[]) && !self.transitions.references(id) {
+            self.tables.remove(id.into());
+            self.archetypes.remove(&id);
+            report.archetypes_removed += 1;
+            return;
+        }
+
+        if len < DEFRAGMENT_MIN_LEN {
+            let table = table.rebuild();
+            self.tables.insert(table);
+            report.tables_rebuilt += 1;
+        } else {
+            table.shrink_to_fit();
+        }
+
+        let after = self.tables.get(id.into()).unwrap().allocated_bytes();
+        if len >= DEFRAGMENT_MIN_LEN && after < bytes {
+            report.tables_shrunk += 1;
+        }
+        report.bytes_after += after;
+    }
+}
@@ -0,0 +1,147 @@
+use super::{
+    resource::{Resource, ResourceType},
+    World,
+};
+use crate::core::{Component, Entity};
+use std::{any::TypeId, collections::HashMap, fmt::Debug};
+
+type ComponentDebugFn = fn(&World, Entity) -> Option<String>;
+type ResourceDebugFn = fn(&World) -> Option<String>;
+
+/// Components and resources `World::dump_text` formats with `Debug`.
+/// Anything not registered is listed by name alone.
+#[derive(Default)]
+pub struct DebugDumpRegistry {
+    components: HashMap<TypeId, ComponentDebugFn>,
+    resources: HashMap<ResourceType, ResourceDebugFn>,
+}
+
+impl DebugDumpRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register_component<C: Component + Debug>(&mut self) -> &mut Self {
+        let debug: ComponentDebugFn = |world, entity| {
+            let component = world.component::<C>(entity)?;
+            Some(format!("{:?}", component))
+        };
+        self.components.insert(TypeId::of::<C>(), debug);
+        self
+    }
+
+    pub fn register_resource<R: Resource + Debug>(&mut self) -> &mut Self {
+        let debug: ResourceDebugFn = |world| {
+            let resource = world.try_resource::<R>()?;
+            Some(format!("{:?}", resource))
+        };
+        self.resources.insert(ResourceType::new::<R>(), debug);
+        self
+    }
+}
+
+impl World {
+    /// A line-per-item text listing of the world for golden files. Two
+    /// worlds with the same entities, values, resources and hierarchy dump
+    /// the same text, in this order:
+    ///
+    /// - `Entity 3v0: Health { value: 50 }, Transform { .. }` per entity,
+    ///   by id, with its components by key (stable id or name). Components
+    ///   the registry doesn't know are shown as `Key { .. }`.
+    /// - `Resource Score(3)` per resource, by key. Internal resources are
+    ///   left out.
+    /// - `Edge 3v0 -> 5v0` per parent and child, parents by id and children
+    ///   in their order under the parent.
+    pub fn dump_text(&self, registry: &DebugDumpRegistry) -> String {
+        let mut lines = vec![];
+
+        let mut entities = self.entities.iter().collect::<Vec<_>>();
+        entities.sort_by_key(|entity| (entity.id(), entity.generation()));
+
+        for &entity in &entities {
+            let mut components = self
+                .archetypes
+                .entity_archetype(entity)
+                .map(|archetype| archetype.components().to_vec())
+                .unwrap_or_default()
+                .into_iter()
+                .map(|id| {
+                    let meta = self.components.meta(id);
+                    let name = short_name(meta.key());
+                    let value = meta
+                        .type_id()
+                        .and_then(|type_id| registry.components.get(&type_id))
+                        .and_then(|debug| debug(self, entity))
+                        .unwrap_or_else(|| format!("{} {{ .. }}", name));
+                    (name, meta.key(), value)
+                })
+                .collect::<Vec<_>>();
+            components.sort_by(|a, b| (&a.0, a.1).cmp(&(&b.0, b.1)));
+
+            let values = components
+                .into_iter()
+                .map(|(_, _, value)| value)
+                .collect::<Vec<_>>();
+            lines.push(format!(
+                "Entity {}: {}",
+                entity_name(entity),
+                values.join(", ")
+            ));
+        }
+
+        let mut resources = self
+            .resources
+            .iter()
+            .filter(|(_, data)| !data.is_internal())
+            .map(|(ty, data)| {
+                let name = short_name(data.key());
+                let value = registry
+                    .resources
+                    .get(ty)
+                    .and_then(|debug| debug(self))
+                    .unwrap_or_else(|| format!("{} {{ .. }}", name));
+                (name, data.key(), value)
+            })
+            .collect::<Vec<_>>();
+        resources.sort_by(|a, b| (&a.0, a.1).cmp(&(&b.0, b.1)));
+        lines.extend(
+            resources
+                .into_iter()
+                .map(|(_, _, value)| format!("Resource {}", value)),
+        );
+
+        for &parent in &entities {
+            for &child in self.entities.children_of(parent) {
+                let edge = format!("Edge {} -> {}", entity_name(parent), entity_name(child));
+                lines.push(edge);
+            }
+        }
+
+        let mut text = lines.join("\n");
+        text.push('\n');
+        text
+    }
+}
+
+fn entity_name(entity: Entity) -> String {
+    format!("{}v{}", entity.id(), entity.generation())
+}
+
+/// `name` with every module path dropped, generics included:
+/// `a::Wrapper<b::Inner>` becomes `Wrapper<Inner>`.
+fn short_name(name: &str) -> String {
+    let mut short = String::with_capacity(name.len());
+    let mut path = String::new();
+    for c in name.chars() {
+        if c.is_alphanumeric() || c == '_' || c == ':' {
+            path.push(c);
+        } else {
+            short.push_str(path.rsplit("::").next().unwrap_or_default());
+            path.clear();
+            short.push(c);
+        }
+    }
+
+    short.push_str(path.rsplit("::").next().unwrap_or_default());
+    short
+}
@@ -0,0 +1,69 @@
+use super::{error::WorldError, World};
+use crate::{
+    core::{ComponentId, Entity},
+    storage::{ptr::Ptr, table::TableCell},
+};
+use std::alloc::Layout;
+
+impl World {
+    /// Registers a component defined at runtime; see
+    /// `Components::register_dynamic`. Its values are added and read as
+    /// bytes with `add_component_raw` and `component_raw`.
+    pub fn register_dynamic(
+        &mut self,
+        name: &str,
+        layout: Layout,
+        drop: Option<fn(*mut u8)>,
+    ) -> ComponentId {
+        self.components.register_dynamic(name, layout, drop)
+    }
+
+    /// Moves the value behind `value` into component `id` of `entity`,
+    /// overwriting the current one. Works for any registered component,
+    /// typed or dynamic. On error the value is left with the caller.
+    ///
+    /// # Safety
+    /// `value` must point to a valid value of the component, which is moved
+    /// into the world: the caller must not use or drop it afterwards.
+    pub unsafe fn add_component_raw(
+        &mut self,
+        entity: Entity,
+        id: ComponentId,
+        value: Ptr,
+    ) -> Result<(), WorldError> {
+        if id.id() >= self.components.len() {
+            return Err(WorldError::UnknownComponent(id));
+        }
+
+        let meta = self.components.meta(id);
+        if value.layout().pad_to_align() != meta.layout().pad_to_align() {
+            return Err(WorldError::LayoutMismatch {
+                component: id,
+                name: meta.name(),
+            });
+        }
+
+        if !self.entities.contains(entity) {
+            return Err(WorldError::EntityDead(entity));
+        }
+
+        let mut blob = meta.blob();
+        blob.push_raw(value.as_ptr());
+        self.insert_values(entity, vec![(id, blob)]);
+        Ok(())
+    }
+
+    /// Pointer to the value of component `id` of `entity`, for reading.
+    pub fn component_raw(&self, entity: Entity, id: ComponentId) -> Option<Ptr<'_>> {
+        if id.id() >= self.components.len() {
+            return None;
+        }
+
+        let name = self.components.meta(id).name();
+        self.component_borrows.check(entity, id, name, false);
+        let archetype = self.archetypes.archetype_id(entity)?;
+        let table = self.tables.get((*archetype).into())?;
+
+        table.cell(entity, id.into()).map(TableCell::into_ptr)
+    }
+}
@@ -0,0 +1,142 @@
+use crate::core::{ComponentId, Entity};
+use std::fmt::Display;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WorldError {
+    /// The requested entity id is already in use.
+    EntityAlive(Entity),
+    /// The requested generation is older than the id's current generation.
+    StaleEntity(Entity),
+    EntityDead(Entity),
+    UnknownComponent(ComponentId),
+    /// A component value whose layout doesn't match the registered component.
+    LayoutMismatch {
+        component: ComponentId,
+        name: &'static str,
+    },
+}
+
+impl Display for WorldError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WorldError::EntityAlive(entity) => write!(f, "{:?} is already alive", entity),
+            WorldError::StaleEntity(entity) => {
+                write!(f, "{:?} is older than the id's current generation", entity)
+            }
+            WorldError::EntityDead(entity) => write!(f, "{:?} is not alive", entity),
+            WorldError::UnknownComponent(id) => {
+                write!(f, "component {} is not registered", id.id())
+            }
+            WorldError::LayoutMismatch { name, .. } => {
+                write!(f, "value layout does not match component {}", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for WorldError {}
+
+/// Why a resource couldn't be borrowed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResourceError {
+    Missing(&'static str),
+    /// A write was asked for while the resource is borrowed immutably.
+    Borrowed(&'static str),
+    BorrowedMut(&'static str),
+}
+
+impl Display for ResourceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResourceError::Missing(name) => write!(f, "resource {} doesn't exist", name),
+            ResourceError::Borrowed(name) => {
+                write!(f, "resource {} is already borrowed immutably", name)
+            }
+            ResourceError::BorrowedMut(name) => {
+                write!(f, "resource {} is already borrowed mutably", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ResourceError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReflectError {
+    /// The component isn't registered with `World::register_reflect`.
+    NotReflected(ComponentId),
+    MissingComponent {
+        entity: Entity,
+        component: &'static str,
+    },
+    UnknownField {
+        component: &'static str,
+        path: String,
+    },
+    /// The value is of another kind than the field, or out of its range.
+    InvalidValue {
+        component: &'static str,
+        path: String,
+    },
+}
+
+impl Display for ReflectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReflectError::NotReflected(id) => {
+                write!(f, "component {} is not registered for reflection", id.id())
+            }
+            ReflectError::MissingComponent { entity, component } => {
+                write!(f, "{:?} has no component {}", entity, component)
+            }
+            ReflectError::UnknownField { component, path } => {
+                write!(f, "component {} has no field {}", component, path)
+            }
+            ReflectError::InvalidValue { component, path } => {
+                write!(
+                    f,
+                    "field {} of component {} can't hold the value",
+                    path, component
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReflectError {}
+
+/// Why `SnapshotLoader::load` refused a snapshot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotError {
+    /// A value was saved under a key nothing is registered or aliased as.
+    UnknownKey {
+        key: String,
+        /// The registered key closest to it.
+        suggestion: Option<String>,
+    },
+    /// A saved field is missing from the type or can't hold the value.
+    InvalidField { key: String, path: String },
+}
+
+impl Display for SnapshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SnapshotError::UnknownKey { key, suggestion } => {
+                write!(f, "nothing is registered as {}", key)?;
+                match suggestion {
+                    Some(suggestion) => write!(f, ", did you mean {}?", suggestion),
+                    None => Ok(()),
+                }
+            }
+            SnapshotError::InvalidField { key, path } => {
+                write!(
+                    f,
+                    "field {} of {} doesn't fit the registered type",
+                    path, key
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
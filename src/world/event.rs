@@ -0,0 +1,201 @@
+use super::{
+    meta::{Access, AccessMeta, AccessType},
+    resource::Resource,
+    World,
+};
+use crate::system::{local::Local, ReadOnlySystemArg, SystemArg};
+use std::marker::PhantomData;
+
+/// Messages of type `T` sent between systems, added with `World::add_event`.
+/// Events stay readable for the run they were sent in and the next one, then
+/// are dropped.
+pub struct Events<T: Send + Sync + 'static> {
+    previous: Vec<T>,
+    current: Vec<T>,
+    /// Id of the first event in `previous`.
+    start: usize,
+}
+
+impl<T: Send + Sync + 'static> Default for Events<T> {
+    fn default() -> Self {
+        Self {
+            previous: Vec::new(),
+            current: Vec::new(),
+            start: 0,
+        }
+    }
+}
+
+impl<T: Send + Sync + 'static> Events<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn send(&mut self, event: T) {
+        self.current.push(event);
+    }
+
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = T>) {
+        self.current.extend(events);
+    }
+
+    /// Drops the events of the previous run and keeps the current ones for
+    /// one more. `World::run` calls this after its flush.
+    pub fn update(&mut self) {
+        self.start += self.previous.len();
+        std::mem::swap(&mut self.previous, &mut self.current);
+        self.current.clear();
+    }
+
+    pub fn clear(&mut self) {
+        self.start += self.previous.len() + self.current.len();
+        self.previous.clear();
+        self.current.clear();
+    }
+
+    /// Every event still buffered, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.previous.iter().chain(self.current.iter())
+    }
+
+    pub fn len(&self) -> usize {
+        self.previous.len() + self.current.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Id the next event sent will get.
+    fn end(&self) -> usize {
+        self.start + self.len()
+    }
+
+    /// Buffered events with an id of `cursor` or later.
+    fn since(&self, cursor: usize) -> impl Iterator<Item = &T> {
+        self.iter().skip(cursor.saturating_sub(self.start))
+    }
+}
+
+impl<T: Send + Sync + 'static> Resource for Events<T> {}
+
+impl World {
+    /// Adds the `Events<T>` resource and has every `run` update it. Does
+    /// nothing if it was already added.
+    pub fn add_event<T: Send + Sync + 'static>(&mut self) {
+        if self.try_resource::<Events<T>>().is_some() {
+            return;
+        }
+
+        self.add_resource(Events::<T>::new());
+        self.events
+            .push(|world| world.resource_mut::<Events<T>>().update());
+    }
+
+    pub(crate) fn update_events(&mut self) {
+        for update in self.events.clone() {
+            update(self);
+        }
+    }
+}
+
+/// Sends events of type `T`. Using it in a system adds the event.
+pub struct EventWriter<'a, T: Send + Sync + 'static> {
+    events: &'a mut Events<T>,
+}
+
+impl<T: Send + Sync + 'static> EventWriter<'_, T> {
+    pub fn send(&mut self, event: T) {
+        self.events.send(event);
+    }
+
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = T>) {
+        self.events.send_batch(events);
+    }
+}
+
+impl<T: Send + Sync + 'static> SystemArg for EventWriter<'_, T> {
+    type Item<'a> = EventWriter<'a, T>;
+
+    fn get<'a>(world: &'a World) -> Self::Item<'a> {
+        EventWriter {
+            events: <&mut Events<T> as SystemArg>::get(world),
+        }
+    }
+
+    fn metas() -> Vec<AccessMeta> {
+        let ty = AccessType::resource::<Events<T>>();
+        vec![AccessMeta::new(ty, Access::Write)]
+    }
+
+    fn register(world: &mut World) {
+        world.add_event::<T>();
+    }
+}
+
+struct EventCursor<T> {
+    next: usize,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Default for EventCursor<T> {
+    fn default() -> Self {
+        Self {
+            next: 0,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Reads events of type `T`. Each system keeps its own cursor, so it sees
+/// every event once: events sent earlier in the same run as well as those
+/// from the previous one. Using it in a system adds the event.
+pub struct EventReader<'a, T: Send + Sync + 'static> {
+    events: &'a Events<T>,
+    cursor: &'a mut usize,
+}
+
+impl<T: Send + Sync + 'static> EventReader<'_, T> {
+    /// Events not read yet, oldest first. Marks them read.
+    pub fn read(&mut self) -> impl Iterator<Item = &T> {
+        let cursor = std::mem::replace(self.cursor, self.events.end());
+        self.events.since(cursor)
+    }
+
+    /// Number of events not read yet.
+    pub fn len(&self) -> usize {
+        self.events.since(*self.cursor).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Marks every event read without visiting them.
+    pub fn clear(&mut self) {
+        *self.cursor = self.events.end();
+    }
+}
+
+impl<T: Send + Sync + 'static> SystemArg for EventReader<'_, T> {
+    type Item<'a> = EventReader<'a, T>;
+
+    fn get<'a>(world: &'a World) -> Self::Item<'a> {
+        let cursor = <Local<EventCursor<T>> as SystemArg>::get(world).into_inner();
+        EventReader {
+            events: <&Events<T> as SystemArg>::get(world),
+            cursor: &mut cursor.next,
+        }
+    }
+
+    fn metas() -> Vec<AccessMeta> {
+        let ty = AccessType::resource::<Events<T>>();
+        vec![AccessMeta::new(ty, Access::Read)]
+    }
+
+    fn register(world: &mut World) {
+        world.add_event::<T>();
+    }
+}
+
+unsafe impl<T: Send + Sync + 'static> ReadOnlySystemArg for EventReader<'_, T> {}
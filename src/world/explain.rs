@@ -0,0 +1,239 @@
+use super::{query::QueryState, World};
+use crate::{
+    archetype::{Archetype, ArchetypeId},
+    core::{ComponentId, Entity},
+    storage::table::TableId,
+};
+use std::{collections::HashMap, fmt::Display};
+
+/// Why an archetype was or wasn't matched by a query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArchetypeVerdict {
+    Matched,
+    /// Lacks these required components.
+    Missing(Vec<ComponentId>),
+    /// Has these components the query filters out with `Not`.
+    ExcludedBy(Vec<ComponentId>),
+    /// Has no components and the query uses `NonEmpty`.
+    Empty,
+    /// Matches, but no table exists for it.
+    TableMissing,
+}
+
+impl ArchetypeVerdict {
+    pub fn is_matched(&self) -> bool {
+        matches!(self, ArchetypeVerdict::Matched)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchetypeExplain {
+    pub id: ArchetypeId,
+    pub entities: usize,
+    pub verdict: ArchetypeVerdict,
+}
+
+/// Why an entity passed to `Query::entities` was or wasn't kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntityVerdict {
+    Matched,
+    NotAlive,
+    Filtered(ArchetypeVerdict),
+}
+
+/// Report built by `Query::explain`.
+#[derive(Debug, Clone)]
+pub struct QueryExplain {
+    pub query: &'static str,
+    pub required: Vec<ComponentId>,
+    pub excluded: Vec<ComponentId>,
+    pub exclude_empty: bool,
+    pub archetypes: Vec<ArchetypeExplain>,
+    /// Set for queries restricted with `Query::entities`.
+    pub entities: Option<Vec<(Entity, EntityVerdict)>>,
+    names: HashMap<ComponentId, &'static str>,
+}
+
+impl QueryExplain {
+    pub(crate) fn new(
+        world: &World,
+        query: &'static str,
+        state: &QueryState,
+        input: Option<&[Entity]>,
+    ) -> Self {
+        let names = state
+            .components()
+            .iter()
+            .chain(state.with())
+            .chain(state.without())
+            .map(|id| (*id, world.components().meta(*id).name()))
+            .collect();
+
+        let archetypes = world
+            .archetypes()
+            .iter()
+            .map(|archetype| ArchetypeExplain {
+                id: *archetype.id(),
+                entities: archetype.entities().len(),
+                verdict: Self::verdict(world, state, archetype),
+            })
+            .collect();
+
+        let entities = input.map(|input| {
+            input
+                .iter()
+                .map(|entity| {
+                    let verdict = match world.archetypes().entity_archetype(*entity) {
+                        Some(archetype) if world.entities().contains(*entity) => {
+                            match Self::verdict(world, state, archetype) {
+                                ArchetypeVerdict::Matched => EntityVerdict::Matched,
+                                verdict => EntityVerdict::Filtered(verdict),
+                            }
+                        }
+                        _ => EntityVerdict::NotAlive,
+                    };
+                    (*entity, verdict)
+                })
+                .collect()
+        });
+
+        Self {
+            query,
+            required: [state.components(), state.with()].concat(),
+            excluded: state.without().to_vec(),
+            exclude_empty: state.excludes_empty(),
+            archetypes,
+            entities,
+            names,
+        }
+    }
+
+    fn verdict(world: &World, state: &QueryState, archetype: &Archetype) -> ArchetypeVerdict {
+        let components = archetype.components();
+        let missing = state
+            .components()
+            .iter()
+            .chain(state.with())
+            .filter(|id| !components.contains(id))
+            .copied()
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return ArchetypeVerdict::Missing(missing);
+        }
+
+        let excluded = state
+            .without()
+            .iter()
+            .filter(|id| components.contains(id))
+            .copied()
+            .collect::<Vec<_>>();
+        if !excluded.is_empty() {
+            return ArchetypeVerdict::ExcludedBy(excluded);
+        }
+
+        if state.excludes_empty() && components.is_empty() {
+            return ArchetypeVerdict::Empty;
+        }
+
+        let table: TableId = (*archetype.id()).into();
+        match world.tables().get(table) {
+            Some(_) => ArchetypeVerdict::Matched,
+            None => ArchetypeVerdict::TableMissing,
+        }
+    }
+
+    /// Registered name of a component referenced by the query.
+    pub fn name(&self, id: ComponentId) -> &'static str {
+        self.names.get(&id).copied().unwrap_or("<unknown>")
+    }
+
+    pub fn matched_archetypes(&self) -> usize {
+        self.archetypes
+            .iter()
+            .filter(|archetype| archetype.verdict.is_matched())
+            .count()
+    }
+
+    /// Entities the query yields.
+    pub fn matched_entities(&self) -> usize {
+        match &self.entities {
+            Some(entities) => entities
+                .iter()
+                .filter(|(_, verdict)| *verdict == EntityVerdict::Matched)
+                .count(),
+            None => self
+                .archetypes
+                .iter()
+                .filter(|archetype| archetype.verdict.is_matched())
+                .map(|archetype| archetype.entities)
+                .sum(),
+        }
+    }
+
+    fn names(&self, ids: &[ComponentId]) -> String {
+        if ids.is_empty() {
+            return "none".to_string();
+        }
+
+        ids.iter()
+            .map(|id| self.name(*id))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn write_verdict(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        verdict: &ArchetypeVerdict,
+    ) -> std::fmt::Result {
+        match verdict {
+            ArchetypeVerdict::Matched => write!(f, "matched"),
+            ArchetypeVerdict::Missing(ids) => write!(f, "missing {}", self.names(ids)),
+            ArchetypeVerdict::ExcludedBy(ids) => write!(f, "excluded by {}", self.names(ids)),
+            ArchetypeVerdict::Empty => write!(f, "excluded as empty"),
+            ArchetypeVerdict::TableMissing => write!(f, "table missing"),
+        }
+    }
+}
+
+impl Display for QueryExplain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "query {}", self.query)?;
+        writeln!(f, "  requires: {}", self.names(&self.required))?;
+        writeln!(f, "  excludes: {}", self.names(&self.excluded))?;
+        if self.exclude_empty {
+            writeln!(f, "  excludes empty archetypes")?;
+        }
+
+        for archetype in &self.archetypes {
+            write!(
+                f,
+                "  archetype {} ({} entities): ",
+                archetype.id.id(),
+                archetype.entities
+            )?;
+            self.write_verdict(f, &archetype.verdict)?;
+            writeln!(f)?;
+        }
+
+        if let Some(entities) = &self.entities {
+            for (entity, verdict) in entities {
+                write!(f, "  entity {:?}: ", entity)?;
+                match verdict {
+                    EntityVerdict::Matched => write!(f, "matched")?,
+                    EntityVerdict::NotAlive => write!(f, "not alive")?,
+                    EntityVerdict::Filtered(verdict) => self.write_verdict(f, verdict)?,
+                }
+                writeln!(f)?;
+            }
+        }
+
+        write!(
+            f,
+            "  matched {} of {} archetypes, {} entities",
+            self.matched_archetypes(),
+            self.archetypes.len(),
+            self.matched_entities()
+        )
+    }
+}
@@ -0,0 +1,181 @@
+use super::World;
+use crate::system::observer::action::{Action, Actions};
+use std::{
+    fmt::Display,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver, SyncSender, TryRecvError, TrySendError},
+        Arc, Mutex,
+    },
+};
+
+pub const DEFAULT_EXTERNAL_CAPACITY: usize = 1024;
+
+/// What `CommandSender::send` does when the queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backpressure {
+    /// Wait until the world drains the queue.
+    #[default]
+    Block,
+    /// Drop the command and count it.
+    Drop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendError {
+    /// The queue was full and the policy is `Backpressure::Drop`.
+    Dropped,
+    /// The world was dropped or its queue reconfigured.
+    Disconnected,
+}
+
+impl Display for SendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SendError::Dropped => write!(f, "external command queue is full"),
+            SendError::Disconnected => write!(f, "external command queue is closed"),
+        }
+    }
+}
+
+impl std::error::Error for SendError {}
+
+/// An action built on another thread, erased together with the code that
+/// queues it.
+pub struct ExternalCommand {
+    enqueue: Box<dyn FnOnce(&mut Actions) + Send>,
+}
+
+impl ExternalCommand {
+    pub fn new<A: Action + Send>(action: A) -> Self {
+        Self {
+            enqueue: Box::new(move |actions| actions.add(action)),
+        }
+    }
+
+    pub fn enqueue(self, actions: &mut Actions) {
+        (self.enqueue)(actions)
+    }
+}
+
+/// Cloneable handle for queueing actions from other threads. Commands are
+/// moved into `Actions` at the start of the next `World::run`.
+#[derive(Clone)]
+pub struct CommandSender {
+    sender: SyncSender<ExternalCommand>,
+    policy: Backpressure,
+    dropped: Arc<AtomicUsize>,
+}
+
+impl CommandSender {
+    pub fn send<A: Action + Send>(&self, action: A) -> Result<(), SendError> {
+        let command = ExternalCommand::new(action);
+        match self.policy {
+            Backpressure::Block => self
+                .sender
+                .send(command)
+                .map_err(|_| SendError::Disconnected),
+            Backpressure::Drop => match self.sender.try_send(command) {
+                Ok(()) => Ok(()),
+                Err(TrySendError::Full(_)) => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    Err(SendError::Dropped)
+                }
+                Err(TrySendError::Disconnected(_)) => Err(SendError::Disconnected),
+            },
+        }
+    }
+
+    pub fn policy(&self) -> Backpressure {
+        self.policy
+    }
+}
+
+pub struct ExternalCommandQueue {
+    sender: SyncSender<ExternalCommand>,
+    receiver: Mutex<Receiver<ExternalCommand>>,
+    policy: Backpressure,
+    dropped: Arc<AtomicUsize>,
+}
+
+impl ExternalCommandQueue {
+    pub fn new(capacity: usize, policy: Backpressure) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(capacity);
+        Self {
+            sender,
+            receiver: Mutex::new(receiver),
+            policy,
+            dropped: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn sender(&self) -> CommandSender {
+        CommandSender {
+            sender: self.sender.clone(),
+            policy: self.policy,
+            dropped: self.dropped.clone(),
+        }
+    }
+
+    /// Commands dropped by senders since the queue was created.
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Moves every command received so far into `actions`. Returns how many
+    /// were moved.
+    pub fn drain(&self, actions: &mut Actions) -> usize {
+        let receiver = self.receiver.lock().unwrap();
+        let mut count = 0;
+        loop {
+            match receiver.try_recv() {
+                Ok(command) => {
+                    command.enqueue(actions);
+                    count += 1;
+                }
+                Err(TryRecvError::Empty | TryRecvError::Disconnected) => break count,
+            }
+        }
+    }
+}
+
+impl Default for ExternalCommandQueue {
+    fn default() -> Self {
+        Self::new(DEFAULT_EXTERNAL_CAPACITY, Backpressure::default())
+    }
+}
+
+impl World {
+    /// A sender for queueing actions from other threads. The queue is
+    /// created on first use with `DEFAULT_EXTERNAL_CAPACITY` and
+    /// `Backpressure::Block` unless configured first.
+    pub fn external_sender(&mut self) -> CommandSender {
+        self.external
+            .get_or_insert_with(ExternalCommandQueue::default)
+            .sender()
+    }
+
+    /// Replaces the external queue. Commands still in the old queue are
+    /// moved into `Actions`; senders handed out before return
+    /// `SendError::Disconnected` from then on.
+    pub fn configure_external_commands(&mut self, capacity: usize, policy: Backpressure) {
+        self.drain_external_commands();
+        self.external = Some(ExternalCommandQueue::new(capacity, policy));
+    }
+
+    pub fn dropped_external_commands(&self) -> usize {
+        self.external
+            .as_ref()
+            .map(|queue| queue.dropped())
+            .unwrap_or(0)
+    }
+
+    /// Moves commands sent from other threads into `Actions`. Called at the
+    /// start of every `run`.
+    pub fn drain_external_commands(&mut self) -> usize {
+        match &self.external {
+            Some(queue) => queue.drain(&mut self.resources.get_mut::<Actions>()),
+            None => 0,
+        }
+    }
+}
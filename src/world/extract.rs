@@ -0,0 +1,168 @@
+use super::World;
+use crate::core::{Component, ComponentId, Entity};
+use std::collections::{HashMap, HashSet};
+
+/// Copies one mirrored component of a sim entity onto its target. Returns
+/// true if a value was written.
+type MirrorFn = fn(&World, Entity, &mut World, Entity, Option<u64>) -> bool;
+
+struct Mirror {
+    id: fn(&World) -> Option<ComponentId>,
+    register: fn(&mut World),
+    copy: MirrorFn,
+}
+
+/// Mirrors a set of components from one world into another, e.g. from the
+/// simulation into a render world once per frame. Every sim entity with at
+/// least one mirrored component gets a target entity that stays the same
+/// across extractions and holds clones of those components. Target entities
+/// are deleted along with their sim entity, or once it has none of them.
+///
+/// ```ignore
+/// let mut extractor = WorldExtractor::new().mirror::<Transform>().mirror::<Sprite>();
+/// extractor.extract(&sim, &mut render);
+/// ```
+#[derive(Default)]
+pub struct WorldExtractor {
+    mirrors: Vec<Mirror>,
+    entities: HashMap<Entity, Entity>,
+    since: Option<u64>,
+    copies: usize,
+}
+
+impl WorldExtractor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn mirror<C: Component + Clone>(mut self) -> Self {
+        self.mirrors.push(Mirror {
+            id: |world| world.components().get_id::<C>(),
+            register: |world| world.register::<C>(),
+            copy: mirror::<C>,
+        });
+        self
+    }
+
+    /// The target entity mirroring `entity`.
+    pub fn target(&self, entity: Entity) -> Option<Entity> {
+        self.entities.get(&entity).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Component values written by the last `extract`.
+    pub fn copies(&self) -> usize {
+        self.copies
+    }
+
+    /// Brings `target` in line with `sim`. The matched set is diffed in
+    /// full; values are only copied when they are new to the target entity
+    /// or changed in `sim` since the previous extraction.
+    pub fn extract(&mut self, sim: &World, target: &mut World) {
+        for mirror in &self.mirrors {
+            (mirror.register)(target);
+        }
+
+        let ids = self
+            .mirrors
+            .iter()
+            .filter_map(|mirror| (mirror.id)(sim))
+            .collect::<Vec<_>>();
+
+        let mut matched = Vec::new();
+        for entity in sim.entities().iter() {
+            let Some(archetype) = sim.archetypes().entity_archetype(entity) else {
+                continue;
+            };
+
+            if archetype.components().iter().any(|id| ids.contains(id)) {
+                matched.push(entity);
+            }
+        }
+
+        let live = matched.iter().copied().collect::<HashSet<_>>();
+        let stale = self
+            .entities
+            .keys()
+            .filter(|entity| !live.contains(entity))
+            .copied()
+            .collect::<Vec<_>>();
+        for entity in stale {
+            if let Some(mirrored) = self.entities.remove(&entity) {
+                target.delete(mirrored);
+            }
+        }
+
+        self.copies = 0;
+        for entity in matched {
+            let (mirrored, since) = match self.entities.get(&entity) {
+                Some(mirrored) if target.entities().contains(*mirrored) => (*mirrored, self.since),
+                _ => (target.create(), None),
+            };
+            self.entities.insert(entity, mirrored);
+
+            for mirror in &self.mirrors {
+                if (mirror.copy)(sim, entity, target, mirrored, since) {
+                    self.copies += 1;
+                }
+            }
+        }
+
+        // Writes made after this point get a newer tick than the one kept.
+        self.since = Some(sim.change_tick());
+        sim.increment_change_tick();
+    }
+}
+
+fn mirror<C: Component + Clone>(
+    sim: &World,
+    entity: Entity,
+    target: &mut World,
+    mirrored: Entity,
+    since: Option<u64>,
+) -> bool {
+    let value = match sim.components().contains::<C>() {
+        true => sim.component::<C>(entity),
+        false => None,
+    };
+
+    let Some(value) = value else {
+        if target.has::<C>(mirrored) {
+            target.remove_component::<C>(mirrored);
+        }
+        return false;
+    };
+
+    if !target.has::<C>(mirrored) {
+        target.add_component(mirrored, value.clone());
+        return true;
+    }
+
+    if since.is_some_and(|since| changed_tick::<C>(sim, entity).is_some_and(|tick| tick <= since)) {
+        return false;
+    }
+
+    match target.component_mut::<C>(mirrored) {
+        Some(mut mirrored) => {
+            *mirrored = value.clone();
+            true
+        }
+        None => false,
+    }
+}
+
+fn changed_tick<C: Component>(world: &World, entity: Entity) -> Option<u64> {
+    let table = world.archetypes().archetype_id(entity)?;
+    let column = world.component_id::<C>();
+    world
+        .tables()
+        .get((*table).into())?
+        .changed_tick(entity, column.into())
+}
@@ -0,0 +1,148 @@
+use super::{resource::Resource, World};
+use crate::{
+    archetype::Archetypes,
+    core::{ComponentId, Entities, Entity},
+    storage::table::{TableId, Tables},
+};
+use std::{any::TypeId, fmt::Display};
+
+type RestoreFn = Box<dyn Fn(&mut World) + Send + Sync>;
+pub(crate) type CaptureFn = fn(&World) -> Option<RestoreFn>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ForkError {
+    /// A table holds values of a component registered without `cloneable`.
+    NotCloneable {
+        table: TableId,
+        component: ComponentId,
+        name: &'static str,
+    },
+    /// The fork was taken from another world.
+    WrongWorld,
+}
+
+impl Display for ForkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ForkError::NotCloneable { name, .. } => {
+                write!(f, "component {} is not cloneable and can't be forked", name)
+            }
+            ForkError::WrongWorld => write!(f, "fork was taken from another world"),
+        }
+    }
+}
+
+impl std::error::Error for ForkError {}
+
+/// A checkpoint of a world's entities and table data, taken by
+/// `World::fork`. Columns are shared with the world until either side writes
+/// to them, so a fork costs the entity and archetype metadata plus one
+/// reference per column.
+///
+/// Only storage is captured. Resources are left out unless opted in with
+/// `World::fork_resource`; queued actions, pending outputs, observers,
+/// schedules, tracking logs, scopes and the frame counter are never part of
+/// a fork.
+pub struct WorldFork {
+    registry: u32,
+    tables: Tables<Entity>,
+    archetypes: Archetypes,
+    entities: Entities,
+    resources: Vec<RestoreFn>,
+}
+
+impl WorldFork {
+    pub fn tables(&self) -> &Tables<Entity> {
+        &self.tables
+    }
+
+    pub fn archetypes(&self) -> &Archetypes {
+        &self.archetypes
+    }
+
+    pub fn entities(&self) -> &Entities {
+        &self.entities
+    }
+}
+
+fn capture<R: Resource + Clone>(world: &World) -> Option<RestoreFn> {
+    if !world.resources.contains::<R>() {
+        return None;
+    }
+
+    let value = world.resource::<R>().clone();
+    Some(Box::new(move |world: &mut World| {
+        if world.resources.contains::<R>() {
+            *world.resource_mut::<R>() = value.clone();
+        } else {
+            world.add_resource(value.clone());
+        }
+    }))
+}
+
+impl World {
+    /// Includes a clone of `R` in every fork taken from now on.
+    pub fn fork_resource<R: Resource + Clone>(&mut self) {
+        let ty = TypeId::of::<R>();
+        if !self.forked_resources.iter().any(|(other, _)| *other == ty) {
+            self.forked_resources.push((ty, capture::<R>));
+        }
+    }
+
+    /// Checkpoints the world's entities and component storage. Every
+    /// component with values in a table must be registered as `cloneable`.
+    pub fn fork(&mut self) -> Result<WorldFork, ForkError> {
+        self.borrows.assert_unborrowed();
+        let components = &self.components;
+        let tables = self
+            .tables
+            .share(|column| components.meta(ComponentId::new(column)).clone_fn())
+            .map_err(|(table, column)| {
+                let component = ComponentId::new(column);
+                ForkError::NotCloneable {
+                    table,
+                    component,
+                    name: components.meta(component).name(),
+                }
+            })?;
+
+        Ok(WorldFork {
+            registry: self.components.registry(),
+            tables,
+            archetypes: self.archetypes.clone(),
+            entities: self.entities.clone(),
+            resources: self
+                .forked_resources
+                .iter()
+                .filter_map(|(_, capture)| capture(self))
+                .collect(),
+        })
+    }
+
+    /// Puts the world's entities, component storage and forked resources
+    /// back to how they were when `fork` was taken. The fork can be restored
+    /// again later.
+    pub fn restore(&mut self, fork: &WorldFork) -> Result<(), ForkError> {
+        if fork.registry != self.components.registry() {
+            return Err(ForkError::WrongWorld);
+        }
+
+        self.borrows.assert_unborrowed();
+        let components = &self.components;
+        self.tables = fork
+            .tables
+            .share(|column| components.meta(ComponentId::new(column)).clone_fn())
+            .expect("forked tables are cloneable");
+        self.archetypes = fork.archetypes.clone();
+        self.entities = fork.entities.clone();
+        self.transitions.clear();
+        self.queries.clear();
+        self.rebuild_indexes();
+
+        for restore in &fork.resources {
+            restore(self);
+        }
+
+        Ok(())
+    }
+}
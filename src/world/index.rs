@@ -0,0 +1,248 @@
+use super::{
+    meta::{Access, AccessMeta, AccessType},
+    World,
+};
+use crate::{
+    core::{Component, ComponentId, Entity},
+    system::{ReadOnlySystemArg, SystemArg},
+};
+use std::{any::Any, collections::HashMap, hash::Hash};
+
+/// Bound for components that can be indexed by value.
+pub trait IndexedComponent: Component + Eq + Hash + Clone {}
+
+impl<C: Component + Eq + Hash + Clone> IndexedComponent for C {}
+
+trait ValueIndex: Send + Sync {
+    /// Brings `entity`'s entry in line with its current value in `world`.
+    fn sync(&mut self, entity: Entity, world: &World);
+    fn remove(&mut self, entity: Entity);
+    fn clear(&mut self);
+    fn as_any(&self) -> &dyn Any;
+}
+
+/// Entities by their value of `C`.
+pub struct ComponentIndex<C: IndexedComponent> {
+    entities: HashMap<C, Vec<Entity>>,
+    values: HashMap<Entity, C>,
+}
+
+impl<C: IndexedComponent> ComponentIndex<C> {
+    fn new() -> Self {
+        Self {
+            entities: HashMap::new(),
+            values: HashMap::new(),
+        }
+    }
+
+    /// Entities whose `C` equals `value`, in the order they got it.
+    pub fn find(&self, value: &C) -> &[Entity] {
+        self.entities.get(value).map_or(&[], |entities| entities)
+    }
+
+    pub fn value(&self, entity: Entity) -> Option<&C> {
+        self.values.get(&entity)
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+impl<C: IndexedComponent> ValueIndex for ComponentIndex<C> {
+    fn sync(&mut self, entity: Entity, world: &World) {
+        match world.component::<C>(entity) {
+            Some(value) if self.values.get(&entity) == Some(&*value) => {}
+            Some(value) => {
+                let value = value.clone();
+                self.remove(entity);
+                self.entities.entry(value.clone()).or_default().push(entity);
+                self.values.insert(entity, value);
+            }
+            None => self.remove(entity),
+        }
+    }
+
+    fn remove(&mut self, entity: Entity) {
+        let Some(value) = self.values.remove(&entity) else {
+            return;
+        };
+
+        if let Some(entities) = self.entities.get_mut(&value) {
+            entities.retain(|other| *other != entity);
+            if entities.is_empty() {
+                self.entities.remove(&value);
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entities.clear();
+        self.values.clear();
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Value indexes registered with `World::register_index`.
+#[derive(Default)]
+pub struct ComponentIndexes {
+    indexes: HashMap<ComponentId, Box<dyn ValueIndex>>,
+}
+
+impl ComponentIndexes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get<C: IndexedComponent>(&self, id: ComponentId) -> Option<&ComponentIndex<C>> {
+        let index = self.indexes.get(&id)?;
+        index.as_any().downcast_ref::<ComponentIndex<C>>()
+    }
+
+    pub fn contains(&self, id: ComponentId) -> bool {
+        self.indexes.contains_key(&id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.indexes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indexes.is_empty()
+    }
+}
+
+impl World {
+    /// Starts keeping entities indexed by their value of `C`, for
+    /// `find_by`. The index follows every add, remove and delete, and
+    /// `SetComponent`. Writes through `&mut C`, from queries or
+    /// `component_mut`, can't be seen: change indexed components with
+    /// `SetComponent` or `update_indexed` instead.
+    pub fn register_index<C: IndexedComponent>(&mut self) {
+        self.register::<C>();
+        let id = self.components.id::<C>();
+        if self.indexes.contains(id) {
+            return;
+        }
+
+        let mut index = ComponentIndex::<C>::new();
+        for entity in self.entities.iter() {
+            index.sync(entity, self);
+        }
+        self.indexes.indexes.insert(id, Box::new(index));
+    }
+
+    pub fn index<C: IndexedComponent>(&self) -> Option<&ComponentIndex<C>> {
+        let id = self.components.get_id::<C>()?;
+        self.indexes.get::<C>(id)
+    }
+
+    /// Entities whose `C` equals `value`. Empty if `C` has no index.
+    pub fn find_by<C: IndexedComponent>(&self, value: &C) -> &[Entity] {
+        self.index::<C>().map_or(&[], |index| index.find(value))
+    }
+
+    /// Sets `entity`'s `C` to `value` and updates its index. Returns false
+    /// if the entity has no `C`.
+    pub fn update_indexed<C: IndexedComponent>(&mut self, entity: Entity, value: C) -> bool {
+        match self.component_mut::<C>(entity) {
+            Some(mut current) => *current = value,
+            None => return false,
+        }
+
+        let id = self.components.id::<C>();
+        self.reindex(entity, &[id]);
+        true
+    }
+
+    /// Updates the indexes of `components` for `entity` after they were
+    /// added, removed or written.
+    pub(crate) fn reindex(&mut self, entity: Entity, components: &[ComponentId]) {
+        if self.indexes.is_empty() {
+            return;
+        }
+
+        let mut indexes = std::mem::take(&mut self.indexes);
+        for id in components {
+            if let Some(index) = indexes.indexes.get_mut(id) {
+                index.sync(entity, self);
+            }
+        }
+        self.indexes = indexes;
+        self.check_unique_name(entity, components);
+    }
+
+    pub(crate) fn unindex(&mut self, entity: Entity) {
+        for index in self.indexes.indexes.values_mut() {
+            index.remove(entity);
+        }
+    }
+
+    /// Empties every index, after all entities were cleared.
+    pub(crate) fn clear_indexes(&mut self) {
+        for index in self.indexes.indexes.values_mut() {
+            index.clear();
+        }
+    }
+
+    /// Rebuilds every index from scratch, after storage was replaced
+    /// wholesale.
+    pub(crate) fn rebuild_indexes(&mut self) {
+        if self.indexes.is_empty() {
+            return;
+        }
+
+        let mut indexes = std::mem::take(&mut self.indexes);
+        for index in indexes.indexes.values_mut() {
+            index.clear();
+            for entity in self.entities.iter() {
+                index.sync(entity, self);
+            }
+        }
+        self.indexes = indexes;
+    }
+}
+
+/// Read-only access to the index of `C`. Using it in a system registers the
+/// index.
+pub struct Indexed<'a, C: IndexedComponent> {
+    index: Option<&'a ComponentIndex<C>>,
+}
+
+impl<C: IndexedComponent> Indexed<'_, C> {
+    pub fn find(&self, value: &C) -> &[Entity] {
+        self.index.map_or(&[], |index| index.find(value))
+    }
+
+    pub fn value(&self, entity: Entity) -> Option<&C> {
+        self.index.and_then(|index| index.value(entity))
+    }
+}
+
+impl<C: IndexedComponent> SystemArg for Indexed<'_, C> {
+    type Item<'a> = Indexed<'a, C>;
+
+    fn get<'a>(world: &'a World) -> Self::Item<'a> {
+        Indexed {
+            index: world.index::<C>(),
+        }
+    }
+
+    fn metas() -> Vec<AccessMeta> {
+        let ty = AccessType::component::<C>();
+        vec![AccessMeta::new(ty, Access::Read)]
+    }
+
+    fn register(world: &mut World) {
+        world.register_index::<C>();
+    }
+}
+
+unsafe impl<C: IndexedComponent> ReadOnlySystemArg for Indexed<'_, C> {}
@@ -10,6 +10,63 @@ use crate::{
 
 pub struct Lifecycle;
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntityEdit {
+    entity: Entity,
+    added: Vec<ComponentId>,
+    removed: Vec<ComponentId>,
+    replaced: Vec<ComponentId>,
+    from: Option<ArchetypeId>,
+    to: Option<ArchetypeId>,
+}
+
+impl EntityEdit {
+    pub fn new(entity: Entity) -> Self {
+        Self {
+            entity,
+            added: Vec::new(),
+            removed: Vec::new(),
+            replaced: Vec::new(),
+            from: None,
+            to: None,
+        }
+    }
+
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    /// Components the entity did not have before the edit.
+    pub fn added(&self) -> &[ComponentId] {
+        &self.added
+    }
+
+    pub fn removed(&self) -> &[ComponentId] {
+        &self.removed
+    }
+
+    /// Components whose values were overwritten in place.
+    pub fn replaced(&self) -> &[ComponentId] {
+        &self.replaced
+    }
+
+    pub fn from(&self) -> Option<ArchetypeId> {
+        self.from
+    }
+
+    pub fn to(&self) -> Option<ArchetypeId> {
+        self.to
+    }
+
+    pub fn moved(&self) -> bool {
+        self.from != self.to
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.replaced.is_empty()
+    }
+}
+
 impl Lifecycle {
     pub fn create_entity(entity: Entity, archetypes: &mut Archetypes, tables: &mut Tables<Entity>) {
         let table_id = ArchetypeId::new(&[]).into();
@@ -25,39 +82,126 @@ impl Lifecycle {
         table.add_row(entity, TableRow::new(entity, SparseSet::new()));
     }
 
+    /// Adds freshly created entities to the empty archetype and its table,
+    /// reserving the table rows up front.
+    pub fn create_entities(
+        entities: &[Entity],
+        archetypes: &mut Archetypes,
+        tables: &mut Tables<Entity>,
+    ) {
+        let table_id = ArchetypeId::new(&[]).into();
+        if tables.get(table_id).is_none() {
+            tables.insert(Table::<Entity>::with_capacity(entities.len()).build());
+        }
+
+        let table = tables.get_mut(table_id).unwrap();
+        table.reserve(entities.len());
+        archetypes.add_entities(entities);
+        for entity in entities {
+            table.add_row(*entity, TableRow::new(*entity, SparseSet::new()));
+        }
+    }
+
+    /// Places a new entity straight into the archetype made of `components`,
+    /// moving the values into its table row.
+    pub fn insert_entity(
+        entity: Entity,
+        components: Vec<(ComponentId, Blob)>,
+        tick: u64,
+        archetypes: &mut Archetypes,
+        tables: &mut Tables<Entity>,
+    ) -> ArchetypeId {
+        archetypes.add_entity(entity);
+        let ids = components.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+        let archetype = archetypes
+            .move_entity(entity, ids)
+            .expect("entity was just added");
+
+        let mut row = TableRow::new(entity, SparseSet::new());
+        for (id, blob) in components {
+            row.insert(id.into(), Self::inserted(blob, tick));
+        }
+
+        let table_id: TableId = archetype.into();
+        let table = if let Some(table) = tables.get_mut(table_id) {
+            table
+        } else {
+            tables.insert(Table::<Entity>::from_row(&row, 1));
+            tables.get_mut(table_id).unwrap()
+        };
+
+        table.add_row(entity, row);
+        archetype
+    }
+
     pub fn add_component<C: Component>(
         entity: Entity,
         component_id: ComponentId,
         component: C,
+        tick: u64,
         archetypes: &mut Archetypes,
         tables: &mut Tables<Entity>,
     ) {
         let mut blob = Blob::new::<C>();
         blob.push(component);
 
-        let archetype = archetypes.archetype_id(entity).cloned().unwrap();
-        let new_archetype_id = archetypes.add_component(entity, component_id).unwrap();
+        let inserts = vec![(component_id, blob)];
+        Self::edit_entity(entity, inserts, &[], tick, archetypes, tables);
+    }
+
+    /// Adds a clone of `value` to every entity of `from`, moving them all
+    /// with one archetype move and one destination table lookup. Returns
+    /// the entities that received the component.
+    pub fn add_component_batch<C: Component + Clone>(
+        from: ArchetypeId,
+        component_id: ComponentId,
+        value: &C,
+        tick: u64,
+        archetypes: &mut Archetypes,
+        tables: &mut Tables<Entity>,
+    ) -> Vec<Entity> {
+        let components = match archetypes.archetype(&from) {
+            Some(archetype) if !archetype.components().contains(&component_id) => {
+                archetype.added(component_id)
+            }
+            _ => return Vec::new(),
+        };
 
-        let old_table_id: TableId = archetype.into();
+        let Some((to, entities)) = archetypes.move_archetype(from, components) else {
+            return Vec::new();
+        };
 
-        let mut row = tables
-            .get_mut(old_table_id)
-            .unwrap()
-            .remove_row(entity)
-            .unwrap();
+        let Some(source) = tables.get_mut(from.into()) else {
+            return Vec::new();
+        };
 
-        row.insert(component_id.into(), Column::from_blob(blob));
+        let mut rows = Vec::with_capacity(entities.len());
+        for entity in entities.iter().rev() {
+            if let Some(mut row) = source.remove_row(*entity).map(|row| row.removed) {
+                let mut blob = Blob::new::<C>();
+                blob.push(value.clone());
+                row.insert(component_id.into(), Self::inserted(blob, tick));
+                rows.push((*entity, row));
+            }
+        }
 
-        let new_table_id: TableId = new_archetype_id.into();
-        let new_table = if let Some(table) = tables.get_mut(new_table_id) {
-            table
-        } else {
-            let table = Table::<Entity>::from_row(&row, 1);
-            tables.insert(table);
-            tables.get_mut(new_table_id).unwrap()
+        let table_id: TableId = to.into();
+        let table = match tables.get_mut(table_id) {
+            Some(table) => table,
+            None => {
+                let Some((_, row)) = rows.first() else {
+                    return entities;
+                };
+                tables.insert(Table::<Entity>::from_row(row, rows.len()));
+                tables.get_mut(table_id).unwrap()
+            }
         };
 
-        new_table.add_row(entity, row);
+        for (entity, row) in rows.into_iter().rev() {
+            table.add_row(entity, row);
+        }
+
+        entities
     }
 
     pub fn remove_component(
@@ -70,20 +214,69 @@ impl Lifecycle {
             return;
         }
 
-        let archetype = archetypes.archetype_id(entity).cloned().unwrap();
-        let new_archetype_id = archetypes.remove_component(entity, component_id).unwrap();
+        Self::edit_entity(entity, vec![], &[component_id], 0, archetypes, tables);
+    }
+
+    /// Applies every insert and removal with a single archetype transition.
+    /// Inserts of components the entity already has overwrite the value in place.
+    /// `inserts` and `removes` are expected to be disjoint.
+    pub fn edit_entity(
+        entity: Entity,
+        inserts: Vec<(ComponentId, Blob)>,
+        removes: &[ComponentId],
+        tick: u64,
+        archetypes: &mut Archetypes,
+        tables: &mut Tables<Entity>,
+    ) -> Option<EntityEdit> {
+        let archetype = archetypes.entity_archetype(entity)?;
+        let old_id = *archetype.id();
+        let current = archetype.components();
+
+        let mut edit = EntityEdit::new(entity);
+        edit.from = Some(old_id);
+        edit.removed = removes
+            .iter()
+            .filter(|c| current.contains(c))
+            .copied()
+            .collect();
+
+        let mut components = current
+            .iter()
+            .filter(|c| !edit.removed.contains(c))
+            .copied()
+            .collect::<Vec<_>>();
+
+        for (id, _) in &inserts {
+            if components.contains(id) {
+                edit.replaced.push(*id);
+            } else {
+                edit.added.push(*id);
+                components.push(*id);
+            }
+        }
+
+        if edit.added.is_empty() && edit.removed.is_empty() {
+            let table = tables.get_mut(old_id.into())?;
+            for (id, blob) in inserts {
+                table.replace(entity, id.into(), blob, tick);
+            }
+
+            edit.to = Some(old_id);
+            return Some(edit);
+        }
 
-        let old_table_id: TableId = archetype.into();
+        let new_id = archetypes.move_entity(entity, components)?;
+        let mut row = tables.get_mut(old_id.into())?.remove_row(entity)?.removed;
 
-        let mut row = tables
-            .get_mut(old_table_id)
-            .unwrap()
-            .remove_row(entity)
-            .unwrap();
+        for id in &edit.removed {
+            row.remove((*id).into());
+        }
 
-        row.remove(component_id.into());
+        for (id, blob) in inserts {
+            row.insert(id.into(), Self::inserted(blob, tick));
+        }
 
-        let new_table_id: TableId = new_archetype_id.into();
+        let new_table_id: TableId = new_id.into();
         let new_table = if let Some(table) = tables.get_mut(new_table_id) {
             table
         } else {
@@ -93,6 +286,16 @@ impl Lifecycle {
         };
 
         new_table.add_row(entity, row);
+        edit.to = Some(new_id);
+
+        Some(edit)
+    }
+
+    /// A row column for a newly inserted value, stamped as changed at `tick`.
+    fn inserted(blob: Blob, tick: u64) -> Column {
+        let column = Column::from_blob(blob);
+        column.set_changed_tick(0, tick);
+        column
     }
 
     pub fn delete_entity(
@@ -104,6 +307,6 @@ impl Lifecycle {
         let table_id = (*archetype).into();
 
         let table = tables.get_mut(table_id)?;
-        table.remove_row(entity)
+        table.remove_row(entity).map(|row| row.removed)
     }
 }
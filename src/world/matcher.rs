@@ -0,0 +1,213 @@
+use super::{
+    query::{BaseQuery, FilterQuery, QueryState},
+    transition::ArchetypeTransition,
+    World,
+};
+use crate::{
+    archetype::ArchetypeId,
+    core::Entity,
+    system::observer::{
+        action::{Action, ActionOutputs},
+        IntoObserver, Observers,
+    },
+};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MatcherId(usize);
+
+impl MatcherId {
+    pub fn id(&self) -> usize {
+        self.0
+    }
+}
+
+struct Matcher {
+    name: String,
+    state: QueryState,
+}
+
+/// Query signatures registered with `World::register_matcher`.
+#[derive(Default)]
+pub struct Matchers {
+    matchers: Vec<Matcher>,
+}
+
+impl Matchers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, name: &str) -> Option<MatcherId> {
+        self.matchers
+            .iter()
+            .position(|matcher| matcher.name == name)
+            .map(MatcherId)
+    }
+
+    pub fn name(&self, id: MatcherId) -> Option<&str> {
+        self.matchers.get(id.0).map(|matcher| matcher.name.as_str())
+    }
+
+    pub fn state(&self, id: MatcherId) -> Option<&QueryState> {
+        self.matchers.get(id.0).map(|matcher| &matcher.state)
+    }
+
+    pub fn len(&self) -> usize {
+        self.matchers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.matchers.is_empty()
+    }
+
+    fn insert(&mut self, name: String, state: QueryState) -> MatcherId {
+        self.matchers.push(Matcher { name, state });
+        MatcherId(self.matchers.len() - 1)
+    }
+}
+
+/// An entity started matching a registered matcher.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MatchBegan {
+    pub entity: Entity,
+    pub matcher: MatcherId,
+}
+
+impl MatchBegan {
+    pub fn new(entity: Entity, matcher: MatcherId) -> Self {
+        Self { entity, matcher }
+    }
+}
+
+impl Action for MatchBegan {
+    type Output = MatchBegan;
+    const PRIORITY: u32 = ArchetypeTransition::PRIORITY;
+
+    fn execute(&mut self, _: &mut World) -> Self::Output {
+        *self
+    }
+}
+
+/// An entity stopped matching a registered matcher, including by being
+/// deleted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MatchEnded {
+    pub entity: Entity,
+    pub matcher: MatcherId,
+}
+
+impl MatchEnded {
+    pub fn new(entity: Entity, matcher: MatcherId) -> Self {
+        Self { entity, matcher }
+    }
+}
+
+impl Action for MatchEnded {
+    type Output = MatchEnded;
+    const PRIORITY: u32 = ArchetypeTransition::PRIORITY;
+
+    fn execute(&mut self, _: &mut World) -> Self::Output {
+        *self
+    }
+}
+
+impl World {
+    /// Registers the component signature of `Q` filtered by `F` under
+    /// `name`. From then on every flush wave emits `MatchBegan` and
+    /// `MatchEnded` outputs for entities entering or leaving it. Registering
+    /// a name again returns the first id.
+    pub fn register_matcher<Q: BaseQuery, F: FilterQuery>(
+        &mut self,
+        name: impl Into<String>,
+    ) -> MatcherId {
+        let name = name.into();
+        if let Some(id) = self.matchers.get(&name) {
+            return id;
+        }
+
+        Q::register(self);
+        F::register(self);
+
+        let mut state = QueryState::new();
+        Q::init(self, &mut state);
+        F::init(self, &mut state);
+
+        self.transitions.set_matching(true);
+        self.matchers.insert(name, state)
+    }
+
+    pub fn matchers(&self) -> &Matchers {
+        &self.matchers
+    }
+
+    /// Registers a matcher for `Q` and `F` and observes the entities that
+    /// start matching it.
+    pub fn on_match<Q: BaseQuery, F: FilterQuery, M>(
+        &mut self,
+        system: impl IntoObserver<MatchBegan, M>,
+    ) -> MatcherId {
+        let id = self.register_matcher::<Q, F>(std::any::type_name::<(Q, F)>());
+        let observer = system
+            .into_observer()
+            .filter(move |began: &MatchBegan| began.matcher == id);
+        self.add_observers(Observers::new().add_system(observer));
+        id
+    }
+
+    /// Registers a matcher for `Q` and `F` and observes the entities that
+    /// stop matching it.
+    pub fn on_unmatch<Q: BaseQuery, F: FilterQuery, M>(
+        &mut self,
+        system: impl IntoObserver<MatchEnded, M>,
+    ) -> MatcherId {
+        let id = self.register_matcher::<Q, F>(std::any::type_name::<(Q, F)>());
+        let observer = system
+            .into_observer()
+            .filter(move |ended: &MatchEnded| ended.matcher == id);
+        self.add_observers(Observers::new().add_system(observer));
+        id
+    }
+
+    /// Compares the first and last archetype of every entity moved since
+    /// the last wave against each matcher, adding an output for every
+    /// matcher the entity entered or left.
+    pub(crate) fn emit_matches(&mut self, outputs: &mut ActionOutputs) {
+        let moves = self.transitions.take_moves();
+        if moves.is_empty() || self.matchers.is_empty() {
+            return;
+        }
+
+        let mut changes = Vec::<(Entity, ArchetypeId, ArchetypeId)>::new();
+        let mut index = HashMap::<Entity, usize>::new();
+        for transition in moves {
+            match index.get(&transition.entity) {
+                Some(change) => changes[*change].2 = transition.to,
+                None => {
+                    index.insert(transition.entity, changes.len());
+                    changes.push((transition.entity, transition.from, transition.to));
+                }
+            }
+        }
+
+        for (entity, from, to) in changes {
+            let from = self.archetypes.archetype(&from);
+            let to = self.archetypes.archetype(&to);
+
+            for (id, matcher) in self.matchers.matchers.iter().enumerate() {
+                let was = from.is_some_and(|archetype| matcher.state.matches(archetype));
+                let is = to.is_some_and(|archetype| matcher.state.matches(archetype));
+
+                match (was, is) {
+                    (false, true) => {
+                        outputs.add::<MatchBegan>(MatchBegan::new(entity, MatcherId(id)))
+                    }
+                    (true, false) => {
+                        outputs.add::<MatchEnded>(MatchEnded::new(entity, MatcherId(id)))
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use super::World;
+use crate::{
+    archetype::ArchetypeId,
+    core::ComponentId,
+    storage::table::TableId,
+    system::observer::{ActionOutputs, Actions},
+};
+use std::fmt::Display;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnMemory {
+    pub component: ComponentId,
+    pub name: &'static str,
+    /// Bytes per value, padded to its alignment.
+    pub element_size: usize,
+    pub len: usize,
+    pub capacity: usize,
+    /// Allocated bytes, `capacity * element_size`.
+    pub bytes: usize,
+    /// Values each chunk has room for.
+    pub chunk_len: usize,
+    /// Values held by each allocated chunk.
+    pub chunks: Vec<usize>,
+}
+
+impl ColumnMemory {
+    /// Bytes used by live values, `len * element_size`.
+    pub fn live_bytes(&self) -> usize {
+        self.len * self.element_size
+    }
+
+    /// Number of chunks holding `chunk_len` values.
+    pub fn full_chunks(&self) -> usize {
+        self.chunks
+            .iter()
+            .filter(|len| **len == self.chunk_len)
+            .count()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableMemory {
+    pub table: TableId,
+    pub rows: usize,
+    pub columns: Vec<ColumnMemory>,
+    /// Row list and row index.
+    pub index_bytes: usize,
+}
+
+impl TableMemory {
+    pub fn bytes(&self) -> usize {
+        self.column_bytes() + self.index_bytes
+    }
+
+    pub fn column_bytes(&self) -> usize {
+        self.columns.iter().map(|column| column.bytes).sum()
+    }
+
+    pub fn live_bytes(&self) -> usize {
+        self.columns.iter().map(|column| column.live_bytes()).sum()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArchetypeMemory {
+    pub archetype: ArchetypeId,
+    pub entities: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceMemory {
+    pub name: &'static str,
+    pub internal: bool,
+    /// Size of the value itself; heap memory it owns isn't visible here.
+    pub bytes: usize,
+}
+
+/// Byte accounting built by `World::memory_report`. Sizes come from the
+/// storage layouts and capacities, so memory owned by component or resource
+/// values (a `Vec` inside a component) is not included.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryReport {
+    pub archetypes: Vec<ArchetypeMemory>,
+    pub tables: Vec<TableMemory>,
+    pub resources: Vec<ResourceMemory>,
+    /// Entity allocator, hierarchy and root set.
+    pub entity_bytes: usize,
+    /// Archetype lookup maps.
+    pub archetype_bytes: usize,
+    pub pending_actions: usize,
+    pub action_bytes: usize,
+    pub output_bytes: usize,
+    /// Values cleared from storage whose drops are still pending.
+    pub pending_drops: usize,
+    pub pending_drop_bytes: usize,
+}
+
+impl MemoryReport {
+    pub fn table_bytes(&self) -> usize {
+        self.tables.iter().map(|table| table.bytes()).sum()
+    }
+
+    pub fn resource_bytes(&self) -> usize {
+        self.resources.iter().map(|resource| resource.bytes).sum()
+    }
+
+    pub fn total_bytes(&self) -> usize {
+        self.table_bytes()
+            + self.resource_bytes()
+            + self.entity_bytes
+            + self.archetype_bytes
+            + self.action_bytes
+            + self.output_bytes
+            + self.pending_drop_bytes
+    }
+
+    pub fn table(&self, table: TableId) -> Option<&TableMemory> {
+        self.tables.iter().find(|memory| memory.table == table)
+    }
+}
+
+impl Display for MemoryReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "total {} bytes", self.total_bytes())?;
+        writeln!(
+            f,
+            "tables: {} bytes in {} tables",
+            self.table_bytes(),
+            self.tables.len()
+        )?;
+
+        let mut tables = self.tables.iter().collect::<Vec<_>>();
+        tables.sort_by_key(|table| std::cmp::Reverse(table.bytes()));
+        for table in tables {
+            writeln!(
+                f,
+                "  table {} ({} rows): {} bytes, {} for the row index",
+                table.table.id(),
+                table.rows,
+                table.bytes(),
+                table.index_bytes
+            )?;
+
+            let mut columns = table.columns.iter().collect::<Vec<_>>();
+            columns.sort_by_key(|column| std::cmp::Reverse(column.bytes));
+            for column in columns {
+                writeln!(
+                    f,
+                    "    {}: {} bytes ({} of {} x {} bytes, {} of {} chunks of {} full)",
+                    column.name,
+                    column.bytes,
+                    column.len,
+                    column.capacity,
+                    column.element_size,
+                    column.full_chunks(),
+                    column.chunks.len(),
+                    column.chunk_len
+                )?;
+            }
+        }
+
+        writeln!(f, "resources: {} bytes", self.resource_bytes())?;
+        let mut resources = self.resources.iter().collect::<Vec<_>>();
+        resources.sort_by_key(|resource| (std::cmp::Reverse(resource.bytes), resource.name));
+        for resource in resources {
+            let internal = if resource.internal { " (internal)" } else { "" };
+            writeln!(
+                f,
+                "  {}{}: {} bytes",
+                resource.name, internal, resource.bytes
+            )?;
+        }
+
+        let entities = self
+            .archetypes
+            .iter()
+            .map(|archetype| archetype.entities)
+            .sum::<usize>();
+        writeln!(f, "entities: {} bytes", self.entity_bytes)?;
+        writeln!(
+            f,
+            "archetypes: {} bytes for {} archetypes, {} entities",
+            self.archetype_bytes,
+            self.archetypes.len(),
+            entities
+        )?;
+        write!(
+            f,
+            "pending: {} actions in {} bytes, {} output bytes, {} drops in {} bytes",
+            self.pending_actions,
+            self.action_bytes,
+            self.output_bytes,
+            self.pending_drops,
+            self.pending_drop_bytes
+        )
+    }
+}
+
+impl World {
+    /// Where the world's memory is going, broken down by table column,
+    /// resource and bookkeeping structure.
+    pub fn memory_report(&self) -> MemoryReport {
+        let archetypes = self
+            .archetypes
+            .iter()
+            .map(|archetype| ArchetypeMemory {
+                archetype: *archetype.id(),
+                entities: archetype.entities().len(),
+            })
+            .collect();
+
+        let tables = self
+            .tables
+            .iter()
+            .map(|table| TableMemory {
+                table: table.id(),
+                rows: table.len(),
+                columns: table
+                    .column_indices()
+                    .filter_map(|index| {
+                        let column = table.column(index)?;
+                        let component = ComponentId::from(index);
+                        Some(ColumnMemory {
+                            component,
+                            name: self.components.meta(component).name(),
+                            element_size: column.element_size(),
+                            len: column.len(),
+                            capacity: column.capacity(),
+                            bytes: column.allocated_bytes(),
+                            chunk_len: column.chunk_len(),
+                            chunks: column.chunk_lens().collect(),
+                        })
+                    })
+                    .collect(),
+                index_bytes: table.index_bytes(),
+            })
+            .collect();
+
+        let resources = self
+            .resources
+            .iter()
+            .map(|(_, data)| ResourceMemory {
+                name: data.name(),
+                internal: data.is_internal(),
+                bytes: data.allocated_bytes(),
+            })
+            .collect();
+
+        let actions = self.resources.get::<Actions>();
+        MemoryReport {
+            archetypes,
+            tables,
+            resources,
+            entity_bytes: self.entities.allocated_bytes(),
+            archetype_bytes: self.archetypes.allocated_bytes(),
+            pending_actions: actions.len(),
+            action_bytes: actions.allocated_bytes(),
+            output_bytes: self.resources.get::<ActionOutputs>().allocated_bytes(),
+            pending_drops: self.pending_drops.len(),
+            pending_drop_bytes: self.pending_drops.allocated_bytes(),
+        }
+    }
+
+    pub fn column_chunk_bytes(&self) -> usize {
+        self.tables.chunk_bytes()
+    }
+
+    /// Sets the size of the chunks table columns are split into, moving the
+    /// values of existing tables into chunks of the new size. Values only
+    /// keep their address while the chunk size stays the same.
+    pub fn set_column_chunk_bytes(&mut self, chunk_bytes: usize) {
+        self.borrows.assert_unborrowed();
+        self.tables.set_chunk_bytes(chunk_bytes);
+    }
+}
@@ -1,6 +1,9 @@
 use crate::{
-    core::{Component, Entity},
-    system::observer::{action::ActionOutputs, builtin::RemoveComponent},
+    core::{Component, ComponentId, Entity},
+    system::observer::{
+        action::ActionOutputs,
+        builtin::{AddComponent, ComponentRemoved, Removal, RemovalCause, RemoveComponent},
+    },
 };
 use std::any::TypeId;
 
@@ -16,7 +19,13 @@ pub enum AccessType {
     None,
     World,
     Component(TypeId),
+    /// A component registered with `register_dynamic`, which has no
+    /// `TypeId`.
+    DynamicComponent(ComponentId),
     Resource(TypeId),
+    /// A synchronization domain owned by a system argument outside this crate.
+    /// Only compared by equality, so two writers of the same id never overlap.
+    Custom(TypeId),
 }
 
 impl AccessType {
@@ -24,10 +33,18 @@ impl AccessType {
         Self::Component(TypeId::of::<C>())
     }
 
+    pub fn dynamic_component(id: ComponentId) -> Self {
+        Self::DynamicComponent(id)
+    }
+
     pub fn resource<R: Resource>() -> Self {
         Self::Resource(TypeId::of::<R>())
     }
 
+    pub fn custom<T: 'static>() -> Self {
+        Self::Custom(TypeId::of::<T>())
+    }
+
     pub fn world() -> Self {
         Self::World
     }
@@ -77,20 +94,32 @@ impl AccessMeta {
     }
 }
 
+type OutputHook = Box<dyn Fn(&Entity, &mut ActionOutputs)>;
+type RemovalHook = Box<dyn Fn(&Entity, RemovalCause, &mut ActionOutputs)>;
+
 pub struct ComponentActionMeta {
-    on_remove: Box<dyn Fn(&Entity, &mut ActionOutputs)>,
+    on_add: OutputHook,
+    on_remove: RemovalHook,
 }
 
 impl ComponentActionMeta {
     pub fn new<C: Component>() -> Self {
         Self {
-            on_remove: Box::new(|entity, outputs: &mut ActionOutputs| {
+            on_add: Box::new(|entity, outputs: &mut ActionOutputs| {
+                outputs.add::<AddComponent<C>>(*entity);
+            }),
+            on_remove: Box::new(|entity, cause, outputs: &mut ActionOutputs| {
                 outputs.add::<RemoveComponent<C>>(*entity);
+                outputs.add::<ComponentRemoved<C>>(Removal::new(*entity, cause));
             }),
         }
     }
 
-    pub fn on_remove(&self) -> &dyn Fn(&Entity, &mut ActionOutputs) {
+    pub fn on_add(&self) -> &dyn Fn(&Entity, &mut ActionOutputs) {
+        &self.on_add
+    }
+
+    pub fn on_remove(&self) -> &dyn Fn(&Entity, RemovalCause, &mut ActionOutputs) {
         &self.on_remove
     }
 }
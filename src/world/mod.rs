@@ -1,26 +1,99 @@
 use self::{
-    lifecycle::Lifecycle,
+    borrow::{BorrowGuard, ComponentBorrows, ComponentRef, ComponentRefMut},
+    defrag::DefragmentProgress,
+    error::{ResourceError, WorldError},
+    external::ExternalCommandQueue,
+    fork::CaptureFn,
+    index::ComponentIndexes,
+    lifecycle::{EntityEdit, Lifecycle},
+    matcher::Matchers,
     meta::ComponentActionMeta,
-    resource::{Resource, Resources},
+    prefab::Prefabs,
+    priority::PriorityDiagnostics,
+    provenance::Provenances,
+    query::{QueryBorrows, QueryCache, QueryScratchStats},
+    recording::ActionRecorder,
+    registered::RefreshPoint,
+    registration::ComponentBuilder,
+    removed::RemovedComponents,
+    resource::{Resource, ResourceRef, ResourceRefMut, Resources},
+    scope::Scopes,
+    trace::{Trace, TraceEntry, TraceLevel},
+    tracking::{TrackedEntities, TrackingEvent, TrackingLog},
+    transition::Transitions,
 };
 use crate::{
-    archetype::Archetypes,
-    core::{Component, ComponentId, Components, Entities, Entity},
-    schedule::{GlobalSchedules, SceneSchedules, Schedule, ScheduleLabel, SchedulePhase},
+    archetype::{ArchetypeId, Archetypes},
+    core::{
+        AllocMode, Component, ComponentHandle, ComponentId, ComponentMask, ComponentMeta,
+        Components, Entities, Entity, OrphanPolicy,
+    },
+    schedule::{
+        label::DynLabel, runner::RunMode, DuplicateSystemPolicy, GlobalSchedules, SceneSchedules,
+        Schedule, ScheduleBuildError, ScheduleError, SchedulePhase, Schedules,
+    },
+    storage::blob::Blob,
     storage::table::Tables,
     system::{
+        commands::CommandBuffers,
         observer::{
             action::{Action, ActionOutputs, Actions},
-            Observables, Observers,
+            builtin::{Bundle, ChangeCapture, CreateEntity, HierarchyChange, RemovalCause},
+            DeliveryKey, DeliveryOrder, Observables, ObserverOrderBuilder, Observers,
+            ScheduledDelivery,
         },
-        IntoSystem,
+        IntoSystem, System,
     },
+    tasks::CancellationToken,
+};
+use std::{
+    any::TypeId,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
 };
 
+pub mod audit;
+pub mod borrow;
+pub mod bulk;
+pub mod compact;
+pub mod consistency;
+pub mod defrag;
+pub mod dump;
+pub mod dynamic;
+pub mod error;
+pub mod event;
+pub mod explain;
+pub mod external;
+pub mod extract;
+pub mod fork;
+pub mod index;
 pub mod lifecycle;
+pub mod matcher;
+pub mod memory;
 pub mod meta;
+pub mod name;
+pub mod phase;
+pub mod prefab;
+pub mod priority;
+pub mod provenance;
 pub mod query;
+pub mod reclaim;
+pub mod recording;
+pub mod reflect;
+pub mod registered;
+pub mod registration;
+pub mod removed;
+pub mod repair;
 pub mod resource;
+pub mod runner;
+pub mod scope;
+pub mod shared;
+pub mod shutdown;
+pub mod snapshot;
+pub mod spawn;
+pub mod trace;
+pub mod tracking;
+pub mod transition;
 
 pub struct World {
     resources: Resources,
@@ -28,16 +101,79 @@ pub struct World {
     entities: Entities,
     components: Components,
     tables: Tables<Entity>,
+    tracked: TrackedEntities,
+    provenance: Provenances,
+    recorder: ActionRecorder,
+    indexes: ComponentIndexes,
+    transitions: Transitions,
+    matchers: Matchers,
+    registered: registered::RegisteredQueries,
+    scopes: Scopes,
+    external: Option<ExternalCommandQueue>,
+    commands: CommandBuffers,
+    priority: PriorityDiagnostics,
+    forked_resources: Vec<(TypeId, CaptureFn)>,
+    events: Vec<fn(&mut World)>,
+    inputs: Vec<fn(&mut World)>,
+    phases: phase::PhaseOrder,
+    trace: Trace,
+    queries: QueryCache,
+    borrows: QueryBorrows,
+    component_borrows: ComponentBorrows,
+    frame: u64,
+    change_tick: AtomicU64,
+    frame_token: CancellationToken,
+    finalized: bool,
+    flushing: bool,
+    shutting_down: bool,
+    duplicate_policy: DuplicateSystemPolicy,
+    paranoid: bool,
+    name_policy: Option<name::NamePolicy>,
+    pending_drops: reclaim::PendingDrops,
+    reclaim_budget: Option<Duration>,
+    audit_window: u64,
+}
+
+/// Summary of a single `World::run`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameReport {
+    frame: u64,
+    phase: &'static str,
+    waves: usize,
+    cancelled: bool,
+}
+
+impl FrameReport {
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn phase(&self) -> &'static str {
+        self.phase
+    }
+
+    /// Flush waves applied after the systems ran.
+    pub fn waves(&self) -> usize {
+        self.waves
+    }
+
+    /// True if `cancel_frame` stopped the run early.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
 }
 
 impl World {
     pub fn new() -> Self {
         let mut resources = Resources::new();
-        resources.insert(GlobalSchedules::new());
-        resources.insert(SceneSchedules::new());
-        resources.insert(Observables::new());
-        resources.insert(ActionOutputs::new());
-        resources.insert(Actions::new());
+        resources.insert_internal(GlobalSchedules::new());
+        resources.insert_internal(SceneSchedules::new());
+        resources.insert_internal(Observables::new());
+        resources.insert_internal(ActionOutputs::new());
+        resources.insert_internal(Actions::new());
+        resources.insert_internal(RemovedComponents::new());
+        resources.insert_internal(Prefabs::new());
+        resources.insert_internal(DefragmentProgress::new());
 
         Self {
             resources,
@@ -45,43 +181,341 @@ impl World {
             entities: Entities::new(),
             components: Components::new(),
             tables: Tables::new(),
+            tracked: TrackedEntities::new(),
+            provenance: Provenances::new(),
+            recorder: ActionRecorder::new(),
+            indexes: ComponentIndexes::new(),
+            transitions: Transitions::new(),
+            matchers: Matchers::new(),
+            registered: registered::RegisteredQueries::new(),
+            scopes: Scopes::new(),
+            external: None,
+            commands: CommandBuffers::new(),
+            priority: PriorityDiagnostics::new(),
+            forked_resources: Vec::new(),
+            events: Vec::new(),
+            inputs: Vec::new(),
+            phases: phase::PhaseOrder::default(),
+            trace: Trace::new(),
+            queries: QueryCache::new(),
+            borrows: QueryBorrows::new(),
+            component_borrows: ComponentBorrows::new(),
+            frame: 0,
+            // Systems that never ran have a last run of 0, so anything
+            // written before the first run counts as changed.
+            change_tick: AtomicU64::new(1),
+            frame_token: CancellationToken::new(),
+            finalized: false,
+            flushing: false,
+            shutting_down: false,
+            duplicate_policy: DuplicateSystemPolicy::Allow,
+            paranoid: false,
+            name_policy: None,
+            pending_drops: reclaim::PendingDrops::default(),
+            reclaim_budget: Some(Duration::from_millis(1)),
+            audit_window: audit::DEFAULT_AUDIT_WINDOW,
         }
     }
 
     pub fn register<C: Component>(&mut self) {
+        if self.components.get_id::<C>().is_some() {
+            return;
+        }
+
         let id = self.components.register::<C>();
         self.components
             .extend_meta(id, ComponentActionMeta::new::<C>());
+        self.recorder.register_component::<C>();
+    }
+
+    /// Starts a registration of `C` with extra options, applied by `finish`.
+    pub fn register_component<C: Component>(&mut self) -> ComponentBuilder<'_, C> {
+        ComponentBuilder::new(self)
+    }
+
+    pub fn component_meta<C: Component>(&self) -> Option<&ComponentMeta> {
+        let id = self.components.get_id::<C>()?;
+        Some(self.components.meta(id))
+    }
+
+    /// Keeps a clone of every `C` removed from an entity, readable through
+    /// the `Removed<C>` system argument until the end of the current flush.
+    pub fn buffer_removals<C: Component + Clone>(&mut self) {
+        if self.components.get_id::<C>().is_none() {
+            self.register::<C>();
+        }
+
+        let id = self.components.id::<C>();
+        self.resources
+            .get_mut::<RemovedComponents>()
+            .enable::<C>(id);
+    }
+
+    /// Keeps a clone of the old value in the `ComponentChanged<C>` output of
+    /// every `SetComponent<C>`.
+    pub fn capture_changes<C: Component + Clone>(&mut self) {
+        self.register::<C>();
+        let id = self.components.id::<C>();
+        self.components
+            .extend_meta(id, ChangeCapture::<C>(C::clone));
     }
 
     pub fn add_resource<T: Resource>(&mut self, resource: T) {
         self.resources.insert(resource);
     }
 
-    pub fn add_system<M>(
+    /// Takes `R` out of the world. Its teardown hook doesn't run.
+    pub fn remove_resource<R: Resource>(&mut self) -> Option<R> {
+        self.resources.remove::<R>()
+    }
+
+    pub fn contains_resource<R: Resource>(&self) -> bool {
+        self.resources.contains::<R>()
+    }
+
+    pub fn resources(&self) -> &Resources {
+        &self.resources
+    }
+
+    /// Type names of the world's resources in insertion order.
+    pub fn resource_names(&self, include_internal: bool) -> Vec<&'static str> {
+        let mut resources = self
+            .resources
+            .iter()
+            .map(|(_, data)| data)
+            .filter(|data| include_internal || !data.is_internal())
+            .collect::<Vec<_>>();
+        resources.sort_by_key(|data| data.order());
+
+        resources.into_iter().map(|data| data.name()).collect()
+    }
+
+    pub fn add_system<M, P: SchedulePhase, L: DynLabel>(
         &mut self,
-        phase: impl SchedulePhase,
-        label: impl ScheduleLabel,
+        phase: P,
+        label: L,
         system: impl IntoSystem<M>,
     ) {
-        let schedules = self.resources.get_mut::<GlobalSchedules>();
+        if let Err(error) = self.try_add_system(phase, label, system) {
+            self.trace(TraceLevel::Error, "add_system", error.to_string());
+        }
+    }
+
+    /// Like `add_system`, but returns the error instead of tracing it when
+    /// the duplicate system policy rejects the system.
+    pub fn try_add_system<M, P: SchedulePhase, L: DynLabel>(
+        &mut self,
+        phase: P,
+        label: L,
+        system: impl IntoSystem<M>,
+    ) -> Result<(), ScheduleError> {
+        let system = system.into_system();
+        let schedules = self.resources.get::<GlobalSchedules>();
+        self.check_duplicate::<P>(&schedules, &label, &system)?;
+        drop(schedules);
+
+        let mut schedules = self.resources.get_mut::<GlobalSchedules>();
         schedules.add_system(phase, label, system);
+        self.finalized = false;
+
+        Ok(())
+    }
+
+    /// Use `AllocMode::Deterministic` to get identical entity ids across
+    /// replays of the same inputs.
+    pub fn set_alloc_mode(&mut self, mode: AllocMode) {
+        self.entities.set_alloc_mode(mode);
+    }
+
+    /// Run mode inherited by schedules created from now on, global and
+    /// scene. `Sequential` also runs queued action groups one after another,
+    /// so a world built this way spawns no threads of its own.
+    pub fn set_default_run_mode(&mut self, mode: RunMode) {
+        self.resources
+            .get_mut::<GlobalSchedules>()
+            .set_default_run_mode(mode);
+        self.resources
+            .get_mut::<SceneSchedules>()
+            .set_default_run_mode(mode);
+    }
+
+    pub fn default_run_mode(&self) -> RunMode {
+        self.resources.get::<GlobalSchedules>().default_run_mode()
+    }
+
+    /// Sets the run mode of the global schedule of `label` in `phase`.
+    pub fn set_run_mode(&mut self, phase: impl SchedulePhase, label: impl DynLabel, mode: RunMode) {
+        let mut schedules = self.resources.get_mut::<GlobalSchedules>();
+        schedules.set_run_mode(phase, label, mode);
+        self.finalized = false;
+    }
+
+    pub fn set_duplicate_system_policy(&mut self, policy: DuplicateSystemPolicy) {
+        self.duplicate_policy = policy;
+    }
+
+    /// Paranoid mode traces what is otherwise tolerated silently, like
+    /// queries skipping tables removed while they were alive.
+    pub fn set_paranoid(&mut self, paranoid: bool) {
+        self.paranoid = paranoid;
+    }
+
+    pub fn is_paranoid(&self) -> bool {
+        self.paranoid
+    }
+
+    fn check_duplicate<P: SchedulePhase>(
+        &self,
+        schedules: &Schedules,
+        label: &impl DynLabel,
+        system: &System,
+    ) -> Result<(), ScheduleError> {
+        let duplicate = match system.type_id() {
+            Some(type_id) => schedules.contains_system::<P>(label.key(), type_id),
+            None => false,
+        };
+
+        if !duplicate {
+            return Ok(());
+        }
+
+        match self.duplicate_policy {
+            DuplicateSystemPolicy::Allow => Ok(()),
+            DuplicateSystemPolicy::Warn => {
+                self.trace(
+                    TraceLevel::Warn,
+                    "add_system",
+                    format!(
+                        "{} added to {}/{} more than once",
+                        system.name(),
+                        P::PHASE,
+                        label.name()
+                    ),
+                );
+                Ok(())
+            }
+            DuplicateSystemPolicy::Reject => Err(ScheduleError::DuplicateSystem {
+                phase: P::PHASE,
+                label: label.name(),
+                system: system.name(),
+            }),
+        }
     }
 
     pub fn add_schedule(
         &mut self,
         phase: impl SchedulePhase,
-        label: impl ScheduleLabel,
+        label: impl DynLabel,
+        schedule: Schedule,
+    ) {
+        let mut schedules = self.resources.get_mut::<GlobalSchedules>();
+        schedules.add_schedule(phase, label, schedule);
+        self.finalized = false;
+    }
+
+    pub fn add_scene_system<M, P: SchedulePhase, L: DynLabel>(
+        &mut self,
+        phase: P,
+        label: L,
+        system: impl IntoSystem<M>,
+    ) {
+        let system = system.into_system();
+        let schedules = self.resources.get::<SceneSchedules>();
+        let checked = self.check_duplicate::<P>(&schedules, &label, &system);
+        drop(schedules);
+        if let Err(error) = checked {
+            self.trace(TraceLevel::Error, "add_scene_system", error.to_string());
+            return;
+        }
+
+        let mut schedules = self.resources.get_mut::<SceneSchedules>();
+        schedules.add_system(phase, label, system);
+        self.finalized = false;
+    }
+
+    pub fn add_scene_schedule(
+        &mut self,
+        phase: impl SchedulePhase,
+        label: impl DynLabel,
         schedule: Schedule,
     ) {
-        let schedules = self.resources.get_mut::<GlobalSchedules>();
+        let mut schedules = self.resources.get_mut::<SceneSchedules>();
         schedules.add_schedule(phase, label, schedule);
+        self.finalized = false;
+    }
+
+    /// Removes a scene schedule so its systems stop running. The schedule is
+    /// returned so it can be re-added with `add_scene_schedule`.
+    pub fn remove_scene_schedule<P: SchedulePhase, L: DynLabel>(
+        &mut self,
+        phase: P,
+        label: L,
+    ) -> Option<Schedule> {
+        let name = label.name();
+        let mut schedules = self.resources.get_mut::<SceneSchedules>();
+        let removed = schedules.remove_label(phase, label);
+
+        if removed.is_none() {
+            self.trace(
+                TraceLevel::Warn,
+                "remove_scene_schedule",
+                format!("no scene schedule for {} in {}", name, P::PHASE),
+            );
+        }
+
+        removed
     }
 
     pub fn add_observers<A: Action>(&mut self, observers: Observers<A>) {
-        self.resources
+        let scheduled = self
+            .resources
             .get_mut::<Observables>()
             .add_observers(observers);
+        if let Some(scheduled) = scheduled {
+            self.add_delivery::<A>(scheduled);
+        }
+        self.finalized = false;
+    }
+
+    /// Adds the system delivering the outputs held for observers of `A`
+    /// scheduled with `Observers::deliver_in`.
+    fn add_delivery<A: Action>(&mut self, scheduled: ScheduledDelivery) {
+        let key = scheduled.key();
+        let mut system = IntoSystem::into_system(move |world: &World| {
+            let mut observers = std::mem::take(&mut *world.resource_mut::<Observables>());
+            observers.execute_scheduled::<A>(key, world);
+            world.resource_mut::<Observables>().swap(observers);
+        });
+        system.set_first(scheduled.order() == DeliveryOrder::First);
+        scheduled.add_to(self, system);
+    }
+
+    /// Outputs of `A` held for observers delivered in `phase` and `label`.
+    pub fn pending_scheduled<A: Action, P: SchedulePhase>(
+        &self,
+        _: P,
+        label: impl DynLabel,
+    ) -> usize {
+        let key = DeliveryKey {
+            phase: TypeId::of::<P>(),
+            label: label.key(),
+        };
+        self.resources
+            .get::<Observables>()
+            .pending_scheduled::<A>(key)
+    }
+
+    /// Orders observer channels against each other, on top of action
+    /// priority:
+    ///
+    /// ```ignore
+    /// world
+    ///     .configure_observer_order()
+    ///     .channel::<RemoveComponent<Collider>>()
+    ///     .before::<DeleteEntity>()?;
+    /// ```
+    pub fn configure_observer_order(&mut self) -> ObserverOrderBuilder<'_> {
+        ObserverOrderBuilder::new(self.resources.exclusive::<Observables>())
     }
 
     pub fn component_id<C: Component>(&self) -> ComponentId {
@@ -104,60 +538,523 @@ impl World {
         &self.tables
     }
 
-    pub fn resource<R: Resource>(&self) -> &R {
+    pub fn query_cache(&self) -> &QueryCache {
+        &self.queries
+    }
+
+    pub fn query_borrows(&self) -> &QueryBorrows {
+        &self.borrows
+    }
+
+    pub fn query_scratch_stats(&self) -> QueryScratchStats {
+        self.queries.stats()
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Tick stamped on component values as they are inserted or mutably
+    /// accessed. Advanced by every `run`, flush and system run, so
+    /// `Changed<C>` can tell writes apart from a system's previous run.
+    pub fn change_tick(&self) -> u64 {
+        self.change_tick.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn increment_change_tick(&self) -> u64 {
+        self.change_tick.fetch_add(1, Ordering::AcqRel) + 1
+    }
+
+    /// Borrows `R` until the returned guard drops. Panics if `R` doesn't
+    /// exist or is borrowed mutably.
+    pub fn resource<R: Resource>(&self) -> ResourceRef<'_, R> {
         self.resources.get::<R>()
     }
 
-    pub fn resource_mut<R: Resource>(&self) -> &mut R {
+    /// Mutable access through a shared world. Meant for `SystemArg::get`
+    /// implementations that declare `Access::Write` on the resource; the
+    /// schedule relies on that declaration to keep other users apart, and
+    /// panics if `R` is borrowed anyway.
+    pub fn resource_mut<R: Resource>(&self) -> ResourceRefMut<'_, R> {
         self.resources.get_mut::<R>()
     }
 
+    /// `None` instead of a panic if `R` was never added.
+    pub fn try_resource<R: Resource>(&self) -> Option<ResourceRef<'_, R>> {
+        self.resources.try_get::<R>()
+    }
+
+    /// `try_resource` with the contract of [`World::resource_mut`].
+    pub fn try_resource_mut<R: Resource>(&self) -> Option<ResourceRefMut<'_, R>> {
+        self.resources.try_get_mut::<R>()
+    }
+
+    /// `resource` that reports a missing or conflicting borrow instead of
+    /// panicking.
+    pub fn try_borrow_resource<R: Resource>(&self) -> Result<ResourceRef<'_, R>, ResourceError> {
+        self.resources.try_borrow::<R>()
+    }
+
+    pub fn try_borrow_resource_mut<R: Resource>(
+        &self,
+    ) -> Result<ResourceRefMut<'_, R>, ResourceError> {
+        self.resources.try_borrow_mut::<R>()
+    }
+
+    /// `R` for a system argument, borrowed until the running system returns.
+    pub(crate) fn hold_resource<R: Resource>(&self) -> Option<&R> {
+        self.resources.hold::<R>()
+    }
+
+    pub(crate) fn hold_resource_mut<R: Resource>(&self) -> Option<&mut R> {
+        self.resources.hold_mut::<R>()
+    }
+
     pub fn create(&mut self) -> Entity {
         let entity = self.entities.create();
+        self.borrows.assert_unborrowed();
         Lifecycle::create_entity(entity, &mut self.archetypes, &mut self.tables);
+        let source = self.transitions.is_recording().then_some(ArchetypeId::NONE);
+        self.record_transition(entity, source);
+        self.priority.record_created(&[entity]);
+        if self.provenance.is_enabled() {
+            self.provenance.record(&[entity], self.frame);
+        }
+        if self.recorder.is_capturing() {
+            self.recorder.created(&[entity]);
+        }
         entity
     }
 
+    /// Creates `count` empty entities in one pass: ids are reserved up front
+    /// and every entity joins the empty archetype and its table together.
+    pub fn create_batch(&mut self, count: usize) -> Vec<Entity> {
+        let entities = self.entities.create_batch(count);
+        self.borrows.assert_unborrowed();
+        Lifecycle::create_entities(&entities, &mut self.archetypes, &mut self.tables);
+        if self.transitions.is_recording() {
+            for entity in &entities {
+                self.record_transition(*entity, Some(ArchetypeId::NONE));
+            }
+        }
+
+        self.priority.record_created(&entities);
+        if self.provenance.is_enabled() {
+            self.provenance.record(&entities, self.frame);
+        }
+        if self.recorder.is_capturing() {
+            self.recorder.created(&entities);
+        }
+        entities
+    }
+
+    /// Creates an entity and places it, with all of `components`, straight
+    /// into its final archetype. Uses `entity`'s exact id and generation if
+    /// given, otherwise allocates a fresh one. Each blob must hold a single
+    /// value of its registered component. Outputs for `CreateEntity` and each
+    /// `AddComponent` are queued so observers run on the next flush.
+    ///
+    /// This is a low-level building block for spawners that restore or
+    /// replicate entities; gameplay code should use actions.
+    pub fn insert_entity_raw(
+        &mut self,
+        entity: Option<Entity>,
+        components: Vec<(ComponentId, Blob)>,
+    ) -> Result<Entity, WorldError> {
+        let mut values = Vec::<(ComponentId, Blob)>::with_capacity(components.len());
+        for (id, blob) in components {
+            if id.id() >= self.components.len() {
+                return Err(WorldError::UnknownComponent(id));
+            }
+
+            let meta = self.components.meta(id);
+            if *blob.layout() != meta.layout() || blob.len() != 1 {
+                return Err(WorldError::LayoutMismatch {
+                    component: id,
+                    name: meta.name(),
+                });
+            }
+
+            values.retain(|(other, _)| *other != id);
+            values.push((id, blob));
+        }
+
+        self.borrows.assert_unborrowed();
+        let entity = match entity {
+            Some(entity) if self.entities.contains_id(entity.id()) => {
+                return Err(WorldError::EntityAlive(entity));
+            }
+            Some(entity) => {
+                if !self.entities.create_at(entity) {
+                    return Err(WorldError::StaleEntity(entity));
+                }
+                entity
+            }
+            None => self.entities.create(),
+        };
+
+        let ids = values.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+        let archetype = Lifecycle::insert_entity(
+            entity,
+            values,
+            self.change_tick(),
+            &mut self.archetypes,
+            &mut self.tables,
+        );
+        self.transitions
+            .record(entity, ArchetypeId::NONE, archetype);
+        self.reindex(entity, &ids);
+
+        self.priority.record_created(&[entity]);
+        if self.provenance.is_enabled() {
+            self.provenance.record(&[entity], self.frame);
+        }
+        if self.recorder.is_capturing() {
+            self.recorder.created(&[entity]);
+        }
+
+        let mut outputs = self.resources.get_mut::<ActionOutputs>();
+        outputs.add::<CreateEntity>(entity);
+        for id in ids {
+            if let Some(meta) = self.components.meta(id).extension::<ComponentActionMeta>() {
+                (meta.on_add())(&entity, &mut outputs);
+            }
+        }
+
+        Ok(entity)
+    }
+
     pub fn has<C: Component>(&self, entity: Entity) -> bool {
         let component_id = self.components.id::<C>();
         self.archetypes.has(entity, component_id)
     }
 
-    pub fn component<C: Component>(&self, entity: Entity) -> Option<&C> {
-        let component_id = self.components.id::<C>();
+    /// True if `entity` has every component of `mask`.
+    pub fn has_all(&self, entity: Entity, mask: &ComponentMask) -> bool {
+        self.archetypes
+            .mask(entity)
+            .is_some_and(|components| components.contains_all(mask))
+    }
+
+    /// True if `entity` has at least one component of `mask`.
+    pub fn has_any(&self, entity: Entity, mask: &ComponentMask) -> bool {
+        self.archetypes
+            .mask(entity)
+            .is_some_and(|components| components.intersects(mask))
+    }
+
+    /// The `C` of `entity`, borrowed until the returned guard is dropped.
+    #[cfg_attr(feature = "strict-borrows", track_caller)]
+    pub fn component<C: Component>(&self, entity: Entity) -> Option<ComponentRef<'_, C>> {
+        self.component_by_id(self.components.id::<C>(), entity)
+    }
+
+    /// Same contract as [`World::resource_mut`]: callers must declare
+    /// `Access::Write` on the component.
+    #[cfg_attr(feature = "strict-borrows", track_caller)]
+    pub fn component_mut<C: Component>(&self, entity: Entity) -> Option<ComponentRefMut<'_, C>> {
+        self.component_mut_by_id(self.components.id::<C>(), entity)
+    }
+
+    #[cfg_attr(feature = "strict-borrows", track_caller)]
+    fn component_by_id<C: Component>(
+        &self,
+        component: ComponentId,
+        entity: Entity,
+    ) -> Option<ComponentRef<'_, C>> {
         let archetype = self.archetypes.archetype_id(entity)?;
         let table = self.tables.get((*archetype).into())?;
+        let guard = self.borrow_component::<C>(entity, component, false);
+        let value = table.get::<C>(entity, component.into())?;
 
-        table.get::<C>(entity, component_id.into())
+        Some(ComponentRef::new(value, guard))
     }
 
-    pub fn component_mut<C: Component>(&self, entity: Entity) -> Option<&mut C> {
-        let component_id = self.components.id::<C>();
+    #[cfg_attr(feature = "strict-borrows", track_caller)]
+    fn component_mut_by_id<C: Component>(
+        &self,
+        component: ComponentId,
+        entity: Entity,
+    ) -> Option<ComponentRefMut<'_, C>> {
         let archetype = self.archetypes.archetype_id(entity)?;
         let table = self.tables.get((*archetype).into())?;
+        let guard = self.borrow_component::<C>(entity, component, true);
+        let value = table.get_mut::<C>(entity, component.into(), self.change_tick())?;
+
+        Some(ComponentRefMut::new(value, guard))
+    }
 
-        table.get_mut::<C>(entity, component_id.into())
+    /// Registers `C` if needed and returns a handle for it. Ids are never
+    /// reassigned, so the handle stays valid for the life of the world.
+    pub fn component_handle<C: Component>(&mut self) -> ComponentHandle<C> {
+        self.register::<C>();
+        self.components.handle::<C>().unwrap()
+    }
+
+    /// `component` without the `TypeId` lookup.
+    #[cfg_attr(feature = "strict-borrows", track_caller)]
+    pub fn get<C: Component>(
+        &self,
+        handle: ComponentHandle<C>,
+        entity: Entity,
+    ) -> Option<ComponentRef<'_, C>> {
+        self.check_handle(handle);
+        self.component_by_id(handle.id(), entity)
+    }
+
+    /// `component_mut` without the `TypeId` lookup. Same contract: callers
+    /// must declare `Access::Write` on the component.
+    #[cfg_attr(feature = "strict-borrows", track_caller)]
+    pub fn get_mut<C: Component>(
+        &self,
+        handle: ComponentHandle<C>,
+        entity: Entity,
+    ) -> Option<ComponentRefMut<'_, C>> {
+        self.check_handle(handle);
+        self.component_mut_by_id(handle.id(), entity)
+    }
+
+    /// `has` without the `TypeId` lookup.
+    pub fn has_handle<C: Component>(&self, handle: ComponentHandle<C>, entity: Entity) -> bool {
+        self.check_handle(handle);
+        self.archetypes.has(entity, handle.id())
+    }
+
+    #[cfg_attr(feature = "strict-borrows", track_caller)]
+    fn borrow_component<C: Component>(
+        &self,
+        entity: Entity,
+        component: ComponentId,
+        write: bool,
+    ) -> BorrowGuard<'_> {
+        let name = std::any::type_name::<C>();
+        self.component_borrows
+            .acquire(entity, component, name, write)
+    }
+
+    fn check_handle<C: Component>(&self, handle: ComponentHandle<C>) {
+        debug_assert!(
+            handle.registry() == self.components.registry(),
+            "ComponentHandle<{}> (component {}) is from registry {}, but this world's registry is {}",
+            std::any::type_name::<C>(),
+            handle.id().id(),
+            handle.registry(),
+            self.components.registry()
+        );
     }
 
     pub fn add_component<C: Component>(&mut self, entity: Entity, component: C) {
         let component_id = self.components.id::<C>();
+        let from = self.tracked_archetype(entity);
+        let source = self.transition_source(entity);
+        self.borrows.assert_unborrowed();
         Lifecycle::add_component(
             entity,
             component_id,
             component,
+            self.change_tick(),
             &mut self.archetypes,
             &mut self.tables,
         );
+        self.record_transition(entity, source);
+        self.reindex(entity, &[component_id]);
+
+        if let (Some(from), Some(to)) = (from, self.archetypes.archetype_id(entity).copied()) {
+            let component = self.components.meta(component_id).name();
+            self.tracked.record(
+                entity,
+                self.frame,
+                TrackingEvent::AddComponent {
+                    component,
+                    from,
+                    to,
+                },
+            );
+        }
     }
 
     pub fn remove_component<C: Component>(&mut self, entity: Entity) {
         let component_id = self.components.id::<C>();
+        let from = self.tracked_archetype(entity);
+        let source = self.transition_source(entity);
+        self.borrows.assert_unborrowed();
+        self.buffer_removed(entity, &[component_id]);
         Lifecycle::remove_component(entity, component_id, &mut self.archetypes, &mut self.tables);
+        self.record_transition(entity, source);
+        self.reindex(entity, &[component_id]);
+
+        if let (Some(from), Some(to)) = (from, self.archetypes.archetype_id(entity).copied()) {
+            if from != to {
+                let component = self.components.meta(component_id).name();
+                self.tracked.record(
+                    entity,
+                    self.frame,
+                    TrackingEvent::RemoveComponent {
+                        component,
+                        from,
+                        to,
+                    },
+                );
+            }
+        }
+    }
+
+    /// Inserts and removes several components with a single archetype move.
+    /// Returns `None` if the entity is not alive.
+    pub fn edit_entity(
+        &mut self,
+        entity: Entity,
+        inserts: Vec<(ComponentId, Blob)>,
+        removes: &[ComponentId],
+    ) -> Option<EntityEdit> {
+        if !self.entities.contains(entity) {
+            return None;
+        }
+
+        self.borrows.assert_unborrowed();
+        self.buffer_removed(entity, removes);
+        let edit = Lifecycle::edit_entity(
+            entity,
+            inserts,
+            removes,
+            self.change_tick(),
+            &mut self.archetypes,
+            &mut self.tables,
+        )?;
+
+        if let (Some(from), Some(to)) = (edit.from(), edit.to()) {
+            self.transitions.record(entity, from, to);
+        }
+
+        if !self.indexes.is_empty() {
+            let changed = [edit.added(), edit.removed(), edit.replaced()].concat();
+            self.reindex(entity, &changed);
+        }
+
+        if !self.tracked.is_empty() && self.tracked.is_tracked(entity) {
+            if let (Some(from), Some(to)) = (edit.from(), edit.to()) {
+                for id in edit.added() {
+                    let component = self.components.meta(*id).name();
+                    let event = TrackingEvent::AddComponent {
+                        component,
+                        from,
+                        to,
+                    };
+                    self.tracked.record(entity, self.frame, event);
+                }
+
+                for id in edit.removed() {
+                    let component = self.components.meta(*id).name();
+                    let event = TrackingEvent::RemoveComponent {
+                        component,
+                        from,
+                        to,
+                    };
+                    self.tracked.record(entity, self.frame, event);
+                }
+            }
+        }
+
+        Some(edit)
+    }
+
+    /// Adds every component of `bundle` to `entity` with a single archetype
+    /// move. An `AddComponent` output is queued per component, so observers
+    /// run on the next flush.
+    pub fn add_bundle<B: Bundle>(&mut self, entity: Entity, bundle: B) -> Option<EntityEdit> {
+        B::register(self);
+        let values = bundle.into_values(&self.components);
+        self.insert_values(entity, values)
     }
 
-    pub fn delete(&mut self, entity: Entity) {
-        let deleted = self.entities.delete(entity, true);
-        for entity in deleted {
+    /// Edits `values` into `entity` and queues `AddComponent` outputs for the
+    /// components it gained or had overwritten. Later values replace earlier
+    /// ones of the same component.
+    pub(crate) fn insert_values(
+        &mut self,
+        entity: Entity,
+        values: Vec<(ComponentId, Blob)>,
+    ) -> Option<EntityEdit> {
+        let mut inserts = Vec::<(ComponentId, Blob)>::with_capacity(values.len());
+        for (id, value) in values {
+            inserts.retain(|(other, _)| *other != id);
+            inserts.push((id, value));
+        }
+
+        let edit = self.edit_entity(entity, inserts, &[])?;
+        let mut outputs = self.resources.get_mut::<ActionOutputs>();
+        for id in edit.added().iter().chain(edit.replaced()) {
+            if let Some(meta) = self.components.meta(*id).extension::<ComponentActionMeta>() {
+                (meta.on_add())(&entity, &mut outputs);
+            }
+        }
+
+        Some(edit)
+    }
+
+    /// Removes `components` from `entity` with a single archetype move and
+    /// queues the removal outputs of each one it had.
+    pub(crate) fn remove_values(
+        &mut self,
+        entity: Entity,
+        components: &[ComponentId],
+    ) -> Option<EntityEdit> {
+        let edit = self.edit_entity(entity, Vec::new(), components)?;
+        let mut outputs = self.resources.get_mut::<ActionOutputs>();
+        for id in edit.removed() {
+            if let Some(meta) = self.components.meta(*id).extension::<ComponentActionMeta>() {
+                (meta.on_remove())(&entity, RemovalCause::Removed, &mut outputs);
+            }
+        }
+
+        Some(edit)
+    }
+
+    pub fn delete(&mut self, entity: Entity) -> Vec<Entity> {
+        self.delete_with_policy(entity, OrphanPolicy::Despawn)
+    }
+
+    /// Deletes `entity`, handling its children according to `policy`.
+    /// Reparented children get a `HierarchyChange` output. Returns the
+    /// entities that were actually deleted.
+    pub fn delete_with_policy(&mut self, entity: Entity, policy: OrphanPolicy) -> Vec<Entity> {
+        self.borrows.assert_unborrowed();
+        let orphans = match policy {
+            OrphanPolicy::Despawn => Vec::new(),
+            _ => self.entities.children(entity, false),
+        };
+        let old_parents = orphans
+            .iter()
+            .map(|child| self.tracked_parent(*child))
+            .collect::<Vec<_>>();
+
+        let deleted = self.entities.delete_with_policy(entity, policy);
+        for entity in deleted.iter().copied() {
+            if !self.tracked.is_empty() {
+                if let Some(log) = self.tracked.finish(entity, self.frame) {
+                    eprintln!("{}", log);
+                }
+            }
+
+            if !self.provenance.is_empty() {
+                self.provenance.remove(entity);
+            }
+
+            if !self.indexes.is_empty() {
+                self.unindex(entity);
+            }
+
+            if !self.resources.get::<RemovedComponents>().is_empty() {
+                if let Some(archetype) = self.archetypes.entity_archetype(entity) {
+                    let components = archetype.components().to_vec();
+                    self.buffer_removed(entity, &components);
+                }
+            }
+
+            if let Some(from) = self.transition_source(entity) {
+                self.transitions.record(entity, from, ArchetypeId::NONE);
+            }
+
             if let Some(row) =
                 Lifecycle::delete_entity(entity, &mut self.archetypes, &mut self.tables)
             {
@@ -166,62 +1063,334 @@ impl World {
 
                     if let Some(meta) = self.components.meta(id).extension::<ComponentActionMeta>()
                     {
-                        (meta.on_remove())(&entity, self.resources.get_mut::<ActionOutputs>());
+                        let mut outputs = self.resources.get_mut::<ActionOutputs>();
+                        (meta.on_remove())(&entity, RemovalCause::EntityDeleted, &mut outputs);
                     }
                 }
             }
         }
+
+        for (child, old) in orphans.into_iter().zip(old_parents) {
+            self.record_parent_change(child, old);
+            self.resources
+                .get_mut::<ActionOutputs>()
+                .add::<HierarchyChange>(child);
+        }
+
+        deleted
     }
 
     pub fn set_parent(&mut self, entity: Entity, parent: Option<Entity>) {
-        self.entities.set_parent(entity, parent)
+        let old = self.tracked_parent(entity);
+        self.entities.set_parent(entity, parent);
+        self.record_parent_change(entity, old);
     }
 
     pub fn add_child(&mut self, entity: Entity, child: Entity) {
-        self.entities.add_child(entity, child)
+        let old = self.tracked_parent(child);
+        self.entities.add_child(entity, child);
+        self.record_parent_change(child, old);
     }
 
     pub fn remove_child(&mut self, entity: Entity, child: Entity) {
-        self.entities.remove_child(entity, child)
+        let old = self.tracked_parent(child);
+        self.entities.remove_child(entity, child);
+        self.record_parent_change(child, old);
     }
 
-    pub fn run<P: SchedulePhase>(&mut self) {
-        let schedules = self.resources.get::<GlobalSchedules>();
-        schedules.run::<P>(self);
+    pub fn track(&mut self, entity: Entity, label: &str) {
+        if self.entities.contains(entity) {
+            self.tracked.track(entity, label, self.frame);
+            if let Some(provenance) = self.provenance.get(entity) {
+                let event = TrackingEvent::Spawned(provenance.clone());
+                self.tracked.record(entity, self.frame, event);
+            }
+        }
+    }
 
-        let schedules = self.resources.get::<SceneSchedules>();
-        schedules.run::<P>(self);
+    pub fn untrack(&mut self, entity: Entity) -> Option<TrackingLog> {
+        self.tracked.untrack(entity)
+    }
 
-        self.flush();
+    pub fn tracking_log(&self, entity: Entity) -> Option<&TrackingLog> {
+        self.tracked.log(entity)
     }
 
-    fn flush(&mut self) {
-        if self.resources.get::<Actions>().is_empty() {
-            return;
+    /// A readable dump of a live entity: its archetype, components, parent
+    /// and, when recorded, provenance.
+    pub fn inspect(&self, entity: Entity) -> Option<String> {
+        use std::fmt::Write;
+
+        let archetype = self.archetypes.entity_archetype(entity)?;
+        let components = archetype
+            .components()
+            .iter()
+            .map(|id| self.components.meta(*id).name())
+            .collect::<Vec<_>>();
+
+        let mut dump = format!("{:?} in archetype {}\n", entity, archetype.id().id());
+        let _ = writeln!(dump, "  components: [{}]", components.join(", "));
+        let _ = writeln!(dump, "  parent: {:?}", self.entities.parent(entity));
+        if let Some(provenance) = self.provenance.get(entity) {
+            let _ = writeln!(dump, "  {}", provenance);
+        }
+
+        Some(dump)
+    }
+
+    pub fn set_tracking_cap(&mut self, cap: usize) {
+        self.tracked.set_cap(cap);
+    }
+
+    pub fn trace(&self, level: TraceLevel, source: &'static str, message: impl Into<String>) {
+        let entry = TraceEntry::new(self.frame, level, source, message.into());
+        self.trace.push(entry);
+    }
+
+    pub fn traces(&self) -> Vec<TraceEntry> {
+        self.trace.entries()
+    }
+
+    pub fn drain_traces(&self) -> Vec<TraceEntry> {
+        self.trace.drain()
+    }
+
+    pub fn set_trace_cap(&mut self, cap: usize) {
+        self.trace.set_cap(cap);
+    }
+
+    fn tracked_archetype(&self, entity: Entity) -> Option<ArchetypeId> {
+        if self.tracked.is_empty() || !self.tracked.is_tracked(entity) {
+            return None;
+        }
+
+        self.archetypes.archetype_id(entity).copied()
+    }
+
+    /// Archetype of `entity` before a change, read only while transition
+    /// events are enabled.
+    fn transition_source(&self, entity: Entity) -> Option<ArchetypeId> {
+        if !self.transitions.is_recording() {
+            return None;
+        }
+
+        self.archetypes.archetype_id(entity).copied()
+    }
+
+    fn record_transition(&mut self, entity: Entity, from: Option<ArchetypeId>) {
+        if let (Some(from), Some(to)) = (from, self.archetypes.archetype_id(entity).copied()) {
+            self.transitions.record(entity, from, to);
+        }
+    }
+
+    fn tracked_parent(&self, entity: Entity) -> Option<Option<Entity>> {
+        if self.tracked.is_empty() || !self.tracked.is_tracked(entity) {
+            return None;
         }
 
+        Some(self.entities.parent(entity))
+    }
+
+    fn record_parent_change(&mut self, entity: Entity, old: Option<Option<Entity>>) {
+        if let Some(old) = old {
+            let new = self.entities.parent(entity);
+            if old != new {
+                self.tracked
+                    .record(entity, self.frame, TrackingEvent::ParentChange { old, new });
+            }
+        }
+    }
+
+    /// Runs the systems of phase `P`, then flushes their actions. If
+    /// `cancel_frame` is called meanwhile, the remaining schedule rows and
+    /// flush waves are skipped; unapplied actions stay queued for the next
+    /// run.
+    pub fn run<P: SchedulePhase>(&mut self) -> FrameReport {
+        self.begin_frame();
+        let report = self.run_phase::<P>();
+        self.end_frame();
+        report
+    }
+
+    /// Work done once per frame before any phase runs.
+    pub(crate) fn begin_frame(&mut self) {
+        if !self.finalized {
+            self.finalize();
+        }
+
+        self.drain_external_commands();
+        self.update_shared_inputs();
+    }
+
+    /// Runs the systems of `P` and flushes their actions, without the
+    /// per-frame work of `begin_frame` and `end_frame`.
+    pub(crate) fn run_phase<P: SchedulePhase>(&mut self) -> FrameReport {
+        self.increment_change_tick();
+        self.refresh_queries(RefreshPoint::phase::<P>());
+        self.resources.get::<GlobalSchedules>().run::<P>(self);
+        self.resources.get::<SceneSchedules>().run::<P>(self);
+
+        let waves = self.flush();
+        FrameReport {
+            frame: self.frame,
+            phase: P::PHASE,
+            waves,
+            cancelled: self.frame_token.is_cancelled(),
+        }
+    }
+
+    /// Work done once per frame after its phases ran: swapping event
+    /// buffers, reclaiming cleared storage and advancing the frame.
+    pub(crate) fn end_frame(&mut self) {
+        self.update_events();
+        self.reclaim_slice();
+        self.frame_token.reset();
+        self.frame += 1;
+        self.resources.set_frame(self.frame);
+    }
+
+    /// Asks the current `run` to stop at the next row, schedule or flush
+    /// wave boundary. Running systems are not interrupted.
+    pub fn cancel_frame(&self) {
+        self.frame_token.cancel();
+    }
+
+    /// Token cancelled by `cancel_frame`. Clone it to cancel from another
+    /// thread, or pass it to cancellable parallel work.
+    pub fn frame_token(&self) -> &CancellationToken {
+        &self.frame_token
+    }
+
+    pub(crate) fn command_buffers(&self) -> &CommandBuffers {
+        &self.commands
+    }
+
+    /// Moves actions queued through `Commands` into `Actions`. Runners call
+    /// this between rows, when no system holds `Actions`.
+    pub(crate) fn merge_commands(&self) {
+        if !self.commands.is_empty() {
+            self.commands.drain(&mut self.resource_mut::<Actions>());
+        }
+    }
+
+    fn flush(&mut self) -> usize {
+        let flushing = std::mem::replace(&mut self.flushing, true);
+        self.increment_change_tick();
+        let waves = self.flush_actions();
+        self.refresh_queries(RefreshPoint::Flush);
+        self.flushing = flushing;
+        self.report_priority_inversions();
+        if !self.frame_token.is_cancelled() {
+            self.resources.get_mut::<RemovedComponents>().clear();
+        }
+
+        waves
+    }
+
+    /// Applies queued actions and runs observers wave by wave. Returns the
+    /// number of waves applied.
+    fn flush_actions(&mut self) -> usize {
+        self.merge_commands();
+        let settled = self.resources.get::<Actions>().is_empty()
+            && self.resources.get::<ActionOutputs>().is_empty();
+        if self.frame_token.is_cancelled() || (settled && self.transitions.is_empty()) {
+            return 0;
+        }
+
+        self.recorder.begin_wave();
         let outputs = {
-            let mut actions = std::mem::take(self.resources.get_mut::<Actions>());
+            let mut actions = std::mem::take(&mut *self.resources.get_mut::<Actions>());
             let mut outputs = actions.execute(self);
             let action_outputs = self.resources.get_mut::<ActionOutputs>().take();
             self.resources.get_mut::<Actions>().append(actions);
 
             outputs.merge(action_outputs);
+            self.emit_matches(&mut outputs);
+            if settled || !self.transitions.coalesce() {
+                self.transitions.drain_into(&mut outputs);
+            }
             outputs
         };
 
-        let mut observers = std::mem::take(self.resources.get_mut::<Observables>());
+        let mut observers = std::mem::take(&mut *self.resources.get_mut::<Observables>());
         observers.execute(outputs, self);
         self.resources.get_mut::<Observables>().swap(observers);
 
+        1 + self.flush_actions()
+    }
+
+    /// Clones the values of buffered components in `ids` before they leave
+    /// `entity`.
+    fn buffer_removed(&mut self, entity: Entity, ids: &[ComponentId]) {
+        let removed = self.resources.get::<RemovedComponents>();
+        if removed.is_empty() {
+            return;
+        }
+
+        let values = ids
+            .iter()
+            .filter_map(|id| Some((*id, removed.capture(self, entity, *id)?)))
+            .collect::<Vec<_>>();
+        drop(removed);
+
+        let mut removed = self.resources.get_mut::<RemovedComponents>();
+        for (id, value) in values {
+            removed.insert(id, entity, value);
+        }
+    }
+
+    /// Runs deferred observers on the outputs held for them, then flushes
+    /// any actions they queued. With a `budget`, stops once it is exceeded
+    /// and resumes from there on the next call. Returns true when every
+    /// pending output has been delivered.
+    pub fn run_deferred_observers(&mut self, budget: Option<Duration>) -> bool {
+        let start = Instant::now();
+        let mut observers = std::mem::take(&mut *self.resources.get_mut::<Observables>());
+        let done = observers.execute_deferred(self, start, budget);
+        self.resources.get_mut::<Observables>().swap(observers);
+
         self.flush();
+        done
+    }
+
+    /// Outputs waiting for deferred observers of `A`.
+    pub fn pending_deferred<A: Action>(&self) -> usize {
+        self.resources.get::<Observables>().pending::<A>()
     }
 
     pub fn init(&mut self) {
-        let schedules = self.resources.get_mut::<GlobalSchedules>();
-        schedules.build();
+        self.finalize();
+    }
+
+    /// Registers every component referenced by systems and observers that
+    /// wasn't registered explicitly, then builds the schedules. Components
+    /// registered before this keep their ids; the rest are assigned in system
+    /// registration order. Called by `init` and by the first `run`. Panics
+    /// if the schedules can't be built; see `build_schedules`.
+    pub fn finalize(&mut self) {
+        if let Err(error) = self.build_schedules() {
+            panic!("{}", error);
+        }
+    }
+
+    /// `finalize`, returning an error naming the systems involved when
+    /// ordering constraints form a cycle. The world stays unfinalized then.
+    pub fn build_schedules(&mut self) -> Result<(), ScheduleBuildError> {
+        let mut registrars = self.resources.get::<GlobalSchedules>().registrars();
+        registrars.extend(self.resources.get::<SceneSchedules>().registrars());
+        registrars.extend(self.resources.get::<Observables>().registrars());
+
+        for register in registrars {
+            register(self);
+        }
+
+        let mut schedules = self.resources.get_mut::<GlobalSchedules>();
+        schedules.build()?;
+
+        let mut schedules = self.resources.get_mut::<SceneSchedules>();
+        schedules.build()?;
 
-        let schedules = self.resources.get_mut::<SceneSchedules>();
-        schedules.build();
+        self.finalized = true;
+        Ok(())
     }
 }
@@ -0,0 +1,106 @@
+use super::{trace::TraceLevel, World};
+use crate::core::{Component, ComponentId, Entity};
+use std::fmt::Display;
+
+/// A name to look an entity up by, e.g. from scripts or config files.
+/// Indexed once `World::register_names` is called.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Name(String);
+
+impl Name {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self(name.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for Name {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Component for Name {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NamePolicy {
+    /// A name belongs to one entity. Giving it to a second one is traced as
+    /// an error, and the name doesn't resolve while both hold it.
+    #[default]
+    Unique,
+    /// Any number of entities can share a name; `find_all_named` returns
+    /// them all.
+    Multi,
+}
+
+impl World {
+    /// Starts indexing `Name`s. The index follows adds, removes, renames
+    /// through `SetComponent` and deletes.
+    pub fn register_names(&mut self, policy: NamePolicy) {
+        self.name_policy = Some(policy);
+        self.register_index::<Name>();
+    }
+
+    pub fn name_policy(&self) -> Option<NamePolicy> {
+        self.name_policy
+    }
+
+    /// The entity named `name`, if exactly one is.
+    pub fn find_named(&self, name: &str) -> Option<Entity> {
+        match self.find_all_named(name) {
+            [entity] => Some(*entity),
+            _ => None,
+        }
+    }
+
+    /// Every entity named `name`, in the order they got it. Empty unless
+    /// `register_names` was called.
+    pub fn find_all_named(&self, name: &str) -> &[Entity] {
+        self.find_by(&Name::new(name))
+    }
+
+    /// `find_named` for actions that take a name: traces why `name` didn't
+    /// resolve, on behalf of `source`.
+    pub(crate) fn resolve_named(&self, source: &'static str, name: &str) -> Option<Entity> {
+        let message = match self.find_all_named(name) {
+            [entity] => return Some(*entity),
+            [] => format!("no entity is named {}", name),
+            entities => format!("{} entities are named {}", entities.len(), name),
+        };
+
+        self.trace(TraceLevel::Warn, source, message);
+        None
+    }
+
+    /// Traces an error if `entity` just took a name another entity holds
+    /// under the unique policy.
+    pub(crate) fn check_unique_name(&self, entity: Entity, changed: &[ComponentId]) {
+        if self.name_policy != Some(NamePolicy::Unique) {
+            return;
+        }
+
+        let Some(id) = self.components.get_id::<Name>() else {
+            return;
+        };
+
+        if !changed.contains(&id) {
+            return;
+        }
+
+        let Some(name) = self.index::<Name>().and_then(|index| index.value(entity)) else {
+            return;
+        };
+
+        let holders = self.find_all_named(name.as_str());
+        if holders.len() > 1 {
+            let message = format!(
+                "{:?} was named {}, which {:?} already is",
+                entity, name, holders[0]
+            );
+            self.trace(TraceLevel::Error, "names", message);
+        }
+    }
+}
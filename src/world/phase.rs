@@ -0,0 +1,104 @@
+use super::{FrameReport, World};
+use crate::schedule::SchedulePhase;
+use std::any::TypeId;
+
+/// A phase run by `World::update`.
+#[derive(Clone, Copy)]
+struct RegisteredPhase {
+    type_id: TypeId,
+    name: &'static str,
+    run: fn(&mut World) -> FrameReport,
+}
+
+impl RegisteredPhase {
+    fn new<P: SchedulePhase>() -> Self {
+        Self {
+            type_id: TypeId::of::<P>(),
+            name: P::PHASE,
+            run: World::run_phase::<P>,
+        }
+    }
+}
+
+/// Phases in the order `World::update` runs them.
+#[derive(Default)]
+pub(crate) struct PhaseOrder {
+    phases: Vec<RegisteredPhase>,
+}
+
+impl PhaseOrder {
+    fn position<P: SchedulePhase>(&self) -> Option<usize> {
+        let type_id = TypeId::of::<P>();
+        self.phases
+            .iter()
+            .position(|phase| phase.type_id == type_id)
+    }
+
+    fn push<P: SchedulePhase>(&mut self) {
+        if self.position::<P>().is_none() {
+            self.phases.push(RegisteredPhase::new::<P>());
+        }
+    }
+
+    /// Moves `P` to `offset` places past `Other`, registering either one
+    /// that isn't yet. `Other` is appended if it is new.
+    fn place<P: SchedulePhase, Other: SchedulePhase>(&mut self, offset: usize) {
+        if TypeId::of::<P>() == TypeId::of::<Other>() {
+            return self.push::<P>();
+        }
+
+        if let Some(index) = self.position::<P>() {
+            self.phases.remove(index);
+        }
+
+        self.push::<Other>();
+        let index = self.position::<Other>().unwrap() + offset;
+        self.phases.insert(index, RegisteredPhase::new::<P>());
+    }
+}
+
+impl World {
+    /// Registers `P` to run in `update` after the phases registered before
+    /// it. Does nothing if it is already registered.
+    pub fn add_phase<P: SchedulePhase>(&mut self) {
+        self.phases.push::<P>();
+    }
+
+    /// Registers `P`, or moves it if it already is, to run in `update`
+    /// right before `Other`. `Other` is registered too if it isn't yet.
+    pub fn add_phase_before<P: SchedulePhase, Other: SchedulePhase>(&mut self) {
+        self.phases.place::<P, Other>(0);
+    }
+
+    /// Registers `P`, or moves it if it already is, to run in `update`
+    /// right after `Other`. `Other` is registered too if it isn't yet.
+    pub fn add_phase_after<P: SchedulePhase, Other: SchedulePhase>(&mut self) {
+        self.phases.place::<P, Other>(1);
+    }
+
+    /// Names of the registered phases, in the order `update` runs them.
+    pub fn phases(&self) -> Vec<&'static str> {
+        self.phases.phases.iter().map(|phase| phase.name).collect()
+    }
+
+    /// Runs one frame: every registered phase in order, flushing the
+    /// actions of a phase before the next one starts. Per-frame work, like
+    /// advancing the frame counter and swapping event buffers, happens once
+    /// for the whole frame, so every phase reports the same frame. Stops
+    /// after a phase that was cancelled. Phases that aren't registered can
+    /// still be run with `run`.
+    pub fn update(&mut self) -> Vec<FrameReport> {
+        self.begin_frame();
+        let mut reports = Vec::with_capacity(self.phases.phases.len());
+        for phase in self.phases.phases.clone() {
+            let report = (phase.run)(self);
+            reports.push(report);
+            if report.is_cancelled() {
+                break;
+            }
+        }
+
+        self.end_frame();
+        reports
+    }
+}
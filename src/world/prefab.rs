@@ -0,0 +1,282 @@
+use super::{
+    provenance::ProvenanceSource,
+    resource::{Resource, ResourceRef},
+    trace::TraceLevel,
+    World,
+};
+use crate::{
+    core::{Component, ComponentId, Entity},
+    storage::blob::Blob,
+    system::observer::{
+        action::{Action, Actions},
+        builtin::{AddChildren, CreateEntity},
+    },
+};
+use std::collections::HashMap;
+
+struct PrefabValue {
+    register: fn(&mut World) -> ComponentId,
+    value: Blob,
+}
+
+impl PrefabValue {
+    fn new<C: Component + Clone>(value: C) -> Self {
+        let mut blob = Blob::new::<C>();
+        blob.push(value);
+
+        Self {
+            register: register_cloneable::<C>,
+            value: blob,
+        }
+    }
+}
+
+fn register_cloneable<C: Component + Clone>(world: &mut World) -> ComponentId {
+    world
+        .register_component::<C>()
+        .cloneable()
+        .finish()
+        .expect("cloneable registration sets no storage")
+}
+
+/// Describes a prefab: the components of its root and its nested children.
+/// Every component is registered as cloneable when the prefab is
+/// registered.
+#[derive(Default)]
+pub struct PrefabBuilder {
+    components: Vec<PrefabValue>,
+    children: Vec<PrefabBuilder>,
+}
+
+impl PrefabBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with<C: Component + Clone>(mut self, component: C) -> Self {
+        self.components.push(PrefabValue::new(component));
+        self
+    }
+
+    pub fn child(mut self, child: PrefabBuilder) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    fn build(self, world: &mut World) -> Prefab {
+        let components = self
+            .components
+            .into_iter()
+            .map(|component| ((component.register)(world), component.value))
+            .collect();
+        let children = self
+            .children
+            .into_iter()
+            .map(|child| child.build(world))
+            .collect();
+
+        Prefab {
+            components,
+            children,
+        }
+    }
+}
+
+/// A registered template. Values are only ever cloned out of it.
+pub struct Prefab {
+    components: Vec<(ComponentId, Blob)>,
+    children: Vec<Prefab>,
+}
+
+impl Prefab {
+    pub fn components(&self) -> impl Iterator<Item = ComponentId> + '_ {
+        self.components.iter().map(|(id, _)| *id)
+    }
+
+    pub fn children(&self) -> &[Prefab] {
+        &self.children
+    }
+
+    fn instantiate(&self, world: &World) -> PrefabInstance {
+        let components = self
+            .components
+            .iter()
+            .filter_map(|(id, value)| {
+                let clone = world.components().meta(*id).clone_fn()?;
+                Some((*id, clone(value, 0)?))
+            })
+            .collect();
+        let children = self
+            .children
+            .iter()
+            .map(|child| child.instantiate(world))
+            .collect();
+
+        PrefabInstance {
+            components,
+            children,
+        }
+    }
+}
+
+struct PrefabInstance {
+    components: Vec<(ComponentId, Blob)>,
+    children: Vec<PrefabInstance>,
+}
+
+impl PrefabInstance {
+    fn spawn(self, world: &mut World) -> Entity {
+        let entity = world
+            .insert_entity_raw(None, self.components)
+            .expect("prefab values match their registered components");
+
+        if !self.children.is_empty() {
+            let children = self
+                .children
+                .into_iter()
+                .map(|child| child.spawn(world))
+                .collect();
+
+            world
+                .resource_mut::<Actions>()
+                .add(AddChildren::new(entity, children));
+        }
+
+        entity
+    }
+}
+
+/// Prefabs registered with `World::register_prefab`, by name.
+#[derive(Default)]
+pub struct Prefabs {
+    prefabs: HashMap<String, Prefab>,
+}
+
+impl Prefabs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Prefab> {
+        self.prefabs.get(name)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.prefabs.contains_key(name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.prefabs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prefabs.is_empty()
+    }
+}
+
+impl Resource for Prefabs {}
+
+/// Spawns a registered prefab, each entity placed in its final archetype
+/// with one move and children parented with `AddChildren`. Overrides
+/// replace or extend the root's components. Outputs the root entity.
+pub struct SpawnPrefab {
+    name: String,
+    overrides: Vec<PrefabValue>,
+}
+
+impl SpawnPrefab {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            overrides: Vec::new(),
+        }
+    }
+
+    pub fn override_component<C: Component + Clone>(mut self, component: C) -> Self {
+        self.overrides.push(PrefabValue::new(component));
+        self
+    }
+
+    /// Same as `override_component`; reads better for placement components.
+    pub fn at<C: Component + Clone>(self, placement: C) -> Self {
+        self.override_component(placement)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Clones the action for a recording. Overrides are always of cloneable
+    /// components.
+    pub(crate) fn record(&self, world: &mut World) -> Option<Self> {
+        let mut overrides = Vec::with_capacity(self.overrides.len());
+        for value in &self.overrides {
+            let id = (value.register)(world);
+            let clone = world.components().meta(id).clone_fn()?;
+            overrides.push(PrefabValue {
+                register: value.register,
+                value: clone(&value.value, 0)?,
+            });
+        }
+
+        Some(Self {
+            name: self.name.clone(),
+            overrides,
+        })
+    }
+}
+
+impl std::fmt::Debug for SpawnPrefab {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SpawnPrefab")
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+impl Action for SpawnPrefab {
+    type Output = Entity;
+    const PRIORITY: u32 = CreateEntity::PRIORITY;
+
+    fn execute(&mut self, world: &mut World) -> Self::Output {
+        let mut instance = world
+            .resource::<Prefabs>()
+            .get(&self.name)
+            .map(|prefab| prefab.instantiate(world))
+            .unwrap();
+
+        for value in self.overrides.drain(..) {
+            let id = (value.register)(world);
+            instance.components.push((id, value.value));
+        }
+
+        let begun = world.begin_provenance(|| ProvenanceSource::Prefab(self.name.clone()));
+        let entity = instance.spawn(world);
+        world.end_provenance(begun);
+        entity
+    }
+
+    fn skip(&self, world: &World) -> bool {
+        let unknown = !world.resource::<Prefabs>().contains(&self.name);
+        if unknown {
+            let message = format!("unknown prefab {}", self.name);
+            world.trace(TraceLevel::Warn, "SpawnPrefab", message);
+        }
+
+        unknown
+    }
+}
+
+impl World {
+    /// Registers `prefab` under `name`, replacing any prefab of that name.
+    pub fn register_prefab(&mut self, name: impl Into<String>, prefab: PrefabBuilder) {
+        let prefab = prefab.build(self);
+        self.resource_mut::<Prefabs>()
+            .prefabs
+            .insert(name.into(), prefab);
+    }
+
+    pub fn prefabs(&self) -> ResourceRef<'_, Prefabs> {
+        self.resource::<Prefabs>()
+    }
+}
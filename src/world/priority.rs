@@ -0,0 +1,171 @@
+use super::{trace::TraceLevel, World};
+use crate::{core::Entity, system::observer::action::Action};
+use std::sync::Mutex;
+
+struct ActionInfo {
+    name: &'static str,
+    priority: u32,
+}
+
+impl ActionInfo {
+    fn of<A: Action>() -> Self {
+        Self {
+            name: std::any::type_name::<A>(),
+            priority: A::PRIORITY,
+        }
+    }
+}
+
+/// A skip noted away from the world, e.g. by a declared action running
+/// concurrently, to be recorded once its group is done.
+pub(crate) struct SkippedAction {
+    info: ActionInfo,
+    entities: Vec<Entity>,
+}
+
+#[derive(Default)]
+struct PriorityLog {
+    seq: usize,
+    current: Option<ActionInfo>,
+    skips: Vec<(usize, ActionInfo, Vec<Entity>)>,
+    created: Vec<(usize, Entity, &'static str, u32)>,
+}
+
+/// Records skipped actions and entity creations during a flush so skips
+/// caused by an action running before the one creating its entity can be
+/// reported. Off unless enabled with `World::enable_priority_diagnostics`.
+#[derive(Default)]
+pub struct PriorityDiagnostics {
+    enabled: bool,
+    log: Mutex<PriorityLog>,
+}
+
+impl PriorityDiagnostics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub(crate) fn begin<A: Action>(&self) {
+        if self.enabled {
+            self.log.lock().unwrap().current = Some(ActionInfo::of::<A>());
+        }
+    }
+
+    pub(crate) fn end(&self) {
+        if self.enabled {
+            self.log.lock().unwrap().current = None;
+        }
+    }
+
+    pub(crate) fn record_skip<A: Action>(&self, action: &A) {
+        if let Some(skipped) = self.skipped(action) {
+            self.record_skipped([skipped]);
+        }
+    }
+
+    /// The skip of `action`, if it would be recorded, without recording it.
+    pub(crate) fn skipped<A: Action>(&self, action: &A) -> Option<SkippedAction> {
+        if !self.enabled {
+            return None;
+        }
+
+        let entities = action.referenced_entities();
+        let info = ActionInfo::of::<A>();
+        (!entities.is_empty()).then_some(SkippedAction { info, entities })
+    }
+
+    /// Records skips noted with `skipped`, in order.
+    pub(crate) fn record_skipped(&self, skips: impl IntoIterator<Item = SkippedAction>) {
+        let mut log = self.log.lock().unwrap();
+        for skipped in skips {
+            log.seq += 1;
+            let seq = log.seq;
+            log.skips.push((seq, skipped.info, skipped.entities));
+        }
+    }
+
+    /// Only creations made while an action executes are kept.
+    pub(crate) fn record_created(&self, entities: &[Entity]) {
+        if !self.enabled {
+            return;
+        }
+
+        let mut log = self.log.lock().unwrap();
+        let Some((name, priority)) = log.current.as_ref().map(|info| (info.name, info.priority))
+        else {
+            return;
+        };
+
+        log.seq += 1;
+        let seq = log.seq;
+
+        for entity in entities {
+            log.created.push((seq, *entity, name, priority));
+        }
+    }
+
+    /// One message per skipped/creating action pair where the skipped
+    /// action referenced an entity created after it in the same flush.
+    fn take_inversions(&self) -> Vec<String> {
+        let log = std::mem::take(&mut *self.log.lock().unwrap());
+        let mut pairs = Vec::<(&'static str, &'static str)>::new();
+        let mut messages = Vec::new();
+
+        for (seq, skipped, entities) in &log.skips {
+            let creators = log
+                .created
+                .iter()
+                .filter(|(created, entity, _, _)| created > seq && entities.contains(entity));
+
+            for (_, entity, creator, priority) in creators {
+                if pairs.contains(&(skipped.name, creator)) {
+                    continue;
+                }
+
+                pairs.push((skipped.name, creator));
+                messages.push(format!(
+                    "{} (priority {}) skipped {:?}, which {} (priority {}) created later in the \
+                     same flush; give {} a priority above {} so it runs after {}",
+                    skipped.name,
+                    skipped.priority,
+                    entity,
+                    creator,
+                    priority,
+                    skipped.name,
+                    priority,
+                    creator
+                ));
+            }
+        }
+
+        messages
+    }
+}
+
+impl World {
+    /// Warns through the trace log when an action is skipped because it ran
+    /// before the action creating the entity it refers to. Only actions
+    /// implementing `Action::referenced_entities` are checked.
+    pub fn enable_priority_diagnostics(&mut self, enabled: bool) {
+        self.priority.enabled = enabled;
+        *self.priority.log.lock().unwrap() = PriorityLog::default();
+    }
+
+    pub(crate) fn priority_diagnostics(&self) -> &PriorityDiagnostics {
+        &self.priority
+    }
+
+    pub(crate) fn report_priority_inversions(&self) {
+        if !self.priority.enabled {
+            return;
+        }
+
+        for message in self.priority.take_inversions() {
+            self.trace(TraceLevel::Warn, "priority", message);
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use super::World;
+use crate::{core::Entity, storage::sparse::SparseMap};
+use std::fmt::Display;
+
+/// What created an entity.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProvenanceSource {
+    /// `World::create` and friends, outside of any action.
+    Direct,
+    /// The type name of the action that was executing.
+    Action(&'static str),
+    Prefab(String),
+    Scope(&'static str),
+}
+
+impl Display for ProvenanceSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProvenanceSource::Direct => write!(f, "direct call"),
+            ProvenanceSource::Action(name) => write!(f, "action {}", name),
+            ProvenanceSource::Prefab(name) => write!(f, "prefab {}", name),
+            ProvenanceSource::Scope(label) => write!(f, "scope {}", label),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Provenance {
+    pub frame: u64,
+    pub source: ProvenanceSource,
+}
+
+impl Display for Provenance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "spawned in frame {} by {}", self.frame, self.source)
+    }
+}
+
+/// Provenance of live entities, recorded while enabled with
+/// `World::enable_provenance`. Kept beside the entities rather than as a
+/// component so archetypes stay as they are.
+#[derive(Default)]
+pub struct Provenances {
+    enabled: bool,
+    sources: Vec<ProvenanceSource>,
+    entries: SparseMap<Entity, Provenance>,
+}
+
+impl Provenances {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The only check creation pays for when provenance is off.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<&Provenance> {
+        self.entries.get(&entity)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Records `entities` as created now, by the innermost source.
+    pub(crate) fn record(&mut self, entities: &[Entity], frame: u64) {
+        let source = self
+            .sources
+            .last()
+            .cloned()
+            .unwrap_or(ProvenanceSource::Direct);
+        for entity in entities {
+            let provenance = Provenance {
+                frame,
+                source: source.clone(),
+            };
+            self.entries.insert(*entity, provenance);
+        }
+    }
+
+    pub(crate) fn remove(&mut self, entity: Entity) {
+        self.entries.remove(&entity);
+    }
+}
+
+impl World {
+    /// Starts or stops recording where entities come from. Stopping forgets
+    /// what was recorded.
+    pub fn enable_provenance(&mut self, enabled: bool) {
+        self.provenance.enabled = enabled;
+        if !enabled {
+            self.provenance.entries.clear();
+        }
+    }
+
+    pub fn provenance(&self, entity: Entity) -> Option<&Provenance> {
+        self.provenance.get(entity)
+    }
+
+    /// Attributes entities created until `end_provenance` to `source`.
+    /// Returns false, without calling `source`, while provenance is off.
+    pub(crate) fn begin_provenance(&mut self, source: impl FnOnce() -> ProvenanceSource) -> bool {
+        if self.provenance.enabled {
+            self.provenance.sources.push(source());
+        }
+
+        self.provenance.enabled
+    }
+
+    /// Ends a source started by `begin_provenance`, given what it returned.
+    pub(crate) fn end_provenance(&mut self, begun: bool) {
+        if begun {
+            self.provenance.sources.pop();
+        }
+    }
+}
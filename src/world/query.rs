@@ -1,25 +1,127 @@
 use super::{
+    borrow::{ComponentRef, ComponentRefMut},
+    explain::QueryExplain,
     meta::{Access, AccessMeta},
+    resource::Resource,
+    trace::TraceLevel,
     World,
 };
 use crate::{
-    archetype::ArchetypeId,
+    archetype::{Archetype, ArchetypeId},
     core::{Component, ComponentId, Entity},
-    storage::table::Table,
-    system::SystemArg,
+    storage::table::{Column, ColumnSliceMut, RowsMut, Table, TableId},
+    system::{last_run_tick, record_query, ReadOnlySystemArg, SystemArg},
+    tasks::{CancellationToken, ScopedTaskPool},
     world::meta::AccessType,
 };
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex, RwLock,
+    },
+};
 
 pub trait BaseQuery {
     type Item<'a>;
+    /// The read-only twin of this query (`&mut C` downgraded to `&C`).
+    type ReadOnly: ReadOnlyBaseQuery;
 
     fn init(_: &World, _: &mut QueryState) {}
     fn fetch(world: &World, entity: Entity) -> Self::Item<'_>;
+    /// `fetch` for entities that may be missing a component.
+    fn try_fetch(world: &World, entity: Entity) -> Option<Self::Item<'_>> {
+        Some(Self::fetch(world, entity))
+    }
     fn metas() -> Vec<AccessMeta>;
+    fn register(_: &mut World) {}
+
+    /// Per-chunk state of the parallel loops: views of the columns `Q`
+    /// fetches over one range of a table's rows.
+    type Rows<'a>: Send;
+
+    /// Columns `rows` takes views of for writing, split between chunks by
+    /// `Table::partition_rows`.
+    fn write_columns(_: &World, _: &mut Vec<usize>) {}
+    fn rows<'a>(
+        world: &'a World,
+        table: &'a Table<Entity>,
+        rows: &mut RowsMut<'a, Entity>,
+    ) -> Self::Rows<'a>;
+    /// The item of the row at `index` within the chunk of `rows`.
+    fn fetch_row<'r>(
+        world: &'r World,
+        rows: &'r mut Self::Rows<'_>,
+        index: usize,
+        entity: Entity,
+    ) -> Self::Item<'r>;
 }
 
+/// One component's column over a chunk of rows, made by `BaseQuery::rows`.
+/// Reads go through the column, writes through the chunk's own view of it.
+pub struct ComponentRows<'a, C: Component> {
+    id: ComponentId,
+    start: usize,
+    column: Option<&'a Column>,
+    slice: Option<ColumnSliceMut<'a, C>>,
+}
+
+impl<'a, C: Component> ComponentRows<'a, C> {
+    fn read(world: &World, table: &'a Table<Entity>, rows: &RowsMut<'a, Entity>) -> Self {
+        let id = world.component_id::<C>();
+        Self {
+            id,
+            start: rows.range().start,
+            column: table.column(id.into()),
+            slice: None,
+        }
+    }
+
+    fn write(world: &World, rows: &mut RowsMut<'a, Entity>) -> Self {
+        let id = world.component_id::<C>();
+        Self {
+            id,
+            start: rows.range().start,
+            column: None,
+            slice: rows.take::<C>(id.into()),
+        }
+    }
+
+    fn get<'r>(
+        &'r self,
+        world: &'r World,
+        index: usize,
+        entity: Entity,
+    ) -> Option<ComponentRef<'r, C>> {
+        let guard = world.borrow_component::<C>(entity, self.id, false);
+        let value = self.column?.get::<C>(self.start + index)?;
+
+        Some(ComponentRef::new(value, guard))
+    }
+
+    fn get_mut<'r>(
+        &'r mut self,
+        world: &'r World,
+        index: usize,
+        entity: Entity,
+    ) -> Option<ComponentRefMut<'r, C>> {
+        let guard = world.borrow_component::<C>(entity, self.id, true);
+        let value = self.slice.as_mut()?.get_mut(index, world.change_tick())?;
+
+        Some(ComponentRefMut::new(value, guard))
+    }
+}
+
+/// Marker for queries whose items never hand out `&mut` access, so the same
+/// entity may be fetched more than once at a time.
+///
+/// # Safety
+/// Implementors must only fetch shared references to component data.
+pub unsafe trait ReadOnlyBaseQuery: BaseQuery<ReadOnly = Self> {}
+
 impl<C: Component> BaseQuery for &C {
-    type Item<'a> = &'a C;
+    type Item<'a> = ComponentRef<'a, C>;
+    type ReadOnly = Self;
 
     fn init(world: &World, state: &mut QueryState) {
         state.add_component(world.component_id::<C>());
@@ -29,14 +131,43 @@ impl<C: Component> BaseQuery for &C {
         world.component::<C>(entity).unwrap()
     }
 
+    fn try_fetch(world: &World, entity: Entity) -> Option<Self::Item<'_>> {
+        world.component::<C>(entity)
+    }
+
     fn metas() -> Vec<AccessMeta> {
         let ty = AccessType::component::<C>();
         vec![AccessMeta::new(ty, Access::Read)]
     }
+
+    fn register(world: &mut World) {
+        world.register::<C>();
+    }
+
+    type Rows<'a> = ComponentRows<'a, C>;
+
+    fn rows<'a>(
+        world: &'a World,
+        table: &'a Table<Entity>,
+        rows: &mut RowsMut<'a, Entity>,
+    ) -> Self::Rows<'a> {
+        ComponentRows::read(world, table, rows)
+    }
+
+    fn fetch_row<'r>(
+        world: &'r World,
+        rows: &'r mut Self::Rows<'_>,
+        index: usize,
+        entity: Entity,
+    ) -> Self::Item<'r> {
+        rows.get(world, index, entity)
+            .unwrap_or_else(|| Self::fetch(world, entity))
+    }
 }
 
 impl<C: Component> BaseQuery for &mut C {
-    type Item<'a> = &'a mut C;
+    type Item<'a> = ComponentRefMut<'a, C>;
+    type ReadOnly = &'static C;
 
     fn init(world: &World, state: &mut QueryState) {
         state.add_component(world.component_id::<C>());
@@ -46,14 +177,47 @@ impl<C: Component> BaseQuery for &mut C {
         world.component_mut::<C>(entity).unwrap()
     }
 
+    fn try_fetch(world: &World, entity: Entity) -> Option<Self::Item<'_>> {
+        world.component_mut::<C>(entity)
+    }
+
     fn metas() -> Vec<AccessMeta> {
         let ty = AccessType::component::<C>();
         vec![AccessMeta::new(ty, Access::Write)]
     }
+
+    fn register(world: &mut World) {
+        world.register::<C>();
+    }
+
+    type Rows<'a> = ComponentRows<'a, C>;
+
+    fn write_columns(world: &World, columns: &mut Vec<usize>) {
+        columns.push(world.component_id::<C>().into());
+    }
+
+    fn rows<'a>(
+        world: &'a World,
+        _: &'a Table<Entity>,
+        rows: &mut RowsMut<'a, Entity>,
+    ) -> Self::Rows<'a> {
+        ComponentRows::write(world, rows)
+    }
+
+    fn fetch_row<'r>(
+        world: &'r World,
+        rows: &'r mut Self::Rows<'_>,
+        index: usize,
+        entity: Entity,
+    ) -> Self::Item<'r> {
+        rows.get_mut(world, index, entity)
+            .unwrap_or_else(|| Self::fetch(world, entity))
+    }
 }
 
 impl<C: Component> BaseQuery for Option<&C> {
-    type Item<'a> = Option<&'a C>;
+    type Item<'a> = Option<ComponentRef<'a, C>>;
+    type ReadOnly = Self;
 
     fn fetch(world: &World, entity: Entity) -> Self::Item<'_> {
         world.component::<C>(entity)
@@ -63,10 +227,35 @@ impl<C: Component> BaseQuery for Option<&C> {
         let ty = AccessType::component::<C>();
         vec![AccessMeta::new(ty, Access::Read)]
     }
+
+    fn register(world: &mut World) {
+        world.register::<C>();
+    }
+
+    type Rows<'a> = ComponentRows<'a, C>;
+
+    fn rows<'a>(
+        world: &'a World,
+        table: &'a Table<Entity>,
+        rows: &mut RowsMut<'a, Entity>,
+    ) -> Self::Rows<'a> {
+        ComponentRows::read(world, table, rows)
+    }
+
+    fn fetch_row<'r>(
+        world: &'r World,
+        rows: &'r mut Self::Rows<'_>,
+        index: usize,
+        entity: Entity,
+    ) -> Self::Item<'r> {
+        rows.get(world, index, entity)
+            .or_else(|| Self::fetch(world, entity))
+    }
 }
 
 impl<C: Component> BaseQuery for Option<&mut C> {
-    type Item<'a> = Option<&'a mut C>;
+    type Item<'a> = Option<ComponentRefMut<'a, C>>;
+    type ReadOnly = Option<&'static C>;
 
     fn fetch(world: &World, entity: Entity) -> Self::Item<'_> {
         world.component_mut::<C>(entity)
@@ -76,10 +265,39 @@ impl<C: Component> BaseQuery for Option<&mut C> {
         let ty = AccessType::component::<C>();
         vec![AccessMeta::new(ty, Access::Write)]
     }
+
+    fn register(world: &mut World) {
+        world.register::<C>();
+    }
+
+    type Rows<'a> = ComponentRows<'a, C>;
+
+    fn write_columns(world: &World, columns: &mut Vec<usize>) {
+        columns.push(world.component_id::<C>().into());
+    }
+
+    fn rows<'a>(
+        world: &'a World,
+        _: &'a Table<Entity>,
+        rows: &mut RowsMut<'a, Entity>,
+    ) -> Self::Rows<'a> {
+        ComponentRows::write(world, rows)
+    }
+
+    fn fetch_row<'r>(
+        world: &'r World,
+        rows: &'r mut Self::Rows<'_>,
+        index: usize,
+        entity: Entity,
+    ) -> Self::Item<'r> {
+        rows.get_mut(world, index, entity)
+            .or_else(|| Self::fetch(world, entity))
+    }
 }
 
 impl BaseQuery for Entity {
     type Item<'a> = Entity;
+    type ReadOnly = Self;
 
     fn fetch(_world: &World, entity: Entity) -> Self::Item<'_> {
         entity
@@ -89,10 +307,133 @@ impl BaseQuery for Entity {
         let ty = AccessType::none();
         vec![AccessMeta::new(ty, Access::Read)]
     }
+
+    type Rows<'a> = ();
+
+    fn rows<'a>(_: &'a World, _: &'a Table<Entity>, _: &mut RowsMut<'a, Entity>) -> Self::Rows<'a> {
+    }
+
+    fn fetch_row<'r>(
+        world: &'r World,
+        _: &'r mut Self::Rows<'_>,
+        _: usize,
+        entity: Entity,
+    ) -> Self::Item<'r> {
+        Self::fetch(world, entity)
+    }
 }
 
+/// The parent of the fetched entity, `None` for roots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Parent(pub Option<Entity>);
+
+impl std::ops::Deref for Parent {
+    type Target = Option<Entity>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl BaseQuery for Parent {
+    type Item<'a> = Parent;
+    type ReadOnly = Self;
+
+    fn fetch(world: &World, entity: Entity) -> Self::Item<'_> {
+        Parent(world.entities().parent(entity))
+    }
+
+    fn metas() -> Vec<AccessMeta> {
+        let ty = AccessType::none();
+        vec![AccessMeta::new(ty, Access::Read)]
+    }
+
+    type Rows<'a> = ();
+
+    fn rows<'a>(_: &'a World, _: &'a Table<Entity>, _: &mut RowsMut<'a, Entity>) -> Self::Rows<'a> {
+    }
+
+    fn fetch_row<'r>(
+        world: &'r World,
+        _: &'r mut Self::Rows<'_>,
+        _: usize,
+        entity: Entity,
+    ) -> Self::Item<'r> {
+        Self::fetch(world, entity)
+    }
+}
+
+/// The children of the fetched entity in the order they were added, empty
+/// for leaves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Children<'a>(pub &'a [Entity]);
+
+impl std::ops::Deref for Children<'_> {
+    type Target = [Entity];
+
+    fn deref(&self) -> &Self::Target {
+        self.0
+    }
+}
+
+impl BaseQuery for Children<'_> {
+    type Item<'a> = Children<'a>;
+    type ReadOnly = Self;
+
+    fn fetch(world: &World, entity: Entity) -> Self::Item<'_> {
+        Children(world.entities().children_of(entity))
+    }
+
+    fn metas() -> Vec<AccessMeta> {
+        let ty = AccessType::none();
+        vec![AccessMeta::new(ty, Access::Read)]
+    }
+
+    type Rows<'a> = ();
+
+    fn rows<'a>(_: &'a World, _: &'a Table<Entity>, _: &mut RowsMut<'a, Entity>) -> Self::Rows<'a> {
+    }
+
+    fn fetch_row<'r>(
+        world: &'r World,
+        _: &'r mut Self::Rows<'_>,
+        _: usize,
+        entity: Entity,
+    ) -> Self::Item<'r> {
+        Self::fetch(world, entity)
+    }
+}
+
+unsafe impl<C: Component> ReadOnlyBaseQuery for &C {}
+unsafe impl<C: Component> ReadOnlyBaseQuery for Option<&C> {}
+unsafe impl ReadOnlyBaseQuery for Entity {}
+unsafe impl ReadOnlyBaseQuery for Parent {}
+unsafe impl ReadOnlyBaseQuery for Children<'_> {}
+
+/// Implemented when `Self` only accesses what `Q` accesses, allowing
+/// `Query::transmute`. `M` keeps the impls apart and is always inferred.
+pub trait SubsetOf<Q, M> {}
+
+/// Marks `Self` as a subset of the `I`th member of a tuple query.
+pub struct Member<M, const I: usize>(std::marker::PhantomData<M>);
+
+/// Marks a tuple whose members are each a subset of the query.
+pub struct Members<M>(std::marker::PhantomData<M>);
+
+impl<C: Component> SubsetOf<&C, ()> for &C {}
+impl<C: Component> SubsetOf<&mut C, ()> for &C {}
+impl<C: Component> SubsetOf<&mut C, ()> for &mut C {}
+impl<C: Component> SubsetOf<&C, ()> for Option<&C> {}
+impl<C: Component> SubsetOf<&mut C, ()> for Option<&C> {}
+impl<C: Component> SubsetOf<Option<&C>, ()> for Option<&C> {}
+impl<C: Component> SubsetOf<Option<&mut C>, ()> for Option<&C> {}
+impl<C: Component> SubsetOf<&mut C, ()> for Option<&mut C> {}
+impl<C: Component> SubsetOf<Option<&mut C>, ()> for Option<&mut C> {}
+impl SubsetOf<Entity, ()> for Entity {}
+
 pub trait FilterQuery {
     fn init(world: &World, state: &mut QueryState);
+    fn register(_: &mut World) {}
 }
 
 pub struct With<C: Component> {
@@ -102,7 +443,11 @@ pub struct With<C: Component> {
 impl<C: Component> FilterQuery for With<C> {
     fn init(world: &World, state: &mut QueryState) {
         let component_id = world.component_id::<C>();
-        state.add_component(component_id);
+        state.add_with(component_id);
+    }
+
+    fn register(world: &mut World) {
+        world.register::<C>();
     }
 }
 
@@ -115,6 +460,39 @@ impl<C: Component> FilterQuery for Not<C> {
         let component_id = world.component_id::<C>();
         state.add_without(component_id);
     }
+
+    fn register(world: &mut World) {
+        world.register::<C>();
+    }
+}
+
+/// Keeps entities whose `C` was inserted or mutably accessed since the
+/// system's last run. Outside of systems every value written at least once
+/// counts as changed.
+pub struct Changed<C: Component> {
+    _marker: std::marker::PhantomData<C>,
+}
+
+impl<C: Component> FilterQuery for Changed<C> {
+    fn init(world: &World, state: &mut QueryState) {
+        let component_id = world.component_id::<C>();
+        state.add_component(component_id);
+        state.add_changed(component_id);
+    }
+
+    fn register(world: &mut World) {
+        world.register::<C>();
+    }
+}
+
+/// Skips component-less entities. Queries that require no components, like
+/// `Query<Entity>`, otherwise match every live entity.
+pub struct NonEmpty;
+
+impl FilterQuery for NonEmpty {
+    fn init(_: &World, state: &mut QueryState) {
+        state.exclude_empty();
+    }
 }
 
 impl FilterQuery for () {
@@ -123,8 +501,10 @@ impl FilterQuery for () {
 
 pub struct Query<'a, Q: BaseQuery, F: FilterQuery = ()> {
     world: &'a World,
-    tables: Box<[&'a Table<Entity>]>,
-    state: QueryState,
+    tables: Arc<[TableId]>,
+    input: Option<&'a [Entity]>,
+    state: Arc<QueryState>,
+    last_run: u64,
     table_index: usize,
     row_index: usize,
     _marker: std::marker::PhantomData<(Q, F)>,
@@ -132,22 +512,46 @@ pub struct Query<'a, Q: BaseQuery, F: FilterQuery = ()> {
 
 impl<'a, Q: BaseQuery, F: FilterQuery> Query<'a, Q, F> {
     pub fn new(world: &'a World) -> Self {
-        let mut state = QueryState::new();
-        Q::init(world, &mut state);
-        F::init(world, &mut state);
+        let (state, tables) = world.query_cache().get::<Q, F>(world);
+        world.query_borrows().acquire(Self::name());
+        record_query(world.frame(), !tables.is_empty());
 
+        Self {
+            world,
+            tables,
+            input: None,
+            state,
+            last_run: last_run_tick(),
+            table_index: 0,
+            row_index: 0,
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Builds the query from a prepared state instead of the query cache.
+    /// `state` must require every component `Q` fetches without `Option`.
+    pub fn with_state(world: &'a World, state: Arc<QueryState>) -> Self {
         let tables = world
             .archetypes()
-            .archetypes(state.components(), &[])
-            .iter()
-            .map(|id| ArchetypeId::into(**id))
-            .collect::<Vec<_>>();
-        let tables = world.tables().array(&tables);
+            .matching(state.components(), state.without())
+            .filter(|archetype| state.matches(archetype))
+            .map(|archetype| ArchetypeId::into(*archetype.id()))
+            .collect::<Vec<TableId>>();
+        Self::with_tables(world, state, tables.into())
+    }
+
+    /// Builds the query over tables matched earlier, e.g. by
+    /// `QueryCache::get`. Tables removed since are skipped when iterated.
+    pub fn with_tables(world: &'a World, state: Arc<QueryState>, tables: Arc<[TableId]>) -> Self {
+        world.query_borrows().acquire(Self::name());
+        record_query(world.frame(), !tables.is_empty());
 
         Self {
             world,
             tables,
+            input: None,
             state,
+            last_run: last_run_tick(),
             table_index: 0,
             row_index: 0,
             _marker: std::marker::PhantomData,
@@ -155,76 +559,828 @@ impl<'a, Q: BaseQuery, F: FilterQuery> Query<'a, Q, F> {
     }
 
     pub fn entities(&self, entities: &'a [Entity]) -> Self {
-        let state = self.state.clone();
-        let tables = self
-            .world
-            .archetypes()
-            .entity_archetypes(state.components(), &[], entities)
+        let mut tables = Vec::<TableId>::new();
+        let archetypes = entities
             .iter()
-            .map(|id| ArchetypeId::into(**id))
-            .collect::<Vec<_>>();
+            .filter_map(|entity| self.world.archetypes().entity_archetype(*entity))
+            .filter(|archetype| self.state.matches(archetype));
 
-        let tables = self.world.tables().array(&tables);
+        for archetype in archetypes {
+            let table = ArchetypeId::into(*archetype.id());
+            if !tables.contains(&table) {
+                tables.push(table);
+            }
+        }
+
+        self.world.query_borrows().acquire(Self::name());
 
         Self {
             world: self.world,
-            tables,
-            state,
+            tables: tables.into(),
+            input: Some(entities),
+            state: self.state.clone(),
+            last_run: self.last_run,
+            table_index: 0,
+            row_index: 0,
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Describes which archetypes the query matched and why the others were
+    /// skipped. For queries restricted with `entities`, also reports why each
+    /// input entity was kept or dropped.
+    pub fn explain(&self) -> QueryExplain {
+        QueryExplain::new(self.world, Self::name(), &self.state, self.input)
+    }
+
+    /// Downgrades the query to its read-only twin over the same tables. The
+    /// twin borrows this query, so it can't outlive it:
+    ///
+    /// ```compile_fail,E0505
+    /// # use ecs::prelude::*;
+    /// # struct Position(f32);
+    /// # impl Component for Position {}
+    /// fn system(query: Query<&mut Position>) {
+    ///     let positions = query.to_readonly();
+    ///     drop(query);
+    ///     positions.count();
+    /// }
+    /// ```
+    pub fn to_readonly(&self) -> Query<'_, Q::ReadOnly, F> {
+        self.world
+            .query_borrows()
+            .acquire(Query::<Q::ReadOnly, F>::name());
+
+        Query {
+            world: self.world,
+            tables: self.tables.clone(),
+            input: self.input,
+            state: self.state.clone(),
+            last_run: self.last_run,
             table_index: 0,
             row_index: 0,
             _marker: std::marker::PhantomData,
         }
     }
+
+    /// Narrows the query to `N`, whose access must be a subset of `Q`'s:
+    /// members can be dropped and `&mut C` downgraded to `&C`. Reuses the
+    /// matched tables and keeps the filter. The narrowed query borrows this
+    /// one, so the two can't hand out overlapping items.
+    ///
+    /// ```
+    /// # use ecs::prelude::*;
+    /// # struct Transform;
+    /// # impl Component for Transform {}
+    /// # struct Velocity;
+    /// # impl Component for Velocity {}
+    /// # struct Active;
+    /// # impl Component for Active {}
+    /// fn system(mut query: Query<(&Transform, &mut Velocity), With<Active>>) {
+    ///     let transforms: Query<&Transform, With<Active>> = query.transmute();
+    /// }
+    /// ```
+    ///
+    /// ```compile_fail,E0277
+    /// # use ecs::prelude::*;
+    /// # struct Transform;
+    /// # impl Component for Transform {}
+    /// # struct Velocity;
+    /// # impl Component for Velocity {}
+    /// fn system(mut query: Query<&Transform>) {
+    ///     let velocities: Query<&Velocity> = query.transmute();
+    /// }
+    /// ```
+    pub fn transmute<N: BaseQuery + SubsetOf<Q, M>, M>(&mut self) -> Query<'_, N, F> {
+        let mut writes = N::metas()
+            .into_iter()
+            .filter(|meta| meta.access() == Access::Write)
+            .map(|meta| meta.ty())
+            .collect::<Vec<_>>();
+        let count = writes.len();
+        writes.dedup();
+        assert_eq!(
+            count,
+            writes.len(),
+            "{} aliases mutable access",
+            std::any::type_name::<N>()
+        );
+
+        self.world.query_borrows().acquire(Query::<N, F>::name());
+
+        Query {
+            world: self.world,
+            tables: self.tables.clone(),
+            input: self.input,
+            state: self.state.clone(),
+            last_run: self.last_run,
+            table_index: 0,
+            row_index: 0,
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Runs `f` on every matched item, looping over tables and rows directly
+    /// instead of going through `Iterator::next`. Prefer this over iterating
+    /// in hot systems. Items live for one call of `f`, so mutable ones can't
+    /// be kept and alias the items of a later call.
+    pub fn for_each_mut(&mut self, mut f: impl FnMut(Q::Item<'_>)) {
+        self.for_each_entity(|entity| f(Q::fetch(self.world, entity)));
+    }
+
+    pub fn for_each_with_entity(&mut self, mut f: impl FnMut(Entity, Q::Item<'_>)) {
+        self.for_each_entity(|entity| f(entity, Q::fetch(self.world, entity)));
+    }
+
+    /// Splits every matched table into chunks of `batch_size` rows and runs
+    /// `f` on the items of each chunk from a scoped task pool, returning once
+    /// every chunk is done. Chunks are disjoint, so each entity is visited
+    /// exactly once and mutable items never alias. Runs on the calling thread
+    /// when there is only one chunk.
+    pub fn par_for_each_mut(&mut self, batch_size: usize, f: impl Fn(Q::Item<'_>) + Send + Sync) {
+        self.par_for_each_row(batch_size, || true, f);
+    }
+
+    /// Like `par_for_each_mut`, but every chunk stops as soon as `token` is
+    /// cancelled. Returns false if any item was skipped.
+    pub fn par_for_each_cancellable(
+        &mut self,
+        batch_size: usize,
+        token: &CancellationToken,
+        f: impl Fn(Q::Item<'_>) + Send + Sync,
+    ) -> bool {
+        let skipped = AtomicBool::new(false);
+        let proceed = || match token.is_cancelled() {
+            true => {
+                skipped.store(true, Ordering::Relaxed);
+                false
+            }
+            false => true,
+        };
+        self.par_for_each_row(batch_size, proceed, f);
+
+        !skipped.load(Ordering::Relaxed)
+    }
+
+    /// Fetches items straight from the chunks of `Table::partition_rows`,
+    /// with the columns `Q` writes split between them. A chunk stops at the
+    /// first row `proceed` turns down.
+    fn par_for_each_row(
+        &self,
+        batch_size: usize,
+        proceed: impl Fn() -> bool + Sync,
+        f: impl Fn(Q::Item<'_>) + Send + Sync,
+    ) {
+        let world = self.world;
+        let mut columns = Vec::new();
+        Q::write_columns(world, &mut columns);
+        columns.sort_unstable();
+        columns.dedup();
+
+        let batch_size = batch_size.max(1);
+        let chunks = (0..self.tables.len())
+            .filter_map(|index| self.table(index))
+            .flat_map(|table| {
+                let chunks = table.partition_rows(&columns, batch_size);
+                chunks.into_iter().map(move |rows| (table, rows))
+            })
+            .collect::<Vec<_>>();
+
+        let (state, last_run, input) = (&*self.state, self.last_run, self.input);
+        let (proceed, f) = (&proceed, &f);
+        let run = move |(table, mut rows): (&Table<Entity>, RowsMut<'_, Entity>)| {
+            let start = rows.range().start;
+            let entities = rows.rows();
+            let mut views = Q::rows(world, table, &mut rows);
+            for (index, entity) in entities.iter().enumerate() {
+                let matched = world.entities().contains(*entity)
+                    && input.is_none_or(|input| input.contains(entity))
+                    && is_row_changed(state, last_run, table, start + index);
+                if !matched {
+                    continue;
+                }
+
+                if !proceed() {
+                    return;
+                }
+
+                f(Q::fetch_row(world, &mut views, index, *entity));
+            }
+        };
+
+        if chunks.len() <= 1 {
+            chunks.into_iter().for_each(run);
+            return;
+        }
+
+        let threads = std::thread::available_parallelism()
+            .map(|threads| threads.get())
+            .unwrap_or(1)
+            .min(chunks.len());
+
+        ScopedTaskPool::scope(threads, |pool| {
+            for chunk in chunks {
+                pool.execute(move || run(chunk));
+            }
+        });
+    }
+
+    fn for_each_entity(&self, mut f: impl FnMut(Entity)) {
+        if let Some(input) = self.input {
+            for entity in input.iter().filter(|entity| self.is_input_match(**entity)) {
+                f(*entity);
+            }
+            return;
+        }
+
+        for index in 0..self.tables.len() {
+            if let Some(table) = self.table(index) {
+                self.for_each_row(table, &mut f);
+            }
+        }
+    }
+
+    fn for_each_row(&self, table: &Table<Entity>, f: &mut impl FnMut(Entity)) {
+        let entities = self.world.entities();
+        for (row, entity) in table.rows().iter().enumerate() {
+            if entities.contains(*entity) && self.is_changed(table, row) {
+                f(*entity);
+            }
+        }
+    }
+
+    /// True if `entity`, taken from the `entities` input, is alive with the
+    /// same generation and sits in one of the matched tables.
+    fn is_input_match(&self, entity: Entity) -> bool {
+        if !self.world.entities().contains(entity) {
+            return false;
+        }
+
+        let Some(table) = self
+            .world
+            .archetypes()
+            .archetype_id(entity)
+            .map(|id| ArchetypeId::into(*id))
+            .filter(|table| self.tables.contains(table))
+        else {
+            return false;
+        };
+
+        self.is_entity_changed(table, entity)
+    }
+
+    /// True if the query would visit `entity`: it is alive, its archetype
+    /// has the queried components and none of the excluded ones, its
+    /// `Changed` components changed after the last run and, for queries over
+    /// an `entities` input, it is part of the input.
+    pub fn contains(&self, entity: Entity) -> bool {
+        if self.input.is_some_and(|input| !input.contains(&entity)) {
+            return false;
+        }
+
+        if !self.world.entities().contains(entity) {
+            return false;
+        }
+
+        let archetypes = self.world.archetypes();
+        let matched = archetypes
+            .entity_archetype(entity)
+            .is_some_and(|archetype| self.state.matches(archetype));
+
+        match archetypes.archetype_id(entity) {
+            Some(id) if matched => self.is_entity_changed(ArchetypeId::into(*id), entity),
+            _ => false,
+        }
+    }
+
+    /// The item of `entity` if the query matches it, without walking the
+    /// matched tables. Exclusive access for queries that fetch `&mut`; see
+    /// `get` for read-only ones.
+    pub fn get_mut(&mut self, entity: Entity) -> Option<Q::Item<'_>> {
+        match self.contains(entity) {
+            true => Q::try_fetch(self.world, entity),
+            false => None,
+        }
+    }
+
+    fn is_entity_changed(&self, table: TableId, entity: Entity) -> bool {
+        self.state.changed().iter().all(|component| {
+            self.world
+                .tables()
+                .get(table)
+                .and_then(|table| table.changed_tick(entity, (*component).into()))
+                .is_some_and(|tick| tick > self.last_run)
+        })
+    }
+
+    fn is_changed(&self, table: &Table<Entity>, row: usize) -> bool {
+        is_row_changed(&self.state, self.last_run, table, row)
+    }
+
+    fn name() -> &'static str {
+        std::any::type_name::<(Q, F)>()
+    }
+
+    /// The matched table at `index`, looked up now rather than when the
+    /// query was built. Tables removed since are skipped.
+    fn table(&self, index: usize) -> Option<&'a Table<Entity>> {
+        let world: &'a World = self.world;
+        let id = *self.tables.get(index)?;
+        let table = world.tables().get(id);
+        if table.is_none() && world.is_paranoid() {
+            let message = format!(
+                "{} skipped {:?}, removed while the query was alive",
+                Self::name(),
+                id
+            );
+            world.trace(TraceLevel::Warn, "query", message);
+        }
+
+        table
+    }
+
+    fn matched_entities(&self) -> Vec<Entity> {
+        let mut entities = Vec::new();
+        self.for_each_entity(|entity| entities.push(entity));
+        entities
+    }
+
+    /// The matched entities, reusing the ones remembered in `cache` while
+    /// no matched table gained or lost rows since they were collected. With
+    /// `Changed` filters the filtered columns must also be unchanged and the
+    /// last run the same. Queries restricted with `entities` always miss.
+    pub fn cached<'c>(&self, cache: &'c mut QueryResultCache) -> &'c [Entity] {
+        let tables = self
+            .tables
+            .iter()
+            .map(|id| {
+                let tick = self.world.tables().get(*id);
+                (*id, tick.map(|table| table.structure_tick()).unwrap_or(0))
+            })
+            .collect::<Vec<_>>();
+        let stamps = self
+            .tables
+            .iter()
+            .filter_map(|id| self.world.tables().get(*id))
+            .flat_map(|table| {
+                self.state.changed().iter().map(|component| {
+                    table
+                        .column((*component).into())
+                        .map(|column| column.stamps())
+                        .unwrap_or(0)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let hit = self.input.is_none()
+            && cache
+                .state
+                .as_ref()
+                .is_some_and(|state| Arc::ptr_eq(state, &self.state))
+            && cache.tables == tables
+            && cache.stamps == stamps
+            && (self.state.changed().is_empty() || cache.last_run == self.last_run);
+
+        if hit {
+            cache.hits += 1;
+        } else {
+            cache.misses += 1;
+            cache.state = Some(self.state.clone());
+            cache.tables = tables;
+            cache.stamps = stamps;
+            cache.last_run = self.last_run;
+            cache.entities = self.matched_entities();
+        }
+
+        &cache.entities
+    }
+}
+
+/// Entities remembered by `Query::cached` for one query. Keep one per
+/// query, e.g. in a resource.
+#[derive(Default)]
+pub struct QueryResultCache {
+    state: Option<Arc<QueryState>>,
+    tables: Vec<(TableId, u64)>,
+    stamps: Vec<u64>,
+    last_run: u64,
+    entities: Vec<Entity>,
+    hits: usize,
+    misses: usize,
+}
+
+impl QueryResultCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+
+    /// The entities collected by the last miss.
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    /// Forgets the remembered entities so the next call misses.
+    pub fn invalidate(&mut self) {
+        self.state = None;
+        self.entities.clear();
+    }
+}
+
+impl Resource for QueryResultCache {}
+
+impl<Q: BaseQuery, F: FilterQuery> Drop for Query<'_, Q, F> {
+    fn drop(&mut self) {
+        self.world.query_borrows().release(Self::name());
+    }
+}
+
+impl<'a, Q: ReadOnlyBaseQuery, F: FilterQuery> Query<'a, Q, F> {
+    /// Read-only counterpart of `get_mut`. Returns `None` for entities the
+    /// query doesn't match, including dead ones and ones missing a
+    /// component:
+    ///
+    /// ```ignore
+    /// fn on_hit(entities: &[Entity], query: Query<&Health>) {
+    ///     for health in entities.iter().filter_map(|e| query.get(*e)) {}
+    /// }
+    /// ```
+    pub fn get(&self, entity: Entity) -> Option<Q::Item<'a>> {
+        match self.contains(entity) {
+            true => Q::try_fetch(self.world, entity),
+            false => None,
+        }
+    }
+
+    /// Read-only counterpart of `for_each_mut`.
+    pub fn for_each(&self, mut f: impl FnMut(Q::Item<'a>)) {
+        self.for_each_entity(|entity| f(Q::fetch(self.world, entity)));
+    }
+
+    /// Read-only counterpart of `par_for_each_mut`.
+    pub fn par_for_each(&self, batch_size: usize, f: impl Fn(Q::Item<'_>) + Send + Sync) {
+        self.par_for_each_row(batch_size, || true, f);
+    }
+
+    /// Iterates every unordered pair of matched entities.
+    ///
+    /// Only available for read-only queries, since both items of a pair are
+    /// alive at the same time:
+    ///
+    /// ```compile_fail,E0599
+    /// # use ecs::prelude::*;
+    /// # struct Position(f32);
+    /// # impl Component for Position {}
+    /// fn system(query: Query<&mut Position>) {
+    ///     for (a, b) in query.iter_combinations() {}
+    /// }
+    /// ```
+    ///
+    /// ```
+    /// # use ecs::prelude::*;
+    /// # struct Position(f32);
+    /// # impl Component for Position {}
+    /// fn system(query: Query<(Entity, &Position)>) {
+    ///     for ((a, pa), (b, pb)) in query.iter_combinations() {}
+    /// }
+    /// ```
+    pub fn iter_combinations(&self) -> QueryCombinations<'a, Q> {
+        QueryCombinations {
+            world: self.world,
+            entities: self.matched_entities(),
+            first: 0,
+            second: 1,
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+/// True if every `Changed` component of `row` changed after `last_run`.
+fn is_row_changed(state: &QueryState, last_run: u64, table: &Table<Entity>, row: usize) -> bool {
+    state.changed().iter().all(|component| {
+        table
+            .column((*component).into())
+            .and_then(|column| column.changed_tick(row))
+            .is_some_and(|tick| tick > last_run)
+    })
+}
+
+pub struct QueryCombinations<'a, Q: ReadOnlyBaseQuery> {
+    world: &'a World,
+    entities: Vec<Entity>,
+    first: usize,
+    second: usize,
+    _marker: std::marker::PhantomData<Q>,
+}
+
+impl<'a, Q: ReadOnlyBaseQuery> Iterator for QueryCombinations<'a, Q> {
+    type Item = (Q::Item<'a>, Q::Item<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.second >= self.entities.len() {
+            self.first += 1;
+            self.second = self.first + 1;
+
+            if self.second >= self.entities.len() {
+                return None;
+            }
+        }
+
+        let first = self.entities[self.first];
+        let second = self.entities[self.second];
+        self.second += 1;
+
+        Some((Q::fetch(self.world, first), Q::fetch(self.world, second)))
+    }
 }
 
 #[derive(Clone)]
 pub struct QueryState {
     components: Vec<ComponentId>,
+    with: Vec<ComponentId>,
     without: Vec<ComponentId>,
+    changed: Vec<ComponentId>,
+    exclude_empty: bool,
 }
 
 impl QueryState {
     pub fn new() -> Self {
         Self {
             components: Vec::new(),
+            with: Vec::new(),
             without: Vec::new(),
+            changed: Vec::new(),
+            exclude_empty: false,
+        }
+    }
+
+    /// Builds a state straight from component ids, e.g. from
+    /// `ComponentHandle::id`, without going through `BaseQuery::init`.
+    pub fn from_handles(components: &[ComponentId], without: &[ComponentId]) -> Self {
+        Self {
+            components: components.to_vec(),
+            with: Vec::new(),
+            without: without.to_vec(),
+            changed: Vec::new(),
+            exclude_empty: false,
         }
     }
 
+    pub fn exclude_empty(&mut self) {
+        self.exclude_empty = true;
+    }
+
+    pub fn excludes_empty(&self) -> bool {
+        self.exclude_empty
+    }
+
     pub fn add_component(&mut self, component: ComponentId) {
         self.components.push(component);
     }
 
+    pub fn add_with(&mut self, component: ComponentId) {
+        self.with.push(component);
+    }
+
     pub fn add_without(&mut self, component: ComponentId) {
         self.without.push(component);
     }
 
+    pub fn add_changed(&mut self, component: ComponentId) {
+        self.changed.push(component);
+    }
+
     pub fn components(&self) -> &[ComponentId] {
         &self.components
     }
+
+    /// Components that must have changed since the last run, see `Changed`.
+    pub fn changed(&self) -> &[ComponentId] {
+        &self.changed
+    }
+
+    /// Components an archetype must have that aren't fetched, see `With`.
+    pub fn with(&self) -> &[ComponentId] {
+        &self.with
+    }
+
+    pub fn without(&self) -> &[ComponentId] {
+        &self.without
+    }
+
+    pub fn matches(&self, archetype: &Archetype) -> bool {
+        if self.exclude_empty && archetype.components().is_empty() {
+            return false;
+        }
+
+        self.components
+            .iter()
+            .chain(&self.with)
+            .all(|c| archetype.components().contains(c))
+            && self
+                .without
+                .iter()
+                .all(|c| !archetype.components().contains(c))
+    }
+}
+
+/// Debug-only count of live queries. Structural changes (creating, editing
+/// or deleting entities) panic while any query is alive, since they can move
+/// the rows a query is walking. Compiled out of release builds.
+#[derive(Default)]
+pub struct QueryBorrows {
+    #[cfg(debug_assertions)]
+    live: AtomicUsize,
+    #[cfg(debug_assertions)]
+    names: Mutex<Vec<&'static str>>,
+}
+
+impl QueryBorrows {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[allow(unused_variables)]
+    pub fn acquire(&self, name: &'static str) {
+        #[cfg(debug_assertions)]
+        {
+            self.live.fetch_add(1, Ordering::SeqCst);
+            self.names.lock().unwrap().push(name);
+        }
+    }
+
+    #[allow(unused_variables)]
+    pub fn release(&self, name: &'static str) {
+        #[cfg(debug_assertions)]
+        {
+            self.live.fetch_sub(1, Ordering::SeqCst);
+            let mut names = self.names.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(index) = names.iter().position(|n| *n == name) {
+                names.swap_remove(index);
+            }
+        }
+    }
+
+    /// Number of live queries. Always 0 in release builds.
+    pub fn live(&self) -> usize {
+        #[cfg(debug_assertions)]
+        return self.live.load(Ordering::SeqCst);
+
+        #[cfg(not(debug_assertions))]
+        0
+    }
+
+    pub fn assert_unborrowed(&self) {
+        #[cfg(debug_assertions)]
+        {
+            let live = self.live.load(Ordering::SeqCst);
+            if live > 0 {
+                let names = self.names.lock().unwrap().clone();
+                panic!(
+                    "structural change while {} queries alive: {:?}",
+                    live, names
+                );
+            }
+        }
+    }
+}
+
+struct CachedQuery {
+    state: Arc<QueryState>,
+    tables: Arc<[TableId]>,
+    generation: u64,
 }
 
-impl<'a, Q: BaseQuery> Iterator for Query<'a, Q> {
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct QueryScratchStats {
+    /// Queries constructed through the cache.
+    pub constructions: usize,
+    /// Times a query's state and table list had to be rebuilt.
+    pub rebuilds: usize,
+}
+
+/// Per query type state and matched tables, shared by every `Query::new` of
+/// that type until a new archetype is created. Hits only take the read lock
+/// and clone two `Arc`s, so parallel systems building queries don't
+/// serialize and constructions don't allocate.
+#[derive(Default)]
+pub struct QueryCache {
+    queries: RwLock<HashMap<&'static str, CachedQuery>>,
+    constructions: AtomicUsize,
+    rebuilds: AtomicUsize,
+}
+
+impl QueryCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get<Q: BaseQuery, F: FilterQuery>(
+        &self,
+        world: &World,
+    ) -> (Arc<QueryState>, Arc<[TableId]>) {
+        let key = std::any::type_name::<(Q, F)>();
+        let generation = world.archetypes().generation();
+        self.constructions.fetch_add(1, Ordering::Relaxed);
+
+        if let Some(cached) = self.queries.read().unwrap().get(key) {
+            if cached.generation == generation {
+                return (cached.state.clone(), cached.tables.clone());
+            }
+        }
+
+        self.rebuilds.fetch_add(1, Ordering::Relaxed);
+
+        let mut state = QueryState::new();
+        Q::init(world, &mut state);
+        F::init(world, &mut state);
+
+        let tables = world
+            .archetypes()
+            .matching(state.components(), state.without())
+            .filter(|archetype| state.matches(archetype))
+            .map(|archetype| ArchetypeId::into(*archetype.id()))
+            .collect::<Vec<TableId>>();
+
+        let cached = CachedQuery {
+            state: Arc::new(state),
+            tables: tables.into(),
+            generation,
+        };
+        let result = (cached.state.clone(), cached.tables.clone());
+        self.queries.write().unwrap().insert(key, cached);
+
+        result
+    }
+
+    pub fn stats(&self) -> QueryScratchStats {
+        QueryScratchStats {
+            constructions: self.constructions.load(Ordering::Relaxed),
+            rebuilds: self.rebuilds.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Forgets every cached state, e.g. after the archetypes were replaced
+    /// wholesale.
+    pub fn clear(&self) {
+        self.queries.write().unwrap().clear();
+    }
+}
+
+impl<'a, Q: BaseQuery, F: FilterQuery> Iterator for Query<'a, Q, F> {
     type Item = Q::Item<'a>;
 
+    /// Rows whose entity was deleted, or whose id now belongs to a newer
+    /// generation, are skipped, as are tables removed since the query was
+    /// built. With `entities`, only the given entities
+    /// are visited.
     fn next(&mut self) -> Option<Self::Item> {
-        if self.table_index >= self.tables.len() {
+        if let Some(input) = self.input {
+            while let Some(entity) = input.get(self.row_index).copied() {
+                self.row_index += 1;
+                if self.is_input_match(entity) {
+                    return Some(Q::fetch(self.world, entity));
+                }
+            }
+
             return None;
-        } else if self.row_index >= self.tables[self.table_index].len() {
-            self.table_index += 1;
-            self.row_index = 0;
-            return self.next();
-        } else {
-            let entity = self.tables[self.table_index].rows()[self.row_index];
+        }
+
+        while self.table_index < self.tables.len() {
+            let Some(table) = self.table(self.table_index) else {
+                self.table_index += 1;
+                self.row_index = 0;
+                continue;
+            };
+
+            if self.row_index >= table.len() {
+                self.table_index += 1;
+                self.row_index = 0;
+                continue;
+            }
+
+            let row = self.row_index;
+            let entity = table.rows()[row];
             self.row_index += 1;
 
-            Some(Q::fetch(self.world, entity))
+            if self.world.entities().contains(entity) && self.is_changed(table, row) {
+                return Some(Q::fetch(self.world, entity));
+            }
         }
+
+        None
     }
 }
 
-impl<Q: BaseQuery> SystemArg for Query<'_, Q> {
-    type Item<'a> = Query<'a, Q>;
+impl<Q: BaseQuery, F: FilterQuery> SystemArg for Query<'_, Q, F> {
+    type Item<'a> = Query<'a, Q, F>;
 
     fn get<'a>(world: &'a World) -> Self::Item<'a> {
         Query::new(world)
@@ -233,14 +1389,22 @@ impl<Q: BaseQuery> SystemArg for Query<'_, Q> {
     fn metas() -> Vec<AccessMeta> {
         Q::metas()
     }
+
+    fn register(world: &mut World) {
+        Q::register(world);
+        F::register(world);
+    }
 }
 
+unsafe impl<Q: ReadOnlyBaseQuery, F: FilterQuery> ReadOnlySystemArg for Query<'_, Q, F> {}
+
 #[macro_export]
 macro_rules! impl_base_query_for_tuples {
     ($(($($name:ident),+)),+) => {
         $(
             impl<$($name: BaseQuery),+> BaseQuery for ($($name,)+) {
                 type Item<'a> = ($($name::Item<'a>,)+);
+                type ReadOnly = ($($name::ReadOnly,)+);
 
                 fn init(world: &World, state: &mut QueryState) {
                     $(
@@ -252,6 +1416,10 @@ macro_rules! impl_base_query_for_tuples {
                     ($($name::fetch(world, entity),)+)
                 }
 
+                fn try_fetch(world: &World, entity: Entity) -> Option<Self::Item<'_>> {
+                    Some(($($name::try_fetch(world, entity)?,)+))
+                }
+
                 fn metas() -> Vec<AccessMeta> {
                     let mut metas = Vec::new();
                     $(
@@ -259,7 +1427,42 @@ macro_rules! impl_base_query_for_tuples {
                     )+
                     metas
                 }
+
+                fn register(world: &mut World) {
+                    $(
+                        $name::register(world);
+                    )+
+                }
+
+                type Rows<'a> = ($($name::Rows<'a>,)+);
+
+                fn write_columns(world: &World, columns: &mut Vec<usize>) {
+                    $(
+                        $name::write_columns(world, columns);
+                    )+
+                }
+
+                fn rows<'a>(
+                    world: &'a World,
+                    table: &'a Table<Entity>,
+                    rows: &mut RowsMut<'a, Entity>,
+                ) -> Self::Rows<'a> {
+                    ($($name::rows(world, table, rows),)+)
+                }
+
+                #[allow(non_snake_case)]
+                fn fetch_row<'r>(
+                    world: &'r World,
+                    rows: &'r mut Self::Rows<'_>,
+                    index: usize,
+                    entity: Entity,
+                ) -> Self::Item<'r> {
+                    let ($($name,)+) = rows;
+                    ($($name::fetch_row(world, $name, index, entity),)+)
+                }
             }
+
+            unsafe impl<$($name: ReadOnlyBaseQuery),+> ReadOnlyBaseQuery for ($($name,)+) {}
         )+
     };
 }
@@ -273,10 +1476,101 @@ macro_rules! impl_filter_query_for_tuple {
                     $filter::init(world, state);
                 )*
             }
+
+            fn register(world: &mut World) {
+                $(
+                    $filter::register(world);
+                )*
+            }
+        }
+    };
+}
+
+macro_rules! impl_subset_of_member {
+    (($($name:ident),+), $index:tt, $member:ident) => {
+        impl<X, M, $($name),+> SubsetOf<($($name,)+), Member<M, $index>> for X where
+            X: SubsetOf<$member, M>
+        {
         }
     };
 }
 
+macro_rules! impl_subset_of_members {
+    ($(($name:ident, $marker:ident)),+) => {
+        impl<Q, $($name, $marker),+> SubsetOf<Q, Members<($($marker,)+)>> for ($($name,)+) where
+            $($name: SubsetOf<Q, $marker>),+
+        {
+        }
+    };
+}
+
+impl_subset_of_member!((A, B), 0, A);
+impl_subset_of_member!((A, B), 1, B);
+
+impl_subset_of_member!((A, B, C), 0, A);
+impl_subset_of_member!((A, B, C), 1, B);
+impl_subset_of_member!((A, B, C), 2, C);
+
+impl_subset_of_member!((A, B, C, D), 0, A);
+impl_subset_of_member!((A, B, C, D), 1, B);
+impl_subset_of_member!((A, B, C, D), 2, C);
+impl_subset_of_member!((A, B, C, D), 3, D);
+
+impl_subset_of_member!((A, B, C, D, E), 0, A);
+impl_subset_of_member!((A, B, C, D, E), 1, B);
+impl_subset_of_member!((A, B, C, D, E), 2, C);
+impl_subset_of_member!((A, B, C, D, E), 3, D);
+impl_subset_of_member!((A, B, C, D, E), 4, E);
+
+impl_subset_of_member!((A, B, C, D, E, F), 0, A);
+impl_subset_of_member!((A, B, C, D, E, F), 1, B);
+impl_subset_of_member!((A, B, C, D, E, F), 2, C);
+impl_subset_of_member!((A, B, C, D, E, F), 3, D);
+impl_subset_of_member!((A, B, C, D, E, F), 4, E);
+impl_subset_of_member!((A, B, C, D, E, F), 5, F);
+
+impl_subset_of_member!((A, B, C, D, E, F, G), 0, A);
+impl_subset_of_member!((A, B, C, D, E, F, G), 1, B);
+impl_subset_of_member!((A, B, C, D, E, F, G), 2, C);
+impl_subset_of_member!((A, B, C, D, E, F, G), 3, D);
+impl_subset_of_member!((A, B, C, D, E, F, G), 4, E);
+impl_subset_of_member!((A, B, C, D, E, F, G), 5, F);
+impl_subset_of_member!((A, B, C, D, E, F, G), 6, G);
+
+impl_subset_of_member!((A, B, C, D, E, F, G, H), 0, A);
+impl_subset_of_member!((A, B, C, D, E, F, G, H), 1, B);
+impl_subset_of_member!((A, B, C, D, E, F, G, H), 2, C);
+impl_subset_of_member!((A, B, C, D, E, F, G, H), 3, D);
+impl_subset_of_member!((A, B, C, D, E, F, G, H), 4, E);
+impl_subset_of_member!((A, B, C, D, E, F, G, H), 5, F);
+impl_subset_of_member!((A, B, C, D, E, F, G, H), 6, G);
+impl_subset_of_member!((A, B, C, D, E, F, G, H), 7, H);
+
+impl_subset_of_members!((A, MA), (B, MB));
+impl_subset_of_members!((A, MA), (B, MB), (C, MC));
+impl_subset_of_members!((A, MA), (B, MB), (C, MC), (D, MD));
+impl_subset_of_members!((A, MA), (B, MB), (C, MC), (D, MD), (E, ME));
+impl_subset_of_members!((A, MA), (B, MB), (C, MC), (D, MD), (E, ME), (F, MF));
+impl_subset_of_members!(
+    (A, MA),
+    (B, MB),
+    (C, MC),
+    (D, MD),
+    (E, ME),
+    (F, MF),
+    (G, MG)
+);
+impl_subset_of_members!(
+    (A, MA),
+    (B, MB),
+    (C, MC),
+    (D, MD),
+    (E, ME),
+    (F, MF),
+    (G, MG),
+    (H, MH)
+);
+
 impl_base_query_for_tuples!((A, B));
 impl_base_query_for_tuples!((A, B, C));
 impl_base_query_for_tuples!((A, B, C, D));
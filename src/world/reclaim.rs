@@ -0,0 +1,181 @@
+use super::{meta::ComponentActionMeta, removed::RemovedComponents, World};
+use crate::{
+    archetype::ArchetypeId,
+    core::{ComponentId, Entity},
+    storage::table::Column,
+    system::observer::{
+        builtin::{DeleteEntity, RemovalCause},
+        ActionOutputs,
+    },
+};
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Values dropped between checks of the budget in `World::reclaim`.
+const RECLAIM_STEP: usize = 64;
+
+/// Columns taken out of storage by `World::clear_entities`, waiting for
+/// `World::reclaim` to drop their values.
+#[derive(Default)]
+pub(crate) struct PendingDrops {
+    columns: VecDeque<Column>,
+}
+
+impl PendingDrops {
+    fn push(&mut self, column: Column) {
+        if !column.is_empty() {
+            self.columns.push_back(column);
+        }
+    }
+
+    /// Values still to be dropped.
+    pub fn len(&self) -> usize {
+        self.columns.iter().map(Column::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+
+    pub fn allocated_bytes(&self) -> usize {
+        self.columns.iter().map(Column::allocated_bytes).sum()
+    }
+
+    /// Drops values until the backlog is empty or `budget` has passed,
+    /// checking the time every `RECLAIM_STEP` drops. Returns true once
+    /// nothing is left.
+    fn drain(&mut self, budget: Duration) -> bool {
+        let start = Instant::now();
+        while let Some(column) = self.columns.front_mut() {
+            // Shared values belong to a fork as well; only our handle goes.
+            if column.is_shared() {
+                self.columns.pop_front();
+                continue;
+            }
+
+            for _ in 0..RECLAIM_STEP {
+                match column.len() {
+                    0 => break,
+                    len => column.swap_remove_drop(len - 1),
+                };
+            }
+
+            if column.is_empty() {
+                self.columns.pop_front();
+            }
+
+            if start.elapsed() >= budget {
+                break;
+            }
+        }
+
+        self.columns.is_empty()
+    }
+}
+
+impl World {
+    /// Deletes every entity at once. The entities are gone as soon as this
+    /// returns; `DeleteEntity` observers get all of them in one batch and
+    /// remove observers get the ids per component. The storage holding
+    /// their components is handed to `reclaim`, so the cost of dropping the
+    /// values is spread over the following frames. Components registered
+    /// with `drop_immediately` are dropped here. Returns the number of
+    /// entities deleted. Resources and schedules are kept, and ids handed
+    /// out afterwards never match an entity of before.
+    pub fn clear_entities(&mut self) -> usize {
+        self.borrows.assert_unborrowed();
+        let entities = self.entities.iter().collect::<Vec<_>>();
+        for entity in entities.iter().copied() {
+            if !self.tracked.is_empty() {
+                if let Some(log) = self.tracked.finish(entity, self.frame) {
+                    eprintln!("{}", log);
+                }
+            }
+
+            if !self.provenance.is_empty() {
+                self.provenance.remove(entity);
+            }
+
+            let components = self
+                .archetypes
+                .entity_archetype(entity)
+                .map(|archetype| archetype.components().to_vec())
+                .unwrap_or_default();
+
+            if !self.resources.get::<RemovedComponents>().is_empty() {
+                self.buffer_removed(entity, &components);
+            }
+
+            if let Some(from) = self.transition_source(entity) {
+                self.transitions.record(entity, from, ArchetypeId::NONE);
+            }
+
+            self.archetypes.delete_entity(entity);
+            self.notify_cleared(entity, &components);
+        }
+
+        let mut outputs = self.resources.get_mut::<ActionOutputs>();
+        for entity in entities.iter().copied() {
+            outputs.add::<DeleteEntity>(entity);
+        }
+        drop(outputs);
+
+        for table in self.tables.iter_mut() {
+            for (index, column) in table.take_columns() {
+                match self
+                    .components
+                    .meta(ComponentId::from(index))
+                    .drops_immediately()
+                {
+                    true => drop(column),
+                    false => self.pending_drops.push(column),
+                }
+            }
+        }
+
+        for root in self.entities.roots().to_vec() {
+            self.entities.delete(root, true);
+        }
+
+        self.clear_indexes();
+
+        entities.len()
+    }
+
+    /// Drops values left by `clear_entities` until none are left or `budget`
+    /// has passed. Returns true once the backlog is empty. `run` calls it
+    /// with the budget set by `set_reclaim_budget`.
+    pub fn reclaim(&mut self, budget: Duration) -> bool {
+        self.pending_drops.drain(budget)
+    }
+
+    /// Component values waiting to be dropped by `reclaim`.
+    pub fn pending_drops(&self) -> usize {
+        self.pending_drops.len()
+    }
+
+    /// Time each `run` spends dropping values left by `clear_entities`, or
+    /// `None` to leave it all to explicit `reclaim` calls.
+    pub fn set_reclaim_budget(&mut self, budget: Option<Duration>) {
+        self.reclaim_budget = budget;
+    }
+
+    pub(crate) fn reclaim_slice(&mut self) {
+        if let Some(budget) = self.reclaim_budget {
+            if !self.pending_drops.is_empty() {
+                self.reclaim(budget);
+            }
+        }
+    }
+
+    fn notify_cleared(&mut self, entity: Entity, components: &[ComponentId]) {
+        for id in components {
+            if let Some(meta) = self.components.meta(*id).extension::<ComponentActionMeta>() {
+                let mut outputs = self.resources.get_mut::<ActionOutputs>();
+                (meta.on_remove())(&entity, RemovalCause::EntityDeleted, &mut outputs);
+            }
+        }
+    }
+}
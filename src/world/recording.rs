@@ -0,0 +1,410 @@
+use super::{removed::RemovedComponents, trace::TraceLevel, World};
+use crate::{
+    core::{Component, Entity},
+    system::observer::{
+        action::{Action, ActionOutputs, Actions},
+        builtin::{
+            AddChildren, AddComponent, CreateEntity, DeleteEntity, EditEntity, RemoveChildren,
+            RemoveComponent, SetComponent, SetParent, SpawnBatch,
+        },
+        Observables,
+    },
+    world::prefab::SpawnPrefab,
+};
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+/// Maps entities of a recorded world to the entities a replay created in
+/// their place. Entities it doesn't know map to themselves.
+#[derive(Debug, Default, Clone)]
+pub struct EntityMapper {
+    entities: HashMap<Entity, Entity>,
+}
+
+impl EntityMapper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, from: Entity, to: Entity) {
+        self.entities.insert(from, to);
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<Entity> {
+        self.entities.get(&entity).copied()
+    }
+
+    pub fn map(&self, entity: Entity) -> Entity {
+        self.get(entity).unwrap_or(entity)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, Entity)> + '_ {
+        self.entities.iter().map(|(from, to)| (*from, *to))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+}
+
+/// Clones an action for a recording, or `None` if this one can't be.
+pub(crate) type RecordFn<A> = fn(&A, &mut World) -> Option<A>;
+
+type CloneAction = dyn Fn(&dyn Any, &mut World) -> Option<Box<dyn RecordedAction>> + Send + Sync;
+
+pub(crate) trait RecordedAction: Send {
+    fn name(&self) -> &'static str;
+    fn replay(&self, world: &mut World, mapper: &EntityMapper, outputs: &mut ActionOutputs);
+}
+
+struct Recorded<A: Action> {
+    action: A,
+    clone: RecordFn<A>,
+}
+
+impl<A: Action + Send> RecordedAction for Recorded<A> {
+    fn name(&self) -> &'static str {
+        std::any::type_name::<A>()
+    }
+
+    fn replay(&self, world: &mut World, mapper: &EntityMapper, outputs: &mut ActionOutputs) {
+        A::register(world);
+        let Some(mut action) = (self.clone)(&self.action, world) else {
+            world.trace(TraceLevel::Warn, "replay", "recorded action not cloneable");
+            return;
+        };
+
+        action.map_entities(mapper);
+        if !action.skip(world) {
+            outputs.add::<A>(action.execute(world));
+        }
+    }
+}
+
+pub(crate) enum RecordedEntry {
+    Action {
+        action: Box<dyn RecordedAction>,
+        /// Entities created while the action executed, in creation order.
+        created: Vec<Entity>,
+    },
+    Gap(&'static str),
+}
+
+impl RecordedEntry {
+    fn name(&self) -> &'static str {
+        match self {
+            RecordedEntry::Action { action, .. } => action.name(),
+            RecordedEntry::Gap(name) => name,
+        }
+    }
+}
+
+/// Actions executed while `World::start_recording` was active, wave by
+/// wave in execution order. Actions of types not registered as recordable
+/// are kept as gaps, by type name.
+#[derive(Default)]
+pub struct ActionRecording {
+    waves: Vec<Vec<RecordedEntry>>,
+}
+
+impl ActionRecording {
+    pub fn waves(&self) -> usize {
+        self.waves.len()
+    }
+
+    /// Type names of the recorded actions and gaps of `wave`.
+    pub fn wave(&self, wave: usize) -> Vec<&'static str> {
+        self.waves
+            .get(wave)
+            .map(|entries| entries.iter().map(RecordedEntry::name).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.waves.iter().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.waves.is_empty()
+    }
+
+    /// Type names of the actions that ran but couldn't be recorded.
+    pub fn gaps(&self) -> Vec<&'static str> {
+        self.waves
+            .iter()
+            .flatten()
+            .filter_map(|entry| match entry {
+                RecordedEntry::Gap(name) => Some(*name),
+                RecordedEntry::Action { .. } => None,
+            })
+            .collect()
+    }
+
+    /// Replays the recording onto `world` and returns which of its entities
+    /// stand in for the recorded ones.
+    pub fn replay(&self, world: &mut World) -> EntityMapper {
+        let mut mapper = EntityMapper::new();
+        self.replay_with(world, &mut mapper);
+        mapper
+    }
+
+    /// Replays the recording onto `world`, starting from `mapper`, e.g. with
+    /// entities that existed before the recording started. Each wave runs
+    /// its actions in recorded order, regardless of priority, and then its
+    /// observers. Actions queued meanwhile are dropped, since the recording
+    /// already holds every action that ran; actions queued before are kept.
+    pub fn replay_with(&self, world: &mut World, mapper: &mut EntityMapper) {
+        world.borrows.assert_unborrowed();
+        world.merge_commands();
+        let pending = std::mem::take(&mut *world.resources.get_mut::<Actions>());
+        let flushing = std::mem::replace(&mut world.flushing, true);
+        let capturing = std::mem::replace(&mut world.recorder.capturing, true);
+        world.increment_change_tick();
+
+        for wave in &self.waves {
+            world.replay_wave(wave, mapper);
+        }
+
+        world.recorder.capturing = capturing;
+        world.flushing = flushing;
+        *world.resources.get_mut::<Actions>() = pending;
+        world.resources.get_mut::<RemovedComponents>().clear();
+    }
+}
+
+#[derive(Default)]
+struct RecorderState {
+    waves: Vec<Vec<RecordedEntry>>,
+    created: Option<Vec<Entity>>,
+}
+
+impl RecorderState {
+    /// Adds `entry` to the current wave and returns its index there.
+    fn push(&mut self, entry: RecordedEntry) -> usize {
+        if self.waves.is_empty() {
+            self.waves.push(Vec::new());
+        }
+
+        let wave = self.waves.last_mut().unwrap();
+        wave.push(entry);
+        wave.len() - 1
+    }
+}
+
+/// Clone fns of the recordable action types and, while recording, the
+/// recording in progress.
+pub struct ActionRecorder {
+    recording: bool,
+    capturing: bool,
+    cloners: HashMap<TypeId, Arc<CloneAction>>,
+    state: Mutex<RecorderState>,
+}
+
+impl ActionRecorder {
+    /// Knows the builtin actions whose payloads can be cloned.
+    pub fn new() -> Self {
+        let mut recorder = Self {
+            recording: false,
+            capturing: false,
+            cloners: HashMap::new(),
+            state: Mutex::default(),
+        };
+
+        recorder.register::<CreateEntity>(CreateEntity::record);
+        recorder.register::<SpawnBatch>(|action, _| Some(action.clone()));
+        recorder.register::<DeleteEntity>(|action, _| Some(action.clone()));
+        recorder.register::<EditEntity>(EditEntity::record);
+        recorder.register::<SetParent>(|action, _| Some(action.clone()));
+        recorder.register::<AddChildren>(|action, _| Some(action.clone()));
+        recorder.register::<RemoveChildren>(|action, _| Some(action.clone()));
+        recorder.register::<SpawnPrefab>(SpawnPrefab::record);
+        recorder
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    pub fn is_recordable<A: Action>(&self) -> bool {
+        self.cloners.contains_key(&TypeId::of::<A>())
+    }
+
+    pub(crate) fn register<A: Action + Send>(&mut self, clone: RecordFn<A>) {
+        let cloner = move |action: &dyn Any, world: &mut World| {
+            let action = clone(action.downcast_ref::<A>()?, world)?;
+            Some(Box::new(Recorded { action, clone }) as Box<dyn RecordedAction>)
+        };
+
+        self.cloners.insert(TypeId::of::<A>(), Arc::new(cloner));
+    }
+
+    /// Registers the component actions of `C` that need no clone of it.
+    pub(crate) fn register_component<C: Component>(&mut self) {
+        self.register::<RemoveComponent<C>>(|action, _| Some(action.clone()));
+    }
+
+    /// Registers the component actions of `C` that carry a value of it.
+    pub(crate) fn register_cloneable<C: Component + Clone>(&mut self) {
+        self.register::<AddComponent<C>>(|action, _| action.try_clone());
+        self.register::<SetComponent<C>>(|action, _| action.try_clone());
+    }
+
+    /// Entities created since the recorded action started executing.
+    pub(crate) fn created(&mut self, entities: &[Entity]) {
+        let state = self.state.get_mut().unwrap();
+        if let Some(created) = state.created.as_mut() {
+            created.extend_from_slice(entities);
+        }
+    }
+
+    pub(crate) fn is_capturing(&self) -> bool {
+        self.capturing
+    }
+
+    /// Starts a new wave unless the current one is still empty.
+    pub(crate) fn begin_wave(&mut self) {
+        if !self.recording {
+            return;
+        }
+
+        let state = self.state.get_mut().unwrap();
+        if state.waves.last().is_none_or(|wave| !wave.is_empty()) {
+            state.waves.push(Vec::new());
+        }
+    }
+}
+
+impl Default for ActionRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl World {
+    /// Starts capturing every executed action, wave by wave, until
+    /// `stop_recording`. Restarting drops what was captured so far.
+    pub fn start_recording(&mut self) {
+        let state = self.recorder.state.get_mut().unwrap();
+        state.waves.clear();
+        state.created = None;
+        self.recorder.recording = true;
+        self.recorder.capturing = true;
+    }
+
+    pub fn stop_recording(&mut self) -> ActionRecording {
+        self.recorder.recording = false;
+        self.recorder.capturing = false;
+
+        let state = self.recorder.state.get_mut().unwrap();
+        let mut waves = std::mem::take(&mut state.waves);
+        waves.retain(|wave| !wave.is_empty());
+        ActionRecording { waves }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.recording
+    }
+
+    /// Lets recordings capture actions of type `A` instead of leaving a gap.
+    pub fn register_recordable<A: Action + Clone + Send>(&mut self) {
+        self.recorder
+            .register::<A>(|action, _| Some(action.clone()));
+    }
+
+    pub fn recorder(&self) -> &ActionRecorder {
+        &self.recorder
+    }
+
+    /// Captures `action` before it executes. Returns the index to hand to
+    /// `record_end` once it has, or `None` if nothing is recording it.
+    pub(crate) fn record_begin<A: Action>(&mut self, action: &A) -> Option<usize> {
+        let entry = self.capture(action)?;
+        let mut state = self.recorder.state.lock().unwrap();
+        let recorded = matches!(entry, RecordedEntry::Action { .. });
+        let index = state.push(entry);
+        if recorded {
+            state.created = Some(Vec::new());
+        }
+
+        recorded.then_some(index)
+    }
+
+    /// Clones `action` for the recording in progress without adding it, for
+    /// actions that execute away from the world. `None` while not
+    /// recording.
+    pub(crate) fn capture<A: Action>(&mut self, action: &A) -> Option<RecordedEntry> {
+        if !self.recorder.recording {
+            return None;
+        }
+
+        let cloner = self.recorder.cloners.get(&TypeId::of::<A>()).cloned();
+        let entry = match cloner.and_then(|clone| clone(action, self)) {
+            Some(action) => RecordedEntry::Action {
+                action,
+                created: Vec::new(),
+            },
+            None => RecordedEntry::Gap(std::any::type_name::<A>()),
+        };
+
+        Some(entry)
+    }
+
+    /// Adds entries made by `capture` to the current wave, in order.
+    pub(crate) fn record_captured(&self, entries: impl IntoIterator<Item = RecordedEntry>) {
+        let mut state = self.recorder.state.lock().unwrap();
+        for entry in entries {
+            state.push(entry);
+        }
+    }
+
+    pub(crate) fn record_end(&mut self, index: usize) {
+        let mut state = self.recorder.state.lock().unwrap();
+        let entities = state.created.take().unwrap_or_default();
+        if let Some(RecordedEntry::Action { created, .. }) =
+            state.waves.last_mut().and_then(|wave| wave.get_mut(index))
+        {
+            *created = entities;
+        }
+    }
+
+    fn replay_wave(&mut self, wave: &[RecordedEntry], mapper: &mut EntityMapper) {
+        let mut outputs = ActionOutputs::new();
+        for entry in wave {
+            match entry {
+                RecordedEntry::Action { action, created } => {
+                    self.recorder.state.get_mut().unwrap().created = Some(Vec::new());
+                    action.replay(self, mapper, &mut outputs);
+
+                    let state = self.recorder.state.get_mut().unwrap();
+                    let replayed = state.created.take().unwrap_or_default();
+                    for (from, to) in created.iter().zip(replayed) {
+                        mapper.insert(*from, to);
+                    }
+                }
+                RecordedEntry::Gap(name) => {
+                    let message = format!("skipped unrecorded {}", name);
+                    self.trace(TraceLevel::Warn, "replay", message);
+                }
+            }
+        }
+
+        let action_outputs = self.resources.get_mut::<ActionOutputs>().take();
+        outputs.merge(action_outputs);
+        self.emit_matches(&mut outputs);
+        self.transitions.drain_into(&mut outputs);
+
+        let mut observers = std::mem::take(&mut *self.resources.get_mut::<Observables>());
+        observers.execute(outputs, self);
+        self.resources.get_mut::<Observables>().swap(observers);
+
+        self.merge_commands();
+        *self.resources.get_mut::<Actions>() = Actions::new();
+    }
+}
@@ -0,0 +1,407 @@
+use super::{borrow::ComponentRef, error::ReflectError, World};
+use crate::core::{Component, ComponentId, Entity};
+
+/// A value that lists its fields by name, so tools can show and edit it
+/// without knowing its type. Implement it by hand or with `impl_reflect!`.
+pub trait Reflect: 'static {
+    fn fields(&self) -> Vec<(&'static str, FieldRef<'_>)>;
+    fn field_mut(&mut self, name: &str) -> Option<FieldMut<'_>>;
+}
+
+/// Implements `Reflect` for a struct from its field list:
+///
+/// ```ignore
+/// impl_reflect!(Transform { x: f32, y: f32 });
+/// ```
+///
+/// Every field type must implement `ReflectField`.
+#[macro_export]
+macro_rules! impl_reflect {
+    ($ty:ident { $($field:ident: $fty:ty),* $(,)? }) => {
+        impl $crate::world::reflect::Reflect for $ty {
+            fn fields(&self) -> Vec<(&'static str, $crate::world::reflect::FieldRef<'_>)> {
+                vec![$((
+                    stringify!($field),
+                    <$fty as $crate::world::reflect::ReflectField>::as_field(&self.$field),
+                )),*]
+            }
+
+            fn field_mut(&mut self, name: &str) -> Option<$crate::world::reflect::FieldMut<'_>> {
+                match name {
+                    $(stringify!($field) => Some(
+                        <$fty as $crate::world::reflect::ReflectField>::as_field_mut(
+                            &mut self.$field,
+                        ),
+                    ),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+pub enum FieldRef<'a> {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(&'a str),
+    Entity(Entity),
+    Nested(&'a dyn Reflect),
+}
+
+pub enum FieldMut<'a> {
+    Bool(&'a mut bool),
+    Int(&'a mut dyn IntField),
+    Float(&'a mut dyn FloatField),
+    String(&'a mut String),
+    Entity(&'a mut Entity),
+    Nested(&'a mut dyn Reflect),
+}
+
+impl FieldMut<'_> {
+    /// Writes `value` if it is of the field's kind and, for integers, fits
+    /// the field's type.
+    pub fn set(self, value: FieldValue) -> bool {
+        match (self, value) {
+            (FieldMut::Bool(field), FieldValue::Bool(value)) => *field = value,
+            (FieldMut::Int(field), FieldValue::Int(value)) => return field.set_int(value),
+            (FieldMut::Float(field), FieldValue::Float(value)) => field.set_float(value),
+            (FieldMut::String(field), FieldValue::String(value)) => *field = value,
+            (FieldMut::Entity(field), FieldValue::Entity(value)) => *field = value,
+            _ => return false,
+        }
+
+        true
+    }
+}
+
+/// An owned value to write into a field with `FieldMut::set`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Entity(Entity),
+}
+
+/// An integer field of any width.
+pub trait IntField {
+    fn int(&self) -> i64;
+
+    /// False, leaving the field as it was, if `value` doesn't fit.
+    fn set_int(&mut self, value: i64) -> bool;
+}
+
+/// A floating point field of any width.
+pub trait FloatField {
+    fn float(&self) -> f64;
+    fn set_float(&mut self, value: f64);
+}
+
+/// A field type `impl_reflect!` can expose.
+pub trait ReflectField {
+    fn as_field(&self) -> FieldRef<'_>;
+    fn as_field_mut(&mut self) -> FieldMut<'_>;
+}
+
+macro_rules! impl_int_field {
+    ($($ty:ty),+) => {$(
+        impl IntField for $ty {
+            fn int(&self) -> i64 {
+                *self as i64
+            }
+
+            fn set_int(&mut self, value: i64) -> bool {
+                match <$ty>::try_from(value) {
+                    Ok(value) => {
+                        *self = value;
+                        true
+                    }
+                    Err(_) => false,
+                }
+            }
+        }
+
+        impl ReflectField for $ty {
+            fn as_field(&self) -> FieldRef<'_> {
+                FieldRef::Int(self.int())
+            }
+
+            fn as_field_mut(&mut self) -> FieldMut<'_> {
+                FieldMut::Int(self)
+            }
+        }
+    )+};
+}
+
+impl_int_field!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+macro_rules! impl_float_field {
+    ($($ty:ty),+) => {$(
+        impl FloatField for $ty {
+            fn float(&self) -> f64 {
+                *self as f64
+            }
+
+            fn set_float(&mut self, value: f64) {
+                *self = value as $ty;
+            }
+        }
+
+        impl ReflectField for $ty {
+            fn as_field(&self) -> FieldRef<'_> {
+                FieldRef::Float(self.float())
+            }
+
+            fn as_field_mut(&mut self) -> FieldMut<'_> {
+                FieldMut::Float(self)
+            }
+        }
+    )+};
+}
+
+impl_float_field!(f32, f64);
+
+impl ReflectField for bool {
+    fn as_field(&self) -> FieldRef<'_> {
+        FieldRef::Bool(*self)
+    }
+
+    fn as_field_mut(&mut self) -> FieldMut<'_> {
+        FieldMut::Bool(self)
+    }
+}
+
+impl ReflectField for String {
+    fn as_field(&self) -> FieldRef<'_> {
+        FieldRef::String(self)
+    }
+
+    fn as_field_mut(&mut self) -> FieldMut<'_> {
+        FieldMut::String(self)
+    }
+}
+
+impl ReflectField for Entity {
+    fn as_field(&self) -> FieldRef<'_> {
+        FieldRef::Entity(*self)
+    }
+
+    fn as_field_mut(&mut self) -> FieldMut<'_> {
+        FieldMut::Entity(self)
+    }
+}
+
+impl<T: Reflect> ReflectField for T {
+    fn as_field(&self) -> FieldRef<'_> {
+        FieldRef::Nested(self)
+    }
+
+    fn as_field_mut(&mut self) -> FieldMut<'_> {
+        FieldMut::Nested(self)
+    }
+}
+
+/// Writes `value` into the field at the dotted `path` of `reflect`. `None`
+/// if there is no such field, `Some(false)` if it can't hold the value.
+pub(crate) fn set_path(reflect: &mut dyn Reflect, path: &str, value: FieldValue) -> Option<bool> {
+    let mut names = path.split('.');
+    let mut field = reflect.field_mut(names.next()?)?;
+    for name in names {
+        field = match field {
+            FieldMut::Nested(nested) => nested.field_mut(name)?,
+            _ => return None,
+        };
+    }
+
+    Some(field.set(value))
+}
+
+/// Every leaf field of `reflect` with its dotted path, in field order.
+pub(crate) fn field_values(reflect: &dyn Reflect) -> Vec<(String, FieldValue)> {
+    let mut values = Vec::new();
+    collect_fields(reflect, "", &mut values);
+    values
+}
+
+fn collect_fields(reflect: &dyn Reflect, prefix: &str, values: &mut Vec<(String, FieldValue)>) {
+    for (name, field) in reflect.fields() {
+        let path = match prefix {
+            "" => name.to_string(),
+            prefix => format!("{}.{}", prefix, name),
+        };
+
+        let value = match field {
+            FieldRef::Bool(value) => FieldValue::Bool(value),
+            FieldRef::Int(value) => FieldValue::Int(value),
+            FieldRef::Float(value) => FieldValue::Float(value),
+            FieldRef::String(value) => FieldValue::String(value.to_string()),
+            FieldRef::Entity(value) => FieldValue::Entity(value),
+            FieldRef::Nested(nested) => {
+                collect_fields(nested, &path, values);
+                continue;
+            }
+        };
+
+        values.push((path, value));
+    }
+}
+
+/// A reflected component of one entity, holding a shared borrow of it.
+pub trait ReflectedView {
+    fn component(&self) -> ComponentId;
+    fn name(&self) -> &'static str;
+    fn value(&self) -> &dyn Reflect;
+
+    fn fields(&self) -> Vec<(&'static str, FieldRef<'_>)> {
+        self.value().fields()
+    }
+}
+
+struct ComponentView<'a, C: Component + Reflect> {
+    id: ComponentId,
+    value: ComponentRef<'a, C>,
+}
+
+impl<C: Component + Reflect> ReflectedView for ComponentView<'_, C> {
+    fn component(&self) -> ComponentId {
+        self.id
+    }
+
+    fn name(&self) -> &'static str {
+        std::any::type_name::<C>()
+    }
+
+    fn value(&self) -> &dyn Reflect {
+        &*self.value
+    }
+}
+
+type ViewFn = for<'w> fn(&'w World, Entity) -> Option<Box<dyn ReflectedView + 'w>>;
+type EditFn = fn(&World, Entity, &mut dyn FnMut(&mut dyn Reflect)) -> bool;
+
+/// Component meta extension added by `World::register_reflect`.
+pub struct ReflectMeta {
+    view: ViewFn,
+    edit: EditFn,
+}
+
+impl ReflectMeta {
+    pub fn new<C: Component + Reflect>() -> Self {
+        Self {
+            view: view::<C>,
+            edit: edit::<C>,
+        }
+    }
+}
+
+fn view<C: Component + Reflect>(
+    world: &World,
+    entity: Entity,
+) -> Option<Box<dyn ReflectedView + '_>> {
+    let value = world.component::<C>(entity)?;
+    let id = world.component_id::<C>();
+    Some(Box::new(ComponentView { id, value }))
+}
+
+fn edit<C: Component + Reflect>(
+    world: &World,
+    entity: Entity,
+    edit: &mut dyn FnMut(&mut dyn Reflect),
+) -> bool {
+    match world.component_mut::<C>(entity) {
+        Some(mut value) => {
+            edit(&mut *value);
+            true
+        }
+        None => false,
+    }
+}
+
+impl World {
+    /// Registers `C` and makes it reachable through `reflect_component` and
+    /// `edit_reflected`.
+    pub fn register_reflect<C: Component + Reflect>(&mut self) {
+        self.register::<C>();
+        let id = self.components.id::<C>();
+        self.components.extend_meta(id, ReflectMeta::new::<C>());
+    }
+
+    /// A read-only view of `entity`'s `component`, if it is reflected and
+    /// the entity has it. The view borrows the component until dropped.
+    pub fn reflect_component(
+        &self,
+        entity: Entity,
+        component: ComponentId,
+    ) -> Option<Box<dyn ReflectedView + '_>> {
+        let meta = self.reflect_meta(component).ok()?;
+        (meta.view)(self, entity)
+    }
+
+    /// Runs `edit` on `entity`'s `component`. The component is marked
+    /// changed for `Changed` queries and its value index is updated.
+    pub fn edit_reflected<R>(
+        &mut self,
+        entity: Entity,
+        component: ComponentId,
+        edit: impl FnOnce(&mut dyn Reflect) -> R,
+    ) -> Result<R, ReflectError> {
+        let edit_fn = self.reflect_meta(component)?.edit;
+        let mut edit = Some(edit);
+        let mut result = None;
+        let found = edit_fn(self, entity, &mut |value| {
+            if let Some(edit) = edit.take() {
+                result = Some(edit(value));
+            }
+        });
+
+        match result {
+            Some(result) if found => {
+                self.reindex(entity, &[component]);
+                Ok(result)
+            }
+            _ => Err(ReflectError::MissingComponent {
+                entity,
+                component: self.components.meta(component).name(),
+            }),
+        }
+    }
+
+    /// Writes `value` into the field at `path` of `entity`'s `component`.
+    /// Nested fields are reached with dots, e.g. `"offset.x"`.
+    pub fn set_field(
+        &mut self,
+        entity: Entity,
+        component: ComponentId,
+        path: &str,
+        value: FieldValue,
+    ) -> Result<(), ReflectError> {
+        let written =
+            self.edit_reflected(entity, component, |reflect| set_path(reflect, path, value))?;
+
+        let name = self.components.meta(component).name();
+        match written {
+            Some(true) => Ok(()),
+            Some(false) => Err(ReflectError::InvalidValue {
+                component: name,
+                path: path.to_string(),
+            }),
+            None => Err(ReflectError::UnknownField {
+                component: name,
+                path: path.to_string(),
+            }),
+        }
+    }
+
+    fn reflect_meta(&self, component: ComponentId) -> Result<&ReflectMeta, ReflectError> {
+        if component.id() >= self.components.len() {
+            return Err(ReflectError::NotReflected(component));
+        }
+
+        self.components
+            .meta(component)
+            .extension::<ReflectMeta>()
+            .ok_or(ReflectError::NotReflected(component))
+    }
+}
@@ -0,0 +1,228 @@
+use super::{
+    query::{FilterQuery, Query, QueryResultCache, ReadOnlyBaseQuery},
+    World,
+};
+use crate::{
+    core::Entity,
+    schedule::SchedulePhase,
+    system::{ReadOnlySystemArg, SystemArg},
+    world::meta::AccessMeta,
+};
+use std::{any::TypeId, marker::PhantomData};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RegisteredQueryId(usize);
+
+impl RegisteredQueryId {
+    pub fn id(&self) -> usize {
+        self.0
+    }
+}
+
+/// When the world refreshes a registered query. Between refreshes every
+/// reader sees the same entities.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RefreshPoint {
+    /// After the actions of every `run` are flushed.
+    #[default]
+    Flush,
+    /// Before the systems of a phase run; see `RefreshPoint::phase`.
+    Phase(TypeId),
+}
+
+impl RefreshPoint {
+    pub fn phase<P: SchedulePhase>() -> Self {
+        Self::Phase(TypeId::of::<P>())
+    }
+}
+
+struct RegisteredQuery {
+    name: String,
+    key: &'static str,
+    point: RefreshPoint,
+    refresh: fn(&World, &mut QueryResultCache),
+    cache: QueryResultCache,
+}
+
+fn refresh<Q: ReadOnlyBaseQuery, F: FilterQuery>(world: &World, cache: &mut QueryResultCache) {
+    Query::<Q, F>::new(world).cached(cache);
+}
+
+/// Entity lists kept by the world for queries registered with
+/// `World::register_query`.
+#[derive(Default)]
+pub struct RegisteredQueries {
+    queries: Vec<RegisteredQuery>,
+}
+
+impl RegisteredQueries {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, name: &str) -> Option<RegisteredQueryId> {
+        self.queries
+            .iter()
+            .position(|query| query.name == name)
+            .map(RegisteredQueryId)
+    }
+
+    pub fn name(&self, id: RegisteredQueryId) -> Option<&str> {
+        self.queries.get(id.0).map(|query| query.name.as_str())
+    }
+
+    /// The entities matched at the last refresh.
+    pub fn entities(&self, id: RegisteredQueryId) -> &[Entity] {
+        self.queries
+            .get(id.0)
+            .map_or(&[], |query| query.cache.entities())
+    }
+
+    /// Refreshes that had to collect the entities again.
+    pub fn rescans(&self, id: RegisteredQueryId) -> usize {
+        self.queries
+            .get(id.0)
+            .map_or(0, |query| query.cache.misses())
+    }
+
+    /// Refreshes skipped because no matched table changed.
+    pub fn skips(&self, id: RegisteredQueryId) -> usize {
+        self.queries.get(id.0).map_or(0, |query| query.cache.hits())
+    }
+
+    pub fn len(&self) -> usize {
+        self.queries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queries.is_empty()
+    }
+
+    fn find<Q: ReadOnlyBaseQuery, F: FilterQuery>(&self) -> Option<RegisteredQueryId> {
+        let key = std::any::type_name::<(Q, F)>();
+        self.queries
+            .iter()
+            .position(|query| query.key == key)
+            .map(RegisteredQueryId)
+    }
+}
+
+impl World {
+    /// Registers `Q` filtered by `F` under `name`. The world keeps the list
+    /// of matching entities, refreshed at each flush unless
+    /// `set_query_refresh` picks another point, and systems read it through
+    /// `Registered<Q, F>`. Registering the same query or name again returns
+    /// the first id.
+    pub fn register_query<Q: ReadOnlyBaseQuery, F: FilterQuery>(
+        &mut self,
+        name: impl Into<String>,
+    ) -> RegisteredQueryId {
+        let name = name.into();
+        if let Some(id) = self
+            .registered
+            .get(&name)
+            .or_else(|| self.registered.find::<Q, F>())
+        {
+            return id;
+        }
+
+        Q::register(self);
+        F::register(self);
+
+        let mut query = RegisteredQuery {
+            name,
+            key: std::any::type_name::<(Q, F)>(),
+            point: RefreshPoint::default(),
+            refresh: refresh::<Q, F>,
+            cache: QueryResultCache::new(),
+        };
+        (query.refresh)(self, &mut query.cache);
+
+        self.registered.queries.push(query);
+        RegisteredQueryId(self.registered.queries.len() - 1)
+    }
+
+    pub fn set_query_refresh(&mut self, id: RegisteredQueryId, point: RefreshPoint) {
+        if let Some(query) = self.registered.queries.get_mut(id.0) {
+            query.point = point;
+        }
+    }
+
+    pub fn registered_queries(&self) -> &RegisteredQueries {
+        &self.registered
+    }
+
+    pub(crate) fn refresh_queries(&mut self, point: RefreshPoint) {
+        if self.registered.is_empty() {
+            return;
+        }
+
+        let mut registered = std::mem::take(&mut self.registered);
+        for query in registered.queries.iter_mut() {
+            if query.point == point {
+                (query.refresh)(self, &mut query.cache);
+            }
+        }
+        self.registered = registered;
+    }
+}
+
+/// The entities of a query registered with `World::register_query`, as of
+/// its last refresh. Using it in a system registers the query under its
+/// type name.
+pub struct Registered<'a, Q: ReadOnlyBaseQuery, F: FilterQuery = ()> {
+    world: &'a World,
+    entities: &'a [Entity],
+    _marker: PhantomData<(Q, F)>,
+}
+
+impl<'a, Q: ReadOnlyBaseQuery, F: FilterQuery> Registered<'a, Q, F> {
+    pub fn entities(&self) -> &'a [Entity] {
+        self.entities
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Fetches `Q` for each listed entity. Entities deleted or changed since
+    /// the refresh so that they lack a component are skipped.
+    pub fn iter(&self) -> impl Iterator<Item = Q::Item<'a>> + '_ {
+        let world = self.world;
+        self.entities
+            .iter()
+            .filter(move |entity| world.entities().contains(**entity))
+            .filter_map(move |entity| Q::try_fetch(world, *entity))
+    }
+}
+
+impl<Q: ReadOnlyBaseQuery, F: FilterQuery> SystemArg for Registered<'_, Q, F> {
+    type Item<'a> = Registered<'a, Q, F>;
+
+    fn get<'a>(world: &'a World) -> Self::Item<'a> {
+        let registered = world.registered_queries();
+        let entities = registered
+            .find::<Q, F>()
+            .map_or(&[][..], |id| registered.entities(id));
+
+        Registered {
+            world,
+            entities,
+            _marker: PhantomData,
+        }
+    }
+
+    fn metas() -> Vec<AccessMeta> {
+        Q::metas()
+    }
+
+    fn register(world: &mut World) {
+        world.register_query::<Q, F>(std::any::type_name::<(Q, F)>());
+    }
+}
+
+unsafe impl<Q: ReadOnlyBaseQuery, F: FilterQuery> ReadOnlySystemArg for Registered<'_, Q, F> {}
@@ -0,0 +1,72 @@
+use super::World;
+use crate::core::{Component, ComponentDescriptor, ComponentError, ComponentId, StorageKind};
+use std::marker::PhantomData;
+
+/// Collects registration options for `C` and applies them together in
+/// `finish`, so the component's meta is never partially configured.
+pub struct ComponentBuilder<'a, C: Component> {
+    world: &'a mut World,
+    descriptor: ComponentDescriptor,
+    requires: Vec<fn(&mut World) -> ComponentId>,
+    recordable: Option<fn(&mut World)>,
+    _marker: PhantomData<C>,
+}
+
+impl<'a, C: Component> ComponentBuilder<'a, C> {
+    pub(super) fn new(world: &'a mut World) -> Self {
+        Self {
+            world,
+            descriptor: ComponentDescriptor::new(),
+            requires: Vec::new(),
+            recordable: None,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn storage(mut self, storage: StorageKind) -> Self {
+        self.descriptor = self.descriptor.storage(storage);
+        self
+    }
+
+    pub fn cloneable(mut self) -> Self
+    where
+        C: Clone,
+    {
+        self.descriptor = self.descriptor.cloneable::<C>();
+        self.recordable = Some(|world| world.recorder.register_cloneable::<C>());
+        self
+    }
+
+    /// Opts out of deferred drops: values are dropped by `clear_entities`
+    /// itself, for components whose `Drop` must run promptly.
+    pub fn drop_immediately(mut self) -> Self {
+        self.descriptor = self.descriptor.drop_immediately();
+        self
+    }
+
+    pub fn requires<R: Component>(mut self) -> Self {
+        self.requires.push(|world| {
+            world.register::<R>();
+            world.component_id::<R>()
+        });
+        self
+    }
+
+    /// Registers `C` if needed and merges the options into its meta. Fails
+    /// without changing the meta if they contradict an earlier registration.
+    pub fn finish(self) -> Result<ComponentId, ComponentError> {
+        let mut descriptor = self.descriptor;
+        for require in self.requires {
+            descriptor = descriptor.requires(require(self.world));
+        }
+
+        self.world.register::<C>();
+        let id = self.world.component_id::<C>();
+        self.world.components.describe(id, descriptor)?;
+        if let Some(recordable) = self.recordable {
+            recordable(self.world);
+        }
+
+        Ok(id)
+    }
+}
@@ -0,0 +1,169 @@
+use super::{
+    meta::{Access, AccessMeta, AccessType},
+    resource::Resource,
+    World,
+};
+use crate::{
+    core::{Component, ComponentId, Entity},
+    storage::{blob::Blob, sparse::SparseMap},
+    system::{ReadOnlySystemArg, SystemArg},
+};
+use std::{collections::HashMap, marker::PhantomData};
+
+type CaptureFn = fn(&World, Entity) -> Option<Blob>;
+
+/// Copies of removed values for one component, kept until the end of the
+/// current flush.
+pub struct RemovedValues {
+    capture: CaptureFn,
+    index: HashMap<Entity, usize>,
+    values: Blob,
+}
+
+impl RemovedValues {
+    pub fn new<C: Component + Clone>() -> Self {
+        Self {
+            capture: capture::<C>,
+            index: HashMap::new(),
+            values: Blob::new::<C>(),
+        }
+    }
+
+    pub fn get<C: Component>(&self, entity: Entity) -> Option<&C> {
+        let index = *self.index.get(&entity)?;
+        self.values.get::<C>(index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    fn insert(&mut self, entity: Entity, mut value: Blob) {
+        match self.index.get(&entity) {
+            Some(index) => self.values.replace_from(*index, &mut value),
+            None => {
+                self.values.append(&mut value);
+                self.index.insert(entity, self.values.len() - 1);
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.values.clear();
+        self.index.clear();
+    }
+}
+
+fn capture<C: Component + Clone>(world: &World, entity: Entity) -> Option<Blob> {
+    let value = world.component::<C>(entity)?.clone();
+    let mut blob = Blob::new::<C>();
+    blob.push(value);
+    Some(blob)
+}
+
+/// Removed component values for every component opted in with
+/// `World::buffer_removals`.
+pub struct RemovedComponents {
+    buffers: SparseMap<ComponentId, RemovedValues>,
+}
+
+impl RemovedComponents {
+    pub fn new() -> Self {
+        Self {
+            buffers: SparseMap::new(),
+        }
+    }
+
+    pub fn enable<C: Component + Clone>(&mut self, id: ComponentId) {
+        if !self.buffers.contains(&id) {
+            self.buffers.insert(id, RemovedValues::new::<C>());
+        }
+    }
+
+    pub fn is_buffered(&self, id: ComponentId) -> bool {
+        self.buffers.contains(&id)
+    }
+
+    pub fn values(&self, id: ComponentId) -> Option<&RemovedValues> {
+        self.buffers.get(&id)
+    }
+
+    /// Clones the current value of `id` on `entity` if the component is
+    /// buffered and the entity has it.
+    pub fn capture(&self, world: &World, entity: Entity, id: ComponentId) -> Option<Blob> {
+        let buffer = self.buffers.get(&id)?;
+        (buffer.capture)(world, entity)
+    }
+
+    pub fn insert(&mut self, id: ComponentId, entity: Entity, value: Blob) {
+        if let Some(buffer) = self.buffers.get_mut(&id) {
+            buffer.insert(entity, value);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffers.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        for buffer in self.buffers.values_mut() {
+            buffer.clear();
+        }
+    }
+}
+
+impl Default for RemovedComponents {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Resource for RemovedComponents {}
+
+/// Looks up values of `C` removed during the current flush. Only filled for
+/// components opted in with `World::buffer_removals`.
+pub struct Removed<'a, C: Component> {
+    values: Option<&'a RemovedValues>,
+    _marker: PhantomData<C>,
+}
+
+impl<'a, C: Component> Removed<'a, C> {
+    pub fn get(&self, entity: Entity) -> Option<&'a C> {
+        self.values?.get::<C>(entity)
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.map(|values| values.len()).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<C: Component> SystemArg for Removed<'_, C> {
+    type Item<'a> = Removed<'a, C>;
+
+    fn get<'a>(world: &'a World) -> Self::Item<'a> {
+        let values = world
+            .components()
+            .get_id::<C>()
+            .and_then(|id| world.hold_resource::<RemovedComponents>()?.values(id));
+
+        Removed {
+            values,
+            _marker: PhantomData,
+        }
+    }
+
+    fn metas() -> Vec<AccessMeta> {
+        let ty = AccessType::resource::<RemovedComponents>();
+        vec![AccessMeta::new(ty, Access::Read)]
+    }
+}
+
+unsafe impl<C: Component> ReadOnlySystemArg for Removed<'_, C> {}
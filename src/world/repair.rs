@@ -0,0 +1,261 @@
+use super::{consistency::ConsistencyError, trace::TraceLevel, World};
+use crate::{
+    archetype::ArchetypeId,
+    core::{Entity, OrphanPolicy},
+    storage::table::TableId,
+    system::observer::{builtin::DeleteEntity, ActionOutputs},
+    testing::Corruption,
+};
+use std::fmt::Display;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RepairPolicy {
+    #[default]
+    Apply,
+    /// Applies at most this many repairs; the rest are handed back in
+    /// `RepairOutcome::deferred` for a later call.
+    Budget(usize),
+    /// Works out the repairs without changing anything.
+    DryRun,
+}
+
+/// One fix made (or planned) by `World::repair`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repair {
+    pub error: ConsistencyError,
+    /// State found before the fix and what the fix does about it.
+    pub detail: String,
+}
+
+impl Display for Repair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.error, self.detail)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepairOutcome {
+    /// Repairs applied, or under `DryRun` the ones that would be.
+    pub repairs: Vec<Repair>,
+    /// Errors left over once the budget ran out.
+    pub deferred: Vec<ConsistencyError>,
+    /// Errors there is no safe fix for.
+    pub unrepaired: Vec<ConsistencyError>,
+}
+
+/// What `World::repair` does about one error.
+enum Fix {
+    Delete(Entity),
+    ResyncMask(Entity),
+    DropRow(Entity, TableId),
+    Dedup(ArchetypeId),
+    PruneChild(Entity, Entity),
+}
+
+impl World {
+    /// Applies conservative fixes for errors found by `check_consistency`,
+    /// so a long-running world can limp on after corruption is detected.
+    /// It doesn't remove the cause.
+    ///
+    /// - Entities without an archetype or table row are deleted like a
+    ///   `DeleteEntity` would, and their children moved to the grandparent.
+    ///   `DeleteEntity` observers get them at the next flush.
+    /// - Masks that drifted from the archetype are copied from it again.
+    /// - Orphan table rows are removed and their values dropped.
+    /// - Repeated archetype components are dropped and the table rebuilt.
+    /// - Dead children are pruned from child lists.
+    ///
+    /// Other errors are left in `unrepaired`. Errors already resolved by an
+    /// earlier fix are skipped. Every repair is traced as a warning.
+    pub fn repair(&mut self, report: &[ConsistencyError], policy: RepairPolicy) -> RepairOutcome {
+        self.borrows.assert_unborrowed();
+        let budget = match policy {
+            RepairPolicy::Budget(budget) => budget,
+            _ => usize::MAX,
+        };
+
+        let mut outcome = RepairOutcome::default();
+        for error in report {
+            let Some(fix) = self.plan_fix(error) else {
+                outcome.unrepaired.push(error.clone());
+                continue;
+            };
+
+            if outcome.repairs.len() >= budget {
+                outcome.deferred.push(error.clone());
+                continue;
+            }
+
+            let Some(detail) = self.describe_fix(&fix) else {
+                continue;
+            };
+
+            if policy != RepairPolicy::DryRun {
+                self.apply_fix(fix);
+                self.trace(TraceLevel::Warn, "repair", format!("{}: {}", error, detail));
+            }
+
+            outcome.repairs.push(Repair {
+                error: error.clone(),
+                detail,
+            });
+        }
+
+        outcome
+    }
+
+    fn plan_fix(&self, error: &ConsistencyError) -> Option<Fix> {
+        match *error {
+            ConsistencyError::MissingArchetype { entity }
+            | ConsistencyError::MissingRow { entity, .. } => Some(Fix::Delete(entity)),
+            ConsistencyError::MaskMismatch { entity, .. } => Some(Fix::ResyncMask(entity)),
+            ConsistencyError::OrphanRow { entity, table } => Some(Fix::DropRow(entity, table)),
+            ConsistencyError::DuplicateComponent { archetype, .. } => Some(Fix::Dedup(archetype)),
+            ConsistencyError::DeadChild { parent, child } => Some(Fix::PruneChild(parent, child)),
+            _ => None,
+        }
+    }
+
+    /// Before and after of `fix`, or `None` if there is nothing left to do.
+    fn describe_fix(&self, fix: &Fix) -> Option<String> {
+        match *fix {
+            Fix::Delete(entity) => {
+                if !self.entities.contains(entity) {
+                    return None;
+                }
+
+                let archetype = match self.archetypes.archetype_id(entity) {
+                    Some(id) => format!("archetype {}", id.id()),
+                    None => "no archetype".to_string(),
+                };
+                let children = self.entities.children(entity, false).len();
+                Some(format!(
+                    "{:?} mapped to {} with {} children -> deleted, children reparented",
+                    entity, archetype, children
+                ))
+            }
+            Fix::ResyncMask(entity) => {
+                let archetype = self.archetypes.entity_archetype(entity)?;
+                let mask = self.archetypes.mask(entity);
+                if mask == Some(archetype.mask()) {
+                    return None;
+                }
+
+                Some(format!(
+                    "mask of {} components -> mask of archetype {} with {}",
+                    mask.map(|mask| mask.len()).unwrap_or_default(),
+                    archetype.id().id(),
+                    archetype.mask().len()
+                ))
+            }
+            Fix::DropRow(entity, table) => {
+                let mapped = self
+                    .archetypes
+                    .archetype_id(entity)
+                    .is_some_and(|id| TableId::from(id.id()) == table);
+                if self.entities.contains(entity) && mapped {
+                    return None;
+                }
+
+                let table = self.tables.get(table)?;
+                if !table.rows().contains(&entity) {
+                    return None;
+                }
+
+                Some(format!(
+                    "table {} with {} rows -> row of {:?} dropped with {} values",
+                    table.id().id(),
+                    table.len(),
+                    entity,
+                    table.columns().count()
+                ))
+            }
+            Fix::Dedup(id) => {
+                let archetype = self.archetypes.archetype(&id)?;
+                let components = archetype.components();
+                let unique = components
+                    .iter()
+                    .enumerate()
+                    .filter(|(index, component)| !components[..*index].contains(component))
+                    .count();
+                if unique == components.len() {
+                    return None;
+                }
+
+                Some(format!(
+                    "archetype {} with {} component ids -> {} unique, table rebuilt",
+                    id.id(),
+                    components.len(),
+                    unique
+                ))
+            }
+            Fix::PruneChild(parent, child) => {
+                let children = self.entities.children_of(parent);
+                if !children.contains(&child) || self.entities.contains(child) {
+                    return None;
+                }
+
+                Some(format!(
+                    "{:?} with {} children -> {:?} pruned",
+                    parent,
+                    children.len(),
+                    child
+                ))
+            }
+        }
+    }
+
+    fn apply_fix(&mut self, fix: Fix) {
+        match fix {
+            Fix::Delete(entity) => {
+                let deleted = self.delete_with_policy(entity, OrphanPolicy::ReparentToGrandparent);
+                let mut outputs = self.resources.get_mut::<ActionOutputs>();
+                for entity in deleted {
+                    outputs.add::<DeleteEntity>(entity);
+                }
+            }
+            Fix::ResyncMask(entity) => {
+                self.archetypes.resync_mask(entity);
+            }
+            Fix::DropRow(entity, table) => {
+                if let Some(table) = self.tables.get_mut(table) {
+                    drop(table.remove_stray_row(entity));
+                }
+            }
+            Fix::Dedup(id) => {
+                self.archetypes.dedup_components(&id);
+                if let Some(table) = self.tables.get_mut(id.into()) {
+                    let table = table.rebuild();
+                    self.tables.insert(table);
+                }
+            }
+            Fix::PruneChild(parent, child) => {
+                self.entities.prune_child(parent, child);
+            }
+        }
+    }
+}
+
+impl World {
+    pub(crate) fn corrupt(&mut self, corruption: Corruption) {
+        match corruption {
+            Corruption::MissingRow(entity) => {
+                if let Some(id) = self.archetypes.archetype_id(entity).copied() {
+                    if let Some(table) = self.tables.get_mut(id.into()) {
+                        drop(table.remove_row(entity));
+                    }
+                }
+            }
+            Corruption::OrphanRow(entity) => {
+                self.archetypes.delete_entity(entity);
+                self.entities.delete(entity, false);
+            }
+            Corruption::RepeatComponent {
+                archetype,
+                component,
+            } => self.archetypes.repeat_component(&archetype, component),
+            Corruption::DeadChild { parent, child } => self.entities.list_child(parent, child),
+            Corruption::ClearMask(entity) => self.archetypes.clear_mask(entity),
+        }
+    }
+}
@@ -1,12 +1,27 @@
+use super::{error::ResourceError, World};
 use crate::storage::{blob::Blob, ptr::Ptr};
 use std::{
     any::TypeId,
+    cell::{Cell, RefCell},
     collections::HashMap,
     fmt::Debug,
     hash::{Hash, Hasher},
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicUsize, Ordering},
 };
 
-pub trait Resource: Send + Sync + 'static {}
+thread_local! {
+    /// Resources borrowed by the arguments of the systems running on this
+    /// thread, innermost system last.
+    static HELD: RefCell<Vec<(ResourceType, bool)>> = const { RefCell::new(Vec::new()) };
+    /// Systems running on this thread, counting nested ones.
+    static SCOPES: Cell<usize> = const { Cell::new(0) };
+}
+
+pub trait Resource: Send + Sync + 'static {
+    /// Name the resource is saved under; see `Component::STABLE_ID`.
+    const STABLE_ID: Option<&'static str> = None;
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct ResourceType(u64);
@@ -51,44 +66,314 @@ fn hash_id(id: &std::any::TypeId) -> u64 {
 
 pub struct Resources {
     resources: HashMap<ResourceType, ResourceData>,
+    frame: u64,
+    next_order: usize,
 }
 
 impl Resources {
     pub fn new() -> Self {
         Self {
             resources: HashMap::new(),
+            frame: 0,
+            next_order: 0,
         }
     }
 
     pub fn insert<R: Resource>(&mut self, resource: R) {
-        self.resources
-            .insert(ResourceType::new::<R>(), ResourceData::new(resource));
+        self.insert_data::<R>(ResourceData::new(resource, false));
     }
 
-    pub fn get<R: Resource>(&self) -> &R {
+    /// Inserts a framework resource that user-facing listings can filter out.
+    pub fn insert_internal<R: Resource>(&mut self, resource: R) {
+        self.insert_data::<R>(ResourceData::new(resource, true));
+    }
+
+    /// Inserts a resource whose `teardown` hook runs during
+    /// `World::shutdown`, before the resource is dropped.
+    pub fn insert_with_teardown<R: Resource>(
+        &mut self,
+        resource: R,
+        teardown: fn(&mut R, &mut World),
+    ) {
+        let mut data = ResourceData::new(resource, false);
+        data.teardown = Some(Box::new(move |data: &ResourceData, world: &mut World| {
+            teardown(data.get_mut::<R>(), world)
+        }));
+        self.insert_data::<R>(data);
+    }
+
+    fn insert_data<R: Resource>(&mut self, mut data: ResourceData) {
+        data.added_frame = self.frame;
+        data.order = self.next_order;
+        self.next_order += 1;
+
+        self.resources.insert(ResourceType::new::<R>(), data);
+    }
+
+    /// Borrows `R` until the returned guard drops. Panics, naming `R`, if
+    /// it doesn't exist or is borrowed mutably.
+    pub fn get<R: Resource>(&self) -> ResourceRef<'_, R> {
+        self.try_borrow::<R>()
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Borrows `R` mutably until the returned guard drops. Panics, naming
+    /// `R`, if it doesn't exist or is borrowed at all.
+    pub fn get_mut<R: Resource>(&self) -> ResourceRefMut<'_, R> {
+        self.try_borrow_mut::<R>()
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// `None` if `R` doesn't exist; still panics on a conflicting borrow.
+    pub fn try_get<R: Resource>(&self) -> Option<ResourceRef<'_, R>> {
+        match self.try_borrow::<R>() {
+            Ok(resource) => Some(resource),
+            Err(ResourceError::Missing(_)) => None,
+            Err(error) => panic!("{}", error),
+        }
+    }
+
+    pub fn try_get_mut<R: Resource>(&self) -> Option<ResourceRefMut<'_, R>> {
+        match self.try_borrow_mut::<R>() {
+            Ok(resource) => Some(resource),
+            Err(ResourceError::Missing(_)) => None,
+            Err(error) => panic!("{}", error),
+        }
+    }
+
+    pub fn try_borrow<R: Resource>(&self) -> Result<ResourceRef<'_, R>, ResourceError> {
+        let res = self.existing::<R>()?;
+        res.acquire(false)?;
+        Ok(ResourceRef {
+            value: res.get::<R>(),
+            data: res,
+        })
+    }
+
+    pub fn try_borrow_mut<R: Resource>(&self) -> Result<ResourceRefMut<'_, R>, ResourceError> {
+        let res = self.existing::<R>()?;
+        res.acquire(true)?;
+        Ok(ResourceRefMut {
+            value: res.get_mut::<R>(),
+            data: res,
+        })
+    }
+
+    fn existing<R: Resource>(&self) -> Result<&ResourceData, ResourceError> {
         let ty = ResourceType::new::<R>();
-        let res = self.resources.get(&ty).expect("Resource doesn't exist.");
-        res.get::<R>()
+        let name = std::any::type_name::<R>();
+        self.resources.get(&ty).ok_or(ResourceError::Missing(name))
     }
 
-    pub fn get_mut<R: Resource>(&self) -> &mut R {
+    /// `R` through `&mut self`, which no borrow guard can outlive, so there
+    /// is nothing to register.
+    pub(crate) fn exclusive<R: Resource>(&mut self) -> &mut R {
         let ty = ResourceType::new::<R>();
-        let res = self.resources.get(&ty).expect("Resource doesn't exist.");
+        let name = std::any::type_name::<R>();
+        let res = self.resources.get_mut(&ty);
+        res.unwrap_or_else(|| panic!("{}", ResourceError::Missing(name)))
+            .get_mut::<R>()
+    }
+
+    /// Borrows `R` for a system argument until the running system returns.
+    /// Panics, naming `R`, on a conflicting borrow or outside of a system.
+    pub(crate) fn hold<R: Resource>(&self) -> Option<&R> {
+        let res = self.hold_data::<R>(false)?;
+        Some(res.get::<R>())
+    }
+
+    pub(crate) fn hold_mut<R: Resource>(&self) -> Option<&mut R> {
+        let res = self.hold_data::<R>(true)?;
+        Some(res.get_mut::<R>())
+    }
+
+    fn hold_data<R: Resource>(&self, write: bool) -> Option<&ResourceData> {
+        let ty = ResourceType::new::<R>();
+        let res = self.resources.get(&ty)?;
+        if SCOPES.get() == 0 {
+            panic!(
+                "resource {} borrowed as a system argument outside of a system",
+                res.name
+            );
+        }
+        if let Err(error) = res.acquire(write) {
+            panic!("{}", error);
+        }
+        HELD.with_borrow_mut(|held| held.push((ty, write)));
+
+        Some(res)
+    }
+
+    /// Starts a scope for the resource borrows of a system's arguments,
+    /// released when the returned guard drops, even if the system panics.
+    pub(crate) fn enter(&self) -> SystemBorrows<'_> {
+        SCOPES.set(SCOPES.get() + 1);
+        SystemBorrows {
+            resources: self,
+            start: HELD.with_borrow(Vec::len),
+        }
+    }
+
+    pub fn data(&self, ty: ResourceType) -> Option<&ResourceData> {
+        self.resources.get(&ty)
+    }
+
+    pub fn remove_type(&mut self, ty: ResourceType) -> Option<ResourceData> {
+        self.resources.remove(&ty)
+    }
+
+    /// Takes `R` out without running its teardown hook.
+    pub fn remove<R: Resource>(&mut self) -> Option<R> {
+        self.remove_type(ResourceType::new::<R>())
+            .map(|data| data.into_inner::<R>())
+    }
+
+    pub fn contains<R: Resource>(&self) -> bool {
+        self.contains_type(ResourceType::new::<R>())
+    }
+
+    pub fn contains_type(&self, ty: ResourceType) -> bool {
+        self.resources.contains_key(&ty)
+    }
+
+    pub fn len(&self) -> usize {
+        self.resources.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.resources.is_empty()
+    }
+
+    /// Resources in insertion order.
+    pub fn iter(&self) -> impl Iterator<Item = (&ResourceType, &ResourceData)> {
+        let mut resources = self.resources.iter().collect::<Vec<_>>();
+        resources.sort_by_key(|(_, data)| data.order);
+        resources.into_iter()
+    }
+
+    pub fn iter_types(&self) -> impl Iterator<Item = (ResourceType, &'static str)> + '_ {
+        self.iter().map(|(ty, data)| (*ty, data.name()))
+    }
+
+    /// Frame stamped on resources inserted from now on.
+    pub fn set_frame(&mut self, frame: u64) {
+        self.frame = frame;
+    }
+}
+
+/// Resource borrows taken by the arguments of a running system, released
+/// on drop so a panicking system doesn't leave them held for later frames.
+pub(crate) struct SystemBorrows<'a> {
+    resources: &'a Resources,
+    start: usize,
+}
 
-        res.get_mut::<R>()
+impl Drop for SystemBorrows<'_> {
+    fn drop(&mut self) {
+        SCOPES.set(SCOPES.get() - 1);
+        HELD.with_borrow_mut(|held| {
+            for (ty, write) in held.drain(self.start..) {
+                if let Some(res) = self.resources.resources.get(&ty) {
+                    res.release(write);
+                }
+            }
+        });
     }
 }
 
+/// A shared borrow of a resource, returned by `World::resource`. Released
+/// when dropped.
+pub struct ResourceRef<'a, R: Resource> {
+    value: &'a R,
+    data: &'a ResourceData,
+}
+
+impl<R: Resource> Deref for ResourceRef<'_, R> {
+    type Target = R;
+
+    fn deref(&self) -> &Self::Target {
+        self.value
+    }
+}
+
+impl<R: Resource + Debug> Debug for ResourceRef<'_, R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+impl<R: Resource> Drop for ResourceRef<'_, R> {
+    fn drop(&mut self) {
+        self.data.release(false);
+    }
+}
+
+/// An exclusive borrow of a resource, returned by `World::resource_mut`.
+/// Released when dropped.
+pub struct ResourceRefMut<'a, R: Resource> {
+    value: &'a mut R,
+    data: &'a ResourceData,
+}
+
+impl<R: Resource> Deref for ResourceRefMut<'_, R> {
+    type Target = R;
+
+    fn deref(&self) -> &Self::Target {
+        self.value
+    }
+}
+
+impl<R: Resource> DerefMut for ResourceRefMut<'_, R> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.value
+    }
+}
+
+impl<R: Resource + Debug> Debug for ResourceRefMut<'_, R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+impl<R: Resource> Drop for ResourceRefMut<'_, R> {
+    fn drop(&mut self) {
+        self.data.release(true);
+    }
+}
+
+/// Borrow state of a resource with nothing held.
+const UNBORROWED: usize = 0;
+/// Borrow state of a resource held mutably; otherwise it counts readers.
+const WRITING: usize = usize::MAX;
+
+type TeardownFn = Box<dyn Fn(&ResourceData, &mut World) + Send + Sync>;
+
 pub struct ResourceData {
     data: Blob,
+    name: &'static str,
+    key: &'static str,
+    internal: bool,
+    added_frame: u64,
+    order: usize,
+    teardown: Option<TeardownFn>,
+    borrow: AtomicUsize,
 }
 
 impl ResourceData {
-    pub fn new<R: Resource>(resource: R) -> Self {
+    pub fn new<R: Resource>(resource: R, internal: bool) -> Self {
         let mut data = Blob::new::<R>();
         data.push(resource);
 
-        ResourceData { data }
+        ResourceData {
+            data,
+            name: std::any::type_name::<R>(),
+            key: R::STABLE_ID.unwrap_or(std::any::type_name::<R>()),
+            internal,
+            added_frame: 0,
+            order: 0,
+            teardown: None,
+            borrow: AtomicUsize::new(UNBORROWED),
+        }
     }
 
     pub fn ptr<'a>(&'a self) -> Ptr<'a> {
@@ -102,4 +387,96 @@ impl ResourceData {
     pub fn get_mut<R: Resource>(&self) -> &mut R {
         self.data.get_mut::<R>(0).unwrap()
     }
+
+    /// Moves the value out; the emptied blob drops nothing.
+    pub fn into_inner<R: Resource>(mut self) -> R {
+        self.data.swap_remove_as::<R>(0)
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// `Resource::STABLE_ID`, or the name for resources without one.
+    pub fn key(&self) -> &'static str {
+        self.key
+    }
+
+    /// Whether the resource is borrowed, and how.
+    pub fn borrowed(&self) -> Option<bool> {
+        match self.borrow.load(Ordering::Acquire) {
+            UNBORROWED => None,
+            WRITING => Some(true),
+            _ => Some(false),
+        }
+    }
+
+    fn acquire(&self, write: bool) -> Result<(), ResourceError> {
+        if write {
+            return self
+                .borrow
+                .compare_exchange(UNBORROWED, WRITING, Ordering::AcqRel, Ordering::Acquire)
+                .map(|_| ())
+                .map_err(|actual| match actual {
+                    WRITING => ResourceError::BorrowedMut(self.name),
+                    _ => ResourceError::Borrowed(self.name),
+                });
+        }
+
+        let mut current = self.borrow.load(Ordering::Acquire);
+        loop {
+            if current == WRITING {
+                return Err(ResourceError::BorrowedMut(self.name));
+            }
+
+            match self.borrow.compare_exchange_weak(
+                current,
+                current + 1,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return Ok(()),
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    fn release(&self, write: bool) {
+        match write {
+            true => self.borrow.store(UNBORROWED, Ordering::Release),
+            false => {
+                self.borrow.fetch_sub(1, Ordering::AcqRel);
+            }
+        }
+    }
+
+    /// Size of the resource value itself; heap memory it owns isn't counted.
+    pub fn allocated_bytes(&self) -> usize {
+        self.data.allocated_bytes()
+    }
+
+    pub fn is_internal(&self) -> bool {
+        self.internal
+    }
+
+    pub fn added_frame(&self) -> u64 {
+        self.added_frame
+    }
+
+    /// Position in insertion order across all resources of the world.
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    pub fn has_teardown(&self) -> bool {
+        self.teardown.is_some()
+    }
+
+    /// Runs the teardown hook, if any. The resource is expected to be
+    /// removed from `world` already, so the hook can't reach it twice.
+    pub fn teardown(&self, world: &mut World) {
+        if let Some(teardown) = &self.teardown {
+            teardown(self, world);
+        }
+    }
 }
@@ -0,0 +1,280 @@
+use super::{
+    resource::Resource,
+    shutdown::{ShutdownError, ShutdownReport},
+    FrameReport, World,
+};
+use crate::{schedule::SchedulePhase, tasks::CancellationToken};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// Time source of a `WorldRunner`.
+pub trait Clock {
+    /// Time since an arbitrary, fixed start.
+    fn now(&self) -> Duration;
+    fn sleep(&mut self, duration: Duration);
+}
+
+/// Wall clock time, sleeping the thread.
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+/// Virtual time that only moves when advanced or slept on. Clones share the
+/// same time, so a tick callback can simulate a stall.
+#[derive(Debug, Clone, Default)]
+pub struct MockClock {
+    nanos: Arc<AtomicU64>,
+}
+
+impl MockClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.nanos
+            .fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Duration {
+        Duration::from_nanos(self.nanos.load(Ordering::Relaxed))
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        self.advance(duration);
+    }
+}
+
+/// Simulation time kept by a `WorldRunner`, advanced by one step before
+/// every tick. Dropped ticks don't advance it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FixedTime {
+    delta: Duration,
+    elapsed: Duration,
+    ticks: u64,
+}
+
+impl FixedTime {
+    pub fn delta(&self) -> Duration {
+        self.delta
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+}
+
+impl Resource for FixedTime {}
+
+/// What `WorldRunner::run_until` did before it stopped.
+pub struct RunnerExit {
+    pub ticks: u64,
+    /// Ticks run late to catch up after a stall.
+    pub catch_up_ticks: u64,
+    /// Ticks skipped because the runner was further behind than
+    /// `max_catch_up` allows.
+    pub dropped_ticks: u64,
+    /// The world, unless it was shut down.
+    pub world: Option<World>,
+    pub shutdown: Option<Result<ShutdownReport, ShutdownError>>,
+}
+
+type TickFn = Box<dyn FnMut(&FrameReport)>;
+
+/// Runs phase `P` at a fixed tick rate, for headless servers.
+///
+/// ```ignore
+/// let exit = WorldRunner::<Update>::new(world)
+///     .tick_rate(30)
+///     .max_catch_up(5)
+///     .on_tick(|report| log(report))
+///     .run_until(|world| world.resource::<ServerState>().should_exit());
+/// ```
+pub struct WorldRunner<P: SchedulePhase, C: Clock = SystemClock> {
+    world: World,
+    clock: C,
+    step: Duration,
+    max_catch_up: u32,
+    shutdown: bool,
+    token: Option<CancellationToken>,
+    on_tick: Option<TickFn>,
+    _marker: std::marker::PhantomData<P>,
+}
+
+impl<P: SchedulePhase> WorldRunner<P> {
+    /// 60 ticks per second, no catch-up and no shutdown on exit.
+    pub fn new(world: World) -> Self {
+        Self {
+            world,
+            clock: SystemClock::new(),
+            step: Duration::from_secs(1) / 60,
+            max_catch_up: 0,
+            shutdown: false,
+            token: None,
+            on_tick: None,
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<P: SchedulePhase, C: Clock> WorldRunner<P, C> {
+    pub fn clock<K: Clock>(self, clock: K) -> WorldRunner<P, K> {
+        WorldRunner {
+            world: self.world,
+            clock,
+            step: self.step,
+            max_catch_up: self.max_catch_up,
+            shutdown: self.shutdown,
+            token: self.token,
+            on_tick: self.on_tick,
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Ticks per second.
+    pub fn tick_rate(mut self, rate: u32) -> Self {
+        assert!(rate > 0, "tick rate must be positive");
+        self.step = Duration::from_secs(1) / rate;
+        self
+    }
+
+    /// Extra ticks run back to back when the runner falls behind. Ticks
+    /// beyond that are dropped.
+    pub fn max_catch_up(mut self, ticks: u32) -> Self {
+        self.max_catch_up = ticks;
+        self
+    }
+
+    /// Calls `World::shutdown` once the loop stops.
+    pub fn shutdown_on_exit(mut self, shutdown: bool) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// Stops the loop once `token` is cancelled.
+    pub fn cancel_token(mut self, token: CancellationToken) -> Self {
+        self.token = Some(token);
+        self
+    }
+
+    pub fn on_tick(mut self, f: impl FnMut(&FrameReport) + 'static) -> Self {
+        self.on_tick = Some(Box::new(f));
+        self
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    /// Ticks until `exit` returns true or the cancel token is cancelled.
+    /// Both are checked before every tick.
+    pub fn run_until(mut self, mut exit: impl FnMut(&World) -> bool) -> RunnerExit {
+        if self.world.try_resource::<FixedTime>().is_none() {
+            self.world.add_resource(FixedTime::default());
+        }
+
+        let mut ticks = 0;
+        let mut catch_up_ticks = 0;
+        let mut dropped_ticks = 0;
+        let mut next = self.clock.now();
+
+        'run: loop {
+            if self.stopped(&mut exit) {
+                break;
+            }
+
+            let now = self.clock.now();
+            if now < next {
+                self.clock.sleep(next - now);
+                continue;
+            }
+
+            let due = ((now - next).as_nanos() / self.step.as_nanos()) as u64 + 1;
+            let run = due.min(self.max_catch_up as u64 + 1);
+            dropped_ticks += due - run;
+            next += Duration::from_nanos((self.step.as_nanos() * due as u128) as u64);
+
+            for tick in 0..run {
+                if tick > 0 {
+                    if self.stopped(&mut exit) {
+                        break 'run;
+                    }
+                    catch_up_ticks += 1;
+                }
+
+                self.tick();
+                ticks += 1;
+            }
+        }
+
+        let (world, shutdown) = match self.shutdown {
+            true => (None, Some(self.world.shutdown())),
+            false => (Some(self.world), None),
+        };
+
+        RunnerExit {
+            ticks,
+            catch_up_ticks,
+            dropped_ticks,
+            world,
+            shutdown,
+        }
+    }
+
+    fn stopped(&self, exit: &mut impl FnMut(&World) -> bool) -> bool {
+        let cancelled = self.token.as_ref().is_some_and(|t| t.is_cancelled());
+        cancelled || exit(&self.world)
+    }
+
+    fn tick(&mut self) {
+        let mut time = self.world.resource_mut::<FixedTime>();
+        time.delta = self.step;
+        time.elapsed += self.step;
+        time.ticks += 1;
+        drop(time);
+
+        let report = self.world.run::<P>();
+        if let Some(on_tick) = self.on_tick.as_mut() {
+            on_tick(&report);
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use super::{provenance::ProvenanceSource, query::Query, World};
+use crate::{
+    core::{Component, Entity},
+    system::observer::{
+        action::{Action, ActionOutputs, Actions},
+        builtin::{Bundle, CreateEntity, DeleteEntity},
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ScopeId(u32);
+
+impl ScopeId {
+    pub fn id(&self) -> u32 {
+        self.0
+    }
+}
+
+/// Handle to a group of entities that are deleted together by
+/// `World::close_scope`. Only an id; it doesn't borrow the world and
+/// dropping it does nothing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EntityScope {
+    id: ScopeId,
+}
+
+impl EntityScope {
+    pub fn id(&self) -> ScopeId {
+        self.id
+    }
+}
+
+/// Tags an entity as a member of a scope. Added by `CreateEntity::in_scope`
+/// and `World::spawn_scoped`; stays with the entity when it is reparented.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScopeMember(ScopeId);
+
+impl ScopeMember {
+    pub fn scope(&self) -> ScopeId {
+        self.0
+    }
+}
+
+impl Component for ScopeMember {}
+
+struct ScopeData {
+    label: &'static str,
+    parent: Option<ScopeId>,
+    children: Vec<ScopeId>,
+    open: bool,
+}
+
+#[derive(Default)]
+pub struct Scopes {
+    scopes: Vec<ScopeData>,
+}
+
+impl Scopes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn create(&mut self, label: &'static str, parent: Option<ScopeId>) -> EntityScope {
+        let id = ScopeId(self.scopes.len() as u32);
+        if let Some(parent) = parent {
+            let parent = &mut self.scopes[parent.0 as usize];
+            assert!(parent.open, "scope {} is closed", parent.label);
+            parent.children.push(id);
+        }
+
+        self.scopes.push(ScopeData {
+            label,
+            parent,
+            children: Vec::new(),
+            open: true,
+        });
+
+        EntityScope { id }
+    }
+
+    pub fn label(&self, scope: ScopeId) -> Option<&'static str> {
+        self.scopes.get(scope.0 as usize).map(|data| data.label)
+    }
+
+    pub fn parent(&self, scope: ScopeId) -> Option<ScopeId> {
+        self.scopes.get(scope.0 as usize)?.parent
+    }
+
+    pub fn is_open(&self, scope: ScopeId) -> bool {
+        self.scopes
+            .get(scope.0 as usize)
+            .map(|data| data.open)
+            .unwrap_or(false)
+    }
+
+    /// `scope` followed by every scope nested in it.
+    pub fn descendants(&self, scope: ScopeId) -> Vec<ScopeId> {
+        let mut scopes = vec![scope];
+        let mut index = 0;
+        while let Some(next) = scopes.get(index) {
+            if let Some(data) = self.scopes.get(next.0 as usize) {
+                scopes.extend(data.children.iter().copied());
+            }
+            index += 1;
+        }
+
+        scopes
+    }
+
+    fn close(&mut self, scope: ScopeId) -> Vec<ScopeId> {
+        let scopes = self.descendants(scope);
+        for scope in &scopes {
+            if let Some(data) = self.scopes.get_mut(scope.0 as usize) {
+                data.open = false;
+            }
+        }
+
+        scopes
+    }
+}
+
+impl CreateEntity {
+    /// Adds the new entity to `scope`.
+    pub fn in_scope(self, scope: EntityScope) -> Self {
+        self.with(ScopeMember(scope.id))
+    }
+}
+
+impl World {
+    pub fn create_scope(&mut self, label: &'static str) -> EntityScope {
+        self.scopes.create(label, None)
+    }
+
+    /// Creates a scope that is closed along with `parent`.
+    pub fn create_child_scope(&mut self, parent: EntityScope, label: &'static str) -> EntityScope {
+        self.scopes.create(label, Some(parent.id))
+    }
+
+    pub fn scopes(&self) -> &Scopes {
+        &self.scopes
+    }
+
+    /// Creates an entity in `scope`. The entity and its components exist
+    /// right away; observers are notified on the next flush.
+    pub fn spawn_scoped<B: Bundle>(&mut self, scope: EntityScope, bundle: B) -> Entity {
+        assert!(
+            self.scopes.is_open(scope.id),
+            "scope {} is closed",
+            self.scopes.label(scope.id).unwrap_or("<unknown>")
+        );
+
+        let label = self.scopes.label(scope.id).unwrap_or("<unknown>");
+        let begun = self.begin_provenance(|| ProvenanceSource::Scope(label));
+        let mut action = CreateEntity::new().with_bundle(bundle).in_scope(scope);
+        let entity = action.execute(self);
+        self.end_provenance(begun);
+        self.resource_mut::<ActionOutputs>()
+            .add::<CreateEntity>(entity);
+
+        entity
+    }
+
+    /// Members of `scope`, and of the scopes nested in it when `recursive`
+    /// is set.
+    pub fn scope_entities(&self, scope: EntityScope, recursive: bool) -> Vec<Entity> {
+        if self.components.get_id::<ScopeMember>().is_none() {
+            return Vec::new();
+        }
+
+        let scopes = match recursive {
+            true => self.scopes.descendants(scope.id),
+            false => vec![scope.id],
+        };
+
+        Query::<(Entity, &ScopeMember)>::new(self)
+            .filter(|(_, member)| scopes.contains(&member.scope()))
+            .map(|(entity, _)| entity)
+            .collect()
+    }
+
+    /// Closes `scope` and every scope nested in it, queueing a `DeleteEntity`
+    /// for each of their members. Closed scopes can't be spawned into.
+    pub fn close_scope(&mut self, scope: EntityScope) {
+        let members = self.scope_entities(scope, true);
+        self.scopes.close(scope.id);
+
+        let mut actions = self.resource_mut::<Actions>();
+        for entity in members {
+            actions.add(DeleteEntity::new(entity));
+        }
+    }
+}
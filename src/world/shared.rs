@@ -0,0 +1,166 @@
+use super::{resource::Resource, World};
+use std::{
+    cell::UnsafeCell,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+};
+
+/// A resource also reachable from threads outside the world, e.g. an audio
+/// thread. Systems take it as `&SharedResource<T>` and `lock` it for as
+/// long as they need; other threads lock the handle from
+/// `World::shared_handle`. Nothing else of the world is locked.
+pub struct SharedResource<T: Send + 'static> {
+    value: Arc<Mutex<T>>,
+}
+
+impl<T: Send + 'static> SharedResource<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            value: Arc::new(Mutex::new(value)),
+        }
+    }
+
+    /// Locks the value. A panic on another thread while it held the lock
+    /// doesn't poison it for the world.
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        self.value.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn handle(&self) -> Arc<Mutex<T>> {
+        self.value.clone()
+    }
+}
+
+impl<T: Send + 'static> Resource for SharedResource<T> {}
+
+/// Set in `TripleBuffer::middle` when the middle slot holds a value the
+/// reader hasn't taken yet.
+const FRESH: u8 = 0b100;
+const SLOT: u8 = 0b011;
+
+/// Three slots: one owned by the writer, one by the reader and one in the
+/// middle that they swap theirs with. Neither side ever waits for the other
+/// or sees a value being written.
+struct TripleBuffer<T> {
+    slots: [UnsafeCell<T>; 3],
+    middle: AtomicU8,
+}
+
+// The writer and reader only touch the slot they own; ownership changes
+// hands through `middle`.
+unsafe impl<T: Send> Send for TripleBuffer<T> {}
+unsafe impl<T: Send + Sync> Sync for TripleBuffer<T> {}
+
+impl<T> TripleBuffer<T> {
+    /// Hands `slot` to the middle and returns the slot that was there.
+    fn exchange(&self, slot: u8) -> u8 {
+        self.middle.swap(slot, Ordering::AcqRel) & SLOT
+    }
+}
+
+/// The latest complete value written by another thread through its
+/// `InputWriter`, for high-frequency producers that shouldn't take locks.
+/// Systems read it as `&SharedInput<T>`; the value only changes at the
+/// start of `World::run`, so every system of a run sees the same one.
+pub struct SharedInput<T: Clone + Send + Sync + 'static> {
+    buffer: Arc<TripleBuffer<T>>,
+    slot: u8,
+}
+
+impl<T: Clone + Send + Sync + 'static> SharedInput<T> {
+    /// The input and the writer feeding it, both starting at `initial`.
+    pub fn new(initial: T) -> (Self, InputWriter<T>) {
+        let buffer = Arc::new(TripleBuffer {
+            slots: [
+                UnsafeCell::new(initial.clone()),
+                UnsafeCell::new(initial.clone()),
+                UnsafeCell::new(initial),
+            ],
+            middle: AtomicU8::new(1),
+        });
+
+        let writer = InputWriter {
+            buffer: buffer.clone(),
+            slot: 2,
+        };
+
+        (Self { buffer, slot: 0 }, writer)
+    }
+
+    pub fn latest(&self) -> &T {
+        // SAFETY: the writer never touches the slot the reader owns.
+        unsafe { &*self.buffer.slots[self.slot as usize].get() }
+    }
+
+    /// Takes the newest value written since the last update. Returns false
+    /// if there was none. `World::run` calls this for inputs added with
+    /// `add_shared_input`.
+    pub fn update(&mut self) -> bool {
+        if self.buffer.middle.load(Ordering::Acquire) & FRESH == 0 {
+            return false;
+        }
+
+        self.slot = self.buffer.exchange(self.slot);
+        true
+    }
+}
+
+impl<T: Clone + Send + Sync + 'static> Resource for SharedInput<T> {}
+
+/// Writing end of a `SharedInput`, to move to the producing thread. There is
+/// only one per input.
+pub struct InputWriter<T: Send> {
+    buffer: Arc<TripleBuffer<T>>,
+    slot: u8,
+}
+
+impl<T: Send> InputWriter<T> {
+    /// Publishes `value`, replacing any value the world hasn't taken yet.
+    pub fn write(&mut self, value: T) {
+        // SAFETY: the reader never touches the slot the writer owns.
+        unsafe { *self.buffer.slots[self.slot as usize].get() = value };
+        self.slot = self.buffer.exchange(self.slot | FRESH);
+    }
+}
+
+impl World {
+    /// Adds `value` as a `SharedResource` and returns a handle to it for
+    /// other threads.
+    pub fn add_shared<T: Send + 'static>(&mut self, value: T) -> Arc<Mutex<T>> {
+        let shared = SharedResource::new(value);
+        let handle = shared.handle();
+        self.add_resource(shared);
+        handle
+    }
+
+    /// Another handle to the `SharedResource<T>`, if it was added.
+    pub fn shared_handle<T: Send + 'static>(&self) -> Option<Arc<Mutex<T>>> {
+        self.try_resource::<SharedResource<T>>()
+            .map(|shared| shared.handle())
+    }
+
+    /// Adds a `SharedInput<T>` starting at `initial`, updated at the start
+    /// of every `run`, and returns its writer.
+    pub fn add_shared_input<T: Clone + Send + Sync + 'static>(
+        &mut self,
+        initial: T,
+    ) -> InputWriter<T> {
+        let (input, writer) = SharedInput::new(initial);
+        if self.try_resource::<SharedInput<T>>().is_none() {
+            self.inputs.push(|world| {
+                world.resource_mut::<SharedInput<T>>().update();
+            });
+        }
+
+        self.add_resource(input);
+        writer
+    }
+
+    pub(crate) fn update_shared_inputs(&mut self) {
+        for update in self.inputs.clone() {
+            update(self);
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use super::{resource::ResourceType, trace::TraceLevel, World};
+use crate::{
+    schedule::{GlobalSchedules, SceneSchedules, Shutdown},
+    system::observer::{action::Actions, builtin::DeleteEntity},
+};
+use std::fmt::{Debug, Display};
+
+/// Flushes spent deleting entities before shutdown gives up on observers
+/// that keep spawning new ones.
+pub const SHUTDOWN_MAX_PASSES: usize = 16;
+
+/// Why `World::shutdown` refused to run. The world is handed back untouched.
+pub enum ShutdownError {
+    /// Called on a world taken out of itself while it was flushing.
+    Flushing(Box<World>),
+    /// Called on a world that is already shutting down.
+    ShuttingDown(Box<World>),
+}
+
+impl ShutdownError {
+    pub fn into_world(self) -> World {
+        match self {
+            ShutdownError::Flushing(world) | ShutdownError::ShuttingDown(world) => *world,
+        }
+    }
+}
+
+impl Debug for ShutdownError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShutdownError::Flushing(_) => f.write_str("Flushing"),
+            ShutdownError::ShuttingDown(_) => f.write_str("ShuttingDown"),
+        }
+    }
+}
+
+impl Display for ShutdownError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShutdownError::Flushing(_) => write!(f, "cannot shut down while flushing"),
+            ShutdownError::ShuttingDown(_) => write!(f, "world is already shutting down"),
+        }
+    }
+}
+
+impl std::error::Error for ShutdownError {}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ShutdownReport {
+    pub ran_shutdown_phase: bool,
+    pub entities_deleted: usize,
+    /// Entities still alive after `SHUTDOWN_MAX_PASSES` flushes, dropped
+    /// without observers.
+    pub entities_leaked: usize,
+    pub resources_dropped: usize,
+}
+
+impl World {
+    /// Inserts a resource whose `teardown` hook runs during `shutdown`. The
+    /// resource is already detached from the world when the hook runs.
+    pub fn add_resource_with_teardown<R: super::resource::Resource>(
+        &mut self,
+        resource: R,
+        teardown: fn(&mut R, &mut World),
+    ) {
+        self.resources.insert_with_teardown(resource, teardown);
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down
+    }
+
+    /// Tears the world down in a fixed order:
+    ///
+    /// 1. runs the `Shutdown` phase if any schedule was added to it,
+    /// 2. deletes every entity through `DeleteEntity` actions so removal and
+    ///    delete observers run,
+    /// 3. tears down user resources in reverse insertion order, running
+    ///    their teardown hooks,
+    /// 4. drops the rest of the world.
+    pub fn shutdown(mut self) -> Result<ShutdownReport, ShutdownError> {
+        if self.flushing {
+            return Err(ShutdownError::Flushing(Box::new(self)));
+        } else if self.shutting_down {
+            return Err(ShutdownError::ShuttingDown(Box::new(self)));
+        }
+
+        self.shutting_down = true;
+        self.frame_token.reset();
+        let mut report = ShutdownReport::default();
+
+        let phase = self
+            .resources
+            .get::<GlobalSchedules>()
+            .contains_phase::<Shutdown>()
+            || self
+                .resources
+                .get::<SceneSchedules>()
+                .contains_phase::<Shutdown>();
+        if phase {
+            self.run::<Shutdown>();
+            report.ran_shutdown_phase = true;
+        }
+
+        let alive = self.entities.len();
+        for _ in 0..SHUTDOWN_MAX_PASSES {
+            if self.entities.is_empty() {
+                break;
+            }
+
+            let roots = self.entities.roots().to_vec();
+            let mut actions = self.resources.get_mut::<Actions>();
+            for root in roots {
+                actions.add(DeleteEntity::new(root));
+            }
+            drop(actions);
+
+            self.frame_token.reset();
+            self.flush();
+        }
+
+        report.entities_leaked = self.entities.len();
+        report.entities_deleted = alive.saturating_sub(report.entities_leaked);
+        if report.entities_leaked > 0 {
+            let message = format!(
+                "{} entities still alive after {} passes",
+                report.entities_leaked, SHUTDOWN_MAX_PASSES
+            );
+            self.trace(TraceLevel::Warn, "shutdown", message);
+        }
+
+        let mut resources = self
+            .resources
+            .iter()
+            .filter(|(_, data)| !data.is_internal())
+            .map(|(ty, _)| *ty)
+            .collect::<Vec<ResourceType>>();
+        resources.reverse();
+
+        for ty in resources {
+            if let Some(data) = self.resources.remove_type(ty) {
+                data.teardown(&mut self);
+                report.resources_dropped += 1;
+            }
+        }
+
+        Ok(report)
+    }
+}
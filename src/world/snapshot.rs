@@ -0,0 +1,293 @@
+use super::{
+    error::SnapshotError,
+    recording::EntityMapper,
+    reflect::{field_values, set_path, FieldValue, Reflect},
+    resource::Resource,
+    World,
+};
+use crate::core::{Component, Entity};
+use std::collections::HashMap;
+
+type Fields = [(String, FieldValue)];
+type SaveComponentFn = fn(&World, Entity) -> Option<Vec<(String, FieldValue)>>;
+type LoadComponentFn = fn(&mut World, Entity, &Fields);
+type SaveResourceFn = fn(&World) -> Option<Vec<(String, FieldValue)>>;
+type LoadResourceFn = fn(&mut World, &Fields);
+/// Builds a value from the fields and throws it away, returning the path
+/// of the first field that doesn't fit.
+type CheckFn = fn(&Fields) -> Result<(), String>;
+
+struct Entry<S, L> {
+    key: &'static str,
+    stable: bool,
+    save: S,
+    check: CheckFn,
+    load: L,
+}
+
+/// Components and resources `World::snapshot` saves and `SnapshotLoader`
+/// loads, through `Reflect`. Each is saved under its key: its `STABLE_ID`,
+/// or its type name if it has none.
+#[derive(Default)]
+pub struct SnapshotRegistry {
+    components: Vec<Entry<SaveComponentFn, LoadComponentFn>>,
+    resources: Vec<Entry<SaveResourceFn, LoadResourceFn>>,
+}
+
+impl SnapshotRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register_component<C: Component + Reflect + Default>(&mut self) -> &mut Self {
+        self.components.push(Entry {
+            key: C::STABLE_ID.unwrap_or(std::any::type_name::<C>()),
+            stable: C::STABLE_ID.is_some(),
+            save: |world, entity| Some(field_values(&*world.component::<C>(entity)?)),
+            check: |fields| build::<C>(fields).map(drop),
+            load: |world, entity, fields| {
+                if let Ok(value) = build::<C>(fields) {
+                    world.register::<C>();
+                    world.add_component(entity, value);
+                }
+            },
+        });
+        self
+    }
+
+    pub fn register_resource<R: Resource + Reflect + Default>(&mut self) -> &mut Self {
+        self.resources.push(Entry {
+            key: R::STABLE_ID.unwrap_or(std::any::type_name::<R>()),
+            stable: R::STABLE_ID.is_some(),
+            save: |world| Some(field_values(&*world.try_resource::<R>()?)),
+            check: |fields| build::<R>(fields).map(drop),
+            load: |world, fields| {
+                if let Ok(value) = build::<R>(fields) {
+                    world.add_resource(value);
+                }
+            },
+        });
+        self
+    }
+
+    fn keys(&self) -> impl Iterator<Item = &'static str> + '_ {
+        let components = self.components.iter().map(|entry| entry.key);
+        components.chain(self.resources.iter().map(|entry| entry.key))
+    }
+}
+
+fn build<T: Reflect + Default>(fields: &Fields) -> Result<T, String> {
+    let mut value = T::default();
+    for (path, field) in fields {
+        if set_path(&mut value, path, field.clone()) != Some(true) {
+            return Err(path.clone());
+        }
+    }
+
+    Ok(value)
+}
+
+/// The saved fields of one component or resource, by dotted path.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavedValue {
+    pub key: String,
+    pub fields: Vec<(String, FieldValue)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavedEntity {
+    pub entity: Entity,
+    pub components: Vec<SavedValue>,
+}
+
+/// Registered components and resources of a world, made by
+/// `World::snapshot`. Hierarchy links are not saved.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Snapshot {
+    /// Keys that fell back to a type name, so the snapshot stops loading
+    /// once the type is renamed or moved unless `SnapshotLoader::alias`
+    /// maps the old name.
+    pub warnings: Vec<String>,
+    pub entities: Vec<SavedEntity>,
+    pub resources: Vec<SavedValue>,
+}
+
+/// Loads snapshots into a world, including ones saved under keys that
+/// changed since.
+pub struct SnapshotLoader<'a> {
+    registry: &'a SnapshotRegistry,
+    aliases: HashMap<String, String>,
+}
+
+impl<'a> SnapshotLoader<'a> {
+    pub fn new(registry: &'a SnapshotRegistry) -> Self {
+        Self {
+            registry,
+            aliases: HashMap::new(),
+        }
+    }
+
+    /// Loads values saved under `old` as the registered `new` key, e.g.
+    /// `alias("old::path::Health", "game::stats::Health")` after a move.
+    pub fn alias(mut self, old: impl Into<String>, new: impl Into<String>) -> Self {
+        self.aliases.insert(old.into(), new.into());
+        self
+    }
+
+    /// Creates an entity per saved entity, with its components, and adds the
+    /// saved resources. Entity fields are pointed at the new entities.
+    /// Nothing is changed if a key is unknown or a value doesn't fit its
+    /// type. Returns the saved entities mapped to the new ones.
+    pub fn load(
+        &self,
+        snapshot: &Snapshot,
+        world: &mut World,
+    ) -> Result<EntityMapper, SnapshotError> {
+        let mut components = Vec::new();
+        for saved in &snapshot.entities {
+            for value in &saved.components {
+                let index = self.resolve(&value.key, &self.registry.components)?;
+                self.check(&self.registry.components[index], value)?;
+                components.push(index);
+            }
+        }
+
+        let mut resources = Vec::new();
+        for value in &snapshot.resources {
+            let index = self.resolve(&value.key, &self.registry.resources)?;
+            self.check(&self.registry.resources[index], value)?;
+            resources.push(index);
+        }
+
+        let mut mapper = EntityMapper::new();
+        for saved in &snapshot.entities {
+            mapper.insert(saved.entity, world.create());
+        }
+
+        let mut components = components.into_iter();
+        for saved in &snapshot.entities {
+            let entity = mapper.map(saved.entity);
+            for (value, index) in saved.components.iter().zip(components.by_ref()) {
+                let fields = map_fields(&value.fields, &mapper);
+                (self.registry.components[index].load)(world, entity, &fields);
+            }
+        }
+
+        for (value, index) in snapshot.resources.iter().zip(resources) {
+            let fields = map_fields(&value.fields, &mapper);
+            (self.registry.resources[index].load)(world, &fields);
+        }
+
+        Ok(mapper)
+    }
+
+    fn resolve<S, L>(&self, key: &str, entries: &[Entry<S, L>]) -> Result<usize, SnapshotError> {
+        let registered = self.aliases.get(key).map_or(key, String::as_str);
+        entries
+            .iter()
+            .position(|entry| entry.key == registered)
+            .ok_or_else(|| SnapshotError::UnknownKey {
+                key: key.to_string(),
+                suggestion: self.nearest(registered),
+            })
+    }
+
+    fn check<S, L>(&self, entry: &Entry<S, L>, value: &SavedValue) -> Result<(), SnapshotError> {
+        (entry.check)(&value.fields).map_err(|path| SnapshotError::InvalidField {
+            key: value.key.clone(),
+            path,
+        })
+    }
+
+    /// The registered key closest to `key`, comparing full keys and their
+    /// last path segments.
+    fn nearest(&self, key: &str) -> Option<String> {
+        let last = |key: &str| key.rsplit("::").next().unwrap_or(key).to_string();
+        self.registry
+            .keys()
+            .min_by_key(|candidate| {
+                let full = distance(key, candidate);
+                full.min(distance(&last(key), &last(candidate)))
+            })
+            .map(str::to_string)
+    }
+}
+
+fn map_fields(fields: &Fields, mapper: &EntityMapper) -> Vec<(String, FieldValue)> {
+    fields
+        .iter()
+        .map(|(path, value)| match value {
+            FieldValue::Entity(entity) => (path.clone(), FieldValue::Entity(mapper.map(*entity))),
+            value => (path.clone(), value.clone()),
+        })
+        .collect()
+}
+
+/// Edit distance between `a` and `b`, by characters.
+fn distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, a) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b) in b.iter().enumerate() {
+            let substitute = diagonal + usize::from(a != *b);
+            diagonal = row[j + 1];
+            row[j + 1] = substitute.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+impl World {
+    /// Saves every registered component of every entity, by entity id, and
+    /// every registered resource.
+    pub fn snapshot(&self, registry: &SnapshotRegistry) -> Snapshot {
+        let mut entities = self.entities.iter().collect::<Vec<_>>();
+        entities.sort_by_key(|entity| (entity.id(), entity.generation()));
+
+        let entities = entities
+            .into_iter()
+            .map(|entity| SavedEntity {
+                entity,
+                components: registry
+                    .components
+                    .iter()
+                    .filter_map(|entry| {
+                        Some(SavedValue {
+                            key: entry.key.to_string(),
+                            fields: (entry.save)(self, entity)?,
+                        })
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        let resources = registry
+            .resources
+            .iter()
+            .filter_map(|entry| {
+                Some(SavedValue {
+                    key: entry.key.to_string(),
+                    fields: (entry.save)(self)?,
+                })
+            })
+            .collect();
+
+        let warnings = registry
+            .components
+            .iter()
+            .map(|entry| (entry.key, entry.stable))
+            .chain(registry.resources.iter().map(|e| (e.key, e.stable)))
+            .filter(|(_, stable)| !stable)
+            .map(|(key, _)| format!("{} has no stable id, saved under its type name", key))
+            .collect();
+
+        Snapshot {
+            warnings,
+            entities,
+            resources,
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use super::World;
+use crate::{
+    core::{Component, ComponentId, Entity},
+    storage::blob::Blob,
+    system::observer::builtin::Bundle,
+};
+
+/// An entity being built by `World::spawn`. Components are collected and
+/// moved in together when the builder finishes or drops, so the entity
+/// goes from the empty archetype straight to its final one.
+pub struct EntityWorldMut<'w> {
+    world: &'w mut World,
+    entity: Entity,
+    components: Vec<(ComponentId, Blob)>,
+}
+
+impl EntityWorldMut<'_> {
+    pub fn id(&self) -> Entity {
+        self.entity
+    }
+
+    /// Adds `component`, replacing one of the same type inserted earlier.
+    pub fn insert<C: Component>(&mut self, component: C) -> &mut Self {
+        self.world.register::<C>();
+        let id = self.world.component_id::<C>();
+        let mut value = Blob::new::<C>();
+        value.push(component);
+
+        self.push(id, value);
+        self
+    }
+
+    pub fn insert_bundle<B: Bundle>(&mut self, bundle: B) -> &mut Self {
+        B::register(self.world);
+        let values = bundle.into_values(self.world.components());
+        for (id, value) in values {
+            self.push(id, value);
+        }
+
+        self
+    }
+
+    /// Moves the collected components in and returns the entity.
+    pub fn finish(mut self) -> Entity {
+        self.flush();
+        self.entity
+    }
+
+    fn push(&mut self, id: ComponentId, value: Blob) {
+        self.components.retain(|(other, _)| *other != id);
+        self.components.push((id, value));
+    }
+
+    fn flush(&mut self) {
+        if !self.components.is_empty() {
+            let components = std::mem::take(&mut self.components);
+            self.world.edit_entity(self.entity, components, &[]);
+        }
+    }
+}
+
+impl Drop for EntityWorldMut<'_> {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+impl World {
+    /// Creates an entity and returns a builder for its components, e.g.
+    /// `world.spawn().insert(a).insert(b).id()`. Like `add_component`,
+    /// nothing is queued for observers.
+    pub fn spawn(&mut self) -> EntityWorldMut<'_> {
+        let entity = self.create();
+        EntityWorldMut {
+            world: self,
+            entity,
+            components: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use std::{fmt::Display, sync::Mutex};
+
+pub const DEFAULT_TRACE_CAP: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TraceLevel {
+    Info,
+    Warn,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEntry {
+    frame: u64,
+    level: TraceLevel,
+    source: &'static str,
+    message: String,
+}
+
+impl TraceEntry {
+    pub fn new(frame: u64, level: TraceLevel, source: &'static str, message: String) -> Self {
+        Self {
+            frame,
+            level,
+            source,
+            message,
+        }
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn level(&self) -> TraceLevel {
+        self.level
+    }
+
+    pub fn source(&self) -> &'static str {
+        self.source
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl Display for TraceEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[frame {}] {:?} {}: {}",
+            self.frame, self.level, self.source, self.message
+        )
+    }
+}
+
+/// Bounded log of diagnostics (skipped actions, warnings) raised by the world.
+/// Entries can be pushed through a shared reference so systems and queries
+/// running in parallel can report as well.
+pub struct Trace {
+    entries: Mutex<Vec<TraceEntry>>,
+    cap: usize,
+}
+
+impl Trace {
+    pub fn new() -> Self {
+        Self {
+            entries: Mutex::new(Vec::new()),
+            cap: DEFAULT_TRACE_CAP,
+        }
+    }
+
+    pub fn push(&self, entry: TraceEntry) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.cap {
+            let overflow = entries.len() + 1 - self.cap.max(1);
+            entries.drain(..overflow);
+        }
+        entries.push(entry);
+    }
+
+    pub fn entries(&self) -> Vec<TraceEntry> {
+        self.entries.lock().unwrap().clone()
+    }
+
+    pub fn drain(&self) -> Vec<TraceEntry> {
+        std::mem::take(&mut *self.entries.lock().unwrap())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn set_cap(&mut self, cap: usize) {
+        self.cap = cap;
+    }
+}
+
+impl Default for Trace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
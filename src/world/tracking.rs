@@ -0,0 +1,227 @@
+use super::provenance::Provenance;
+use crate::{archetype::ArchetypeId, core::Entity, storage::sparse::SparseMap};
+use std::fmt::Display;
+
+pub const DEFAULT_TRACKING_CAP: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrackingEvent {
+    Tracked,
+    /// Where the entity came from, when provenance was recorded.
+    Spawned(Provenance),
+    AddComponent {
+        component: &'static str,
+        from: ArchetypeId,
+        to: ArchetypeId,
+    },
+    RemoveComponent {
+        component: &'static str,
+        from: ArchetypeId,
+        to: ArchetypeId,
+    },
+    ParentChange {
+        old: Option<Entity>,
+        new: Option<Entity>,
+    },
+    Delete,
+}
+
+impl Display for TrackingEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrackingEvent::Tracked => write!(f, "tracked"),
+            TrackingEvent::Spawned(provenance) => write!(f, "{}", provenance),
+            TrackingEvent::AddComponent {
+                component,
+                from,
+                to,
+            } => write!(
+                f,
+                "added {} (archetype {} -> {})",
+                component,
+                from.id(),
+                to.id()
+            ),
+            TrackingEvent::RemoveComponent {
+                component,
+                from,
+                to,
+            } => write!(
+                f,
+                "removed {} (archetype {} -> {})",
+                component,
+                from.id(),
+                to.id()
+            ),
+            TrackingEvent::ParentChange { old, new } => {
+                write!(f, "parent changed ({:?} -> {:?})", old, new)
+            }
+            TrackingEvent::Delete => write!(f, "deleted"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrackingRecord {
+    frame: u64,
+    event: TrackingEvent,
+}
+
+impl TrackingRecord {
+    pub fn new(frame: u64, event: TrackingEvent) -> Self {
+        Self { frame, event }
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn event(&self) -> &TrackingEvent {
+        &self.event
+    }
+}
+
+impl Display for TrackingRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[frame {}] {}", self.frame, self.event)
+    }
+}
+
+pub struct TrackingLog {
+    entity: Entity,
+    label: String,
+    records: Vec<TrackingRecord>,
+    dropped: usize,
+}
+
+impl TrackingLog {
+    pub fn new(entity: Entity, label: &str) -> Self {
+        Self {
+            entity,
+            label: label.to_string(),
+            records: Vec::new(),
+            dropped: 0,
+        }
+    }
+
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn records(&self) -> &[TrackingRecord] {
+        &self.records
+    }
+
+    /// Number of records discarded because the log hit the tracking cap.
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    fn push(&mut self, record: TrackingRecord, cap: usize) {
+        if cap == 0 {
+            self.dropped += 1;
+            return;
+        }
+
+        if self.records.len() >= cap {
+            let overflow = self.records.len() + 1 - cap;
+            self.records.drain(..overflow);
+            self.dropped += overflow;
+        }
+
+        self.records.push(record);
+    }
+}
+
+impl Display for TrackingLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Tracking log for {:?} ({}):", self.entity, self.label)?;
+        if self.dropped > 0 {
+            writeln!(f, "  ... {} earlier records dropped", self.dropped)?;
+        }
+        for record in &self.records {
+            writeln!(f, "  {}", record)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Per-entity debug logs for entities registered with `World::track`.
+/// Logs of deleted entities are kept until they are untracked.
+pub struct TrackedEntities {
+    active: SparseMap<Entity, TrackingLog>,
+    finished: SparseMap<Entity, TrackingLog>,
+    cap: usize,
+}
+
+impl TrackedEntities {
+    pub fn new() -> Self {
+        Self {
+            active: SparseMap::new(),
+            finished: SparseMap::new(),
+            cap: DEFAULT_TRACKING_CAP,
+        }
+    }
+
+    /// True when no live entity is tracked. This is the only check the
+    /// lifecycle paths pay for when tracking is unused.
+    pub fn is_empty(&self) -> bool {
+        self.active.is_empty()
+    }
+
+    pub fn is_tracked(&self, entity: Entity) -> bool {
+        self.active.contains(&entity)
+    }
+
+    pub fn track(&mut self, entity: Entity, label: &str, frame: u64) {
+        let mut log = TrackingLog::new(entity, label);
+        log.push(TrackingRecord::new(frame, TrackingEvent::Tracked), self.cap);
+        self.finished.remove(&entity);
+        self.active.insert(entity, log);
+    }
+
+    pub fn untrack(&mut self, entity: Entity) -> Option<TrackingLog> {
+        self.active
+            .remove(&entity)
+            .or_else(|| self.finished.remove(&entity))
+    }
+
+    pub fn record(&mut self, entity: Entity, frame: u64, event: TrackingEvent) {
+        if let Some(log) = self.active.get_mut(&entity) {
+            log.push(TrackingRecord::new(frame, event), self.cap);
+        }
+    }
+
+    /// Records the deletion and retires the log, returning it for dumping.
+    pub fn finish(&mut self, entity: Entity, frame: u64) -> Option<&TrackingLog> {
+        let mut log = self.active.remove(&entity)?;
+        log.push(TrackingRecord::new(frame, TrackingEvent::Delete), self.cap);
+        self.finished.insert(entity, log);
+        self.finished.get(&entity)
+    }
+
+    pub fn log(&self, entity: Entity) -> Option<&TrackingLog> {
+        self.active
+            .get(&entity)
+            .or_else(|| self.finished.get(&entity))
+    }
+
+    pub fn cap(&self) -> usize {
+        self.cap
+    }
+
+    pub fn set_cap(&mut self, cap: usize) {
+        self.cap = cap;
+    }
+}
+
+impl Default for TrackedEntities {
+    fn default() -> Self {
+        Self::new()
+    }
+}
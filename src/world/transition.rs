@@ -0,0 +1,185 @@
+use super::World;
+use crate::{
+    archetype::ArchetypeId,
+    core::Entity,
+    system::observer::{action::ActionOutputs, builtin::CreateEntity, Action},
+};
+use std::collections::HashMap;
+
+/// An entity moved from one archetype to another. Only emitted once enabled
+/// with `World::enable_transition_events`.
+///
+/// `from` is `ArchetypeId::NONE` for newly created entities and `to` is
+/// `ArchetypeId::NONE` for deleted ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ArchetypeTransition {
+    pub entity: Entity,
+    pub from: ArchetypeId,
+    pub to: ArchetypeId,
+}
+
+impl ArchetypeTransition {
+    pub fn new(entity: Entity, from: ArchetypeId, to: ArchetypeId) -> Self {
+        Self { entity, from, to }
+    }
+
+    pub fn is_spawn(&self) -> bool {
+        self.from == ArchetypeId::NONE
+    }
+
+    pub fn is_despawn(&self) -> bool {
+        self.to == ArchetypeId::NONE
+    }
+}
+
+impl Action for ArchetypeTransition {
+    type Output = ArchetypeTransition;
+    const PRIORITY: u32 = CreateEntity::PRIORITY - 20;
+
+    fn execute(&mut self, _: &mut World) -> Self::Output {
+        *self
+    }
+}
+
+/// Transitions recorded but not yet handed to observers. Moves are also
+/// kept, uncoalesced, for matchers while `matching` is set, whether or not
+/// transition events are enabled.
+pub struct Transitions {
+    enabled: bool,
+    coalesce: bool,
+    matching: bool,
+    pending: Vec<ArchetypeTransition>,
+    index: HashMap<Entity, usize>,
+    moves: Vec<ArchetypeTransition>,
+}
+
+impl Transitions {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            coalesce: false,
+            matching: false,
+            pending: Vec::new(),
+            index: HashMap::new(),
+            moves: Vec::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// True if moves need to be recorded, for events or for matchers.
+    pub fn is_recording(&self) -> bool {
+        self.enabled || self.matching
+    }
+
+    pub fn set_matching(&mut self, matching: bool) {
+        self.matching = matching;
+        if !matching {
+            self.moves.clear();
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.pending.clear();
+            self.index.clear();
+        }
+    }
+
+    pub fn coalesce(&self) -> bool {
+        self.coalesce
+    }
+
+    pub fn set_coalesce(&mut self, coalesce: bool) {
+        self.coalesce = coalesce;
+    }
+
+    pub fn record(&mut self, entity: Entity, from: ArchetypeId, to: ArchetypeId) {
+        if from == to {
+            return;
+        }
+
+        if self.matching {
+            self.moves.push(ArchetypeTransition::new(entity, from, to));
+        }
+
+        if !self.enabled {
+            return;
+        }
+
+        if self.coalesce {
+            if let Some(index) = self.index.get(&entity) {
+                self.pending[*index].to = to;
+                return;
+            }
+
+            self.index.insert(entity, self.pending.len());
+        }
+
+        self.pending
+            .push(ArchetypeTransition::new(entity, from, to));
+    }
+
+    /// True if no events or matcher moves are waiting.
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty() && self.moves.is_empty()
+    }
+
+    /// True if a waiting event or matcher move starts or ends in `id`.
+    pub fn references(&self, id: ArchetypeId) -> bool {
+        self.pending
+            .iter()
+            .chain(self.moves.iter())
+            .any(|transition| transition.from == id || transition.to == id)
+    }
+
+    pub fn clear(&mut self) {
+        self.pending.clear();
+        self.index.clear();
+        self.moves.clear();
+    }
+
+    /// Moves recorded for matchers since the last call, in order.
+    pub fn take_moves(&mut self) -> Vec<ArchetypeTransition> {
+        std::mem::take(&mut self.moves)
+    }
+
+    /// Moves the pending transitions into `outputs`, in the order they were
+    /// recorded.
+    pub fn drain_into(&mut self, outputs: &mut ActionOutputs) {
+        self.index.clear();
+        for transition in self.pending.drain(..) {
+            outputs.add::<ArchetypeTransition>(transition);
+        }
+    }
+}
+
+impl Default for Transitions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl World {
+    /// Emits an `ArchetypeTransition` output whenever an entity is created,
+    /// deleted or changes archetype. Transitions are delivered with the flush
+    /// wave that caused them, in the order they happened.
+    pub fn enable_transition_events(&mut self, enabled: bool) {
+        self.transitions.set_enabled(enabled);
+    }
+
+    /// Merges the transitions of an entity into a single first-to-last event.
+    /// Coalesced events are held back until the other actions of the flush
+    /// have settled, then delivered in a wave of their own. A round trip
+    /// still produces one event with `from == to`.
+    pub fn coalesce_transition_events(&mut self, coalesce: bool) {
+        self.transitions.set_coalesce(coalesce);
+    }
+
+    pub fn transition_events_enabled(&self) -> bool {
+        self.transitions.is_enabled()
+    }
+}
@@ -0,0 +1,109 @@
+#[allow(dead_code)]
+#[path = "../examples/basic_game.rs"]
+mod basic_game;
+
+use basic_game::{Equipped, GameLog, Player, Position, Velocity};
+use ecs::prelude::*;
+
+#[test]
+fn basic_game_runs_like_the_example() {
+    let mut world = basic_game::setup();
+
+    let players = Query::<(Entity, &Player)>::new(&world)
+        .map(|(entity, _)| entity)
+        .collect::<Vec<_>>();
+    assert_eq!(players.len(), 1);
+
+    let children = world.entities().children(players[0], false);
+    assert_eq!(children.len(), 2);
+    assert_eq!(Query::<Entity, With<Equipped>>::new(&world).count(), 2);
+    assert_eq!(world.resource::<GameLog>().equipped, 2);
+
+    for _ in 0..4 {
+        basic_game::frame(&mut world, 0.25);
+    }
+
+    let log = world.resource::<GameLog>();
+    assert_eq!(log.fixed_steps, 60);
+    assert_eq!(
+        log.lines,
+        vec![
+            "player hp 100 at (0.50, 0.00)",
+            "player hp 100 at (0.00, 0.00)",
+            "player hp 100 at (-0.50, 0.00)",
+            "player hp 100 at (-1.00, 0.00)",
+        ]
+    );
+    drop(log);
+    assert_eq!(
+        world.component::<Velocity>(players[0]).as_deref(),
+        Some(&Velocity { x: -2.0, y: 0.0 })
+    );
+
+    basic_game::shutdown(&mut world);
+
+    assert_eq!(world.resource::<GameLog>().deleted, 3);
+    assert!(world.entities().is_empty());
+    assert_eq!(Query::<&Position>::new(&world).count(), 0);
+    assert!(world.check_consistency().is_ok());
+}
+
+fn debug_registry() -> DebugDumpRegistry {
+    let mut registry = DebugDumpRegistry::new();
+    registry
+        .register_component::<Player>()
+        .register_component::<Position>()
+        .register_component::<Velocity>();
+    registry
+}
+
+fn basic_game_after(frames: usize) -> World {
+    let mut world = basic_game::setup();
+    for _ in 0..frames {
+        basic_game::frame(&mut world, 0.25);
+    }
+    world
+}
+
+#[test]
+fn basic_game_matches_its_golden_dump() {
+    let world = basic_game_after(4);
+    ecs::assert_world_snapshot!(
+        world,
+        &debug_registry(),
+        "tests/golden/basic_game_frame_4.txt"
+    );
+}
+
+#[test]
+fn perturbed_values_show_up_in_the_golden_diff() {
+    let world = basic_game_after(4);
+    let player = Query::<(Entity, &Player)>::new(&world)
+        .map(|(entity, _)| entity)
+        .next()
+        .unwrap();
+    let before = format!("{:?}", world.component::<Position>(player).unwrap());
+    world.component_mut::<Position>(player).unwrap().x = 42.0;
+    let after = format!("{:?}", world.component::<Position>(player).unwrap());
+
+    let panic = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        ecs::assert_world_snapshot!(
+            world,
+            &debug_registry(),
+            "tests/golden/basic_game_frame_4.txt"
+        );
+    }))
+    .unwrap_err();
+    let message = panic.downcast_ref::<String>().unwrap();
+
+    assert!(message.contains("doesn't match"), "{message}");
+    let removed = message
+        .lines()
+        .find(|line| line.starts_with('-') && line.contains(&before))
+        .unwrap_or_else(|| panic!("{message}"));
+    let added = message
+        .lines()
+        .find(|line| line.starts_with('+') && line.contains(&after))
+        .unwrap_or_else(|| panic!("{message}"));
+    assert_eq!(removed[1..].replace(&before, &after), added[1..]);
+}
@@ -0,0 +1,566 @@
+use ecs::{
+    core::OrphanPolicy, prelude::*, system::observer::builtin::HierarchyChange,
+    world::trace::TraceLevel,
+};
+
+struct Update;
+
+impl SchedulePhase for Update {
+    const PHASE: &'static str = "update";
+}
+
+#[derive(Default)]
+struct Notified {
+    deleted: Vec<Entity>,
+    reparented: Vec<Entity>,
+}
+
+impl Resource for Notified {}
+
+fn deleted(entities: &[Entity], notified: &mut Notified) {
+    notified.deleted.extend_from_slice(entities);
+}
+
+fn reparented(entities: &[Entity], notified: &mut Notified) {
+    notified.reparented.extend_from_slice(entities);
+}
+
+/// A grandparent with one child, `parent`, which has two children.
+struct Family {
+    grandparent: Entity,
+    parent: Entity,
+    children: [Entity; 2],
+}
+
+fn family(world: &mut World) -> Family {
+    let grandparent = world.spawn().finish();
+    let parent = world.spawn().finish();
+    let children = [world.spawn().finish(), world.spawn().finish()];
+    world.add_child(grandparent, parent);
+    for child in children {
+        world.add_child(parent, child);
+    }
+
+    Family {
+        grandparent,
+        parent,
+        children,
+    }
+}
+
+/// Deletes the middle of a fresh family with `policy` through the
+/// `DeleteEntity` action.
+fn delete_parent(policy: OrphanPolicy) -> (World, Family) {
+    let mut world = World::new();
+    world.add_resource(Notified::default());
+    world.add_observers(Observers::<DeleteEntity>::new().add_system(deleted));
+    world.add_observers(Observers::<HierarchyChange>::new().add_system(reparented));
+    let family = family(&mut world);
+
+    world
+        .resource_mut::<Actions>()
+        .add(DeleteEntity::new(family.parent).orphans(policy));
+    world.run::<Update>();
+
+    (world, family)
+}
+
+#[test]
+fn despawn_deletes_the_whole_subtree() {
+    let (world, family) = delete_parent(OrphanPolicy::Despawn);
+    let entities = world.entities();
+
+    assert!(entities.contains(family.grandparent));
+    assert!(!entities.contains(family.parent));
+    assert!(family
+        .children
+        .iter()
+        .all(|child| !entities.contains(*child)));
+    assert!(entities.children_of(family.grandparent).is_empty());
+
+    let notified = world.resource::<Notified>();
+    let mut deleted = notified.deleted.clone();
+    deleted.sort_by_key(|entity| entity.id());
+    assert_eq!(
+        deleted,
+        vec![family.parent, family.children[0], family.children[1]]
+    );
+    assert!(notified.reparented.is_empty());
+}
+
+#[test]
+fn reparent_to_grandparent_keeps_the_children_in_the_tree() {
+    let (world, family) = delete_parent(OrphanPolicy::ReparentToGrandparent);
+    let entities = world.entities();
+
+    assert!(!entities.contains(family.parent));
+    for child in family.children {
+        assert!(entities.contains(child));
+        assert_eq!(entities.parent(child), Some(family.grandparent));
+        assert_eq!(entities.depth(child), Some(1));
+    }
+    assert_eq!(entities.children_of(family.grandparent), &family.children);
+
+    let notified = world.resource::<Notified>();
+    assert_eq!(notified.deleted, vec![family.parent]);
+    assert_eq!(notified.reparented, family.children.to_vec());
+    assert!(world.check_consistency().is_ok());
+}
+
+#[test]
+fn reparent_to_root_detaches_the_children() {
+    let (world, family) = delete_parent(OrphanPolicy::ReparentToRoot);
+    let entities = world.entities();
+
+    assert!(!entities.contains(family.parent));
+    for child in family.children {
+        assert!(entities.contains(child));
+        assert_eq!(entities.parent(child), None);
+        assert_eq!(entities.depth(child), Some(0));
+    }
+    assert!(entities.children_of(family.grandparent).is_empty());
+
+    let notified = world.resource::<Notified>();
+    assert_eq!(notified.deleted, vec![family.parent]);
+    assert_eq!(notified.reparented, family.children.to_vec());
+    assert!(world.check_consistency().is_ok());
+}
+
+#[test]
+fn deleting_a_middle_child_detaches_it() {
+    let mut world = World::new();
+    let parent = world.spawn().finish();
+    let children = (0..3).map(|_| world.spawn().finish()).collect::<Vec<_>>();
+    for child in &children {
+        world.add_child(parent, *child);
+    }
+
+    world.delete(children[1]);
+
+    assert_eq!(
+        world.entities().children_of(parent),
+        &[children[0], children[2]]
+    );
+    assert_eq!(world.entities().children(parent, true).len(), 2);
+    assert!(world.check_consistency().is_ok());
+
+    // A dead entity can't be attached on either side.
+    world.add_child(parent, children[1]);
+    world.add_child(children[1], children[0]);
+    assert_eq!(world.entities().children_of(parent).len(), 2);
+    assert_eq!(world.entities().parent(children[0]), Some(parent));
+}
+
+#[test]
+fn reparenting_keeps_grandchildren_links() {
+    let mut world = World::new();
+    let Family {
+        grandparent,
+        parent,
+        children,
+    } = family(&mut world);
+    let grandchild = world.spawn().finish();
+    world.add_child(children[0], grandchild);
+
+    let deleted = world.delete_with_policy(parent, OrphanPolicy::ReparentToGrandparent);
+
+    assert_eq!(deleted, vec![parent]);
+    let entities = world.entities();
+    assert_eq!(entities.children_of(grandparent), &children);
+    assert_eq!(entities.parent(grandchild), Some(children[0]));
+    assert_eq!(entities.children_of(children[0]), &[grandchild]);
+    assert_eq!(entities.depth(grandchild), Some(2));
+    assert!(world.check_consistency().is_ok());
+}
+
+#[test]
+fn deleted_ids_come_back_with_a_new_generation() {
+    let mut world = World::new();
+    let parent = world.spawn().finish();
+    let stale = world.spawn().finish();
+    let child = world.spawn().finish();
+    world.add_child(parent, stale);
+    world.add_child(stale, child);
+
+    let mut deleted = world.delete(stale);
+    deleted.sort_by_key(|entity| entity.id());
+    assert_eq!(deleted, vec![stale, child]);
+    let fresh = world.spawn().finish();
+    assert_eq!(fresh.id(), stale.id());
+    assert_ne!(fresh, stale);
+
+    let entities = world.entities();
+    assert!(entities.contains(fresh));
+    assert!(!entities.contains(stale));
+    assert_eq!(entities.parent(fresh), None);
+    assert!(entities.children_of(fresh).is_empty());
+    assert!(entities.children_of(parent).is_empty());
+
+    // The stale handle reaches neither the fresh entity nor its links.
+    let other = world.spawn().finish();
+    world.add_child(stale, other);
+    world.add_child(parent, stale);
+    assert!(world.delete(stale).is_empty());
+    assert!(world.entities().contains(fresh));
+    assert!(world.entities().children_of(fresh).is_empty());
+    assert!(world.entities().children_of(parent).is_empty());
+    assert_eq!(world.entities().parent(other), None);
+    assert!(world.check_consistency().is_ok());
+}
+
+#[test]
+fn deleting_twice_is_a_no_op() {
+    let mut world = World::new();
+    world.add_resource(Notified::default());
+    world.add_observers(Observers::<DeleteEntity>::new().add_system(deleted));
+    let family = family(&mut world);
+    let len = world.entities().len();
+
+    assert_eq!(world.delete(family.children[0]), vec![family.children[0]]);
+    assert!(world.delete(family.children[0]).is_empty());
+    assert_eq!(world.entities().len(), len - 1);
+    assert_eq!(
+        world.entities().children_of(family.parent),
+        &[family.children[1]]
+    );
+
+    let mut actions = world.resource_mut::<Actions>();
+    actions.add(DeleteEntity::new(family.children[1]));
+    actions.add(DeleteEntity::new(family.children[1]));
+    drop(actions);
+    world.run::<Update>();
+    world
+        .resource_mut::<Actions>()
+        .add(DeleteEntity::new(family.children[1]));
+    world.run::<Update>();
+
+    assert_eq!(
+        world.resource::<Notified>().deleted,
+        vec![family.children[1]]
+    );
+    assert_eq!(world.entities().len(), len - 2);
+    assert!(world.entities().children_of(family.parent).is_empty());
+    assert!(world.check_consistency().is_ok());
+}
+
+#[test]
+fn deleting_a_grandparent_follows_each_policy() {
+    for policy in [
+        OrphanPolicy::Despawn,
+        OrphanPolicy::ReparentToGrandparent,
+        OrphanPolicy::ReparentToRoot,
+    ] {
+        let mut world = World::new();
+        let family = family(&mut world);
+
+        let mut deleted = world.delete_with_policy(family.grandparent, policy);
+        deleted.sort_by_key(|entity| entity.id());
+        let entities = world.entities();
+        assert!(!entities.contains(family.grandparent), "{policy:?}");
+
+        match policy {
+            OrphanPolicy::Despawn => {
+                assert_eq!(
+                    deleted,
+                    vec![
+                        family.grandparent,
+                        family.parent,
+                        family.children[0],
+                        family.children[1],
+                    ]
+                );
+                assert_eq!(entities.len(), 0);
+                assert!(entities.roots().is_empty());
+            }
+            // The grandparent was a root, so both policies leave the parent
+            // as one with its children still attached.
+            _ => {
+                assert_eq!(deleted, vec![family.grandparent], "{policy:?}");
+                assert_eq!(entities.parent(family.parent), None);
+                assert_eq!(entities.depth(family.parent), Some(0));
+                assert_eq!(entities.roots(), &[family.parent]);
+                assert_eq!(entities.children_of(family.parent), &family.children);
+                for child in family.children {
+                    assert_eq!(entities.parent(child), Some(family.parent));
+                    assert_eq!(entities.depth(child), Some(1));
+                }
+            }
+        }
+        assert!(world.check_consistency().is_ok(), "{policy:?}");
+    }
+}
+
+#[test]
+fn hierarchy_stays_consistent_under_churn() {
+    let mut world = World::new();
+    let mut alive = Vec::<Entity>::new();
+    let mut dead = Vec::<Entity>::new();
+    let mut seed = 0x2545_f491_4f6c_dd1du64;
+    let mut next = |bound: usize| {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        (seed % bound as u64) as usize
+    };
+
+    for step in 0..1_000 {
+        match next(6) {
+            0 | 1 => alive.push(world.spawn().finish()),
+            2 if alive.len() > 1 => {
+                let parent = alive[next(alive.len())];
+                let child = alive[next(alive.len())];
+                world.add_child(parent, child);
+            }
+            3 if !alive.is_empty() => {
+                world.set_parent(alive[next(alive.len())], None);
+            }
+            4 if !alive.is_empty() => {
+                let entity = alive[next(alive.len())];
+                let policy = [
+                    OrphanPolicy::Despawn,
+                    OrphanPolicy::ReparentToGrandparent,
+                    OrphanPolicy::ReparentToRoot,
+                ][next(3)];
+                dead.extend(world.delete_with_policy(entity, policy));
+                alive.retain(|entity| world.entities().contains(*entity));
+            }
+            5 if !alive.is_empty() && !dead.is_empty() => {
+                let entity = alive[next(alive.len())];
+                let stale = dead[next(dead.len())];
+                world.add_child(entity, stale);
+                world.add_child(stale, entity);
+            }
+            _ => {}
+        }
+
+        if let Err(errors) = world.check_consistency() {
+            panic!("step {}: {:?}", step, errors);
+        }
+    }
+
+    assert!(dead
+        .iter()
+        .all(|entity| !world.entities().contains(*entity)));
+}
+
+/// Checks cached depths, roots and subtree sizes against walks of the
+/// parent and child links.
+fn assert_cached_hierarchy(world: &World, alive: &[Entity]) {
+    let entities = world.entities();
+    for entity in alive {
+        let mut depth = 0;
+        let mut parent = entities.parent(*entity);
+        while let Some(next) = parent {
+            depth += 1;
+            parent = entities.parent(next);
+        }
+        assert_eq!(entities.depth(*entity), Some(depth), "{:?}", entity);
+        assert_eq!(entities.is_root(*entity), depth == 0);
+
+        let mut size = 0;
+        let mut stack = vec![*entity];
+        while let Some(next) = stack.pop() {
+            size += 1;
+            stack.extend_from_slice(entities.children_of(next));
+        }
+        assert_eq!(entities.subtree_size(*entity), Some(size));
+    }
+
+    let mut roots = entities.roots().to_vec();
+    roots.sort_by_key(|entity| entity.id());
+    let mut expected = alive
+        .iter()
+        .copied()
+        .filter(|entity| entities.parent(*entity).is_none())
+        .collect::<Vec<_>>();
+    expected.sort_by_key(|entity| entity.id());
+    assert_eq!(roots, expected);
+}
+
+#[test]
+fn cached_depths_and_roots_match_the_links() {
+    let mut world = World::new();
+    let mut alive = (0..64).map(|_| world.spawn().finish()).collect::<Vec<_>>();
+    let mut seed = 0x9e37_79b9_7f4a_7c15u64;
+    let mut next = |bound: usize| {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        (seed % bound as u64) as usize
+    };
+
+    for _ in 0..400 {
+        let entity = alive[next(alive.len())];
+        let other = alive[next(alive.len())];
+        match next(5) {
+            0 | 1 => world.add_child(other, entity),
+            2 => world.set_parent(entity, None),
+            3 => {
+                if let Some(parent) = world.entities().parent(entity) {
+                    world.remove_child(parent, entity);
+                }
+            }
+            _ => {
+                world.delete_with_policy(entity, OrphanPolicy::ReparentToGrandparent);
+                alive.retain(|entity| world.entities().contains(*entity));
+                alive.push(world.spawn().finish());
+            }
+        }
+        assert_cached_hierarchy(&world, &alive);
+    }
+}
+
+#[test]
+fn reparenting_a_deep_subtree_updates_every_depth() {
+    let mut world = World::new();
+    let chain = (0..40).map(|_| world.spawn().finish()).collect::<Vec<_>>();
+    for pair in chain.windows(2) {
+        world.add_child(pair[0], pair[1]);
+    }
+    let other = (0..10).map(|_| world.spawn().finish()).collect::<Vec<_>>();
+    for pair in other.windows(2) {
+        world.add_child(pair[0], pair[1]);
+    }
+
+    // Moves the lower half of the chain under the bottom of `other`.
+    world.set_parent(chain[20], Some(other[9]));
+    for (index, entity) in chain[20..].iter().enumerate() {
+        assert_eq!(world.entities().depth(*entity), Some(10 + index as u16));
+    }
+    assert_eq!(world.entities().subtree_size(other[0]), Some(30));
+    assert_eq!(world.entities().subtree_size(chain[0]), Some(20));
+
+    world.set_parent(chain[20], None);
+    assert!(world.entities().is_root(chain[20]));
+    assert_eq!(world.entities().depth(chain[39]), Some(19));
+
+    let alive = chain.iter().chain(&other).copied().collect::<Vec<_>>();
+    assert_cached_hierarchy(&world, &alive);
+}
+
+fn traced(world: &World, level: TraceLevel, source: &str) -> Vec<String> {
+    world
+        .traces()
+        .into_iter()
+        .filter(|entry| entry.level() == level && entry.source() == source)
+        .map(|entry| entry.message().to_string())
+        .collect()
+}
+
+#[test]
+fn named_parents_resolve_when_the_action_runs() {
+    let mut world = World::new();
+    world.register_names(NamePolicy::Unique);
+    let root = world.spawn().insert(Name::new("level_root")).id();
+    let child = world.spawn().finish();
+    assert_eq!(world.find_named("level_root"), Some(root));
+
+    world
+        .resource_mut::<Actions>()
+        .add(SetParent::by_name(child, "level_root"));
+    world.run::<Update>();
+    assert_eq!(world.entities().parent(child), Some(root));
+
+    world
+        .resource_mut::<Actions>()
+        .add(SetComponent::new(root, Name::new("menu_root")));
+    world.run::<Update>();
+    assert_eq!(world.find_named("level_root"), None);
+    assert_eq!(world.find_named("menu_root"), Some(root));
+
+    let stray = world.spawn().finish();
+    world
+        .resource_mut::<Actions>()
+        .add(SetParent::by_name(stray, "level_root"));
+    world.run::<Update>();
+    assert_eq!(world.entities().parent(stray), None);
+    assert_eq!(
+        traced(&world, TraceLevel::Warn, "SetParent"),
+        vec!["no entity is named level_root"]
+    );
+
+    world.delete(root);
+    assert_eq!(world.find_named("menu_root"), None);
+}
+
+#[test]
+fn duplicate_names_follow_the_policy() {
+    let mut unique = World::new();
+    unique.register_names(NamePolicy::Unique);
+    let first = unique.spawn().insert(Name::new("guard")).id();
+    let second = unique.spawn().insert(Name::new("guard")).id();
+    let recruit = unique.spawn().finish();
+
+    let errors = traced(&unique, TraceLevel::Error, "names");
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].contains(&format!("{first:?}")));
+    assert!(errors[0].contains(&format!("{second:?}")));
+    assert_eq!(unique.find_named("guard"), None);
+
+    unique
+        .resource_mut::<Actions>()
+        .add(SetParent::by_name(recruit, "guard"));
+    unique.run::<Update>();
+    assert_eq!(unique.entities().parent(recruit), None);
+    assert_eq!(
+        traced(&unique, TraceLevel::Warn, "SetParent"),
+        vec!["2 entities are named guard"]
+    );
+
+    let mut multi = World::new();
+    multi.register_names(NamePolicy::Multi);
+    let first = multi.spawn().insert(Name::new("guard")).id();
+    let second = multi.spawn().insert(Name::new("guard")).id();
+    assert_eq!(multi.find_all_named("guard"), &[first, second]);
+    assert!(traced(&multi, TraceLevel::Error, "names").is_empty());
+}
+
+/// The `Parent` and `Children` items of `entity`, as a query sees them.
+fn links(world: &World, entity: Entity) -> (Option<Entity>, Vec<Entity>) {
+    let query = Query::<(Parent, Children)>::new(world);
+    let (parent, children) = query.get(entity).unwrap();
+    (*parent, children.to_vec())
+}
+
+#[test]
+fn parent_and_children_items_follow_reparenting() {
+    let mut world = World::new();
+    let first = world.spawn().finish();
+    let second = world.spawn().finish();
+    let middle = world.spawn().finish();
+    let leaves = [world.spawn().finish(), world.spawn().finish()];
+
+    let mut actions = world.resource_mut::<Actions>();
+    actions.add(AddChildren::new(first, vec![middle]));
+    actions.add(SetParent::new(leaves[0], Some(middle)));
+    actions.add(SetParent::new(leaves[1], Some(middle)));
+    drop(actions);
+    world.run::<Update>();
+
+    assert_eq!(links(&world, first), (None, vec![middle]));
+    assert_eq!(links(&world, middle), (Some(first), leaves.to_vec()));
+    assert_eq!(links(&world, leaves[0]), (Some(middle), vec![]));
+
+    world
+        .resource_mut::<Actions>()
+        .add(SetParent::new(middle, Some(second)));
+    world.run::<Update>();
+
+    assert_eq!(links(&world, first), (None, vec![]));
+    assert_eq!(links(&world, second), (None, vec![middle]));
+    assert_eq!(links(&world, middle), (Some(second), leaves.to_vec()));
+
+    world
+        .resource_mut::<Actions>()
+        .add(SetParent::new(leaves[0], None));
+    world.run::<Update>();
+
+    assert_eq!(links(&world, middle), (Some(second), vec![leaves[1]]));
+    assert_eq!(links(&world, leaves[0]), (None, vec![]));
+
+    let roots = Query::<(Entity, Parent)>::new(&world)
+        .filter(|(_, parent)| parent.is_none())
+        .map(|(entity, _)| entity)
+        .collect::<Vec<_>>();
+    assert_eq!(roots, vec![first, second, leaves[0]]);
+}
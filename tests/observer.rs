@@ -0,0 +1,1266 @@
+use ecs::{
+    archetype::ArchetypeId,
+    schedule::runner::RunMode,
+    storage::blob::Blob,
+    world::{meta::AccessType, trace::TraceLevel, transition::ArchetypeTransition},
+};
+use ecs::{
+    prelude::*,
+    system::observer::{
+        builtin::{Removal, RemovalCause},
+        ActionOutput, ActionOutputs, IntoObserver, Observables, ObserverSystems, DEFERRED_CHUNK,
+    },
+};
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+struct Update;
+
+impl SchedulePhase for Update {
+    const PHASE: &'static str = "update";
+}
+
+struct Main;
+
+impl ScheduleLabel for Main {
+    const LABEL: &'static str = "main";
+}
+
+#[derive(Default)]
+struct Fired {
+    names: Vec<&'static str>,
+}
+
+impl Resource for Fired {}
+
+fn added(_: &[Entity], fired: &mut Fired) {
+    fired.names.push("add_system");
+}
+
+fn prioritized(_: &[Entity], fired: &mut Fired) {
+    fired.names.push("add_system_with_priority");
+}
+
+fn chained(_: &[Entity], fired: &mut Fired) {
+    fired.names.push("chained");
+}
+
+fn chained_before(_: &[Entity], fired: &mut Fired) {
+    fired.names.push("before");
+}
+
+fn direct(_: &[Entity], fired: &mut Fired) {
+    fired.names.push("observables");
+}
+
+fn deferred(_: &[Entity], fired: &mut Fired) {
+    fired.names.push("deferred");
+}
+
+fn scheduled(_: &[Entity], fired: &mut Fired) {
+    fired.names.push("deliver_in");
+}
+
+fn spawn(actions: &mut Actions) {
+    actions.add(CreateEntity::new());
+}
+
+#[test]
+fn every_registration_path_fires() {
+    let mut world = World::new();
+    world.add_resource(Fired::default());
+    world.add_observers(
+        Observers::<CreateEntity>::new()
+            .add_system(added)
+            .add_system_with_priority(-1, prioritized)
+            .add_system(IntoObserver::<CreateEntity, _>::before(
+                chained,
+                chained_before,
+            )),
+    );
+    world
+        .resource_mut::<Observables>()
+        .add_observer(IntoObserver::<CreateEntity, _>::into_observer(direct));
+    world.add_observers(
+        Observers::<CreateEntity>::new()
+            .add_system(deferred)
+            .deferred(),
+    );
+    world.add_observers(
+        Observers::<CreateEntity>::new()
+            .add_system(scheduled)
+            .deliver_in(Update, Main),
+    );
+    world.add_system(Update, Main, spawn);
+
+    world.run::<Update>();
+    assert_eq!(
+        world.resource::<Fired>().names,
+        vec![
+            "add_system_with_priority",
+            "add_system",
+            "before",
+            "chained",
+            "observables",
+        ]
+    );
+
+    world.resource_mut::<Fired>().names.clear();
+    world.run_deferred_observers(None);
+    assert_eq!(world.resource::<Fired>().names, vec!["deferred"]);
+
+    world.resource_mut::<Fired>().names.clear();
+    world.run::<Update>();
+    assert_eq!(world.resource::<Fired>().names[0], "deliver_in");
+    assert!(world.resource::<Fired>().names.contains(&"add_system"));
+}
+
+fn broadphase(_: &[Entity], fired: &mut Fired) {
+    fired.names.push("broadphase");
+}
+
+fn spatial_index(_: &[Entity], fired: &mut Fired) {
+    fired.names.push("spatial_index");
+}
+
+fn cleanup(_: &[Entity], fired: &mut Fired) {
+    fired.names.push("cleanup");
+}
+
+#[test]
+fn observers_run_in_declared_order() {
+    let mut world = World::new();
+    world.add_resource(Fired::default());
+    world.add_observers(
+        Observers::<CreateEntity>::new()
+            .add_system_with_priority(20, cleanup)
+            .add_system_with_priority(10, broadphase),
+    );
+    world
+        .add_observers(Observers::<CreateEntity>::new().add_system_with_priority(0, spatial_index));
+    world.add_system(Update, Main, spawn);
+
+    world.run::<Update>();
+    assert_eq!(
+        world.resource::<Fired>().names,
+        vec!["spatial_index", "broadphase", "cleanup"]
+    );
+
+    let mut world = World::new();
+    world.add_resource(Fired::default());
+    world.add_observers(Observers::<CreateEntity>::new().add_system(
+        IntoObserver::<CreateEntity, _>::after(broadphase, cleanup).before(spatial_index),
+    ));
+    world.add_system(Update, Main, spawn);
+
+    world.run::<Update>();
+    assert_eq!(
+        world.resource::<Fired>().names,
+        vec!["spatial_index", "broadphase", "cleanup"]
+    );
+}
+
+struct Stunned;
+
+impl Component for Stunned {}
+
+#[derive(Debug, PartialEq)]
+struct Recovering(u32);
+
+impl Component for Recovering {}
+
+#[derive(Debug, PartialEq)]
+struct Velocity(i32);
+
+impl Component for Velocity {}
+
+#[derive(Default)]
+struct Recovered {
+    seen: Vec<(Entity, u32, i32)>,
+}
+
+impl Resource for Recovered {}
+
+fn stun_removed(
+    entities: &[Entity],
+    query: Query<(Entity, &Recovering, &Velocity)>,
+    recovered: &mut Recovered,
+) {
+    for (entity, recovering, velocity) in query {
+        assert!(entities.contains(&entity));
+        recovered.seen.push((entity, recovering.0, velocity.0));
+    }
+}
+
+#[test]
+fn entity_edits_move_once_and_notify_with_the_final_state() {
+    let mut world = World::new();
+    world.register::<Stunned>();
+    world.register::<Recovering>();
+    world.register::<Velocity>();
+    world.add_resource(Recovered::default());
+    world.add_observers(Observers::<RemoveComponent<Stunned>>::new().add_system(stun_removed));
+
+    let entity = world.spawn().insert(Stunned).insert(Velocity(5)).id();
+    let moves = world.archetypes().moves();
+
+    world.resource_mut::<Actions>().add(
+        EditEntity::new(entity)
+            .add(Recovering(3))
+            .remove::<Stunned>()
+            .set(Velocity(0)),
+    );
+    world.run::<Update>();
+
+    assert_eq!(world.archetypes().moves(), moves + 1);
+    assert_eq!(world.resource::<Recovered>().seen, vec![(entity, 3, 0)]);
+    assert!(world.component::<Stunned>(entity).is_none());
+
+    world.delete(entity);
+    let traces = world.traces().len();
+    world
+        .resource_mut::<Actions>()
+        .add(EditEntity::new(entity).remove::<Velocity>());
+    world.run::<Update>();
+
+    let traces = world.traces().split_off(traces);
+    assert!(traces
+        .iter()
+        .any(|entry| entry.source().contains("EditEntity") && entry.message() == "skipped"));
+    assert_eq!(world.resource::<Recovered>().seen.len(), 1);
+}
+
+#[derive(Default)]
+struct Rebuilt {
+    entities: Vec<Entity>,
+}
+
+impl Resource for Rebuilt {}
+
+fn rebuild(entities: &[Entity], rebuilt: &mut Rebuilt) {
+    rebuilt.entities.extend_from_slice(entities);
+}
+
+fn spawn_fifty(actions: &mut Actions) {
+    for _ in 0..50 {
+        actions.add(CreateEntity::new());
+    }
+}
+
+#[test]
+fn deferred_observers_wait_for_the_explicit_call() {
+    let mut world = World::new();
+    world.add_resource(Rebuilt::default());
+    world.add_observers(
+        Observers::<CreateEntity>::new()
+            .add_system(rebuild)
+            .deferred(),
+    );
+    world.add_system(Update, Main, spawn_fifty);
+
+    world.run::<Update>();
+    world.run::<Update>();
+    assert!(world.resource::<Rebuilt>().entities.is_empty());
+    assert_eq!(world.pending_deferred::<CreateEntity>(), 100);
+
+    // A zero budget still delivers one chunk per call.
+    assert!(!world.run_deferred_observers(Some(Duration::ZERO)));
+    assert_eq!(world.resource::<Rebuilt>().entities.len(), DEFERRED_CHUNK);
+    assert_eq!(
+        world.pending_deferred::<CreateEntity>(),
+        100 - DEFERRED_CHUNK
+    );
+
+    assert!(world.run_deferred_observers(Some(Duration::ZERO)));
+    assert_eq!(world.pending_deferred::<CreateEntity>(), 0);
+
+    let mut entities = world.resource::<Rebuilt>().entities.clone();
+    entities.sort_by_key(|entity| entity.id());
+    entities.dedup();
+    assert_eq!(entities.len(), 100);
+    assert!(entities
+        .iter()
+        .all(|entity| world.entities().contains(*entity)));
+    assert!(world.run_deferred_observers(None));
+    assert_eq!(world.resource::<Rebuilt>().entities.len(), 100);
+}
+
+struct Health;
+
+impl Component for Health {}
+
+#[derive(Default)]
+struct Teardown {
+    log: Vec<(&'static str, Entity, Option<RemovalCause>)>,
+}
+
+impl Resource for Teardown {}
+
+fn unit_deleted(entities: &[Entity], teardown: &mut Teardown) {
+    for entity in entities {
+        teardown.log.push(("deleted", *entity, None));
+    }
+}
+
+fn health_removed(entities: &[Entity], teardown: &mut Teardown) {
+    for entity in entities {
+        teardown.log.push(("removed", *entity, None));
+    }
+}
+
+fn health_removal(removals: &[Removal], teardown: &mut Teardown) {
+    for removal in removals {
+        teardown
+            .log
+            .push(("removal", removal.entity(), Some(removal.cause())));
+    }
+}
+
+#[test]
+fn removal_causes_tell_removals_from_deletions() {
+    let mut world = World::new();
+    world.register::<Health>();
+    world.add_resource(Teardown::default());
+    world.add_observers(Observers::<DeleteEntity>::new().add_system(unit_deleted));
+    world.add_observers(Observers::<RemoveComponent<Health>>::new().add_system(health_removed));
+    world.add_observers(Observers::<ComponentRemoved<Health>>::new().add_system(health_removal));
+
+    let unit = world.spawn().insert(Health).id();
+    world
+        .resource_mut::<Actions>()
+        .add(RemoveComponent::<Health>::new(unit));
+    world.run::<Update>();
+    assert_eq!(
+        world.resource::<Teardown>().log,
+        vec![
+            ("removed", unit, None),
+            ("removal", unit, Some(RemovalCause::Removed)),
+        ]
+    );
+
+    world.resource_mut::<Teardown>().log.clear();
+    let unit = world.spawn().insert(Health).id();
+    world.resource_mut::<Actions>().add(DeleteEntity::new(unit));
+    world.run::<Update>();
+    assert_eq!(
+        world.resource::<Teardown>().log,
+        vec![
+            ("deleted", unit, None),
+            ("removed", unit, None),
+            ("removal", unit, Some(RemovalCause::EntityDeleted)),
+        ]
+    );
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Collider {
+    extents: (f32, f32),
+}
+
+impl Component for Collider {}
+
+#[derive(Default)]
+struct SpatialHash {
+    removed: Vec<(Entity, Option<Collider>)>,
+    buffered_next_frame: Vec<usize>,
+}
+
+impl Resource for SpatialHash {}
+
+fn unhash(entities: &[Entity], colliders: Removed<Collider>, hash: &mut SpatialHash) {
+    for entity in entities {
+        hash.removed
+            .push((*entity, colliders.get(*entity).cloned()));
+    }
+}
+
+fn count_buffered(colliders: Removed<Collider>, hash: &mut SpatialHash) {
+    hash.buffered_next_frame.push(colliders.len());
+}
+
+fn remove_collider(buffered: bool) -> (World, Entity) {
+    let mut world = World::new();
+    world.register::<Collider>();
+    if buffered {
+        world.buffer_removals::<Collider>();
+    }
+    world.add_resource(SpatialHash::default());
+    world.add_observers(Observers::<RemoveComponent<Collider>>::new().add_system(unhash));
+    world.add_system(Update, Main, count_buffered);
+
+    let collider = Collider {
+        extents: (2.0, 3.0),
+    };
+    let entity = world.spawn().insert(collider).id();
+    world
+        .resource_mut::<Actions>()
+        .add(RemoveComponent::<Collider>::new(entity));
+    world.run::<Update>();
+    world.run::<Update>();
+
+    (world, entity)
+}
+
+#[test]
+fn removed_values_are_readable_during_the_flush_when_buffered() {
+    let (world, entity) = remove_collider(true);
+    let hash = world.resource::<SpatialHash>();
+    assert_eq!(
+        hash.removed,
+        vec![(
+            entity,
+            Some(Collider {
+                extents: (2.0, 3.0)
+            })
+        )]
+    );
+    assert_eq!(hash.buffered_next_frame, vec![0, 0]);
+}
+
+#[test]
+fn removed_values_are_not_kept_without_opting_in() {
+    let (world, entity) = remove_collider(false);
+    let hash = world.resource::<SpatialHash>();
+    assert_eq!(hash.removed, vec![(entity, None)]);
+    assert_eq!(hash.buffered_next_frame, vec![0, 0]);
+}
+
+struct Armor;
+
+impl Component for Armor {}
+
+struct Shield;
+
+impl Component for Shield {}
+
+#[derive(Default)]
+struct Transitions {
+    seen: Vec<ArchetypeTransition>,
+}
+
+impl Resource for Transitions {}
+
+fn moved(transitions: &[ArchetypeTransition], seen: &mut Transitions) {
+    seen.seen.extend_from_slice(transitions);
+}
+
+/// Takes armor off again in the next wave of the same flush.
+fn strip_armor(entities: &[Entity], actions: &mut Actions) {
+    for entity in entities {
+        actions.add(RemoveComponent::<Armor>::new(*entity));
+    }
+}
+
+fn transition_world(coalesce: bool) -> (World, Entity) {
+    let mut world = World::new();
+    world.register::<Armor>();
+    world.register::<Shield>();
+    world.add_resource(Transitions::default());
+    world.add_observers(Observers::<ArchetypeTransition>::new().add_system(moved));
+    world.enable_transition_events(true);
+    world.coalesce_transition_events(coalesce);
+
+    let entity = world.spawn().insert(Health).id();
+    world.run::<Update>();
+    world.resource_mut::<Transitions>().seen.clear();
+    (world, entity)
+}
+
+/// Adds armor to the entity; an observer removes it again in the same
+/// flush.
+fn armor_round_trip(coalesce: bool) -> (World, Entity, ArchetypeId) {
+    let (mut world, entity) = transition_world(coalesce);
+    world.add_observers(Observers::<AddComponent<Armor>>::new().add_system(strip_armor));
+    let start = *world.archetypes().archetype_id(entity).unwrap();
+
+    world
+        .resource_mut::<Actions>()
+        .add(AddComponent::new(entity, Armor));
+    let report = world.run::<Update>();
+    assert!(report.waves() >= 2);
+
+    (world, entity, start)
+}
+
+#[test]
+fn transitions_are_delivered_in_order_within_a_flush() {
+    let (world, entity, start) = armor_round_trip(false);
+
+    let seen = &world.resource::<Transitions>().seen;
+    assert_eq!(seen.len(), 2);
+    assert_eq!(seen[0].entity, entity);
+    assert_eq!(seen[0].from, start);
+    assert_ne!(seen[0].to, start);
+    assert_eq!(seen[1], ArchetypeTransition::new(entity, seen[0].to, start));
+    assert_eq!(world.archetypes().archetype_id(entity), Some(&start));
+}
+
+#[test]
+fn coalesced_round_trips_produce_one_event() {
+    let (world, entity, start) = armor_round_trip(true);
+    assert_eq!(
+        world.resource::<Transitions>().seen,
+        vec![ArchetypeTransition::new(entity, start, start)]
+    );
+}
+
+#[test]
+fn coalesced_transitions_go_from_first_to_last() {
+    let (mut world, entity) = transition_world(true);
+    let start = *world.archetypes().archetype_id(entity).unwrap();
+
+    let mut actions = world.resource_mut::<Actions>();
+    actions.add(AddComponent::new(entity, Armor));
+    actions.add(AddComponent::new(entity, Shield));
+    drop(actions);
+    world.run::<Update>();
+
+    let end = *world.archetypes().archetype_id(entity).unwrap();
+    assert_eq!(
+        world.resource::<Transitions>().seen,
+        vec![ArchetypeTransition::new(entity, start, end)]
+    );
+    assert_eq!(
+        world
+            .archetypes()
+            .entity_archetype(entity)
+            .unwrap()
+            .components()
+            .len(),
+        3
+    );
+}
+
+/// Counts how many declared actions are executing at once.
+#[derive(Default)]
+struct Overlap {
+    active: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+impl Resource for Overlap {}
+
+impl Overlap {
+    fn run<T>(&self, f: impl FnOnce() -> T) -> T {
+        let active = self.active.fetch_add(1, Ordering::SeqCst) + 1;
+        self.peak.fetch_max(active, Ordering::SeqCst);
+        std::thread::sleep(Duration::from_millis(1));
+        let output = f();
+        self.active.fetch_sub(1, Ordering::SeqCst);
+        output
+    }
+}
+
+#[derive(Default)]
+struct AudioQueue {
+    sounds: Vec<u32>,
+}
+
+impl Resource for AudioQueue {}
+
+#[derive(Default)]
+struct Particles {
+    spawned: Vec<u32>,
+}
+
+impl Resource for Particles {}
+
+struct PlaySound(u32);
+
+impl Action for PlaySound {
+    type Output = u32;
+
+    fn execute(&mut self, world: &mut World) -> u32 {
+        self.execute_shared(world)
+    }
+
+    fn execute_shared(&mut self, world: &World) -> u32 {
+        let sound = self.0;
+        world
+            .resource::<Overlap>()
+            .run(|| world.resource_mut::<AudioQueue>().sounds.push(sound));
+        sound
+    }
+
+    fn access() -> ActionAccess {
+        ActionAccess::Declared {
+            reads: vec![AccessType::resource::<Overlap>()],
+            writes: vec![AccessType::resource::<AudioQueue>()],
+        }
+    }
+}
+
+struct SpawnParticle(u32);
+
+impl Action for SpawnParticle {
+    type Output = u32;
+
+    fn execute(&mut self, world: &mut World) -> u32 {
+        self.execute_shared(world)
+    }
+
+    fn execute_shared(&mut self, world: &World) -> u32 {
+        let particle = self.0;
+        world
+            .resource::<Overlap>()
+            .run(|| world.resource_mut::<Particles>().spawned.push(particle));
+        particle
+    }
+
+    fn access() -> ActionAccess {
+        ActionAccess::Declared {
+            reads: vec![AccessType::resource::<Overlap>()],
+            writes: vec![AccessType::resource::<Particles>()],
+        }
+    }
+}
+
+#[derive(Default)]
+struct Heard {
+    sounds: Vec<u32>,
+    particles: Vec<u32>,
+    armored: Vec<bool>,
+}
+
+impl Resource for Heard {}
+
+fn heard_sounds(sounds: &[u32], heard: &mut Heard) {
+    heard.sounds.extend_from_slice(sounds);
+}
+
+fn heard_particles(particles: &[u32], heard: &mut Heard) {
+    heard.particles.extend_from_slice(particles);
+}
+
+fn heard_armor(armored: &[bool], heard: &mut Heard) {
+    heard.armored.extend_from_slice(armored);
+}
+
+/// Reads a component a builtin writes, so it can't share a group with it.
+struct InspectArmor(Entity);
+
+impl Action for InspectArmor {
+    type Output = bool;
+    const PRIORITY: u32 = AddComponent::<Armor>::PRIORITY + 5;
+
+    fn execute(&mut self, world: &mut World) -> bool {
+        self.execute_shared(world)
+    }
+
+    fn execute_shared(&mut self, world: &World) -> bool {
+        world.component::<Armor>(self.0).is_some()
+    }
+
+    fn access() -> ActionAccess {
+        ActionAccess::Declared {
+            reads: vec![AccessType::component::<Armor>()],
+            writes: vec![],
+        }
+    }
+}
+
+fn declared_world() -> World {
+    let mut world = World::new();
+    world.register::<Armor>();
+    world.add_resource(Overlap::default());
+    world.add_resource(AudioQueue::default());
+    world.add_resource(Particles::default());
+    world.add_resource(Heard::default());
+    world.add_observers(Observers::<PlaySound>::new().add_system(heard_sounds));
+    world.add_observers(Observers::<SpawnParticle>::new().add_system(heard_particles));
+    world.add_observers(Observers::<InspectArmor>::new().add_system(heard_armor));
+    world
+}
+
+#[test]
+fn disjoint_declared_actions_run_concurrently() {
+    let mut world = declared_world();
+
+    let mut actions = world.resource_mut::<Actions>();
+    for i in 0..50 {
+        actions.add(PlaySound(i));
+        actions.add(SpawnParticle(i + 100));
+    }
+    drop(actions);
+    world.run::<Update>();
+
+    assert!(world.resource::<Overlap>().peak.load(Ordering::SeqCst) >= 2);
+    assert_eq!(world.resource::<Overlap>().active.load(Ordering::SeqCst), 0);
+    assert_eq!(
+        world.resource::<AudioQueue>().sounds,
+        (0..50).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        world.resource::<Particles>().spawned,
+        (100..150).collect::<Vec<_>>()
+    );
+
+    let heard = world.resource::<Heard>();
+    assert_eq!(heard.sounds, (0..50).collect::<Vec<_>>());
+    assert_eq!(heard.particles, (100..150).collect::<Vec<_>>());
+}
+
+#[test]
+fn sequential_mode_runs_declared_actions_one_at_a_time() {
+    let mut world = declared_world();
+    world.set_default_run_mode(RunMode::Sequential);
+
+    let mut actions = world.resource_mut::<Actions>();
+    for i in 0..10 {
+        actions.add(PlaySound(i));
+        actions.add(SpawnParticle(i));
+    }
+    drop(actions);
+    world.run::<Update>();
+
+    assert_eq!(world.resource::<Overlap>().peak.load(Ordering::SeqCst), 1);
+    assert_eq!(world.resource::<Heard>().sounds.len(), 10);
+    assert_eq!(world.resource::<Heard>().particles.len(), 10);
+}
+
+#[test]
+fn declared_action_conflicting_with_a_builtin_runs_after_it() {
+    let mut world = declared_world();
+    let entity = world.spawn().finish();
+
+    // Queued first, but the builtin's lower priority puts it ahead.
+    let mut actions = world.resource_mut::<Actions>();
+    actions.add(InspectArmor(entity));
+    actions.add(AddComponent::new(entity, Armor));
+    drop(actions);
+    world.run::<Update>();
+
+    assert_eq!(world.resource::<Heard>().armored, vec![true]);
+}
+
+/// Declared action skipped for odd values, to leave traces behind.
+#[derive(Clone)]
+struct Chime(u32);
+
+impl Action for Chime {
+    type Output = u32;
+
+    fn execute(&mut self, world: &mut World) -> u32 {
+        self.execute_shared(world)
+    }
+
+    fn execute_shared(&mut self, world: &World) -> u32 {
+        let chime = self.0;
+        world
+            .resource::<Overlap>()
+            .run(|| world.resource_mut::<AudioQueue>().sounds.push(chime));
+        chime
+    }
+
+    fn skip(&self, _: &World) -> bool {
+        self.0 % 2 == 1
+    }
+
+    fn access() -> ActionAccess {
+        PlaySound::access()
+    }
+}
+
+#[derive(Clone)]
+struct Flash(u32);
+
+impl Action for Flash {
+    type Output = u32;
+
+    fn execute(&mut self, world: &mut World) -> u32 {
+        self.execute_shared(world)
+    }
+
+    fn execute_shared(&mut self, world: &World) -> u32 {
+        let flash = self.0;
+        world
+            .resource::<Overlap>()
+            .run(|| world.resource_mut::<Particles>().spawned.push(flash));
+        flash
+    }
+
+    fn skip(&self, _: &World) -> bool {
+        self.0 % 2 == 1
+    }
+
+    fn access() -> ActionAccess {
+        SpawnParticle::access()
+    }
+}
+
+/// Concurrent declared types used to share one `&mut World`, which Miri
+/// reports as aliasing, and interleaved their traces and recorded actions.
+/// Each type now reports into its own journal, applied in queue order.
+#[test]
+fn concurrent_declared_actions_report_in_queue_order() {
+    let mut world = declared_world();
+    world.register_recordable::<Chime>();
+    world.register_recordable::<Flash>();
+    world.enable_priority_diagnostics(true);
+    world.start_recording();
+
+    let mut actions = world.resource_mut::<Actions>();
+    for i in 0..20 {
+        actions.add(Chime(i));
+        actions.add(Flash(i));
+    }
+    drop(actions);
+    world.run::<Update>();
+    let recording = world.stop_recording();
+
+    assert!(world.resource::<Overlap>().peak.load(Ordering::SeqCst) >= 2);
+    let evens = (0..20).step_by(2).collect::<Vec<_>>();
+    assert_eq!(world.resource::<AudioQueue>().sounds, evens);
+    assert_eq!(world.resource::<Particles>().spawned, evens);
+
+    let chime = std::any::type_name::<Chime>();
+    let flash = std::any::type_name::<Flash>();
+    let skipped = world
+        .traces()
+        .iter()
+        .filter(|entry| entry.message() == "skipped")
+        .map(|entry| entry.source())
+        .collect::<Vec<_>>();
+    let expected = [[chime; 10], [flash; 10]].concat();
+    assert_eq!(skipped, expected);
+    assert_eq!(recording.wave(0), expected);
+}
+
+/// Queues an action, which declared actions may not do.
+struct Echo;
+
+impl Action for Echo {
+    type Output = ();
+
+    fn execute(&mut self, world: &mut World) {
+        self.execute_shared(world)
+    }
+
+    fn execute_shared(&mut self, world: &World) {
+        world.resource_mut::<Actions>().add(PlaySound(0));
+    }
+
+    fn access() -> ActionAccess {
+        ActionAccess::Declared {
+            reads: vec![],
+            writes: vec![AccessType::resource::<Actions>()],
+        }
+    }
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "queued actions")]
+fn declared_actions_queueing_actions_panic() {
+    let mut world = declared_world();
+
+    let mut actions = world.resource_mut::<Actions>();
+    actions.add(Echo);
+    actions.add(SpawnParticle(0));
+    drop(actions);
+    world.run::<Update>();
+}
+
+/// How many removals the spatial hash had buffered when each delete
+/// observer ran.
+#[derive(Default)]
+struct Cleanup {
+    removals_seen: Vec<usize>,
+}
+
+impl Resource for Cleanup {}
+
+fn clean_up_deleted(_: &[Entity], hash: &SpatialHash, cleanup: &mut Cleanup) {
+    cleanup.removals_seen.push(hash.removed.len());
+}
+
+fn collider_cleanup_world(configure: bool) -> World {
+    let mut world = World::new();
+    world.register::<Collider>();
+    world.add_resource(SpatialHash::default());
+    world.add_resource(Cleanup::default());
+    world.add_observers(Observers::<RemoveComponent<Collider>>::new().add_system(unhash));
+    world.add_observers(Observers::<DeleteEntity>::new().add_system(clean_up_deleted));
+    if configure {
+        world
+            .configure_observer_order()
+            .channel::<RemoveComponent<Collider>>()
+            .before::<DeleteEntity>()
+            .unwrap();
+    }
+
+    let collider = Collider {
+        extents: (1.0, 1.0),
+    };
+    let stripped = world.spawn().insert(collider).id();
+    let deleted = world.spawn().insert(Health).id();
+    let mut actions = world.resource_mut::<Actions>();
+    actions.add(RemoveComponent::<Collider>::new(stripped));
+    actions.add(DeleteEntity::new(deleted));
+    drop(actions);
+    world.run::<Update>();
+    world
+}
+
+#[test]
+fn configured_channel_order_runs_removal_observers_before_deletes() {
+    let world = collider_cleanup_world(true);
+    assert_eq!(world.resource::<SpatialHash>().removed.len(), 1);
+    assert_eq!(world.resource::<Cleanup>().removals_seen, vec![1]);
+}
+
+#[test]
+fn unconfigured_channels_keep_priority_order() {
+    let world = collider_cleanup_world(false);
+    assert_eq!(world.resource::<SpatialHash>().removed.len(), 1);
+    assert_eq!(world.resource::<Cleanup>().removals_seen, vec![0]);
+}
+
+#[test]
+fn cyclic_channel_order_names_both_channels() {
+    let mut world = World::new();
+    world.register::<Collider>();
+    let builder = world
+        .configure_observer_order()
+        .channel::<RemoveComponent<Collider>>()
+        .before::<DeleteEntity>()
+        .unwrap();
+    let error = match builder
+        .channel::<DeleteEntity>()
+        .before::<RemoveComponent<Collider>>()
+    {
+        Ok(_) => panic!("expected a cycle"),
+        Err(error) => error,
+    };
+
+    let message = error.to_string();
+    assert!(message.contains("DeleteEntity"), "{message}");
+    assert!(
+        message.contains("RemoveComponent<observer::Collider>"),
+        "{message}"
+    );
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Heat(u32);
+
+impl Component for Heat {}
+
+#[derive(Default)]
+struct HeatChanges {
+    changes: Vec<(Entity, Option<Heat>)>,
+}
+
+impl Resource for HeatChanges {}
+
+fn heat_changed(changes: &[Change<Heat>], seen: &mut HeatChanges) {
+    seen.changes.extend(
+        changes
+            .iter()
+            .map(|change| (change.entity(), change.old().cloned())),
+    );
+}
+
+fn heat_world(capture: bool) -> (World, Entity) {
+    let mut world = World::new();
+    world.register::<Heat>();
+    if capture {
+        world.capture_changes::<Heat>();
+    }
+    world.add_resource(HeatChanges::default());
+    world.add_observers(Observers::<ComponentChanged<Heat>>::new().add_system(heat_changed));
+    let entity = world.spawn().insert(Heat(10)).insert(Health).id();
+    world.run::<Update>();
+    (world, entity)
+}
+
+#[test]
+fn set_component_overwrites_in_place() {
+    let (mut world, entity) = heat_world(true);
+    let row = world
+        .component::<Heat>(entity)
+        .map(|heat| &*heat as *const Heat)
+        .unwrap();
+    let archetype = *world.archetypes().archetype_id(entity).unwrap();
+    let moves = world.archetypes().moves();
+
+    let mut actions = world.resource_mut::<Actions>();
+    actions.add(SetComponent::new(entity, Heat(20)));
+    drop(actions);
+    world.run::<Update>();
+    world
+        .resource_mut::<Actions>()
+        .add(SetComponent::<Heat>::with(entity, |heat| heat.0 += 5));
+    world.run::<Update>();
+
+    assert_eq!(world.component::<Heat>(entity).as_deref(), Some(&Heat(25)));
+    assert_eq!(
+        &*world.component::<Heat>(entity).as_deref().unwrap() as *const Heat,
+        row
+    );
+    assert_eq!(world.archetypes().archetype_id(entity), Some(&archetype));
+    assert_eq!(world.archetypes().moves(), moves);
+    assert_eq!(
+        world.resource::<HeatChanges>().changes,
+        vec![(entity, Some(Heat(10))), (entity, Some(Heat(20)))]
+    );
+}
+
+#[test]
+fn set_component_without_capture_reports_no_old_value() {
+    let (mut world, entity) = heat_world(false);
+    world
+        .resource_mut::<Actions>()
+        .add(SetComponent::new(entity, Heat(20)));
+    world.run::<Update>();
+
+    assert_eq!(world.component::<Heat>(entity).as_deref(), Some(&Heat(20)));
+    assert_eq!(
+        world.resource::<HeatChanges>().changes,
+        vec![(entity, None)]
+    );
+}
+
+#[test]
+fn set_on_a_missing_component_is_skipped_and_traced() {
+    let (mut world, _) = heat_world(true);
+    let cold = world.spawn().insert(Health).id();
+    let moves = world.archetypes().moves();
+
+    world
+        .resource_mut::<Actions>()
+        .add(SetComponent::new(cold, Heat(20)));
+    world.run::<Update>();
+
+    assert_eq!(world.component::<Heat>(cold).as_deref(), None);
+    assert_eq!(world.archetypes().moves(), moves);
+    assert!(world.resource::<HeatChanges>().changes.is_empty());
+    assert!(world.traces().iter().any(|entry| {
+        entry.level() == TraceLevel::Info
+            && entry.source().contains("SetComponent")
+            && entry.message() == "skipped"
+    }));
+}
+
+/// The id a fresh world hands out first.
+fn first_entity() -> Entity {
+    Entity::new(0, 0)
+}
+
+struct EquipWeapon {
+    entity: Entity,
+}
+
+impl Action for EquipWeapon {
+    type Output = Entity;
+    const PRIORITY: u32 = CreateEntity::PRIORITY - 1;
+
+    fn execute(&mut self, world: &mut World) -> Entity {
+        world.add_component(self.entity, Armor);
+        self.entity
+    }
+
+    fn skip(&self, world: &World) -> bool {
+        !world.entities().contains(self.entity)
+    }
+
+    fn referenced_entities(&self) -> Vec<Entity> {
+        vec![self.entity]
+    }
+}
+
+/// A spawn action that runs before everything else.
+struct SpawnHero;
+
+impl Action for SpawnHero {
+    type Output = Entity;
+    const PRIORITY: u32 = 0;
+
+    fn execute(&mut self, world: &mut World) -> Entity {
+        world.create()
+    }
+}
+
+fn priority_warnings(world: &World) -> Vec<String> {
+    world
+        .traces()
+        .into_iter()
+        .filter(|entry| entry.source() == "priority" && entry.level() == TraceLevel::Warn)
+        .map(|entry| entry.message().to_string())
+        .collect()
+}
+
+#[test]
+fn action_running_before_its_entity_is_created_is_diagnosed() {
+    let mut world = World::new();
+    world.register::<Armor>();
+    world.enable_priority_diagnostics(true);
+
+    let mut actions = world.resource_mut::<Actions>();
+    actions.add(CreateEntity::new());
+    actions.add(EquipWeapon {
+        entity: first_entity(),
+    });
+    drop(actions);
+    world.run::<Update>();
+
+    assert!(world.entities().contains(first_entity()));
+    assert!(!world.has::<Armor>(first_entity()));
+    let warnings = priority_warnings(&world);
+    assert_eq!(warnings.len(), 1);
+    assert!(
+        warnings[0].starts_with("observer::EquipWeapon"),
+        "{}",
+        warnings[0]
+    );
+    assert!(warnings[0].contains("CreateEntity"), "{}", warnings[0]);
+}
+
+#[test]
+fn well_ordered_actions_produce_no_priority_warnings() {
+    let mut world = World::new();
+    world.register::<Armor>();
+    world.enable_priority_diagnostics(true);
+
+    let mut actions = world.resource_mut::<Actions>();
+    actions.add(EquipWeapon {
+        entity: first_entity(),
+    });
+    actions.add(SpawnHero);
+    drop(actions);
+    world.run::<Update>();
+
+    assert!(world.has::<Armor>(first_entity()));
+    assert!(priority_warnings(&world).is_empty());
+}
+
+struct Placed;
+
+impl Component for Placed {}
+
+struct Sprite;
+
+impl Component for Sprite {}
+
+struct Hidden;
+
+impl Component for Hidden {}
+
+#[derive(Default)]
+struct Matches {
+    events: Vec<(&'static str, Entity, MatcherId)>,
+}
+
+impl Resource for Matches {}
+
+fn match_began(began: &[MatchBegan], matches: &mut Matches) {
+    for began in began {
+        matches.events.push(("began", began.entity, began.matcher));
+    }
+}
+
+fn match_ended(ended: &[MatchEnded], matches: &mut Matches) {
+    for ended in ended {
+        matches.events.push(("ended", ended.entity, ended.matcher));
+    }
+}
+
+fn take_matches(world: &World) -> Vec<(&'static str, Entity, MatcherId)> {
+    std::mem::take(&mut world.resource_mut::<Matches>().events)
+}
+
+#[test]
+fn matchers_report_entities_entering_and_leaving() {
+    let mut world = World::new();
+    world.add_resource(Matches::default());
+    world.add_observers(Observers::<MatchBegan>::new().add_system(match_began));
+    world.add_observers(Observers::<MatchEnded>::new().add_system(match_ended));
+    let renderable = world.register_matcher::<(&Placed, &Sprite), Not<Hidden>>("renderable");
+
+    let mut actions = world.resource_mut::<Actions>();
+    actions.add(CreateEntity::new().with(Placed).with(Sprite));
+    actions.add(CreateEntity::new().with(Sprite));
+    drop(actions);
+    world.run::<Update>();
+    let events = take_matches(&world);
+    assert_eq!(events.len(), 1);
+    let (kind, entity, matcher) = events[0];
+    assert_eq!((kind, matcher), ("began", renderable));
+    let bystander = Query::<Entity, Not<Placed>>::new(&world).next().unwrap();
+
+    let mut actions = world.resource_mut::<Actions>();
+    actions.add(AddComponent::new(entity, Hidden));
+    actions.add(AddComponent::new(bystander, Hidden));
+    drop(actions);
+    world.run::<Update>();
+    assert_eq!(take_matches(&world), vec![("ended", entity, renderable)]);
+
+    let mut actions = world.resource_mut::<Actions>();
+    actions.add(RemoveComponent::<Hidden>::new(entity));
+    actions.add(RemoveComponent::<Hidden>::new(bystander));
+    drop(actions);
+    world.run::<Update>();
+    assert_eq!(take_matches(&world), vec![("began", entity, renderable)]);
+
+    let mut actions = world.resource_mut::<Actions>();
+    actions.add(DeleteEntity::new(entity));
+    actions.add(DeleteEntity::new(bystander));
+    drop(actions);
+    world.run::<Update>();
+    world.run::<Update>();
+    assert_eq!(take_matches(&world), vec![("ended", entity, renderable)]);
+}
+
+/// Claims `u64` outputs, but the tests below hand it `i64` blobs built
+/// elsewhere, the same size and alignment.
+struct Forged;
+
+impl Action for Forged {
+    type Output = u64;
+
+    fn execute(&mut self, _: &mut World) -> Self::Output {
+        0
+    }
+}
+
+fn forged_outputs() -> ActionOutput {
+    let mut outputs = Blob::new::<i64>();
+    outputs.push(-1i64);
+    ActionOutput::from_blob::<Forged>(outputs)
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "written as u64 into outputs stored as i64")]
+fn adding_to_forged_outputs_panics() {
+    let world = World::new();
+    let forged = forged_outputs();
+    let mut outputs = world.resource_mut::<ActionOutputs>().take();
+    outputs.insert(forged);
+    outputs.add::<Forged>(1);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "merging i64 outputs of observer::Forged into u64 outputs")]
+fn merging_forged_outputs_panics() {
+    let world = World::new();
+    let forged = forged_outputs();
+    let mut outputs = world.resource_mut::<ActionOutputs>().take();
+    let mut other = outputs.take();
+    other.insert(forged);
+    outputs.add::<Forged>(1);
+    outputs.merge(other);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "observers of observer::Forged given i64 outputs")]
+fn delivering_forged_outputs_panics() {
+    let world = World::new();
+    let forged = forged_outputs();
+    let mut observers = ObserverSystems::new::<Forged>();
+    observers.execute(forged, &world);
+}
@@ -0,0 +1,248 @@
+use ecs::{prelude::*, world::registered::RefreshPoint};
+
+struct PreUpdate;
+
+impl SchedulePhase for PreUpdate {
+    const PHASE: &'static str = "pre_update";
+}
+
+struct Update;
+
+impl SchedulePhase for Update {
+    const PHASE: &'static str = "update";
+}
+
+struct PostUpdate;
+
+impl SchedulePhase for PostUpdate {
+    const PHASE: &'static str = "post_update";
+}
+
+struct Main;
+
+impl ScheduleLabel for Main {
+    const LABEL: &'static str = "main";
+}
+
+#[derive(Default)]
+struct Log {
+    lines: Vec<String>,
+}
+
+impl Resource for Log {}
+
+struct Ping(u32);
+
+fn pre_update(log: &mut Log, mut pings: EventReader<Ping>) {
+    for ping in pings.read() {
+        log.lines.push(format!("ping {}", ping.0));
+    }
+    log.lines.push("pre_update".to_string());
+}
+
+fn update(log: &mut Log, mut pings: EventWriter<Ping>) {
+    pings.send(Ping(log.lines.len() as u32));
+    log.lines.push("update".to_string());
+}
+
+fn post_update(log: &mut Log) {
+    log.lines.push("post_update".to_string());
+}
+
+fn world() -> World {
+    let mut world = World::new();
+    world.add_resource(Log::default());
+    world.add_event::<Ping>();
+    world.add_system(PostUpdate, Main, post_update);
+    world.add_system(Update, Main, update);
+    world.add_system(PreUpdate, Main, pre_update);
+
+    world.add_phase::<PreUpdate>();
+    world.add_phase::<PostUpdate>();
+    world.add_phase_before::<Update, PostUpdate>();
+    world
+}
+
+#[test]
+fn update_runs_registered_phases_in_order() {
+    let mut world = world();
+    assert_eq!(world.phases(), vec!["pre_update", "update", "post_update"]);
+
+    let reports = world.update();
+
+    assert_eq!(
+        world.resource::<Log>().lines,
+        vec!["pre_update", "update", "post_update"]
+    );
+    assert_eq!(reports.len(), 3);
+    assert!(reports.iter().all(|report| report.frame() == 0));
+    assert_eq!(world.frame(), 1);
+}
+
+#[test]
+fn events_sent_during_update_reach_the_next_frame() {
+    let mut world = world();
+
+    world.update();
+    world.update();
+
+    assert_eq!(
+        world.resource::<Log>().lines,
+        vec![
+            "pre_update",
+            "update",
+            "post_update",
+            "ping 1",
+            "pre_update",
+            "update",
+            "post_update",
+        ]
+    );
+    assert_eq!(world.frame(), 2);
+}
+
+fn spawn_pair(actions: &mut Actions) {
+    actions.add(CreateEntity::new());
+    actions.add(CreateEntity::new());
+}
+
+fn spawned_at_flush(entities: &[Entity], log: &mut Log, actions: &mut Actions) {
+    log.lines.push(format!("flush {}", entities.len()));
+    if log
+        .lines
+        .iter()
+        .filter(|line| line.starts_with("flush"))
+        .count()
+        == 1
+    {
+        for _ in entities {
+            actions.add(CreateEntity::new());
+        }
+    }
+}
+
+fn spawned_in_post_update(entities: &[Entity], log: &mut Log) {
+    log.lines.push(format!("delivered {entities:?}"));
+}
+
+#[test]
+fn phase_scheduled_observers_wait_for_their_phase() {
+    let mut world = World::new();
+    world.add_resource(Log::default());
+    world.add_system(Update, Main, spawn_pair);
+    world.add_system(PostUpdate, Main, post_update);
+    world.add_observers(Observers::<CreateEntity>::new().add_system(spawned_at_flush));
+    world.add_observers(
+        Observers::<CreateEntity>::new()
+            .add_system(spawned_in_post_update)
+            .deliver_in(PostUpdate, Main),
+    );
+    world.add_phase::<PostUpdate>();
+
+    let report = world.run::<Update>();
+    assert_eq!(report.waves(), 2);
+    assert_eq!(world.resource::<Log>().lines, vec!["flush 2", "flush 2"]);
+
+    world.run::<PostUpdate>();
+    let entities = (0..4).map(|id| Entity::new(id, 0)).collect::<Vec<_>>();
+    assert_eq!(
+        world.resource::<Log>().lines,
+        vec![
+            "flush 2".to_string(),
+            "flush 2".to_string(),
+            format!("delivered {entities:?}"),
+            "post_update".to_string(),
+        ]
+    );
+
+    world.run::<PostUpdate>();
+    let lines = &world.resource::<Log>().lines;
+    assert_eq!(lines.len(), 5);
+    assert_eq!(lines[4], "post_update");
+}
+
+struct Enemy;
+
+impl Component for Enemy {}
+
+struct Visible;
+
+impl Component for Visible {}
+
+type VisibleEnemies<'a> = Registered<'a, &'static Enemy, With<Visible>>;
+
+#[derive(Default)]
+struct Sightings {
+    frame: usize,
+    update: Vec<Vec<Entity>>,
+    post_update: Vec<Vec<Entity>>,
+    fetched: Vec<usize>,
+    doomed: Option<Entity>,
+}
+
+impl Resource for Sightings {}
+
+fn sight_in_update(enemies: VisibleEnemies, sightings: &mut Sightings) {
+    sightings.update.push(enemies.entities().to_vec());
+}
+
+fn sight_in_post_update(enemies: VisibleEnemies, sightings: &mut Sightings) {
+    sightings.fetched.push(enemies.iter().count());
+    sightings.post_update.push(enemies.entities().to_vec());
+    sightings.frame += 1;
+}
+
+/// Spawns a visible enemy in the first frame and deletes it in the third.
+fn script_enemies(sightings: &Sightings, actions: &mut Actions) {
+    match (sightings.frame, sightings.doomed) {
+        (0, _) => actions.add(CreateEntity::new().with(Enemy).with(Visible)),
+        (2, Some(doomed)) => actions.add(DeleteEntity::new(doomed)),
+        _ => {}
+    }
+}
+
+#[test]
+fn registered_queries_refresh_once_per_frame_for_every_reader() {
+    let mut world = world();
+    world.add_resource(Sightings::default());
+    let seen = world.spawn().insert(Enemy).insert(Visible).id();
+    world.spawn().insert(Enemy);
+    let id = world.register_query::<&Enemy, With<Visible>>("visible_enemies");
+    world.set_query_refresh(id, RefreshPoint::phase::<PreUpdate>());
+    world.add_system(Update, Main, sight_in_update);
+    world.add_system(Update, Main, script_enemies);
+    world.add_system(PostUpdate, Main, sight_in_post_update);
+
+    // The spawn waits for the next refresh.
+    world.update();
+    assert_eq!(world.registered_queries().entities(id), &[seen]);
+
+    world.update();
+    let spawned = Query::<Entity, With<Visible>>::new(&world)
+        .find(|entity| *entity != seen)
+        .unwrap();
+    world.resource_mut::<Sightings>().doomed = Some(spawned);
+    let rescans = world.registered_queries().rescans(id);
+
+    world.update();
+    world.update();
+    let skips = world.registered_queries().skips(id);
+    world.update();
+    assert_eq!(world.registered_queries().skips(id), skips + 1);
+    assert_eq!(world.registered_queries().rescans(id), rescans + 1);
+
+    let sightings = world.resource::<Sightings>();
+    assert_eq!(sightings.update, sightings.post_update);
+    assert_eq!(
+        sightings.update,
+        vec![
+            vec![seen],
+            vec![seen, spawned],
+            vec![seen, spawned],
+            vec![seen],
+            vec![seen],
+        ]
+    );
+    // Deleted during the third frame: still listed, but no longer fetched.
+    assert_eq!(sightings.fetched, vec![1, 2, 1, 1, 1]);
+}
@@ -0,0 +1,703 @@
+use ecs::{
+    core::StorageKind,
+    prelude::*,
+    storage::table::TableId,
+    tasks::CancellationToken,
+    world::{
+        explain::{ArchetypeVerdict, EntityVerdict},
+        query::{BaseQuery, FilterQuery, QueryCache, QueryState},
+        trace::TraceLevel,
+    },
+};
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+struct Visits(u32);
+
+impl Component for Visits {}
+
+fn world_with_visits(count: usize) -> World {
+    let mut world = World::new();
+    world.register::<Visits>();
+    for _ in 0..count {
+        let entity = world.create();
+        world.add_component(entity, Visits(0));
+    }
+
+    world
+}
+
+#[test]
+fn par_for_each_mut_visits_each_entity_once() {
+    let world = world_with_visits(10_000);
+
+    Query::<&mut Visits>::new(&world).par_for_each_mut(64, |mut visits| visits.0 += 1);
+
+    let visits = Query::<&Visits>::new(&world)
+        .map(|visits| visits.0)
+        .collect::<Vec<_>>();
+    assert_eq!(visits.len(), 10_000);
+    assert!(visits.iter().all(|visits| *visits == 1));
+}
+
+#[test]
+fn par_for_each_mut_covers_every_chunk_of_every_table() {
+    let mut world = world_with_visits(100);
+    world.register::<Position>();
+    let tagged = Query::<Entity>::new(&world).step_by(3).collect::<Vec<_>>();
+    for entity in &tagged {
+        world.add_component(*entity, Position);
+    }
+
+    Query::<(&mut Visits, Option<&Position>)>::new(&world)
+        .par_for_each_mut(7, |(mut visits, position)| {
+            visits.0 += 1 + position.is_some() as u32
+        });
+
+    let seen = AtomicUsize::new(0);
+    Query::<(Entity, &Visits)>::new(&world).par_for_each(7, |(entity, visits)| {
+        assert_eq!(visits.0, 1 + tagged.contains(&entity) as u32);
+        seen.fetch_add(1, Ordering::Relaxed);
+    });
+    assert_eq!(seen.load(Ordering::Relaxed), 100);
+}
+
+#[test]
+fn par_for_each_cancellable_stops_after_cancel() {
+    let world = world_with_visits(10_000);
+    let token = CancellationToken::new();
+
+    let finished =
+        Query::<&mut Visits>::new(&world).par_for_each_cancellable(64, &token, |mut visits| {
+            visits.0 += 1;
+            token.cancel();
+        });
+
+    assert!(!finished);
+    assert!(Query::<&Visits>::new(&world).all(|visits| visits.0 <= 1));
+}
+
+struct Position;
+
+impl Component for Position {}
+
+struct Velocity;
+
+impl Component for Velocity {}
+
+struct Health;
+
+impl Component for Health {}
+
+#[test]
+fn transmute_to_one_member_matches_a_fresh_query() {
+    let mut world = World::new();
+    world.register::<Position>();
+    world.register::<Velocity>();
+    world.register::<Health>();
+    for index in 0..20 {
+        let entity = world.create();
+        world.add_component(entity, Position);
+        if index % 2 == 0 {
+            world.add_component(entity, Velocity);
+        }
+        if index % 3 == 0 {
+            world.add_component(entity, Health);
+        }
+    }
+
+    let mut query = Query::<(Entity, &Position, &mut Velocity), With<Health>>::new(&world);
+    let narrowed = query.transmute::<Entity, _>().collect::<Vec<_>>();
+    drop(query);
+
+    let fresh = Query::<(Entity, &Position, &Velocity), With<Health>>::new(&world)
+        .map(|(entity, _, _)| entity)
+        .collect::<Vec<_>>();
+    assert_eq!(narrowed.len(), 4);
+    assert_eq!(narrowed, fresh);
+}
+
+#[test]
+fn query_cache_rebuilds_when_an_archetype_is_replaced() {
+    let mut world = World::new();
+    world.register::<Position>();
+    world.register::<Velocity>();
+    let entity = world.create();
+    world.add_component(entity, Position);
+    world.delete(entity);
+
+    let cache = QueryCache::new();
+    let (_, tables) = cache.get::<&Velocity, ()>(&world);
+    assert!(tables.is_empty());
+
+    // Same archetype count as before: the empty one plus one other.
+    let archetypes = world.archetypes().len();
+    world.defragment(Duration::MAX);
+    let entity = world.create();
+    world.add_component(entity, Velocity);
+    assert_eq!(world.archetypes().len(), archetypes);
+
+    let (_, tables) = cache.get::<&Velocity, ()>(&world);
+    assert_eq!(tables.len(), 1);
+    assert_eq!(cache.stats().rebuilds, 2);
+    assert_eq!(Query::<&Velocity>::new(&world).count(), 1);
+}
+
+#[test]
+fn three_component_queries_yield_each_entity_once() {
+    let mut world = World::new();
+    world.register::<Position>();
+    world.register::<Velocity>();
+    world.register::<Health>();
+    let mut expected = vec![];
+    for index in 0..12 {
+        let entity = world.create();
+        world.add_component(entity, Position);
+        if index % 2 == 0 {
+            world.add_component(entity, Velocity);
+        }
+        if index % 3 == 0 {
+            world.add_component(entity, Health);
+            if index % 2 == 0 {
+                expected.push(entity);
+            }
+        }
+    }
+
+    let entities = Query::<(Entity, &Health, &Velocity, &Position)>::new(&world)
+        .map(|(entity, ..)| entity)
+        .collect::<Vec<_>>();
+    assert_eq!(entities, expected);
+
+    let components = [
+        world.components().id::<Velocity>(),
+        world.components().id::<Health>(),
+        world.components().id::<Position>(),
+    ];
+    let archetypes = world.archetypes();
+    assert_eq!(archetypes.archetypes(&components, &[]).len(), 1);
+    assert_eq!(archetypes.entities(&components, &[]).len(), expected.len());
+    assert_eq!(
+        archetypes
+            .entity_archetypes(&components, &[], &expected)
+            .len(),
+        1
+    );
+}
+
+#[derive(Debug, PartialEq)]
+struct Score(u32);
+
+impl Component for Score {}
+
+#[test]
+fn queries_skip_entities_with_a_stale_generation() {
+    let mut world = World::new();
+    world.register::<Score>();
+    let stale = world.create();
+    world.add_component(stale, Score(1));
+    world.delete(stale);
+
+    let fresh = world.create();
+    world.add_component(fresh, Score(2));
+    assert_eq!(fresh.id(), stale.id());
+    assert_ne!(fresh, stale);
+
+    let scores = Query::<(Entity, &Score)>::new(&world)
+        .map(|(entity, score)| (entity, score.0))
+        .collect::<Vec<_>>();
+    assert_eq!(scores, vec![(fresh, 2)]);
+
+    let input = [stale, fresh];
+    let query = Query::<(Entity, &Score)>::new(&world);
+    let scores = query
+        .entities(&input)
+        .map(|(entity, score)| (entity, score.0))
+        .collect::<Vec<_>>();
+    assert_eq!(scores, vec![(fresh, 2)]);
+
+    let mut visited = vec![];
+    query
+        .entities(&[stale])
+        .for_each_with_entity(|entity, _| visited.push(entity));
+    assert!(visited.is_empty());
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "structural change while 1 queries alive")]
+fn structural_changes_panic_while_a_query_is_alive() {
+    let mut world = world_with_visits(1);
+    let entity = world.create();
+
+    // A leaked query never releases its borrow, standing in for one held
+    // across a structural change.
+    std::mem::forget(Query::<&Visits>::new(&world));
+    assert_eq!(world.query_borrows().live(), 1);
+    world.add_component(entity, Visits(0));
+}
+
+#[test]
+fn dropped_queries_release_their_borrow() {
+    let mut world = world_with_visits(4);
+    let entity = world.create();
+
+    let query = Query::<&Visits>::new(&world);
+    assert_eq!(query.count(), 4);
+    let narrowed = Query::<&mut Visits>::new(&world).to_readonly().count();
+    assert_eq!(narrowed, 4);
+
+    assert_eq!(world.query_borrows().live(), 0);
+    world.add_component(entity, Visits(0));
+    world.delete(entity);
+}
+
+struct Dead;
+
+impl Component for Dead {}
+
+#[test]
+fn match_all_queries_include_component_less_entities() {
+    let mut world = world_with_visits(2);
+    world.register::<Dead>();
+    let bare = world.create();
+    let dead = world.create();
+    world.add_component(dead, Dead);
+    let deleted = world.create();
+    world.delete(deleted);
+
+    let mut all = Query::<Entity>::new(&world).collect::<Vec<_>>();
+    all.sort_by_key(|entity| entity.id());
+    let mut live = world
+        .archetypes()
+        .iter()
+        .flat_map(|archetype| archetype.entities().iter().copied())
+        .collect::<Vec<_>>();
+    live.sort_by_key(|entity| entity.id());
+    assert_eq!(all, live);
+    assert_eq!(all.len(), 4);
+    assert!(all.contains(&bare) && all.contains(&dead));
+    assert!(!all.contains(&deleted));
+
+    let alive = Query::<Entity, Not<Dead>>::new(&world).collect::<Vec<_>>();
+    assert_eq!(alive.len(), 3);
+    assert!(alive.contains(&bare));
+    assert!(!alive.contains(&dead));
+}
+
+/// Misspelled marker that is registered but never attached.
+struct Enemey;
+
+impl Component for Enemey {}
+
+#[test]
+fn explain_names_a_component_no_archetype_has() {
+    let mut world = world_with_visits(3);
+    world.register::<Enemey>();
+    let enemey = world.component_id::<Enemey>();
+
+    let query = Query::<&Visits, With<Enemey>>::new(&world);
+    let explain = query.explain();
+
+    assert_eq!(explain.matched_archetypes(), 0);
+    assert_eq!(explain.matched_entities(), 0);
+    assert!(explain.required.contains(&enemey));
+    assert!(explain.name(enemey).ends_with("Enemey"));
+    let visits = explain
+        .archetypes
+        .iter()
+        .find(|archetype| archetype.entities == 3)
+        .unwrap();
+    assert_eq!(visits.verdict, ArchetypeVerdict::Missing(vec![enemey]));
+
+    let report = explain.to_string();
+    assert!(report.contains("missing query::Enemey"), "{report}");
+    assert!(report.contains("matched 0 of"), "{report}");
+}
+
+#[test]
+fn explain_reports_the_not_filter_that_excluded_an_entity() {
+    let mut world = world_with_visits(1);
+    world.register::<Dead>();
+    let alive = world.create();
+    world.add_component(alive, Visits(0));
+    let dead = world.create();
+    world.add_component(dead, Visits(0));
+    world.add_component(dead, Dead);
+    let dead_id = world.component_id::<Dead>();
+
+    let input = [alive, dead];
+    let query = Query::<&Visits, Not<Dead>>::new(&world).entities(&input);
+    let explain = query.explain();
+
+    let entities = explain.entities.as_ref().unwrap();
+    assert_eq!(entities[0], (alive, EntityVerdict::Matched));
+    assert_eq!(
+        entities[1],
+        (
+            dead,
+            EntityVerdict::Filtered(ArchetypeVerdict::ExcludedBy(vec![dead_id]))
+        )
+    );
+    assert_eq!(explain.matched_entities(), 1);
+
+    let report = explain.to_string();
+    assert!(
+        report.contains(&format!("entity {dead:?}: excluded by query::Dead")),
+        "{report}"
+    );
+}
+
+fn quest_givers(world: &World, cache: &mut QueryResultCache) -> Vec<Entity> {
+    Query::<&Position, Not<Dead>>::new(world)
+        .cached(cache)
+        .to_vec()
+}
+
+fn uncached_quest_givers(world: &World) -> Vec<Entity> {
+    Query::<(Entity, &Position), Not<Dead>>::new(world)
+        .map(|(entity, _)| entity)
+        .collect()
+}
+
+fn quest_world() -> World {
+    let mut world = World::new();
+    world.register::<Position>();
+    world.register::<Velocity>();
+    world.register::<Dead>();
+    for index in 0..30 {
+        let entity = world.create();
+        world.add_component(entity, Position);
+        if index % 3 == 0 {
+            world.add_component(entity, Velocity);
+        }
+        if index % 5 == 0 {
+            world.add_component(entity, Dead);
+        }
+    }
+    world
+}
+
+#[test]
+fn cached_queries_hit_until_a_matched_table_changes() {
+    let mut world = quest_world();
+    let mut cache = QueryResultCache::new();
+
+    let first = quest_givers(&world, &mut cache);
+    assert_eq!(first, uncached_quest_givers(&world));
+    assert_eq!((cache.hits(), cache.misses()), (0, 1));
+    for _ in 0..10 {
+        assert_eq!(quest_givers(&world, &mut cache), first);
+    }
+    assert_eq!((cache.hits(), cache.misses()), (10, 1));
+
+    let entity = world.create();
+    world.add_component(entity, Position);
+    let second = quest_givers(&world, &mut cache);
+    assert_eq!((cache.hits(), cache.misses()), (10, 2));
+    assert_eq!(second.len(), first.len() + 1);
+    assert!(second.contains(&entity));
+    assert_eq!(second, uncached_quest_givers(&world));
+}
+
+#[test]
+fn cached_results_match_uncached_results_under_random_mutation() {
+    let mut world = quest_world();
+    let mut cache = QueryResultCache::new();
+    let mut entities = Query::<Entity>::new(&world).collect::<Vec<_>>();
+    let mut seed = 0x2545_f491_u64;
+    let mut next = |bound: usize| {
+        seed = seed.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+        (seed >> 33) as usize % bound
+    };
+
+    for _ in 0..500 {
+        let entity = entities[next(entities.len())];
+        match next(7) {
+            0 => {
+                let created = world.create();
+                world.add_component(created, Position);
+                entities.push(created);
+            }
+            1 => {
+                world.delete(entity);
+                entities.retain(|e| *e != entity);
+            }
+            2 => world.add_component(entity, Dead),
+            3 => {
+                world.remove_component::<Dead>(entity);
+            }
+            4 => world.add_component(entity, Velocity),
+            5 => {
+                world.remove_component::<Position>(entity);
+            }
+            // Reads only, so the next call can hit.
+            _ => {}
+        }
+        if entities.is_empty() {
+            entities.push(world.create());
+        }
+
+        let mut cached = quest_givers(&world, &mut cache);
+        let mut expected = uncached_quest_givers(&world);
+        cached.sort_by_key(|entity| entity.id());
+        expected.sort_by_key(|entity| entity.id());
+        assert_eq!(cached, expected);
+    }
+    assert!(cache.hits() > 0);
+    assert!(cache.misses() > 0);
+}
+
+#[test]
+fn not_filters_skip_entities_with_the_excluded_component() {
+    let mut world = quest_world();
+    let mut alive = Query::<(Entity, &Position), Not<Dead>>::new(&world)
+        .map(|(entity, _)| entity)
+        .collect::<Vec<_>>();
+    assert_eq!(alive.len(), 24);
+    assert!(alive.iter().all(|entity| !world.has::<Dead>(*entity)));
+
+    let all = Query::<Entity>::new(&world).collect::<Vec<_>>();
+    let mut restricted = Query::<(Entity, &Position), Not<Dead>>::new(&world)
+        .entities(&all)
+        .map(|(entity, _)| entity)
+        .collect::<Vec<_>>();
+    alive.sort_by_key(|entity| entity.id());
+    restricted.sort_by_key(|entity| entity.id());
+    assert_eq!(restricted, alive);
+
+    let dead = Query::<Entity, With<Dead>>::new(&world).next().unwrap();
+    world.remove_component::<Dead>(dead);
+    assert_eq!(Query::<&Position, Not<Dead>>::new(&world).count(), 25);
+}
+
+/// Kept outside the tables, so a fetch of it from a table column would
+/// find nothing.
+struct QuestMarker;
+
+impl Component for QuestMarker {}
+
+#[test]
+fn with_filters_select_archetypes_without_fetching() {
+    let mut world = quest_world();
+    world
+        .register_component::<QuestMarker>()
+        .storage(StorageKind::Sparse)
+        .finish()
+        .unwrap();
+    let mut givers = Query::<Entity>::new(&world).step_by(4).collect::<Vec<_>>();
+    givers.sort_by_key(|entity| entity.id());
+    for entity in &givers {
+        world.add_component(*entity, QuestMarker);
+    }
+
+    let mut state = QueryState::new();
+    <&Position as BaseQuery>::init(&world, &mut state);
+    <With<QuestMarker> as FilterQuery>::init(&world, &mut state);
+    let marker = world.component_id::<QuestMarker>();
+    assert_eq!(state.components(), &[world.component_id::<Position>()]);
+    assert_eq!(state.with(), &[marker]);
+
+    let mut found = Query::<(Entity, &Position), With<QuestMarker>>::new(&world)
+        .map(|(entity, _)| entity)
+        .collect::<Vec<_>>();
+    found.sort_by_key(|entity| entity.id());
+    assert_eq!(found, givers);
+}
+
+#[test]
+fn tables_removed_after_matching_are_skipped() {
+    let mut world = World::new();
+    world.set_paranoid(true);
+    world.spawn().insert(Score(1));
+    world.spawn().insert(Score(2));
+    let doomed = world.spawn().insert(Score(3)).insert(Dead).id();
+    let removed: TableId = (*world.archetypes().archetype_id(doomed).unwrap()).into();
+    world.delete(doomed);
+
+    let (state, tables) = world.query_cache().get::<&Score, ()>(&world);
+    assert!(tables.contains(&removed));
+    world.defragment(Duration::MAX);
+    assert!(world.tables().get(removed).is_none());
+
+    let mut scores = Query::<&Score>::with_tables(&world, state, tables)
+        .map(|score| score.0)
+        .collect::<Vec<_>>();
+    scores.sort();
+    assert_eq!(scores, vec![1, 2]);
+
+    let removed = format!("{removed:?}");
+    assert!(world.traces().iter().any(|entry| {
+        entry.level() == TraceLevel::Warn
+            && entry.source() == "query"
+            && entry.message().contains(&removed)
+    }));
+}
+
+fn scored_world(count: u32) -> World {
+    let mut world = World::new();
+    world.register::<Score>();
+    world.register::<Velocity>();
+    world.register::<Health>();
+    world.register::<Dead>();
+    for index in 0..count {
+        let entity = world.create();
+        world.add_component(entity, Score(index));
+        if index % 2 == 0 {
+            world.add_component(entity, Velocity);
+        }
+        if index % 3 == 0 {
+            world.add_component(entity, Health);
+        }
+        if index % 7 == 0 {
+            world.add_component(entity, Dead);
+        }
+    }
+    world
+}
+
+#[test]
+fn for_each_visits_the_same_items_as_iter() {
+    let world = scored_world(200);
+    let query = Query::<(Entity, &Score), Not<Dead>>::new(&world);
+    assert!(query.explain().matched_archetypes() > 2);
+
+    let iterated = Query::<(Entity, &Score), Not<Dead>>::new(&world)
+        .map(|(entity, score)| (entity, score.0))
+        .collect::<Vec<_>>();
+    let mut visited = vec![];
+    query.for_each(|(entity, score)| visited.push((entity, score.0)));
+    assert_eq!(visited, iterated);
+    assert_eq!(visited.len(), 200 - 200usize.div_ceil(7));
+
+    let mut with_entity = vec![];
+    Query::<&Score, Not<Dead>>::new(&world)
+        .for_each_with_entity(|entity, score| with_entity.push((entity, score.0)));
+    assert_eq!(with_entity, iterated);
+
+    Query::<&mut Score, Not<Dead>>::new(&world).for_each_mut(|mut score| score.0 += 1);
+    let bumped = Query::<(Entity, &Score), Not<Dead>>::new(&world)
+        .map(|(entity, score)| (entity, score.0 - 1))
+        .collect::<Vec<_>>();
+    assert_eq!(bumped, iterated);
+}
+
+#[test]
+#[ignore = "benchmark, run with --ignored --nocapture in release"]
+fn for_each_and_iter_over_a_million_entities() {
+    let world = scored_world(1_000_000);
+
+    let start = std::time::Instant::now();
+    let mut iterated = 0u64;
+    for score in Query::<&Score, Not<Dead>>::new(&world) {
+        iterated += u64::from(score.0);
+    }
+    let iter_time = start.elapsed();
+
+    let start = std::time::Instant::now();
+    let mut visited = 0u64;
+    Query::<&Score, Not<Dead>>::new(&world).for_each(|score| visited += u64::from(score.0));
+    let for_each_time = start.elapsed();
+
+    assert_eq!(visited, iterated);
+    println!("iter: {iter_time:?}, for_each: {for_each_time:?}");
+}
+
+struct Update;
+
+impl SchedulePhase for Update {
+    const PHASE: &'static str = "update";
+}
+
+struct Main;
+
+impl ScheduleLabel for Main {
+    const LABEL: &'static str = "main";
+}
+
+/// Scores seen by `changed_scores`, one list per run.
+#[derive(Default)]
+struct ChangedScores(Vec<Vec<u32>>);
+
+impl Resource for ChangedScores {}
+
+fn changed_scores(scores: Query<&Score, Changed<Score>>, changed: &mut ChangedScores) {
+    let mut scores = scores.map(|score| score.0).collect::<Vec<_>>();
+    scores.sort();
+    changed.0.push(scores);
+}
+
+/// 100 scores over columns chunked 16 values at a time.
+fn chunked_score_world() -> (World, Vec<Entity>) {
+    let mut world = World::new();
+    world.set_column_chunk_bytes(64);
+    world.register::<Score>();
+    world.add_resource(ChangedScores::default());
+    world.add_system(Update, Main, changed_scores);
+    let entities = (0..100)
+        .map(|score| world.spawn().insert(Score(score)).id())
+        .collect::<Vec<_>>();
+
+    let table: TableId = (*world.archetypes().archetype_id(entities[0]).unwrap()).into();
+    let column = world.component_id::<Score>().into();
+    let chunks = world.tables().get(table).unwrap().column(column).unwrap();
+    assert!(chunks.chunk_count() > 5);
+    (world, entities)
+}
+
+#[test]
+fn changed_sees_writes_through_component_mut_in_any_chunk() {
+    let (mut world, entities) = chunked_score_world();
+    world.run::<Update>();
+    world.run::<Update>();
+
+    world.component_mut::<Score>(entities[3]).unwrap().0 += 1000;
+    world.component_mut::<Score>(entities[97]).unwrap().0 += 1000;
+    world.run::<Update>();
+    world.run::<Update>();
+
+    let changed = &world.resource::<ChangedScores>().0;
+    assert_eq!(changed[0], (0..100).collect::<Vec<_>>());
+    assert_eq!(changed[1..], [vec![], vec![1003, 1097], vec![]]);
+}
+
+#[test]
+fn changed_sees_components_added_by_actions() {
+    let (mut world, entities) = chunked_score_world();
+    let fresh = world.spawn().id();
+    world.run::<Update>();
+
+    let mut actions = world.resource_mut::<Actions>();
+    actions.add(AddComponent::new(fresh, Score(500)));
+    actions.add(AddComponent::new(entities[50], Score(550)));
+    // Applied by the flush after this run's systems, so seen by the next.
+    drop(actions);
+    world.run::<Update>();
+    world.run::<Update>();
+    world.run::<Update>();
+
+    let changed = &world.resource::<ChangedScores>().0;
+    assert_eq!(changed[1..], [vec![], vec![500, 550], vec![]]);
+}
+
+#[test]
+fn changed_is_empty_on_frames_without_writes() {
+    let (mut world, entities) = chunked_score_world();
+    for _ in 0..4 {
+        world.run::<Update>();
+    }
+
+    // Reading doesn't count as a change.
+    assert_eq!(
+        world.component::<Score>(entities[20]).as_deref(),
+        Some(&Score(20))
+    );
+    assert_eq!(Query::<&Score>::new(&world).count(), 100);
+    world.run::<Update>();
+
+    let changed = &world.resource::<ChangedScores>().0;
+    assert_eq!(changed.len(), 5);
+    assert!(changed[1..].iter().all(Vec::is_empty));
+}
@@ -0,0 +1,115 @@
+use ecs::prelude::*;
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+
+/// Counts the allocations made by the current thread, so tests running in
+/// parallel don't skew each other.
+struct Counting;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, size: usize) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        unsafe { System.realloc(ptr, layout, size) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+fn allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+struct Position;
+
+impl Component for Position {}
+
+struct Tag<const N: usize>;
+
+impl<const N: usize> Component for Tag<N> {}
+
+fn add_tag<const N: usize>(world: &mut World) {
+    world.register::<Tag<N>>();
+    let entity = world.create();
+    world.add_component(entity, Position);
+    world.add_component(entity, Tag::<N>);
+}
+
+/// Builds `per_change` queries after each of ten archetype changes and
+/// returns the allocations made by the constructions alone.
+fn construction_allocations(per_change: usize) -> (usize, usize) {
+    let mut world = World::new();
+    world.register::<Position>();
+    for _ in 0..100 {
+        let entity = world.create();
+        world.add_component(entity, Position);
+    }
+    drop(Query::<&Position>::new(&world));
+
+    let changes: [fn(&mut World); 10] = [
+        add_tag::<0>,
+        add_tag::<1>,
+        add_tag::<2>,
+        add_tag::<3>,
+        add_tag::<4>,
+        add_tag::<5>,
+        add_tag::<6>,
+        add_tag::<7>,
+        add_tag::<8>,
+        add_tag::<9>,
+    ];
+    let rebuilds = world.query_cache().stats().rebuilds;
+    let mut total = 0;
+    for change in changes {
+        change(&mut world);
+        total += allocations(|| {
+            for _ in 0..per_change {
+                drop(Query::<&Position>::new(&world));
+            }
+        });
+    }
+
+    (total, world.query_cache().stats().rebuilds - rebuilds)
+}
+
+#[test]
+fn query_construction_allocates_per_archetype_change_not_per_query() {
+    let mut world = World::new();
+    world.register::<Position>();
+    let entity = world.create();
+    world.add_component(entity, Position);
+    drop(Query::<&Position>::new(&world));
+
+    let steady = allocations(|| {
+        for _ in 0..10_000 {
+            drop(Query::<&Position>::new(&world));
+        }
+    });
+    assert_eq!(steady, 0);
+    assert_eq!(world.query_cache().stats().constructions, 10_001);
+    assert_eq!(world.query_cache().stats().rebuilds, 1);
+
+    let (few, rebuilds) = construction_allocations(100);
+    assert_eq!(rebuilds, 10);
+    let (many, rebuilds) = construction_allocations(1_000);
+    assert_eq!(rebuilds, 10);
+    assert!(few > 0);
+    assert_eq!(few, many);
+}
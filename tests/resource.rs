@@ -0,0 +1,187 @@
+use ecs::{prelude::*, world::resource::ResourceType};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+struct Title {
+    text: String,
+    drops: Arc<AtomicUsize>,
+}
+
+impl Drop for Title {
+    fn drop(&mut self) {
+        self.drops.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Resource for Title {}
+
+static OBSERVED: AtomicUsize = AtomicUsize::new(0);
+
+fn title(drops: &Arc<AtomicUsize>) -> Title {
+    Title {
+        text: "rouge".repeat(100),
+        drops: drops.clone(),
+    }
+}
+
+#[test]
+fn removed_resources_are_handed_back_intact() {
+    let drops = Arc::new(AtomicUsize::new(0));
+    let mut world = World::new();
+    world.add_resource(title(&drops));
+
+    let removed = world.remove_resource::<Title>().unwrap();
+    assert_eq!(removed.text, "rouge".repeat(100));
+    assert!(!world.contains_resource::<Title>());
+    assert!(world.remove_resource::<Title>().is_none());
+    assert_eq!(drops.load(Ordering::Relaxed), 0);
+
+    drop(removed);
+    drop(world);
+    assert_eq!(drops.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+fn remove_resource_actions_drop_the_resource_once() {
+    let drops = Arc::new(AtomicUsize::new(0));
+    let mut world = World::new();
+    world.add_resource(title(&drops));
+    world.add_observers(Observers::<RemoveResource<Title>>::new().add_system(
+        |titles: &[Title]| {
+            assert_eq!(titles.len(), 1);
+            assert_eq!(titles[0].text, "rouge".repeat(100));
+            OBSERVED.fetch_add(1, Ordering::Relaxed);
+        },
+    ));
+
+    world
+        .resource_mut::<Actions>()
+        .add(RemoveResource::<Title>::new());
+    world
+        .resource_mut::<Actions>()
+        .add(RemoveResource::<Title>::new());
+    world.run::<Frame>();
+
+    assert!(!world.contains_resource::<Title>());
+    assert_eq!(OBSERVED.load(Ordering::Relaxed), 1);
+    assert_eq!(drops.load(Ordering::Relaxed), 1);
+    drop(world);
+    assert_eq!(drops.load(Ordering::Relaxed), 1);
+}
+
+struct Frame;
+
+impl SchedulePhase for Frame {
+    const PHASE: &'static str = "frame";
+}
+
+struct Gravity;
+
+impl Resource for Gravity {}
+
+struct Score;
+
+impl Resource for Score {}
+
+#[test]
+fn resource_names_list_user_resources_in_insertion_order() {
+    let mut world = World::new();
+    let resources = world.resources().len();
+    world.add_resource(Gravity);
+    world.add_resource(Score);
+
+    assert_eq!(
+        world.resource_names(false),
+        vec![
+            std::any::type_name::<Gravity>(),
+            std::any::type_name::<Score>(),
+        ]
+    );
+
+    let all = world.resource_names(true);
+    assert_eq!(all.len(), resources + 2);
+    assert_eq!(all.len(), world.resources().len());
+    assert!(all.contains(&std::any::type_name::<Actions>()));
+    assert!(all.ends_with(&[
+        std::any::type_name::<Gravity>(),
+        std::any::type_name::<Score>(),
+    ]));
+
+    let types = world.resources().iter_types().collect::<Vec<_>>();
+    assert!(types.contains(&(ResourceType::new::<Score>(), std::any::type_name::<Score>())));
+    assert!(world
+        .resources()
+        .contains_type(ResourceType::new::<Gravity>()));
+    assert!(!world
+        .resources()
+        .contains_type(ResourceType::new::<Title>()));
+}
+
+struct Main;
+
+impl ScheduleLabel for Main {
+    const LABEL: &'static str = "main";
+}
+
+fn panic_message(f: impl FnOnce()) -> String {
+    let panic = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).unwrap_err();
+    panic.downcast::<String>().map(|message| *message).unwrap()
+}
+
+#[test]
+fn conflicting_resource_borrows_panic_naming_the_resource() {
+    let mut world = World::new();
+    world.add_resource(Score);
+    let name = std::any::type_name::<Score>();
+
+    let message = panic_message(|| {
+        let _held = world.resource::<Score>();
+        world.resource_mut::<Score>();
+    });
+    assert!(message.contains(name), "{message}");
+    assert!(message.contains("borrowed immutably"), "{message}");
+
+    let message = panic_message(|| {
+        let _held = world.resource_mut::<Score>();
+        world.resource::<Score>();
+    });
+    assert!(message.contains(name), "{message}");
+    assert!(message.contains("borrowed mutably"), "{message}");
+
+    let held = world.resource::<Score>();
+    assert!(world.try_borrow_resource::<Score>().is_ok());
+    assert!(world.try_borrow_resource_mut::<Score>().is_err());
+    drop(held);
+    assert!(world.try_borrow_resource_mut::<Score>().is_ok());
+
+    // A system reading `Score` through the world while its argument holds
+    // it mutably.
+    world.add_system(Frame, Main, |_: &mut Score, world: &World| {
+        world.resource::<Score>();
+    });
+    let message = panic_message(|| {
+        world.run::<Frame>();
+    });
+    assert!(message.contains(name), "{message}");
+
+    // The panicking system's borrow was released with it.
+    assert!(world.try_borrow_resource_mut::<Score>().is_ok());
+}
+
+#[test]
+fn system_arguments_borrowing_a_resource_twice_panic_naming_it() {
+    let mut world = World::new();
+    world.add_resource(Gravity);
+    world.add_system(Frame, Main, |_: &Gravity, _: &mut Gravity| {});
+
+    let message = panic_message(|| {
+        world.run::<Frame>();
+    });
+    assert!(
+        message.contains(std::any::type_name::<Gravity>()),
+        "{message}"
+    );
+    assert!(world.try_borrow_resource_mut::<Gravity>().is_ok());
+}
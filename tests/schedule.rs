@@ -0,0 +1,764 @@
+use ecs::{
+    prelude::*,
+    schedule::{
+        graph::SystemGraph, runner::RunMode, DuplicateSystemPolicy, ScheduleBuildError,
+        ScheduleError,
+    },
+    tasks::CancellationToken,
+    world::{
+        runner::{FixedTime, MockClock, WorldRunner},
+        trace::TraceLevel,
+    },
+};
+use std::{
+    cell::Cell,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, ThreadId},
+    time::Duration,
+};
+
+struct Update;
+
+impl SchedulePhase for Update {
+    const PHASE: &'static str = "update";
+}
+
+struct Main;
+
+impl ScheduleLabel for Main {
+    const LABEL: &'static str = "main";
+}
+
+#[derive(Default)]
+struct Counter(u32);
+
+impl Resource for Counter {}
+
+fn read_counter(_: &Counter) {}
+
+fn between() {}
+
+fn write_counter(counter: &mut Counter) {
+    counter.0 += 1;
+}
+
+/// `between` is ordered between the two counter systems, and their
+/// conflicting access to `Counter` closes the loop.
+fn cyclic_graph() -> SystemGraph {
+    let mut graph = SystemGraph::new();
+    graph.add_system(between.after(read_counter).before(write_counter));
+    graph
+}
+
+#[test]
+fn cycles_are_reported_with_the_systems_involved() {
+    let error = cyclic_graph().build().unwrap_err();
+
+    let ScheduleBuildError::Cycle { systems } = &error;
+    let names = systems
+        .iter()
+        .map(|name| name.rsplit("::").next().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["read_counter", "between", "write_counter"]);
+    assert!(error.to_string().contains("schedule::between"));
+
+    assert_eq!(cyclic_graph().build().unwrap_err(), error);
+}
+
+#[derive(Default)]
+struct Counts {
+    ticks: Vec<u32>,
+    tocks: Vec<u32>,
+}
+
+impl Resource for Counts {}
+
+fn tick(mut count: Local<u32>, counts: &mut Counts) {
+    *count += 1;
+    counts.ticks.push(*count);
+}
+
+fn tock(mut count: Local<u32>, counts: &mut Counts) {
+    *count += 10;
+    counts.tocks.push(*count);
+}
+
+#[test]
+fn locals_are_kept_per_system() {
+    let mut world = World::new();
+    world.add_resource(Counts::default());
+    world.add_system(Update, Main, tick);
+    world.add_system(Update, Main, tock);
+
+    for _ in 0..3 {
+        world.run::<Update>();
+    }
+
+    let counts = world.resource::<Counts>();
+    assert_eq!(counts.ticks, vec![1, 2, 3]);
+    assert_eq!(counts.tocks, vec![10, 20, 30]);
+}
+
+fn spawn_one(mut commands: Commands) {
+    commands.add(CreateEntity::new());
+}
+
+fn spawn_two(mut commands: Commands) {
+    commands.add(CreateEntity::new());
+    commands.add(CreateEntity::new());
+}
+
+#[test]
+fn commands_systems_share_a_row_and_all_their_actions_run() {
+    let mut graph = SystemGraph::new();
+    graph.add_system(spawn_one.into_system());
+    graph.add_system(spawn_two.into_system());
+    graph.build().unwrap();
+    assert_eq!(graph.hierarchy().len(), 1);
+    assert_eq!(graph.hierarchy()[0].len(), 2);
+
+    let mut world = World::new();
+    world.add_system(Update, Main, spawn_one);
+    world.add_system(Update, Main, spawn_two);
+    world.run::<Update>();
+    world.run::<Update>();
+
+    assert_eq!(world.entities().len(), 6);
+}
+
+/// Shared by reading systems, which the scheduler may run side by side.
+#[derive(Default)]
+struct Trace {
+    entries: Mutex<Vec<(&'static str, ThreadId)>>,
+}
+
+impl Trace {
+    fn record(&self, name: &'static str) {
+        self.entries
+            .lock()
+            .unwrap()
+            .push((name, thread::current().id()));
+    }
+}
+
+impl Resource for Trace {}
+
+fn trace_a(trace: &Trace, mut commands: Commands) {
+    trace.record("a");
+    commands.add(CreateEntity::new());
+}
+
+fn trace_b(trace: &Trace) {
+    trace.record("b");
+}
+
+fn trace_c(trace: &Trace) {
+    trace.record("c");
+}
+
+fn trace_d(trace: &Trace) {
+    trace.record("d");
+}
+
+fn trace_created(_: &[Entity], trace: &Trace) {
+    trace.record("created");
+}
+
+fn sequential_trace() -> Vec<(&'static str, ThreadId)> {
+    let mut world = World::new();
+    world.set_default_run_mode(RunMode::Sequential);
+    world.add_resource(Trace::default());
+    world.add_system(Update, Main, trace_a);
+    world.add_system(Update, Main, trace_b);
+    world.add_system(Update, Main, trace_c);
+    world.add_system(Update, Main, trace_d);
+    world.add_observers(Observers::<CreateEntity>::new().add_system(trace_created));
+
+    for _ in 0..5 {
+        world.run::<Update>();
+    }
+
+    let entries = std::mem::take(&mut *world.resource::<Trace>().entries.lock().unwrap());
+    entries
+}
+
+#[test]
+fn sequential_worlds_stay_on_the_calling_thread_in_a_fixed_order() {
+    let expected = sequential_trace();
+    assert_eq!(expected.len(), 25);
+    assert!(expected.iter().all(|(_, id)| *id == thread::current().id()));
+
+    for _ in 0..10 {
+        assert_eq!(sequential_trace(), expected);
+    }
+}
+
+struct Transform;
+
+impl Component for Transform {}
+
+struct Velocity;
+
+impl Component for Velocity {}
+
+struct Health;
+
+impl Component for Health {}
+
+struct Mana;
+
+impl Component for Mana {}
+
+#[derive(Default)]
+struct Seen {
+    moving: Vec<Entity>,
+    added: Vec<Entity>,
+}
+
+impl Resource for Seen {}
+
+fn moving(query: Query<(Entity, &Transform), With<Velocity>>, seen: &mut Seen) {
+    seen.moving.extend(query.map(|(entity, _)| entity));
+}
+
+fn health(_: Query<&Health>) {}
+
+fn mana(_: Query<&Mana>) {}
+
+fn transform_added(entities: &[Entity], seen: &mut Seen) {
+    seen.added.extend_from_slice(entities);
+}
+
+#[test]
+fn systems_and_observers_registered_before_components_resolve_at_finalize() {
+    let mut world = World::new();
+    world.add_resource(Seen::default());
+    world.add_system(Update, Main, moving);
+    world.add_system(Update, Main, health);
+    world.add_system(Update, Main, mana);
+    world.add_observers(Observers::<AddComponent<Transform>>::new().add_system(transform_added));
+
+    world.register::<Velocity>();
+    world.register::<Transform>();
+    world.finalize();
+
+    let velocity = world.component_id::<Velocity>();
+    let transform = world.component_id::<Transform>();
+    let health = world.component_id::<Health>();
+    let mana = world.component_id::<Mana>();
+    assert!(velocity < transform);
+    assert!(transform < health);
+    assert!(health < mana);
+
+    let entity = world.spawn().insert(Velocity).id();
+    world.spawn().insert(Transform);
+    world
+        .resource_mut::<Actions>()
+        .add(AddComponent::new(entity, Transform));
+    world.run::<Update>();
+    assert!(world.resource::<Seen>().moving.is_empty());
+    assert_eq!(world.resource::<Seen>().added, vec![entity]);
+
+    world.run::<Update>();
+    assert_eq!(world.resource::<Seen>().moving, vec![entity]);
+    assert_eq!(world.component_id::<Mana>(), mana);
+}
+
+struct Combat;
+
+impl ScheduleLabel for Combat {
+    const LABEL: &'static str = "combat";
+}
+
+struct Ui;
+
+impl ScheduleLabel for Ui {
+    const LABEL: &'static str = "ui";
+}
+
+#[derive(Default)]
+struct Ran {
+    combat: u32,
+    ui: u32,
+}
+
+impl Resource for Ran {}
+
+fn ran(world: &World) -> (u32, u32) {
+    let ran = world.resource::<Ran>();
+    (ran.combat, ran.ui)
+}
+
+fn combat(ran: &mut Ran) {
+    ran.combat += 1;
+}
+
+fn ui(ran: &mut Ran) {
+    ran.ui += 1;
+}
+
+#[test]
+fn removed_scene_schedules_stop_running_until_re_added() {
+    let mut world = World::new();
+    world.add_resource(Ran::default());
+    world.add_scene_system(Update, Combat, combat);
+    world.add_scene_system(Update, Ui, ui);
+
+    world.run::<Update>();
+    assert_eq!(ran(&world), (1, 1));
+
+    let removed = world.remove_scene_schedule(Update, Combat).unwrap();
+    world.run::<Update>();
+    world.run::<Update>();
+    assert_eq!(ran(&world), (1, 3));
+
+    let traces = world.traces().len();
+    assert!(world.remove_scene_schedule(Update, Combat).is_none());
+    let warning = &world.traces()[traces];
+    assert_eq!(warning.source(), "remove_scene_schedule");
+    assert!(warning.message().contains("combat"));
+
+    world.add_scene_schedule(Update, Combat, removed);
+    world.run::<Update>();
+    assert_eq!(ran(&world), (2, 4));
+}
+
+fn counter_world(policy: DuplicateSystemPolicy) -> World {
+    let mut world = World::new();
+    world.add_resource(Counter::default());
+    world.set_duplicate_system_policy(policy);
+    world
+}
+
+#[test]
+fn duplicate_systems_are_warned_about_under_warn() {
+    let mut world = counter_world(DuplicateSystemPolicy::Warn);
+    world.add_system(Update, Main, write_counter);
+    assert!(world.traces().is_empty());
+    world.add_system(Update, Main, write_counter);
+
+    world.run::<Update>();
+    assert_eq!(world.resource::<Counter>().0, 2);
+
+    let warnings = world
+        .traces()
+        .into_iter()
+        .filter(|entry| entry.level() == TraceLevel::Warn)
+        .collect::<Vec<_>>();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].source(), "add_system");
+    assert!(warnings[0].message().contains("write_counter"));
+}
+
+#[test]
+fn duplicate_systems_are_rejected_under_reject() {
+    let mut world = counter_world(DuplicateSystemPolicy::Reject);
+    world.try_add_system(Update, Main, write_counter).unwrap();
+
+    let error = world
+        .try_add_system(Update, Main, write_counter)
+        .unwrap_err();
+    let ScheduleError::DuplicateSystem {
+        phase,
+        label,
+        system,
+    } = error;
+    assert_eq!((phase, label), ("update", "main"));
+    assert!(system.ends_with("write_counter"));
+
+    world.run::<Update>();
+    assert_eq!(world.resource::<Counter>().0, 1);
+}
+
+#[derive(Default)]
+struct Steps {
+    ran: Vec<u32>,
+    /// Cancelled by step 2 the first time it runs.
+    cancel: Option<CancellationToken>,
+}
+
+impl Resource for Steps {}
+
+fn step_1(steps: &mut Steps, actions: &mut Actions) {
+    steps.ran.push(1);
+    actions.add(CreateEntity::new());
+}
+
+fn step_2(steps: &mut Steps) {
+    steps.ran.push(2);
+    if let Some(token) = steps.cancel.take() {
+        token.cancel();
+    }
+}
+
+fn step_3(steps: &mut Steps) {
+    steps.ran.push(3);
+}
+
+fn step_4(steps: &mut Steps) {
+    steps.ran.push(4);
+}
+
+#[test]
+fn cancelled_frames_skip_later_rows_and_keep_pending_actions() {
+    for mode in [RunMode::Sequential, RunMode::Parallel] {
+        let mut world = World::new();
+        world.set_default_run_mode(mode);
+        let token = world.frame_token().clone();
+        world.add_resource(Steps {
+            ran: vec![],
+            cancel: Some(token),
+        });
+        world.add_system(
+            Update,
+            Main,
+            step_1.after(step_2.after(step_3.after(step_4))),
+        );
+
+        let report = world.run::<Update>();
+        assert!(report.is_cancelled());
+        assert_eq!(world.resource::<Steps>().ran, vec![1, 2]);
+        assert_eq!(world.entities().len(), 0);
+        assert_eq!(world.resource::<Actions>().len(), 1);
+        assert!(world.check_consistency().is_ok());
+
+        let report = world.run::<Update>();
+        assert!(!report.is_cancelled());
+        assert_eq!(world.resource::<Steps>().ran, vec![1, 2, 1, 2, 3, 4]);
+        assert_eq!(world.entities().len(), 2);
+        assert!(world.check_consistency().is_ok());
+    }
+}
+
+#[test]
+fn string_and_type_labels_coexist_in_one_phase() {
+    let mut world = World::new();
+    world.add_resource(Ran::default());
+    world.add_scene_system(Update, "enemy_ai", combat);
+    world.add_scene_system(Update, Ui, ui);
+
+    world.run::<Update>();
+    assert_eq!(ran(&world), (1, 1));
+
+    // A label built at runtime finds the schedule added under the literal.
+    let label = format!("enemy_{}", "ai");
+    let removed = world.remove_scene_schedule(Update, label).unwrap();
+    world.run::<Update>();
+    assert_eq!(ran(&world), (1, 2));
+    assert!(world.remove_scene_schedule(Update, "enemy_ai").is_none());
+
+    world.add_scene_schedule(Update, String::from("enemy_ai"), removed);
+    world.run::<Update>();
+    assert_eq!(ran(&world), (2, 3));
+}
+
+#[test]
+fn distinct_string_labels_do_not_collide() {
+    let mut world = counter_world(DuplicateSystemPolicy::Reject);
+    world
+        .try_add_system(Update, "enemy_ai", write_counter)
+        .unwrap();
+    world
+        .try_add_system(Update, "ally_ai", write_counter)
+        .unwrap();
+    world.try_add_system(Update, Main, write_counter).unwrap();
+
+    let ScheduleError::DuplicateSystem { label, .. } = world
+        .try_add_system(Update, String::from("ally_ai"), write_counter)
+        .unwrap_err();
+    assert_eq!(label, "ally_ai");
+
+    world.set_run_mode(Update, "enemy_ai", RunMode::Sequential);
+    world.run::<Update>();
+    assert_eq!(world.resource::<Counter>().0, 3);
+}
+
+/// Held by the server world so its shutdown can be counted.
+struct ServerSocket;
+
+impl Resource for ServerSocket {}
+
+static SOCKETS_CLOSED: AtomicUsize = AtomicUsize::new(0);
+
+fn close_server_socket(_: &mut ServerSocket, _: &mut World) {
+    SOCKETS_CLOSED.fetch_add(1, Ordering::SeqCst);
+}
+
+#[test]
+fn runner_caps_catch_up_after_a_stall_and_shuts_down_once() {
+    let mut world = World::new();
+    world.add_resource(Counter::default());
+    world.add_resource_with_teardown(ServerSocket, close_server_socket);
+    world.add_system(Update, Main, write_counter);
+
+    // Stall for 500ms during the 10th tick.
+    let clock = MockClock::new();
+    let stall = clock.clone();
+    let ticked = Rc::new(Cell::new(0));
+    let on_tick = ticked.clone();
+    let exit = WorldRunner::<Update>::new(world)
+        .clock(clock)
+        .tick_rate(30)
+        .max_catch_up(5)
+        .shutdown_on_exit(true)
+        .on_tick(move |_| {
+            on_tick.set(on_tick.get() + 1);
+            if on_tick.get() == 10 {
+                stall.advance(Duration::from_millis(500));
+            }
+        })
+        .run_until(|world| {
+            let time = world.resource::<FixedTime>();
+            assert_eq!(time.ticks(), world.resource::<Counter>().0 as u64);
+            assert_eq!(time.elapsed(), time.delta() * time.ticks() as u32);
+            time.ticks() == 30
+        });
+
+    assert_eq!(exit.ticks, 30);
+    assert_eq!(ticked.get(), 30);
+    assert_eq!(exit.catch_up_ticks, 5);
+    // 15 ticks were due after the stall; one plus five catch-up ran.
+    assert_eq!(exit.dropped_ticks, 9);
+    assert!(exit.world.is_none());
+    // Counter, ServerSocket and the runner's FixedTime.
+    assert_eq!(exit.shutdown.unwrap().unwrap().resources_dropped, 3);
+    assert_eq!(SOCKETS_CLOSED.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn runner_stops_on_cancellation_and_keeps_the_world() {
+    let mut world = World::new();
+    world.add_resource(Counter::default());
+    world.add_system(Update, Main, write_counter);
+
+    let token = CancellationToken::new();
+    let cancel = token.clone();
+    let exit = WorldRunner::<Update>::new(world)
+        .clock(MockClock::new())
+        .cancel_token(token)
+        .on_tick(move |_| cancel.cancel())
+        .run_until(|_| false);
+
+    assert_eq!(exit.ticks, 1);
+    assert!(exit.shutdown.is_none());
+    let world = exit.world.unwrap();
+    assert_eq!(world.resource::<Counter>().0, 1);
+    assert_eq!(
+        world.resource::<FixedTime>().delta(),
+        Duration::from_nanos(1_000_000_000 / 60)
+    );
+}
+
+/// Written by the producer thread with every field set to the same count.
+#[derive(Clone, Copy)]
+struct Reading([u64; 16]);
+
+#[derive(Default)]
+struct Readings {
+    inputs: Vec<u64>,
+    shared: Vec<u64>,
+    torn: usize,
+}
+
+impl Resource for Readings {}
+
+fn complete(reading: &Reading) -> Option<u64> {
+    let first = reading.0[0];
+    reading
+        .0
+        .iter()
+        .all(|value| *value == first)
+        .then_some(first)
+}
+
+fn read_shared(
+    input: &SharedInput<Reading>,
+    shared: &SharedResource<Reading>,
+    readings: &mut Readings,
+) {
+    match complete(input.latest()) {
+        Some(count) => readings.inputs.push(count),
+        None => readings.torn += 1,
+    }
+
+    match complete(&shared.lock()) {
+        Some(count) => readings.shared.push(count),
+        None => readings.torn += 1,
+    }
+}
+
+#[test]
+fn external_writers_never_tear_or_block_systems() {
+    let mut world = World::new();
+    world.add_resource(Readings::default());
+    world.add_system(Update, Main, read_shared);
+    let mut writer = world.add_shared_input(Reading([0; 16]));
+    let handle = world.add_shared(Reading([0; 16]));
+    assert!(world.shared_handle::<Reading>().is_some());
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let producer = {
+        let stop = stop.clone();
+        thread::spawn(move || {
+            let mut count = 0;
+            while !stop.load(Ordering::Relaxed) {
+                count += 1;
+                writer.write(Reading([count; 16]));
+                let mut shared = handle.lock().unwrap();
+                for value in shared.0.iter_mut() {
+                    *value = count;
+                    thread::yield_now();
+                }
+            }
+            count
+        })
+    };
+
+    for _ in 0..2_000 {
+        world.run::<Update>();
+    }
+    stop.store(true, Ordering::Relaxed);
+    let written = producer.join().unwrap();
+
+    let readings = world.resource::<Readings>();
+    assert_eq!(readings.torn, 0);
+    assert_eq!(readings.inputs.len(), 2_000);
+    assert!(readings.inputs.windows(2).all(|pair| pair[0] <= pair[1]));
+    assert!(readings.inputs.iter().all(|count| *count <= written));
+}
+
+#[derive(Default)]
+struct Latest(Vec<u32>);
+
+impl Resource for Latest {}
+
+fn read_latest(input: &SharedInput<u32>, latest: &mut Latest) {
+    latest.0.push(*input.latest());
+}
+
+#[test]
+fn shared_inputs_swap_in_the_newest_value_at_frame_start() {
+    let mut world = World::new();
+    world.add_resource(Latest::default());
+    world.add_system(Update, Main, read_latest);
+    world.add_system(Update, Main, read_latest);
+    let mut writer = world.add_shared_input(0u32);
+
+    let exit = WorldRunner::<Update>::new(world)
+        .clock(MockClock::new())
+        .tick_rate(30)
+        .on_tick(move |report| {
+            // Several writes between frames: only the last one is seen.
+            let frame = report.frame() as u32 + 1;
+            writer.write(frame * 10);
+            writer.write(frame * 10 + 1);
+        })
+        .run_until(|world| world.resource::<FixedTime>().ticks() == 4);
+
+    let world = exit.world.unwrap();
+    assert_eq!(
+        world.resource::<Latest>().0,
+        vec![0, 0, 11, 11, 21, 21, 31, 31]
+    );
+}
+
+/// Work for `budgeted_system`, finished once no steps are left.
+struct Job {
+    id: u32,
+    steps: u32,
+}
+
+#[derive(Default)]
+struct Finished {
+    jobs: Vec<(u32, u64)>,
+}
+
+impl Resource for Finished {}
+
+/// The frame being run, for `work` to record.
+#[derive(Default)]
+struct CurrentFrame(u64);
+
+impl Resource for CurrentFrame {}
+
+fn work(mut job: Job, (finished, time): &mut (&mut Finished, &CurrentFrame)) -> Option<Job> {
+    job.steps -= 1;
+    match job.steps {
+        0 => {
+            finished.jobs.push((job.id, time.0));
+            None
+        }
+        _ => Some(job),
+    }
+}
+
+fn job_world(budget: Duration) -> World {
+    let mut world = World::new();
+    world.add_resource(Finished::default());
+    world.add_resource(CurrentFrame::default());
+    world.add_system(Update, Main, budgeted_system(budget, work));
+    world.add_resource(WorkQueue::<Job>::new());
+    world
+}
+
+fn run_jobs(world: &mut World) {
+    world.resource_mut::<CurrentFrame>().0 = world.frame();
+    world.run::<Update>();
+}
+
+#[test]
+fn budgeted_systems_work_through_a_backlog_in_order() {
+    let mut world = job_world(Duration::ZERO);
+    for id in 0..1_000 {
+        world
+            .resource_mut::<WorkQueue<Job>>()
+            .push(Job { id, steps: 1 });
+    }
+
+    let mut ages = Vec::new();
+    while !world.resource::<WorkQueue<Job>>().is_empty() {
+        run_jobs(&mut world);
+        let stats = world.resource::<WorkQueue<Job>>().stats();
+        assert_eq!(stats.processed, 1);
+        assert_eq!(
+            stats.pending + world.resource::<Finished>().jobs.len(),
+            1_000
+        );
+        ages.push(stats.oldest_age);
+    }
+
+    // At least one item is worked on per run, even without a budget.
+    assert_eq!(world.frame(), 1_000);
+    let jobs = &world.resource::<Finished>().jobs;
+    assert!(jobs.iter().map(|job| job.0).eq(0..1_000));
+    assert!(jobs.iter().map(|job| job.1).eq(0..1_000));
+
+    // Everything was queued before the first run, so the oldest item ages
+    // with every frame until the backlog is gone.
+    assert!(ages[..999].iter().copied().eq(0..999));
+    assert_eq!(ages[999], 0);
+}
+
+#[test]
+fn requeued_items_continue_on_later_runs() {
+    let mut world = job_world(Duration::MAX);
+    let mut queue = world.resource_mut::<WorkQueue<Job>>();
+    queue.push(Job { id: 7, steps: 4 });
+    queue.push(Job { id: 8, steps: 1 });
+    drop(queue);
+
+    for frame in 0..3 {
+        run_jobs(&mut world);
+        let stats = world.resource::<WorkQueue<Job>>().stats();
+        assert_eq!(stats.requeued, 1, "frame {frame}");
+        assert_eq!(stats.pending, 1);
+    }
+
+    run_jobs(&mut world);
+    assert!(world.resource::<WorkQueue<Job>>().is_empty());
+    assert_eq!(world.resource::<Finished>().jobs, vec![(8, 0), (7, 3)]);
+}
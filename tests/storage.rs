@@ -0,0 +1,558 @@
+use ecs::{
+    archetype::ArchetypeId,
+    core::{ComponentId, Entity},
+    schedule::graph::NodeId,
+    storage::{
+        blob::Blob,
+        sparse::{SparseMap, SparseSet},
+        table::{Column, Table, TableRow, COLUMN_CHUNK_BYTES},
+    },
+};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+/// Counts its drops in a shared counter.
+struct Tracked(Arc<AtomicUsize>);
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// A `u64` column in chunks of 8 values.
+fn small_chunk_column() -> Column {
+    let mut column = Column::new::<u64>();
+    column.rechunk(64);
+    assert_eq!(column.chunk_len(), 8);
+    column
+}
+
+#[test]
+fn small_columns_allocate_less_than_a_chunk() {
+    let mut column = Column::new::<u64>();
+    column.push(1u64);
+    column.push(2u64);
+
+    assert!(column.allocated_bytes() < COLUMN_CHUNK_BYTES);
+
+    column.swap_remove_drop(0);
+    column.swap_remove_drop(0);
+    assert!(column.allocated_bytes() < COLUMN_CHUNK_BYTES);
+}
+
+#[test]
+fn full_chunks_keep_their_addresses_across_growth() {
+    let mut column = small_chunk_column();
+    for value in 0..20u64 {
+        column.push(value);
+    }
+
+    let full = 16;
+    let addresses = (0..full)
+        .map(|index| column.get::<u64>(index).unwrap() as *const u64)
+        .collect::<Vec<_>>();
+
+    for value in 20..200u64 {
+        column.push(value);
+    }
+
+    for (index, address) in addresses.iter().enumerate() {
+        assert_eq!(column.get::<u64>(index).unwrap() as *const u64, *address);
+    }
+    for value in 0..200u64 {
+        assert_eq!(column.get::<u64>(value as usize), Some(&value));
+    }
+    assert!(column.chunk_lens().all(|len| len <= 8));
+}
+
+#[test]
+fn swap_remove_at_chunk_boundaries() {
+    let mut column = small_chunk_column();
+    let mut expected = (0..24u64).collect::<Vec<_>>();
+    for value in expected.iter() {
+        column.push(*value);
+    }
+
+    // Ends and starts of chunks, the first value and finally the last one.
+    for index in [7, 8, 15, 0, 19] {
+        let last = expected.len() - 1;
+        let moved = column.swap_remove_drop(index);
+        expected.swap_remove(index);
+        assert_eq!(moved, (index != last).then_some(last));
+
+        assert_eq!(column.len(), expected.len());
+        for (index, value) in expected.iter().enumerate() {
+            assert_eq!(column.get::<u64>(index), Some(value));
+        }
+    }
+
+    while !column.is_empty() {
+        column.swap_remove_drop(column.len() / 2);
+    }
+    assert!(column.chunk_count() <= 1);
+}
+
+#[test]
+fn chunked_columns_drop_each_value_once() {
+    let drops = Arc::new(AtomicUsize::new(0));
+    let mut column = Column::new::<Tracked>();
+    column.rechunk(64);
+    let chunk_len = column.chunk_len();
+
+    let count = chunk_len * 3 + 5;
+    for _ in 0..count {
+        column.push(Tracked(drops.clone()));
+    }
+
+    let (blob, _) = column.swap_remove(chunk_len - 1);
+    drop(blob);
+    column.swap_remove_drop(chunk_len);
+    column.swap_remove_drop(column.len() - 1);
+    assert_eq!(drops.load(Ordering::Relaxed), 3);
+
+    column.shrink_to_fit();
+    column.reserve(chunk_len * 2);
+    assert_eq!(drops.load(Ordering::Relaxed), 3);
+
+    drop(column);
+    assert_eq!(drops.load(Ordering::Relaxed), count);
+}
+
+/// Pushes `capacity + extra` values made by `value` into a blob created
+/// with `capacity`, then reads them all back.
+fn push_past_capacity<T: PartialEq + std::fmt::Debug>(value: fn(usize) -> T) {
+    for capacity in [0, 1, 2, 3, 7, 16] {
+        for extra in 0..5 {
+            let mut blob = Blob::with_capacity::<T>(capacity);
+            assert_eq!(blob.capacity(), capacity);
+
+            let count = capacity + extra;
+            for index in 0..count {
+                blob.push(value(index));
+            }
+
+            assert_eq!(blob.len(), count);
+            assert!(blob.capacity() >= count);
+            for index in 0..count {
+                assert_eq!(blob.get::<T>(index), Some(&value(index)));
+            }
+        }
+    }
+}
+
+#[test]
+fn blobs_hold_more_than_their_initial_capacity() {
+    push_past_capacity(|index| index as u8);
+    push_past_capacity(|index| index as u16);
+    push_past_capacity(|index| index as u64);
+    push_past_capacity(|index| index as u128);
+    push_past_capacity(|index| [index as u8; 3]);
+    push_past_capacity(|index| (index as u64, index as u8));
+    push_past_capacity(|_| ());
+}
+
+#[test]
+fn blobs_keep_values_while_growing_from_any_capacity() {
+    for capacity in 0..32 {
+        let mut blob = Blob::with_capacity::<(u32, u64)>(capacity);
+        for index in 0..100u32 {
+            if index % 10 == 0 {
+                blob.reserve(index as usize % 7);
+            }
+            blob.push((index, index as u64 * 3));
+        }
+
+        let values = blob.as_slice::<(u32, u64)>();
+        assert_eq!(values.len(), 100);
+        for (index, value) in values.iter().enumerate() {
+            assert_eq!(*value, (index as u32, index as u64 * 3));
+        }
+    }
+}
+
+#[test]
+fn blob_push_drops_each_value_once() {
+    let drops = Arc::new(AtomicUsize::new(0));
+    let shared = Arc::new(());
+    let mut blob = Blob::new::<(Tracked, Arc<()>, String)>();
+    for index in 0..10 {
+        blob.push((Tracked(drops.clone()), shared.clone(), index.to_string()));
+    }
+
+    assert_eq!(drops.load(Ordering::Relaxed), 0);
+    assert_eq!(Arc::strong_count(&shared), 11);
+    let names = blob
+        .iter::<(Tracked, Arc<()>, String)>()
+        .map(|(_, _, name)| name.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        (0..10).map(|index| index.to_string()).collect::<Vec<_>>()
+    );
+
+    let old = blob.replace(
+        3,
+        (Tracked(drops.clone()), shared.clone(), "new".to_string()),
+    );
+    assert_eq!(old.map(|(_, _, name)| name), Some("3".to_string()));
+    assert_eq!(drops.load(Ordering::Relaxed), 1);
+
+    blob.clear();
+    assert_eq!(drops.load(Ordering::Relaxed), 11);
+    assert_eq!(Arc::strong_count(&shared), 1);
+
+    blob.push((Tracked(drops.clone()), shared.clone(), "last".to_string()));
+    drop(blob);
+    assert_eq!(drops.load(Ordering::Relaxed), 12);
+    assert_eq!(Arc::strong_count(&shared), 1);
+}
+
+#[test]
+fn blob_swap_remove_drops_each_value_once() {
+    let drops = Arc::new(AtomicUsize::new(0));
+    let mut blob = Blob::new::<(Tracked, u32)>();
+    for index in 0..6u32 {
+        blob.push((Tracked(drops.clone()), index));
+    }
+
+    // The last value: nothing is swapped in.
+    let removed = blob.swap_remove(5);
+    assert_eq!(drops.load(Ordering::Relaxed), 0);
+    drop(removed);
+    assert_eq!(drops.load(Ordering::Relaxed), 1);
+
+    // The first value: the last one is swapped in.
+    let (tracked, index) = blob.swap_remove_as::<(Tracked, u32)>(0);
+    assert_eq!(index, 0);
+    assert_eq!(
+        blob.get::<(Tracked, u32)>(0).map(|(_, index)| *index),
+        Some(4)
+    );
+    drop(tracked);
+    assert_eq!(drops.load(Ordering::Relaxed), 2);
+
+    // A middle value.
+    drop(blob.swap_remove(1));
+    assert_eq!(drops.load(Ordering::Relaxed), 3);
+    let indices = blob
+        .iter::<(Tracked, u32)>()
+        .map(|(_, index)| *index)
+        .collect::<Vec<_>>();
+    assert_eq!(indices, vec![4, 3, 2]);
+
+    drop(blob);
+    assert_eq!(drops.load(Ordering::Relaxed), 6);
+}
+
+#[test]
+fn column_swap_remove_drop_drops_the_removed_value() {
+    let drops = Arc::new(AtomicUsize::new(0));
+    let mut column = Column::new::<Tracked>();
+    for _ in 0..3 {
+        column.push(Tracked(drops.clone()));
+    }
+
+    assert_eq!(column.swap_remove_drop(0), Some(2));
+    assert_eq!(drops.load(Ordering::Relaxed), 1);
+    assert_eq!(column.swap_remove_drop(1), None);
+    assert_eq!(drops.load(Ordering::Relaxed), 2);
+
+    drop(column);
+    assert_eq!(drops.load(Ordering::Relaxed), 3);
+}
+
+const POSITION: usize = 0;
+const NAME: usize = 1;
+
+fn row(entity: Entity, position: Option<(f32, f32)>, name: &str) -> TableRow<Entity> {
+    let mut columns = SparseSet::new();
+    if let Some(position) = position {
+        let mut column = Column::new::<(f32, f32)>();
+        column.push(position);
+        columns.insert(POSITION, column);
+    }
+
+    let mut column = Column::new::<String>();
+    column.push(name.to_string());
+    columns.insert(NAME, column);
+    TableRow::new(entity, columns)
+}
+
+fn name_table() -> Table<Entity> {
+    Table::with_capacity(4)
+        .add_column(POSITION, Column::new::<(f32, f32)>())
+        .add_column(NAME, Column::new::<String>())
+        .build()
+}
+
+#[test]
+fn table_rows_round_trip_through_add_and_remove() {
+    let mut table = name_table();
+    let entities = (0..3).map(|id| Entity::new(id, 0)).collect::<Vec<_>>();
+    for (index, entity) in entities.iter().enumerate() {
+        let position = (index as f32, -(index as f32));
+        table.add_row(
+            *entity,
+            row(*entity, Some(position), &format!("e{}", index)),
+        );
+    }
+
+    assert_eq!(table.len(), 3);
+    assert_eq!(
+        table.get::<(f32, f32)>(entities[1], POSITION),
+        Some(&(1.0, -1.0))
+    );
+    assert_eq!(
+        table.get::<String>(entities[2], NAME).map(String::as_str),
+        Some("e2")
+    );
+
+    let removed = table.remove_row(entities[0]).unwrap().removed;
+    assert_eq!(table.len(), 2);
+    assert_eq!(table.get::<String>(entities[0], NAME), None);
+    assert_eq!(
+        table.get::<String>(entities[2], NAME).map(String::as_str),
+        Some("e2")
+    );
+
+    let mut other = name_table();
+    other.add_row(entities[0], removed);
+    assert_eq!(
+        other.get::<(f32, f32)>(entities[0], POSITION),
+        Some(&(0.0, -0.0))
+    );
+    assert_eq!(
+        other.get::<String>(entities[0], NAME).map(String::as_str),
+        Some("e0")
+    );
+}
+
+#[test]
+#[should_panic(expected = "Missing column 0")]
+fn add_row_without_a_table_column_panics() {
+    let mut table = name_table();
+    let entity = Entity::new(0, 0);
+    table.add_row(entity, row(entity, None, "e0"));
+}
+
+#[test]
+fn add_row_panics_before_changing_the_table() {
+    let mut table = name_table();
+    let first = Entity::new(0, 0);
+    table.add_row(first, row(first, Some((1.0, 2.0)), "e0"));
+
+    let second = Entity::new(1, 0);
+    let added = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        table.add_row(second, row(second, None, "e1"));
+    }));
+
+    assert!(added.is_err());
+    assert_eq!(table.len(), 1);
+    assert_eq!(table.rows(), &[first]);
+    assert_eq!(table.get::<String>(second, NAME), None);
+}
+
+const COUNT: usize = 0;
+const SEEN: usize = 1;
+
+/// A table of `rows` entities with a `u32` visit count and a `u64` copy of
+/// the entity id.
+fn counted_table(rows: usize) -> Table<Entity> {
+    let mut table = Table::with_capacity(rows)
+        .add_column(COUNT, Column::new::<u32>())
+        .add_column(SEEN, Column::new::<u64>())
+        .build();
+    for id in 0..rows {
+        let entity = Entity::new(id, 0);
+        let mut columns = SparseSet::new();
+        let mut count = Column::new::<u32>();
+        count.push(0u32);
+        columns.insert(COUNT, count);
+        let mut seen = Column::new::<u64>();
+        seen.push(u64::MAX);
+        columns.insert(SEEN, seen);
+        table.add_row(entity, TableRow::new(entity, columns));
+    }
+    table
+}
+
+/// Splits `rows` rows into 7 views of `chunk_size` rows, the last one
+/// shorter, and mutates each view on its own thread.
+fn mutate_partitioned_rows(rows: usize, chunk_size: usize) {
+    let mut table = counted_table(rows);
+
+    let mut views = table.partition_rows_mut(&[COUNT, SEEN], chunk_size);
+    assert_eq!(views.len(), 7);
+    assert_eq!(views.last().unwrap().range(), chunk_size * 6..rows);
+
+    std::thread::scope(|scope| {
+        for view in views.iter_mut() {
+            let mut counts = view.take::<u32>(COUNT).unwrap();
+            let mut seen = view.take::<u64>(SEEN).unwrap();
+            assert!(view.take::<u32>(COUNT).is_none());
+            let rows = view.rows();
+            scope.spawn(move || {
+                counts.for_each_mut(1, |count| *count += 1);
+                for (index, entity) in rows.iter().enumerate() {
+                    *seen.get_mut(index, 1).unwrap() = entity.id() as u64;
+                }
+            });
+        }
+    });
+    drop(views);
+
+    for (row, entity) in table.rows().to_vec().into_iter().enumerate() {
+        assert_eq!(entity.id() as usize, row);
+        assert_eq!(table.get::<u32>(entity, COUNT), Some(&1));
+        assert_eq!(table.get::<u64>(entity, SEEN), Some(&(row as u64)));
+    }
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn partitioned_rows_are_each_mutated_once_in_parallel() {
+    mutate_partitioned_rows(10_000, 1_500);
+}
+
+/// Small enough to run under Miri.
+#[test]
+fn small_partitioned_rows_are_each_mutated_once_in_parallel() {
+    mutate_partitioned_rows(70, 11);
+}
+
+#[test]
+fn uneven_column_partitions_cover_each_row_once() {
+    let mut column = Column::new::<u32>();
+    for _ in 0..10_000 {
+        column.push(0u32);
+    }
+
+    let bounds = [0, 1, 700, 701, 3_000, 3_333, 9_999, 10_000];
+    let ranges = bounds
+        .windows(2)
+        .map(|pair| pair[0]..pair[1])
+        .rev()
+        .collect::<Vec<_>>();
+    let mut views = column.partition_mut::<u32>(&ranges);
+    assert_eq!(views.len(), 7);
+
+    std::thread::scope(|scope| {
+        for (view, range) in views.iter_mut().zip(&ranges) {
+            assert_eq!(view.range(), *range);
+            scope.spawn(move || view.for_each_mut(1, |value| *value += 1));
+        }
+    });
+    drop(views);
+
+    for row in 0..10_000 {
+        assert_eq!(column.get::<u32>(row), Some(&1));
+    }
+}
+
+#[test]
+#[should_panic(expected = "overlap")]
+fn overlapping_column_partitions_panic() {
+    let mut column = Column::new::<u32>();
+    for _ in 0..100 {
+        column.push(0u32);
+    }
+    column.partition_mut::<u32>(&[0..40, 60..100, 39..60]);
+}
+
+#[test]
+#[should_panic(expected = "out of bounds")]
+fn out_of_bounds_column_partitions_panic() {
+    let mut column = Column::new::<u32>();
+    for _ in 0..100 {
+        column.push(0u32);
+    }
+    column.partition_mut::<u32>(&[0..50, 50..101]);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "read as f32")]
+fn column_partitions_of_the_wrong_type_panic() {
+    let mut column = Column::new::<u32>();
+    column.push(0u32);
+    column.partition_mut::<f32>(&[0..1]);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "read as i64")]
+fn taking_row_views_of_the_wrong_type_panics() {
+    let mut table = counted_table(10);
+    let mut views = table.partition_rows_mut(&[COUNT, SEEN], 5);
+    views[0].take::<i64>(SEEN);
+}
+
+#[test]
+fn table_rows_of_an_old_generation_are_unreachable() {
+    let mut table = name_table();
+    let old = Entity::new(3, 0);
+    table.add_row(old, row(old, Some((1.0, 1.0)), "old"));
+    assert!(table.remove_row(old).is_some());
+
+    let new = Entity::new(3, 1);
+    table.add_row(new, row(new, Some((2.0, 2.0)), "new"));
+
+    assert_eq!(table.get::<String>(old, NAME), None);
+    assert!(table.row(old).is_none());
+    assert!(table.remove_row(old).is_none());
+    assert_eq!(
+        table.get::<String>(new, NAME).map(String::as_str),
+        Some("new")
+    );
+    assert_eq!(table.len(), 1);
+}
+
+#[test]
+fn dense_keys_allocate_one_slot_per_element() {
+    const COUNT: usize = 1000;
+    let mut by_index = SparseSet::new();
+    let mut by_component = SparseSet::new();
+    let mut by_node = SparseSet::new();
+    for index in 0..COUNT {
+        by_index.insert(index, index);
+        by_component.insert(ComponentId::new(index), index);
+        by_node.insert(NodeId::new(index), index);
+    }
+
+    for set in [&by_index, &by_component, &by_node] {
+        assert_eq!(set.len(), COUNT);
+        assert!((COUNT..2 * COUNT).contains(&set.capacity()));
+        assert!((COUNT..2 * COUNT).contains(&set.sparse_capacity()));
+    }
+}
+
+#[test]
+fn hashed_keys_allocate_per_element_not_per_key_value() {
+    const COUNT: usize = 1000;
+    let mut dense = SparseMap::new();
+    let mut by_archetype = SparseMap::new();
+    for index in 0..COUNT {
+        dense.insert(index, index);
+        let archetype = ArchetypeId::new(&[ComponentId::new(index)]);
+        by_archetype.insert(archetype, index);
+    }
+
+    // Archetype ids are hashes spread over the whole `u64` range, yet the
+    // map takes as much memory as one keyed by 0..COUNT.
+    assert!(by_archetype.keys().any(|id| id.id() > u64::from(u32::MAX)));
+    assert_eq!(by_archetype.len(), COUNT);
+    assert!((COUNT..2 * COUNT).contains(&by_archetype.capacity()));
+    assert_eq!(by_archetype.allocated_bytes(), dense.allocated_bytes());
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "hashed keys belong in a SparseMap")]
+fn hash_sized_indices_panic_in_sparse_sets() {
+    let archetype = ArchetypeId::new(&[ComponentId::new(0)]);
+    let mut set = SparseSet::new();
+    set.insert(archetype.id() as usize | 1 << 40, ());
+}
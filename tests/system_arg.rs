@@ -0,0 +1,172 @@
+use ecs::{
+    schedule::{graph::SystemGraph, ScheduleLabel, SchedulePhase},
+    system::{IntoSystem, ReadOnlySystemArg, SystemArg},
+    world::{
+        meta::{Access, AccessMeta, AccessType},
+        resource::{Resource, ResourceRef, ResourceRefMut},
+        World,
+    },
+};
+
+struct Update;
+
+impl SchedulePhase for Update {
+    const PHASE: &'static str = "update";
+}
+
+struct DefaultLabel;
+
+impl ScheduleLabel for DefaultLabel {
+    const LABEL: &'static str = "default";
+}
+
+struct Time {
+    delta: f32,
+}
+
+impl Resource for Time {}
+
+/// Seconds since the last frame, read from the `Time` resource.
+struct DeltaTime<'a>(ResourceRef<'a, Time>);
+
+impl DeltaTime<'_> {
+    fn seconds(&self) -> f32 {
+        self.0.delta
+    }
+}
+
+impl SystemArg for DeltaTime<'_> {
+    type Item<'a> = DeltaTime<'a>;
+
+    fn get<'a>(world: &'a World) -> Self::Item<'a> {
+        DeltaTime(world.resource::<Time>())
+    }
+
+    fn metas() -> Vec<AccessMeta> {
+        vec![AccessMeta::new(
+            AccessType::resource::<Time>(),
+            Access::Read,
+        )]
+    }
+}
+
+unsafe impl ReadOnlySystemArg for DeltaTime<'_> {}
+
+#[derive(Default)]
+struct Outbox {
+    messages: Vec<String>,
+}
+
+impl Resource for Outbox {}
+
+/// Synchronization domain shared by everything touching the network.
+struct NetworkDomain;
+
+struct Net<'a> {
+    outbox: ResourceRefMut<'a, Outbox>,
+}
+
+impl Net<'_> {
+    fn send(&mut self, message: impl Into<String>) {
+        self.outbox.messages.push(message.into());
+    }
+}
+
+impl SystemArg for Net<'_> {
+    type Item<'a> = Net<'a>;
+
+    fn get<'a>(world: &'a World) -> Self::Item<'a> {
+        Net {
+            outbox: world.resource_mut::<Outbox>(),
+        }
+    }
+
+    fn metas() -> Vec<AccessMeta> {
+        vec![AccessMeta::new(
+            AccessType::custom::<NetworkDomain>(),
+            Access::Write,
+        )]
+    }
+}
+
+fn assert_read_only<A: ReadOnlySystemArg>() {}
+
+fn advance(time: DeltaTime, mut net: Net) {
+    net.send(format!("tick {}", time.seconds()));
+}
+
+fn ping(mut net: Net) {
+    net.send("ping");
+}
+
+fn pong(mut net: Net) {
+    net.send("pong");
+}
+
+fn log_time(_: DeltaTime) {}
+
+#[test]
+fn custom_args_run_in_systems() {
+    assert_read_only::<DeltaTime>();
+    assert_read_only::<(DeltaTime, &World)>();
+
+    let mut world = World::new();
+    world.add_resource(Time { delta: 0.5 });
+    world.add_resource(Outbox::default());
+    world.add_system(Update, DefaultLabel, advance);
+
+    world.init();
+    world.run::<Update>();
+
+    assert_eq!(world.resource::<Outbox>().messages, vec!["tick 0.5"]);
+}
+
+#[test]
+fn custom_writers_are_serialized() {
+    let mut graph = SystemGraph::new();
+    graph.add_system(ping.into_system());
+    graph.add_system(pong.into_system());
+    graph.build().unwrap();
+
+    assert_eq!(graph.hierarchy().len(), 2);
+}
+
+#[test]
+fn disjoint_custom_access_runs_together() {
+    let mut graph = SystemGraph::new();
+    graph.add_system(ping.into_system());
+    graph.add_system(log_time.into_system());
+    graph.build().unwrap();
+
+    assert_eq!(graph.hierarchy().len(), 1);
+}
+
+struct Overlay {
+    label: &'static str,
+}
+
+impl Resource for Overlay {}
+
+#[derive(Default)]
+struct Seen {
+    overlays: Vec<Option<&'static str>>,
+}
+
+impl Resource for Seen {}
+
+fn draw_overlay(overlay: Option<&Overlay>, seen: &mut Seen) {
+    seen.overlays.push(overlay.map(|overlay| overlay.label));
+}
+
+#[test]
+fn optional_resources_are_none_until_added() {
+    let mut world = World::new();
+    world.add_resource(Seen::default());
+    world.add_system(Update, DefaultLabel, draw_overlay);
+
+    world.run::<Update>();
+    world.add_resource(Overlay { label: "debug" });
+    world.run::<Update>();
+
+    assert_eq!(world.resource::<Seen>().overlays, vec![None, Some("debug")]);
+}
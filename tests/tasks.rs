@@ -0,0 +1,142 @@
+use ecs::tasks::{ScopedTaskPool, TaskPool};
+use std::{
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread::ThreadId,
+    time::{Duration, Instant},
+};
+
+#[test]
+fn scoped_pool_runs_every_job_before_returning() {
+    let done = AtomicUsize::new(0);
+    let threads = Mutex::new(Vec::<ThreadId>::new());
+
+    let queued = ScopedTaskPool::scope(4, |pool| {
+        for _ in 0..100 {
+            pool.execute(|| {
+                done.fetch_add(1, Ordering::Relaxed);
+                threads.lock().unwrap().push(std::thread::current().id());
+            });
+        }
+        100
+    });
+
+    assert_eq!(done.load(Ordering::Relaxed), queued);
+    let threads = threads.into_inner().unwrap();
+    assert_eq!(threads.len(), 100);
+    assert!(!threads.contains(&std::thread::current().id()));
+}
+
+#[test]
+fn scoped_pool_surfaces_a_job_panic_without_hanging() {
+    let done = AtomicUsize::new(0);
+
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        ScopedTaskPool::scope(4, |pool| {
+            pool.execute(|| panic!("job failed"));
+            for _ in 0..20 {
+                pool.execute(|| {
+                    done.fetch_add(1, Ordering::Relaxed);
+                });
+            }
+        })
+    }));
+
+    let payload = result.unwrap_err();
+    assert_eq!(payload.downcast_ref::<&str>(), Some(&"job failed"));
+    assert_eq!(done.load(Ordering::Relaxed), 20);
+}
+
+const JOBS: usize = 100_000;
+const WORKERS: usize = 4;
+
+/// Enough work that a job isn't only queue overhead.
+fn tiny_job(done: &AtomicUsize) {
+    std::hint::black_box((0..32u64).sum::<u64>());
+    done.fetch_add(1, Ordering::Relaxed);
+}
+
+fn run_pool(batch: bool) -> (Duration, Vec<usize>) {
+    let done = Arc::new(AtomicUsize::new(0));
+    let mut pool = TaskPool::new(WORKERS);
+
+    let start = Instant::now();
+    if batch {
+        pool.execute_batch((0..JOBS).map(|_| {
+            let done = done.clone();
+            move || tiny_job(&done)
+        }));
+    } else {
+        for _ in 0..JOBS {
+            let done = done.clone();
+            pool.execute(move || tiny_job(&done));
+        }
+    }
+    pool.join();
+    let elapsed = start.elapsed();
+
+    assert_eq!(done.load(Ordering::Relaxed), JOBS);
+    let metrics = pool.metrics();
+    assert_eq!(metrics.total(), JOBS);
+    (elapsed, metrics.executed)
+}
+
+/// Every worker sharing one `Mutex<Receiver>`, as the pool used to.
+fn run_shared_receiver() -> Duration {
+    let done = Arc::new(AtomicUsize::new(0));
+    let (sender, receiver) = mpsc::channel::<Box<dyn FnOnce() + Send>>();
+    let receiver = Arc::new(Mutex::new(receiver));
+
+    let start = Instant::now();
+    let workers = (0..WORKERS)
+        .map(|_| {
+            let receiver = receiver.clone();
+            std::thread::spawn(move || loop {
+                let job = receiver.lock().unwrap().recv();
+                match job {
+                    Ok(job) => job(),
+                    Err(_) => break,
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for _ in 0..JOBS {
+        let done = done.clone();
+        sender.send(Box::new(move || tiny_job(&done))).unwrap();
+    }
+    drop(sender);
+    for worker in workers {
+        worker.join().unwrap();
+    }
+    let elapsed = start.elapsed();
+
+    assert_eq!(done.load(Ordering::Relaxed), JOBS);
+    elapsed
+}
+
+#[test]
+fn task_pool_runs_every_job_spread_over_its_workers() {
+    for batch in [false, true] {
+        let (_, executed) = run_pool(batch);
+        assert_eq!(executed.len(), WORKERS);
+        // Stealing moves jobs around, so only a lopsided split fails.
+        let most = executed.iter().max().unwrap();
+        assert!(*most < JOBS * 3 / 4, "{:?}", executed);
+    }
+}
+
+#[test]
+fn task_pool_keeps_up_with_a_shared_receiver() {
+    let baseline = run_shared_receiver();
+    let (pool, _) = run_pool(true);
+    // Loose enough for a loaded machine; a convoy on one lock is far worse.
+    assert!(
+        pool <= baseline * 3,
+        "pool took {:?}, shared receiver {:?}",
+        pool,
+        baseline
+    );
+}
@@ -0,0 +1,2748 @@
+use ecs::{
+    archetype::ArchetypeId,
+    core::{allocator::AllocMode, ComponentError, ComponentId, StorageKind},
+    prelude::*,
+    schedule::Shutdown,
+    storage::{blob::Blob, ptr::Ptr},
+    testing::{corrupt, Corruption},
+    world::{
+        consistency::ConsistencyError,
+        defrag::DefragmentProgress,
+        error::{ReflectError, SnapshotError, WorldError},
+        external::SendError,
+        index::IndexedComponent,
+        query::QueryState,
+        reflect::FieldRef,
+        repair::RepairPolicy,
+        shutdown::ShutdownError,
+        trace::TraceLevel,
+    },
+};
+use std::{
+    alloc::Layout,
+    collections::HashMap,
+    ptr::NonNull,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+#[derive(Debug, PartialEq)]
+struct A(u32);
+
+impl Component for A {}
+
+#[derive(Debug, PartialEq)]
+struct B(u32);
+
+impl Component for B {}
+
+#[derive(Debug, PartialEq)]
+struct C(u32);
+
+impl Component for C {}
+
+#[derive(Debug, PartialEq)]
+struct D(u32);
+
+impl Component for D {}
+
+#[derive(Debug, PartialEq)]
+struct E(u32);
+
+impl Component for E {}
+
+fn world() -> World {
+    let mut world = World::new();
+    world.register::<A>();
+    world.register::<B>();
+    world.register::<C>();
+    world.register::<D>();
+    world.register::<E>();
+    world
+}
+
+#[test]
+fn spawning_five_components_moves_the_entity_once() {
+    let mut world = world();
+    let moves = world.archetypes().moves();
+
+    let mut spawn = world.spawn();
+    spawn.insert(A(1)).insert(B(2)).insert(C(3));
+    spawn.insert(D(4)).insert(E(5));
+    let entity = spawn.finish();
+
+    assert_eq!(world.archetypes().moves(), moves + 1);
+    let archetypes = world
+        .archetypes()
+        .iter()
+        .filter(|archetype| !archetype.components().is_empty())
+        .collect::<Vec<_>>();
+    assert_eq!(archetypes.len(), 1);
+    assert_eq!(archetypes[0].components().len(), 5);
+    assert_eq!(archetypes[0].entities(), &[entity]);
+    assert_eq!(world.component::<C>(entity).as_deref(), Some(&C(3)));
+    assert_eq!(world.component::<E>(entity).as_deref(), Some(&E(5)));
+}
+
+#[test]
+fn tracked_entities_log_each_lifecycle_step_in_order() {
+    use ecs::world::tracking::TrackingEvent;
+
+    let mut world = world();
+    let parent = world.spawn().finish();
+    let entity = world.spawn().insert(A(1)).id();
+
+    world.track(entity, "player");
+    world.add_component(entity, B(2));
+    world.add_child(parent, entity);
+    world.remove_component::<A>(entity);
+    world.delete(entity);
+
+    let log = world.tracking_log(entity).unwrap();
+    assert_eq!(log.label(), "player");
+    assert_eq!(log.dropped(), 0);
+    assert!(log
+        .records()
+        .iter()
+        .all(|record| record.frame() == world.frame()));
+
+    let events = log
+        .records()
+        .iter()
+        .map(|record| record.event().clone())
+        .collect::<Vec<_>>();
+    assert_eq!(events.len(), 5, "{}", log);
+    assert_eq!(events[0], TrackingEvent::Tracked);
+    assert!(matches!(
+        events[1],
+        TrackingEvent::AddComponent { component, .. } if component.ends_with("::B")
+    ));
+    assert_eq!(
+        events[2],
+        TrackingEvent::ParentChange {
+            old: None,
+            new: Some(parent),
+        }
+    );
+    assert!(matches!(
+        events[3],
+        TrackingEvent::RemoveComponent { component, .. } if component.ends_with("::A")
+    ));
+    assert_eq!(events[4], TrackingEvent::Delete);
+
+    let dump = log.to_string();
+    assert!(dump.contains("added") && dump.contains("removed") && dump.contains("deleted"));
+}
+
+#[test]
+fn reused_ids_start_without_the_old_components() {
+    let mut world = world();
+    let old = world.spawn().insert(A(1)).insert(B(2)).id();
+    world.delete(old);
+
+    let new = world.create();
+    assert_eq!(new.id(), old.id());
+    assert_ne!(new.generation(), old.generation());
+
+    assert_eq!(world.component::<A>(new).as_deref(), None);
+    assert_eq!(world.component::<B>(new).as_deref(), None);
+    assert!(world.archetypes().mask(new).unwrap().is_empty());
+    assert!(world
+        .archetypes()
+        .entity_archetype(new)
+        .unwrap()
+        .components()
+        .is_empty());
+
+    assert_eq!(world.component::<A>(old).as_deref(), None);
+    assert!(world.archetypes().archetype_id(old).is_none());
+    assert!(world.archetypes().mask(old).is_none());
+
+    world.add_component(new, C(3));
+    assert_eq!(world.component::<C>(new).as_deref(), Some(&C(3)));
+    assert_eq!(world.component::<C>(old).as_deref(), None);
+}
+
+struct Update;
+
+impl SchedulePhase for Update {
+    const PHASE: &'static str = "update";
+}
+
+#[derive(Default)]
+struct Observed {
+    created: Vec<Entity>,
+    added_a: Vec<Entity>,
+    added_c: Vec<Entity>,
+}
+
+impl Resource for Observed {}
+
+fn created(entities: &[Entity], observed: &mut Observed) {
+    observed.created.extend_from_slice(entities);
+}
+
+fn added_a(entities: &[Entity], observed: &mut Observed) {
+    observed.added_a.extend_from_slice(entities);
+}
+
+fn added_c(entities: &[Entity], observed: &mut Observed) {
+    observed.added_c.extend_from_slice(entities);
+}
+
+fn value<C: Component>(component: C) -> Blob {
+    let mut blob = Blob::new::<C>();
+    blob.push(component);
+    blob
+}
+
+/// The archetype made of exactly `components`, if one was ever created.
+fn archetype_of(world: &World, components: &[ComponentId]) -> Option<ArchetypeId> {
+    let mut components = components.to_vec();
+    components.sort();
+    world
+        .archetypes()
+        .iter()
+        .find(|archetype| {
+            let mut other = archetype.components().to_vec();
+            other.sort();
+            other == components
+        })
+        .map(|archetype| *archetype.id())
+}
+
+#[test]
+fn bundle_inserts_move_straight_to_the_final_archetype() {
+    let mut world = world();
+    world.add_resource(Observed::default());
+    world.add_observers(Observers::<AddComponent<A>>::new().add_system(added_a));
+    world.add_observers(Observers::<AddComponent<C>>::new().add_system(added_c));
+    let [a, b, c] = [
+        world.component_id::<A>(),
+        world.component_id::<B>(),
+        world.component_id::<C>(),
+    ];
+    let entity = world.spawn().insert(B(2)).id();
+    let archetypes = world.archetypes().len();
+    let moves = world.archetypes().moves();
+
+    world.add_bundle(entity, (A(1), C(3))).unwrap();
+
+    assert_eq!(world.archetypes().len(), archetypes + 1);
+    assert_eq!(world.archetypes().moves(), moves + 1);
+    assert_eq!(archetype_of(&world, &[a, b]), None);
+    assert_eq!(archetype_of(&world, &[b, c]), None);
+    assert_eq!(
+        world.archetypes().archetype_id(entity).copied(),
+        archetype_of(&world, &[a, b, c])
+    );
+    assert_eq!(world.component::<A>(entity).as_deref(), Some(&A(1)));
+    assert_eq!(world.component::<B>(entity).as_deref(), Some(&B(2)));
+    assert_eq!(world.component::<C>(entity).as_deref(), Some(&C(3)));
+
+    world.run::<Update>();
+    let observed = world.resource::<Observed>();
+    assert_eq!(observed.added_a, vec![entity]);
+    assert_eq!(observed.added_c, vec![entity]);
+}
+
+#[test]
+fn remove_bundle_round_trips_through_the_same_archetypes() {
+    let mut world = world();
+    let entity = world.spawn().insert(B(2)).id();
+    let original = *world.archetypes().archetype_id(entity).unwrap();
+    world.add_bundle(entity, (A(1), C(3))).unwrap();
+    let bundled = *world.archetypes().archetype_id(entity).unwrap();
+    let archetypes = world.archetypes().len();
+    let moves = world.archetypes().moves();
+
+    world
+        .resource_mut::<Actions>()
+        .add(RemoveBundle::<(A, C)>::new(entity));
+    world.run::<Update>();
+
+    assert_eq!(world.archetypes().archetype_id(entity), Some(&original));
+    assert_eq!(world.archetypes().moves(), moves + 1);
+    assert_eq!(world.component::<B>(entity).as_deref(), Some(&B(2)));
+    assert!(!world.has::<A>(entity));
+    assert!(!world.has::<C>(entity));
+
+    world.add_bundle(entity, (A(4), C(5))).unwrap();
+
+    assert_eq!(world.archetypes().archetype_id(entity), Some(&bundled));
+    assert_eq!(world.archetypes().moves(), moves + 2);
+    assert_eq!(world.archetypes().len(), archetypes);
+    assert_eq!(world.component::<A>(entity).as_deref(), Some(&A(4)));
+    assert_eq!(world.component::<B>(entity).as_deref(), Some(&B(2)));
+    assert_eq!(world.component::<C>(entity).as_deref(), Some(&C(5)));
+    assert!(world.check_consistency().is_ok());
+}
+
+#[test]
+fn raw_inserts_match_action_built_entities() {
+    let mut world = world();
+    world.add_resource(Observed::default());
+    world.add_observers(Observers::<CreateEntity>::new().add_system(created));
+    world.add_observers(Observers::<AddComponent<A>>::new().add_system(added_a));
+    world.add_observers(Observers::<AddComponent<C>>::new().add_system(added_c));
+
+    let components = vec![
+        (world.component_id::<A>(), value(A(1))),
+        (world.component_id::<B>(), value(B(2))),
+        (world.component_id::<C>(), value(C(3))),
+    ];
+    let raw = world.insert_entity_raw(None, components).unwrap();
+    world
+        .resource_mut::<Actions>()
+        .add(CreateEntity::new().with(A(1)).with(B(2)).with(C(3)));
+    world.run::<Update>();
+
+    let rows = Query::<(Entity, &A, &B, &C)>::new(&world).collect::<Vec<_>>();
+    assert_eq!(rows.len(), 2);
+    let built = rows.iter().find(|row| row.0 != raw).unwrap().0;
+    for (_, a, b, c) in rows {
+        assert_eq!((&*a, &*b, &*c), (&A(1), &B(2), &C(3)));
+    }
+    assert_eq!(
+        world.archetypes().archetype_id(raw),
+        world.archetypes().archetype_id(built)
+    );
+
+    let observed = world.resource::<Observed>();
+    for entities in [&observed.created, &observed.added_a, &observed.added_c] {
+        let mut entities = entities.clone();
+        entities.sort_by_key(|entity| entity.id());
+        assert_eq!(entities, vec![raw, built]);
+    }
+}
+
+#[test]
+fn raw_inserts_over_a_live_id_fail_without_changes() {
+    let mut world = world();
+    let live = world.spawn().insert(A(1)).id();
+    let moves = world.archetypes().moves();
+    let count = world.entities().len();
+
+    let components = vec![(world.component_id::<B>(), value(B(2)))];
+    assert_eq!(
+        world.insert_entity_raw(Some(live), components),
+        Err(WorldError::EntityAlive(live))
+    );
+
+    assert_eq!(world.entities().len(), count);
+    assert_eq!(world.archetypes().moves(), moves);
+    assert_eq!(world.component::<A>(live).as_deref(), Some(&A(1)));
+    assert_eq!(world.component::<B>(live).as_deref(), None);
+    assert!(world.check_consistency().is_ok());
+
+    world.delete(live);
+    let reused = world.create();
+    world.delete(reused);
+    let components = vec![(world.component_id::<B>(), value(B(2)))];
+    assert_eq!(
+        world.insert_entity_raw(Some(live), components),
+        Err(WorldError::StaleEntity(live))
+    );
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Transform(u32);
+
+impl Component for Transform {}
+
+struct GlobalTransform;
+
+impl Component for GlobalTransform {}
+
+#[test]
+fn component_builders_apply_every_option() {
+    let mut world = world();
+    let id = world
+        .register_component::<Transform>()
+        .storage(StorageKind::Sparse)
+        .cloneable()
+        .drop_immediately()
+        .requires::<GlobalTransform>()
+        .finish()
+        .unwrap();
+    assert_eq!(id, world.component_id::<Transform>());
+
+    let meta = world.component_meta::<Transform>().unwrap();
+    assert_eq!(meta.storage(), StorageKind::Sparse);
+    assert!(meta.is_cloneable());
+    assert!(meta.drops_immediately());
+    assert_eq!(meta.requires(), &[world.component_id::<GlobalTransform>()]);
+    assert_eq!(meta.name(), std::any::type_name::<Transform>());
+
+    let clone = meta.clone_fn().unwrap();
+    let cloned = clone(&value(Transform(7)), 0).unwrap();
+    assert_eq!(cloned.get::<Transform>(0), Some(&Transform(7)));
+}
+
+#[test]
+fn conflicting_re_registration_errors_without_changes() {
+    let mut world = world();
+    world
+        .register_component::<Transform>()
+        .storage(StorageKind::Sparse)
+        .finish()
+        .unwrap();
+    let id = world
+        .register_component::<Transform>()
+        .storage(StorageKind::Sparse)
+        .cloneable()
+        .finish()
+        .unwrap();
+
+    let error = world
+        .register_component::<Transform>()
+        .storage(StorageKind::Table)
+        .drop_immediately()
+        .finish()
+        .unwrap_err();
+    assert_eq!(
+        error,
+        ComponentError::ConflictingStorage {
+            component: std::any::type_name::<Transform>(),
+            current: StorageKind::Sparse,
+            requested: StorageKind::Table,
+        }
+    );
+    assert!(error.to_string().contains("Transform"));
+
+    let meta = world.component_meta::<Transform>().unwrap();
+    assert_eq!(world.component_id::<Transform>(), id);
+    assert_eq!(meta.storage(), StorageKind::Sparse);
+    assert!(meta.is_cloneable());
+    assert!(!meta.drops_immediately());
+}
+
+#[test]
+fn plain_registration_keeps_the_defaults() {
+    let world = world();
+    let meta = world.component_meta::<A>().unwrap();
+    assert_eq!(meta.storage(), StorageKind::Table);
+    assert!(!meta.is_cloneable());
+    assert!(!meta.drops_immediately());
+    assert!(meta.requires().is_empty());
+    assert!(world.component_meta::<Transform>().is_none());
+}
+
+/// Spawns and deletes entities in a fixed pattern, returning every entity
+/// created and the allocator's final state hash.
+fn scripted_ids(mode: AllocMode) -> (Vec<Entity>, u64) {
+    let mut world = world();
+    world.set_alloc_mode(mode);
+    let mut created = Vec::new();
+    let mut alive = Vec::new();
+
+    for round in 0..20usize {
+        for _ in 0..5 {
+            let entity = world.create();
+            created.push(entity);
+            alive.push(entity);
+        }
+        for index in [round % 3, 1, round % 2] {
+            if index < alive.len() {
+                world.delete(alive.remove(index));
+            }
+        }
+    }
+
+    (created, world.entities().state_hash())
+}
+
+#[test]
+fn deterministic_ids_replay_identically() {
+    let (first, hash) = scripted_ids(AllocMode::Deterministic);
+    let (second, replayed) = scripted_ids(AllocMode::Deterministic);
+    assert_eq!(first, second);
+    assert_eq!(hash, replayed);
+    assert_ne!(hash, scripted_ids(AllocMode::Standard).1);
+}
+
+#[test]
+fn deterministic_ids_recycle_in_freeing_order() {
+    let freed = |mode: AllocMode| {
+        let mut world = world();
+        world.set_alloc_mode(mode);
+        let entities = (0..4).map(|_| world.create()).collect::<Vec<_>>();
+        for index in [1, 3, 2] {
+            world.delete(entities[index]);
+        }
+        (0..4).map(|_| world.create()).collect::<Vec<_>>()
+    };
+
+    let deterministic = freed(AllocMode::Deterministic);
+    assert_eq!(
+        deterministic,
+        vec![
+            Entity::new(1, 1),
+            Entity::new(3, 1),
+            Entity::new(2, 1),
+            Entity::new(4, 0),
+        ]
+    );
+
+    let standard = freed(AllocMode::Standard);
+    assert_eq!(
+        standard,
+        vec![
+            Entity::new(2, 1),
+            Entity::new(3, 1),
+            Entity::new(1, 1),
+            Entity::new(4, 0),
+        ]
+    );
+}
+
+/// Counts its drops in a shared counter.
+struct Tracked {
+    index: usize,
+    drops: Arc<AtomicUsize>,
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        self.drops.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Component for Tracked {}
+
+#[test]
+fn compaction_after_mass_deletion_shrinks_and_drops_once() {
+    let mut world = world();
+    world.register::<Tracked>();
+    let drops = Arc::new(AtomicUsize::new(0));
+    let entities = (0..100_000)
+        .map(|index| {
+            let drops = drops.clone();
+            world.spawn().insert(Tracked { index, drops }).id()
+        })
+        .collect::<Vec<_>>();
+
+    for entity in &entities[1_000..] {
+        world.delete(*entity);
+    }
+    assert_eq!(drops.load(Ordering::Relaxed), 99_000);
+
+    // Columns free whole chunks as rows are removed; the rest of the spare
+    // capacity is the row list and row index, which only compaction frees.
+    let column = world.component_id::<Tracked>().id();
+    let table = |world: &World| {
+        let table = world.tables().iter().find(|table| table.len() == 1_000);
+        let table = table.unwrap();
+        (
+            table.column(column).unwrap().capacity(),
+            table.allocated_bytes(),
+        )
+    };
+    let (capacity, bytes) = table(&world);
+    assert!(capacity < 2_000, "{}", capacity);
+
+    let report = world.compact();
+    assert_eq!(report.tables_shrunk, 1);
+    assert!(report.bytes_after < report.bytes_before);
+    let (shrunk, shrunk_bytes) = table(&world);
+    assert_eq!(shrunk, 1_000);
+    assert!(shrunk_bytes * 2 < bytes, "{} -> {}", bytes, shrunk_bytes);
+    assert_eq!(drops.load(Ordering::Relaxed), 99_000);
+
+    for (index, entity) in entities[..1_000].iter().enumerate() {
+        assert_eq!(world.component::<Tracked>(*entity).unwrap().index, index);
+    }
+    assert!(world.check_consistency().is_ok());
+
+    drop(world);
+    assert_eq!(drops.load(Ordering::Relaxed), 100_000);
+}
+
+/// Five bytes of data padded to eight by the `u32`'s alignment.
+#[allow(dead_code)]
+struct Padded {
+    tag: u8,
+    value: u32,
+}
+
+impl Component for Padded {}
+
+#[test]
+fn memory_report_column_bytes_match_padded_sizes() {
+    let mut world = world();
+    world.register::<Padded>();
+    for value in 0..300 {
+        world
+            .spawn()
+            .insert(A(value))
+            .insert(Padded { tag: 1, value })
+            .id();
+    }
+
+    let report = world.memory_report();
+    let table = report
+        .tables
+        .iter()
+        .find(|table| table.rows == 300)
+        .unwrap();
+    let column = |name: &str| {
+        table
+            .columns
+            .iter()
+            .find(|column| column.name.ends_with(name))
+            .unwrap()
+    };
+
+    let padded = column("Padded");
+    assert_eq!(padded.element_size, 8);
+    assert_eq!(padded.len, 300);
+    assert_eq!(padded.live_bytes(), 300 * 8);
+    assert_eq!(padded.bytes, padded.capacity * 8);
+
+    let a = column("::A");
+    assert_eq!(a.element_size, std::mem::size_of::<A>());
+    assert_eq!(a.live_bytes(), 300 * std::mem::size_of::<A>());
+    assert_eq!(a.bytes, a.capacity * std::mem::size_of::<A>());
+    assert_eq!(table.column_bytes(), padded.bytes + a.bytes);
+    assert_eq!(table.bytes(), table.column_bytes() + table.index_bytes);
+
+    assert_eq!(
+        report.total_bytes(),
+        report
+            .tables
+            .iter()
+            .map(|table| table.bytes())
+            .sum::<usize>()
+            + report
+                .resources
+                .iter()
+                .map(|resource| resource.bytes)
+                .sum::<usize>()
+            + report.entity_bytes
+            + report.archetype_bytes
+            + report.action_bytes
+            + report.output_bytes
+            + report.pending_drop_bytes
+    );
+    assert!(report.to_string().contains(padded.name));
+}
+
+#[derive(Default)]
+struct Despawned {
+    entities: Vec<Entity>,
+}
+
+impl Resource for Despawned {}
+
+fn despawned(entities: &[Entity], despawned: &mut Despawned) {
+    despawned.entities.extend_from_slice(entities);
+}
+
+#[test]
+fn closing_a_scope_deletes_nested_members_only() {
+    let mut world = world();
+    world.add_resource(Despawned::default());
+    world.add_observers(Observers::<DeleteEntity>::new().add_system(despawned));
+
+    let spell = world.create_scope("spell");
+    let projectiles = world.create_child_scope(spell, "projectiles");
+    let sparks = world.create_child_scope(projectiles, "sparks");
+    let dialog = world.create_scope("dialog");
+
+    let caster = world.spawn_scoped(spell, (A(0),));
+    let projectile = world.spawn_scoped(projectiles, (A(1), B(1)));
+    let spark = world.spawn_scoped(sparks, (C(2),));
+    let node = world.spawn_scoped(dialog, (A(3),));
+    let unrelated = world.spawn().insert(A(4)).id();
+    world
+        .resource_mut::<Actions>()
+        .add(CreateEntity::new().with(B(5)).in_scope(sparks));
+    world.run::<Update>();
+
+    // Membership follows the entity, not its place in the hierarchy.
+    world.set_parent(projectile, Some(unrelated));
+    let mut members = world.scope_entities(spell, true);
+    assert_eq!(members.len(), 4);
+    assert_eq!(world.scope_entities(spell, false), vec![caster]);
+    let queued = world.scope_entities(sparks, false);
+    assert_eq!(queued.len(), 2);
+    assert!(queued.contains(&spark));
+
+    world.close_scope(spell);
+    world.run::<Update>();
+
+    for scope in [spell, projectiles, sparks] {
+        assert!(!world.scopes().is_open(scope.id()));
+        assert!(world.scope_entities(scope, false).is_empty());
+    }
+    for member in &members {
+        assert!(!world.entities().contains(*member));
+    }
+    let mut deleted = world.resource::<Despawned>().entities.clone();
+    deleted.sort_by_key(|entity| entity.id());
+    members.sort_by_key(|entity| entity.id());
+    assert_eq!(deleted, members);
+
+    assert!(world.scopes().is_open(dialog.id()));
+    assert_eq!(world.scope_entities(dialog, true), vec![node]);
+    assert_eq!(world.component::<A>(unrelated).as_deref(), Some(&A(4)));
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Speed(i32);
+
+impl Component for Speed {}
+
+#[derive(Clone, Debug, PartialEq)]
+struct DebugColor(u32);
+
+impl Component for DebugColor {}
+
+#[test]
+fn fill_writes_every_matching_row_and_no_others() {
+    let mut world = world();
+    world.register::<Speed>();
+    let plain = world.spawn().insert(Speed(1)).id();
+    let with_a = world.spawn().insert(Speed(2)).insert(A(0)).id();
+    let with_ab = world
+        .spawn()
+        .insert(Speed(3))
+        .insert(A(0))
+        .insert(B(0))
+        .id();
+    let with_c = world.spawn().insert(Speed(4)).insert(C(0)).id();
+    let without_speed = world.spawn().insert(A(0)).id();
+
+    let written = world.fill_component(Speed(0), &[], &[world.component_id::<C>()]);
+    assert_eq!(written, 3);
+    for entity in [plain, with_a, with_ab] {
+        assert_eq!(world.component::<Speed>(entity).as_deref(), Some(&Speed(0)));
+    }
+    assert_eq!(world.component::<Speed>(with_c).as_deref(), Some(&Speed(4)));
+    assert_eq!(world.component::<Speed>(without_speed).as_deref(), None);
+
+    let a = world.component_id::<A>();
+    assert_eq!(world.fill_component(Speed(9), &[a], &[]), 2);
+    assert_eq!(world.component::<Speed>(plain).as_deref(), Some(&Speed(0)));
+    assert_eq!(world.component::<Speed>(with_a).as_deref(), Some(&Speed(9)));
+    assert_eq!(
+        world.component::<Speed>(with_ab).as_deref(),
+        Some(&Speed(9))
+    );
+}
+
+#[derive(Default)]
+struct Colored {
+    entities: Vec<Entity>,
+}
+
+impl Resource for Colored {}
+
+fn colored(entities: &[Entity], colored: &mut Colored) {
+    colored.entities.extend_from_slice(entities);
+}
+
+#[test]
+fn bulk_add_moves_each_archetype_once() {
+    let mut world = world();
+    world.add_resource(Colored::default());
+    world.add_observers(Observers::<AddComponent<DebugColor>>::new().add_system(colored));
+    let mut expected = Vec::new();
+    for index in 0..10_000 {
+        let entity = match index % 4 {
+            0 => world.spawn().insert(A(index)).insert(B(index)).id(),
+            1 => world.spawn().insert(A(index)).insert(C(index)).id(),
+            2 => world.spawn().insert(A(index)).insert(D(index)).id(),
+            _ => world.spawn().insert(A(index)).id(),
+        };
+        expected.push(entity);
+    }
+    let skipped = world.spawn().insert(B(0)).id();
+    world.run::<Update>();
+
+    let moves = world.archetypes().moves();
+    let batches = world.archetypes().batch_moves();
+    let a = world.component_id::<A>();
+    let mut added = world.add_component_to_matching(DebugColor(7), &[a], &[]);
+    assert_eq!(added.len(), 10_000);
+    assert_eq!(world.archetypes().batch_moves() - batches, 4);
+    assert_eq!(world.archetypes().moves() - moves, 10_000);
+
+    world.run::<Update>();
+    let mut observed = world.resource::<Colored>().entities.clone();
+    observed.sort_by_key(|entity| entity.id());
+    added.sort_by_key(|entity| entity.id());
+    assert_eq!(observed, added);
+    assert_eq!(added, expected);
+
+    for (index, entity) in expected.iter().enumerate() {
+        assert_eq!(
+            world.component::<A>(*entity).as_deref(),
+            Some(&A(index as u32))
+        );
+        assert_eq!(
+            world.component::<DebugColor>(*entity).as_deref(),
+            Some(&DebugColor(7))
+        );
+    }
+    assert_eq!(world.component::<DebugColor>(skipped).as_deref(), None);
+    assert!(world.check_consistency().is_ok());
+}
+
+#[derive(Default)]
+struct Messages {
+    received: Vec<u32>,
+    observed: Vec<u32>,
+}
+
+impl Resource for Messages {}
+
+/// Sent from a network thread.
+struct Message(u32);
+
+impl Action for Message {
+    type Output = u32;
+
+    fn execute(&mut self, world: &mut World) -> u32 {
+        world.resource_mut::<Messages>().received.push(self.0);
+        self.0
+    }
+}
+
+fn observe_messages(messages: &[u32], received: &mut Messages) {
+    received.observed.extend_from_slice(messages);
+}
+
+#[test]
+fn external_commands_sent_from_another_thread_are_applied() {
+    let mut world = world();
+    world.add_resource(Observed::default());
+    world.add_resource(Messages::default());
+    world.add_observers(Observers::<CreateEntity>::new().add_system(created));
+    world.add_observers(Observers::<Message>::new().add_system(observe_messages));
+    world.configure_external_commands(8, Backpressure::Block);
+
+    let sender = world.external_sender();
+    let network = std::thread::spawn(move || {
+        for index in 0..100 {
+            sender.send(CreateEntity::new().with(A(index))).unwrap();
+            sender.send(Message(index)).unwrap();
+        }
+    });
+    while !network.is_finished() {
+        world.run::<Update>();
+    }
+    network.join().unwrap();
+    world.run::<Update>();
+
+    let mut values = Query::<&A>::new(&world).map(|a| a.0).collect::<Vec<_>>();
+    values.sort();
+    assert_eq!(values, (0..100).collect::<Vec<_>>());
+    assert_eq!(world.resource::<Observed>().created.len(), 100);
+    let messages = world.resource::<Messages>();
+    assert_eq!(messages.received, (0..100).collect::<Vec<_>>());
+    assert_eq!(messages.observed, messages.received);
+    assert_eq!(world.dropped_external_commands(), 0);
+}
+
+#[test]
+fn bounded_drop_policy_counts_excess_commands() {
+    let mut world = world();
+    world.add_resource(Messages::default());
+    world.configure_external_commands(4, Backpressure::Drop);
+
+    let sender = world.external_sender();
+    let results = std::thread::spawn(move || {
+        (0..10)
+            .map(|index| sender.send(Message(index)))
+            .collect::<Vec<_>>()
+    })
+    .join()
+    .unwrap();
+    assert!(results[..4].iter().all(|result| result.is_ok()));
+    assert!(results[4..]
+        .iter()
+        .all(|result| *result == Err(SendError::Dropped)));
+    assert_eq!(world.dropped_external_commands(), 6);
+
+    world.run::<Update>();
+    assert_eq!(world.resource::<Messages>().received, vec![0, 1, 2, 3]);
+}
+
+#[test]
+fn handle_access_matches_typed_access_across_moves() {
+    let mut world = world();
+    let a = world.component_handle::<A>();
+    let b = world.component_handle::<B>();
+    let entities = (0..20)
+        .map(|index| world.spawn().insert(A(index)).id())
+        .collect::<Vec<_>>();
+
+    let agree = |world: &World| {
+        for entity in &entities {
+            assert_eq!(
+                world.get(a, *entity).as_deref(),
+                world.component::<A>(*entity).as_deref()
+            );
+            assert_eq!(
+                world.get(b, *entity).as_deref(),
+                world.component::<B>(*entity).as_deref()
+            );
+            assert_eq!(world.has_handle(b, *entity), world.has::<B>(*entity));
+        }
+    };
+    agree(&world);
+
+    for entity in entities.iter().step_by(2) {
+        world.add_component(*entity, B(entity.id() as u32));
+    }
+    for entity in entities.iter().step_by(3) {
+        world.add_component(*entity, C(0));
+    }
+    agree(&world);
+
+    world.get_mut(a, entities[4]).unwrap().0 = 99;
+    assert_eq!(world.component::<A>(entities[4]).as_deref(), Some(&A(99)));
+    world.remove_component::<B>(entities[4]);
+    agree(&world);
+
+    let state = QueryState::from_handles(&[a.id(), b.id()], &[]);
+    let mut by_handle = Query::<(Entity, &A, &B)>::with_state(&world, Arc::new(state))
+        .map(|(entity, a, b)| (entity, a.0, b.0))
+        .collect::<Vec<_>>();
+    let mut typed = Query::<(Entity, &A, &B)>::new(&world)
+        .map(|(entity, a, b)| (entity, a.0, b.0))
+        .collect::<Vec<_>>();
+    by_handle.sort_by_key(|(entity, ..)| entity.id());
+    typed.sort_by_key(|(entity, ..)| entity.id());
+    assert_eq!(by_handle, typed);
+    assert_eq!(typed.len(), 9);
+}
+
+#[derive(Debug, PartialEq)]
+struct Late(u32);
+
+impl Component for Late {}
+
+#[test]
+fn handle_taken_before_first_use_still_resolves() {
+    let mut world = World::new();
+    let late = world.component_handle::<Late>();
+    let entity = world.spawn().insert(Late(3)).id();
+
+    assert_eq!(late.id(), world.component_id::<Late>());
+    assert_eq!(world.get(late, entity).as_deref(), Some(&Late(3)));
+    assert!(world.has_handle(late, entity));
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "ComponentHandle<world::A> (component 0) is from registry")]
+fn handle_from_another_world_panics() {
+    let mut other = world();
+    let a = other.component_handle::<A>();
+
+    let world = world();
+    let entity = Entity::new(0, 0);
+    world.get(a, entity);
+}
+
+#[derive(Default)]
+struct ShutdownLog {
+    steps: Vec<&'static str>,
+}
+
+impl Resource for ShutdownLog {}
+
+/// Where the log ends up once the world holding it is gone.
+static SHUTDOWN_STEPS: std::sync::Mutex<Vec<&'static str>> = std::sync::Mutex::new(Vec::new());
+
+fn close_log(log: &mut ShutdownLog, _: &mut World) {
+    log.steps.push("log");
+    *SHUTDOWN_STEPS.lock().unwrap() = std::mem::take(&mut log.steps);
+}
+
+struct Socket;
+
+impl Resource for Socket {}
+
+fn close_socket(_: &mut Socket, world: &mut World) {
+    world.resource_mut::<ShutdownLog>().steps.push("socket");
+}
+
+struct GpuBuffers;
+
+impl Resource for GpuBuffers {}
+
+fn release_buffers(_: &mut GpuBuffers, world: &mut World) {
+    world.resource_mut::<ShutdownLog>().steps.push("gpu");
+
+    // Shutting down again from inside a teardown hook is refused.
+    let inner = std::mem::replace(world, World::new());
+    let error = inner.shutdown().unwrap_err();
+    assert!(matches!(error, ShutdownError::ShuttingDown(_)));
+    *world = error.into_world();
+}
+
+fn save_game(log: &mut ShutdownLog) {
+    log.steps.push("save");
+}
+
+fn entity_deleted(entities: &[Entity], log: &mut ShutdownLog) {
+    log.steps.extend(entities.iter().map(|_| "delete"));
+}
+
+#[test]
+fn shutdown_tears_down_entities_then_resources_in_reverse() {
+    let mut world = world();
+    world.add_resource_with_teardown(ShutdownLog::default(), close_log);
+    world.add_resource_with_teardown(Socket, close_socket);
+    world.add_resource_with_teardown(GpuBuffers, release_buffers);
+    world.add_system(Shutdown, "save", save_game);
+    world.add_observers(Observers::<DeleteEntity>::new().add_system(entity_deleted));
+    world.spawn().insert(A(0)).id();
+    world.spawn().insert(B(0)).id();
+
+    let report = world.shutdown().unwrap();
+    assert!(report.ran_shutdown_phase);
+    assert_eq!(report.entities_deleted, 2);
+    assert_eq!(report.entities_leaked, 0);
+    assert_eq!(report.resources_dropped, 3);
+    assert_eq!(
+        *SHUTDOWN_STEPS.lock().unwrap(),
+        vec!["save", "delete", "delete", "gpu", "socket", "log"]
+    );
+}
+
+/// Tries to shut the world down while its actions are being flushed.
+struct ShutdownDuringFlush;
+
+impl Action for ShutdownDuringFlush {
+    type Output = bool;
+
+    fn execute(&mut self, world: &mut World) -> bool {
+        let inner = std::mem::replace(world, World::new());
+        let error = inner.shutdown().unwrap_err();
+        let refused = matches!(error, ShutdownError::Flushing(_));
+        *world = error.into_world();
+        refused
+    }
+}
+
+fn refused(results: &[bool], log: &mut ShutdownLog) {
+    log.steps.extend(
+        results
+            .iter()
+            .map(|refused| if *refused { "refused" } else { "ran" }),
+    );
+}
+
+#[test]
+fn shutdown_during_a_flush_hands_the_world_back() {
+    let mut world = world();
+    world.add_resource(ShutdownLog::default());
+    world.add_observers(Observers::<ShutdownDuringFlush>::new().add_system(refused));
+    let entity = world.spawn().insert(A(1)).id();
+
+    world.resource_mut::<Actions>().add(ShutdownDuringFlush);
+    world.run::<Update>();
+
+    assert_eq!(world.resource::<ShutdownLog>().steps, vec!["refused"]);
+    assert_eq!(world.component::<A>(entity).as_deref(), Some(&A(1)));
+    assert!(!world.is_shutting_down());
+    assert_eq!(world.shutdown().unwrap().entities_deleted, 1);
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Position(i32);
+
+impl Component for Position {}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Momentum(i32);
+
+impl Component for Momentum {}
+
+type EntityState = (Entity, Option<i32>, Option<i32>, Option<Entity>, u64);
+
+fn world_state(world: &World) -> Vec<EntityState> {
+    let mut state = Query::<Entity>::new(world)
+        .map(|entity| {
+            (
+                entity,
+                world.component::<Position>(entity).map(|p| p.0),
+                world.component::<Momentum>(entity).map(|m| m.0),
+                world.entities().parent(entity),
+                world.archetypes().archetype_id(entity).unwrap().id(),
+            )
+        })
+        .collect::<Vec<_>>();
+    state.sort_by_key(|(entity, ..)| entity.id());
+    state
+}
+
+#[test]
+fn restoring_a_fork_undoes_changes_and_copies_only_written_columns() {
+    let mut world = World::new();
+    world
+        .register_component::<Position>()
+        .cloneable()
+        .finish()
+        .unwrap();
+    world
+        .register_component::<Momentum>()
+        .cloneable()
+        .finish()
+        .unwrap();
+    world.register::<A>();
+    let entities = (0..1_000)
+        .map(|index| {
+            world
+                .spawn()
+                .insert(Position(index))
+                .insert(Momentum(-index))
+                .id()
+        })
+        .collect::<Vec<_>>();
+    for pair in entities.chunks(2) {
+        world.set_parent(pair[1], Some(pair[0]));
+    }
+    let before = world_state(&world);
+
+    let fork = world.fork().unwrap();
+    for entity in entities.iter().step_by(100) {
+        world.component_mut::<Position>(*entity).unwrap().0 += 1_000;
+    }
+
+    // Only the written column was copied.
+    let position = world.component_id::<Position>().id();
+    let momentum = world.component_id::<Momentum>().id();
+    let table = world.tables().iter().find(|t| t.len() == 1_000).unwrap();
+    let forked = fork.tables().get(table.id()).unwrap();
+    assert!(!table
+        .column(position)
+        .unwrap()
+        .same_data(forked.column(position).unwrap()));
+    assert!(table
+        .column(momentum)
+        .unwrap()
+        .same_data(forked.column(momentum).unwrap()));
+    assert!(table.column(momentum).unwrap().is_shared());
+    assert_ne!(world_state(&world), before);
+
+    world.delete(entities[1]);
+    world.add_component(entities[2], A(0));
+    world.restore(&fork).unwrap();
+
+    assert_eq!(world_state(&world), before);
+    assert_eq!(world.component::<A>(entities[2]).as_deref(), None);
+    assert!(world.check_consistency().is_ok());
+}
+
+#[derive(Default)]
+struct DeliveryLog {
+    entries: Vec<(&'static str, usize)>,
+}
+
+impl Resource for DeliveryLog {}
+
+fn log_created(entities: &[Entity], log: &mut DeliveryLog) {
+    log.entries
+        .extend(entities.iter().map(|entity| ("created", entity.id())));
+}
+
+fn log_added_b(entities: &[Entity], log: &mut DeliveryLog) {
+    log.entries
+        .extend(entities.iter().map(|entity| ("added_b", entity.id())));
+}
+
+fn log_removed_a(entities: &[Entity], log: &mut DeliveryLog) {
+    log.entries
+        .extend(entities.iter().map(|entity| ("removed_a", entity.id())));
+}
+
+fn log_deleted(entities: &[Entity], log: &mut DeliveryLog) {
+    log.entries
+        .extend(entities.iter().map(|entity| ("deleted", entity.id())));
+}
+
+/// Runs the same scripted frame on a fresh world and hashes everything
+/// whose order is observable.
+fn scripted_frame_hash() -> u64 {
+    use std::hash::{DefaultHasher, Hash, Hasher};
+
+    let mut world = world();
+    world.add_resource(DeliveryLog::default());
+    world.add_observers(Observers::<CreateEntity>::new().add_system(log_created));
+    world.add_observers(Observers::<AddComponent<B>>::new().add_system(log_added_b));
+    world.add_observers(Observers::<RemoveComponent<A>>::new().add_system(log_removed_a));
+    world.add_observers(Observers::<DeleteEntity>::new().add_system(log_deleted));
+
+    let mut entities = Vec::new();
+    for index in 0..40 {
+        let entity = match index % 5 {
+            0 => world.spawn().insert(A(index)).id(),
+            1 => world.spawn().insert(A(index)).insert(C(index)).id(),
+            2 => world.spawn().insert(A(index)).insert(D(index)).id(),
+            3 => world
+                .spawn()
+                .insert(A(index))
+                .insert(C(index))
+                .insert(E(index))
+                .id(),
+            _ => world.spawn().insert(A(index)).insert(E(index)).id(),
+        };
+        entities.push(entity);
+    }
+    world.run::<Update>();
+
+    // Built in a separate buffer so `Actions::append` merges the types.
+    let mut queued = Actions::new();
+    for (index, entity) in entities.iter().enumerate() {
+        match index % 4 {
+            0 => queued.add(AddComponent::new(*entity, B(0))),
+            1 => queued.add(RemoveComponent::<A>::new(*entity)),
+            2 => queued.add(DeleteEntity::new(*entity)),
+            _ => queued.add(CreateEntity::new().with(C(0))),
+        }
+    }
+    world.resource_mut::<Actions>().append(queued);
+    world.run::<Update>();
+
+    let mut hasher = DefaultHasher::new();
+    world.resource::<DeliveryLog>().entries.hash(&mut hasher);
+    Query::<(Entity, &A)>::new(&world)
+        .map(|(entity, a)| (entity.id(), a.0))
+        .collect::<Vec<_>>()
+        .hash(&mut hasher);
+    Query::<(Entity, &C)>::new(&world)
+        .map(|(entity, _)| entity.id())
+        .collect::<Vec<_>>()
+        .hash(&mut hasher);
+    hasher.finish()
+}
+
+#[test]
+fn identical_frames_deliver_and_iterate_in_the_same_order() {
+    let expected = scripted_frame_hash();
+    for _ in 0..50 {
+        assert_eq!(scripted_frame_hash(), expected);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Hitpoints(u32);
+
+impl Component for Hitpoints {}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Sprite(&'static str);
+
+impl Component for Sprite {}
+
+#[derive(Clone, Debug, PartialEq)]
+struct WeaponSlot(u32);
+
+impl Component for WeaponSlot {}
+
+#[derive(Default)]
+struct Spawned {
+    roots: Vec<Entity>,
+    parented: Vec<Vec<Entity>>,
+}
+
+impl Resource for Spawned {}
+
+fn prefab_spawned(roots: &[Entity], spawned: &mut Spawned) {
+    spawned.roots.extend_from_slice(roots);
+}
+
+fn children_added(children: &[Vec<Entity>], spawned: &mut Spawned) {
+    spawned.parented.extend_from_slice(children);
+}
+
+#[test]
+fn prefab_instances_are_independent_and_overridden() {
+    let mut world = World::new();
+    world.add_resource(Spawned::default());
+    world.add_observers(Observers::<SpawnPrefab>::new().add_system(prefab_spawned));
+    world.add_observers(Observers::<AddChildren>::new().add_system(children_added));
+    world.register_prefab(
+        "goblin",
+        PrefabBuilder::new()
+            .with(Hitpoints(30))
+            .with(Sprite("goblin"))
+            .child(PrefabBuilder::new().with(WeaponSlot(1))),
+    );
+
+    let mut actions = world.resource_mut::<Actions>();
+    actions.add(SpawnPrefab::new("goblin").at(Transform(5)));
+    actions.add(
+        SpawnPrefab::new("goblin")
+            .at(Transform(9))
+            .override_component(Hitpoints(50)),
+    );
+    drop(actions);
+    world.run::<Update>();
+
+    let spawned = world.resource::<Spawned>();
+    let roots = spawned.roots.clone();
+    assert_eq!(roots.len(), 2);
+    assert_ne!(roots[0], roots[1]);
+    assert_eq!(spawned.parented.len(), 2);
+    drop(spawned);
+
+    let expected = [(Hitpoints(30), Transform(5)), (Hitpoints(50), Transform(9))];
+    let mut weapons = Vec::new();
+    for (root, (hitpoints, transform)) in roots.iter().zip(expected) {
+        assert_eq!(
+            world.component::<Hitpoints>(*root).as_deref(),
+            Some(&hitpoints)
+        );
+        assert_eq!(
+            world.component::<Transform>(*root).as_deref(),
+            Some(&transform)
+        );
+        assert_eq!(
+            world.component::<Sprite>(*root).as_deref(),
+            Some(&Sprite("goblin"))
+        );
+        assert_eq!(world.entities().parent(*root), None);
+
+        let children = world.entities().children(*root, false);
+        assert_eq!(children.len(), 1);
+        assert_eq!(world.entities().parent(children[0]), Some(*root));
+        assert_eq!(
+            world.component::<WeaponSlot>(children[0]).as_deref(),
+            Some(&WeaponSlot(1))
+        );
+        assert_eq!(world.component::<Hitpoints>(children[0]).as_deref(), None);
+        weapons.push(children[0]);
+    }
+    assert_ne!(weapons[0], weapons[1]);
+
+    world.component_mut::<Hitpoints>(roots[0]).unwrap().0 = 1;
+    world.component_mut::<WeaponSlot>(weapons[0]).unwrap().0 = 7;
+    assert_eq!(
+        world.component::<Hitpoints>(roots[1]).as_deref(),
+        Some(&Hitpoints(50))
+    );
+    assert_eq!(
+        world.component::<WeaponSlot>(weapons[1]).as_deref(),
+        Some(&WeaponSlot(1))
+    );
+
+    world
+        .resource_mut::<Actions>()
+        .add(SpawnPrefab::new("goblin"));
+    world.run::<Update>();
+    let third = *world.resource::<Spawned>().roots.last().unwrap();
+    assert_eq!(
+        world.component::<Hitpoints>(third).as_deref(),
+        Some(&Hitpoints(30))
+    );
+    let weapon = world.entities().children(third, false)[0];
+    assert_eq!(
+        world.component::<WeaponSlot>(weapon).as_deref(),
+        Some(&WeaponSlot(1))
+    );
+}
+
+#[test]
+fn unknown_prefabs_are_skipped_and_traced() {
+    let mut world = World::new();
+    world
+        .resource_mut::<Actions>()
+        .add(SpawnPrefab::new("gobiln"));
+    world.run::<Update>();
+
+    assert_eq!(world.entities().len(), 0);
+    assert!(world.traces().iter().any(|entry| {
+        entry.source() == "SpawnPrefab" && entry.message() == "unknown prefab gobiln"
+    }));
+}
+
+#[derive(Debug, PartialEq)]
+struct Bit<const N: usize>(u32);
+
+impl<const N: usize> Component for Bit<N> {}
+
+fn add_bits(world: &mut World, entity: Entity, mask: u32) {
+    for bit in 0..8 {
+        if mask & (1 << bit) == 0 {
+            continue;
+        }
+
+        match bit {
+            0 => world.add_component(entity, Bit::<0>(mask)),
+            1 => world.add_component(entity, Bit::<1>(mask)),
+            2 => world.add_component(entity, Bit::<2>(mask)),
+            3 => world.add_component(entity, Bit::<3>(mask)),
+            4 => world.add_component(entity, Bit::<4>(mask)),
+            5 => world.add_component(entity, Bit::<5>(mask)),
+            6 => world.add_component(entity, Bit::<6>(mask)),
+            _ => world.add_component(entity, Bit::<7>(mask)),
+        };
+    }
+}
+
+fn bits(world: &World, entity: Entity) -> [Option<u32>; 9] {
+    [
+        world.component::<A>(entity).map(|a| a.0),
+        world.component::<Bit<0>>(entity).map(|b| b.0),
+        world.component::<Bit<1>>(entity).map(|b| b.0),
+        world.component::<Bit<2>>(entity).map(|b| b.0),
+        world.component::<Bit<3>>(entity).map(|b| b.0),
+        world.component::<Bit<4>>(entity).map(|b| b.0),
+        world.component::<Bit<5>>(entity).map(|b| b.0),
+        world.component::<Bit<6>>(entity).map(|b| b.0),
+        world.component::<Bit<7>>(entity).map(|b| b.0),
+    ]
+}
+
+fn bit_queries(world: &World) -> Vec<Vec<usize>> {
+    let ids = |entities: Vec<Entity>| {
+        let mut ids = entities.iter().map(|e| e.id()).collect::<Vec<_>>();
+        ids.sort();
+        ids
+    };
+    vec![
+        ids(Query::<Entity, With<A>>::new(world).collect()),
+        ids(Query::<Entity, With<Bit<0>>>::new(world).collect()),
+        ids(Query::<Entity, With<Bit<3>>>::new(world).collect()),
+        ids(Query::<Entity, With<Bit<7>>>::new(world).collect()),
+    ]
+}
+
+#[test]
+fn budgeted_defragment_shrinks_storage_and_keeps_values() {
+    let mut world = world();
+    world.register::<Bit<0>>();
+    world.register::<Bit<1>>();
+    world.register::<Bit<2>>();
+    world.register::<Bit<3>>();
+    world.register::<Bit<4>>();
+    world.register::<Bit<5>>();
+    world.register::<Bit<6>>();
+    world.register::<Bit<7>>();
+    let mut entities = Vec::new();
+    for mask in 1..=200 {
+        let spawned = (0..3)
+            .map(|_| world.spawn().insert(A(mask)).id())
+            .collect::<Vec<_>>();
+        for entity in &spawned {
+            add_bits(&mut world, *entity, mask);
+        }
+
+        // Leave two, one or no entities behind.
+        let keep = (mask % 3) as usize;
+        for entity in &spawned[keep..] {
+            world.delete(*entity);
+        }
+        entities.extend_from_slice(&spawned[..keep]);
+    }
+    let hot = (0..4_000)
+        .map(|index| world.spawn().insert(A(index)).insert(B(index)).id())
+        .collect::<Vec<_>>();
+    for entity in &hot[1_000..] {
+        world.delete(*entity);
+    }
+    entities.extend_from_slice(&hot[..1_000]);
+
+    let values = entities
+        .iter()
+        .map(|entity| bits(&world, *entity))
+        .collect::<Vec<_>>();
+    let queries = bit_queries(&world);
+    let before = world.memory_report();
+    assert!(before.archetypes.len() > 200);
+
+    let mut calls = 0;
+    let report = loop {
+        calls += 1;
+        let report = world.defragment(Duration::ZERO);
+        if report.complete {
+            break report;
+        }
+        assert!(world.resource::<DefragmentProgress>().is_running());
+    };
+    assert_eq!(calls, before.archetypes.len());
+    assert!(!world.resource::<DefragmentProgress>().is_running());
+
+    let after = world.memory_report();
+    assert!(report.archetypes_removed > 0);
+    assert!(report.tables_rebuilt > 0);
+    assert!(report.tables_shrunk > 0);
+    assert!(report.bytes_after < report.bytes_before);
+    assert_eq!(
+        after.tables.len(),
+        before.tables.len() - report.archetypes_removed
+    );
+    assert!(after.table_bytes() < before.table_bytes());
+
+    for (entity, values) in entities.iter().zip(&values) {
+        assert_eq!(bits(&world, *entity), *values);
+    }
+    assert_eq!(bit_queries(&world), queries);
+    assert!(world.check_consistency().is_ok());
+}
+
+#[test]
+fn provenance_records_source_and_frame() {
+    let mut world = world();
+    world.enable_provenance(true);
+    world.register_prefab("crate", PrefabBuilder::new().with(Transform(1)));
+    world.run::<Update>();
+    world.run::<Update>();
+
+    let frame = world.frame();
+    let direct = world.create();
+    let mut actions = world.resource_mut::<Actions>();
+    actions.add(CreateEntity::new().with(A(1)));
+    actions.add(SpawnPrefab::new("crate"));
+    drop(actions);
+    world.run::<Update>();
+    let scope = world.create_scope("wave");
+    let scoped = world.spawn_scoped(scope, (B(0),));
+
+    let source = |entity: Entity| world.provenance(entity).map(|p| p.source.clone());
+    let action = Query::<Entity, With<A>>::new(&world).next().unwrap();
+    let prefab = Query::<Entity, With<Transform>>::new(&world)
+        .next()
+        .unwrap();
+    assert_eq!(source(direct), Some(ProvenanceSource::Direct));
+    assert_eq!(
+        source(action),
+        Some(ProvenanceSource::Action(
+            std::any::type_name::<CreateEntity>()
+        ))
+    );
+    assert_eq!(
+        source(prefab),
+        Some(ProvenanceSource::Prefab("crate".to_string()))
+    );
+    assert_eq!(source(scoped), Some(ProvenanceSource::Scope("wave")));
+    for entity in [direct, action, prefab] {
+        assert_eq!(world.provenance(entity).unwrap().frame, frame);
+    }
+    assert_eq!(world.provenance(scoped).unwrap().frame, frame + 1);
+
+    let inspect = world.inspect(direct).unwrap();
+    assert!(
+        inspect.contains(&format!("spawned in frame {} by direct call", frame)),
+        "{inspect}"
+    );
+
+    world.delete(direct);
+    assert!(world.provenance(direct).is_none());
+    let recycled = world.create();
+    assert_eq!(recycled.id(), direct.id());
+    let provenance = world.provenance(recycled).unwrap();
+    assert_eq!(provenance.source, ProvenanceSource::Direct);
+    assert_eq!(provenance.frame, frame + 1);
+}
+
+#[test]
+fn provenance_is_not_recorded_while_disabled() {
+    let mut world = world();
+    let entity = world.create();
+    assert!(world.provenance(entity).is_none());
+    assert!(!world.inspect(entity).unwrap().contains("spawned"));
+
+    world.enable_provenance(true);
+    let tracked = world.create();
+    assert!(world.provenance(tracked).is_some());
+    world.enable_provenance(false);
+    assert!(world.provenance(tracked).is_none());
+}
+
+fn recordable_world() -> World {
+    let mut world = world();
+    world
+        .register_component::<Position>()
+        .cloneable()
+        .finish()
+        .unwrap();
+    world
+        .register_component::<Momentum>()
+        .cloneable()
+        .finish()
+        .unwrap();
+    world.add_resource(Messages::default());
+    world
+}
+
+/// Component values, parent and archetype of `entities`, in order.
+fn replay_state(world: &World, entities: &[Entity]) -> Vec<EntityState> {
+    entities
+        .iter()
+        .map(|entity| {
+            (
+                *entity,
+                world.component::<Position>(*entity).map(|p| p.0),
+                world.component::<Momentum>(*entity).map(|m| m.0),
+                world.entities().parent(*entity),
+                world.archetypes().archetype_id(*entity).unwrap().id(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn replaying_a_recording_rebuilds_an_equivalent_world() {
+    let mut world = recordable_world();
+    world.start_recording();
+
+    let mut actions = world.resource_mut::<Actions>();
+    for index in 0..5 {
+        actions.add(
+            CreateEntity::new()
+                .with(Position(index))
+                .with(Momentum(-index)),
+        );
+    }
+    actions.add(Message(1));
+    drop(actions);
+    world.run::<Update>();
+    let mut entities = Query::<(Entity, &Position)>::new(&world)
+        .map(|(entity, position)| (position.0, entity))
+        .collect::<Vec<_>>();
+    entities.sort_by_key(|(position, _)| *position);
+    let entities = entities.into_iter().map(|(_, e)| e).collect::<Vec<_>>();
+
+    let mut actions = world.resource_mut::<Actions>();
+    actions.add(SetComponent::new(entities[1], Position(10)));
+    actions.add(RemoveComponent::<Momentum>::new(entities[2]));
+    actions.add(AddChildren::new(
+        entities[0],
+        vec![entities[3], entities[4]],
+    ));
+    drop(actions);
+    world.run::<Update>();
+
+    let mut actions = world.resource_mut::<Actions>();
+    actions.add(SetParent::new(entities[4], Some(entities[1])));
+    actions.add(AddComponent::new(entities[2], Momentum(77)));
+    actions.add(DeleteEntity::new(entities[3]));
+    drop(actions);
+    world.run::<Update>();
+
+    let recording = world.stop_recording();
+    assert_eq!(recording.gaps(), vec![std::any::type_name::<Message>()]);
+
+    // Shift the fresh world's ids so the mapping has work to do.
+    let mut replayed = recordable_world();
+    for _ in 0..3 {
+        let entity = replayed.create();
+        replayed.delete(entity);
+    }
+    let mapper = recording.replay(&mut replayed);
+
+    let live = entities
+        .iter()
+        .copied()
+        .filter(|entity| world.entities().contains(*entity))
+        .collect::<Vec<_>>();
+    assert_eq!(live.len(), 4);
+    let mapped = live
+        .iter()
+        .map(|entity| mapper.map(*entity))
+        .collect::<Vec<_>>();
+    assert!(live.iter().zip(&mapped).any(|(from, to)| from != to));
+
+    let original = replay_state(&world, &live);
+    let unmapped = replay_state(&replayed, &mapped)
+        .into_iter()
+        .zip(&live)
+        .map(|((_, position, momentum, parent, archetype), entity)| {
+            let parent = parent.map(|parent| {
+                let (from, _) = mapper.iter().find(|(_, to)| *to == parent).unwrap();
+                from
+            });
+            (*entity, position, momentum, parent, archetype)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(unmapped, original);
+    assert_eq!(replayed.entities().len(), world.entities().len());
+    assert!(!replayed.entities().contains(mapper.map(entities[3])));
+    assert!(replayed.check_consistency().is_ok());
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct GridCell(i32, i32);
+impl Component for GridCell {}
+
+const CELLS: [GridCell; 3] = [GridCell(0, 0), GridCell(0, 1), GridCell(1, 1)];
+
+fn assert_index_exact(world: &World) {
+    for cell in CELLS {
+        let mut indexed = world.find_by(&cell).to_vec();
+        let mut scanned = Query::<(Entity, &GridCell)>::new(world)
+            .filter(|(_, c)| **c == cell)
+            .map(|(entity, _)| entity)
+            .collect::<Vec<_>>();
+        indexed.sort_by_key(|e| e.id());
+        scanned.sort_by_key(|e| e.id());
+        assert_eq!(indexed, scanned, "{cell:?}");
+    }
+}
+
+struct Lookups;
+
+impl ScheduleLabel for Lookups {
+    const LABEL: &'static str = "lookups";
+}
+
+#[derive(Default)]
+struct Neighbours(Vec<usize>);
+impl Resource for Neighbours {}
+
+fn count_origin(cells: Indexed<GridCell>, neighbours: &mut Neighbours) {
+    neighbours.0.push(cells.find(&GridCell(0, 0)).len());
+}
+
+#[test]
+fn grid_cell_index_stays_exact_across_mutations() {
+    let mut world = World::new();
+    let early = world.spawn().insert(GridCell(0, 0)).id();
+    world.register_index::<GridCell>();
+    assert_eq!(world.find_by(&GridCell(0, 0)), &[early]);
+
+    let a = world.spawn().insert(GridCell(0, 0)).id();
+    let b = world.spawn().insert(GridCell(0, 1)).id();
+    let c = world.spawn().id();
+    assert_index_exact(&world);
+
+    world
+        .resource_mut::<Actions>()
+        .add(SetComponent::new(a, GridCell(1, 1)));
+    world.run::<Update>();
+    assert_index_exact(&world);
+    assert_eq!(world.find_by(&GridCell(1, 1)), &[a]);
+
+    // Same-frame add and set: whichever value ends up stored is the one indexed.
+    let mut actions = world.resource_mut::<Actions>();
+    actions.add(AddComponent::new(c, GridCell(0, 1)));
+    actions.add(SetComponent::new(c, GridCell(1, 1)));
+    drop(actions);
+    world.run::<Update>();
+    assert_index_exact(&world);
+    let stored = *world.component::<GridCell>(c).unwrap();
+    assert!(world.find_by(&stored).contains(&c));
+    assert!(world.update_indexed(c, GridCell(1, 1)));
+    assert_index_exact(&world);
+
+    assert!(world.update_indexed(b, GridCell(0, 0)));
+    assert_index_exact(&world);
+    assert!(world.find_by(&GridCell(0, 1)).is_empty());
+
+    world
+        .resource_mut::<Actions>()
+        .add(RemoveComponent::<GridCell>::new(early));
+    world.run::<Update>();
+    assert_index_exact(&world);
+
+    world.resource_mut::<Actions>().add(DeleteEntity::new(a));
+    world.run::<Update>();
+    assert_index_exact(&world);
+    assert_eq!(world.find_by(&GridCell(1, 1)), &[c]);
+    assert_eq!(world.find_by(&GridCell(0, 0)), &[b]);
+
+    world.add_resource(Neighbours::default());
+    world.add_system(Update, Lookups, count_origin);
+    world.run::<Update>();
+    assert_eq!(world.resource::<Neighbours>().0, vec![1]);
+}
+
+#[derive(Debug, PartialEq)]
+struct Gauge {
+    level: f32,
+    tier: i64,
+}
+impl Component for Gauge {}
+ecs::impl_reflect!(Gauge {
+    level: f32,
+    tier: i64
+});
+
+#[derive(Default)]
+struct ChangedGauges(Vec<Vec<Entity>>);
+impl Resource for ChangedGauges {}
+
+fn changed_gauges(gauges: Query<Entity, Changed<Gauge>>, changed: &mut ChangedGauges) {
+    changed.0.push(gauges.collect());
+}
+
+#[test]
+fn reflected_field_edits_change_the_typed_value() {
+    let mut world = World::new();
+    world.register_reflect::<Gauge>();
+    world.add_resource(ChangedGauges::default());
+    world.add_system(Update, Lookups, changed_gauges);
+    let gauge = world
+        .spawn()
+        .insert(Gauge {
+            level: 0.5,
+            tier: 1,
+        })
+        .id();
+    let id = world.component_id::<Gauge>();
+
+    let view = world.reflect_component(gauge, id).unwrap();
+    let fields = view
+        .fields()
+        .into_iter()
+        .map(|(name, field)| match field {
+            FieldRef::Float(value) => (name, value),
+            FieldRef::Int(value) => (name, value as f64),
+            _ => panic!("unexpected field {name}"),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(fields, vec![("level", 0.5), ("tier", 1.0)]);
+    drop(view);
+
+    world.run::<Update>();
+    world.run::<Update>();
+    world
+        .set_field(gauge, id, "tier", FieldValue::Int(3))
+        .unwrap();
+    world.run::<Update>();
+
+    assert_eq!(
+        world.component::<Gauge>(gauge).as_deref(),
+        Some(&Gauge {
+            level: 0.5,
+            tier: 3
+        })
+    );
+    assert_eq!(
+        world.resource::<ChangedGauges>().0,
+        vec![vec![gauge], vec![], vec![gauge]]
+    );
+
+    assert!(matches!(
+        world.set_field(gauge, id, "speed", FieldValue::Int(1)),
+        Err(ReflectError::UnknownField { .. })
+    ));
+    assert!(matches!(
+        world.set_field(gauge, id, "tier", FieldValue::Bool(true)),
+        Err(ReflectError::InvalidValue { .. })
+    ));
+}
+
+fn assert_mirrored(sim: &World, render: &World, extractor: &WorldExtractor) {
+    let mut mirrored = 0;
+    for entity in sim.entities().iter() {
+        let position = sim.component::<Position>(entity);
+        let momentum = sim.component::<Momentum>(entity);
+        if position.is_none() && momentum.is_none() {
+            assert_eq!(extractor.target(entity), None);
+            continue;
+        }
+
+        let target = extractor.target(entity).unwrap();
+        assert_eq!(
+            render.component::<Position>(target).as_deref(),
+            position.as_deref()
+        );
+        assert_eq!(
+            render.component::<Momentum>(target).as_deref(),
+            momentum.as_deref()
+        );
+        mirrored += 1;
+    }
+
+    assert_eq!(extractor.len(), mirrored);
+    assert_eq!(render.entities().iter().count(), mirrored);
+}
+
+#[test]
+fn extraction_mirrors_the_sim_world_each_frame() {
+    let mut sim = World::new();
+    let mut render = World::new();
+    let mut extractor = WorldExtractor::new()
+        .mirror::<Position>()
+        .mirror::<Momentum>();
+
+    let moving = sim.spawn().insert(Position(0)).insert(Momentum(2)).id();
+    let resting = sim.spawn().insert(Position(5)).id();
+    let doomed = sim.spawn().insert(Momentum(1)).id();
+    sim.spawn();
+
+    extractor.extract(&sim, &mut render);
+    assert_mirrored(&sim, &render, &extractor);
+    assert_eq!(extractor.copies(), 4);
+    let moving_target = extractor.target(moving).unwrap();
+    let resting_target = extractor.target(resting).unwrap();
+    let doomed_target = extractor.target(doomed).unwrap();
+
+    let mut actions = sim.resource_mut::<Actions>();
+    actions.add(SetComponent::new(moving, Position(2)));
+    actions.add(DeleteEntity::new(doomed));
+    actions.add(CreateEntity::new().with(Position(9)));
+    drop(actions);
+    sim.run::<Update>();
+
+    extractor.extract(&sim, &mut render);
+    assert_mirrored(&sim, &render, &extractor);
+    assert_eq!(extractor.target(moving), Some(moving_target));
+    assert_eq!(extractor.target(resting), Some(resting_target));
+    assert_eq!(extractor.target(doomed), None);
+    assert!(!render.entities().contains(doomed_target));
+    // The moved position and the spawned one; unchanged values are skipped.
+    assert_eq!(extractor.copies(), 2);
+
+    extractor.extract(&sim, &mut render);
+    assert_mirrored(&sim, &render, &extractor);
+    assert_eq!(extractor.copies(), 0);
+}
+
+/// Like `Tracked`, but registered to drop as soon as it is cleared.
+struct Urgent(Arc<AtomicUsize>);
+
+impl Drop for Urgent {
+    fn drop(&mut self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Component for Urgent {}
+
+#[test]
+fn unloading_a_large_scene_spreads_drops_over_reclaim_calls() {
+    let mut world = world();
+    world.register::<Tracked>();
+    world
+        .register_component::<Urgent>()
+        .drop_immediately()
+        .finish()
+        .unwrap();
+    let drops = Arc::new(AtomicUsize::new(0));
+    let urgent_drops = Arc::new(AtomicUsize::new(0));
+    for index in 0..100_000 {
+        let drops = drops.clone();
+        let mut spawn = world.spawn();
+        spawn.insert(Tracked { index, drops });
+        if index % 100 == 0 {
+            spawn.insert(Urgent(urgent_drops.clone()));
+        }
+        spawn.finish();
+    }
+
+    assert_eq!(world.clear_entities(), 100_000);
+    assert_eq!(Query::<&Tracked>::new(&world).count(), 0);
+    assert_eq!(Query::<Entity>::new(&world).count(), 0);
+    assert_eq!(urgent_drops.load(Ordering::Relaxed), 1_000);
+    assert_eq!(drops.load(Ordering::Relaxed), 0);
+    assert_eq!(world.pending_drops(), 100_000);
+    assert_eq!(world.memory_report().pending_drops, 100_000);
+    assert!(world.memory_report().pending_drop_bytes > 0);
+
+    // A zero budget still drops one step's worth of values.
+    assert!(!world.reclaim(Duration::ZERO));
+    assert_eq!(drops.load(Ordering::Relaxed), 64);
+
+    while !world.reclaim(Duration::from_micros(500)) {
+        assert_eq!(
+            drops.load(Ordering::Relaxed) + world.pending_drops(),
+            100_000
+        );
+    }
+
+    assert_eq!(drops.load(Ordering::Relaxed), 100_000);
+    assert_eq!(world.pending_drops(), 0);
+    assert_eq!(world.memory_report().pending_drop_bytes, 0);
+    assert_eq!(urgent_drops.load(Ordering::Relaxed), 1_000);
+}
+
+#[derive(Default)]
+struct Cleared(Vec<usize>);
+
+impl Resource for Cleared {}
+
+fn cleared(counts: &[usize], cleared: &mut Cleared) {
+    cleared.0.extend_from_slice(counts);
+}
+
+#[test]
+fn cleared_entities_come_back_without_their_components() {
+    let mut world = world();
+    world.add_resource(Cleared::default());
+    world.add_observers(Observers::<ClearEntities>::new().add_system(cleared));
+    let old = (0..50u32)
+        .map(|index| world.spawn().insert(A(index)).insert(B(index)).id())
+        .collect::<Vec<_>>();
+    world.add_child(old[0], old[1]);
+
+    world.resource_mut::<Actions>().add(ClearEntities);
+    world.run::<Update>();
+    assert_eq!(world.resource::<Cleared>().0, vec![50]);
+    assert_eq!(world.entities().len(), 0);
+
+    let new = (0..50).map(|_| world.create()).collect::<Vec<_>>();
+    let mut reused = new.iter().map(|entity| entity.id()).collect::<Vec<_>>();
+    reused.sort_unstable();
+    let mut ids = old.iter().map(|entity| entity.id()).collect::<Vec<_>>();
+    ids.sort_unstable();
+    assert_eq!(reused, ids);
+
+    for entity in &new {
+        assert!(!old.contains(entity));
+        assert_eq!(world.component::<A>(*entity).as_deref(), None);
+        assert_eq!(world.component::<B>(*entity).as_deref(), None);
+        assert!(world.entities().children_of(*entity).is_empty());
+        assert_eq!(world.entities().parent(*entity), None);
+    }
+    assert_eq!(Query::<&A>::new(&world).count(), 0);
+    assert_eq!(Query::<&B>::new(&world).count(), 0);
+
+    world.add_component(new[0], A(100));
+    assert_eq!(world.component::<A>(new[0]).as_deref(), Some(&A(100)));
+    assert_eq!(Query::<&A>::new(&world).count(), 1);
+    assert!(world.check_consistency().is_ok());
+}
+
+struct Wheel;
+impl Component for Wheel {}
+
+struct Engine;
+impl Component for Engine {}
+
+/// Registered but never given to an entity.
+struct Fossil;
+impl Component for Fossil {}
+
+/// Never queued.
+struct Honk;
+
+impl Action for Honk {
+    type Output = ();
+
+    fn execute(&mut self, _: &mut World) {}
+}
+
+fn roll(wheels: Query<&Wheel>) {
+    assert_eq!(wheels.count(), 1);
+}
+
+/// No entity has both a wheel and an engine.
+fn drive(cars: Query<(&Wheel, &Engine)>) {
+    assert_eq!(cars.count(), 0);
+}
+
+fn spawned(_: &[Entity]) {}
+
+fn honked(_: &[()]) {}
+
+fn audited_world() -> World {
+    let mut world = World::new();
+    world.spawn().insert(Wheel);
+    world.spawn().insert(Engine);
+    world.add_system(Update, Lookups, roll);
+    world.add_observers(Observers::<CreateEntity>::new().add_system(spawned));
+    world.resource_mut::<Actions>().add(CreateEntity::new());
+    world
+}
+
+#[test]
+fn audit_names_exactly_what_is_unused() {
+    let mut world = audited_world();
+    world.register::<Fossil>();
+    world.add_system(Update, Lookups, drive);
+    world.add_observers(Observers::<Honk>::new().add_system(honked));
+    for _ in 0..3 {
+        world.run::<Update>();
+    }
+
+    let report = world.audit();
+    assert_eq!(
+        report.unused_components,
+        vec![std::any::type_name::<Fossil>()]
+    );
+    assert_eq!(report.unmatched_systems.len(), 1);
+    assert!(report.unmatched_systems[0].ends_with("drive"));
+    assert_eq!(report.silent_observers, vec![std::any::type_name::<Honk>()]);
+
+    let text = report.to_string();
+    assert!(text.contains("Fossil"));
+    assert!(text.contains("drive"));
+    assert!(text.contains("Honk"));
+}
+
+#[test]
+fn audit_of_a_fully_used_world_is_empty() {
+    let mut world = audited_world();
+    for _ in 0..3 {
+        world.run::<Update>();
+    }
+
+    let report = world.audit();
+    assert!(report.is_empty(), "{report}");
+    assert_eq!(report.to_string(), "nothing unused");
+}
+
+fn add_letter(world: &mut World, entity: Entity, letter: u32) {
+    match letter {
+        0 => world.add_component(entity, A(letter)),
+        1 => world.add_component(entity, B(letter)),
+        2 => world.add_component(entity, C(letter)),
+        3 => world.add_component(entity, D(letter)),
+        _ => world.add_component(entity, E(letter)),
+    };
+}
+
+fn remove_letter(world: &mut World, entity: Entity, letter: u32) {
+    match letter {
+        0 => world.remove_component::<A>(entity),
+        1 => world.remove_component::<B>(entity),
+        2 => world.remove_component::<C>(entity),
+        3 => world.remove_component::<D>(entity),
+        _ => world.remove_component::<E>(entity),
+    }
+}
+
+/// Checks every mask test against the entity's archetype.
+fn assert_masks_agree(world: &World, entity: Entity, masks: &[ComponentMask]) {
+    let archetypes = world.archetypes();
+    let Some(archetype) = archetypes.entity_archetype(entity) else {
+        assert!(archetypes.mask(entity).is_none());
+        assert!(masks.iter().all(|mask| !world.has_any(entity, mask)));
+        return;
+    };
+
+    let components = archetype.components();
+    assert_eq!(
+        archetypes.mask(entity),
+        Some(&ComponentMask::from_ids(components))
+    );
+    for index in 0..world.components().len() {
+        let id = ComponentId::new(index);
+        assert_eq!(archetypes.has(entity, id), components.contains(&id));
+    }
+    for mask in masks {
+        let ids = mask.iter().collect::<Vec<_>>();
+        let all = ids.iter().all(|id| components.contains(id));
+        let any = ids.iter().any(|id| components.contains(id));
+        assert_eq!(world.has_all(entity, mask), all);
+        assert_eq!(world.has_any(entity, mask), any);
+    }
+}
+
+#[test]
+fn component_masks_agree_with_archetypes_across_random_lifecycles() {
+    let mut world = world();
+    let masks = vec![
+        ComponentMask::from_types::<(A,)>(world.components()),
+        ComponentMask::from_types::<(A, C)>(world.components()),
+        ComponentMask::from_types::<(B, D, E)>(world.components()),
+    ];
+
+    let mut seed = 0x2545_f491_4f6c_dd1du64;
+    let mut next = |bound: u64| {
+        seed = seed
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        (seed >> 33) % bound
+    };
+
+    let mut live = Vec::new();
+    let mut dead = Vec::new();
+    for _ in 0..5_000 {
+        match (next(6), live.len()) {
+            (0, _) | (_, 0) => live.push(world.spawn().id()),
+            (1, len) => {
+                let entity = live.swap_remove(next(len as u64) as usize);
+                world.delete(entity);
+                dead.push(entity);
+            }
+            (2 | 3, len) => {
+                let entity = live[next(len as u64) as usize];
+                add_letter(&mut world, entity, next(5) as u32);
+            }
+            (_, len) => {
+                let entity = live[next(len as u64) as usize];
+                remove_letter(&mut world, entity, next(5) as u32);
+            }
+        }
+
+        for entity in live.iter().chain(&dead) {
+            assert_masks_agree(&world, *entity, &masks);
+        }
+        dead.retain(|entity| !world.entities().contains(*entity));
+    }
+
+    assert!(world.check_consistency().is_ok());
+}
+
+static MANA_DROPS: AtomicUsize = AtomicUsize::new(0);
+
+fn drop_mana(_: *mut u8) {
+    MANA_DROPS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// A pointer to `value` for `add_component_raw`, which moves it out.
+fn raw<T>(value: &mut T) -> Ptr<'_> {
+    Ptr::new(NonNull::from(value).cast(), Layout::new::<T>(), 1)
+}
+
+#[test]
+fn dynamic_components_round_trip_through_raw_bytes() {
+    let mut world = World::new();
+    let layout = Layout::new::<[u16; 3]>();
+    let mana = world.register_dynamic("Mana", layout, Some(drop_mana));
+    assert_eq!(world.register_dynamic("Mana", layout, None), mana);
+    assert_eq!(world.components().dynamic_id("Mana"), Some(mana));
+    assert!(world.components().meta(mana).is_dynamic());
+
+    let entity = world.spawn().insert(A(1)).id();
+    let mut value = [7u16, 300, 65535];
+    unsafe { world.add_component_raw(entity, mana, raw(&mut value)) }.unwrap();
+
+    let bytes = |world: &World| {
+        let ptr = world.component_raw(entity, mana).unwrap();
+        unsafe { std::slice::from_raw_parts(ptr.as_ptr(), layout.size()) }.to_vec()
+    };
+    let expected = [7u16, 300, 65535]
+        .iter()
+        .flat_map(|value| value.to_ne_bytes())
+        .collect::<Vec<_>>();
+    assert_eq!(bytes(&world), expected);
+    assert_eq!(world.component::<A>(entity).as_deref(), Some(&A(1)));
+
+    // Adding again overwrites the bytes and drops the old value.
+    let mut value = [1u16, 2, 3];
+    unsafe { world.add_component_raw(entity, mana, raw(&mut value)) }.unwrap();
+    assert_eq!(bytes(&world)[..2], 1u16.to_ne_bytes());
+    assert_eq!(MANA_DROPS.load(Ordering::Relaxed), 1);
+
+    let mut wide = 0u64;
+    let mismatch = unsafe { world.add_component_raw(entity, mana, raw(&mut wide)) };
+    assert_eq!(
+        mismatch,
+        Err(WorldError::LayoutMismatch {
+            component: mana,
+            name: "Mana"
+        })
+    );
+    let unknown = ComponentId::new(mana.id() + 100);
+    let missing = unsafe { world.add_component_raw(entity, unknown, raw(&mut value)) };
+    assert_eq!(missing, Err(WorldError::UnknownComponent(unknown)));
+    assert!(world.component_raw(entity, unknown).is_none());
+
+    world.delete(entity);
+    assert_eq!(MANA_DROPS.load(Ordering::Relaxed), 2);
+    assert!(world.component_raw(entity, mana).is_none());
+}
+
+#[test]
+fn component_masks_hold_ids_past_the_inline_width() {
+    let mut world = World::new();
+    let tags = (0..200)
+        .map(|index| world.register_dynamic(&format!("tag_{index}"), Layout::new::<u8>(), None))
+        .collect::<Vec<_>>();
+
+    let tag = |id: ComponentId| {
+        let mut blob = Blob::new::<u8>();
+        blob.push(1u8);
+        (id, blob)
+    };
+    let high = world
+        .insert_entity_raw(
+            None,
+            vec![tag(tags[3]), tag(tags[70]), tag(tags[150]), tag(tags[199])],
+        )
+        .unwrap();
+    let low = world.insert_entity_raw(None, vec![tag(tags[3])]).unwrap();
+
+    // Past 64 ids the mask still fits inline; past 128 it is boxed.
+    let near = ComponentMask::from_ids(&[tags[3], tags[70]]);
+    let far = ComponentMask::from_ids(&[tags[150], tags[199]]);
+    assert!(near.is_inline());
+    assert!(!far.is_inline());
+
+    assert!(world.has_all(high, &far));
+    assert!(world.has_all(high, &near));
+    assert!(!world.has_all(low, &far));
+    assert!(!world.has_any(low, &far));
+    assert!(!world.has_all(low, &near));
+    assert!(world.has_any(low, &near));
+    assert!(!world.archetypes().has(low, tags[70]));
+    assert!(world.archetypes().has(high, tags[70]));
+    assert!(world.archetypes().has(high, tags[199]));
+    assert_masks_agree(&world, high, &[far.clone(), near.clone()]);
+    assert_masks_agree(&world, low, &[far, near]);
+}
+
+/// Types as an older version of a game declared them.
+mod v1 {
+    use ecs::prelude::*;
+
+    #[derive(Default)]
+    pub struct Health {
+        pub hp: i64,
+    }
+
+    impl Component for Health {
+        const STABLE_ID: Option<&'static str> = Some("stats.health");
+    }
+
+    ecs::impl_reflect!(Health { hp: i64 });
+
+    #[derive(Default)]
+    pub struct Mana {
+        pub points: i64,
+    }
+
+    impl Component for Mana {}
+
+    ecs::impl_reflect!(Mana { points: i64 });
+
+    #[derive(Default)]
+    pub struct Calendar {
+        pub day: i64,
+    }
+
+    impl Resource for Calendar {
+        const STABLE_ID: Option<&'static str> = Some("world.calendar");
+    }
+
+    ecs::impl_reflect!(Calendar { day: i64 });
+}
+
+/// The same types, renamed and moved.
+mod v2 {
+    use ecs::prelude::*;
+
+    #[derive(Default)]
+    pub struct Vitality {
+        pub hp: i64,
+    }
+
+    impl Component for Vitality {
+        const STABLE_ID: Option<&'static str> = Some("stats.health");
+    }
+
+    ecs::impl_reflect!(Vitality { hp: i64 });
+
+    #[derive(Default)]
+    pub struct Mana {
+        pub points: i64,
+    }
+
+    impl Component for Mana {}
+
+    ecs::impl_reflect!(Mana { points: i64 });
+
+    #[derive(Default)]
+    pub struct Almanac {
+        pub day: i64,
+    }
+
+    impl Resource for Almanac {
+        const STABLE_ID: Option<&'static str> = Some("world.calendar");
+    }
+
+    ecs::impl_reflect!(Almanac { day: i64 });
+}
+
+fn v1_snapshot() -> Snapshot {
+    let mut world = World::new();
+    world.add_resource(v1::Calendar { day: 12 });
+    world.spawn().insert(v1::Health { hp: 30 });
+    world
+        .spawn()
+        .insert(v1::Health { hp: 5 })
+        .insert(v1::Mana { points: 8 });
+
+    let mut registry = SnapshotRegistry::new();
+    registry
+        .register_component::<v1::Health>()
+        .register_component::<v1::Mana>()
+        .register_resource::<v1::Calendar>();
+    world.snapshot(&registry)
+}
+
+fn v2_registry() -> SnapshotRegistry {
+    let mut registry = SnapshotRegistry::new();
+    registry
+        .register_component::<v2::Vitality>()
+        .register_component::<v2::Mana>()
+        .register_resource::<v2::Almanac>();
+    registry
+}
+
+#[test]
+fn snapshots_load_into_renamed_types_by_stable_id_and_alias() {
+    let snapshot = v1_snapshot();
+    let mana = std::any::type_name::<v1::Mana>();
+    assert_eq!(
+        snapshot.warnings,
+        vec![format!(
+            "{mana} has no stable id, saved under its type name"
+        )]
+    );
+
+    let registry = v2_registry();
+    let mut world = World::new();
+    let mapper = SnapshotLoader::new(&registry)
+        .alias(mana, std::any::type_name::<v2::Mana>())
+        .load(&snapshot, &mut world)
+        .unwrap();
+
+    let [first, second] = [0, 1].map(|index| mapper.map(snapshot.entities[index].entity));
+    assert_eq!(world.component::<v2::Vitality>(first).unwrap().hp, 30);
+    assert!(world.component::<v2::Mana>(first).is_none());
+    assert_eq!(world.component::<v2::Vitality>(second).unwrap().hp, 5);
+    assert_eq!(world.component::<v2::Mana>(second).unwrap().points, 8);
+    assert_eq!(world.resource::<v2::Almanac>().day, 12);
+}
+
+#[test]
+fn unknown_snapshot_keys_suggest_the_nearest_registered_one() {
+    let snapshot = v1_snapshot();
+    let registry = v2_registry();
+    let mut world = World::new();
+
+    let error = SnapshotLoader::new(&registry)
+        .load(&snapshot, &mut world)
+        .unwrap_err();
+    let v2_mana = std::any::type_name::<v2::Mana>();
+    assert_eq!(
+        error,
+        SnapshotError::UnknownKey {
+            key: std::any::type_name::<v1::Mana>().to_string(),
+            suggestion: Some(v2_mana.to_string()),
+        }
+    );
+    assert!(error
+        .to_string()
+        .ends_with(&format!("did you mean {v2_mana}?")));
+    assert_eq!(world.entities().iter().count(), 0);
+    assert!(world.try_resource::<v2::Almanac>().is_none());
+}
+
+#[derive(Default)]
+struct Repaired {
+    deleted: Vec<Entity>,
+}
+
+impl Resource for Repaired {}
+
+fn repaired_deletion(entities: &[Entity], repaired: &mut Repaired) {
+    repaired.deleted.extend_from_slice(entities);
+}
+
+/// Entities of `corrupted_world`, one per kind of damage.
+struct Damage {
+    rowless: Entity,
+    orphan: Entity,
+    repeated: Entity,
+    parent: Entity,
+    ghost: Entity,
+    masked: Entity,
+}
+
+fn corrupted_world(drops: &Arc<AtomicUsize>) -> (World, Damage) {
+    let mut world = world();
+    world.register::<Tracked>();
+    world.add_resource(Repaired::default());
+    world.add_observers(Observers::<DeleteEntity>::new().add_system(repaired_deletion));
+
+    let rowless = world.spawn().insert(A(1)).insert(B(2)).id();
+    let orphan = world
+        .spawn()
+        .insert(Tracked {
+            index: 0,
+            drops: drops.clone(),
+        })
+        .id();
+    let repeated = world.spawn().insert(C(4)).insert(D(5)).id();
+    let parent = world.spawn().id();
+    let ghost = world.spawn().id();
+    world.delete(ghost);
+    let masked = world.spawn().insert(E(6)).id();
+
+    let archetype = *world.archetypes().archetype_id(repeated).unwrap();
+    let component = world.component_id::<C>();
+    for corruption in [
+        Corruption::MissingRow(rowless),
+        Corruption::OrphanRow(orphan),
+        Corruption::RepeatComponent {
+            archetype,
+            component,
+        },
+        Corruption::DeadChild {
+            parent,
+            child: ghost,
+        },
+        Corruption::ClearMask(masked),
+    ] {
+        corrupt(&mut world, corruption);
+    }
+
+    let damage = Damage {
+        rowless,
+        orphan,
+        repeated,
+        parent,
+        ghost,
+        masked,
+    };
+    (world, damage)
+}
+
+#[test]
+fn repair_fixes_every_kind_of_injected_corruption() {
+    let drops = Arc::new(AtomicUsize::new(0));
+    let (mut world, damage) = corrupted_world(&drops);
+    let errors = world.check_consistency().unwrap_err();
+    let found = |matches: fn(&ConsistencyError) -> bool| errors.iter().any(matches);
+    assert!(found(|e| matches!(e, ConsistencyError::MissingRow { .. })));
+    assert!(found(|e| matches!(e, ConsistencyError::OrphanRow { .. })));
+    assert!(found(|e| matches!(
+        e,
+        ConsistencyError::DuplicateComponent { .. }
+    )));
+    assert!(found(|e| matches!(e, ConsistencyError::DeadChild { .. })));
+    assert!(found(|e| matches!(
+        e,
+        ConsistencyError::MaskMismatch { .. }
+    )));
+
+    let outcome = world.repair(&errors, RepairPolicy::Apply);
+    assert!(outcome.unrepaired.is_empty());
+    assert!(outcome.deferred.is_empty());
+    assert_eq!(outcome.repairs.len(), errors.len());
+    assert!(world.check_consistency().is_ok());
+
+    assert!(!world.entities().contains(damage.rowless));
+    assert!(!world.entities().contains(damage.orphan));
+    assert_eq!(drops.load(Ordering::Relaxed), 1);
+    assert_eq!(
+        world.component::<C>(damage.repeated).as_deref(),
+        Some(&C(4))
+    );
+    assert_eq!(
+        world.component::<D>(damage.repeated).as_deref(),
+        Some(&D(5))
+    );
+    assert!(world.entities().children_of(damage.parent).is_empty());
+    assert!(!world.entities().contains(damage.ghost));
+    assert!(world.has::<E>(damage.masked));
+
+    let repairs = world
+        .traces()
+        .into_iter()
+        .filter(|entry| entry.source() == "repair" && entry.level() == TraceLevel::Warn)
+        .count();
+    assert_eq!(repairs, outcome.repairs.len());
+
+    world.run::<Update>();
+    assert_eq!(world.resource::<Repaired>().deleted, vec![damage.rowless]);
+}
+
+#[test]
+fn dry_run_repairs_change_nothing() {
+    let drops = Arc::new(AtomicUsize::new(0));
+    let (mut world, damage) = corrupted_world(&drops);
+    let errors = world.check_consistency().unwrap_err();
+
+    let planned = world.repair(&errors, RepairPolicy::DryRun);
+    assert_eq!(planned.repairs.len(), errors.len());
+    assert_eq!(world.check_consistency().unwrap_err(), errors);
+    assert!(world.entities().contains(damage.rowless));
+    assert_eq!(drops.load(Ordering::Relaxed), 0);
+    assert!(world
+        .traces()
+        .iter()
+        .all(|entry| entry.source() != "repair"));
+
+    world.run::<Update>();
+    assert!(world.resource::<Repaired>().deleted.is_empty());
+
+    let applied = world.repair(&errors, RepairPolicy::Apply);
+    assert_eq!(applied.repairs, planned.repairs);
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Team(u8);
+
+impl Component for Team {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Rank(u8);
+
+impl Component for Rank {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Zone(u8);
+
+impl Component for Zone {}
+
+/// Xorshift, so the sequence is the same on every run.
+struct Steps(u64);
+
+impl Steps {
+    fn next(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % bound as u64) as usize
+    }
+}
+
+type Model = HashMap<Entity, [Option<u8>; 3]>;
+
+fn insert_slot(world: &mut World, entity: Entity, slot: usize, value: u8) {
+    match slot {
+        0 => world.add_component(entity, Team(value)),
+        1 => world.add_component(entity, Rank(value)),
+        _ => world.add_component(entity, Zone(value)),
+    }
+}
+
+fn remove_slot(world: &mut World, entity: Entity, slot: usize) {
+    match slot {
+        0 => world.remove_component::<Team>(entity),
+        1 => world.remove_component::<Rank>(entity),
+        _ => world.remove_component::<Zone>(entity),
+    }
+}
+
+/// Compares the index of `C` against a scan of the world and the model.
+fn assert_index<C: IndexedComponent>(
+    world: &World,
+    model: &Model,
+    slot: usize,
+    value: impl Fn(&C) -> u8,
+    make: impl Fn(u8) -> C,
+) {
+    let mut scanned = Query::<(Entity, &C)>::new(world)
+        .map(|(entity, component)| (entity, value(&component)))
+        .collect::<Vec<_>>();
+    scanned.sort_by_key(|(entity, _)| entity.id());
+    let mut expected = model
+        .iter()
+        .filter_map(|(entity, slots)| Some((*entity, slots[slot]?)))
+        .collect::<Vec<_>>();
+    expected.sort_by_key(|(entity, _)| entity.id());
+    assert_eq!(scanned, expected);
+
+    let index = world.index::<C>().unwrap();
+    assert_eq!(index.len(), expected.len());
+    for v in 0..4 {
+        let mut found = world.find_by(&make(v)).to_vec();
+        found.sort_by_key(|entity| entity.id());
+        let matching = expected
+            .iter()
+            .filter(|(_, other)| *other == v)
+            .map(|(entity, _)| *entity)
+            .collect::<Vec<_>>();
+        assert_eq!(found, matching);
+    }
+}
+
+#[test]
+fn indexes_match_a_scan_across_random_changes() {
+    let mut world = World::new();
+    world.register_index::<Team>();
+    world.register_index::<Rank>();
+    world.register_index::<Zone>();
+
+    let mut steps = Steps(0x2545_f491_4f6c_dd1d);
+    let mut model = Model::new();
+    let mut live = Vec::new();
+    for _ in 0..2_000 {
+        match steps.next(if live.is_empty() { 1 } else { 4 }) {
+            0 => {
+                let entity = world.create();
+                let mut slots = [None; 3];
+                for (slot, held) in slots.iter_mut().enumerate() {
+                    if steps.next(2) == 0 {
+                        let value = steps.next(4) as u8;
+                        insert_slot(&mut world, entity, slot, value);
+                        *held = Some(value);
+                    }
+                }
+                model.insert(entity, slots);
+                live.push(entity);
+            }
+            1 => {
+                let entity = live[steps.next(live.len())];
+                let (slot, value) = (steps.next(3), steps.next(4) as u8);
+                insert_slot(&mut world, entity, slot, value);
+                model.get_mut(&entity).unwrap()[slot] = Some(value);
+            }
+            2 => {
+                let entity = live[steps.next(live.len())];
+                let slot = steps.next(3);
+                remove_slot(&mut world, entity, slot);
+                model.get_mut(&entity).unwrap()[slot] = None;
+            }
+            _ => {
+                let entity = live.swap_remove(steps.next(live.len()));
+                world.delete(entity);
+                model.remove(&entity);
+            }
+        }
+
+        assert_index::<Team>(&world, &model, 0, |team| team.0, Team);
+        assert_index::<Rank>(&world, &model, 1, |rank| rank.0, Rank);
+        assert_index::<Zone>(&world, &model, 2, |zone| zone.0, Zone);
+        assert!(world.check_consistency().is_ok());
+    }
+}
+
+#[cfg(feature = "strict-borrows")]
+fn borrow_panic(f: impl FnOnce()) -> String {
+    let panic = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).unwrap_err();
+    panic.downcast::<String>().map(|message| *message).unwrap()
+}
+
+#[cfg(feature = "strict-borrows")]
+#[test]
+fn strict_borrows_name_both_borrow_locations() {
+    let mut world = World::new();
+    world.register::<A>();
+    let entity = world.spawn().insert(A(1)).id();
+    let handle = world.component_handle::<A>();
+
+    let mut lines = (0, 0);
+    let message = borrow_panic(|| {
+        lines.0 = line!() + 1;
+        let held = world.component_mut::<A>(entity);
+        lines.1 = line!() + 1;
+        let _aliased = world.component_mut::<A>(entity);
+        drop(held);
+    });
+    assert!(message.starts_with("world::A of"), "{message}");
+    assert!(
+        message.contains(&format!("world.rs:{}", lines.0)),
+        "{message}"
+    );
+    assert!(
+        message.contains(&format!("world.rs:{}", lines.1)),
+        "{message}"
+    );
+
+    let message = borrow_panic(|| {
+        let held = world.get(handle, entity);
+        let _aliased = Query::<&mut A>::new(&world).next();
+        drop(held);
+    });
+    assert!(message.contains("accessed mutably"), "{message}");
+    assert!(
+        message.contains("while borrowed immutably at tests/world.rs"),
+        "{message}"
+    );
+
+    // Both guards are released once the values are dropped.
+    let held = world.component::<A>(entity);
+    assert_eq!(world.component::<A>(entity).as_deref(), held.as_deref());
+    drop(held);
+    world.component_mut::<A>(entity).unwrap().0 = 2;
+    assert_eq!(world.component::<A>(entity).as_deref(), Some(&A(2)));
+}